@@ -15,19 +15,18 @@
  * limitations under the License.
  */
 
-//! Lowest level stream communication as JSON.
-//! Because DAP debugging is hard, we write everything we see to stdout (for the protocol)
-//! AND stderr (for debugging).
-
 use std::env;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io;
-use std::io::Read;
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::Arc;
+
+use starlark::debug::DapServer;
+use starlark::debug::DebugRegistry;
 
-use serde_json::Value;
+use crate::eval::file_kind;
 
 // Debugging anything through DAP is a nightmare, because VS Code doesn't surface any logs.
 // Therefore, do the hacky thing of putting logs next to the binary.
@@ -37,35 +36,28 @@ fn log_file() -> PathBuf {
     res
 }
 
-pub(crate) fn log_begin() {
-    File::create(log_file()).unwrap();
-}
-
-pub(crate) fn log(x: &str) {
+fn log(x: &str) {
     let mut file = OpenOptions::new().append(true).open(log_file()).unwrap();
     file.write_all(format!("{}\n", x).as_bytes()).unwrap()
 }
 
-pub(crate) fn send(x: Value) {
-    let s = x.to_string();
-    log(&format!("SEND: {}", s));
-    print!("Content-Length: {}\r\n\r\n{}", s.len(), s);
-    io::stdout().flush().unwrap()
-}
-
-pub(crate) fn read() -> Value {
-    let mut s = String::new();
-    io::stdin().read_line(&mut s).unwrap();
-    let len: usize = s
-        .strip_prefix("Content-Length: ")
-        .unwrap()
-        .trim()
-        .parse()
-        .unwrap();
-    io::stdin().read_line(&mut s).unwrap();
-    let mut res = vec![0u8; len];
-    io::stdin().lock().read_exact(&mut res).unwrap();
-    let s = String::from_utf8_lossy(&res);
-    log(&format!("RECV: {}", s));
-    serde_json::from_str(&s).unwrap()
+pub(crate) fn server() -> anyhow::Result<()> {
+    File::create(log_file())?;
+
+    // Because of the eval we're running in, we probably can't see panics.
+    // So mirror them to the log file.
+    let orig_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        if let Some(s) = panic_info.payload().downcast_ref::<&str>() {
+            log(&format!("Panic occurred: {:?}", s));
+        } else {
+            log("Panic occurred: Unknown message");
+        }
+        orig_hook(panic_info);
+    }));
+
+    // Nothing else registers evaluations, so only `launch` is useful here.
+    let mut server = DapServer::new(DebugRegistry::new(), Arc::new(file_kind));
+    server.set_log(log);
+    server.run(io::stdin().lock(), io::stdout())
 }
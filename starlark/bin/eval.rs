@@ -21,26 +21,39 @@ use std::io;
 use std::iter;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::RwLock;
 
 use gazebo::prelude::*;
 use itertools::Either;
 use lsp_types::Diagnostic;
 use lsp_types::Url;
+use starlark::bench::run_benchmark;
+use starlark::bench::BenchProgram;
 use starlark::docs::get_registered_starlark_docs;
 use starlark::docs::render_docs_as_code;
 use starlark::docs::Doc;
 use starlark::docs::DocItem;
+use starlark::environment::FileKind;
+use starlark::environment::FileKindResolver;
 use starlark::environment::FrozenModule;
 use starlark::environment::Globals;
+use starlark::environment::GlobalsBuilder;
 use starlark::environment::Module;
 use starlark::errors::EvalMessage;
+use starlark::eval::EvalRecorder;
+use starlark::eval::EvalRecording;
 use starlark::eval::Evaluator;
+use starlark::eval::FsFileLoader;
+use starlark::eval::ReplayTrace;
 use starlark::lsp::server::LspContext;
 use starlark::lsp::server::LspEvalResult;
 use starlark::lsp::server::LspUrl;
+use starlark::lsp::server::LspWorkspaceRoot;
 use starlark::lsp::server::StringLiteralResult;
 use starlark::syntax::AstModule;
+use starlark::syntax::Codemod;
 use starlark::syntax::Dialect;
+use walkdir::WalkDir;
 
 #[derive(Debug)]
 pub(crate) enum ContextMode {
@@ -58,14 +71,57 @@ enum ContextError {
     WrongScheme(String, LspUrl),
 }
 
-#[derive(Debug)]
 pub(crate) struct Context {
     pub(crate) mode: ContextMode,
+    /// Dialect and globals for each file.
+    pub(crate) file_kinds: Box<dyn FileKindResolver>,
     pub(crate) print_non_none: bool,
     pub(crate) prelude: Vec<FrozenModule>,
     pub(crate) module: Option<Module>,
     pub(crate) builtin_docs: HashMap<LspUrl, String>,
     pub(crate) builtin_symbols: HashMap<String, LspUrl>,
+    /// Workspace roots opened in the LSP client.
+    pub(crate) roots: RwLock<Vec<LspWorkspaceRoot>>,
+    /// Settings of the workspace roots, by root name.
+    pub(crate) root_settings: RwLock<HashMap<String, RootSettings>>,
+    /// Records the evaluated files, for `--record`.
+    pub(crate) recorder: Option<EvalRecorder>,
+}
+
+/// Dialect used in a workspace root.
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DialectSetting {
+    Standard,
+    Extended,
+    Build,
+}
+
+/// Globals available in a workspace root.
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum GlobalsSetting {
+    Standard,
+    Extended,
+}
+
+/// Settings of a workspace root, sent by the LSP client with `workspace/didChangeConfiguration`
+/// as `{"starlark": {"roots": {"<root name>": {...}}}}`, for example
+/// `{"dialect": "build", "globals": "standard", "lint": false}`.
+/// Unset fields keep the defaults for the file.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct RootSettings {
+    dialect: Option<DialectSetting>,
+    globals: Option<GlobalsSetting>,
+    lint: Option<bool>,
+}
+
+/// The `starlark` section of the client settings, other settings are used by the client only.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct Settings {
+    roots: HashMap<String, RootSettings>,
 }
 
 /// The outcome of evaluating (checking, parsing or running) given starlark code.
@@ -92,16 +148,17 @@ enum ResolveLoadError {
 impl Context {
     pub(crate) fn new(
         mode: ContextMode,
+        file_kinds: Box<dyn FileKindResolver>,
         print_non_none: bool,
         prelude: &[PathBuf],
         module: bool,
     ) -> anyhow::Result<Self> {
-        let globals = globals();
         let prelude = prelude.try_map(|x| {
             let env = Module::new();
 
             let mut eval = Evaluator::new(&env);
-            let module = AstModule::parse_file(x, &dialect())?;
+            let FileKind { dialect, globals } = file_kinds.resolve(x);
+            let module = AstModule::parse_file(x, &dialect)?;
             eval.eval_module(module, &globals)?;
             env.freeze()
         })?;
@@ -125,14 +182,53 @@ impl Context {
 
         Ok(Self {
             mode,
+            file_kinds,
             print_non_none,
             prelude,
             module,
             builtin_docs,
             builtin_symbols,
+            roots: RwLock::default(),
+            root_settings: RwLock::default(),
+            recorder: None,
         })
     }
 
+    /// Settings of the workspace root containing `path`.
+    fn root_settings(&self, path: &Path) -> RootSettings {
+        let roots = self.roots.read().unwrap();
+        match LspWorkspaceRoot::find(&roots, &LspUrl::File(path.to_owned())) {
+            Some(root) => self
+                .root_settings
+                .read()
+                .unwrap()
+                .get(&root.name)
+                .cloned()
+                .unwrap_or_default(),
+            None => RootSettings::default(),
+        }
+    }
+
+    /// Dialect and globals for `path`, with the settings of its workspace root applied.
+    fn file_kind(&self, path: &Path) -> FileKind {
+        let mut file_kind = self.file_kinds.resolve(path);
+        let settings = self.root_settings(path);
+        if let Some(dialect) = settings.dialect {
+            file_kind.dialect = match dialect {
+                DialectSetting::Standard => Dialect::Standard,
+                DialectSetting::Extended => Dialect::Extended,
+                DialectSetting::Build => Dialect::Build,
+            };
+        }
+        if let Some(globals) = settings.globals {
+            file_kind.globals = match globals {
+                GlobalsSetting::Standard => Globals::standard(),
+                GlobalsSetting::Extended => Globals::extended(),
+            };
+        }
+        file_kind
+    }
+
     fn url_for_doc(doc: &Doc) -> LspUrl {
         let url = match &doc.item {
             DocItem::Module(_) => Url::parse("starlark:/native/builtins.bzl").unwrap(),
@@ -157,7 +253,7 @@ impl Context {
         let mut errors = Either::Left(iter::empty());
         let final_ast = match self.mode {
             ContextMode::Check => {
-                warnings = Either::Right(self.check(&ast));
+                warnings = Either::Right(self.check(file, &ast));
                 Some(ast)
             }
             ContextMode::Run => {
@@ -193,9 +289,10 @@ impl Context {
         content: String,
     ) -> EvalResult<impl Iterator<Item = EvalMessage>> {
         let file = "expression";
+        let dialect = self.file_kind(Path::new(file)).dialect;
         Self::err(
             file,
-            AstModule::parse(file, content, &dialect()).map(|module| self.go(file, module)),
+            AstModule::parse(file, content, &dialect).map(|module| self.go(file, module)),
         )
     }
 
@@ -214,9 +311,10 @@ impl Context {
         filename: &str,
         content: String,
     ) -> EvalResult<impl Iterator<Item = EvalMessage>> {
+        let dialect = self.file_kind(Path::new(filename)).dialect;
         Self::err(
             filename,
-            AstModule::parse(filename, content, &dialect()).map(|module| self.go(filename, module)),
+            AstModule::parse(filename, content, &dialect).map(|module| self.go(filename, module)),
         )
     }
 
@@ -229,9 +327,17 @@ impl Context {
                 &new_module
             }
         };
+        let path = Path::new(file);
+        let file_kind = |path: &Path| self.file_kind(path);
+        let mut loader = FsFileLoader::new(path.parent().unwrap_or(path), &file_kind);
         let mut eval = Evaluator::new(module);
+        if let Some(recorder) = &self.recorder {
+            loader.set_recorder(recorder);
+            eval.set_recorder(recorder);
+        }
         eval.enable_terminal_breakpoint_console();
-        let globals = globals();
+        eval.set_loader(&loader);
+        let globals = self.file_kind(path).globals;
         Self::err(
             file,
             eval.eval_module(ast, &globals).map(|v| {
@@ -246,7 +352,8 @@ impl Context {
         )
     }
 
-    fn check(&self, module: &AstModule) -> impl Iterator<Item = EvalMessage> {
+    fn check(&self, file: &str, module: &AstModule) -> impl Iterator<Item = EvalMessage> {
+
         let mut globals = Vec::new();
         for x in &self.prelude {
             globals.extend(x.names().map(|s| s.as_str()));
@@ -257,7 +364,12 @@ impl Context {
             Some(globals.as_slice())
         };
 
-        module.lint(globals).into_iter().map(EvalMessage::from)
+        let lints = if self.root_settings(Path::new(file)).lint == Some(false) {
+            Vec::new()
+        } else {
+            module.lint(globals)
+        };
+        lints.into_iter().map(EvalMessage::from)
     }
 }
 
@@ -277,6 +389,14 @@ impl LspContext for Context {
     }
 
     fn resolve_load(&self, path: &str, current_file: &LspUrl) -> anyhow::Result<LspUrl> {
+        // Paths starting with `//` are relative to the workspace root of the current file.
+        if let Some(path) = path.strip_prefix("//") {
+            let roots = self.roots.read().unwrap();
+            if let Some(root) = LspWorkspaceRoot::find(&roots, current_file) {
+                let absolute_path = root.url.path().join(path);
+                return Ok(Url::from_file_path(absolute_path).unwrap().try_into()?);
+            }
+        }
         let path = PathBuf::from(path);
         match current_file {
             LspUrl::File(current_file_path) => {
@@ -329,12 +449,157 @@ impl LspContext for Context {
     ) -> anyhow::Result<Option<LspUrl>> {
         Ok(self.builtin_symbols.get(symbol).cloned())
     }
+
+    fn set_workspace_roots(&self, roots: &[LspWorkspaceRoot]) {
+        *self.roots.write().unwrap() = roots.to_vec();
+    }
+
+    fn set_configuration(&self, settings: &serde_json::Value) -> anyhow::Result<()> {
+        let settings: Settings = match settings.get("starlark") {
+            Some(settings) => serde_json::from_value(settings.clone())?,
+            None => Settings::default(),
+        };
+        *self.root_settings.write().unwrap() = settings.roots;
+        Ok(())
+    }
+
+    fn workspace_files(&self, roots: &[LspWorkspaceRoot]) -> anyhow::Result<Vec<LspUrl>> {
+        let mut files = Vec::new();
+        for root in roots {
+            for entry in WalkDir::new(root.url.path()) {
+                let entry = entry?;
+                if entry.file_type().is_file() && is_starlark_file(entry.path()) {
+                    files.push(LspUrl::File(entry.into_path()));
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Files analyzed in the background by the LSP.
+fn is_starlark_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|x| x.to_str()),
+        Some("star" | "bzl" | "bxl")
+    ) || is_build_file(path)
+}
+
+fn is_build_file(path: &Path) -> bool {
+    matches!(
+        path.file_name().and_then(|x| x.to_str()),
+        Some("BUILD" | "BUILD.bazel" | "BUCK")
+    )
 }
 
-pub(crate) fn globals() -> Globals {
-    Globals::extended()
+/// Write the files evaluated with `recorder` to `path` as JSON.
+pub(crate) fn write_recording(recorder: EvalRecorder, path: &Path) -> anyhow::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(&recorder.finish())?)?;
+    Ok(())
 }
 
-pub(crate) fn dialect() -> Dialect {
-    Dialect::Extended
+/// Evaluate the files recorded by `--record` in `path` again.
+/// With `check_determinism`, `save_trace` or `compare_trace`, trace the replay
+/// and report where it diverges from another replay or from the saved trace.
+pub(crate) fn replay(
+    path: &Path,
+    check_determinism: bool,
+    save_trace: Option<&Path>,
+    compare_trace: Option<&Path>,
+) -> anyhow::Result<()> {
+    let recording: EvalRecording = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if !check_determinism && save_trace.is_none() && compare_trace.is_none() {
+        recording.replay(GlobalsBuilder::extended)?;
+        return Ok(());
+    }
+
+    let trace = recording.replay_trace(GlobalsBuilder::extended);
+    if let Some(path) = save_trace {
+        fs::write(path, serde_json::to_string_pretty(&trace)?)?;
+    }
+    let mut divergences = Vec::new();
+    if check_determinism {
+        let again = recording.replay_trace(GlobalsBuilder::extended);
+        divergences.extend(trace.first_divergence(&again));
+    }
+    if let Some(path) = compare_trace {
+        let saved: ReplayTrace = serde_json::from_str(&fs::read_to_string(path)?)?;
+        divergences.extend(saved.first_divergence(&trace));
+    }
+    for x in &divergences {
+        eprintln!("{}", x);
+    }
+    if divergences.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Replay diverged"))
+    }
+}
+
+/// Rewrite `files` in place with `codemods`.
+/// Files which fail to migrate are reported, and the others are still migrated.
+pub(crate) fn migrate(
+    files: impl Iterator<Item = PathBuf>,
+    codemods: &[&dyn Codemod],
+) -> anyhow::Result<()> {
+    fn migrate_file(file: &Path, codemods: &[&dyn Codemod]) -> anyhow::Result<bool> {
+        let source = fs::read_to_string(file)?;
+        let dialect = file_kind(file).dialect;
+        let ast = AstModule::parse(&file.to_string_lossy(), source.clone(), &dialect)?;
+        let edited = ast.migrate(codemods)?;
+        if edited == source {
+            return Ok(false);
+        }
+        fs::write(file, edited)?;
+        Ok(true)
+    }
+
+    let (mut migrated, mut failed) = (0, 0);
+    for file in files {
+        match migrate_file(&file, codemods) {
+            Ok(changed) => migrated += changed as usize,
+            Err(e) => {
+                eprintln!("{:#}", e);
+                failed += 1;
+            }
+        }
+    }
+    println!("{} files migrated, {} failed", migrated, failed);
+    if failed > 0 {
+        return Err(anyhow::anyhow!("Failed to migrate {} files", failed));
+    }
+    Ok(())
+}
+
+/// Benchmark the evaluation of each file, printing the results as one JSON line per file.
+pub(crate) fn bench(
+    files: impl Iterator<Item = PathBuf>,
+    warmup: usize,
+    iterations: usize,
+) -> anyhow::Result<()> {
+    for file in files {
+        let source = fs::read_to_string(&file)?;
+        let mut program = BenchProgram::new(&file.to_string_lossy(), &source);
+        let kind = file_kind(&file);
+        program.dialect = kind.dialect;
+        program.globals = kind.globals;
+        program.warmup = warmup;
+        program.iterations = iterations;
+        let result = run_benchmark(&program)?;
+        println!("{}", serde_json::to_string(&result)?);
+    }
+    Ok(())
+}
+
+/// `BUILD` files use the BUILD dialect, other files are extended Starlark.
+pub(crate) fn file_kind(path: &Path) -> FileKind {
+    let dialect = if is_build_file(path) {
+        Dialect::Build
+    } else {
+        Dialect::Extended
+    };
+    FileKind {
+        dialect,
+        globals: Globals::extended(),
+    }
 }
@@ -27,6 +27,7 @@ use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fmt;
 use std::fmt::Display;
+use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -45,8 +46,13 @@ use starlark::docs::RenderMarkdown;
 use starlark::environment::Globals;
 use starlark::errors::EvalMessage;
 use starlark::errors::EvalSeverity;
+use starlark::eval::EvalRecorder;
 use starlark::lsp;
 use starlark::read_line::ReadLine;
+use starlark::syntax::Codemod;
+use starlark::syntax::DictUnion;
+use starlark::syntax::LoadRenames;
+use starlark::syntax::PercentFormat;
 use walkdir::WalkDir;
 
 use crate::eval::ContextMode;
@@ -138,6 +144,93 @@ struct Args {
         conflicts_with_all = &["lsp", "dap"],
     )]
     files: Vec<PathBuf>,
+
+    #[arg(
+        long = "record",
+        value_name = "RECORDING",
+        help = "Record the evaluated files into a JSON file, to evaluate them elsewhere.",
+        conflicts_with_all = &["lsp", "dap", "check", "prelude", "evaluate", "replay"],
+    )]
+    record: Option<PathBuf>,
+
+    #[arg(
+        long = "replay",
+        value_name = "RECORDING",
+        help = "Evaluate the files recorded by --record.",
+        conflicts_with_all = &["lsp", "dap", "check", "prelude", "evaluate", "files"],
+    )]
+    replay: Option<PathBuf>,
+
+    #[arg(
+        long = "check-determinism",
+        help = "With --replay, replay twice and report the first difference.",
+        requires = "replay"
+    )]
+    check_determinism: bool,
+
+    #[arg(
+        long = "save-trace",
+        value_name = "TRACE",
+        help = "With --replay, write the executed statements and the values they produced.",
+        requires = "replay"
+    )]
+    save_trace: Option<PathBuf>,
+
+    #[arg(
+        long = "compare-trace",
+        value_name = "TRACE",
+        help = "With --replay, report the first difference with a trace written by --save-trace.",
+        requires = "replay"
+    )]
+    compare_trace: Option<PathBuf>,
+
+    #[arg(
+        long = "migrate",
+        value_name = "CODEMOD",
+        help = "Rewrite the files in place with codemods.",
+        num_args = 1..,
+        conflicts_with_all = &["lsp", "dap", "check", "evaluate", "record", "replay"],
+    )]
+    migrate: Vec<ArgsCodemod>,
+
+    #[arg(
+        long = "load-renames",
+        value_name = "MAPPING",
+        help = "JSON object mapping old load() paths to new ones, for --migrate load-renames."
+    )]
+    load_renames: Option<PathBuf>,
+
+    #[arg(
+        long = "bench",
+        help = "Benchmark the files, printing the timings and heap usage as JSON lines.",
+        conflicts_with_all = &["lsp", "dap", "check", "evaluate", "record", "replay", "migrate"],
+    )]
+    bench: bool,
+
+    #[arg(
+        long = "bench-warmup",
+        value_name = "N",
+        help = "With --bench, evaluations of each file before measuring.",
+        default_value_t = 1,
+        requires = "bench"
+    )]
+    bench_warmup: usize,
+
+    #[arg(
+        long = "bench-iterations",
+        value_name = "N",
+        help = "With --bench, measured evaluations of each file.",
+        default_value_t = 10,
+        requires = "bench"
+    )]
+    bench_iterations: usize,
+}
+
+#[derive(ValueEnum, Copy, Clone, Dupe, Debug, PartialEq, Eq)]
+enum ArgsCodemod {
+    PercentFormat,
+    DictUnion,
+    LoadRenames,
 }
 
 #[derive(ValueEnum, Copy, Clone, Dupe, Debug, PartialEq, Eq)]
@@ -238,7 +331,14 @@ fn main() -> anyhow::Result<()> {
     let args = argfile::expand_args(argfile::parse_fromfile, argfile::PREFIX)?;
     let args: Args = Args::parse_from(args);
     if args.dap {
-        dap::server();
+        dap::server()?;
+    } else if let Some(recording) = &args.replay {
+        eval::replay(
+            recording,
+            args.check_determinism,
+            args.save_trace.as_deref(),
+            args.compare_trace.as_deref(),
+        )?;
     } else {
         let is_interactive = args.evaluate.is_empty() && args.files.is_empty();
 
@@ -246,17 +346,50 @@ fn main() -> anyhow::Result<()> {
             .extension
             .as_ref()
             .map_or("bzl", |x| x.strip_prefix('.').unwrap_or(x.as_str()));
+        if !args.migrate.is_empty() {
+            let load_renames = match &args.load_renames {
+                Some(path) => LoadRenames::from_json(&fs::read_to_string(path)?)?,
+                None => LoadRenames::default(),
+            };
+            let codemods: Vec<&dyn Codemod> = args
+                .migrate
+                .iter()
+                .map(|x| -> &dyn Codemod {
+                    match x {
+                        ArgsCodemod::PercentFormat => &PercentFormat {
+                            single_values: false,
+                        },
+                        ArgsCodemod::DictUnion => &DictUnion,
+                        ArgsCodemod::LoadRenames => &load_renames,
+                    }
+                })
+                .collect();
+            return eval::migrate(expand_dirs(ext, args.files), &codemods);
+        }
+        if args.bench {
+            return eval::bench(
+                expand_dirs(ext, args.files),
+                args.bench_warmup,
+                args.bench_iterations,
+            );
+        }
+
         let mut ctx = Context::new(
             if args.check {
                 ContextMode::Check
             } else {
                 ContextMode::Run
             },
+            Box::new(eval::file_kind),
             !args.evaluate.is_empty() || is_interactive,
             &expand_dirs(ext, args.prelude).collect::<Vec<_>>(),
             is_interactive,
         )?;
 
+        if args.record.is_some() {
+            ctx.recorder = Some(EvalRecorder::new());
+        }
+
         if args.lsp {
             ctx.mode = ContextMode::Check;
             lsp::server::stdio_server(ctx)?;
@@ -299,6 +432,10 @@ fn main() -> anyhow::Result<()> {
                 drain(ctx.file(&file).messages, args.json, &mut stats);
             }
 
+            if let (Some(path), Some(recorder)) = (&args.record, ctx.recorder.take()) {
+                eval::write_recording(recorder, path)?;
+            }
+
             if !args.json {
                 println!("{}", stats);
                 if stats.error > 0 {
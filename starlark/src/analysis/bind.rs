@@ -255,7 +255,8 @@ fn stmt(x: &AstStmt, res: &mut Vec<Bind>) {
             }
         }
         Stmt::Break | Stmt::Continue | Stmt::Return(None) => flow(res),
-        Stmt::Pass => {}
+        Stmt::Pass | Stmt::Yield(None) => {}
+        Stmt::Yield(Some(x)) => expr(x, res),
         Stmt::Return(Some(x)) => {
             expr(x, res);
             flow(res)
@@ -314,6 +315,12 @@ fn stmt(x: &AstStmt, res: &mut Vec<Bind>) {
             stmt(body, res);
             flow(res)
         }
+        Stmt::While(cond, body) => {
+            flow(res);
+            expr(cond, res);
+            stmt(body, res);
+            flow(res)
+        }
         Stmt::Load(load) => {
             for x in &load.args {
                 res.push(Bind::Set(
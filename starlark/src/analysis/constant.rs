@@ -0,0 +1,787 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Constant propagation over the AST with a simple abstract domain:
+//! finds conditions which are always true or false, indexes which are always out of bounds,
+//! and divisions by zero.
+//!
+//! A variable has a known value only if it is the same on all paths reaching the use:
+//! values are merged after branches, and variables assigned in loops are unknown.
+//! Only immutable values (numbers, strings, tuples of them) are tracked.
+//! Module variables are unknown inside functions, because functions can be called at any time.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use gazebo::variants::VariantName;
+use thiserror::Error;
+
+use crate::analysis::types::LintT;
+use crate::analysis::types::LintWarning;
+use crate::codemap::CodeMap;
+use crate::codemap::Span;
+use crate::syntax::ast::Assign;
+use crate::syntax::ast::AssignOp;
+use crate::syntax::ast::AstAssign;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::BinOp;
+use crate::syntax::ast::Clause;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::ForClause;
+use crate::syntax::ast::Stmt;
+use crate::syntax::lexer::TokenInt;
+use crate::syntax::uniplate::Visit;
+use crate::syntax::AstModule;
+
+#[derive(Error, Debug, VariantName)]
+pub(crate) enum ConstantIssue {
+    #[error("Condition `{0}` is always `{1}`")]
+    ConstantCondition(String, &'static str),
+    #[error("Index {1} is out of bounds in `{0}`, the length is {2}")]
+    IndexOutOfBounds(String, i64, usize),
+    #[error("Division by zero in `{0}`")]
+    DivisionByZero(String),
+}
+
+impl LintWarning for ConstantIssue {
+    fn is_serious(&self) -> bool {
+        true
+    }
+}
+
+/// Abstract value of an expression.
+#[derive(Debug, Clone, PartialEq)]
+enum Abstract {
+    /// Any value.
+    Unknown,
+    None,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Tuple(Vec<Abstract>),
+}
+
+impl Abstract {
+    fn truth(&self) -> Option<bool> {
+        match self {
+            Abstract::Unknown => None,
+            Abstract::None => Some(false),
+            Abstract::Bool(x) => Some(*x),
+            Abstract::Int(x) => Some(*x != 0),
+            Abstract::Float(x) => Some(*x != 0.0),
+            Abstract::String(x) => Some(!x.is_empty()),
+            Abstract::Tuple(xs) => Some(!xs.is_empty()),
+        }
+    }
+
+    fn len(&self) -> Option<usize> {
+        match self {
+            Abstract::String(x) => Some(x.chars().count()),
+            Abstract::Tuple(xs) => Some(xs.len()),
+            _ => None,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Abstract::Int(x) => *x == 0,
+            Abstract::Float(x) => *x == 0.0,
+            _ => false,
+        }
+    }
+
+    /// `x == y`, `None` if unknown.
+    fn equals(&self, other: &Abstract) -> Option<bool> {
+        match (self, other) {
+            (Abstract::Unknown, _) | (_, Abstract::Unknown) => None,
+            (Abstract::Int(x), Abstract::Float(y)) | (Abstract::Float(y), Abstract::Int(x)) => {
+                Some(*x as f64 == *y)
+            }
+            (Abstract::Tuple(xs), Abstract::Tuple(ys)) => {
+                if xs.len() != ys.len() {
+                    return Some(false);
+                }
+                let mut res = Some(true);
+                for (x, y) in xs.iter().zip(ys) {
+                    match x.equals(y)? {
+                        true => {}
+                        false => res = Some(false),
+                    }
+                }
+                res
+            }
+            (x, y) if std::mem::discriminant(x) == std::mem::discriminant(y) => Some(x == y),
+            _ => Some(false),
+        }
+    }
+
+    /// `x < y`, `None` if unknown.
+    fn less(&self, other: &Abstract) -> Option<bool> {
+        match (self, other) {
+            (Abstract::Int(x), Abstract::Int(y)) => Some(x < y),
+            (Abstract::String(x), Abstract::String(y)) => Some(x < y),
+            _ => None,
+        }
+    }
+
+    /// `x in self`, `None` if unknown.
+    fn contains(&self, x: &Abstract) -> Option<bool> {
+        match (self, x) {
+            (Abstract::String(s), Abstract::String(x)) => Some(s.contains(x.as_str())),
+            (Abstract::Tuple(xs), x) => {
+                let mut res = Some(false);
+                for y in xs {
+                    match y.equals(x) {
+                        Some(true) => return Some(true),
+                        Some(false) => {}
+                        None => res = None,
+                    }
+                }
+                res
+            }
+            _ => None,
+        }
+    }
+
+    fn bool_or_unknown(x: Option<bool>) -> Abstract {
+        match x {
+            Some(x) => Abstract::Bool(x),
+            None => Abstract::Unknown,
+        }
+    }
+
+    fn int_or_unknown(x: Option<i64>) -> Abstract {
+        match x {
+            Some(x) => Abstract::Int(x),
+            None => Abstract::Unknown,
+        }
+    }
+}
+
+/// Known values of the variables in scope.
+#[derive(Clone, Default)]
+struct Env {
+    vars: HashMap<String, Abstract>,
+}
+
+impl Env {
+    /// Merge the values from another path.
+    fn join(&mut self, other: &Env) {
+        for (name, value) in &mut self.vars {
+            if other.vars.get(name) != Some(value) {
+                *value = Abstract::Unknown;
+            }
+        }
+        for name in other.vars.keys() {
+            if !self.vars.contains_key(name) {
+                self.vars.insert(name.clone(), Abstract::Unknown);
+            }
+        }
+    }
+
+    /// Forget the values of the names.
+    fn forget<'a>(&mut self, names: impl IntoIterator<Item = &'a String>) {
+        for name in names {
+            self.vars.insert(name.clone(), Abstract::Unknown);
+        }
+    }
+}
+
+/// Visit the names bound anywhere in a statement, including nested functions.
+/// A name is visited once for each binding.
+pub(crate) fn visit_assigned_names(stmt: &AstStmt, f: &mut impl FnMut(&str)) {
+    match &stmt.node {
+        Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) | Stmt::For(lhs, _) => {
+            lhs.visit_lvalue(|x| f(&x.node.0))
+        }
+        Stmt::Def(def) => {
+            f(&def.name.node.0);
+            for p in &def.params {
+                if let (Some(name), _, _) = p.split() {
+                    f(&name.node.0);
+                }
+            }
+        }
+        Stmt::Load(load) => {
+            for (name, _) in &load.args {
+                f(&name.node.0);
+            }
+        }
+        _ => {}
+    }
+    stmt.visit_children(|x| {
+        if let Visit::Stmt(x) = x {
+            visit_assigned_names(x, f);
+        }
+    });
+}
+
+/// Names assigned anywhere in a statement, including nested functions.
+fn assigned_names(stmt: &AstStmt, names: &mut HashSet<String>) {
+    visit_assigned_names(stmt, &mut |name| {
+        names.insert(name.to_owned());
+    });
+}
+
+struct Analysis<'a> {
+    codemap: &'a CodeMap,
+    /// Names assigned anywhere in the module, these are unknown unless in [`Env`].
+    assigned: HashSet<String>,
+    res: Vec<LintT<ConstantIssue>>,
+}
+
+impl Analysis<'_> {
+    fn report(&mut self, span: Span, issue: ConstantIssue) {
+        self.res.push(LintT::new(self.codemap, span, issue));
+    }
+
+    fn is_builtin(&self, env: &Env, name: &str) -> bool {
+        !env.vars.contains_key(name) && !self.assigned.contains(name)
+    }
+
+    fn lookup(&self, env: &Env, name: &str) -> Abstract {
+        if let Some(x) = env.vars.get(name) {
+            return x.clone();
+        }
+        if self.assigned.contains(name) {
+            return Abstract::Unknown;
+        }
+        match name {
+            "True" => Abstract::Bool(true),
+            "False" => Abstract::Bool(false),
+            "None" => Abstract::None,
+            _ => Abstract::Unknown,
+        }
+    }
+
+    /// Evaluate a condition, reporting it if it is constant.
+    ///
+    /// Literals and variables like `if False:` or `while True:` are not reported,
+    /// because they are usually intentional.
+    fn condition(&mut self, env: &mut Env, cond: &AstExpr) -> Option<bool> {
+        fn is_trivial(x: &AstExpr) -> bool {
+            match &x.node {
+                Expr::Literal(_) | Expr::Identifier(..) => true,
+                Expr::Not(x) => is_trivial(x),
+                _ => false,
+            }
+        }
+
+        let truth = self.expr(env, cond).truth();
+        if let Some(truth) = truth {
+            if !is_trivial(cond) {
+                let truth_str = if truth { "True" } else { "False" };
+                self.report(
+                    cond.span,
+                    ConstantIssue::ConstantCondition(cond.to_string(), truth_str),
+                );
+            }
+        }
+        truth
+    }
+
+    fn binop(
+        &mut self,
+        op: BinOp,
+        l: Abstract,
+        r: Abstract,
+        span: Span,
+        text: impl FnOnce() -> String,
+    ) -> Abstract {
+        let division = matches!(op, BinOp::Divide | BinOp::FloorDivide)
+            || (op == BinOp::Percent && matches!(l, Abstract::Int(_) | Abstract::Float(_)));
+        if division && r.is_zero() {
+            self.report(span, ConstantIssue::DivisionByZero(text()));
+            return Abstract::Unknown;
+        }
+        match (op, &l, &r) {
+            (BinOp::Equal, ..) => Abstract::bool_or_unknown(l.equals(&r)),
+            (BinOp::NotEqual, ..) => Abstract::bool_or_unknown(l.equals(&r).map(|x| !x)),
+            (BinOp::Less, ..) => Abstract::bool_or_unknown(l.less(&r)),
+            (BinOp::Greater, ..) => Abstract::bool_or_unknown(r.less(&l)),
+            (BinOp::LessOrEqual, ..) => Abstract::bool_or_unknown(r.less(&l).map(|x| !x)),
+            (BinOp::GreaterOrEqual, ..) => Abstract::bool_or_unknown(l.less(&r).map(|x| !x)),
+            (BinOp::In, ..) => Abstract::bool_or_unknown(r.contains(&l)),
+            (BinOp::NotIn, ..) => Abstract::bool_or_unknown(r.contains(&l).map(|x| !x)),
+            (BinOp::Add, Abstract::Int(x), Abstract::Int(y)) => {
+                Abstract::int_or_unknown(x.checked_add(*y))
+            }
+            (BinOp::Add, Abstract::String(x), Abstract::String(y)) => {
+                Abstract::String(format!("{}{}", x, y))
+            }
+            (BinOp::Add, Abstract::Tuple(xs), Abstract::Tuple(ys)) => {
+                Abstract::Tuple(xs.iter().chain(ys).cloned().collect())
+            }
+            (BinOp::Subtract, Abstract::Int(x), Abstract::Int(y)) => {
+                Abstract::int_or_unknown(x.checked_sub(*y))
+            }
+            (BinOp::Multiply, Abstract::Int(x), Abstract::Int(y)) => {
+                Abstract::int_or_unknown(x.checked_mul(*y))
+            }
+            (BinOp::FloorDivide, Abstract::Int(x), Abstract::Int(y)) => {
+                Abstract::int_or_unknown(x.checked_div(*y).map(|q| {
+                    if x % y != 0 && (*x < 0) != (*y < 0) {
+                        q - 1
+                    } else {
+                        q
+                    }
+                }))
+            }
+            (BinOp::Percent, Abstract::Int(x), Abstract::Int(y)) => {
+                Abstract::int_or_unknown(x.checked_rem(*y).map(|r| {
+                    if r != 0 && (r < 0) != (*y < 0) {
+                        r + y
+                    } else {
+                        r
+                    }
+                }))
+            }
+            (BinOp::BitAnd, Abstract::Int(x), Abstract::Int(y)) => Abstract::Int(x & y),
+            (BinOp::BitOr, Abstract::Int(x), Abstract::Int(y)) => Abstract::Int(x | y),
+            (BinOp::BitXor, Abstract::Int(x), Abstract::Int(y)) => Abstract::Int(x ^ y),
+            _ => Abstract::Unknown,
+        }
+    }
+
+    fn index(&mut self, x: &AstExpr, a: Abstract, i: Abstract) -> Abstract {
+        let (len, i) = match (a.len(), &i) {
+            (Some(len), Abstract::Int(i)) => (len, *i),
+            _ => return Abstract::Unknown,
+        };
+        let index = if i < 0 { i + len as i64 } else { i };
+        if index < 0 || index >= len as i64 {
+            self.report(
+                x.span,
+                ConstantIssue::IndexOutOfBounds(x.to_string(), i, len),
+            );
+            return Abstract::Unknown;
+        }
+        match a {
+            Abstract::Tuple(mut xs) => xs.swap_remove(index as usize),
+            Abstract::String(s) => match s.chars().nth(index as usize) {
+                Some(c) => Abstract::String(c.to_string()),
+                None => Abstract::Unknown,
+            },
+            _ => Abstract::Unknown,
+        }
+    }
+
+    /// Evaluate comprehension clauses and the results in the scope of the comprehension.
+    fn comprehension(
+        &mut self,
+        env: &Env,
+        for_: &ForClause,
+        clauses: &[Clause],
+        results: &[&AstExpr],
+    ) {
+        let mut env = env.clone();
+        self.expr(&mut env, &for_.over);
+        let bind = |env: &mut Env, var: &AstAssign| {
+            var.visit_lvalue(|x| {
+                env.vars.insert(x.node.0.clone(), Abstract::Unknown);
+            })
+        };
+        bind(&mut env, &for_.var);
+        for clause in clauses {
+            match clause {
+                Clause::For(for_) => {
+                    self.expr(&mut env, &for_.over);
+                    bind(&mut env, &for_.var);
+                }
+                Clause::If(cond) => {
+                    if self.condition(&mut env, cond) == Some(false) {
+                        return;
+                    }
+                }
+            }
+        }
+        for x in results {
+            self.expr(&mut env, x);
+        }
+    }
+
+    fn expr(&mut self, env: &mut Env, x: &AstExpr) -> Abstract {
+        match &x.node {
+            Expr::Literal(AstLiteral::Int(i)) => match &i.node {
+                TokenInt::I32(i) => Abstract::Int(*i as i64),
+                TokenInt::BigInt(_) => Abstract::Unknown,
+            },
+            Expr::Literal(AstLiteral::Float(f)) => Abstract::Float(f.node),
+            Expr::Literal(AstLiteral::String(s)) => Abstract::String(s.node.clone()),
+            Expr::Identifier(name, ()) => self.lookup(env, &name.node),
+            Expr::Tuple(xs) => Abstract::Tuple(xs.iter().map(|x| self.expr(env, x)).collect()),
+            Expr::Not(a) => Abstract::bool_or_unknown(self.expr(env, a).truth().map(|x| !x)),
+            Expr::Minus(a) => match self.expr(env, a) {
+                Abstract::Int(i) => Abstract::int_or_unknown(i.checked_neg()),
+                Abstract::Float(f) => Abstract::Float(-f),
+                _ => Abstract::Unknown,
+            },
+            Expr::Plus(a) => match self.expr(env, a) {
+                a @ (Abstract::Int(_) | Abstract::Float(_)) => a,
+                _ => Abstract::Unknown,
+            },
+            Expr::BitNot(a) => match self.expr(env, a) {
+                Abstract::Int(i) => Abstract::Int(!i),
+                _ => Abstract::Unknown,
+            },
+            Expr::Op(a, op @ (BinOp::And | BinOp::Or), b) => {
+                let l = self.expr(env, a);
+                match (op, l.truth()) {
+                    // The right operand is not evaluated.
+                    (BinOp::And, Some(false)) | (BinOp::Or, Some(true)) => l,
+                    (_, Some(_)) => self.expr(env, b),
+                    (_, None) => {
+                        self.expr(env, b);
+                        Abstract::Unknown
+                    }
+                }
+            }
+            Expr::Op(a, op, b) => {
+                let l = self.expr(env, a);
+                let r = self.expr(env, b);
+                self.binop(*op, l, r, x.span, || x.to_string())
+            }
+            Expr::If(c_t_f) => {
+                let (c, t, f) = &**c_t_f;
+                match self.condition(env, c) {
+                    Some(true) => self.expr(env, t),
+                    Some(false) => self.expr(env, f),
+                    None => {
+                        let t = self.expr(env, t);
+                        let f = self.expr(env, f);
+                        if t == f { t } else { Abstract::Unknown }
+                    }
+                }
+            }
+            Expr::ArrayIndirection(a_i) => {
+                let (a, i) = &**a_i;
+                let a = self.expr(env, a);
+                let i = self.expr(env, i);
+                self.index(x, a, i)
+            }
+            Expr::Call(f, args) => {
+                let is_len = matches!(&f.node, Expr::Identifier(name, ()) if name.node == "len")
+                    && self.is_builtin(env, "len");
+                self.expr(env, f);
+                let args: Vec<Abstract> = args.iter().map(|x| self.expr(env, x.expr())).collect();
+                match (is_len, args.as_slice()) {
+                    (true, [arg]) => match arg.len() {
+                        Some(len) => Abstract::Int(len as i64),
+                        None => Abstract::Unknown,
+                    },
+                    _ => Abstract::Unknown,
+                }
+            }
+            Expr::Lambda(lambda) => {
+                for p in &lambda.params {
+                    if let (_, _, Some(default)) = p.split() {
+                        self.expr(env, default);
+                    }
+                }
+                let mut env = env.clone();
+                for p in &lambda.params {
+                    if let (Some(name), _, _) = p.split() {
+                        env.vars.insert(name.node.0.clone(), Abstract::Unknown);
+                    }
+                }
+                self.expr(&mut env, &lambda.body);
+                Abstract::Unknown
+            }
+            Expr::ListComprehension(a, for_, clauses) => {
+                self.comprehension(env, for_, clauses, &[a]);
+                Abstract::Unknown
+            }
+            Expr::DictComprehension(k_v, for_, clauses) => {
+                self.comprehension(env, for_, clauses, &[&k_v.0, &k_v.1]);
+                Abstract::Unknown
+            }
+            Expr::Dot(..) | Expr::Slice(..) | Expr::List(..) | Expr::Dict(..) => {
+                x.visit_expr(|x| {
+                    self.expr(env, x);
+                });
+                Abstract::Unknown
+            }
+        }
+    }
+
+    fn assign(&mut self, env: &mut Env, lhs: &AstAssign, value: Abstract) {
+        match &lhs.node {
+            Assign::Identifier(name) => {
+                env.vars.insert(name.node.0.clone(), value);
+            }
+            Assign::Tuple(xs) => match value {
+                Abstract::Tuple(values) if values.len() == xs.len() => {
+                    for (x, v) in xs.iter().zip(values) {
+                        self.assign(env, x, v);
+                    }
+                }
+                _ => {
+                    for x in xs {
+                        self.assign(env, x, Abstract::Unknown);
+                    }
+                }
+            },
+            Assign::ArrayIndirection(a_i) => {
+                self.expr(env, &a_i.0);
+                self.expr(env, &a_i.1);
+            }
+            Assign::Dot(a, _) => {
+                self.expr(env, a);
+            }
+            Assign::Slice(a, b, c, d) => {
+                self.expr(env, a);
+                for x in [b, c, d].into_iter().flatten() {
+                    self.expr(env, x);
+                }
+            }
+        }
+    }
+
+    /// Analyze a loop body on all iterations: the variables assigned in the loop are unknown.
+    fn loop_body(&mut self, env: &mut Env, cond: Option<&AstExpr>, body: &AstStmt) {
+        let mut names = HashSet::new();
+        assigned_names(body, &mut names);
+        env.forget(&names);
+        if let Some(cond) = cond {
+            if self.condition(env, cond) == Some(false) {
+                return;
+            }
+        }
+        let mut body_env = env.clone();
+        self.stmt(&mut body_env, body);
+    }
+
+    fn stmt(&mut self, env: &mut Env, stmt: &AstStmt) {
+        match &stmt.node {
+            Stmt::Break | Stmt::Continue | Stmt::Pass => {}
+            Stmt::Return(x) | Stmt::Yield(x) => {
+                if let Some(x) = x {
+                    self.expr(env, x);
+                }
+            }
+            Stmt::Expression(x) => {
+                self.expr(env, x);
+            }
+            Stmt::Assign(lhs, ty_rhs) => {
+                let value = self.expr(env, &ty_rhs.1);
+                self.assign(env, lhs, value);
+            }
+            Stmt::AssignModify(lhs, op, rhs) => {
+                let r = self.expr(env, rhs);
+                let value = match &lhs.node {
+                    Assign::Identifier(name) => {
+                        let l = self.lookup(env, &name.node.0);
+                        self.binop(assign_op(*op), l, r, stmt.span, || {
+                            format!("{}{}{}", lhs.node, op, rhs.node)
+                        })
+                    }
+                    _ => Abstract::Unknown,
+                };
+                self.assign(env, lhs, value);
+            }
+            Stmt::Statements(xs) => {
+                for x in xs {
+                    self.stmt(env, x);
+                }
+            }
+            Stmt::If(cond, body) => match self.condition(env, cond) {
+                Some(false) => {}
+                Some(true) => self.stmt(env, body),
+                None => {
+                    let mut body_env = env.clone();
+                    self.stmt(&mut body_env, body);
+                    env.join(&body_env);
+                }
+            },
+            Stmt::IfElse(cond, then_else) => {
+                let (then_block, else_block) = &**then_else;
+                match self.condition(env, cond) {
+                    Some(true) => self.stmt(env, then_block),
+                    Some(false) => self.stmt(env, else_block),
+                    None => {
+                        let mut then_env = env.clone();
+                        self.stmt(&mut then_env, then_block);
+                        self.stmt(env, else_block);
+                        env.join(&then_env);
+                    }
+                }
+            }
+            Stmt::For(var, over_body) => {
+                let (over, body) = &**over_body;
+                self.expr(env, over);
+                self.assign(env, var, Abstract::Unknown);
+                self.loop_body(env, None, body);
+            }
+            Stmt::While(cond, body) => self.loop_body(env, Some(cond), body),
+            Stmt::Def(def) => {
+                for p in &def.params {
+                    if let (_, _, Some(default)) = p.split() {
+                        self.expr(env, default);
+                    }
+                }
+                env.vars.insert(def.name.node.0.clone(), Abstract::Unknown);
+                // Parameters are unknown, because they are in `assigned`.
+                self.stmt(&mut Env::default(), &def.body);
+            }
+            Stmt::Load(load) => {
+                for (name, _) in &load.args {
+                    env.vars.insert(name.node.0.clone(), Abstract::Unknown);
+                }
+            }
+        }
+    }
+}
+
+fn assign_op(op: AssignOp) -> BinOp {
+    match op {
+        AssignOp::Add => BinOp::Add,
+        AssignOp::Subtract => BinOp::Subtract,
+        AssignOp::Multiply => BinOp::Multiply,
+        AssignOp::Divide => BinOp::Divide,
+        AssignOp::FloorDivide => BinOp::FloorDivide,
+        AssignOp::Percent => BinOp::Percent,
+        AssignOp::BitAnd => BinOp::BitAnd,
+        AssignOp::BitOr => BinOp::BitOr,
+        AssignOp::BitXor => BinOp::BitXor,
+        AssignOp::LeftShift => BinOp::LeftShift,
+        AssignOp::RightShift => BinOp::RightShift,
+    }
+}
+
+pub(crate) fn constant_issues(module: &AstModule) -> Vec<LintT<ConstantIssue>> {
+    let mut assigned = HashSet::new();
+    assigned_names(&module.statement, &mut assigned);
+    let mut analysis = Analysis {
+        codemap: &module.codemap,
+        assigned,
+        res: Vec::new(),
+    };
+    analysis.stmt(&mut Env::default(), &module.statement);
+    analysis.res
+}
+
+#[cfg(test)]
+mod tests {
+    use gazebo::prelude::*;
+
+    use super::*;
+    use crate::syntax::Dialect;
+
+    fn issues(program: &str) -> Vec<String> {
+        let dialect = Dialect {
+            enable_while: true,
+            ..Dialect::Extended
+        };
+        let module = AstModule::parse("bad.bzl", program.to_owned(), &dialect).unwrap();
+        constant_issues(&module).map(|x| x.to_string())
+    }
+
+    #[test]
+    fn test_constant_condition() {
+        assert_eq!(
+            issues(
+                r#"
+X = 2
+if X == 1:
+    pass
+Y = "linux" if X > 1 else "mac"
+if "lin" in Y:
+    pass
+if False:
+    pass
+while True:
+    break
+"#
+            ),
+            &[
+                "bad.bzl:3:4-10: Condition `(X == 1)` is always `False`",
+                "bad.bzl:5:16-21: Condition `(X > 1)` is always `True`",
+                "bad.bzl:6:4-14: Condition `(\"lin\" in Y)` is always `True`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_index_out_of_bounds() {
+        assert_eq!(
+            issues(
+                r#"
+T = (1, 2, 3)
+T[3]
+T[-3]
+S = "ab"
+S[len(T)]
+"#
+            ),
+            &[
+                "bad.bzl:3:1-5: Index 3 is out of bounds in `T[3]`, the length is 3",
+                "bad.bzl:6:1-10: Index 3 is out of bounds in `S[len(T)]`, the length is 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        assert_eq!(
+            issues(
+                r#"
+N = 0
+def f(x):
+    return x / N
+x = 10 // N
+y = 10
+y %= N
+"%s" % N
+N != 0 and 1 / N
+"#
+            ),
+            &[
+                "bad.bzl:5:5-12: Division by zero in `(10 // N)`",
+                "bad.bzl:7:1-7: Division by zero in `y %= N`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_paths_merged() {
+        assert!(
+            issues(
+                r#"
+def f(c, xs):
+    n = 0
+    if c:
+        n = 1
+    x = 1 / n
+    i = 0
+    for x in xs:
+        i += 1
+    if i == 0:
+        pass
+    t = (1,)
+    while c:
+        t = t + (2,)
+    t[1]
+    return c and (1 / n)
+"#
+            )
+            .is_empty()
+        );
+    }
+}
@@ -0,0 +1,358 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Find expression statements which have no effect, in particular calls of pure functions
+//! like `sorted(x)`, where the user likely expected the function to modify its argument.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use gazebo::variants::VariantName;
+use thiserror::Error;
+
+use crate::analysis::constant::visit_assigned_names;
+use crate::analysis::types::LintT;
+use crate::analysis::types::LintWarning;
+use crate::codemap::CodeMap;
+use crate::syntax::ast::Assign;
+use crate::syntax::ast::AstAssign;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::DefP;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::Stmt;
+use crate::syntax::AstModule;
+
+#[derive(Error, Debug, VariantName)]
+pub(crate) enum DiscardedIssue {
+    #[error("Result of `{0}` is not used, `{1}` does not modify its arguments")]
+    UnusedReturn(String, String),
+    #[error("Expression `{0}` has no effect")]
+    NoEffect(String),
+}
+
+impl LintWarning for DiscardedIssue {
+    fn is_serious(&self) -> bool {
+        true
+    }
+}
+
+/// Global functions which do not modify their arguments.
+const PURE_BUILTINS: &[&str] = &[
+    "all",
+    "any",
+    "bool",
+    "chr",
+    "dict",
+    "dir",
+    "enumerate",
+    "float",
+    "getattr",
+    "hasattr",
+    "hash",
+    "int",
+    "len",
+    "list",
+    "max",
+    "min",
+    "ord",
+    "range",
+    "repr",
+    "reversed",
+    "sorted",
+    "str",
+    "tuple",
+    "type",
+    "zip",
+];
+
+/// Functions known to be pure, by name.
+struct PureFunctions {
+    /// Number of bindings of each name in the module.
+    bindings: HashMap<String, usize>,
+    defs: HashSet<String>,
+}
+
+impl PureFunctions {
+    fn is_pure(&self, name: &str) -> bool {
+        match self.bindings.get(name) {
+            None => PURE_BUILTINS.contains(&name),
+            Some(1) => self.defs.contains(name),
+            Some(_) => false,
+        }
+    }
+
+    /// Evaluating the expression does not modify anything.
+    /// Lambdas are pure if their bodies are, because they may be called by pure functions.
+    fn expr(&self, x: &AstExpr) -> bool {
+        match &x.node {
+            Expr::Call(f, _) if !self.callee(f) => false,
+            _ => {
+                let mut pure = true;
+                x.visit_expr(|x| pure = pure && self.expr(x));
+                pure
+            }
+        }
+    }
+
+    fn callee(&self, f: &AstExpr) -> bool {
+        match &f.node {
+            Expr::Identifier(name, _) => self.is_pure(&name.node),
+            _ => false,
+        }
+    }
+
+    /// Executing the statement in a function body only modifies local variables.
+    ///
+    /// Augmented assignments to parameters are not pure, because `+=` modifies lists in place.
+    fn stmt(&self, x: &AstStmt, params: &HashSet<&str>) -> bool {
+        fn local(x: &AstAssign) -> bool {
+            match &x.node {
+                Assign::Identifier(_) => true,
+                Assign::Tuple(xs) => xs.iter().all(local),
+                _ => false,
+            }
+        }
+
+        match &x.node {
+            Stmt::Break | Stmt::Continue | Stmt::Pass | Stmt::Return(None) => true,
+            // Nested functions cannot be called, because they are not known to be pure.
+            Stmt::Def(..) => true,
+            Stmt::Load(..) | Stmt::Yield(..) => false,
+            Stmt::Return(Some(x)) | Stmt::Expression(x) => self.expr(x),
+            Stmt::Assign(lhs, ty_rhs) => local(lhs) && self.expr(&ty_rhs.1),
+            Stmt::AssignModify(lhs, _, rhs) => match &lhs.node {
+                Assign::Identifier(name) => {
+                    !params.contains(name.node.0.as_str()) && self.expr(rhs)
+                }
+                _ => false,
+            },
+            Stmt::For(var, over_body) => {
+                local(var) && self.expr(&over_body.0) && self.stmt(&over_body.1, params)
+            }
+            Stmt::Statements(xs) => xs.iter().all(|x| self.stmt(x, params)),
+            Stmt::If(cond, body) | Stmt::While(cond, body) => {
+                self.expr(cond) && self.stmt(body, params)
+            }
+            Stmt::IfElse(cond, then_else) => {
+                self.expr(cond)
+                    && self.stmt(&then_else.0, params)
+                    && self.stmt(&then_else.1, params)
+            }
+        }
+    }
+
+    fn def(&self, def: &DefP<AstNoPayload>) -> bool {
+        let params = def
+            .params
+            .iter()
+            .filter_map(|p| Some(p.split().0?.node.0.as_str()))
+            .collect();
+        def.params
+            .iter()
+            .filter_map(|p| p.split().2)
+            .all(|x| self.expr(x))
+            && self.stmt(&def.body, &params)
+    }
+
+    fn new(module: &AstModule) -> PureFunctions {
+        // Top-level functions with a return type other than `None`.
+        fn candidates<'a>(x: &'a AstStmt, res: &mut Vec<&'a DefP<AstNoPayload>>) {
+            match &x.node {
+                Stmt::Statements(xs) => {
+                    for x in xs {
+                        candidates(x, res);
+                    }
+                }
+                Stmt::Def(def) => match def.return_type.as_deref().map(|x| &x.node) {
+                    None => {}
+                    Some(Expr::Identifier(name, _)) if name.node == "None" => {}
+                    Some(_) => res.push(def),
+                },
+                _ => {}
+            }
+        }
+
+        let mut bindings = HashMap::new();
+        visit_assigned_names(&module.statement, &mut |name| {
+            *bindings.entry(name.to_owned()).or_insert(0) += 1;
+        });
+        let mut defs = Vec::new();
+        candidates(&module.statement, &mut defs);
+
+        // Assume all the candidates are pure, and exclude the ones calling impure functions
+        // until nothing changes, so recursive functions can be pure.
+        let mut pure = PureFunctions {
+            bindings,
+            defs: defs.iter().map(|def| def.name.node.0.clone()).collect(),
+        };
+        loop {
+            let impure: Vec<&DefP<AstNoPayload>> = defs
+                .iter()
+                .copied()
+                .filter(|def| pure.defs.contains(&def.name.node.0) && !pure.def(def))
+                .collect();
+            if impure.is_empty() {
+                return pure;
+            }
+            for def in impure {
+                pure.defs.remove(&def.name.node.0);
+            }
+        }
+    }
+}
+
+fn check(
+    codemap: &CodeMap,
+    pure: &PureFunctions,
+    x: &AstStmt,
+    res: &mut Vec<LintT<DiscardedIssue>>,
+) {
+    match &x.node {
+        Stmt::Expression(e) => {
+            let issue = match &e.node {
+                Expr::Call(f, _) => match &f.node {
+                    Expr::Identifier(name, _) if pure.expr(e) => Some(
+                        DiscardedIssue::UnusedReturn(e.to_string(), name.node.clone()),
+                    ),
+                    _ => None,
+                },
+                // Documentation strings.
+                Expr::Literal(AstLiteral::String(_)) => None,
+                // Usually `==` instead of `=`, or a function which is not called.
+                // Attribute access, indexing and collection literals are not reported,
+                // because they are sometimes used to check a value is valid.
+                Expr::Op(..)
+                | Expr::Not(..)
+                | Expr::Minus(..)
+                | Expr::Plus(..)
+                | Expr::BitNot(..)
+                | Expr::Identifier(..)
+                | Expr::Literal(..)
+                    if pure.expr(e) =>
+                {
+                    Some(DiscardedIssue::NoEffect(e.to_string()))
+                }
+                _ => None,
+            };
+            if let Some(issue) = issue {
+                res.push(LintT::new(codemap, x.span, issue));
+            }
+        }
+        _ => x.visit_stmt(|x| check(codemap, pure, x, res)),
+    }
+}
+
+pub(crate) fn discarded_issues(module: &AstModule) -> Vec<LintT<DiscardedIssue>> {
+    let pure = PureFunctions::new(module);
+    let mut res = Vec::new();
+    check(&module.codemap, &pure, &module.statement, &mut res);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use gazebo::prelude::*;
+
+    use super::*;
+    use crate::syntax::Dialect;
+
+    impl DiscardedIssue {
+        fn about(&self) -> &String {
+            match self {
+                DiscardedIssue::UnusedReturn(x, _) => x,
+                DiscardedIssue::NoEffect(x) => x,
+            }
+        }
+    }
+
+    fn discarded(program: &str) -> Vec<LintT<DiscardedIssue>> {
+        let module = AstModule::parse("X", program.to_owned(), &Dialect::Extended).unwrap();
+        discarded_issues(&module)
+    }
+
+    #[test]
+    fn test_unused_return() {
+        let res = discarded(
+            r#"
+def f(xs):
+    """Documentation."""
+    sorted(xs)
+    xs.append(1)
+    len(xs) == 0
+    print(sorted(xs))
+    sorted(xs, key = lambda x: x.pop())
+    sorted(xs, key = lambda x: -x)
+    xs[0]
+    f
+"#,
+        );
+        assert_eq!(
+            res.map(|x| x.problem.about()),
+            &[
+                "sorted(xs)",
+                "(len(xs) == 0)",
+                "sorted(xs, key = (lambda x: -x))",
+                "f"
+            ]
+        );
+        assert_eq!(
+            res[0].to_string(),
+            "X:4:5-15: Result of `sorted(xs)` is not used, `sorted` does not modify its arguments"
+        );
+        assert_eq!(
+            res[1].to_string(),
+            "X:6:5-17: Expression `(len(xs) == 0)` has no effect"
+        );
+    }
+
+    #[test]
+    fn test_pure_defs() {
+        let res = discarded(
+            r#"
+def pure(xs) -> "list":
+    res = []
+    for x in xs:
+        res += [x, recursive(x)]
+    return res
+def recursive(x) -> "int":
+    return recursive(x - 1) if x else 0
+def modifies(xs) -> "list":
+    xs += [1]
+    return xs
+def calls(xs) -> "list":
+    return modifies(xs)
+def not_annotated(xs):
+    return len(xs)
+def shadowed(len) -> "int":
+    return 1
+pure([])
+recursive(1)
+modifies([])
+calls([])
+not_annotated([])
+len(1)
+"#,
+        );
+        assert_eq!(
+            res.map(|x| x.problem.about()),
+            &["pure([])", "recursive(1)"]
+        );
+    }
+}
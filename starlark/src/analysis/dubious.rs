@@ -28,8 +28,12 @@ use crate::codemap::FileSpan;
 use crate::codemap::Span;
 use crate::syntax::ast::AstExpr;
 use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstParameter;
 use crate::syntax::ast::Expr;
+use crate::syntax::ast::Stmt;
 use crate::syntax::lexer::TokenInt;
+use crate::syntax::uniplate::Visit;
 use crate::syntax::AstModule;
 use crate::values::num::Num;
 
@@ -37,6 +41,8 @@ use crate::values::num::Num;
 pub(crate) enum Dubious {
     #[error("Duplicate dictionary key `{0}`, also used at {1}")]
     DuplicateKey(String, FileSpan),
+    #[error("Default value `{0}` of parameter `{1}` is mutable and shared between calls")]
+    MutableDefault(String, String),
 }
 
 impl LintWarning for Dubious {
@@ -110,9 +116,64 @@ fn duplicate_dictionary_key(module: &AstModule, res: &mut Vec<LintT<Dubious>>) {
         .visit_expr(|x| expr(x, &module.codemap, res))
 }
 
+// Default values are evaluated once, when the function is defined,
+// so mutating a list or dict default is visible in all the later calls.
+fn mutable_default(module: &AstModule, res: &mut Vec<LintT<Dubious>>) {
+    fn is_mutable(x: &AstExpr) -> bool {
+        match &**x {
+            Expr::List(_)
+            | Expr::Dict(_)
+            | Expr::ListComprehension(..)
+            | Expr::DictComprehension(..) => true,
+            Expr::Call(f, _) => match &f.node {
+                Expr::Identifier(name, ()) => name.node == "list" || name.node == "dict",
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn check(params: &[AstParameter], codemap: &CodeMap, res: &mut Vec<LintT<Dubious>>) {
+        for p in params {
+            if let (Some(name), _, Some(default)) = p.split() {
+                if is_mutable(default) {
+                    res.push(LintT::new(
+                        codemap,
+                        default.span,
+                        Dubious::MutableDefault(default.to_string(), name.node.0.clone()),
+                    ))
+                }
+            }
+        }
+    }
+
+    fn visit(x: Visit<AstNoPayload>, codemap: &CodeMap, res: &mut Vec<LintT<Dubious>>) {
+        match x {
+            Visit::Stmt(x) => {
+                if let Stmt::Def(def) = &**x {
+                    check(&def.params, codemap, res);
+                }
+            }
+            Visit::Expr(x) => {
+                if let Expr::Lambda(lambda) = &**x {
+                    check(&lambda.params, codemap, res);
+                }
+            }
+        }
+        x.visit_children(|x| visit(x, codemap, res));
+    }
+
+    if module.dialect.enable_fresh_defaults {
+        // Defaults are copied on each call which uses them.
+        return;
+    }
+    visit(Visit::Stmt(&module.statement), &module.codemap, res);
+}
+
 pub(crate) fn dubious(module: &AstModule) -> Vec<LintT<Dubious>> {
     let mut res = Vec::new();
     duplicate_dictionary_key(module, &mut res);
+    mutable_default(module, &mut res);
     res
 }
 
@@ -131,6 +192,7 @@ mod tests {
         fn about(&self) -> &String {
             match self {
                 Dubious::DuplicateKey(x, _) => x,
+                Dubious::MutableDefault(_, x) => x,
             }
         }
     }
@@ -161,4 +223,38 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_lint_mutable_default() {
+        let m = module(
+            r#"
+def f(a, b = [], c = {}, d = None, e = (), *, g = list(), h = [x for x in e]):
+    def nested(i = dict(a = 1)):
+        pass
+    return lambda j = [1], k = 1: j
+"#,
+        );
+        let mut res = Vec::new();
+        mutable_default(&m, &mut res);
+        assert_eq!(
+            res.map(|x| x.problem.about()),
+            &["b", "c", "g", "h", "i", "j"]
+        );
+    }
+
+    #[test]
+    fn test_lint_mutable_default_fresh_defaults() {
+        let m = AstModule::parse(
+            "X",
+            "def f(x = []):\n    pass\n".to_owned(),
+            &Dialect {
+                enable_fresh_defaults: true,
+                ..Dialect::Extended
+            },
+        )
+        .unwrap();
+        let mut res = Vec::new();
+        mutable_default(&m, &mut res);
+        assert!(res.is_empty());
+    }
 }
@@ -15,12 +15,90 @@
  * limitations under the License.
  */
 
+use dupe::Dupe;
+use num_traits::ToPrimitive;
+
 use crate::codemap::FileSpan;
+use crate::codemap::Span;
 use crate::collections::SmallMap;
+use crate::docs::DocString;
+use crate::docs::DocStringKind;
+use crate::syntax::ast::AssignP;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
 use crate::syntax::ast::DefP;
+use crate::syntax::ast::Expr;
 use crate::syntax::ast::Stmt;
+use crate::syntax::lexer::TokenInt;
 use crate::syntax::AstModule;
 
+/// Whether an exported symbol is a function or a variable.
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq)]
+pub enum ExportedSymbolKind {
+    /// Defined with `def`.
+    Function,
+    /// Defined with an assignment.
+    Variable,
+}
+
+/// A symbol exported by a module, see [`AstModule::exported_symbols_detailed`].
+#[derive(Debug, Clone)]
+pub struct ExportedSymbol<'a> {
+    /// Name of the symbol.
+    pub name: &'a str,
+    /// Whether the symbol is a function or a variable, from its first definition.
+    pub kind: ExportedSymbolKind,
+    /// Location of the name in its first definition.
+    pub span: FileSpan,
+    /// Docstring of a function.
+    pub docstring: Option<DocString>,
+    /// Value of a variable assigned once, if it is a literal:
+    /// `None`, `True`, `False`, a number, a string,
+    /// or a list, tuple or dict with string keys of literals.
+    pub value: Option<serde_json::Value>,
+}
+
+/// Value of a literal expression.
+fn literal_value(x: &AstExpr) -> Option<serde_json::Value> {
+    Some(match &**x {
+        Expr::Literal(AstLiteral::String(x)) => serde_json::Value::String(x.node.clone()),
+        Expr::Literal(AstLiteral::Int(x)) => match &x.node {
+            TokenInt::I32(x) => serde_json::Value::from(*x),
+            TokenInt::BigInt(x) => serde_json::Value::from(x.to_i64()?),
+        },
+        Expr::Literal(AstLiteral::Float(x)) => {
+            serde_json::Value::Number(serde_json::Number::from_f64(x.node)?)
+        }
+        Expr::Minus(x) => match literal_value(x)? {
+            serde_json::Value::Number(x) => match x.as_i64() {
+                Some(x) => serde_json::Value::from(x.checked_neg()?),
+                None => serde_json::Value::from(-x.as_f64()?),
+            },
+            _ => return None,
+        },
+        Expr::Identifier(x, _) => match x.as_str() {
+            "None" => serde_json::Value::Null,
+            "True" => serde_json::Value::Bool(true),
+            "False" => serde_json::Value::Bool(false),
+            _ => return None,
+        },
+        Expr::List(xs) | Expr::Tuple(xs) => {
+            serde_json::Value::Array(xs.iter().map(literal_value).collect::<Option<_>>()?)
+        }
+        Expr::Dict(xs) => serde_json::Value::Object(
+            xs.iter()
+                .map(|(k, v)| match &**k {
+                    Expr::Literal(AstLiteral::String(k)) => {
+                        Some((k.node.clone(), literal_value(v)?))
+                    }
+                    _ => None,
+                })
+                .collect::<Option<_>>()?,
+        ),
+        _ => return None,
+    })
+}
+
 impl AstModule {
     /// Which symbols are exported by this module. These are the top-level assignments,
     /// including function definitions. Any symbols that start with `_` are not exported.
@@ -45,6 +123,81 @@ impl AstModule {
             .map(|(name, span)| (self.file_span(span), name))
             .collect()
     }
+
+    /// Like [`exported_symbols`](AstModule::exported_symbols), but with the kinds, docstrings
+    /// and literal values of the symbols, found without evaluating the module.
+    pub fn exported_symbols_detailed(&self) -> Vec<ExportedSymbol<'_>> {
+        struct Definition<'a> {
+            kind: ExportedSymbolKind,
+            span: Span,
+            docstring: Option<DocString>,
+            value: Option<&'a AstExpr>,
+            /// Defined more than once, so the value is not known statically.
+            redefined: bool,
+        }
+
+        let mut result: SmallMap<&str, Definition> = SmallMap::new();
+        let mut define = |name, span, kind, docstring, value| match result.get_mut(name) {
+            Some(x) => x.redefined = true,
+            None => {
+                result.insert(
+                    name,
+                    Definition {
+                        kind,
+                        span,
+                        docstring,
+                        value,
+                        redefined: false,
+                    },
+                );
+            }
+        };
+        self.statement.visit_stmt(|x| match &**x {
+            // Keep the kind and docstring of the decorated `def`.
+            Stmt::Assign(..) if Stmt::is_decorator_assign(x) => {}
+            Stmt::Assign(dest, ty_rhs) => {
+                let value = match &dest.node {
+                    AssignP::Identifier(_) => Some(&ty_rhs.1),
+                    _ => None,
+                };
+                dest.visit_lvalue(|name| {
+                    define(
+                        &name.0,
+                        name.span,
+                        ExportedSymbolKind::Variable,
+                        None,
+                        value,
+                    )
+                });
+            }
+            Stmt::AssignModify(dest, _, _) => dest.visit_lvalue(|name| {
+                define(&name.0, name.span, ExportedSymbolKind::Variable, None, None)
+            }),
+            Stmt::Def(DefP { name, body, .. }) => {
+                let docstring = DocString::extract_raw_starlark_docstring(body)
+                    .and_then(|x| DocString::from_docstring(DocStringKind::Starlark, &x));
+                define(
+                    &name.0,
+                    name.span,
+                    ExportedSymbolKind::Function,
+                    docstring,
+                    None,
+                )
+            }
+            _ => {}
+        });
+        result
+            .into_iter()
+            .filter(|(name, _)| !name.starts_with('_'))
+            .map(|(name, x)| ExportedSymbol {
+                name,
+                kind: x.kind,
+                span: self.file_span(x.span),
+                docstring: x.docstring,
+                value: x.value.filter(|_| !x.redefined).and_then(literal_value),
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -75,4 +228,68 @@ d = 2
             &["X:3:5-6 b", "X:4:1-2 d"]
         );
     }
+
+    #[test]
+    fn test_exported_detailed() {
+        let modu = module(
+            r#"
+load("test", "a")
+def b():
+    """Summary of b.
+
+    Details.
+    """
+    pass
+c = {"x": [1, -2.5, None], "y": (True, "s")}
+d = 1
+d = 2
+e = b()
+f, g = 1, 2
+h = -2147483648
+_i = 1
+"#,
+        );
+        let res = modu.exported_symbols_detailed();
+        assert_eq!(
+            res.map(|x| format!(
+                "{} {} {:?} {}",
+                x.span,
+                x.name,
+                x.kind,
+                x.value.as_ref().map_or("-".to_owned(), |x| x.to_string())
+            )),
+            &[
+                "X:3:5-6 b Function -",
+                r#"X:9:1-2 c Variable {"x":[1,-2.5,null],"y":[true,"s"]}"#,
+                "X:10:1-2 d Variable -",
+                "X:12:1-2 e Variable -",
+                "X:13:1-2 f Variable -",
+                "X:13:4-5 g Variable -",
+                "X:14:1-2 h Variable -2147483648",
+            ]
+        );
+        let docstring = res[0].docstring.as_ref().unwrap();
+        assert_eq!("Summary of b.", docstring.summary);
+        assert_eq!(Some("Details."), docstring.details.as_deref());
+        assert!(res[1].docstring.is_none());
+    }
+
+    #[test]
+    fn test_exported_decorated() {
+        let modu = module(
+            r#"
+@memo
+def a():
+    """Cached."""
+    pass
+"#,
+        );
+        let res = modu.exported_symbols_detailed();
+        assert_eq!(1, res.len());
+        assert_eq!(
+            "X:3:5-6 a Function",
+            format!("{} {} {:?}", res[0].span, res[0].name, res[0].kind)
+        );
+        assert_eq!("Cached.", res[0].docstring.as_ref().unwrap().summary);
+    }
 }
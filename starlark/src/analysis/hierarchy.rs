@@ -0,0 +1,330 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Functions and types defined at the top level of a module, and the calls between functions,
+//! used for the call and type hierarchies of the LSP.
+
+use std::collections::HashSet;
+
+use crate::analysis::LspModule;
+use crate::codemap::ResolvedSpan;
+use crate::codemap::Span;
+use crate::syntax::ast::AssignP;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::DefP;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::Stmt;
+
+/// Builtin functions which create a type when assigned to a top level name,
+/// e.g. `MyInfo = provider(fields = ["x"])`.
+const TYPE_CONSTRUCTORS: &[&str] = &["enum", "provider", "record"];
+
+/// A function or a type defined at the top level of a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DefinedSymbol {
+    pub(crate) name: String,
+    /// The whole `def` or assignment statement.
+    pub(crate) span: ResolvedSpan,
+    /// The name in the statement.
+    pub(crate) name_span: ResolvedSpan,
+    /// For types, the function which created the type, e.g. `record`.
+    pub(crate) constructor: Option<String>,
+}
+
+/// A symbol bound by a `load()` statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LoadedSymbol {
+    /// The name in this module.
+    pub(crate) local: String,
+    /// The path of the loaded module, as written in the `load()` statement.
+    pub(crate) module: String,
+    /// The name in the loaded module.
+    pub(crate) name: String,
+}
+
+/// A call of a function by name, e.g. `foo(1)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FunctionCall {
+    /// Top level function containing the call, `None` for calls at the top level.
+    pub(crate) caller: Option<String>,
+    /// Name of the called function, as visible at the call site.
+    pub(crate) callee: String,
+    /// The callee identifier at the call site.
+    pub(crate) span: ResolvedSpan,
+}
+
+impl LspModule {
+    fn top_level_statements(&self, mut f: impl FnMut(&AstStmt)) {
+        fn visit(stmt: &AstStmt, f: &mut impl FnMut(&AstStmt)) {
+            match &stmt.node {
+                Stmt::Statements(_) | Stmt::If(..) | Stmt::IfElse(..) => {
+                    stmt.visit_stmt(|x| visit(x, f))
+                }
+                _ => f(stmt),
+            }
+        }
+        visit(&self.ast.statement, &mut f)
+    }
+
+    fn defined_symbol(
+        &self,
+        name: &str,
+        span: Span,
+        name_span: Span,
+        constructor: Option<&str>,
+    ) -> DefinedSymbol {
+        let span = self.trim_span(span);
+        DefinedSymbol {
+            name: name.to_owned(),
+            span: self.ast.codemap.resolve_span(span),
+            name_span: self.ast.codemap.resolve_span(name_span),
+            constructor: constructor.map(str::to_owned),
+        }
+    }
+
+    /// Functions defined by `def` at the top level, in the order they are defined.
+    pub(crate) fn top_level_functions(&self) -> Vec<DefinedSymbol> {
+        let mut res = Vec::new();
+        self.top_level_statements(|x| {
+            if let Stmt::Def(DefP { name, .. }) = &x.node {
+                res.push(self.defined_symbol(&name.0, x.span, name.span, None));
+            }
+        });
+        res
+    }
+
+    /// Types created by `record`, `enum` or `provider` and assigned to a top level name,
+    /// in the order they are defined.
+    pub(crate) fn top_level_types(&self) -> Vec<DefinedSymbol> {
+        let mut res = Vec::new();
+        self.top_level_statements(|x| {
+            if let Stmt::Assign(lhs, ty_rhs) = &x.node {
+                let (_, rhs) = &**ty_rhs;
+                let (name, constructor) = match (&lhs.node, &rhs.node) {
+                    (AssignP::Identifier(name), Expr::Call(f, _)) => match &f.node {
+                        Expr::Identifier(f, _) if TYPE_CONSTRUCTORS.contains(&f.as_str()) => {
+                            (name, f)
+                        }
+                        _ => return,
+                    },
+                    _ => return,
+                };
+                res.push(self.defined_symbol(&name.0, x.span, name.span, Some(constructor)));
+            }
+        });
+        res
+    }
+
+    /// Symbols bound by the `load()` statements of this module.
+    pub(crate) fn loaded_symbols(&self) -> Vec<LoadedSymbol> {
+        let mut res = Vec::new();
+        self.top_level_statements(|x| {
+            if let Stmt::Load(load) = &x.node {
+                for (local, name) in &load.args {
+                    res.push(LoadedSymbol {
+                        local: local.0.clone(),
+                        module: load.module.node.clone(),
+                        name: name.node.clone(),
+                    });
+                }
+            }
+        });
+        res
+    }
+
+    /// Find the symbol among `symbols` whose name is at the zero based `line` and `col`.
+    pub(crate) fn symbol_at(
+        &self,
+        symbols: Vec<DefinedSymbol>,
+        line: u32,
+        col: u32,
+    ) -> Option<DefinedSymbol> {
+        let (line, col) = (line as usize, col as usize);
+        symbols.into_iter().find(|x| {
+            let span = x.name_span;
+            (span.begin_line, span.begin_column) <= (line, col)
+                && (line, col) <= (span.end_line, span.end_column)
+        })
+    }
+
+    /// All calls of functions by name in this module.
+    ///
+    /// Calls inside a function of a name which is local to the function,
+    /// like a parameter, are not included, because they cannot call a top level function.
+    /// Calls of expressions other than identifiers, like `x.f()`, are not included either.
+    pub(crate) fn function_calls(&self) -> Vec<FunctionCall> {
+        fn visit_expr(
+            expr: &AstExpr,
+            caller: Option<&str>,
+            locals: &HashSet<&str>,
+            res: &mut Vec<(Option<String>, String, Span)>,
+        ) {
+            if let Expr::Call(f, _) = &expr.node {
+                if let Expr::Identifier(name, _) = &f.node {
+                    if !locals.contains(name.as_str()) {
+                        res.push((caller.map(str::to_owned), name.node.clone(), name.span));
+                    }
+                }
+            }
+            expr.visit_expr(|x| visit_expr(x, caller, locals, res));
+        }
+
+        let mut res = Vec::new();
+        let no_locals = HashSet::new();
+        self.top_level_statements(|x| match &x.node {
+            Stmt::Def(def) => {
+                let locals = local_names(def);
+                let caller = Some(def.name.0.as_str());
+                x.visit_expr(|x| visit_expr(x, caller, &locals, &mut res));
+            }
+            _ => x.visit_expr(|x| visit_expr(x, None, &no_locals, &mut res)),
+        });
+        res.into_iter()
+            .map(|(caller, callee, span)| FunctionCall {
+                caller,
+                callee,
+                span: self.ast.codemap.resolve_span(span),
+            })
+            .collect()
+    }
+}
+
+/// Names assigned in a function, including its parameters and the names in nested functions.
+fn local_names<'a>(def: &'a DefP<AstNoPayload>) -> HashSet<&'a str> {
+    fn visit<'a>(stmt: &'a AstStmt, res: &mut HashSet<&'a str>) {
+        match &stmt.node {
+            Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) | Stmt::For(lhs, _) => lhs
+                .visit_lvalue(|x| {
+                    res.insert(&x.0);
+                }),
+            Stmt::Def(def) => {
+                res.insert(&def.name.0);
+            }
+            _ => {}
+        }
+        stmt.visit_stmt(|x| visit(x, res));
+    }
+
+    let mut res = HashSet::new();
+    for param in &def.params {
+        if let (Some(name), _, _) = param.split() {
+            res.insert(name.0.as_str());
+        }
+    }
+    visit(&def.body, &mut res);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use textwrap::dedent;
+
+    use crate::analysis::hierarchy::FunctionCall;
+    use crate::analysis::hierarchy::LoadedSymbol;
+    use crate::analysis::definition::helpers::FixtureWithRanges;
+
+    fn fixture(program: &str) -> FixtureWithRanges {
+        FixtureWithRanges::from_fixture("foo.star", dedent(program).trim()).unwrap()
+    }
+
+    #[test]
+    fn test_top_level_symbols() -> anyhow::Result<()> {
+        let fixture = fixture(
+            r#"
+            load("bar.star", baz = "bar")
+            <f_def>def <f>f</f>():
+                def g():
+                    pass
+                return g()</f_def>
+            <info_def><info>Info</info> = provider(fields = ["x"])</info_def>
+            Point = struct(x = 1)
+            if True:
+                <h_def>def <h>h</h>(): pass</h_def>
+            "#,
+        );
+        let module = fixture.module()?;
+
+        let functions = module.top_level_functions();
+        assert_eq!(
+            vec![
+                ("f", fixture.span("f"), fixture.span("f_def")),
+                ("h", fixture.span("h"), fixture.span("h_def")),
+            ],
+            functions
+                .iter()
+                .map(|x| (x.name.as_str(), x.name_span, x.span))
+                .collect::<Vec<_>>()
+        );
+
+        let types = module.top_level_types();
+        assert_eq!(1, types.len());
+        assert_eq!("Info", types[0].name);
+        assert_eq!(fixture.span("info"), types[0].name_span);
+        assert_eq!(fixture.span("info_def"), types[0].span);
+        assert_eq!(Some("provider"), types[0].constructor.as_deref());
+
+        assert_eq!(
+            vec![LoadedSymbol {
+                local: "baz".to_owned(),
+                module: "bar.star".to_owned(),
+                name: "bar".to_owned(),
+            }],
+            module.loaded_symbols()
+        );
+
+        let f = fixture.span("f");
+        let found = module.symbol_at(functions, f.begin_line as u32, f.begin_column as u32 + 1);
+        assert_eq!(Some("f"), found.as_ref().map(|x| x.name.as_str()));
+        let found = module.symbol_at(module.top_level_functions(), 0, 0);
+        assert_eq!(None, found);
+        Ok(())
+    }
+
+    #[test]
+    fn test_function_calls() -> anyhow::Result<()> {
+        let fixture = fixture(
+            r#"
+            def f(g, *args):
+                h = lambda: 1
+                for i in args:
+                    <f_k>k</f_k>(<f_j>j</f_j>(i), g(h()))
+
+            def j(x):
+                return x.k()
+
+            <top_f>f</top_f>(j, [])
+            "#,
+        );
+        let module = fixture.module()?;
+        let call = |caller: Option<&str>, callee: &str, span: &str| FunctionCall {
+            caller: caller.map(str::to_owned),
+            callee: callee.to_owned(),
+            span: fixture.span(span),
+        };
+        assert_eq!(
+            vec![
+                call(Some("f"), "k", "f_k"),
+                call(Some("f"), "j", "f_j"),
+                call(None, "f", "top_f"),
+            ],
+            module.function_calls()
+        );
+        Ok(())
+    }
+}
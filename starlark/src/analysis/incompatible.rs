@@ -154,6 +154,7 @@ fn duplicate_top_level_assignment(module: &AstModule, res: &mut Vec<LintT<Incomp
         res: &mut Vec<LintT<Incompatibility>>,
     ) {
         match &**x {
+            Stmt::Assign(..) if Stmt::is_decorator_assign(x) => {}
             Stmt::Assign(lhs, op_rhs) => match (&**lhs, &(**op_rhs).1.node) {
                 (Assign::Identifier(x), Expr::Identifier(y, _))
                     if x.node.0 == y.node
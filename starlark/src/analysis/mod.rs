@@ -21,6 +21,11 @@ pub(crate) use definition::Definition;
 pub(crate) use definition::DottedDefinition;
 pub(crate) use definition::IdentifierDefinition;
 pub(crate) use definition::LspModule;
+pub use exported::ExportedSymbol;
+pub use exported::ExportedSymbolKind;
+pub(crate) use hierarchy::DefinedSymbol;
+pub use strict::StrictMode;
+pub(crate) use structure::FoldingRegionKind;
 pub use types::EvalMessage;
 pub use types::EvalSeverity;
 pub use types::Lint;
@@ -29,13 +34,18 @@ use crate::analysis::types::LintT;
 use crate::syntax::AstModule;
 
 mod bind;
+mod constant;
 mod definition;
+mod discarded;
 mod dubious;
 mod exported;
 mod flow;
+mod hierarchy;
 mod incompatible;
 mod names;
 mod performance;
+mod strict;
+mod structure;
 mod types;
 
 impl AstModule {
@@ -51,6 +61,16 @@ impl AstModule {
                 .map(LintT::erase),
         );
         res.extend(dubious::dubious(self).into_iter().map(LintT::erase));
+        res.extend(
+            constant::constant_issues(self)
+                .into_iter()
+                .map(LintT::erase),
+        );
+        res.extend(
+            discarded::discarded_issues(self)
+                .into_iter()
+                .map(LintT::erase),
+        );
         res.extend(
             names::name_warnings(self, globals)
                 .into_iter()
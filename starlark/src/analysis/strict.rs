@@ -0,0 +1,288 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Lints which are evaluation errors in strict mode.
+
+use std::collections::HashSet;
+
+use dupe::Dupe;
+use gazebo::variants::VariantName;
+use thiserror::Error;
+
+use crate::analysis::bind;
+use crate::analysis::bind::Bind;
+use crate::analysis::bind::Scope;
+use crate::analysis::names::name_warnings;
+use crate::analysis::names::NameWarning;
+use crate::analysis::types::LintT;
+use crate::analysis::types::LintWarning;
+use crate::codemap::CodeMap;
+use crate::environment::Globals;
+use crate::errors::Diagnostic;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::BinOp;
+use crate::syntax::ast::Expr;
+use crate::syntax::AstModule;
+
+/// Lint findings which are evaluation errors, so that code evaluated dynamically,
+/// which the linter never sees, is held to the same standard.
+/// Set with [`Evaluator::set_strict_mode`](crate::eval::Evaluator::set_strict_mode),
+/// all the checks are disabled by default.
+///
+/// The checks are done on the whole module before it is evaluated,
+/// so a violation fails the evaluation even in code which is never executed.
+#[derive(Debug, Clone, Copy, Dupe, Default, PartialEq, Eq)]
+pub struct StrictMode {
+    /// Is assigning a name of a global an error, e.g. `len = 1` or `def f(list): ...`.
+    pub shadowed_builtins: bool,
+    /// Is concatenating string literals an error, e.g. `"a" + "b"` instead of `"ab"`.
+    pub implicit_string_concat: bool,
+    /// Is a `load()` of a symbol which is never used an error.
+    pub unused_loads: bool,
+}
+
+impl StrictMode {
+    /// All the checks enabled.
+    pub const ALL: StrictMode = StrictMode {
+        shadowed_builtins: true,
+        implicit_string_concat: true,
+        unused_loads: true,
+    };
+}
+
+#[derive(Error, Debug, VariantName)]
+pub(crate) enum StrictModeViolation {
+    #[error("Strict mode: `{0}` shadows a builtin")]
+    ShadowedBuiltin(String),
+    #[error("Strict mode: string literals are concatenated, write a single string literal")]
+    ImplicitStringConcat,
+    #[error("Strict mode: unused `load` of `{0}`")]
+    UnusedLoad(String),
+}
+
+impl LintWarning for StrictModeViolation {
+    fn is_serious(&self) -> bool {
+        true
+    }
+}
+
+fn shadowed_builtins(
+    codemap: &CodeMap,
+    scope: &Scope,
+    globals: &HashSet<&str>,
+    res: &mut Vec<LintT<StrictModeViolation>>,
+) {
+    for x in &scope.inner {
+        match x {
+            Bind::Set(_, x) if globals.contains(x.0.as_str()) => res.push(LintT::new(
+                codemap,
+                x.span,
+                StrictModeViolation::ShadowedBuiltin(x.0.clone()),
+            )),
+            Bind::Scope(scope) => shadowed_builtins(codemap, scope, globals, res),
+            _ => {}
+        }
+    }
+}
+
+fn implicit_string_concat(module: &AstModule, res: &mut Vec<LintT<StrictModeViolation>>) {
+    fn is_string(x: &AstExpr) -> bool {
+        matches!(&**x, Expr::Literal(AstLiteral::String(_)))
+    }
+
+    fn check(codemap: &CodeMap, x: &AstExpr, res: &mut Vec<LintT<StrictModeViolation>>) {
+        match &**x {
+            Expr::Op(lhs, BinOp::Add, rhs) if is_string(lhs) && is_string(rhs) => res.push(
+                LintT::new(codemap, x.span, StrictModeViolation::ImplicitStringConcat),
+            ),
+            _ => {}
+        }
+        x.visit_expr(|x| check(codemap, x, res));
+    }
+
+    module
+        .statement
+        .visit_expr(|x| check(&module.codemap, x, res));
+}
+
+fn unused_loads(module: &AstModule, res: &mut Vec<LintT<StrictModeViolation>>) {
+    for x in name_warnings(module, None) {
+        if let NameWarning::UnusedLoad(name) = x.problem {
+            res.push(LintT {
+                location: x.location,
+                original: x.original,
+                problem: StrictModeViolation::UnusedLoad(name),
+            });
+        }
+    }
+}
+
+impl AstModule {
+    /// Violations of the strict `mode`, sorted by location.
+    pub(crate) fn strict_mode_violations(
+        &self,
+        mode: &StrictMode,
+        globals: &Globals,
+    ) -> Vec<LintT<StrictModeViolation>> {
+        let mut res = Vec::new();
+        if mode.shadowed_builtins {
+            let names: Vec<_> = globals.names().collect();
+            let names = names.iter().map(|x| x.as_str()).collect();
+            shadowed_builtins(&self.codemap, &bind::scope(self), &names, &mut res);
+        }
+        if mode.implicit_string_concat {
+            implicit_string_concat(self, &mut res);
+        }
+        if mode.unused_loads {
+            unused_loads(self, &mut res);
+        }
+        res.sort_by_key(|x| x.location.span.begin());
+        res
+    }
+
+    /// Fail with the first violation of the strict `mode`.
+    pub(crate) fn check_strict_mode(
+        &self,
+        mode: &StrictMode,
+        globals: &Globals,
+    ) -> anyhow::Result<()> {
+        match self
+            .strict_mode_violations(mode, globals)
+            .into_iter()
+            .next()
+        {
+            None => Ok(()),
+            Some(x) => Err(Diagnostic::new(
+                x.problem,
+                x.location.span,
+                &x.location.file,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gazebo::prelude::*;
+
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::eval::Evaluator;
+    use crate::eval::ReturnFileLoader;
+    use crate::eval::StrictMode;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn module(x: &str) -> AstModule {
+        AstModule::parse("bad.bzl", x.to_owned(), &Dialect::Extended).unwrap()
+    }
+
+    fn violations(mode: StrictMode, program: &str) -> Vec<String> {
+        module(program)
+            .strict_mode_violations(&mode, &Globals::standard())
+            .map(|x| x.to_string())
+    }
+
+    #[test]
+    fn test_shadowed_builtins() {
+        let mode = StrictMode {
+            shadowed_builtins: true,
+            ..StrictMode::default()
+        };
+        assert_eq!(
+            &[
+                "bad.bzl:1:1-4: Strict mode: `len` shadows a builtin",
+                "bad.bzl:2:7-11: Strict mode: `list` shadows a builtin",
+                "bad.bzl:3:9-12: Strict mode: `str` shadows a builtin",
+            ],
+            violations(
+                mode,
+                "len = 1\ndef f(list):\n    for str in list:\n        pass\nx = 1"
+            )
+            .as_slice()
+        );
+    }
+
+    #[test]
+    fn test_implicit_string_concat() {
+        let mode = StrictMode {
+            implicit_string_concat: true,
+            ..StrictMode::default()
+        };
+        assert_eq!(
+            &[
+                "bad.bzl:2:12-21: Strict mode: string literals are concatenated, \
+                write a single string literal"
+            ],
+            violations(mode, "def f(x):\n    return \"a\" + \"b\" + x + \"c\"").as_slice()
+        );
+    }
+
+    #[test]
+    fn test_unused_loads() {
+        let mode = StrictMode {
+            unused_loads: true,
+            ..StrictMode::default()
+        };
+        assert_eq!(
+            &["bad.bzl:1:20-25: Strict mode: unused `load` of `bar`"],
+            violations(mode, "load('foo', 'baz', 'bar')\nx = baz").as_slice()
+        );
+        assert!(violations(StrictMode::default(), "load('foo', 'bar')").is_empty());
+    }
+
+    #[test]
+    fn test_eval_strict_mode() {
+        let foo = Module::new();
+        foo.set("bar", foo.heap().alloc(1));
+        let foo = foo.freeze().unwrap();
+        let modules = hashmap!["foo" => &foo];
+        let loader = ReturnFileLoader { modules: &modules };
+
+        let eval = |mode: StrictMode| {
+            let env = Module::new();
+            let mut eval = Evaluator::new(&env);
+            eval.set_loader(&loader);
+            eval.set_strict_mode(mode);
+            eval.eval_module(
+                module("load('foo', 'bar')\nx = 1\nif x == 2:\n    len = 1"),
+                &Globals::standard(),
+            )
+            .map(|_| ())
+        };
+        eval(StrictMode::default()).unwrap();
+        let err = eval(StrictMode::ALL).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Strict mode: unused `load` of `bar`"),
+            "{}",
+            err
+        );
+        // Reported even in code which is not executed.
+        let err = eval(StrictMode {
+            unused_loads: false,
+            ..StrictMode::ALL
+        })
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("`len` shadows a builtin"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,307 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Syntactic structure of a module, used by the LSP to expand the selection
+//! and to fold regions of code.
+
+use crate::analysis::LspModule;
+use crate::codemap::Pos;
+use crate::codemap::ResolvedSpan;
+use crate::codemap::Span;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::Stmt;
+use crate::syntax::uniplate::Visit;
+
+/// What a folding region contains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FoldingRegionKind {
+    /// A statement suite.
+    Suite,
+    /// A bracketed expression, like call arguments or a list.
+    Brackets,
+    /// Consecutive `load()` statements.
+    Imports,
+}
+
+/// Lines of a region which can be folded, the first line stays visible.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FoldingRegion {
+    /// Zero based first line of the region.
+    pub(crate) start_line: usize,
+    /// Zero based last line of the region.
+    pub(crate) end_line: usize,
+    pub(crate) kind: FoldingRegionKind,
+}
+
+impl LspModule {
+    /// Shrink the span to exclude the trailing whitespace.
+    /// Statement spans include the newlines up to the next statement.
+    pub(crate) fn trim_span(&self, span: Span) -> Span {
+        let text = self.ast.codemap.source_span(span);
+        Span::new(span.begin(), span.begin() + text.trim_end().len() as u32)
+    }
+
+    /// Spans of the syntax nodes containing the zero based `line` and `col`,
+    /// from the innermost to the whole file, each span containing the previous one.
+    pub(crate) fn selection_ranges(&self, line: u32, col: u32) -> Vec<ResolvedSpan> {
+        fn visit(node: Visit<AstNoPayload>, pos: Pos, res: &mut Vec<Span>) {
+            let span = match node {
+                Visit::Stmt(x) => x.span,
+                Visit::Expr(x) => x.span,
+            };
+            if !span.contains(pos) {
+                return;
+            }
+            res.push(span);
+
+            // Nodes which are not visited as statements or expressions.
+            let mut parts = Vec::new();
+            match node {
+                Visit::Stmt(x) => match &x.node {
+                    Stmt::Def(def) => {
+                        parts.push(def.name.span);
+                        parts.extend(def.params.iter().map(|x| x.span));
+                    }
+                    Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) | Stmt::For(lhs, _) => {
+                        parts.push(lhs.span)
+                    }
+                    Stmt::Load(load) => {
+                        parts.push(load.module.span);
+                        for (local, name) in &load.args {
+                            parts.push(local.span.merge(name.span));
+                        }
+                    }
+                    _ => {}
+                },
+                Visit::Expr(x) => {
+                    if let Expr::Call(_, args) = &x.node {
+                        parts.extend(args.iter().map(|x| x.span));
+                    }
+                }
+            }
+            res.extend(parts.into_iter().filter(|x| x.contains(pos)));
+
+            node.visit_children(|x| visit(x, pos, res));
+        }
+
+        let line_span = self.ast.codemap.line_span(line as usize);
+        let pos = std::cmp::min(line_span.begin() + col, line_span.end());
+        let mut spans = Vec::new();
+        visit(Visit::Stmt(&self.ast.statement), pos, &mut spans);
+        let mut spans: Vec<Span> = spans
+            .into_iter()
+            .map(|x| self.trim_span(x))
+            .filter(|x| x.contains(pos))
+            .collect();
+        spans.push(self.ast.codemap.full_span());
+        spans.sort_by_key(|x| (x.len(), std::cmp::Reverse(x.begin())));
+        spans.dedup();
+        spans
+            .into_iter()
+            .map(|x| self.ast.codemap.resolve_span(x))
+            .collect()
+    }
+
+    /// Regions of the module which can be folded: statement suites, multi-line bracketed
+    /// expressions like call arguments, and `load()` statements. Sorted by the first line.
+    pub(crate) fn folding_regions(&self) -> Vec<FoldingRegion> {
+        let mut res = Vec::new();
+        self.visit_folding_regions(Visit::Stmt(&self.ast.statement), &mut res);
+        self.load_folding_regions(&mut res);
+        res.sort_by_key(|x| (x.start_line, std::cmp::Reverse(x.end_line)));
+        res
+    }
+
+    fn region(&self, start: Pos, end: Pos, kind: FoldingRegionKind) -> Option<FoldingRegion> {
+        let start_line = self.ast.codemap.find_line(start);
+        let mut end_line = self.ast.codemap.find_line(end);
+        // Keep the closing bracket of an expression visible when it is on its own line.
+        let last_line = self.ast.codemap.source_line(end_line).trim_start();
+        if kind == FoldingRegionKind::Brackets && last_line.starts_with([')', ']', '}']) {
+            end_line -= 1;
+        }
+        (start_line < end_line).then_some(FoldingRegion {
+            start_line,
+            end_line,
+            kind,
+        })
+    }
+
+    /// Region from the header line of a statement to the end of its suite.
+    fn suite_region(&self, header: Pos, suite: &AstStmt) -> Option<FoldingRegion> {
+        let end = self.trim_span(suite.span).end();
+        self.region(header, end, FoldingRegionKind::Suite)
+    }
+
+    fn visit_folding_regions(&self, node: Visit<AstNoPayload>, res: &mut Vec<FoldingRegion>) {
+        match node {
+            Visit::Stmt(x) => match &x.node {
+                Stmt::Def(def) => res.extend(self.suite_region(x.span.begin(), &def.body)),
+                Stmt::If(_, body) | Stmt::While(_, body) => {
+                    res.extend(self.suite_region(x.span.begin(), body))
+                }
+                Stmt::For(_, over_body) => {
+                    res.extend(self.suite_region(x.span.begin(), &over_body.1))
+                }
+                Stmt::IfElse(_, then_else) => {
+                    let (then_block, else_block) = &**then_else;
+                    res.extend(self.suite_region(x.span.begin(), then_block));
+                    // `elif` is a nested `if` statement, which has its own region.
+                    if !matches!(else_block.node, Stmt::If(..) | Stmt::IfElse(..)) {
+                        let between = Span::new(
+                            self.trim_span(then_block.span).end(),
+                            else_block.span.begin(),
+                        );
+                        let header = match self.ast.codemap.source_span(between).rfind("else") {
+                            Some(offset) => between.begin() + offset as u32,
+                            None => else_block.span.begin(),
+                        };
+                        res.extend(self.suite_region(header, else_block));
+                    }
+                }
+                _ => {}
+            },
+            Visit::Expr(x) => match &x.node {
+                Expr::Call(..)
+                | Expr::List(..)
+                | Expr::Dict(..)
+                | Expr::ListComprehension(..)
+                | Expr::DictComprehension(..) => res.extend(self.region(
+                    x.span.begin(),
+                    x.span.end(),
+                    FoldingRegionKind::Brackets,
+                )),
+                _ => {}
+            },
+        }
+        node.visit_children(|x| self.visit_folding_regions(x, res));
+    }
+
+    /// Regions of consecutive top level `load()` statements.
+    fn load_folding_regions(&self, res: &mut Vec<FoldingRegion>) {
+        let mut group: Option<Span> = None;
+        let mut add_group = |group: Option<Span>| {
+            if let Some(group) = group {
+                let group = self.trim_span(group);
+                res.extend(self.region(group.begin(), group.end(), FoldingRegionKind::Imports));
+            }
+        };
+        self.ast.statement.visit_stmt(|x| match (&x.node, group) {
+            (Stmt::Load(_), Some(span)) => group = Some(span.merge(x.span)),
+            (Stmt::Load(_), None) => group = Some(x.span),
+            _ => add_group(group.take()),
+        });
+        add_group(group);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use textwrap::dedent;
+
+    use crate::analysis::definition::helpers::FixtureWithRanges;
+    use crate::analysis::structure::FoldingRegion;
+    use crate::analysis::structure::FoldingRegionKind;
+
+    fn fixture(program: &str) -> FixtureWithRanges {
+        FixtureWithRanges::from_fixture("foo.star", dedent(program).trim()).unwrap()
+    }
+
+    #[test]
+    fn test_selection_ranges() -> anyhow::Result<()> {
+        let fixture = fixture(
+            r#"
+            <all>load("bar.star", "baz")
+            <def>def f(x):
+                <body><if>if x:
+                    <suite><stmt>return <call>baz(<arg>1 + <x>x</x></arg>, y = 2)</call></stmt>
+                    pass</suite></if>
+                return None</body></def></all>
+            "#,
+        );
+        let module = fixture.module()?;
+        let x = fixture.span("x");
+        let expected = [
+            "x", "arg", "call", "stmt", "suite", "if", "body", "def", "all",
+        ]
+        .map(|x| {
+            let span = fixture.span(x);
+            (
+                span.begin_line,
+                span.begin_column,
+                span.end_line,
+                span.end_column,
+            )
+        });
+        let actual = module
+            .selection_ranges(x.begin_line as u32, x.begin_column as u32)
+            .into_iter()
+            .map(|x| (x.begin_line, x.begin_column, x.end_line, x.end_column))
+            .collect::<Vec<_>>();
+        assert_eq!(expected.to_vec(), actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_folding_regions() -> anyhow::Result<()> {
+        let module = fixture(
+            r#"
+            load("a.star", "a")
+            load(
+                "b.star",
+                "b",
+            )
+
+            def f(x):
+                if x:
+                    return a(
+                        1,
+                        2,
+                    )
+                elif x == 1:
+                    pass
+                    pass
+                else:
+                    pass
+                    pass
+                return [
+                    y for y in b]
+            "#,
+        )
+        .module()?;
+        let region = |start_line, end_line, kind| FoldingRegion {
+            start_line,
+            end_line,
+            kind,
+        };
+        assert_eq!(
+            vec![
+                region(0, 4, FoldingRegionKind::Imports),
+                region(6, 19, FoldingRegionKind::Suite),
+                region(7, 11, FoldingRegionKind::Suite),
+                region(8, 10, FoldingRegionKind::Brackets),
+                region(12, 14, FoldingRegionKind::Suite),
+                region(15, 17, FoldingRegionKind::Suite),
+                region(18, 19, FoldingRegionKind::Brackets),
+            ],
+            module.folding_regions()
+        );
+        Ok(())
+    }
+}
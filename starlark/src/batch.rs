@@ -0,0 +1,246 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Evaluation of a function of one program for many inputs.
+//!
+//! The program is parsed, evaluated and frozen once, then its function is called for each input
+//! in a fresh [`Module`], optionally on several threads. Values defined at the top level of the
+//! program are shared by all the calls, and being frozen, can't be modified by them.
+//!
+//! ```
+//! use starlark::batch::evaluate_many;
+//! use starlark::batch::BatchProgram;
+//! use starlark::batch::Inputs;
+//!
+//! let program = BatchProgram::new(
+//!     "double.star",
+//!     "FACTOR = 2\ndef double(x): return x * FACTOR",
+//!     "double",
+//! );
+//! let inputs = (1..=3).map(|x| Inputs {
+//!     positional: vec![x.into()],
+//!     ..Inputs::default()
+//! });
+//! let results = evaluate_many(&program, inputs).unwrap();
+//! let results: Vec<String> = results.into_iter().map(|x| x.unwrap().to_string()).collect();
+//! assert_eq!(vec!["2", "4", "6"], results);
+//! ```
+
+use std::cmp;
+use std::panic;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use gazebo::prelude::*;
+
+use crate::environment::Globals;
+use crate::environment::Module;
+use crate::eval::Evaluator;
+use crate::stdlib::json::serde_to_starlark;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+use crate::values::OwnedFrozenValue;
+
+/// Name of the variable holding the result in the module of a call.
+const RESULT: &str = "result";
+
+/// A program and the function in it which [`evaluate_many`] calls for each input.
+#[derive(Debug, Clone)]
+pub struct BatchProgram {
+    /// Name of the program file, used in error messages.
+    pub filename: String,
+    /// Source of the program.
+    pub source: String,
+    /// Dialect the program is parsed with.
+    pub dialect: Dialect,
+    /// Globals available to the program.
+    pub globals: Globals,
+    /// Name of the function defined by the program which is called for each input.
+    pub function: String,
+    /// Number of threads evaluating the inputs, `1` evaluates them on the current thread.
+    pub threads: usize,
+}
+
+/// Arguments of one call of the [`BatchProgram`] function.
+#[derive(Debug, Clone, Default)]
+pub struct Inputs {
+    /// Positional arguments.
+    pub positional: Vec<serde_json::Value>,
+    /// Named arguments.
+    pub named: Vec<(String, serde_json::Value)>,
+}
+
+impl BatchProgram {
+    /// Program calling `function`, parsed with the extended dialect, evaluated with the
+    /// standard globals on the current thread.
+    pub fn new(filename: &str, source: &str, function: &str) -> Self {
+        Self {
+            filename: filename.to_owned(),
+            source: source.to_owned(),
+            dialect: Dialect::Extended,
+            globals: Globals::standard(),
+            function: function.to_owned(),
+            threads: 1,
+        }
+    }
+
+    /// Evaluate and freeze the program, returning its function.
+    fn compile(&self) -> anyhow::Result<OwnedFrozenValue> {
+        let ast = AstModule::parse(&self.filename, self.source.clone(), &self.dialect)?;
+        let module = Module::new();
+        Evaluator::new(&module).eval_module(ast, &self.globals)?;
+        module.freeze()?.get(&self.function)
+    }
+}
+
+/// Call the function of `program` with each of `inputs`.
+///
+/// Fails if the program itself fails to parse or evaluate, otherwise returns the result of
+/// each call, in the order of `inputs`.
+pub fn evaluate_many(
+    program: &BatchProgram,
+    inputs: impl IntoIterator<Item = Inputs>,
+) -> anyhow::Result<Vec<anyhow::Result<OwnedFrozenValue>>> {
+    let function = program.compile()?;
+    let inputs: Vec<Inputs> = inputs.into_iter().collect();
+    Ok(map_on_threads(&inputs, program.threads, |x| {
+        evaluate_one(&function, x)
+    }))
+}
+
+/// Apply `f` to each of `inputs` on up to `threads` threads, returning the results
+/// in the order of `inputs`. With one thread `f` is called on the current thread.
+pub(crate) fn map_on_threads<T: Sync, R: Send>(
+    inputs: &[T],
+    threads: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let threads = cmp::min(threads, inputs.len());
+    if threads <= 1 {
+        return inputs.map(f);
+    }
+
+    // Inputs are claimed one at a time, so a slow input doesn't hold up a whole share of them.
+    let next = AtomicUsize::new(0);
+    let mut results = Vec::with_capacity(inputs.len());
+    results.resize_with(inputs.len(), || None);
+    thread::scope(|scope| {
+        let workers = (0..threads).map(|_| {
+            scope.spawn(|| {
+                let mut done = Vec::new();
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    match inputs.get(i) {
+                        Some(x) => done.push((i, f(x))),
+                        None => return done,
+                    }
+                }
+            })
+        });
+        for worker in workers.collect::<Vec<_>>() {
+            let done = worker.join().unwrap_or_else(|e| panic::resume_unwind(e));
+            for (i, result) in done {
+                results[i] = Some(result);
+            }
+        }
+    });
+    results.into_map(|x| x.expect("every input is evaluated"))
+}
+
+fn evaluate_one(function: &OwnedFrozenValue, inputs: &Inputs) -> anyhow::Result<OwnedFrozenValue> {
+    let module = Module::new();
+    let heap = module.heap();
+    let positional = inputs
+        .positional
+        .try_map(|x| serde_to_starlark(x.clone(), heap))?;
+    let named = inputs
+        .named
+        .try_map(|(k, v)| anyhow::Ok((k.as_str(), serde_to_starlark(v.clone(), heap)?)))?;
+    let function = function.owned_value(module.frozen_heap());
+    let result = Evaluator::new(&module).eval_function(function, &positional, &named)?;
+    module.set(RESULT, result);
+    module.freeze()?.get(RESULT)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::batch::evaluate_many;
+    use crate::batch::BatchProgram;
+    use crate::batch::Inputs;
+
+    const PROGRAM: &str = r#"
+def describe(x, suffix = "!"):
+    if x < 0:
+        fail("negative")
+    return {"x": x, "text": str(x) + suffix}
+"#;
+
+    fn results(program: &BatchProgram, inputs: Vec<Inputs>) -> Vec<String> {
+        evaluate_many(program, inputs)
+            .unwrap()
+            .into_iter()
+            .map(|x| match x {
+                Ok(x) => x.to_string(),
+                Err(e) => format!("error: {}", e),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_evaluate_many() {
+        let inputs = |n: i64| {
+            (0..n)
+                .map(|x| Inputs {
+                    positional: vec![json!(x - 1)],
+                    named: vec![("suffix".to_owned(), json!("?"))],
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut program = BatchProgram::new("describe.star", PROGRAM, "describe");
+        let expected = vec![
+            r#"{"x": 0, "text": "0?"}"#.to_owned(),
+            r#"{"x": 1, "text": "1?"}"#.to_owned(),
+        ];
+        let res = results(&program, inputs(3));
+        assert!(res[0].contains("fail: negative"), "{}", res[0]);
+        assert_eq!(expected, res[1..]);
+
+        let serial = results(&program, inputs(50));
+        program.threads = 4;
+        assert_eq!(serial, results(&program, inputs(50)));
+        assert_eq!(expected, results(&program, inputs(3))[1..]);
+        assert!(results(&program, Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_many_shared_state_is_frozen() {
+        let program = BatchProgram::new("f.star", "XS = []\ndef f(): XS.append(1)", "f");
+        let res = results(&program, vec![Inputs::default()]);
+        assert!(res[0].contains("Immutable"), "{}", res[0]);
+    }
+
+    #[test]
+    fn test_evaluate_many_program_errors() {
+        let program = BatchProgram::new("f.star", "def f(:", "f");
+        assert!(evaluate_many(&program, Vec::new()).is_err());
+        let program = BatchProgram::new("f.star", "def g(): pass", "f");
+        assert!(evaluate_many(&program, Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Benchmarks of whole Starlark programs.
+//!
+//! A program is evaluated in a fresh [`Module`] a few times to warm up, then a number of
+//! times while measuring the time of each evaluation and the heap memory it uses.
+//! Parsing is not measured. The [`BenchResult`] serializes to JSON, so results can be stored
+//! and compared across versions of the interpreter.
+//!
+//! ```
+//! use starlark::bench::run_benchmark;
+//! use starlark::bench::BenchProgram;
+//!
+//! let mut program = BenchProgram::new("squares.star", "x = [i * i for i in range(1000)]");
+//! program.iterations = 3;
+//! let result = run_benchmark(&program).unwrap();
+//! assert_eq!(3, result.iterations);
+//! assert!(result.min_nanos <= result.max_nanos);
+//! ```
+
+use std::time::Duration;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::environment::Globals;
+use crate::environment::Module;
+use crate::eval::Evaluator;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+/// A program measured by [`run_benchmark`].
+#[derive(Debug, Clone)]
+pub struct BenchProgram {
+    /// Name of the program file, used in error messages and in the result.
+    pub filename: String,
+    /// Source of the program.
+    pub source: String,
+    /// Dialect the program is parsed with.
+    pub dialect: Dialect,
+    /// Globals available to the program.
+    pub globals: Globals,
+    /// Number of evaluations before measuring.
+    pub warmup: usize,
+    /// Number of measured evaluations, at least one.
+    pub iterations: usize,
+}
+
+/// Measurements of a [`BenchProgram`].
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchResult {
+    /// Name of the program file.
+    pub name: String,
+    /// Number of measured evaluations.
+    pub iterations: usize,
+    /// Fastest evaluation.
+    pub min_nanos: u64,
+    /// Median evaluation time.
+    pub median_nanos: u64,
+    /// Mean evaluation time.
+    pub mean_nanos: u64,
+    /// Slowest evaluation.
+    pub max_nanos: u64,
+    /// Bytes allocated on the heap of the module by the end of an evaluation,
+    /// the largest of all evaluations.
+    pub allocated_bytes: usize,
+    /// Peak bytes allocated on the heap of the module during an evaluation,
+    /// the largest of all evaluations. Differs from `allocated_bytes` if garbage
+    /// collection ran during the evaluation.
+    pub peak_heap_bytes: usize,
+}
+
+impl BenchProgram {
+    /// Program parsed with the extended dialect and evaluated with the extended globals,
+    /// with one warmup evaluation and ten measured evaluations.
+    pub fn new(filename: &str, source: &str) -> Self {
+        Self {
+            filename: filename.to_owned(),
+            source: source.to_owned(),
+            dialect: Dialect::Extended,
+            globals: Globals::extended(),
+            warmup: 1,
+            iterations: 10,
+        }
+    }
+
+    /// Evaluate the program once, returning the time and the heap usage.
+    fn evaluate(&self) -> anyhow::Result<(Duration, usize, usize)> {
+        let ast = AstModule::parse(&self.filename, self.source.clone(), &self.dialect)?;
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        let start = Instant::now();
+        eval.eval_module(ast, &self.globals)?;
+        let time = start.elapsed();
+        drop(eval);
+        let heap = module.heap();
+        Ok((time, heap.allocated_bytes(), heap.peak_allocated_bytes()))
+    }
+}
+
+/// Evaluate `program` repeatedly and report the measurements.
+///
+/// Fails if any evaluation fails.
+pub fn run_benchmark(program: &BenchProgram) -> anyhow::Result<BenchResult> {
+    for _ in 0..program.warmup {
+        program.evaluate()?;
+    }
+    let iterations = program.iterations.max(1);
+    let mut times = Vec::with_capacity(iterations);
+    let mut allocated_bytes = 0;
+    let mut peak_heap_bytes = 0;
+    for _ in 0..iterations {
+        let (time, allocated, peak) = program.evaluate()?;
+        times.push(time.as_nanos() as u64);
+        allocated_bytes = allocated_bytes.max(allocated);
+        peak_heap_bytes = peak_heap_bytes.max(peak);
+    }
+    times.sort_unstable();
+    Ok(BenchResult {
+        name: program.filename.clone(),
+        iterations,
+        min_nanos: times[0],
+        median_nanos: times[iterations / 2],
+        mean_nanos: times.iter().sum::<u64>() / iterations as u64,
+        max_nanos: times[iterations - 1],
+        allocated_bytes,
+        peak_heap_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bench::run_benchmark;
+    use crate::bench::BenchProgram;
+
+    #[test]
+    fn test_run_benchmark() {
+        let mut program = BenchProgram::new("test.star", "x = [str(i) for i in range(100)]");
+        program.warmup = 0;
+        program.iterations = 5;
+        let result = run_benchmark(&program).unwrap();
+        assert_eq!(5, result.iterations);
+        assert!(result.min_nanos <= result.median_nanos);
+        assert!(result.median_nanos <= result.max_nanos);
+        assert!(result.allocated_bytes > 0);
+        assert!(result.peak_heap_bytes >= result.allocated_bytes);
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!("test.star", json["name"]);
+        assert_eq!(5, json["iterations"]);
+    }
+
+    #[test]
+    fn test_run_benchmark_fails() {
+        let program = BenchProgram::new("test.star", "fail('oops')");
+        let err = run_benchmark(&program).unwrap_err();
+        assert!(err.to_string().contains("oops"), "{}", err);
+    }
+}
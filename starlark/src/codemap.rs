@@ -36,6 +36,11 @@ use allocative::Allocative;
 use dupe::Dupe;
 use once_cell::sync::Lazy;
 
+pub use crate::codemap::source_map::SourceMap;
+pub use crate::codemap::source_map::SourceMapFile;
+
+mod source_map;
+
 /// A small, `Copy`, value representing a position in a `CodeMap`'s file.
 #[derive(
     Copy, Clone, Dupe, Hash, Eq, PartialEq, PartialOrd, Ord, Debug, Default, Allocative
@@ -47,6 +52,11 @@ impl Pos {
     pub const fn new(x: u32) -> Self {
         Self(x)
     }
+
+    /// The byte offset.
+    pub(crate) fn get(self) -> u32 {
+        self.0
+    }
 }
 
 impl Add<u32> for Pos {
@@ -180,6 +190,8 @@ struct CodeMapData {
     source: String,
     /// Byte positions of line beginnings.
     lines: Vec<Pos>,
+    /// Locations in the original sources, for generated code.
+    source_map: Option<SourceMap>,
 }
 
 /// "Codemap" for `.rs` files.
@@ -241,6 +253,15 @@ impl Hash for CodeMap {
 impl CodeMap {
     /// Creates an new `CodeMap`.
     pub(crate) fn new(filename: String, source: String) -> CodeMap {
+        Self::new_with_source_map(filename, source, None)
+    }
+
+    /// Creates a `CodeMap` for generated code.
+    pub(crate) fn new_with_source_map(
+        filename: String,
+        source: String,
+        source_map: Option<SourceMap>,
+    ) -> CodeMap {
         let mut lines = vec![Pos(0)];
         lines.extend(source.match_indices('\n').map(|(p, _)| Pos(p as u32 + 1)));
 
@@ -248,6 +269,7 @@ impl CodeMap {
             filename,
             source,
             lines,
+            source_map,
         })))
     }
 
@@ -280,6 +302,17 @@ impl CodeMap {
         }
     }
 
+    /// Like [`file_span`](CodeMap::file_span), but for generated code the span
+    /// in the original source, if the [`SourceMap`] has it.
+    pub(crate) fn original_file_span(&self, span: Span) -> FileSpan {
+        if let CodeMapImpl::Real(data) = &self.0 {
+            if let Some(x) = data.source_map.as_ref().and_then(|x| x.map(span)) {
+                return x;
+            }
+        }
+        self.file_span(span)
+    }
+
     /// Gets the name of the file
     pub fn filename(&self) -> &str {
         match &self.0 {
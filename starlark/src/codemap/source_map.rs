@@ -0,0 +1,294 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Source maps of generated Starlark code.
+
+use std::ops::Range;
+
+use allocative::Allocative;
+use dupe::Dupe;
+
+use crate::codemap::CodeMap;
+use crate::codemap::FileSpan;
+use crate::codemap::Pos;
+use crate::codemap::Span;
+
+/// Locations in the original sources of generated Starlark code, e.g. the templates
+/// a `.bzl` file was generated from. Parsing with
+/// [`AstModule::parse_with_source_map`](crate::syntax::AstModule::parse_with_source_map)
+/// makes diagnostics and stack traces point at the original sources rather than
+/// at the generated code.
+///
+/// The generated code is mapped in ranges, usually one per token. A span of the generated
+/// code is mapped from the ranges at its beginning and end, parts of the generated code
+/// which are not mapped keep their generated location.
+///
+/// ```
+/// use starlark::codemap::SourceMap;
+/// use starlark::syntax::AstModule;
+/// use starlark::syntax::Dialect;
+///
+/// let mut source_map = SourceMap::new();
+/// let template = source_map.add_file("rules.tmpl", "x = {{ value }}".to_owned());
+/// // The generated code is `x = )`, with the `)` coming from `{{ value }}` of the template.
+/// source_map.add(4..5, template, 4..15);
+/// let err = AstModule::parse_with_source_map(
+///     "rules.bzl",
+///     "x = )".to_owned(),
+///     &Dialect::Standard,
+///     source_map,
+/// )
+/// .unwrap_err();
+/// assert!(err.to_string().contains("--> rules.tmpl:1:5"));
+/// ```
+#[derive(Debug, Default, Allocative)]
+pub struct SourceMap {
+    files: Vec<CodeMap>,
+    /// Sorted and not overlapping.
+    mappings: Vec<Mapping>,
+}
+
+/// An original source file of a [`SourceMap`].
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq)]
+pub struct SourceMapFile(usize);
+
+#[derive(Debug, Clone, Copy, Allocative)]
+struct Mapping {
+    generated: Span,
+    file: usize,
+    original: Span,
+}
+
+fn span(range: Range<usize>) -> Span {
+    Span::new(Pos::new(range.start as u32), Pos::new(range.end as u32))
+}
+
+impl Mapping {
+    /// Original position of `pos` within the generated range, `default` if the original code
+    /// differs from the generated code, so positions within them can't be matched.
+    fn map(&self, pos: Pos, default: Pos) -> Pos {
+        if self.generated.len() == self.original.len() {
+            self.original.begin() + (pos.get() - self.generated.begin().get())
+        } else {
+            default
+        }
+    }
+}
+
+impl SourceMap {
+    /// An empty source map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an original source file.
+    pub fn add_file(&mut self, filename: &str, source: String) -> SourceMapFile {
+        self.files.push(CodeMap::new(filename.to_owned(), source));
+        SourceMapFile(self.files.len() - 1)
+    }
+
+    /// Map the byte range `generated` of the generated code to the byte range `original`
+    /// of `file`. If both have the same length, positions within them are mapped one to one.
+    ///
+    /// Panics if `original` is not within `file` or `generated` overlaps a range already mapped.
+    pub fn add(&mut self, generated: Range<usize>, file: SourceMapFile, original: Range<usize>) {
+        let generated = span(generated);
+        let original = span(original);
+        // Check the span is valid: this will panic if the span is not valid.
+        self.files[file.0].source_span(original);
+        let i = self
+            .mappings
+            .partition_point(|x| x.generated.begin() < generated.begin());
+        let overlaps = |x: &Mapping| {
+            x.generated.begin() < generated.end() && generated.begin() < x.generated.end()
+        };
+        let overlaps_prev = i > 0 && overlaps(&self.mappings[i - 1]);
+        let overlaps_next = self.mappings.get(i).is_some_and(overlaps);
+        assert!(
+            !overlaps_prev && !overlaps_next,
+            "source map ranges overlap"
+        );
+        self.mappings.insert(
+            i,
+            Mapping {
+                generated,
+                file: file.0,
+                original,
+            },
+        );
+    }
+
+    /// Original location of the beginning of `span`: the first mapped range
+    /// which intersects it.
+    fn map_begin(&self, span: Span) -> Option<(usize, Pos)> {
+        let i = self
+            .mappings
+            .partition_point(|x| x.generated.end() <= span.begin());
+        let x = self.mappings.get(i)?;
+        if x.generated.begin() <= span.begin() {
+            Some((x.file, x.map(span.begin(), x.original.begin())))
+        } else if x.generated.begin() < span.end() {
+            Some((x.file, x.original.begin()))
+        } else {
+            None
+        }
+    }
+
+    /// Original location of the end of `span`: the last mapped range which intersects it.
+    fn map_end(&self, span: Span) -> Option<(usize, Pos)> {
+        let i = self
+            .mappings
+            .partition_point(|x| x.generated.begin() < span.end());
+        let x = self.mappings.get(i.checked_sub(1)?)?;
+        if span.end() <= x.generated.end() {
+            Some((x.file, x.map(span.end(), x.original.end())))
+        } else if span.begin() < x.generated.end() {
+            Some((x.file, x.original.end()))
+        } else {
+            None
+        }
+    }
+
+    /// Original location of a span of the generated code, `None` if it is not mapped.
+    pub(crate) fn map(&self, span: Span) -> Option<FileSpan> {
+        let (file, begin) = self.map_begin(span)?;
+        let original = if span.len() == 0 {
+            Span::new(begin, begin)
+        } else {
+            match self.map_end(span) {
+                Some((end_file, end)) if end_file == file && begin <= end => Span::new(begin, end),
+                // The span ends in another file or before it begins,
+                // e.g. because the generator reordered the code.
+                _ => Span::new(begin, begin),
+            }
+        };
+        Some(self.files[file].file_span(original))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codemap::Pos;
+    use crate::codemap::SourceMap;
+    use crate::codemap::Span;
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::errors::Diagnostic;
+    use crate::eval::Evaluator;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    // Template where `{{ divisor }}` expands to `0`.
+    const TEMPLATE: &str = "def f(x):\n    return x // {{ divisor }}\n\nf(1)\n";
+    const GENERATED: &str = "# generated\ndef f(x):\n    return x // 0\n\nf(1)\n";
+
+    fn source_map() -> SourceMap {
+        let mut source_map = SourceMap::new();
+        let template = source_map.add_file("f.tmpl", TEMPLATE.to_owned());
+        let offset = GENERATED.find("def").unwrap();
+        let divisor = GENERATED.find('0').unwrap();
+        let original = TEMPLATE.find("{{").unwrap();
+        // Code before and after the expanded template variable is copied as is.
+        source_map.add(offset..divisor, template, 0..original);
+        source_map.add(
+            divisor..divisor + 1,
+            template,
+            original..original + "{{ divisor }}".len(),
+        );
+        source_map.add(
+            divisor + 1..GENERATED.len(),
+            template,
+            original + "{{ divisor }}".len()..TEMPLATE.len(),
+        );
+        source_map
+    }
+
+    fn eval_error() -> anyhow::Error {
+        let ast = AstModule::parse_with_source_map(
+            "f.bzl",
+            GENERATED.to_owned(),
+            &Dialect::Standard,
+            source_map(),
+        )
+        .unwrap();
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.eval_module(ast, &Globals::standard()).unwrap_err()
+    }
+
+    #[test]
+    fn test_error_span() {
+        let err = eval_error();
+        let diagnostic = err.downcast_ref::<Diagnostic>().unwrap();
+        let span = diagnostic.span.as_ref().unwrap();
+        assert_eq!("f.tmpl", span.filename());
+        assert_eq!("x // {{ divisor }}", span.source_span());
+    }
+
+    #[test]
+    fn test_stack_trace() {
+        let err = eval_error();
+        let diagnostic = err.downcast::<Diagnostic>().unwrap();
+        let locations: Vec<String> = diagnostic
+            .call_stack
+            .into_frames()
+            .iter()
+            .map(|x| x.location.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(vec!["f.tmpl:4:1-5"], locations);
+    }
+
+    #[test]
+    fn test_unmapped() {
+        let err = AstModule::parse_with_source_map(
+            "f.bzl",
+            "x = (".to_owned(),
+            &Dialect::Standard,
+            SourceMap::new(),
+        )
+        .unwrap_err();
+        let span = err.downcast_ref::<Diagnostic>().unwrap().span.as_ref();
+        assert_eq!("f.bzl", span.unwrap().filename());
+    }
+
+    #[test]
+    fn test_map() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("f.tmpl", "abcdefghij".to_owned());
+        source_map.add(10..12, file, 2..4);
+        source_map.add(0..5, file, 5..6);
+        let map = |begin, end| {
+            let span = source_map.map(Span::new(Pos::new(begin), Pos::new(end)))?;
+            Some((span.span.begin().get(), span.span.end().get()))
+        };
+        assert_eq!(Some((2, 4)), map(10, 12));
+        assert_eq!(Some((3, 3)), map(11, 11));
+        assert_eq!(Some((5, 6)), map(1, 3));
+        // Begins after it ends in the original code.
+        assert_eq!(Some((5, 5)), map(3, 11));
+        assert_eq!(None, map(6, 9));
+    }
+
+    #[test]
+    #[should_panic(expected = "source map ranges overlap")]
+    fn test_overlap() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("f.tmpl", "abcdefghij".to_owned());
+        source_map.add(0..5, file, 0..5);
+        source_map.add(4..6, file, 0..2);
+    }
+}
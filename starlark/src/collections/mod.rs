@@ -33,5 +33,6 @@ pub use starlark_map::StarlarkHasher;
 pub(crate) mod aligned_padded_str;
 pub(crate) mod alloca;
 pub(crate) mod maybe_uninit_backport;
+pub(crate) mod small_value_vec;
 pub(crate) mod string_pool;
 pub(crate) mod symbol_map;
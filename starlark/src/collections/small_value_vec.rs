@@ -0,0 +1,233 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Vector of values which stores a few elements inline.
+//!
+//! Used when collecting function arguments: most calls pass only a few arguments,
+//! so we avoid a heap allocation for them.
+
+use std::fmt;
+use std::fmt::Debug;
+use std::mem::MaybeUninit;
+use std::ops::Deref;
+use std::slice;
+
+use allocative::Allocative;
+
+use crate::values::Freeze;
+use crate::values::Freezer;
+use crate::values::Trace;
+use crate::values::Tracer;
+
+/// Number of elements stored without heap allocation.
+pub(crate) const SMALL_VALUE_VEC_INLINE_CAPACITY: usize = 8;
+
+#[derive(Clone)]
+enum Repr<V: Copy> {
+    /// First `len` elements are initialized.
+    Inline {
+        len: usize,
+        items: [MaybeUninit<V>; SMALL_VALUE_VEC_INLINE_CAPACITY],
+    },
+    Heap(Vec<V>),
+}
+
+/// Vector of `Copy` elements (usually `Value` or `FrozenValue`)
+/// which stores up to [`SMALL_VALUE_VEC_INLINE_CAPACITY`] elements inline.
+#[derive(Clone)]
+pub(crate) struct SmallValueVec<V: Copy>(Repr<V>);
+
+impl<V: Copy> SmallValueVec<V> {
+    /// Empty vector.
+    #[inline]
+    pub(crate) fn new() -> SmallValueVec<V> {
+        SmallValueVec(Repr::Inline {
+            len: 0,
+            items: [MaybeUninit::uninit(); SMALL_VALUE_VEC_INLINE_CAPACITY],
+        })
+    }
+
+    /// Elements as a slice.
+    #[inline]
+    pub(crate) fn as_slice(&self) -> &[V] {
+        match &self.0 {
+            Repr::Inline { len, items } => unsafe {
+                slice::from_raw_parts(items.as_ptr() as *const V, *len)
+            },
+            Repr::Heap(vec) => vec,
+        }
+    }
+
+    /// Elements as a mutable slice.
+    #[inline]
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [V] {
+        match &mut self.0 {
+            Repr::Inline { len, items } => unsafe {
+                slice::from_raw_parts_mut(items.as_mut_ptr() as *mut V, *len)
+            },
+            Repr::Heap(vec) => vec,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn spill_and_push(&mut self, value: V) {
+        let mut vec = Vec::with_capacity(SMALL_VALUE_VEC_INLINE_CAPACITY * 2);
+        vec.extend_from_slice(self.as_slice());
+        vec.push(value);
+        self.0 = Repr::Heap(vec);
+    }
+
+    /// Append an element.
+    #[inline]
+    pub(crate) fn push(&mut self, value: V) {
+        match &mut self.0 {
+            Repr::Inline { len, items } if *len < SMALL_VALUE_VEC_INLINE_CAPACITY => {
+                items[*len] = MaybeUninit::new(value);
+                *len += 1;
+            }
+            Repr::Inline { .. } => self.spill_and_push(value),
+            Repr::Heap(vec) => vec.push(value),
+        }
+    }
+}
+
+impl<V: Copy> Default for SmallValueVec<V> {
+    #[inline]
+    fn default() -> Self {
+        SmallValueVec::new()
+    }
+}
+
+impl<V: Copy> Deref for SmallValueVec<V> {
+    type Target = [V];
+
+    #[inline]
+    fn deref(&self) -> &[V] {
+        self.as_slice()
+    }
+}
+
+impl<V: Copy + Debug> Debug for SmallValueVec<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.as_slice(), f)
+    }
+}
+
+impl<V: Copy + PartialEq> PartialEq for SmallValueVec<V> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<V: Copy + Eq> Eq for SmallValueVec<V> {}
+
+impl<V: Copy> Extend<V> for SmallValueVec<V> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = V>>(&mut self, iter: I) {
+        for v in iter {
+            self.push(v);
+        }
+    }
+}
+
+impl<V: Copy> FromIterator<V> for SmallValueVec<V> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = V>>(iter: I) -> Self {
+        let mut vec = SmallValueVec::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl<'a, V: Copy> IntoIterator for &'a SmallValueVec<V> {
+    type Item = &'a V;
+    type IntoIter = slice::Iter<'a, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_slice().iter()
+    }
+}
+
+impl<V: Copy + Allocative> Allocative for SmallValueVec<V> {
+    fn visit<'a, 'b: 'a>(&self, visitor: &'a mut allocative::Visitor<'b>) {
+        let mut visitor = visitor.enter_self_sized::<Self>();
+        if let Repr::Heap(vec) = &self.0 {
+            visitor.visit_field(allocative::Key::new("heap"), vec);
+        }
+        visitor.exit();
+    }
+}
+
+unsafe impl<'v, V: Copy + Trace<'v>> Trace<'v> for SmallValueVec<V> {
+    fn trace(&mut self, tracer: &Tracer<'v>) {
+        self.as_mut_slice().trace(tracer);
+    }
+}
+
+impl<V> Freeze for SmallValueVec<V>
+where
+    V: Copy + Freeze,
+    V::Frozen: Copy,
+{
+    type Frozen = SmallValueVec<V::Frozen>;
+
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<SmallValueVec<V::Frozen>> {
+        let mut frozen = SmallValueVec::new();
+        for v in self.as_slice() {
+            frozen.push(v.freeze(freezer)?);
+        }
+        Ok(frozen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collections::small_value_vec::Repr;
+    use crate::collections::small_value_vec::SmallValueVec;
+    use crate::collections::small_value_vec::SMALL_VALUE_VEC_INLINE_CAPACITY;
+
+    #[test]
+    fn test_inline_then_spill() {
+        let mut vec = SmallValueVec::new();
+        for i in 0..SMALL_VALUE_VEC_INLINE_CAPACITY {
+            vec.push(i);
+        }
+        assert!(matches!(vec.0, Repr::Inline { .. }));
+        assert_eq!(
+            (0..SMALL_VALUE_VEC_INLINE_CAPACITY).collect::<Vec<_>>(),
+            vec.as_slice()
+        );
+
+        vec.push(SMALL_VALUE_VEC_INLINE_CAPACITY);
+        assert!(matches!(vec.0, Repr::Heap(_)));
+        assert_eq!(
+            (0..=SMALL_VALUE_VEC_INLINE_CAPACITY).collect::<Vec<_>>(),
+            vec.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_collect() {
+        let vec: SmallValueVec<u32> = (0..3).collect();
+        assert_eq!([0, 1, 2], *vec);
+        assert_eq!(vec, vec.clone());
+        assert!(SmallValueVec::<u32>::default().is_empty());
+    }
+}
@@ -20,25 +20,24 @@ use dupe::Dupe;
 use serde::Serialize;
 use serde_json::Value;
 
-use crate::dap::library::stream::log;
-use crate::dap::library::stream::send;
+use crate::debug::dap::stream::Stream;
 
-#[derive(Debug, Clone, Dupe)]
+#[derive(Clone, Dupe)]
 pub(crate) struct Client {
-    _private: (),
+    stream: Stream,
 }
 
 impl Client {
-    pub(crate) fn new() -> Self {
-        Self { _private: () }
+    pub(crate) fn new(stream: Stream) -> Self {
+        Self { stream }
     }
 
     pub(crate) fn log(&self, x: &str) {
-        log(x)
+        self.stream.log(x)
     }
 
     fn event(&self, x: impl Serialize) {
-        send(serde_json::to_value(&x).unwrap())
+        self.stream.send(serde_json::to_value(&x).unwrap())
     }
 
     pub(crate) fn event_stopped(&self, body: StoppedEventBody) {
@@ -0,0 +1,640 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Debug Adapter Protocol server, debugging evaluations of a [`DebugRegistry`].
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+
+use debugserver_types::*;
+use dupe::Dupe;
+use gazebo::prelude::*;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+
+use crate::codemap::FileSpan;
+use crate::debug::dap::events::Client;
+use crate::debug::dap::requests::dispatch;
+use crate::debug::dap::requests::DebugServer;
+use crate::debug::dap::stream::DapLog;
+use crate::debug::dap::stream::Stream;
+use crate::debug::DebugAttachment;
+use crate::debug::DebugEvaluationState;
+use crate::debug::DebugEvent;
+use crate::debug::DebugExceptionBreakpoint;
+use crate::debug::DebugRegistry;
+use crate::debug::DebugStopReason;
+use crate::environment::FileKind;
+use crate::environment::FileKindResolver;
+use crate::environment::Module;
+use crate::eval::Evaluator;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+mod events;
+mod requests;
+mod stream;
+
+/// Exception filter stopping at `fail()` calls.
+const FAIL_FILTER: &str = "fail";
+/// Exception filter stopping at all errors.
+const ERROR_FILTER: &str = "error";
+
+/// What the client asked to debug.
+enum Target {
+    /// Evaluate a file, `launch` request.
+    Launch(String),
+    /// Attach to an evaluation of the registry by name, `attach` request.
+    Attach(String),
+}
+
+struct Backend {
+    client: Client,
+    registry: DebugRegistry,
+    file_kinds: Arc<dyn FileKindResolver + Send + Sync>,
+    target: Mutex<Option<Target>>,
+
+    // These breakpoints must all match statements as per before_stmt.
+    // Those values for which we abort the execution.
+    breakpoints: Mutex<HashMap<String, HashSet<FileSpan>>>,
+    exception_breakpoints: Mutex<Vec<DebugExceptionBreakpoint>>,
+    attachment: Mutex<Option<Arc<DebugAttachment>>>,
+}
+
+impl Backend {
+    fn attachment(&self) -> anyhow::Result<Arc<DebugAttachment>> {
+        match &*self.attachment.lock().unwrap() {
+            Some(attachment) => Ok(attachment.dupe()),
+            None => Err(anyhow::anyhow!("Not attached to an evaluation")),
+        }
+    }
+
+    /// Attach to the evaluation `name`, forwarding its events to the client.
+    fn attach_to(&self, name: &str, launched: bool) -> anyhow::Result<()> {
+        let client = self.client.dupe();
+        let attachment = self.registry.attach(name, move |event| match event {
+            DebugEvent::Stopped(reason) => {
+                let (reason, description, text) = match reason {
+                    DebugStopReason::Breakpoint => ("breakpoint", None, None),
+                    DebugStopReason::Pause => ("pause", None, None),
+                    DebugStopReason::Exception(message) => (
+                        "exception",
+                        Some("Paused on error".to_owned()),
+                        Some(message),
+                    ),
+                };
+                client.event_stopped(StoppedEventBody {
+                    reason: reason.to_owned(),
+                    thread_id: Some(0),
+                    description,
+                    all_threads_stopped: Some(true),
+                    preserve_focus_hint: None,
+                    text,
+                })
+            }
+            // A launched evaluation reports its result before terminating.
+            DebugEvent::Finished if !launched => client.event_terminated(None),
+            DebugEvent::Finished => {}
+        })?;
+        for (file, spans) in self.breakpoints.lock().unwrap().iter() {
+            attachment.set_breakpoints(file, spans);
+        }
+        attachment.set_exception_breakpoints(self.exception_breakpoints.lock().unwrap().clone());
+        *self.attachment.lock().unwrap() = Some(Arc::new(attachment));
+        Ok(())
+    }
+
+    fn execute(&self, path: &str) -> anyhow::Result<()> {
+        // Attach before the evaluation is registered, so it stops at the first breakpoint.
+        self.attach_to(path, true)?;
+        let client = self.client.dupe();
+        let client2 = self.client.dupe();
+        let registry = self.registry.dupe();
+        let file_kinds = self.file_kinds.dupe();
+        let path = PathBuf::from(path);
+
+        let go = move || -> anyhow::Result<String> {
+            client.log(&format!("EVALUATION PREPARE: {}", path.display()));
+            let target = registry.register(&path.to_string_lossy());
+            let FileKind { dialect, globals } = file_kinds.resolve(&path);
+            let ast = AstModule::parse_file(&path, &dialect)?;
+            let module = Module::new();
+            let mut eval = Evaluator::new(&module);
+            target.install(&mut eval);
+            // No way to pass back success/failure to the caller
+            client.log(&format!("EVALUATION START: {}", path.display()));
+            let v = eval.eval_module(ast, &globals)?;
+            let s = v.to_string();
+            client.log(&format!("EVALUATION FINISHED: {}", path.display()));
+            Ok(s)
+        };
+
+        thread::spawn(move || {
+            let res = go();
+            let output = match &res {
+                Err(e) => format!("{:#}", e),
+                Ok(v) => v.to_owned(),
+            };
+            client2.event_output(OutputEventBody {
+                output,
+                category: None,
+                column: None,
+                data: None,
+                line: None,
+                source: None,
+                variables_reference: None,
+            });
+            client2.event_exited(ExitedEventBody {
+                exit_code: if res.is_ok() { 0 } else { 1 },
+            });
+            client2.event_terminated(None);
+        });
+        Ok(())
+    }
+}
+
+fn breakpoint(verified: bool) -> Breakpoint {
+    Breakpoint {
+        column: None,
+        end_column: None,
+        end_line: None,
+        id: None,
+        line: None,
+        message: None,
+        source: None,
+        verified,
+    }
+}
+
+impl DebugServer for Backend {
+    fn initialize(&self, _: InitializeRequestArguments) -> anyhow::Result<Value> {
+        self.client.event_initialized(None);
+        let filter = |filter: &str, label: &str| ExceptionBreakpointsFilter {
+            filter: filter.to_owned(),
+            label: label.to_owned(),
+            default: Some(false),
+        };
+        let mut capabilities = serde_json::to_value(Capabilities {
+            supports_configuration_done_request: Some(true),
+            supports_evaluate_for_hovers: Some(true),
+            supports_set_variable: Some(true),
+            supports_step_in_targets_request: Some(true),
+            exception_breakpoint_filters: Some(vec![
+                filter(FAIL_FILTER, "fail() calls"),
+                filter(ERROR_FILTER, "All errors"),
+            ]),
+            ..Capabilities::default()
+        })?;
+        // Not supported by `debugserver_types`: the condition of an exception filter,
+        // which is a string the error message must contain.
+        capabilities["supportsExceptionFilterOptions"] = Value::Bool(true);
+        if let Some(Value::Array(filters)) = capabilities.get_mut("exceptionBreakpointFilters") {
+            for filter in filters {
+                filter["supportsCondition"] = Value::Bool(true);
+                filter["conditionDescription"] = json!("Text the error message contains");
+            }
+        }
+        Ok(capabilities)
+    }
+
+    fn set_breakpoints(
+        &self,
+        x: SetBreakpointsArguments,
+    ) -> anyhow::Result<SetBreakpointsResponseBody> {
+        let breakpoints = x.breakpoints.unwrap_or_default();
+        let source = x.source.path.unwrap();
+
+        let res = if breakpoints.is_empty() {
+            self.breakpoints.lock().unwrap().remove(&source);
+            Ok(SetBreakpointsResponseBody {
+                breakpoints: Vec::new(),
+            })
+        } else {
+            let path = Path::new(&source);
+            match AstModule::parse_file(path, &self.file_kinds.resolve(path).dialect) {
+                Err(_) => {
+                    self.breakpoints.lock().unwrap().remove(&source);
+                    Ok(SetBreakpointsResponseBody {
+                        breakpoints: vec![breakpoint(false); breakpoints.len()],
+                    })
+                }
+                Ok(ast) => {
+                    let poss: HashMap<usize, FileSpan> = ast
+                        .stmt_locations()
+                        .iter()
+                        .map(|span| (span.resolve_span().begin_line, span.dupe()))
+                        .collect();
+                    let list = breakpoints.map(|x| poss.get(&(x.line as usize - 1)));
+                    self.breakpoints.lock().unwrap().insert(
+                        source.clone(),
+                        list.iter().filter_map(|x| x.duped()).collect(),
+                    );
+                    Ok(SetBreakpointsResponseBody {
+                        breakpoints: list.map(|x| breakpoint(x.is_some())),
+                    })
+                }
+            }
+        };
+        if let Some(attachment) = &*self.attachment.lock().unwrap() {
+            let spans = self.breakpoints.lock().unwrap().get(&source).cloned();
+            attachment.set_breakpoints(&source, &spans.unwrap_or_default());
+        }
+        res
+    }
+
+    fn set_exception_breakpoints(
+        &self,
+        x: SetExceptionBreakpointsArguments,
+        args: Map<String, Value>,
+    ) -> anyhow::Result<()> {
+        // Filters without a condition are listed in `filters`, the others in `filterOptions`.
+        let mut filters: Vec<(String, Option<String>)> =
+            x.filters.into_iter().map(|x| (x, None)).collect();
+        if let Some(Value::Array(options)) = args.get("filterOptions") {
+            for option in options {
+                if let Some(Value::String(filter)) = option.get("filterId") {
+                    let condition = match option.get("condition") {
+                        Some(Value::String(x)) if !x.is_empty() => Some(x.clone()),
+                        _ => None,
+                    };
+                    filters.push((filter.clone(), condition));
+                }
+            }
+        }
+        let breakpoints = filters
+            .into_iter()
+            .map(|(filter, message)| match filter.as_str() {
+                FAIL_FILTER => Ok(DebugExceptionBreakpoint {
+                    only_fail: true,
+                    message,
+                }),
+                ERROR_FILTER => Ok(DebugExceptionBreakpoint {
+                    only_fail: false,
+                    message,
+                }),
+                _ => Err(anyhow::anyhow!("Unknown exception filter: {}", filter)),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if let Some(attachment) = &*self.attachment.lock().unwrap() {
+            attachment.set_exception_breakpoints(breakpoints.clone());
+        }
+        *self.exception_breakpoints.lock().unwrap() = breakpoints;
+        Ok(())
+    }
+
+    fn launch(&self, _: LaunchRequestArguments, args: Map<String, Value>) -> anyhow::Result<()> {
+        // Expecting program of type string
+        match args.get("program") {
+            Some(Value::String(path)) => {
+                *self.target.lock().unwrap() = Some(Target::Launch(path.to_owned()));
+                Ok(())
+            }
+            _ => Err(anyhow::anyhow!(
+                "Couldn't find a program to launch, got args {:?}",
+                args
+            )),
+        }
+    }
+
+    fn attach(&self, _: AttachRequestArguments, args: Map<String, Value>) -> anyhow::Result<()> {
+        // Expecting evaluation of type string
+        match args.get("evaluation") {
+            Some(Value::String(name)) => {
+                *self.target.lock().unwrap() = Some(Target::Attach(name.to_owned()));
+                Ok(())
+            }
+            _ => Err(anyhow::anyhow!(
+                "Couldn't find an evaluation to attach to, got args {:?}, evaluations: {}",
+                args,
+                self.registry
+                    .evaluations()
+                    .iter()
+                    .map(|x| x.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    fn evaluations(&self) -> anyhow::Result<Value> {
+        let evaluations = self.registry.evaluations().into_map(|x| {
+            let state = match x.state {
+                DebugEvaluationState::Waiting => "waiting",
+                DebugEvaluationState::Running => "running",
+                DebugEvaluationState::Paused => "paused",
+            };
+            json!({"name": x.name, "state": state, "attached": x.attached})
+        });
+        Ok(json!({ "evaluations": evaluations }))
+    }
+
+    fn threads(&self) -> anyhow::Result<ThreadsResponseBody> {
+        let name = match &*self.attachment.lock().unwrap() {
+            Some(attachment) => attachment.name().to_owned(),
+            None => "main".to_owned(),
+        };
+        Ok(ThreadsResponseBody {
+            threads: vec![Thread { id: 0, name }],
+        })
+    }
+
+    fn configuration_done(&self) -> anyhow::Result<()> {
+        match &*self.target.lock().unwrap() {
+            Some(Target::Launch(path)) => self.execute(path),
+            Some(Target::Attach(name)) => self.attach_to(name, false),
+            None => Ok(()),
+        }
+    }
+
+    fn stack_trace(&self, _: StackTraceArguments) -> anyhow::Result<StackTraceResponseBody> {
+        fn convert_frame(id: usize, name: String, location: Option<FileSpan>) -> StackFrame {
+            let mut s = StackFrame {
+                id: id as i64,
+                name,
+                column: 0,
+                line: 0,
+                end_column: None,
+                end_line: None,
+                module_id: None,
+                presentation_hint: None,
+                source: None,
+            };
+            if let Some(loc) = location {
+                let span = loc.resolve_span();
+                s.line = span.begin_line as i64 + 1;
+                s.column = span.begin_column as i64 + 1;
+                s.end_line = Some(span.end_line as i64 + 1);
+                s.end_column = Some(span.end_column as i64 + 1);
+                s.source = Some(Source {
+                    path: Some(loc.filename().to_owned()),
+                    ..Source::default()
+                })
+            }
+            s
+        }
+
+        // Our model of a Frame and the debugger model are a bit different.
+        // We record the location of the call, but DAP wants the location we are at.
+        // We also have them in the wrong order
+        self.attachment()?.with_paused(|span, eval| {
+            let frames = eval.call_stack().into_frames();
+            let mut next = Some(span.to_file_span());
+            let mut res = Vec::with_capacity(frames.len() + 1);
+            for (i, x) in frames.iter().rev().enumerate() {
+                res.push(convert_frame(i, x.name.clone(), next));
+                next = x.location.dupe();
+            }
+            res.push(convert_frame(10000, "Root".to_owned(), next));
+            StackTraceResponseBody {
+                total_frames: Some(res.len() as i64),
+                stack_frames: res,
+            }
+        })
+    }
+
+    fn scopes(&self, _: ScopesArguments) -> anyhow::Result<ScopesResponseBody> {
+        self.attachment()?.with_paused(|_, eval| {
+            let vars = eval.local_variables();
+            ScopesResponseBody {
+                scopes: vec![Scope {
+                    name: "Locals".to_owned(),
+                    named_variables: Some(vars.len() as i64),
+                    variables_reference: 2000,
+                    expensive: false,
+                    column: None,
+                    end_column: None,
+                    end_line: None,
+                    indexed_variables: None,
+                    line: None,
+                    source: None,
+                }],
+            }
+        })
+    }
+
+    fn variables(&self, _: VariablesArguments) -> anyhow::Result<VariablesResponseBody> {
+        self.attachment()?.with_paused(|_, eval| {
+            let vars = eval.local_variables();
+            VariablesResponseBody {
+                variables: vars
+                    .into_iter()
+                    .map(|(name, value)| Variable {
+                        name,
+                        value: value.to_string(),
+                        type_: Some(value.get_type().to_owned()),
+                        evaluate_name: None,
+                        indexed_variables: None,
+                        named_variables: None,
+                        presentation_hint: None,
+                        variables_reference: 0,
+                    })
+                    .collect(),
+            }
+        })
+    }
+
+    fn continue_(&self, _: ContinueArguments) -> anyhow::Result<ContinueResponseBody> {
+        self.attachment()?.resume()?;
+        Ok(ContinueResponseBody::default())
+    }
+
+    fn pause(&self, _: PauseArguments) -> anyhow::Result<()> {
+        self.attachment()?.pause();
+        Ok(())
+    }
+
+    fn evaluate(&self, x: EvaluateArguments) -> anyhow::Result<EvaluateResponseBody> {
+        // Breakpoints are not triggered during an evaluate, not least because
+        // we currently don't allow reenterant evaluate.
+        self.attachment()?.with_paused(move |_, eval| {
+            let ast = AstModule::parse("interactive", x.expression, &Dialect::Extended);
+            let s = match ast.and_then(|ast| eval.eval_statements(ast)) {
+                Err(e) => format!("{:#}", e),
+                Ok(v) => v.to_string(),
+            };
+            EvaluateResponseBody {
+                indexed_variables: None,
+                named_variables: None,
+                presentation_hint: None,
+                result: s,
+                type_: None,
+                variables_reference: 0.0,
+            }
+        })
+    }
+
+    fn disconnect(&self, _: DisconnectArguments) -> anyhow::Result<()> {
+        // Let an attached evaluation continue without the debugger.
+        self.attachment.lock().unwrap().take();
+        Ok(())
+    }
+}
+
+/// A [Debug Adapter Protocol](https://microsoft.github.io/debug-adapter-protocol/) server.
+///
+/// Besides `launch`, which evaluates the file given as `program`, the server supports `attach`
+/// to an evaluation of the [`DebugRegistry`] by the name given as `evaluation`,
+/// which may be registered after the debugger attaches. The custom `evaluations` request
+/// lists the evaluations of the registry.
+pub struct DapServer {
+    registry: DebugRegistry,
+    file_kinds: Arc<dyn FileKindResolver + Send + Sync>,
+    log: Option<DapLog>,
+}
+
+impl DapServer {
+    /// Server for the evaluations of `registry`. Files are parsed, for breakpoints
+    /// and for `launch`, with the dialect and globals given by `file_kinds`.
+    pub fn new(
+        registry: DebugRegistry,
+        file_kinds: Arc<dyn FileKindResolver + Send + Sync>,
+    ) -> DapServer {
+        DapServer {
+            registry,
+            file_kinds,
+            log: None,
+        }
+    }
+
+    /// Write the messages and events of the server to `log`.
+    pub fn set_log(&mut self, log: impl Fn(&str) + Send + Sync + 'static) {
+        self.log = Some(Arc::new(log));
+    }
+
+    /// Serve one client, reading requests from `input` and writing responses and events
+    /// to `output`, until the client disconnects or the input ends.
+    pub fn run(
+        &self,
+        mut input: impl BufRead,
+        output: impl Write + Send + 'static,
+    ) -> anyhow::Result<()> {
+        let stream = Stream::new(Box::new(output), self.log.dupe());
+        let backend = Backend {
+            client: Client::new(stream.dupe()),
+            registry: self.registry.dupe(),
+            file_kinds: self.file_kinds.dupe(),
+            target: Default::default(),
+            breakpoints: Default::default(),
+            exception_breakpoints: Default::default(),
+            attachment: Default::default(),
+        };
+
+        stream.log("DEBUG ADAPTER STARTING");
+        while let Some(recv) = stream.read(&mut input)? {
+            let r: Request = serde_json::from_value(recv)?;
+            anyhow::ensure!(
+                r.type_ == "request",
+                "Expected a request, got `{}`",
+                r.type_
+            );
+            let resp = dispatch(&backend, &r);
+            stream.send(serde_json::to_value(resp)?);
+
+            if r.command == "disconnect" {
+                break;
+            }
+        }
+        stream.log("DEBUG ADAPTER STOPPING");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use dupe::Dupe;
+    use serde_json::json;
+    use serde_json::Value;
+
+    use crate::debug::DapServer;
+    use crate::debug::DebugRegistry;
+    use crate::environment::FileKind;
+    use crate::environment::Globals;
+    use crate::syntax::Dialect;
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn request(seq: i64, command: &str, arguments: Value) -> String {
+        let s = json!({"seq": seq, "type": "request", "command": command, "arguments": arguments})
+            .to_string();
+        format!("Content-Length: {}\r\n\r\n{}", s.len(), s)
+    }
+
+    #[test]
+    fn test_list_and_attach_evaluations() -> anyhow::Result<()> {
+        let registry = DebugRegistry::new();
+        let _target = registry.register("build //foo:bar");
+        let file_kind = FileKind {
+            dialect: Dialect::Standard,
+            globals: Globals::standard(),
+        };
+        let server = DapServer::new(registry.dupe(), Arc::new(file_kind));
+        let input = [
+            request(1, "evaluations", json!({})),
+            request(2, "attach", json!({"evaluation": "build //foo:bar"})),
+            request(3, "configurationDone", json!({})),
+            request(4, "threads", json!({})),
+        ]
+        .concat();
+        let output = Output::default();
+        server.run(input.as_bytes(), output.clone())?;
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone())?;
+        let bodies: Vec<Value> = output
+            .split("Content-Length: ")
+            .skip(1)
+            .map(|x| serde_json::from_str(x.split_once("\r\n\r\n").unwrap().1).unwrap())
+            .map(|x: Value| x["body"].clone())
+            .collect();
+        assert_eq!(
+            vec![
+                json!({"evaluations": [
+                    {"name": "build //foo:bar", "state": "running", "attached": false}
+                ]}),
+                Value::Null,
+                Value::Null,
+                json!({"threads": [{"id": 0, "name": "build //foo:bar"}]}),
+            ],
+            bodies
+        );
+        // The debugger detached at the end of the input.
+        assert!(!registry.evaluations()[0].attached);
+        Ok(())
+    }
+}
@@ -22,19 +22,28 @@ use serde_json::Map;
 use serde_json::Value;
 
 pub(crate) trait DebugServer {
-    fn initialize(&self, x: InitializeRequestArguments) -> anyhow::Result<Option<Capabilities>>;
+    /// Returns the capabilities, which may not be supported by `debugserver_types`.
+    fn initialize(&self, x: InitializeRequestArguments) -> anyhow::Result<Value>;
     fn set_breakpoints(
         &self,
         x: SetBreakpointsArguments,
     ) -> anyhow::Result<SetBreakpointsResponseBody>;
-    fn set_exception_breakpoints(&self, x: SetExceptionBreakpointsArguments) -> anyhow::Result<()>;
+    fn set_exception_breakpoints(
+        &self,
+        x: SetExceptionBreakpointsArguments,
+        args: Map<String, Value>,
+    ) -> anyhow::Result<()>;
     fn launch(&self, x: LaunchRequestArguments, args: Map<String, Value>) -> anyhow::Result<()>;
+    fn attach(&self, x: AttachRequestArguments, args: Map<String, Value>) -> anyhow::Result<()>;
+    /// Custom request listing the evaluations which can be attached to.
+    fn evaluations(&self) -> anyhow::Result<Value>;
     fn threads(&self) -> anyhow::Result<ThreadsResponseBody>;
     fn configuration_done(&self) -> anyhow::Result<()>;
     fn stack_trace(&self, x: StackTraceArguments) -> anyhow::Result<StackTraceResponseBody>;
     fn scopes(&self, x: ScopesArguments) -> anyhow::Result<ScopesResponseBody>;
     fn variables(&self, x: VariablesArguments) -> anyhow::Result<VariablesResponseBody>;
     fn continue_(&self, x: ContinueArguments) -> anyhow::Result<ContinueResponseBody>;
+    fn pause(&self, x: PauseArguments) -> anyhow::Result<()>;
     fn evaluate(&self, x: EvaluateArguments) -> anyhow::Result<EvaluateResponseBody>;
     fn disconnect(&self, _x: DisconnectArguments) -> anyhow::Result<()> {
         Ok(())
@@ -74,16 +83,21 @@ pub(crate) fn dispatch(server: &impl DebugServer, r: &Request) -> Response {
     }
 
     match r.command.as_str() {
-        "initialize" => ret(r, server.initialize(arg(r))),
+        "initialize" => ret_some(r, server.initialize(arg(r))),
         "setBreakpoints" => ret_some(r, server.set_breakpoints(arg(r))),
-        "setExceptionBreakpoints" => ret_none(r, server.set_exception_breakpoints(arg(r))),
+        "setExceptionBreakpoints" => {
+            ret_none(r, server.set_exception_breakpoints(arg(r), arg_extra(r)))
+        }
         "launch" => ret_none(r, server.launch(arg(r), arg_extra(r))),
+        "attach" => ret_none(r, server.attach(arg(r), arg_extra(r))),
+        "evaluations" => ret_some(r, server.evaluations()),
         "threads" => ret_some(r, server.threads()),
         "configurationDone" => ret_none(r, server.configuration_done()),
         "stackTrace" => ret_some(r, server.stack_trace(arg(r))),
         "scopes" => ret_some(r, server.scopes(arg(r))),
         "variables" => ret_some(r, server.variables(arg(r))),
         "continue" => ret_some(r, server.continue_(arg(r))),
+        "pause" => ret_none(r, server.pause(arg(r))),
         "evaluate" => ret_some(r, server.evaluate(arg(r))),
         "disconnect" => ret_none(r, server.disconnect(arg(r))),
         _ => ret_none(r, Err(anyhow::anyhow!("Unknown command: {}", r.command))),
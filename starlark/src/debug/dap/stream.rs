@@ -0,0 +1,88 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Lowest level stream communication as JSON.
+//! Because DAP debugging is hard, everything we see is also written to the log, if there is one.
+
+use std::io::BufRead;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use dupe::Dupe;
+use serde_json::Value;
+
+/// Callback receiving the log of the adapter.
+pub(crate) type DapLog = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Writes messages to the client, shared by the threads of the adapter.
+#[derive(Clone, Dupe)]
+pub(crate) struct Stream {
+    output: Arc<Mutex<Box<dyn Write + Send>>>,
+    log: Option<DapLog>,
+}
+
+impl Stream {
+    pub(crate) fn new(output: Box<dyn Write + Send>, log: Option<DapLog>) -> Self {
+        Self {
+            output: Arc::new(Mutex::new(output)),
+            log,
+        }
+    }
+
+    pub(crate) fn log(&self, x: &str) {
+        if let Some(log) = &self.log {
+            log(x)
+        }
+    }
+
+    pub(crate) fn send(&self, x: Value) {
+        let s = x.to_string();
+        self.log(&format!("SEND: {}", s));
+        let mut output = self.output.lock().unwrap();
+        let res =
+            write!(output, "Content-Length: {}\r\n\r\n{}", s.len(), s).and_then(|_| output.flush());
+        // Events are sent from the evaluation threads, which can't do anything about failures.
+        if let Err(e) = res {
+            self.log(&format!("SEND FAILED: {}", e));
+        }
+    }
+
+    /// Read the next message, `None` at the end of the input.
+    pub(crate) fn read(&self, input: &mut impl BufRead) -> anyhow::Result<Option<Value>> {
+        let mut len = None;
+        loop {
+            let mut s = String::new();
+            if input.read_line(&mut s)? == 0 {
+                return Ok(None);
+            }
+            let s = s.trim();
+            if s.is_empty() {
+                break;
+            }
+            if let Some(x) = s.strip_prefix("Content-Length: ") {
+                len = Some(x.parse::<usize>()?);
+            }
+        }
+        let len = len.ok_or_else(|| anyhow::anyhow!("Missing `Content-Length` header"))?;
+        let mut res = vec![0u8; len];
+        input.read_exact(&mut res)?;
+        let s = String::from_utf8_lossy(&res);
+        self.log(&format!("RECV: {}", s));
+        Ok(Some(serde_json::from_str(&s)?))
+    }
+}
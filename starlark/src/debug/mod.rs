@@ -15,6 +15,26 @@
  * limitations under the License.
  */
 
+//! Debugging of evaluations, with the
+//! [Debug Adapter Protocol](https://microsoft.github.io/debug-adapter-protocol/).
+//!
+//! Evaluations are registered in a [`DebugRegistry`], which a debugger, like [`DapServer`],
+//! uses to attach to them.
+
 mod breakpoint;
+mod dap;
 mod evaluate;
 mod inspect;
+mod registry;
+mod tracepoint;
+
+pub use dap::DapServer;
+pub use registry::DebugAttachment;
+pub use registry::DebugEvaluation;
+pub use registry::DebugEvaluationState;
+pub use registry::DebugEvent;
+pub use registry::DebugExceptionBreakpoint;
+pub use registry::DebugRegistry;
+pub use registry::DebugStopReason;
+pub use registry::DebugTarget;
+pub use tracepoint::Tracepoints;
@@ -0,0 +1,645 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Evaluations registered by name, so a debugger can attach to them while they run.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use dupe::Dupe;
+
+use crate::codemap::FileSpan;
+use crate::codemap::FileSpanRef;
+use crate::codemap::Span;
+use crate::errors::Diagnostic;
+use crate::eval::Evaluator;
+use crate::stdlib::funcs::FailError;
+
+#[derive(Debug, thiserror::Error)]
+enum DebugError {
+    #[error("A debugger is already attached to evaluation `{0}`")]
+    AlreadyAttached(String),
+    #[error("Evaluation `{0}` has finished")]
+    Finished(String),
+}
+
+/// State of an evaluation known to a [`DebugRegistry`].
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq)]
+pub enum DebugEvaluationState {
+    /// A debugger is attached, but the evaluation has not been registered yet.
+    Waiting,
+    /// The evaluation is running.
+    Running,
+    /// The evaluation is stopped, waiting for the debugger to resume it.
+    Paused,
+}
+
+/// An evaluation known to a [`DebugRegistry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugEvaluation {
+    /// Name the evaluation was registered with.
+    pub name: String,
+    /// Whether a debugger is attached to the evaluation.
+    pub attached: bool,
+    /// Whether the evaluation is running.
+    pub state: DebugEvaluationState,
+}
+
+/// Errors which stop an evaluation where they are raised, before the stack unwinds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugExceptionBreakpoint {
+    /// Only stop at errors raised by `fail()`, rather than at all errors.
+    pub only_fail: bool,
+    /// Only stop at errors whose message contains this string, like `key not found`.
+    pub message: Option<String>,
+}
+
+impl DebugExceptionBreakpoint {
+    fn matches(&self, error: &anyhow::Error, message: &str) -> bool {
+        (!self.only_fail || error.is::<FailError>())
+            && match &self.message {
+                Some(x) => message.contains(x.as_str()),
+                None => true,
+            }
+    }
+}
+
+/// Why an evaluation stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugStopReason {
+    /// The statement about to be executed has a breakpoint.
+    Breakpoint,
+    /// The debugger asked to [`pause`](DebugAttachment::pause).
+    Pause,
+    /// An error matching a [`DebugExceptionBreakpoint`] was raised, with this message.
+    Exception(String),
+}
+
+/// Notification sent to an attached debugger, from the thread of the evaluation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugEvent {
+    /// The evaluation stopped before a statement.
+    Stopped(DebugStopReason),
+    /// The evaluation finished, the [`DebugTarget`] was dropped.
+    Finished,
+}
+
+/// Whether the evaluation continues after a command run while it is paused.
+enum DebugNext {
+    Continue,
+    RemainPaused,
+}
+
+type DebugCommand =
+    Box<dyn for<'v, 'a> FnOnce(FileSpanRef, &mut Evaluator<'v, 'a>) -> DebugNext + Send>;
+
+type DebugEventHandler = Box<dyn Fn(DebugEvent) + Send>;
+
+/// State of one evaluation, shared by the evaluation and the debugger.
+struct DebugSession {
+    name: String,
+    /// A [`DebugTarget`] was created for the session.
+    registered: AtomicBool,
+    /// Set when the [`DebugTarget`] is dropped.
+    finished: AtomicBool,
+    paused: AtomicBool,
+    attached: AtomicBool,
+    /// Commands from the attached debugger, `None` when no debugger is attached.
+    commands: Mutex<Option<Receiver<DebugCommand>>>,
+    events: Mutex<Option<DebugEventHandler>>,
+    // These breakpoints must all match statements as per before_stmt.
+    // Spans are compared within a file name, because code maps are compared by identity.
+    breakpoints: Mutex<HashMap<String, HashSet<Span>>>,
+    exception_breakpoints: Mutex<Vec<DebugExceptionBreakpoint>>,
+    pause_requested: AtomicBool,
+    // Set while we are running debugger commands (>= 1 means disable).
+    breakpoints_disabled: AtomicUsize,
+}
+
+impl DebugSession {
+    fn new(name: String) -> DebugSession {
+        DebugSession {
+            name,
+            registered: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            attached: AtomicBool::new(false),
+            commands: Mutex::new(None),
+            events: Mutex::new(None),
+            breakpoints: Mutex::new(HashMap::new()),
+            exception_breakpoints: Mutex::new(Vec::new()),
+            pause_requested: AtomicBool::new(false),
+            breakpoints_disabled: AtomicUsize::new(0),
+        }
+    }
+
+    fn info(&self) -> DebugEvaluation {
+        let state = if !self.registered.load(Ordering::SeqCst) {
+            DebugEvaluationState::Waiting
+        } else if self.paused.load(Ordering::SeqCst) {
+            DebugEvaluationState::Paused
+        } else {
+            DebugEvaluationState::Running
+        };
+        DebugEvaluation {
+            name: self.name.clone(),
+            attached: self.attached.load(Ordering::SeqCst),
+            state,
+        }
+    }
+
+    fn send_event(&self, event: DebugEvent) {
+        if let Some(handler) = &*self.events.lock().unwrap() {
+            handler(event);
+        }
+    }
+
+    fn before_stmt(&self, span: FileSpanRef, eval: &mut Evaluator) {
+        // Statements run by debugger commands, e.g. an expression evaluated while paused,
+        // never stop: the evaluation is already paused.
+        if self.breakpoints_disabled.load(Ordering::SeqCst) > 0 {
+            return;
+        }
+        let reason = if self.pause_requested.swap(false, Ordering::SeqCst) {
+            DebugStopReason::Pause
+        } else if self
+            .breakpoints
+            .lock()
+            .unwrap()
+            .get(span.filename())
+            .map(|x| x.contains(&span.span))
+            .unwrap_or_default()
+        {
+            DebugStopReason::Breakpoint
+        } else {
+            return;
+        };
+        self.stop(reason, span, eval);
+    }
+
+    fn on_error(&self, error: &anyhow::Error, span: FileSpanRef, eval: &mut Evaluator) {
+        if self.breakpoints_disabled.load(Ordering::SeqCst) > 0 {
+            return;
+        }
+        // Errors of native functions have the call stack attached when they are raised.
+        let error = match error.downcast_ref::<Diagnostic>() {
+            Some(d) => &d.message,
+            None => error,
+        };
+        let message = error.to_string();
+        let stop = self
+            .exception_breakpoints
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|x| x.matches(error, &message));
+        if stop {
+            self.stop(DebugStopReason::Exception(message), span, eval);
+        }
+    }
+
+    /// Run the commands of the debugger until one of them resumes the evaluation,
+    /// or the debugger detaches.
+    fn stop(&self, reason: DebugStopReason, span: FileSpanRef, eval: &mut Evaluator) {
+        let mut commands = self.commands.lock().unwrap();
+        let receiver = match &*commands {
+            Some(receiver) => receiver,
+            None => return,
+        };
+        self.paused.store(true, Ordering::SeqCst);
+        self.send_event(DebugEvent::Stopped(reason));
+        let mut detached = true;
+        while let Ok(command) = receiver.recv() {
+            self.breakpoints_disabled.fetch_add(1, Ordering::SeqCst);
+            let next = command(span, eval);
+            self.breakpoints_disabled.fetch_sub(1, Ordering::SeqCst);
+            if let DebugNext::Continue = next {
+                detached = false;
+                break;
+            }
+        }
+        if detached {
+            *commands = None;
+        }
+        self.paused.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Evaluations which a debugger can attach to, identified by name.
+///
+/// An embedder, like a long running build server, shares one registry between the threads
+/// running evaluations and a debug adapter, like [`DapServer`](crate::debug::DapServer).
+/// Each evaluation calls [`register`](DebugRegistry::register) and installs the returned
+/// [`DebugTarget`] in its [`Evaluator`]. The debugger can list the evaluations, and
+/// [`attach`](DebugRegistry::attach) to an evaluation which is running or which is
+/// registered later.
+#[derive(Clone, Dupe, Default)]
+pub struct DebugRegistry {
+    sessions: Arc<Mutex<Vec<Arc<DebugSession>>>>,
+}
+
+impl DebugRegistry {
+    /// Create an empty registry.
+    pub fn new() -> DebugRegistry {
+        DebugRegistry::default()
+    }
+
+    /// Register an evaluation, which must be debugged with the returned target.
+    /// If a debugger is waiting for an evaluation with this name, it is attached to this one.
+    pub fn register(&self, name: &str) -> DebugTarget {
+        let mut sessions = self.sessions.lock().unwrap();
+        let waiting = sessions
+            .iter()
+            .find(|x| x.name == name && !x.registered.load(Ordering::SeqCst));
+        let session = match waiting {
+            Some(session) => session.dupe(),
+            None => {
+                let session = Arc::new(DebugSession::new(name.to_owned()));
+                sessions.push(session.dupe());
+                session
+            }
+        };
+        session.registered.store(true, Ordering::SeqCst);
+        let session2 = session.dupe();
+        DebugTarget {
+            registry: self.dupe(),
+            session: session.dupe(),
+            hook: Box::new(move |span, eval| session.before_stmt(span, eval)),
+            error_hook: Box::new(move |error, span, eval| session2.on_error(error, span, eval)),
+        }
+    }
+
+    /// Evaluations which are running, and those a debugger is waiting for,
+    /// in the order they were registered.
+    pub fn evaluations(&self) -> Vec<DebugEvaluation> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|x| x.info())
+            .collect()
+    }
+
+    /// Attach a debugger to the first evaluation with this name which has no debugger.
+    /// If there is no such evaluation, the debugger waits for the next evaluation registered
+    /// with this name. The `events` handler is called from the thread of the evaluation.
+    pub fn attach(
+        &self,
+        name: &str,
+        events: impl Fn(DebugEvent) + Send + 'static,
+    ) -> anyhow::Result<DebugAttachment> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let same_name: Vec<&Arc<DebugSession>> =
+            sessions.iter().filter(|x| x.name == name).collect();
+        let session = match same_name
+            .iter()
+            .find(|x| !x.attached.load(Ordering::SeqCst))
+        {
+            Some(session) => (*session).dupe(),
+            None if same_name.is_empty() => {
+                let session = Arc::new(DebugSession::new(name.to_owned()));
+                sessions.push(session.dupe());
+                session
+            }
+            None => return Err(DebugError::AlreadyAttached(name.to_owned()).into()),
+        };
+        let (sender, receiver) = channel();
+        *session.events.lock().unwrap() = Some(Box::new(events));
+        *session.commands.lock().unwrap() = Some(receiver);
+        session.attached.store(true, Ordering::SeqCst);
+        Ok(DebugAttachment {
+            registry: self.dupe(),
+            session,
+            sender,
+        })
+    }
+
+    fn remove(&self, session: &Arc<DebugSession>) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|x| !Arc::ptr_eq(x, session));
+    }
+}
+
+/// An evaluation registered in a [`DebugRegistry`].
+///
+/// The evaluation is removed from the registry when the target is dropped,
+/// and the attached debugger is notified with [`DebugEvent::Finished`].
+pub struct DebugTarget {
+    registry: DebugRegistry,
+    session: Arc<DebugSession>,
+    hook: Box<dyn for<'v, 'a> Fn(FileSpanRef, &mut Evaluator<'v, 'a>) + Send + Sync>,
+    error_hook:
+        Box<dyn for<'v, 'a> Fn(&anyhow::Error, FileSpanRef, &mut Evaluator<'v, 'a>) + Send + Sync>,
+}
+
+impl DebugTarget {
+    /// Name the evaluation was registered with.
+    pub fn name(&self) -> &str {
+        &self.session.name
+    }
+
+    /// Let the debugger stop the evaluation before statements, and where errors are raised.
+    ///
+    /// Must be called before the code is evaluated:
+    /// only code compiled after this call can be stopped before statements.
+    pub fn install<'a>(&'a self, eval: &mut Evaluator<'_, 'a>) {
+        eval.before_stmt(&*self.hook);
+        eval.on_error(&*self.error_hook);
+    }
+}
+
+impl Drop for DebugTarget {
+    fn drop(&mut self) {
+        self.session.finished.store(true, Ordering::SeqCst);
+        // Pending commands fail, as there is nothing left to inspect.
+        self.session.commands.lock().unwrap().take();
+        self.session.send_event(DebugEvent::Finished);
+        self.registry.remove(&self.session);
+    }
+}
+
+/// A debugger attached to an evaluation with [`DebugRegistry::attach`].
+///
+/// Dropping the attachment detaches the debugger, and resumes the evaluation if it was paused.
+pub struct DebugAttachment {
+    registry: DebugRegistry,
+    session: Arc<DebugSession>,
+    sender: Sender<DebugCommand>,
+}
+
+impl DebugAttachment {
+    /// Name of the evaluation.
+    pub fn name(&self) -> &str {
+        &self.session.name
+    }
+
+    /// State of the evaluation, `None` once it has finished.
+    pub fn state(&self) -> Option<DebugEvaluationState> {
+        if self.session.finished.load(Ordering::SeqCst) {
+            None
+        } else {
+            Some(self.session.info().state)
+        }
+    }
+
+    /// Stop before the statements at `spans` in `filename`, replacing the previous
+    /// breakpoints of the file. The spans must be statement locations, like those returned by
+    /// [`AstModule::stmt_locations`](crate::syntax::AstModule::stmt_locations).
+    /// They are matched by file name and position, so may come from a different parse of the file.
+    pub fn set_breakpoints(&self, filename: &str, spans: &HashSet<FileSpan>) {
+        let mut breakpoints = self.session.breakpoints.lock().unwrap();
+        if spans.is_empty() {
+            breakpoints.remove(filename);
+        } else {
+            breakpoints.insert(filename.to_owned(), spans.iter().map(|x| x.span).collect());
+        }
+    }
+
+    /// Stop where errors matching any of `breakpoints` are raised, replacing the previous
+    /// exception breakpoints.
+    pub fn set_exception_breakpoints(&self, breakpoints: Vec<DebugExceptionBreakpoint>) {
+        *self.session.exception_breakpoints.lock().unwrap() = breakpoints;
+    }
+
+    /// Stop the evaluation before the next statement.
+    pub fn pause(&self) {
+        self.session.pause_requested.store(true, Ordering::SeqCst);
+    }
+
+    fn inject<T: Send + 'static>(
+        &self,
+        f: impl for<'v, 'a> FnOnce(FileSpanRef, &mut Evaluator<'v, 'a>) -> (DebugNext, T)
+        + Send
+        + 'static,
+    ) -> anyhow::Result<T> {
+        let finished = || DebugError::Finished(self.session.name.clone());
+        let (sender, receiver) = channel();
+        self.sender
+            .send(Box::new(move |span, eval| {
+                let (next, res) = f(span, eval);
+                // The debugger may have stopped waiting.
+                let _ignored = sender.send(res);
+                next
+            }))
+            .map_err(|_| finished())?;
+        Ok(receiver.recv().map_err(|_| finished())?)
+    }
+
+    /// Run `f` on the thread of the evaluation, with the location of the statement
+    /// it is stopped at and its evaluator, and return the result.
+    ///
+    /// If the evaluation is running, this blocks until it stops.
+    /// Fails if the evaluation finishes first.
+    pub fn with_paused<T: Send + 'static>(
+        &self,
+        f: impl for<'v, 'a> FnOnce(FileSpanRef, &mut Evaluator<'v, 'a>) -> T + Send + 'static,
+    ) -> anyhow::Result<T> {
+        self.inject(move |span, eval| (DebugNext::RemainPaused, f(span, eval)))
+    }
+
+    /// Resume the evaluation when it is paused, or when it next stops.
+    pub fn resume(&self) -> anyhow::Result<()> {
+        self.inject(|_, _| (DebugNext::Continue, ()))
+    }
+}
+
+impl Drop for DebugAttachment {
+    fn drop(&mut self) {
+        self.session.attached.store(false, Ordering::SeqCst);
+        self.session.events.lock().unwrap().take();
+        self.session.breakpoints.lock().unwrap().clear();
+        self.session.exception_breakpoints.lock().unwrap().clear();
+        self.session.pause_requested.store(false, Ordering::SeqCst);
+        // When the evaluation is paused it holds the receiver, and resumes
+        // once the sender is dropped, after this function.
+        if let Ok(mut commands) = self.session.commands.try_lock() {
+            commands.take();
+        }
+        if !self.session.registered.load(Ordering::SeqCst) {
+            self.registry.remove(&self.session);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::mpsc::channel;
+    use std::sync::mpsc::Receiver;
+    use std::thread;
+    use std::thread::JoinHandle;
+
+    use crate::codemap::FileSpan;
+    use crate::debug::DebugAttachment;
+    use crate::debug::DebugEvaluation;
+    use crate::debug::DebugEvaluationState;
+    use crate::debug::DebugEvent;
+    use crate::debug::DebugExceptionBreakpoint;
+    use crate::debug::DebugRegistry;
+    use crate::debug::DebugStopReason;
+    use crate::debug::DebugTarget;
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::eval::Evaluator;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    const PROGRAM: &str = "x = 1\ny = x + 1\nz = y + 1\n";
+
+    fn parse() -> AstModule {
+        AstModule::parse("a.star", PROGRAM.to_owned(), &Dialect::Standard).unwrap()
+    }
+
+    /// Evaluate `PROGRAM` on another thread, returning `z`.
+    fn evaluate(target: DebugTarget) -> JoinHandle<String> {
+        thread::spawn(move || {
+            let module = Module::new();
+            let mut eval = Evaluator::new(&module);
+            target.install(&mut eval);
+            eval.eval_module(parse(), &Globals::standard()).unwrap();
+            module.get("z").unwrap().to_string()
+        })
+    }
+
+    fn attach(registry: &DebugRegistry, name: &str) -> (DebugAttachment, Receiver<DebugEvent>) {
+        let (sender, receiver) = channel();
+        let attachment = registry
+            .attach(name, move |x| sender.send(x).unwrap())
+            .unwrap();
+        (attachment, receiver)
+    }
+
+    #[test]
+    fn test_attach_before_register() -> anyhow::Result<()> {
+        let registry = DebugRegistry::new();
+        let (attachment, events) = attach(&registry, "a");
+        assert_eq!(
+            vec![DebugEvaluation {
+                name: "a".to_owned(),
+                attached: true,
+                state: DebugEvaluationState::Waiting,
+            }],
+            registry.evaluations()
+        );
+        // Breakpoints are matched with the statements of another parse of the same file.
+        let breakpoints: HashSet<FileSpan> = parse()
+            .stmt_locations()
+            .into_iter()
+            .filter(|x| x.resolve_span().begin_line == 1)
+            .collect();
+        attachment.set_breakpoints("a.star", &breakpoints);
+
+        let handle = evaluate(registry.register("a"));
+        assert_eq!(
+            DebugEvent::Stopped(DebugStopReason::Breakpoint),
+            events.recv()?
+        );
+        assert_eq!(Some(DebugEvaluationState::Paused), attachment.state());
+        let (line, x, y) = attachment.with_paused(|span, eval| {
+            let vars = eval.local_variables();
+            let var = |name| vars.get(name).map(|x| x.to_string());
+            (span.resolve_span().begin_line, var("x"), var("y"))
+        })?;
+        assert_eq!((1, Some("1".to_owned()), None), (line, x, y));
+
+        attachment.resume()?;
+        assert_eq!(DebugEvent::Finished, events.recv()?);
+        assert_eq!("3", handle.join().unwrap());
+        assert_eq!(None, attachment.state());
+        assert!(attachment.with_paused(|_, _| ()).is_err());
+        assert!(registry.evaluations().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_attach_registered() -> anyhow::Result<()> {
+        let registry = DebugRegistry::new();
+        let target = registry.register("a");
+        assert_eq!(
+            vec![DebugEvaluation {
+                name: "a".to_owned(),
+                attached: false,
+                state: DebugEvaluationState::Running,
+            }],
+            registry.evaluations()
+        );
+
+        let (attachment, events) = attach(&registry, "a");
+        assert!(registry.attach("a", |_| {}).is_err());
+        attachment.pause();
+        let handle = evaluate(target);
+        assert_eq!(DebugEvent::Stopped(DebugStopReason::Pause), events.recv()?);
+
+        // Detaching resumes the evaluation.
+        drop(attachment);
+        assert_eq!("3", handle.join().unwrap());
+        assert!(events.recv().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_exception_breakpoints() -> anyhow::Result<()> {
+        let registry = DebugRegistry::new();
+        let (attachment, events) = attach(&registry, "a");
+        attachment.set_exception_breakpoints(vec![
+            DebugExceptionBreakpoint {
+                only_fail: false,
+                message: Some("not found".to_owned()),
+            },
+            DebugExceptionBreakpoint {
+                only_fail: true,
+                message: None,
+            },
+        ]);
+
+        let target = registry.register("a");
+        let handle = thread::spawn(move || {
+            let program = "def f(x):\n    y = x + 1\n    fail('bad', y)\nf(1)\n";
+            let ast = AstModule::parse("a.star", program.to_owned(), &Dialect::Standard)?;
+            let module = Module::new();
+            let mut eval = Evaluator::new(&module);
+            target.install(&mut eval);
+            eval.eval_module(ast, &Globals::standard()).map(|_| ())
+        });
+        assert_eq!(
+            DebugEvent::Stopped(DebugStopReason::Exception("fail: bad 2".to_owned())),
+            events.recv()?
+        );
+        // Stopped where `fail()` was called, before the stack unwinds.
+        let (line, y) = attachment.with_paused(|span, eval| {
+            let y = eval.local_variables().get("y").map(|x| x.to_string());
+            (span.resolve_span().begin_line, y)
+        })?;
+        assert_eq!((2, Some("2".to_owned())), (line, y));
+
+        attachment.resume()?;
+        assert_eq!(DebugEvent::Finished, events.recv()?);
+        let error = handle.join().unwrap().unwrap_err();
+        assert!(error.to_string().contains("fail: bad 2"));
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Callbacks run before the statements at given lines, without attaching a debugger.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use dupe::Dupe;
+
+use crate::codemap::FileSpanRef;
+use crate::codemap::Span;
+use crate::eval::Evaluator;
+
+type TracepointCallback =
+    Arc<dyn for<'v, 'a> Fn(FileSpanRef, &mut Evaluator<'v, 'a>) + Send + Sync>;
+
+/// Callbacks by file name and 1-indexed line.
+type TracepointsByFile = Arc<Mutex<HashMap<String, HashMap<usize, Vec<TracepointCallback>>>>>;
+
+/// Callbacks run before the statements starting at given `file:line` locations,
+/// for instrumenting code which cannot be edited, like printing the local variables
+/// of a third-party macro.
+///
+/// Unlike a debugger attached through a [`DebugRegistry`](crate::debug::DebugRegistry),
+/// the evaluation never stops: the callbacks run on the evaluation thread with access to the
+/// [`Evaluator`], e.g. to its [`local_variables`](Evaluator::local_variables) and
+/// [`call_stack`](Evaluator::call_stack). Tracepoints can be added and removed while
+/// evaluations which [`install`](Tracepoints::install)ed them are running,
+/// for example from another thread through an `Arc<Tracepoints>`.
+pub struct Tracepoints {
+    by_file: TracepointsByFile,
+    hook: Box<dyn for<'v, 'a> Fn(FileSpanRef, &mut Evaluator<'v, 'a>) + Send + Sync>,
+}
+
+impl Default for Tracepoints {
+    fn default() -> Tracepoints {
+        Tracepoints::new()
+    }
+}
+
+impl Tracepoints {
+    /// Create an empty set of tracepoints.
+    pub fn new() -> Tracepoints {
+        let by_file = TracepointsByFile::default();
+        let by_file2 = by_file.dupe();
+        Tracepoints {
+            by_file,
+            hook: Box::new(move |span, eval| {
+                // Release the lock before running the callbacks, so they can change the
+                // tracepoints.
+                for callback in callbacks(&by_file2, span) {
+                    callback(span, eval);
+                }
+            }),
+        }
+    }
+
+    /// Run `callback` before the statement starting the 1-indexed `line` of `filename`.
+    ///
+    /// When a line holds several statements, like `if x: y` or `a = 1; b = 2`,
+    /// only the first one is instrumented, so the callback runs once each time
+    /// the line is reached. Lines without a statement are never reached.
+    pub fn add(
+        &self,
+        filename: &str,
+        line: usize,
+        callback: impl for<'v, 'a> Fn(FileSpanRef, &mut Evaluator<'v, 'a>) + Send + Sync + 'static,
+    ) {
+        self.by_file
+            .lock()
+            .unwrap()
+            .entry(filename.to_owned())
+            .or_default()
+            .entry(line)
+            .or_default()
+            .push(Arc::new(callback));
+    }
+
+    /// Remove the callbacks of a line.
+    pub fn remove(&self, filename: &str, line: usize) {
+        let mut by_file = self.by_file.lock().unwrap();
+        if let Some(lines) = by_file.get_mut(filename) {
+            lines.remove(&line);
+            if lines.is_empty() {
+                by_file.remove(filename);
+            }
+        }
+    }
+
+    /// Remove all the callbacks.
+    pub fn clear(&self) {
+        self.by_file.lock().unwrap().clear();
+    }
+
+    /// Run the callbacks in the evaluation.
+    ///
+    /// Must be called before the code is evaluated:
+    /// only code compiled after this call runs the callbacks.
+    pub fn install<'a>(&'a self, eval: &mut Evaluator<'_, 'a>) {
+        eval.before_stmt(&*self.hook);
+    }
+}
+
+fn callbacks(by_file: &TracepointsByFile, span: FileSpanRef) -> Vec<TracepointCallback> {
+    let by_file = by_file.lock().unwrap();
+    let lines = match by_file.get(span.filename()) {
+        Some(lines) => lines,
+        None => return Vec::new(),
+    };
+    let line = span.file.find_line(span.span.begin());
+    let callbacks = match lines.get(&(line + 1)) {
+        Some(callbacks) => callbacks,
+        None => return Vec::new(),
+    };
+    // Only the first statement of the line, the one preceded by indentation alone.
+    let line_begin = span.file.line_span(line).begin();
+    let prefix = span
+        .file
+        .source_span(Span::new(line_begin, span.span.begin()));
+    if !prefix.trim_start().is_empty() {
+        return Vec::new();
+    }
+    callbacks.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use dupe::Dupe;
+
+    use crate::debug::Tracepoints;
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::eval::Evaluator;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    const PROGRAM: &str = r#"
+def f(x):
+    y = x * 2
+    if y > 2: z = y
+    return y
+
+a = [f(i) for i in range(3)]
+"#;
+
+    fn run(tracepoints: &Tracepoints) {
+        let ast = AstModule::parse("macro.star", PROGRAM.to_owned(), &Dialect::Standard).unwrap();
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        tracepoints.install(&mut eval);
+        eval.eval_module(ast, &Globals::standard()).unwrap();
+    }
+
+    fn log_line(tracepoints: &Tracepoints, line: usize) -> Arc<Mutex<Vec<String>>> {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let log2 = log.dupe();
+        tracepoints.add("macro.star", line, move |span, eval| {
+            let mut vars: Vec<String> = eval
+                .local_variables()
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect();
+            vars.sort();
+            log2.lock()
+                .unwrap()
+                .push(format!("{}: {}", span.resolve_span(), vars.join(" ")));
+        });
+        log
+    }
+
+    #[test]
+    fn test_tracepoint_locals() {
+        let tracepoints = Tracepoints::new();
+        let log = log_line(&tracepoints, 5);
+        run(&tracepoints);
+        assert_eq!(
+            vec!["5:5-13: x=0 y=0", "5:5-13: x=1 y=2", "5:5-13: x=2 y=4 z=4"],
+            *log.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tracepoint_first_statement_of_line() {
+        let tracepoints = Tracepoints::new();
+        let log = log_line(&tracepoints, 4);
+        // Not a statement.
+        let blank = log_line(&tracepoints, 1);
+        run(&tracepoints);
+        assert_eq!(3, log.lock().unwrap().len());
+        assert!(blank.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tracepoint_remove() {
+        let tracepoints = Tracepoints::new();
+        let log = log_line(&tracepoints, 3);
+        let other = log_line(&tracepoints, 7);
+        tracepoints.remove("macro.star", 3);
+        run(&tracepoints);
+        assert!(log.lock().unwrap().is_empty());
+        assert_eq!(1, other.lock().unwrap().len());
+        tracepoints.clear();
+        run(&tracepoints);
+        assert_eq!(1, other.lock().unwrap().len());
+    }
+}
@@ -0,0 +1,89 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Native functions which can only be called by evaluations granted a capability.
+
+use allocative::Allocative;
+use derive_more::Display;
+use gazebo::any::ProvidesStaticType;
+
+use crate as starlark;
+use crate::docs::DocItem;
+use crate::eval::Arguments;
+use crate::eval::Evaluator;
+use crate::values::function::NativeFunction;
+use crate::values::function::FUNCTION_TYPE;
+use crate::values::FrozenHeap;
+use crate::values::FrozenValue;
+use crate::values::FrozenValueTyped;
+use crate::values::Heap;
+use crate::values::StarlarkValue;
+use crate::values::Value;
+
+/// A native function added within
+/// [`GlobalsBuilder::require_capability`](crate::environment::GlobalsBuilder::require_capability).
+///
+/// A wrapper rather than a field of [`NativeFunction`], because functions of
+/// `#[starlark_module]` are allocated once and shared by all the globals which include them.
+/// Not being a [`NativeFunction`], the function is also never evaluated at compile time.
+#[derive(Debug, Display, NoSerialize, ProvidesStaticType, Allocative)]
+#[display(fmt = "{}", function)]
+pub(crate) struct RestrictedFunction {
+    capability: String,
+    function: FrozenValueTyped<'static, NativeFunction>,
+}
+
+starlark_simple_value!(RestrictedFunction);
+
+impl RestrictedFunction {
+    /// Restrict `value` if it is a native function.
+    pub(crate) fn wrap(capability: &str, value: FrozenValue, heap: &FrozenHeap) -> FrozenValue {
+        match FrozenValueTyped::new(value) {
+            Some(function) => heap.alloc_simple(RestrictedFunction {
+                capability: capability.to_owned(),
+                function,
+            }),
+            None => value,
+        }
+    }
+}
+
+impl<'v> StarlarkValue<'v> for RestrictedFunction {
+    starlark_type!(FUNCTION_TYPE);
+
+    fn invoke(
+        &self,
+        me: Value<'v>,
+        args: &Arguments<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        eval.check_capability(&self.function.name, &self.capability)?;
+        self.function.as_ref().invoke(me, args, eval)
+    }
+
+    fn get_attr(&self, attribute: &str, heap: &'v Heap) -> Option<Value<'v>> {
+        self.function.as_ref().get_attr(attribute, heap)
+    }
+
+    fn dir_attr(&self) -> Vec<String> {
+        self.function.as_ref().dir_attr()
+    }
+
+    fn documentation(&self) -> Option<DocItem> {
+        self.function.as_ref().documentation()
+    }
+}
@@ -0,0 +1,57 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Selection of the dialect and globals for a file.
+
+use std::path::Path;
+
+use crate::environment::Globals;
+use crate::syntax::Dialect;
+
+/// The environment a file is parsed and evaluated in.
+#[derive(Debug, Clone)]
+pub struct FileKind {
+    /// Dialect used to parse the file.
+    pub dialect: Dialect,
+    /// Globals available to the file.
+    pub globals: Globals,
+}
+
+/// Chooses the [`FileKind`] for a file by its path,
+/// so that for example `BUILD` files and `.bzl` files are parsed and evaluated differently.
+///
+/// Tools which process many files (the file loader, LSP, CLI) should use the same resolver,
+/// so the files get the same environment everywhere.
+///
+/// Any function `Fn(&Path) -> FileKind` is a resolver.
+pub trait FileKindResolver {
+    /// Dialect and globals for the file at `path`.
+    fn resolve(&self, path: &Path) -> FileKind;
+}
+
+impl<F: Fn(&Path) -> FileKind> FileKindResolver for F {
+    fn resolve(&self, path: &Path) -> FileKind {
+        self(path)
+    }
+}
+
+impl FileKindResolver for FileKind {
+    /// Same dialect and globals for all files.
+    fn resolve(&self, _path: &Path) -> FileKind {
+        self.clone()
+    }
+}
@@ -0,0 +1,94 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Checks of module bindings run during freeze,
+//! see [`FreezeOptions::validators`](crate::environment::FreezeOptions::validators).
+
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+
+use crate::codemap::FileSpan;
+use crate::values::FrozenValue;
+
+/// Check run on each exported binding of a module
+/// by [`Module::freeze_with_options`](crate::environment::Module::freeze_with_options).
+///
+/// Implemented for closures `Fn(&str, FrozenValue) -> anyhow::Result<()>`.
+pub trait FreezeValidator: Send + Sync + 'static {
+    /// Check binding `name` with frozen `value`. Return an error describing the violation.
+    fn validate(&self, name: &str, value: FrozenValue) -> anyhow::Result<()>;
+}
+
+impl<F> FreezeValidator for F
+where
+    F: Fn(&str, FrozenValue) -> anyhow::Result<()> + Send + Sync + 'static,
+{
+    fn validate(&self, name: &str, value: FrozenValue) -> anyhow::Result<()> {
+        self(name, value)
+    }
+}
+
+impl Debug for dyn FreezeValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FreezeValidator")
+    }
+}
+
+/// Binding rejected by a [`FreezeValidator`].
+#[derive(Debug, Clone)]
+pub struct FreezeViolation {
+    /// Name of the binding.
+    pub name: String,
+    /// Where the binding was first assigned.
+    /// `None` for bindings not assigned by module code, e.g. added with
+    /// [`Module::set`](crate::environment::Module::set).
+    pub span: Option<FileSpan>,
+    /// Error reported by the validator.
+    pub message: String,
+}
+
+impl Display for FreezeViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(span) = &self.span {
+            write!(f, "{}: ", span)?;
+        }
+        write!(f, "`{}`: {}", self.name, self.message)
+    }
+}
+
+/// Error returned from freeze when validators reject some bindings.
+/// Contains all the violations, not just the first one.
+#[derive(Debug, Clone, thiserror::Error)]
+pub struct FreezeValidationError {
+    /// Violations, in the order of the bindings, then of the validators.
+    pub violations: Vec<FreezeViolation>,
+}
+
+impl Display for FreezeValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Module validation failed with {} error(s):",
+            self.violations.len()
+        )?;
+        for violation in &self.violations {
+            write!(f, "\n  {}", violation)?;
+        }
+        Ok(())
+    }
+}
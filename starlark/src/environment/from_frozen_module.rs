@@ -0,0 +1,211 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Typed access to the exported bindings of a [`FrozenModule`].
+
+use crate::environment::EnvironmentError;
+use crate::environment::FrozenModule;
+use crate::values::OwnedFrozenValue;
+use crate::values::OwnedFrozenValueTyped;
+use crate::values::StarlarkValue;
+use crate::values::UnpackValue;
+
+/// Rust type which can be constructed from the exported bindings of a [`FrozenModule`].
+///
+/// Usually implemented with [`#[derive(FromFrozenModule)]`](macro@crate::environment::FromFrozenModule),
+/// which reads each field from the binding of the same name:
+///
+/// ```
+/// use starlark::environment::FromFrozenModule;
+/// # use starlark::environment::{Globals, Module};
+/// # use starlark::eval::Evaluator;
+/// # use starlark::syntax::{AstModule, Dialect};
+///
+/// #[derive(FromFrozenModule)]
+/// struct Config {
+///     name: String,
+///     #[starlark(rename = "JOBS")]
+///     jobs: i32,
+///     tags: Option<Vec<String>>,
+/// }
+///
+/// # fn run() -> anyhow::Result<()> {
+/// let module = Module::new();
+/// let ast = AstModule::parse("config.star", "name = 'x'\nJOBS = 4".to_owned(), &Dialect::Standard)?;
+/// Evaluator::new(&module).eval_module(ast, &Globals::standard())?;
+/// let config = Config::from_frozen_module(&module.freeze()?)?;
+/// assert_eq!("x", config.name);
+/// assert_eq!(4, config.jobs);
+/// assert_eq!(None, config.tags);
+/// # Ok(())
+/// # }
+/// # run().unwrap();
+/// ```
+pub trait FromFrozenModule: Sized {
+    /// Read the bindings of the module into `Self`.
+    fn from_frozen_module(module: &FrozenModule) -> anyhow::Result<Self>;
+}
+
+/// Rust type which can be read from a single exported binding of a [`FrozenModule`].
+///
+/// Implemented for all types implementing [`UnpackValue`] for any lifetime,
+/// for [`OwnedFrozenValue`] and [`OwnedFrozenValueTyped`],
+/// and for [`Option`] of these, which is `None` when the binding is not defined.
+pub trait FrozenModuleField: Sized {
+    /// Read the binding `name` of the module.
+    fn from_frozen_module_binding(module: &FrozenModule, name: &str) -> anyhow::Result<Self>;
+
+    /// Read the binding `name`, returning `None` if the module does not define it.
+    fn from_frozen_module_binding_option(
+        module: &FrozenModule,
+        name: &str,
+    ) -> anyhow::Result<Option<Self>> {
+        match module.get_option(name)? {
+            None => Ok(None),
+            Some(_) => Self::from_frozen_module_binding(module, name).map(Some),
+        }
+    }
+}
+
+impl<T: for<'v> UnpackValue<'v>> FrozenModuleField for T {
+    fn from_frozen_module_binding(module: &FrozenModule, name: &str) -> anyhow::Result<Self> {
+        let value = module.get(name)?;
+        let value = value.value();
+        T::unpack_value(value).ok_or_else(|| {
+            EnvironmentError::ModuleSymbolIncorrectType(
+                name.to_owned(),
+                value.get_type().to_owned(),
+                T::expected(),
+            )
+            .into()
+        })
+    }
+}
+
+impl FrozenModuleField for OwnedFrozenValue {
+    fn from_frozen_module_binding(module: &FrozenModule, name: &str) -> anyhow::Result<Self> {
+        module.get(name)
+    }
+}
+
+impl<T: StarlarkValue<'static>> FrozenModuleField for OwnedFrozenValueTyped<T> {
+    fn from_frozen_module_binding(module: &FrozenModule, name: &str) -> anyhow::Result<Self> {
+        module.get(name)?.downcast::<T>().map_err(|value| {
+            EnvironmentError::ModuleSymbolIncorrectType(
+                name.to_owned(),
+                value.value().get_type().to_owned(),
+                T::get_type_value_static().as_str().to_owned(),
+            )
+            .into()
+        })
+    }
+}
+
+impl<T: FrozenModuleField> FrozenModuleField for Option<T> {
+    fn from_frozen_module_binding(module: &FrozenModule, name: &str) -> anyhow::Result<Self> {
+        T::from_frozen_module_binding_option(module, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as starlark;
+    use crate::assert::Assert;
+    use crate::environment::FromFrozenModule;
+    use crate::values::float::StarlarkFloat;
+    use crate::values::OwnedFrozenValue;
+    use crate::values::OwnedFrozenValueTyped;
+
+    #[derive(FromFrozenModule)]
+    struct Config {
+        name: String,
+        #[starlark(rename = "JOBS")]
+        jobs: i32,
+        enabled: bool,
+        srcs: Vec<String>,
+        deps: Option<Vec<String>>,
+        extra: OwnedFrozenValue,
+        ratio: OwnedFrozenValueTyped<StarlarkFloat>,
+    }
+
+    #[derive(FromFrozenModule)]
+    struct Private {
+        #[starlark(rename = "_jobs")]
+        _jobs: Option<i32>,
+    }
+
+    fn module(program: &str) -> starlark::environment::FrozenModule {
+        Assert::new().pass_module(program)
+    }
+
+    #[test]
+    fn test_from_frozen_module() {
+        let config = Config::from_frozen_module(&module(
+            r#"
+name = "hello"
+JOBS = 3
+enabled = True
+srcs = ["a.c", "b.c"]
+extra = struct(x = 1)
+ratio = 0.5
+_private = 1
+"#,
+        ))
+        .unwrap();
+        assert_eq!("hello", config.name);
+        assert_eq!(3, config.jobs);
+        assert!(config.enabled);
+        assert_eq!(vec!["a.c".to_owned(), "b.c".to_owned()], config.srcs);
+        assert_eq!(None, config.deps);
+        assert_eq!("struct", config.extra.value().get_type());
+        assert_eq!(0.5, config.ratio.as_ref().0);
+    }
+
+    #[test]
+    fn test_from_frozen_module_errors() {
+        let err = |program: &str| {
+            Config::from_frozen_module(&module(program))
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        let base = "name = 'x'\nenabled = True\nsrcs = []\nextra = 1\n";
+        assert_eq!(
+            "Module has no symbol `JOBS`",
+            err(&format!("{base}ratio = 1.0")),
+        );
+        assert_eq!(
+            "Module symbol `JOBS` has type `string`, expected `int.type`",
+            err(&format!("{base}ratio = 1.0\nJOBS = 'many'")),
+        );
+        assert_eq!(
+            "Module symbol `deps` has type `list`, expected `list or tuple of str`",
+            err(&format!("{base}ratio = 1.0\nJOBS = 1\ndeps = [1]")),
+        );
+        assert_eq!(
+            "Module symbol `ratio` has type `int`, expected `float`",
+            err(&format!("{base}ratio = 1\nJOBS = 1")),
+        );
+        assert_eq!(
+            "Module symbol `_jobs` is not exported",
+            Private::from_frozen_module(&module("_jobs = 1"))
+                .err()
+                .unwrap()
+                .to_string(),
+        );
+    }
+}
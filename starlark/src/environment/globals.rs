@@ -16,7 +16,10 @@
  */
 
 use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Debug;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use allocative::Allocative;
 use derive_more::Display;
@@ -33,6 +36,8 @@ use crate::docs;
 use crate::docs::DocItem;
 use crate::docs::DocString;
 use crate::docs::DocStringKind;
+use crate::environment::capability::RestrictedFunction;
+use crate::environment::StarlarkGlobalsStatic;
 use crate::stdlib;
 pub use crate::stdlib::LibraryExtension;
 use crate::values::function::NativeAttribute;
@@ -66,6 +71,7 @@ pub struct Methods(Arc<MethodsData>);
 struct GlobalsData {
     heap: FrozenHeapRef,
     variables: SymbolMap<FrozenValue>,
+    lazy_variables: SymbolMap<Arc<LazyGlobal>>,
     variable_names: Vec<FrozenStringValue>,
     docstring: Option<String>,
 }
@@ -86,10 +92,59 @@ pub struct GlobalsBuilder {
     heap: FrozenHeap,
     // Normal top-level variables, e.g. True/hash
     variables: SymbolMap<FrozenValue>,
+    // Top-level variables computed on first access
+    lazy_variables: SymbolMap<Arc<LazyGlobal>>,
     // The list of struct fields, pushed to the end
     struct_fields: Vec<SmallMap<FrozenStringValue, FrozenValue>>,
     // The raw docstring for this module
     docstring: Option<String>,
+    // Capability required by the functions being added, see `require_capability`
+    capability: Option<String>,
+}
+
+/// Global variable whose value is computed on first access.
+struct LazyGlobal {
+    /// Taken when the value is computed.
+    init: Mutex<Option<Box<dyn FnOnce(&FrozenHeap) -> FrozenValue + Send>>>,
+    /// The value, and the heap it is allocated in.
+    value: OnceCell<(FrozenValue, FrozenHeapRef)>,
+}
+
+impl LazyGlobal {
+    fn get(&self) -> FrozenValue {
+        self.value
+            .get_or_init(|| {
+                let init = self
+                    .init
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .expect("lazy global initializer called twice");
+                let heap = FrozenHeap::new();
+                let value = init(&heap);
+                (value, heap.into_ref())
+            })
+            .0
+    }
+}
+
+impl Debug for LazyGlobal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value.get() {
+            Some((value, _)) => Debug::fmt(value, f),
+            None => write!(f, "<lazy>"),
+        }
+    }
+}
+
+impl Allocative for LazyGlobal {
+    fn visit<'a, 'b: 'a>(&self, visitor: &'a mut allocative::Visitor<'b>) {
+        let mut visitor = visitor.enter_self_sized::<Self>();
+        if let Some((_, heap)) = self.value.get() {
+            visitor.visit_field(allocative::Key::new("heap"), heap);
+        }
+        visitor.exit();
+    }
 }
 
 /// Used to build a [`Methods`] value.
@@ -109,6 +164,11 @@ impl Globals {
         GlobalsBuilder::new().build()
     }
 
+    /// Whether both are the same globals, not copies.
+    pub(crate) fn ptr_eq(&self, other: &Globals) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
     /// Create a [`Globals`] following the
     /// [Starlark standard](https://github.com/bazelbuild/starlark/blob/master/spec.md#built-in-constants-and-functions).
     pub fn standard() -> Self {
@@ -141,8 +201,14 @@ impl Globals {
 
     /// This function is only safe if you first call `heap` and keep a reference to it.
     /// Therefore, don't expose it on the public API.
+    ///
+    /// If the variable was registered with [`GlobalsBuilder::set_lazy`],
+    /// this computes its value on first access.
     pub(crate) fn get_frozen(&self, name: &str) -> Option<FrozenValue> {
-        self.0.variables.get_str(name).copied()
+        match self.0.variables.get_str(name) {
+            Some(value) => Some(*value),
+            None => self.0.lazy_variables.get_str(name).map(|lazy| lazy.get()),
+        }
     }
 
     /// Get all the names defined in this environment.
@@ -233,8 +299,10 @@ impl GlobalsBuilder {
         Self {
             heap: FrozenHeap::new(),
             variables: SymbolMap::new(),
+            lazy_variables: SymbolMap::new(),
             struct_fields: Vec::new(),
             docstring: None,
+            capability: None,
         }
     }
 
@@ -269,6 +337,16 @@ impl GlobalsBuilder {
         self.set(name, AllocStruct(fields));
     }
 
+    /// Native functions added by `f` can only be called by evaluations granted `capability`
+    /// with [`Evaluator::set_capabilities`](crate::eval::Evaluator::set_capabilities),
+    /// e.g. to deny file system access to untrusted scripts. Other values added by `f`
+    /// are not restricted. Only the innermost capability applies when calls are nested.
+    pub fn require_capability(&mut self, capability: &str, f: impl FnOnce(&mut GlobalsBuilder)) {
+        let outer = self.capability.replace(capability.to_owned());
+        f(self);
+        self.capability = outer;
+    }
+
     /// A fluent API for modifying [`GlobalsBuilder`] and returning the result.
     pub fn with(mut self, f: impl FnOnce(&mut Self)) -> Self {
         f(&mut self);
@@ -286,11 +364,17 @@ impl GlobalsBuilder {
         let variable_names = self
             .variables
             .keys()
+            .chain(
+                self.lazy_variables
+                    .keys()
+                    .filter(|x| self.variables.get(x).is_none()),
+            )
             .map(|x| self.heap.alloc_str_intern(x.as_str()))
             .collect();
         Globals(Arc::new(GlobalsData {
             heap: self.heap.into_ref(),
             variables: self.variables,
+            lazy_variables: self.lazy_variables,
             variable_names,
             docstring: self.docstring,
         }))
@@ -298,7 +382,10 @@ impl GlobalsBuilder {
 
     /// Set a value in the [`GlobalsBuilder`].
     pub fn set<'v, V: AllocFrozenValue>(&'v mut self, name: &str, value: V) {
-        let value = value.alloc_frozen_value(&self.heap);
+        let mut value = value.alloc_frozen_value(&self.heap);
+        if let Some(capability) = &self.capability {
+            value = RestrictedFunction::wrap(capability, value, &self.heap);
+        }
         match self.struct_fields.last_mut() {
             None => self.variables.insert(name, value),
             Some(fields) => {
@@ -308,6 +395,52 @@ impl GlobalsBuilder {
         };
     }
 
+    /// Set a value in the [`GlobalsBuilder`] which is computed on first access.
+    ///
+    /// The function is called at most once, when a script referencing `name`
+    /// is compiled, and the result is cached for the lifetime of the [`Globals`].
+    /// Lazy values are not included in [`Globals::describe`] or documentation,
+    /// because producing those would force the value.
+    /// A value set with [`set`](GlobalsBuilder::set) under the same name takes precedence.
+    ///
+    /// Lazy values cannot be placed in a [`struct_`](GlobalsBuilder::struct_).
+    pub fn set_lazy<F, V>(&mut self, name: &str, f: F)
+    where
+        F: FnOnce() -> V + Send + 'static,
+        V: AllocFrozenValue,
+    {
+        assert!(
+            self.struct_fields.is_empty(),
+            "lazy values are not supported in structs"
+        );
+        self.lazy_variables.insert(
+            name,
+            Arc::new(LazyGlobal {
+                init: Mutex::new(Some(Box::new(move |heap| f().alloc_frozen_value(heap)))),
+                value: OnceCell::new(),
+            }),
+        );
+    }
+
+    /// Add the globals defined in Starlark by `globals`, see [`StarlarkGlobalsStatic`].
+    /// They are lazy values, see [`set_lazy`](GlobalsBuilder::set_lazy).
+    pub fn set_starlark_globals(&mut self, globals: &'static StarlarkGlobalsStatic) {
+        assert!(
+            self.struct_fields.is_empty(),
+            "Starlark globals are not supported in structs"
+        );
+        for name in globals.names() {
+            let init = move |heap: &FrozenHeap| globals.get(name, heap);
+            self.lazy_variables.insert(
+                name,
+                Arc::new(LazyGlobal {
+                    init: Mutex::new(Some(Box::new(init))),
+                    value: OnceCell::new(),
+                }),
+            );
+        }
+    }
+
     /// Set a method. This function is usually called from code
     /// generated by `starlark_derive` and rarely needs to be called manually.
     pub fn set_function<F>(
@@ -523,6 +656,10 @@ impl GlobalsStatic {
         for (name, value) in globals.0.variables.iter() {
             out.set(name.as_str(), *value)
         }
+        for (name, lazy) in globals.0.lazy_variables.iter() {
+            // Share the lazy value, so it is computed only once.
+            out.lazy_variables.insert(name.as_str(), lazy.dupe());
+        }
         out.docstring = globals.0.docstring.clone();
     }
 }
@@ -590,6 +727,9 @@ fn common_documentation<'a>(
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic;
+    use std::sync::atomic::AtomicUsize;
+
     use derive_more::Display;
     use gazebo::any::ProvidesStaticType;
 
@@ -632,4 +772,34 @@ assert_eq(magic.my_type, "magic")
 assert_eq(magic.my_value, 42)"#,
         );
     }
+
+    #[test]
+    fn test_set_lazy() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        let globals = GlobalsBuilder::new()
+            .with(|x| {
+                x.set_lazy("lazy", || {
+                    CALLS.fetch_add(1, atomic::Ordering::SeqCst);
+                    "computed"
+                });
+                x.set_lazy("unused", || -> i32 { panic!("must not be computed") });
+            })
+            .build();
+        assert_eq!(0, CALLS.load(atomic::Ordering::SeqCst));
+        assert_eq!(
+            vec!["lazy", "unused"],
+            globals
+                .names()
+                .map(|x| x.as_str())
+                .sorted()
+                .collect::<Vec<_>>()
+        );
+
+        let mut a = Assert::new();
+        a.globals(globals.dupe());
+        a.eq("'computed'", "lazy");
+        a.eq("'computed'", "lazy + ''");
+        assert_eq!(1, CALLS.load(atomic::Ordering::SeqCst));
+    }
 }
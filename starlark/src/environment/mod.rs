@@ -22,14 +22,27 @@
 //! User executions store their values in a [`Module`], which have to be converted to a
 //! [`FrozenModule`] using [`freeze`](Module::freeze) before they can be `load()`'d as a dependency.
 
+mod capability;
+mod file_kind;
+mod freeze_validator;
+mod from_frozen_module;
 mod globals;
+mod module_diff;
 mod module_dump;
+mod module_inspect;
 mod modules;
 pub(crate) mod names;
 pub(crate) mod slots;
+mod starlark_globals;
 
+pub use file_kind::*;
+pub use freeze_validator::*;
+pub use from_frozen_module::*;
 pub use globals::*;
+pub use module_inspect::ModuleBinding;
 pub use modules::*;
+pub use starlark_derive::FromFrozenModule;
+pub use starlark_globals::*;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -50,6 +63,8 @@ pub(crate) enum EnvironmentError {
     ModuleHasNoSymbolDidYouMean(String, String),
     #[error("Module symbol `{0}` is not exported")]
     ModuleSymbolIsNotExported(String),
+    #[error("Module symbol `{0}` has type `{1}`, expected `{2}`")]
+    ModuleSymbolIncorrectType(String, String, String),
     #[error("No imports are available, you tried `{0}` (no call to `Evaluator.set_loader`)")]
     NoImportsAvailable(String),
 }
@@ -0,0 +1,111 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::environment::FrozenModule;
+use crate::values::diff::Differ;
+use crate::values::DiffPathItem;
+use crate::values::ValueDiff;
+
+impl FrozenModule {
+    /// Structural differences from the exported bindings of this module to those of `new`,
+    /// e.g. to show what changed in a generated configuration. Values are compared with
+    /// [`Value::diff`](crate::values::Value::diff), and paths start with the binding.
+    pub fn diff(&self, new: &FrozenModule) -> Vec<ValueDiff> {
+        let mut differ = Differ::default();
+        let names = |module: &FrozenModule| {
+            module
+                .names()
+                .filter_map(|name| Some((name.as_str().to_owned(), module.get(&name).ok()?)))
+                .collect::<Vec<_>>()
+        };
+        let (old, new) = (names(self), names(new));
+        for (name, value) in &old {
+            let binding = DiffPathItem::Binding(name.clone());
+            match new.iter().find(|(x, _)| x == name) {
+                Some((_, x)) => differ.diff_at(binding, value.value(), x.value()),
+                None => differ.removed(binding, value.value()),
+            }
+        }
+        for (name, value) in &new {
+            if !old.iter().any(|(x, _)| x == name) {
+                differ.added(DiffPathItem::Binding(name.clone()), value.value());
+            }
+        }
+        differ.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+
+    fn diff(old: &str, new: &str) -> Vec<String> {
+        let (old, new) = (assert::pass_module(old), assert::pass_module(new));
+        old.diff(&new).iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn test_module_diff() {
+        let old = r#"
+config = {"name": "x", "deps": [1, 2, 3], "opts": struct(debug = True)}
+removed = 1
+_private = 1
+same = [1, {"a": (1, 2)}]
+"#;
+        let new = r#"
+config = {"name": "y", "deps": [1, 2], "opts": struct(debug = "yes", level = 2)}
+added = None
+_private = 2
+same = [1, {"a": (1, 2)}]
+"#;
+        assert_eq!(
+            vec![
+                "config[\"name\"]: \"x\" -> \"y\"",
+                "config[\"deps\"][2]: removed 3",
+                "config[\"opts\"].debug: True (bool) -> \"yes\" (string)",
+                "config[\"opts\"].level: added 2",
+                "removed: removed 1",
+                "added: added None",
+            ],
+            diff(old, new)
+        );
+        assert!(diff(old, old).is_empty());
+    }
+
+    #[test]
+    fn test_value_diff() {
+        let old = assert::pass("[1, {'k': 2}, 'x']");
+        let new = assert::pass("[1, {'k': 3, 'l': 4}]");
+        let diffs = old.value().diff(new.value());
+        assert_eq!(
+            vec![
+                "[1][\"k\"]: 2 -> 3",
+                "[1][\"l\"]: added 4",
+                "[2]: removed \"x\""
+            ],
+            diffs.iter().map(|x| x.to_string()).collect::<Vec<_>>()
+        );
+        let diffs = old.value().diff(assert::pass("(1,)").value());
+        assert_eq!(1, diffs.len());
+        assert!(diffs[0].path.is_empty());
+        // Values which refer to themselves do not recurse forever.
+        let diffs = assert::pass("x = [1]; x.append(x); x")
+            .value()
+            .diff(assert::pass("x = [2]; x.append(x); x").value());
+        assert_eq!(1, diffs.len());
+    }
+}
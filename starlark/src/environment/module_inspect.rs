@@ -0,0 +1,151 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::docs::DocItem;
+use crate::environment::Module;
+use crate::values::Value;
+
+/// A binding of a [`Module`] which is being evaluated, as returned by [`Module::inspect`].
+#[derive(Debug, Clone)]
+pub struct ModuleBinding<'v> {
+    /// Name of the binding.
+    pub name: String,
+    /// Current value of the binding.
+    pub value: Value<'v>,
+    /// Type of the value, as returned by `type()`.
+    pub typ: &'static str,
+    /// Whether the binding is exported. `load`ed symbols and names starting
+    /// with an underscore are not.
+    pub public: bool,
+    /// Documentation of the value, e.g. the docstring of a function.
+    pub docs: Option<DocItem>,
+    /// How many times the name was assigned, so far.
+    pub assignments: u32,
+}
+
+impl Module {
+    /// The bindings of this module which are assigned, in the order of definition.
+    ///
+    /// This is a snapshot of the module which can be taken at any point of the evaluation,
+    /// including from a native function, e.g. to display the state of an interactive session.
+    /// In-place modifications of the values, like `list.append`, are not counted
+    /// as assignments.
+    pub fn inspect<'v>(&'v self) -> Vec<ModuleBinding<'v>> {
+        let slots = self.slots();
+        let private = self.names().private_slots();
+        let mut res = Vec::new();
+        for (name, slot) in self.names().all_names() {
+            let value = match slots.get_slot(slot) {
+                Some(value) => value,
+                None => continue,
+            };
+            res.push(ModuleBinding {
+                name: name.as_str().to_owned(),
+                value,
+                typ: value.get_type(),
+                public: !private.contains(&slot),
+                docs: value.documentation(),
+                assignments: slots.assignments(slot),
+            });
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use starlark_derive::starlark_module;
+
+    use crate as starlark;
+    use crate::docs::DocItem;
+    use crate::environment::GlobalsBuilder;
+    use crate::environment::Module;
+    use crate::eval::Evaluator;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+    use crate::values::none::NoneType;
+
+    thread_local! {
+        static SNAPSHOT: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[starlark_module]
+    fn snapshot(builder: &mut GlobalsBuilder) {
+        fn snapshot(eval: &mut Evaluator) -> anyhow::Result<NoneType> {
+            let bindings = eval.inspect_module();
+            SNAPSHOT.with(|s| {
+                *s.borrow_mut() = bindings
+                    .iter()
+                    .map(|b| {
+                        format!(
+                            "{}: {} = {} ({})",
+                            b.name,
+                            b.typ,
+                            b.value.to_repr(),
+                            b.assignments
+                        )
+                    })
+                    .collect()
+            });
+            Ok(NoneType)
+        }
+    }
+
+    #[test]
+    fn test_inspect() -> anyhow::Result<()> {
+        let program = r#"
+x = 1
+x += 1
+xs = []
+xs.append(x)
+def f():
+    """Does nothing."""
+    pass
+_private = "p"
+snapshot()
+later = 2
+"#;
+        let module = Module::new();
+        let globals = GlobalsBuilder::standard().with(snapshot).build();
+        let ast = AstModule::parse("x.star", program.to_owned(), &Dialect::Extended)?;
+        Evaluator::new(&module).eval_module(ast, &globals)?;
+        assert_eq!(
+            vec![
+                "x: int = 2 (2)",
+                "xs: list = [2] (1)",
+                "f: function = x.star.f (1)",
+                "_private: string = \"p\" (1)",
+            ],
+            SNAPSHOT.with(|s| s.borrow().clone())
+        );
+
+        let bindings = module.inspect();
+        let names: Vec<&str> = bindings.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(vec!["x", "xs", "f", "_private", "later"], names);
+        assert!(bindings[0].public);
+        assert!(!bindings[3].public);
+        match &bindings[2].docs {
+            Some(DocItem::Function(f)) => {
+                assert_eq!("Does nothing.", f.docs.as_ref().unwrap().summary)
+            }
+            docs => panic!("unexpected docs: {:?}", docs),
+        }
+        Ok(())
+    }
+}
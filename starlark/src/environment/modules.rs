@@ -34,7 +34,10 @@ use dupe::Dupe;
 use gazebo::any::ProvidesStaticType;
 use itertools::Itertools;
 
+use crate::codemap::FileSpan;
 use crate::collections::Hashed;
+use crate::collections::SmallMap;
+use crate::collections::SmallSet;
 use crate::docs;
 use crate::docs::DocItem;
 use crate::docs::DocString;
@@ -45,13 +48,20 @@ use crate::environment::slots::FrozenSlots;
 use crate::environment::slots::ModuleSlotId;
 use crate::environment::slots::MutableSlots;
 use crate::environment::EnvironmentError;
+use crate::environment::FreezeValidationError;
+use crate::environment::FreezeValidator;
+use crate::environment::FreezeViolation;
 use crate::errors::did_you_mean::did_you_mean;
 use crate::eval::runtime::profile::heap::RetainedHeapProfileMode;
+use crate::eval::AllocArgs;
+use crate::eval::AllocNamedArgs;
+use crate::eval::Evaluator;
 use crate::eval::ProfileData;
 use crate::syntax::ast::Visibility;
 use crate::values::layout::heap::heap_type::HeapKind;
 use crate::values::layout::heap::profile::aggregated::AggregateHeapProfileInfo;
 use crate::values::layout::heap::profile::aggregated::RetainedHeapProfile;
+use crate::values::layout::heap::profile::by_module::RetainedByBinding;
 use crate::values::Freezer;
 use crate::values::FrozenHeap;
 use crate::values::FrozenHeapRef;
@@ -61,6 +71,7 @@ use crate::values::Heap;
 use crate::values::OwnedFrozenValue;
 use crate::values::Trace;
 use crate::values::Tracer;
+use crate::values::UnpackValue;
 use crate::values::Value;
 
 #[derive(Debug, thiserror::Error)]
@@ -126,6 +137,23 @@ pub struct ModuleDocs {
     pub members: HashMap<String, Option<DocItem>>,
 }
 
+/// Options for [`Module::freeze_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct FreezeOptions {
+    /// Drop private bindings (names starting with underscore and `load`ed symbols)
+    /// which are not read by functions reachable from exported bindings.
+    ///
+    /// Values of dropped bindings are not copied to the frozen heap
+    /// (unless they are referenced by other retained values),
+    /// and dropped bindings cannot be accessed from the [`FrozenModule`].
+    pub drop_unreferenced_private: bool,
+    /// Validators run over every exported binding after the values are frozen.
+    ///
+    /// If any binding is rejected, freeze fails with [`FreezeValidationError`]
+    /// listing all the violations with the locations of the bindings.
+    pub validators: Vec<Arc<dyn FreezeValidator>>,
+}
+
 /// A container for user values, used during execution.
 ///
 /// A module contains both a [`FrozenHeap`] and [`Heap`] on which different values are allocated.
@@ -143,6 +171,8 @@ pub struct Module {
     // exported.
     slots: MutableSlots<'static>,
     docstring: RefCell<Option<String>>,
+    /// Where the top-level bindings were first assigned, used to report validation errors.
+    binding_spans: RefCell<SmallMap<String, FileSpan>>,
     /// Module evaluation duration:
     /// * evaluation of the top-level statements
     /// * optimizations during that evaluation
@@ -156,6 +186,11 @@ pub struct Module {
 }
 
 impl FrozenModule {
+    /// Whether both are the same module, not copies.
+    pub(crate) fn ptr_eq(&self, other: &FrozenModule) -> bool {
+        Arc::ptr_eq(&self.module.0, &other.module.0)
+    }
+
     fn get_any_visibility_option(&self, name: &str) -> Option<(OwnedFrozenValue, Visibility)> {
         self.module.0.names.get_name(name).and_then(|(slot, vis)|
         // This code is safe because we know the frozen module ref keeps the values alive
@@ -210,6 +245,45 @@ impl FrozenModule {
             })
     }
 
+    /// Call the exported function `name` with arguments converted from Rust values,
+    /// and unpack the result to a Rust type, see [`Evaluator::call_function`].
+    ///
+    /// ```
+    /// # use starlark::environment::Globals;
+    /// # use starlark::environment::Module;
+    /// # use starlark::eval::Evaluator;
+    /// # use starlark::syntax::AstModule;
+    /// # use starlark::syntax::Dialect;
+    /// let module = Module::new();
+    /// let mut eval = Evaluator::new(&module);
+    /// let ast = AstModule::parse(
+    ///     "greet.star",
+    ///     "def greet(name, punct = '.'): return 'Hello, ' + name + punct".to_owned(),
+    ///     &Dialect::Standard,
+    /// )
+    /// .unwrap();
+    /// eval.eval_module(ast, &Globals::standard()).unwrap();
+    /// drop(eval);
+    /// let frozen = module.freeze().unwrap();
+    ///
+    /// let module = Module::new();
+    /// let mut eval = Evaluator::new(&module);
+    /// let res: &str = frozen
+    ///     .call_function(&mut eval, "greet", ("World",), (("punct", "!"),))
+    ///     .unwrap();
+    /// assert_eq!("Hello, World!", res);
+    /// ```
+    pub fn call_function<'v, 'n, R: UnpackValue<'v>>(
+        &self,
+        eval: &mut Evaluator<'v, '_>,
+        name: &str,
+        positional: impl AllocArgs<'v>,
+        named: impl AllocNamedArgs<'v, 'n>,
+    ) -> anyhow::Result<R> {
+        let function = self.get(name)?.owned_value(eval.frozen_heap());
+        eval.call_function(function, positional, named)
+    }
+
     /// Iterate through all the names defined in this module.
     pub fn names(&self) -> impl Iterator<Item = FrozenStringValue> + '_ {
         self.module.0.names()
@@ -254,6 +328,14 @@ impl FrozenModule {
         }
     }
 
+    pub(crate) fn retained_heap_profile(&self) -> anyhow::Result<&RetainedHeapProfile> {
+        self.module
+            .0
+            .heap_profile
+            .as_ref()
+            .ok_or_else(|| ModuleError::RetainedMemoryProfileNotEnabled.into())
+    }
+
     /// Retained memory info, or error if not enabled.
     pub fn aggregated_heap_profile_info(&self) -> anyhow::Result<&AggregateHeapProfileInfo> {
         match &self.module.0.heap_profile {
@@ -325,6 +407,7 @@ impl Module {
             names: MutableNames::new(),
             slots: MutableSlots::new(),
             docstring: RefCell::new(None),
+            binding_spans: RefCell::new(SmallMap::new()),
             eval_duration: Cell::new(Duration::ZERO),
             extra_value: Cell::new(None),
             heap_profile_on_freeze: Cell::new(None),
@@ -376,12 +459,18 @@ impl Module {
 
     /// Freeze the environment, all its value will become immutable afterwards.
     pub fn freeze(self) -> anyhow::Result<FrozenModule> {
+        self.freeze_with_options(&FreezeOptions::default())
+    }
+
+    /// Freeze the environment, with the given [`FreezeOptions`].
+    pub fn freeze_with_options(self, options: &FreezeOptions) -> anyhow::Result<FrozenModule> {
         let Module {
             names,
             slots,
             frozen_heap,
             heap,
             docstring,
+            binding_spans,
             eval_duration,
             extra_value: extra_v,
             heap_profile_on_freeze,
@@ -393,14 +482,34 @@ impl Module {
         // slot-index in the code, and we don't walk into them, so don't know if
         // they are used.
         let freezer = Freezer::new(frozen_heap);
-        let slots = slots.freeze(&freezer)?;
+        let mut retained = heap_profile_on_freeze.get().map(|_| Vec::new());
+        let slots = if options.drop_unreferenced_private {
+            let private: SmallSet<ModuleSlotId> = names.private_slots().into_iter().collect();
+            slots.freeze_reachable(&freezer, |slot| !private.contains(&slot), retained.as_mut())?
+        } else {
+            slots.freeze(&freezer, retained.as_mut())?
+        };
         let stacks = if let Some(mode) = heap_profile_on_freeze.get() {
             // TODO(nga): retained heap profile does not store information about data
             //   allocated in frozen heap before freeze starts.
             let heap_profile = AggregateHeapProfileInfo::collect(&heap, Some(HeapKind::Frozen));
+            let retained = retained.unwrap_or_default();
+            let mut by_binding = RetainedByBinding::default();
+            for (name, slot) in names.all_names() {
+                let bytes = retained.get(slot.0 as usize).copied().unwrap_or_default();
+                if bytes != 0 {
+                    *by_binding
+                        .bindings
+                        .entry(name.as_str().to_owned())
+                        .or_default() += bytes;
+                }
+            }
+            let attributed = by_binding.bindings.values().sum::<usize>();
+            by_binding.other = freezer.heap.used_bytes() - attributed;
             Some(RetainedHeapProfile {
                 info: heap_profile,
                 mode,
+                by_binding,
             })
         } else {
             None
@@ -427,6 +536,25 @@ impl Module {
                 .set(freezer.heap.unused_capacity());
         }
 
+        if !options.validators.is_empty() {
+            let binding_spans = binding_spans.into_inner();
+            let mut violations = Vec::new();
+            for (name, value) in rest.0.items() {
+                for validator in &options.validators {
+                    if let Err(e) = validator.validate(name.as_str(), value) {
+                        violations.push(FreezeViolation {
+                            name: name.as_str().to_owned(),
+                            span: binding_spans.get(name.as_str()).cloned(),
+                            message: format!("{:#}", e),
+                        });
+                    }
+                }
+            }
+            if !violations.is_empty() {
+                return Err(FreezeValidationError { violations }.into());
+            }
+        }
+
         Ok(FrozenModule {
             heap: freezer.into_ref(),
             module: rest,
@@ -493,6 +621,17 @@ impl Module {
         self.docstring.replace(Some(docstring));
     }
 
+    /// Remember where the top-level bindings were assigned.
+    /// Earlier locations win if a name is bound by several evaluated files.
+    pub(crate) fn add_binding_spans(&self, spans: Vec<(FileSpan, &str)>) {
+        let mut binding_spans = self.binding_spans.borrow_mut();
+        for (span, name) in spans {
+            if !binding_spans.contains_key(name) {
+                binding_spans.insert(name.to_owned(), span);
+            }
+        }
+    }
+
     pub(crate) fn add_eval_duration(&self, duration: Duration) {
         self.eval_duration.set(self.eval_duration.get() + duration);
     }
@@ -530,12 +669,81 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
+    use crate::environment::FreezeOptions;
+    use crate::environment::FreezeValidationError;
+    use crate::environment::FrozenModule;
     use crate::environment::Globals;
     use crate::environment::Module;
     use crate::eval::Evaluator;
     use crate::eval::ProfileMode;
     use crate::syntax::AstModule;
     use crate::syntax::Dialect;
+    use crate::values::FrozenValue;
+    use crate::values::RetainedMemoryByModule;
+
+    #[test]
+    fn test_freeze_validators() {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        let program = r#"
+good = "a/b"
+bad = "/abs"
+_private = "/private"
+def f(): pass
+also_bad = ["/x"]
+also_bad = "/y"
+"#;
+        let ast = AstModule::parse("x.star", program.to_owned(), &Dialect::Extended).unwrap();
+        eval.eval_module(ast, &Globals::standard()).unwrap();
+        module.set("injected", module.heap().alloc("/z"));
+
+        let no_abs_paths = |_name: &str, value: FrozenValue| -> anyhow::Result<()> {
+            match value.to_value().unpack_str() {
+                Some(s) if s.starts_with('/') => Err(anyhow::anyhow!("absolute path `{}`", s)),
+                _ => Ok(()),
+            }
+        };
+        let no_functions = |name: &str, value: FrozenValue| -> anyhow::Result<()> {
+            anyhow::ensure!(
+                value.to_value().get_type() != "function",
+                "`{}` is a function",
+                name
+            );
+            Ok(())
+        };
+        let err = module
+            .freeze_with_options(&FreezeOptions {
+                validators: vec![Arc::new(no_abs_paths), Arc::new(no_functions)],
+                ..FreezeOptions::default()
+            })
+            .unwrap_err();
+        let err = err.downcast_ref::<FreezeValidationError>().unwrap();
+        let violations = err
+            .violations
+            .iter()
+            .map(|v| {
+                let span = v.span.as_ref().map(|s| s.resolve_span().begin_line + 1);
+                (v.name.as_str(), span, v.message.as_str())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("bad", Some(3), "absolute path `/abs`"),
+                ("f", Some(5), "`f` is a function"),
+                ("also_bad", Some(6), "absolute path `/y`"),
+                ("injected", None, "absolute path `/z`"),
+            ],
+            violations
+        );
+        assert!(
+            err.to_string()
+                .contains("\n  x.star:3:1-4: `bad`: absolute path `/abs`"),
+            "{}",
+            err
+        );
+    }
 
     #[test]
     fn test_gen_heap_summary_profile() {
@@ -566,4 +774,99 @@ x = f(1)
         assert!(profile_info.unused_capacity.get() > 0);
         assert!(heap_summary.contains("\"x.star.f\""), "{:?}", heap_summary);
     }
+
+    #[test]
+    fn test_retained_memory_by_module() -> anyhow::Result<()> {
+        let freeze = |program: &str| -> anyhow::Result<FrozenModule> {
+            let module = Module::new();
+            let mut eval = Evaluator::new(&module);
+            eval.enable_profile(&ProfileMode::HeapSummaryRetained)?;
+            let ast = AstModule::parse("x.star", program.to_owned(), &Dialect::Extended)?;
+            eval.eval_module(ast, &Globals::standard())?;
+            module.freeze()
+        };
+        let a = freeze("small = [1]\nlarge = ['x' * 10000]\nalias = large")?;
+        let b = freeze("small = [2]")?;
+
+        let mut summary = RetainedMemoryByModule::new();
+        summary.add("b.star", &b)?;
+        summary.add("a.star", &a)?;
+        assert!(summary.add("c.star", &Module::new().freeze()?).is_err());
+
+        let rows = |csv: String| -> Vec<Vec<String>> {
+            csv.lines()
+                .skip(1)
+                .map(|x| {
+                    x.split(',')
+                        .map(|x| x.trim_matches('"').to_owned())
+                        .collect()
+                })
+                .collect()
+        };
+        let modules = rows(summary.gen_csv());
+        assert_eq!(
+            vec!["TOTAL", "a.star", "b.star"],
+            modules.iter().map(|x| x[0].as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!("large", modules[1][2]);
+        assert!(modules[1][3].parse::<usize>()? >= 10000);
+
+        let bindings = rows(summary.gen_bindings_csv());
+        assert_eq!(vec!["a.star", "large"], bindings[0][..2]);
+        // Values reachable from several bindings are attributed to the first one.
+        assert!(!bindings.iter().any(|x| x[1] == "alias"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_freeze_drop_unreferenced_private() {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.eval_module(
+            AstModule::parse(
+                "x.star",
+                r"
+_unused = list(range(1000))
+_used = [1, 2]
+_used_nested = [3]
+_referenced_by_value = [4]
+
+def f():
+    return _used
+
+def g():
+    return lambda: _used_nested
+
+x = [_referenced_by_value]
+"
+                .to_owned(),
+                &Dialect::Extended,
+            )
+            .unwrap(),
+            &Globals::standard(),
+        )
+        .unwrap();
+        let module = module
+            .freeze_with_options(&FreezeOptions {
+                drop_unreferenced_private: true,
+                ..FreezeOptions::default()
+            })
+            .unwrap();
+
+        assert!(module.get_any_visibility("_unused").is_err());
+        assert!(module.get_any_visibility("_referenced_by_value").is_err());
+        assert!(module.get_any_visibility("_used").is_ok());
+        assert!(module.get_any_visibility("_used_nested").is_ok());
+        assert!(module.get("x").is_ok());
+
+        let f = module.get("f").unwrap();
+        let g = module.get("g").unwrap();
+        let caller = Module::new();
+        let mut eval = Evaluator::new(&caller);
+        let res = eval.eval_function(f.value(), &[], &[]).unwrap();
+        assert_eq!("[1, 2]", res.to_repr());
+        let lambda = eval.eval_function(g.value(), &[], &[]).unwrap();
+        let res = eval.eval_function(lambda, &[], &[]).unwrap();
+        assert_eq!("[3]", res.to_repr());
+    }
 }
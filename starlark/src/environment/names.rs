@@ -22,6 +22,7 @@ use allocative::Allocative;
 use crate::collections::Hashed;
 use crate::collections::SmallMap;
 use crate::environment::slots::ModuleSlotId;
+use crate::environment::Module;
 use crate::syntax::ast::Visibility;
 use crate::values::FrozenStringValue;
 
@@ -120,6 +121,20 @@ impl MutableNames {
             .collect()
     }
 
+    /// Slots of names which are not exported:
+    /// `load`ed symbols, and names starting with underscore.
+    pub(crate) fn private_slots(&self) -> Vec<ModuleSlotId> {
+        self.0
+            .borrow()
+            .iter()
+            .filter(|(name, (_slot, vis))| {
+                *vis == Visibility::Private
+                    || Module::default_visibility(name.as_str()) == Visibility::Private
+            })
+            .map(|(_name, (slot, _vis))| *slot)
+            .collect()
+    }
+
     pub fn freeze(self) -> FrozenNames {
         FrozenNames(self.0.into_inner())
     }
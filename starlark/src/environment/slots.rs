@@ -26,7 +26,7 @@ use crate::values::Freezer;
 use crate::values::FrozenValue;
 use crate::values::Value;
 
-#[derive(Clone, Copy, Dupe, Debug, PartialEq, Eq, Allocative)]
+#[derive(Clone, Copy, Dupe, Debug, PartialEq, Eq, Hash, Allocative)]
 pub(crate) struct ModuleSlotId(pub(crate) u32);
 
 impl ModuleSlotId {
@@ -36,8 +36,9 @@ impl ModuleSlotId {
 }
 
 // Indexed slots of a module. May contain unassigned values as `None`.
+// The second field counts the assignments of each slot.
 #[derive(Debug)]
-pub(crate) struct MutableSlots<'v>(RefCell<Vec<Option<Value<'v>>>>);
+pub(crate) struct MutableSlots<'v>(RefCell<Vec<Option<Value<'v>>>>, RefCell<Vec<u32>>);
 
 // Indexed slots of a module. May contain unassigned values as `None`.
 #[derive(Debug, Allocative)]
@@ -45,7 +46,7 @@ pub(crate) struct FrozenSlots(Vec<Option<FrozenValue>>);
 
 impl<'v> MutableSlots<'v> {
     pub fn new() -> Self {
-        Self(RefCell::new(Vec::new()))
+        Self(RefCell::new(Vec::new()), RefCell::new(Vec::new()))
     }
 
     pub(crate) fn get_slots_mut(&self) -> RefMut<Vec<Option<Value<'v>>>> {
@@ -58,6 +59,12 @@ impl<'v> MutableSlots<'v> {
 
     pub fn set_slot(&self, slot: ModuleSlotId, value: Value<'v>) {
         self.0.borrow_mut()[slot.0 as usize] = Some(value);
+        self.1.borrow_mut()[slot.0 as usize] += 1;
+    }
+
+    /// How many times the slot was assigned.
+    pub(crate) fn assignments(&self, slot: ModuleSlotId) -> u32 {
+        self.1.borrow()[slot.0 as usize]
     }
 
     pub fn ensure_slot(&self, slot: ModuleSlotId) {
@@ -75,15 +82,100 @@ impl<'v> MutableSlots<'v> {
         for _ in 0..extra {
             slots.push(None);
         }
+        self.1.borrow_mut().resize(count as usize, 0);
     }
 
-    pub(crate) fn freeze(self, freezer: &Freezer) -> anyhow::Result<FrozenSlots> {
-        let slots = self
-            .0
-            .into_inner()
-            .try_map(|x| x.into_try_map(|x| x.freeze(freezer)))?;
+    /// Freeze the slots.
+    ///
+    /// When `retained` is given, it is filled with the number of bytes
+    /// allocated in the frozen heap when freezing each slot.
+    pub(crate) fn freeze(
+        self,
+        freezer: &Freezer,
+        mut retained: Option<&mut Vec<usize>>,
+    ) -> anyhow::Result<FrozenSlots> {
+        let slots = self.0.into_inner();
+        if let Some(retained) = &mut retained {
+            retained.resize(slots.len(), 0);
+        }
+        let slots = slots
+            .into_iter()
+            .enumerate()
+            .map(|(i, x)| {
+                x.into_try_map(|x| {
+                    freeze_slot(x, freezer, retained.as_deref_mut().map(|r| &mut r[i]))
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
         Ok(FrozenSlots(slots))
     }
+
+    /// Freeze the slots for which `is_root` returns `true`,
+    /// and the slots which may be read by functions reachable from them.
+    /// Other slots are dropped.
+    pub(crate) fn freeze_reachable(
+        self,
+        freezer: &Freezer,
+        is_root: impl Fn(ModuleSlotId) -> bool,
+        mut retained: Option<&mut Vec<usize>>,
+    ) -> anyhow::Result<FrozenSlots> {
+        let slots = self.0.into_inner();
+        if let Some(retained) = &mut retained {
+            retained.resize(slots.len(), 0);
+        }
+        let mut frozen = vec![None; slots.len()];
+        let mut reachable: Vec<bool> = (0..slots.len())
+            .map(|i| is_root(ModuleSlotId(i as u32)))
+            .collect();
+        let mut queue: Vec<usize> = (0..slots.len()).filter(|i| reachable[*i]).collect();
+
+        // Number of defs produced by `freezer` for which we have already visited the slots.
+        let mut defs_visited = 0;
+        loop {
+            while let Some(i) = queue.pop() {
+                if let Some(value) = slots[i] {
+                    let retained = retained.as_deref_mut().map(|r| &mut r[i]);
+                    frozen[i] = Some(freeze_slot(value, freezer, retained)?);
+                }
+            }
+
+            // Functions frozen in the loop above may read module slots
+            // which are not reachable through the value graph.
+            let frozen_defs = freezer.frozen_defs.borrow();
+            if defs_visited == frozen_defs.len() {
+                break;
+            }
+            for def in &frozen_defs[defs_visited..] {
+                for slot in def.module_slots_for_freeze() {
+                    let i = slot.0 as usize;
+                    if i < slots.len() && !reachable[i] {
+                        reachable[i] = true;
+                        queue.push(i);
+                    }
+                }
+            }
+            defs_visited = frozen_defs.len();
+        }
+
+        Ok(FrozenSlots(frozen))
+    }
+}
+
+/// Freeze the value of a slot, adding the bytes allocated in the frozen heap to `retained`.
+fn freeze_slot(
+    value: Value,
+    freezer: &Freezer,
+    retained: Option<&mut usize>,
+) -> anyhow::Result<FrozenValue> {
+    match retained {
+        None => value.freeze(freezer),
+        Some(retained) => {
+            let before = freezer.heap.used_bytes();
+            let value = value.freeze(freezer)?;
+            *retained += freezer.heap.used_bytes() - before;
+            Ok(value)
+        }
+    }
 }
 
 impl FrozenSlots {
@@ -0,0 +1,122 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Globals defined in Starlark rather than natively.
+
+use once_cell::sync::OnceCell;
+
+use crate::environment::FrozenModule;
+use crate::environment::Globals;
+use crate::environment::Module;
+use crate::eval::Evaluator;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+use crate::values::FrozenHeap;
+use crate::values::FrozenValue;
+
+/// Globals defined by Starlark source code, e.g. helper functions which do not need
+/// a native implementation. Added to a [`GlobalsBuilder`](crate::environment::GlobalsBuilder)
+/// with [`set_starlark_globals`](crate::environment::GlobalsBuilder::set_starlark_globals).
+///
+/// The public top level names of the source become globals. The source is evaluated
+/// with the extended globals when any of them is first used, and the frozen module
+/// is shared by all the globals including it:
+///
+/// ```
+/// use starlark::environment::GlobalsBuilder;
+/// use starlark::environment::StarlarkGlobalsStatic;
+///
+/// static HELPERS: StarlarkGlobalsStatic = StarlarkGlobalsStatic::new(
+///     "helpers.star",
+///     "def double(x):\n    return x * 2\n",
+/// );
+///
+/// let globals = GlobalsBuilder::standard()
+///     .with(|builder| builder.set_starlark_globals(&HELPERS))
+///     .build();
+/// assert!(globals.names().any(|x| x.as_str() == "double"));
+/// ```
+///
+/// The source is part of the program, so failing to parse or evaluate it is a panic.
+pub struct StarlarkGlobalsStatic {
+    filename: &'static str,
+    source: &'static str,
+    names: OnceCell<Vec<String>>,
+    module: OnceCell<FrozenModule>,
+}
+
+impl StarlarkGlobalsStatic {
+    /// Globals defined by `source`, with `filename` used in error messages and call stacks.
+    pub const fn new(filename: &'static str, source: &'static str) -> Self {
+        StarlarkGlobalsStatic {
+            filename,
+            source,
+            names: OnceCell::new(),
+            module: OnceCell::new(),
+        }
+    }
+
+    fn parse(&self) -> AstModule {
+        match AstModule::parse(self.filename, self.source.to_owned(), &Dialect::Extended) {
+            Ok(ast) => ast,
+            Err(e) => panic!(
+                "Failed to parse Starlark globals `{}`: {:#}",
+                self.filename, e
+            ),
+        }
+    }
+
+    /// Public top level names, without evaluating the source.
+    pub(crate) fn names(&self) -> &[String] {
+        self.names.get_or_init(|| {
+            let ast = self.parse();
+            let names = ast.exported_symbols();
+            names.into_iter().map(|(_, name)| name.to_owned()).collect()
+        })
+    }
+
+    fn module(&self) -> &FrozenModule {
+        self.module.get_or_init(|| {
+            let module = Module::new();
+            let mut eval = Evaluator::new(&module);
+            if let Err(e) = eval.eval_module(self.parse(), &Globals::extended()) {
+                panic!(
+                    "Failed to evaluate Starlark globals `{}`: {:#}",
+                    self.filename, e
+                );
+            }
+            drop(eval);
+            match module.freeze() {
+                Ok(module) => module,
+                Err(e) => panic!(
+                    "Failed to freeze Starlark globals `{}`: {:#}",
+                    self.filename, e
+                ),
+            }
+        })
+    }
+
+    /// Value of the global `name`, referenced from `heap`.
+    pub(crate) fn get(&self, name: &str, heap: &FrozenHeap) -> FrozenValue {
+        let value = match self.module().get(name) {
+            Ok(value) => value,
+            Err(e) => panic!("Starlark globals `{}`: {:#}", self.filename, e),
+        };
+        // SAFETY: the frozen module heap is kept alive by `heap`.
+        unsafe { value.owned_frozen_value(heap) }
+    }
+}
@@ -19,17 +19,21 @@
 
 use std::fmt::Write;
 
+use crate::eval::bc::addr::BcAddr;
 use crate::eval::bc::addr::BcPtrAddr;
 use crate::eval::bc::frame::BcFramePtr;
 use crate::eval::bc::instr::BcInstr;
 use crate::eval::bc::instr::InstrControl;
 use crate::eval::bc::instr_impl::InstrEnd;
+use crate::eval::bc::instr_impl::InstrGenForLoop;
+use crate::eval::bc::instr_impl::InstrWhileLoop;
+use crate::eval::bc::instr_impl::InstrYield;
 use crate::eval::bc::instrs::BcInstrs;
 use crate::eval::bc::opcode::BcOpcode;
 use crate::eval::bc::opcode::BcOpcodeHandler;
 use crate::eval::bc::slow_arg::BcInstrEndArg;
 use crate::eval::bc::slow_arg::BcInstrSlowArg;
-use crate::eval::compiler::add_span_to_expr_error;
+use crate::eval::compiler::add_span_to_raised_error;
 use crate::eval::compiler::EvalException;
 use crate::eval::Evaluator;
 use crate::values::Value;
@@ -77,10 +81,10 @@ impl Bc {
     pub(crate) fn wrap_error_for_instr_ptr(
         ptr: BcPtrAddr,
         e: anyhow::Error,
-        eval: &Evaluator,
+        eval: &mut Evaluator,
     ) -> EvalException {
         let span = Self::slow_arg_at_ptr(ptr).span;
-        add_span_to_expr_error(e, span, eval)
+        add_span_to_raised_error(e, span, eval)
     }
 
     /// Run the bytecode in the current frame allocated in the evaluator.
@@ -101,6 +105,63 @@ impl Bc {
             RunBlockResult::Err(e) => Err(e),
             RunBlockResult::Break => unreachable!("break outside of loop"),
             RunBlockResult::Continue => unreachable!("continue outside of loop"),
+            RunBlockResult::Yield(..) => unreachable!("yield outside of generator"),
+        }
+    }
+
+    /// Run the generator function body in the current frame,
+    /// from the start if `resume` is `None`, or after the `yield` instruction at `resume`.
+    ///
+    /// Return the yielded value and the address of the `yield` instruction,
+    /// or `None` if the body finished.
+    pub(crate) fn resume<'v>(
+        &self,
+        eval: &mut Evaluator<'v, '_>,
+        resume: Option<BcAddr>,
+    ) -> Result<Option<(Value<'v>, BcAddr)>, EvalException> {
+        let start = self.instrs.start_ptr();
+        let res = match resume {
+            None => run_block(eval, start),
+            Some(addr) => {
+                let ip = start.offset(addr);
+                let (_, loops) = &ip.get_instr::<InstrYield>().arg;
+                Self::resume_in_loops(eval, start, loops, ip)
+            }
+        };
+        match res {
+            RunBlockResult::Return(_) => Ok(None),
+            RunBlockResult::Yield(v, ip) => Ok(Some((v, ip.offset_from(start)))),
+            RunBlockResult::Err(e) => Err(e),
+            RunBlockResult::Break => unreachable!("break outside of loop"),
+            RunBlockResult::Continue => unreachable!("continue outside of loop"),
+        }
+    }
+
+    /// Run the rest of the generator body after the `yield` instruction at `ip`
+    /// enclosed in `loops`, outermost first.
+    ///
+    /// Rust stack of the loops was unwound on `yield`, so the loops are entered again
+    /// starting from the outermost, and each loop runs the rest of its body
+    /// (which resumes the inner loops) before continuing with the next iteration.
+    fn resume_in_loops<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        start: BcPtrAddr<'b>,
+        loops: &[BcAddr],
+        ip: BcPtrAddr<'b>,
+    ) -> RunBlockResult<'v, 'b> {
+        let (loop_addr, inner) = match loops.split_first() {
+            Some(x) => x,
+            None => return run_block(eval, ip.add_instr::<InstrYield>()),
+        };
+        let loop_ip = start.offset(*loop_addr);
+        match loop_ip.get_opcode() {
+            BcOpcode::GenForLoop => InstrGenForLoop::resume(eval, loop_ip, |eval| {
+                Self::resume_in_loops(eval, start, inner, ip)
+            }),
+            BcOpcode::WhileLoop => InstrWhileLoop::resume(eval, loop_ip, |eval| {
+                Self::resume_in_loops(eval, start, inner, ip)
+            }),
+            opcode => unreachable!("not a loop: {:?}", opcode),
         }
     }
 
@@ -145,7 +206,7 @@ fn step<'v, 'b>(
 }
 
 /// Result of instruction block evaluation.
-pub(crate) enum RunBlockResult<'v> {
+pub(crate) enum RunBlockResult<'v, 'b> {
     /// Go to the next loop iteration.
     Continue,
     /// Break off the loop.
@@ -154,11 +215,16 @@ pub(crate) enum RunBlockResult<'v> {
     Return(Value<'v>),
     /// Error.
     Err(EvalException),
+    /// Suspend the generator at `yield` instruction.
+    Yield(Value<'v>, BcPtrAddr<'b>),
 }
 
 /// Execute the code block, either a module, a function body or a loop body.
 // Do not inline this function because it is called from two places: function and loop.
-pub(crate) fn run_block<'v>(eval: &mut Evaluator<'v, '_>, mut ip: BcPtrAddr) -> RunBlockResult<'v> {
+pub(crate) fn run_block<'v, 'b>(
+    eval: &mut Evaluator<'v, '_>,
+    mut ip: BcPtrAddr<'b>,
+) -> RunBlockResult<'v, 'b> {
     // Copy frame pointer to local variable to generate more efficient code.
     let frame = eval.current_frame;
 
@@ -173,6 +239,7 @@ pub(crate) fn run_block<'v>(eval: &mut Evaluator<'v, '_>, mut ip: BcPtrAddr) ->
             InstrControl::Return(v) => return RunBlockResult::Return(v),
             InstrControl::LoopContinue => return RunBlockResult::Continue,
             InstrControl::LoopBreak => return RunBlockResult::Break,
+            InstrControl::Yield(v, ip) => return RunBlockResult::Yield(v, ip),
             InstrControl::Err(e) => {
                 return RunBlockResult::Err(Bc::wrap_error_for_instr_ptr(ip, e, eval));
             }
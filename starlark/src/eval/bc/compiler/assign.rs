@@ -20,9 +20,11 @@
 use gazebo::prelude::*;
 
 use crate::collections::symbol_map::Symbol;
+use crate::eval::bc::compiler::expr::write_expr_opt;
 use crate::eval::bc::compiler::expr::write_n_exprs;
 use crate::eval::bc::instr_impl::InstrSetArrayIndex;
 use crate::eval::bc::instr_impl::InstrSetObjectField;
+use crate::eval::bc::instr_impl::InstrSetSlice;
 use crate::eval::bc::instr_impl::InstrStoreModuleAndExport;
 use crate::eval::bc::instr_impl::InstrUnpack;
 use crate::eval::bc::stack_ptr::BcSlotIn;
@@ -44,6 +46,19 @@ impl AssignCompiledValue {
                 array.mark_definitely_assigned_after(bc);
                 index.mark_definitely_assigned_after(bc);
             }
+            AssignCompiledValue::Slice(v_start_stop_step) => {
+                let (v, start, stop, step) = &**v_start_stop_step;
+                v.mark_definitely_assigned_after(bc);
+                if let Some(start) = start {
+                    start.mark_definitely_assigned_after(bc);
+                }
+                if let Some(stop) = stop {
+                    stop.mark_definitely_assigned_after(bc);
+                }
+                if let Some(step) = step {
+                    step.mark_definitely_assigned_after(bc);
+                }
+            }
             AssignCompiledValue::LocalCaptured(_slot) => {}
             AssignCompiledValue::Local(slot) => {
                 bc.mark_definitely_assigned(*slot);
@@ -72,6 +87,18 @@ impl IrSpanned<AssignCompiledValue> {
                     bc.write_instr::<InstrSetArrayIndex>(span, (value, array, index));
                 });
             }
+            AssignCompiledValue::Slice(ref v_start_stop_step) => {
+                let (v, start, stop, step) = &**v_start_stop_step;
+                v.write_bc_cb(bc, |v, bc| {
+                    write_expr_opt(start, bc, |start, bc| {
+                        write_expr_opt(stop, bc, |stop, bc| {
+                            write_expr_opt(step, bc, |step, bc| {
+                                bc.write_instr::<InstrSetSlice>(span, (value, v, start, stop, step))
+                            })
+                        })
+                    })
+                });
+            }
             AssignCompiledValue::Tuple(ref xs) => {
                 // All assignments are to local variables, e. g.
                 // ```
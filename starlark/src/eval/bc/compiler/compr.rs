@@ -39,7 +39,7 @@ impl ClauseCompiled {
         rem: &[ClauseCompiled],
         term: impl FnOnce(&mut BcWriter),
     ) {
-        write_for(&self.over, &self.var, self.over.span, bc, |bc| {
+        write_for(&self.over, &self.var, self.over.span, false, bc, |bc| {
             for c in &self.ifs {
                 write_if_then(
                     c,
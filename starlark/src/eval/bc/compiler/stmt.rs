@@ -27,6 +27,7 @@ use crate::eval::bc::instr_impl::InstrReturn;
 use crate::eval::bc::instr_impl::InstrReturnCheckType;
 use crate::eval::bc::instr_impl::InstrReturnConst;
 use crate::eval::bc::stack_ptr::BcSlotIn;
+use crate::eval::bc::stack_ptr::BcSlotOut;
 use crate::eval::bc::writer::BcWriter;
 use crate::eval::compiler::expr::ExprCompiled;
 use crate::eval::compiler::expr::MaybeNot;
@@ -41,10 +42,28 @@ use crate::values::FrozenRef;
 use crate::values::FrozenStringValue;
 use crate::values::FrozenValue;
 
+fn write_for_instr(
+    over: BcSlotIn,
+    var: BcSlotOut,
+    span: FrameSpan,
+    generator: bool,
+    bc: &mut BcWriter,
+    body: impl FnOnce(&mut BcWriter),
+) {
+    if generator {
+        bc.write_gen_for(over, var, span, body)
+    } else {
+        bc.write_for(over, var, span, body)
+    }
+}
+
+/// Write a loop. Loops in generator functions need `GenForLoop` instruction
+/// to be resumable after `yield`.
 pub(crate) fn write_for(
     over: &IrSpanned<ExprCompiled>,
     var: &IrSpanned<AssignCompiledValue>,
     span: FrameSpan,
+    generator: bool,
     bc: &mut BcWriter,
     body: impl FnOnce(&mut BcWriter),
 ) {
@@ -54,7 +73,7 @@ pub(crate) fn write_for(
         if let Some(var) = var.as_local_non_captured() {
             // Typical case: `for x in ...: ...`,
             // compile loop assignment directly to a local variable.
-            bc.write_for(over, var.to_bc_slot().to_out(), span, |bc| {
+            write_for_instr(over, var.to_bc_slot().to_out(), span, generator, bc, |bc| {
                 bc.mark_definitely_assigned(var);
                 body(bc);
             })
@@ -63,7 +82,7 @@ pub(crate) fn write_for(
             // compile loop assignment to a temporary variable,
             // and reassign it in the loop body.
             bc.alloc_slot(|var_slot, bc| {
-                bc.write_for(over, var_slot.to_out(), span, |bc| {
+                write_for_instr(over, var_slot.to_out(), span, generator, bc, |bc| {
                     var.write_bc(var_slot.to_in(), bc);
                     var.mark_definitely_assigned_after(bc);
                     body(bc);
@@ -93,6 +112,7 @@ impl StmtCompiled {
                 // but no code is executed after `return`, so marking would be useless.
                 let _ = e;
             }
+            StmtCompiled::Yield(e) => e.mark_definitely_assigned_after(bc),
             StmtCompiled::Expr(e) => e.mark_definitely_assigned_after(bc),
             StmtCompiled::Assign(lhs, ty, rhs) => {
                 lhs.mark_definitely_assigned_after(bc);
@@ -121,6 +141,11 @@ impl StmtCompiled {
                 let (_var, over, _body) = &**var_over_body;
                 over.mark_definitely_assigned_after(bc);
             }
+            StmtCompiled::While(cond_body) => {
+                // Condition is evaluated at least once.
+                let (cond, _body) = &**cond_body;
+                cond.mark_definitely_assigned_after(bc);
+            }
             StmtCompiled::Break => {}
             StmtCompiled::Continue => {}
         }
@@ -217,6 +242,9 @@ impl IrSpanned<StmtCompiled> {
         match &self.node {
             StmtCompiled::PossibleGc => bc.write_instr::<InstrPossibleGc>(span, ()),
             StmtCompiled::Return(expr) => Self::write_return(span, expr, compiler, bc),
+            StmtCompiled::Yield(expr) => {
+                expr.write_bc_cb(bc, |slot, bc| bc.write_yield(span, slot));
+            }
             StmtCompiled::Expr(expr) => {
                 expr.write_bc_for_effect(bc);
             }
@@ -253,7 +281,21 @@ impl IrSpanned<StmtCompiled> {
             }
             StmtCompiled::For(assign_over_body) => {
                 let (assign, over, body) = &**assign_over_body;
-                write_for(over, assign, span, bc, |bc| body.write_bc(compiler, bc));
+                write_for(over, assign, span, compiler.is_generator, bc, |bc| {
+                    body.write_bc(compiler, bc)
+                });
+            }
+            StmtCompiled::While(cond_body) => {
+                let (cond, body) = &**cond_body;
+                bc.write_while(span, |bc| {
+                    write_if_then(
+                        cond,
+                        MaybeNot::Not,
+                        |bc| bc.write_instr::<InstrBreak>(span, ()),
+                        bc,
+                    );
+                    body.write_bc(compiler, bc);
+                });
             }
             StmtCompiled::Break => {
                 bc.write_instr::<InstrBreak>(span, ());
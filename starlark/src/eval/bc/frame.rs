@@ -136,6 +136,31 @@ impl<'v> BcFramePtr<'v> {
     pub(crate) fn locals(&self) -> &[Cell<Option<Value<'v>>>] {
         self.frame().locals()
     }
+
+    /// Copy local slots followed by empty stack slots,
+    /// so the copy can be later restored with [`restore_slots`](Self::restore_slots).
+    pub(crate) fn save_locals(self) -> Box<[Option<Value<'v>>]> {
+        let frame = self.frame();
+        let mut slots = Vec::with_capacity(frame.slot_count());
+        slots.extend(frame.locals().iter().map(Cell::get));
+        slots.resize(frame.slot_count(), None);
+        slots.into_boxed_slice()
+    }
+
+    /// Copy all the slots, locals and stack.
+    ///
+    /// Stack slots are not initialized when the frame is allocated,
+    /// so this can only be called after [`restore_slots`](Self::restore_slots).
+    pub(crate) fn save_slots(self) -> Box<[Option<Value<'v>>]> {
+        self.frame().slots().into()
+    }
+
+    /// Overwrite all the slots with slots previously saved from the frame of the same size.
+    pub(crate) fn restore_slots(mut self, slots: &[Option<Value<'v>>]) {
+        self.frame_mut().slots_uninit().copy_from_slice(unsafe {
+            slice::from_raw_parts(slots.as_ptr() as *const MaybeUninit<_>, slots.len())
+        });
+    }
 }
 
 impl<'v> BcFrame<'v> {
@@ -163,6 +188,19 @@ impl<'v> BcFrame<'v> {
         }
     }
 
+    fn slot_count(&self) -> usize {
+        (self.local_count + self.max_stack_size) as usize
+    }
+
+    /// All the slots, the caller must guarantee that the stack slots are initialized.
+    fn slots(&self) -> &[Option<Value<'v>>] {
+        unsafe { slice::from_raw_parts(self.slots.as_ptr(), self.slot_count()) }
+    }
+
+    fn slots_uninit(&mut self) -> &mut [MaybeUninit<Option<Value<'v>>>] {
+        unsafe { slice::from_raw_parts_mut(self.slots.as_mut_ptr() as *mut _, self.slot_count()) }
+    }
+
     #[inline(always)]
     fn locals_mut(&mut self) -> &mut [Option<Value<'v>>] {
         unsafe { slice::from_raw_parts_mut(self.slots.as_mut_ptr(), self.local_count as usize) }
@@ -0,0 +1,334 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Generator, the value returned by a call of a `def` containing `yield`.
+//!
+//! Generator does not use a separate thread or Rust stack: when suspended,
+//! it keeps a copy of the function frame slots and the address of the `yield` instruction.
+//! Loops enclosing `yield` are compiled to `GenForLoop` instruction,
+//! which stores the loop state in the frame slots too.
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::Display;
+use std::iter;
+use std::mem;
+use std::ptr;
+
+use allocative::Allocative;
+use gazebo::any::ProvidesStaticType;
+
+use crate as starlark;
+use crate::eval::bc::addr::BcAddr;
+use crate::eval::compiler::def::Def;
+use crate::eval::compiler::def::FrozenDef;
+use crate::eval::Evaluator;
+use crate::values::AllocValue;
+use crate::values::Freeze;
+use crate::values::Freezer;
+use crate::values::FrozenValue;
+use crate::values::Heap;
+use crate::values::StarlarkValue;
+use crate::values::Trace;
+use crate::values::Value;
+use crate::values::ValueLike;
+
+#[derive(Debug, thiserror::Error)]
+enum GeneratorError {
+    #[error("Generator is already running")]
+    AlreadyRunning,
+    #[error("Cannot freeze unfinished generator `{0}`")]
+    FreezeUnfinished(String),
+    #[error("Generator can only be iterated while the module which created it is evaluated")]
+    Iterate,
+}
+
+// Natives and operators like `in` iterate values without an evaluator,
+// but resuming a generator runs the function body. Passing the evaluator
+// to every `iterate` would change a lot of signatures, so the evaluator
+// running code on this thread is stored in a thread-local,
+// like the stack depth in `stack_guard`.
+thread_local! {
+    static CURRENT_EVALUATOR: Cell<*mut ()> = const { Cell::new(ptr::null_mut()) };
+}
+
+/// Restores the previous current evaluator on drop.
+#[must_use]
+pub(crate) struct CurrentEvaluatorGuard {
+    prev: *mut (),
+}
+
+impl Drop for CurrentEvaluatorGuard {
+    fn drop(&mut self) {
+        CURRENT_EVALUATOR.with(|current| current.set(self.prev));
+    }
+}
+
+/// Make `eval` the evaluator which resumes generators iterated without an evaluator,
+/// until the guard is dropped. `eval` must not be moved while the guard is alive.
+pub(crate) fn enter_evaluator(eval: &mut Evaluator) -> CurrentEvaluatorGuard {
+    let prev = CURRENT_EVALUATOR.with(|current| current.replace(eval as *mut Evaluator as *mut ()));
+    CurrentEvaluatorGuard { prev }
+}
+
+/// Result of running the generator function body until the next `yield`.
+pub(crate) enum GeneratorStep<'v> {
+    /// Yielded value, address of the `yield` instruction, and the frame slots.
+    Yield(Value<'v>, BcAddr, Box<[Option<Value<'v>>]>),
+    /// The function body finished.
+    Finished,
+}
+
+#[derive(Debug, Trace, Allocative)]
+enum GeneratorState<'v> {
+    /// Not running, can be resumed.
+    Suspended {
+        /// Frame slots of the generator function, locals followed by stack.
+        slots: Box<[Option<Value<'v>>]>,
+        /// Address of `yield` instruction to resume after, `None` if not yet started.
+        #[trace(unsafe_ignore)]
+        #[allocative(skip)]
+        resume: Option<BcAddr>,
+    },
+    /// Executing the function body.
+    Running,
+    /// The function body returned or failed.
+    Finished,
+}
+
+/// Value returned by a generator function.
+/// Items are produced lazily, when the generator is iterated by a `for` loop or a comprehension.
+#[derive(Debug, Trace, ProvidesStaticType, NoSerialize, Allocative)]
+pub(crate) struct Generator<'v> {
+    /// The generator function, `Def` or `FrozenDef`.
+    def: Value<'v>,
+    state: RefCell<GeneratorState<'v>>,
+    /// Heap of the module which created the generator.
+    #[trace(unsafe_ignore)]
+    #[allocative(skip)]
+    heap: &'v Heap,
+}
+
+/// Frozen generator. Only finished generators can be frozen, so it produces no items.
+#[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
+pub(crate) struct FrozenGenerator {
+    def: FrozenValue,
+}
+
+impl<'v> Generator<'v> {
+    /// Create a generator for the function `def` invoked in the current frame.
+    /// Parameters must be already stored in the frame.
+    pub(crate) fn alloc(def: Value<'v>, eval: &mut Evaluator<'v, '_>) -> Value<'v> {
+        eval.heap().alloc(Generator {
+            def,
+            heap: eval.heap(),
+            state: RefCell::new(GeneratorState::Suspended {
+                slots: eval.current_frame.save_locals(),
+                resume: None,
+            }),
+        })
+    }
+
+    /// Run the generator until the next `yield`.
+    /// Return `None` when the generator is finished.
+    pub(crate) fn next(&self, eval: &mut Evaluator<'v, '_>) -> anyhow::Result<Option<Value<'v>>> {
+        let state = mem::replace(&mut *self.state.borrow_mut(), GeneratorState::Running);
+        let (slots, resume) = match state {
+            GeneratorState::Suspended { slots, resume } => (slots, resume),
+            GeneratorState::Running => return Err(GeneratorError::AlreadyRunning.into()),
+            GeneratorState::Finished => {
+                *self.state.borrow_mut() = GeneratorState::Finished;
+                return Ok(None);
+            }
+        };
+
+        let step = if let Some(def) = self.def.downcast_ref::<Def>() {
+            def.resume_generator(self.def, &slots, resume, eval)
+        } else if let Some(def) = self.def.downcast_ref::<FrozenDef>() {
+            def.resume_generator(self.def, &slots, resume, eval)
+        } else {
+            unreachable!("generator function must be a def")
+        };
+
+        // Generator cannot be resumed after error.
+        let (state, res) = match step {
+            Ok(GeneratorStep::Yield(value, resume, slots)) => (
+                GeneratorState::Suspended {
+                    slots,
+                    resume: Some(resume),
+                },
+                Ok(Some(value)),
+            ),
+            Ok(GeneratorStep::Finished) => (GeneratorState::Finished, Ok(None)),
+            Err(e) => (GeneratorState::Finished, Err(e)),
+        };
+        *self.state.borrow_mut() = state;
+        res
+    }
+
+    /// Call `f` with the evaluator running the code on this thread,
+    /// see `enter_evaluator`.
+    fn with_current_evaluator<R>(
+        &self,
+        f: impl FnOnce(&mut Evaluator<'v, '_>) -> anyhow::Result<R>,
+    ) -> anyhow::Result<R> {
+        let eval = CURRENT_EVALUATOR.with(|current| current.get()) as *mut Evaluator<'v, '_>;
+        if eval.is_null() {
+            return Err(GeneratorError::Iterate.into());
+        }
+        // SAFETY: the evaluator is alive while the guard is, and the code which called
+        // `iterate` does not use the evaluator until it returns.
+        let eval = unsafe { &mut *eval };
+        // Values of a different heap would have a different lifetime.
+        if !ptr::eq(eval.heap(), self.heap) {
+            return Err(GeneratorError::Iterate.into());
+        }
+        f(eval)
+    }
+}
+
+/// If `value` is a generator, run it to the end and return a list of the yielded items,
+/// which natives can iterate without the evaluator. Other values are returned unchanged.
+pub(crate) fn collect_generator<'v>(
+    value: Value<'v>,
+    eval: &mut Evaluator<'v, '_>,
+) -> anyhow::Result<Value<'v>> {
+    match value.downcast_ref::<Generator>() {
+        Some(generator) => {
+            let mut items = Vec::new();
+            while let Some(item) = generator.next(eval)? {
+                items.push(item);
+            }
+            Ok(eval.heap().alloc_list(&items))
+        }
+        None => Ok(value),
+    }
+}
+
+/// Call `f` on the items of `value` until it returns `false`, resuming a generator
+/// only as far as needed. Return `false` if `f` stopped the iteration.
+pub(crate) fn iterate_while<'v>(
+    value: Value<'v>,
+    eval: &mut Evaluator<'v, '_>,
+    mut f: impl FnMut(Value<'v>) -> bool,
+) -> anyhow::Result<bool> {
+    match value.downcast_ref::<Generator>() {
+        Some(generator) => {
+            while let Some(item) = generator.next(eval)? {
+                if !f(item) {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        None => value.with_iterator(eval.heap(), |it| {
+            for item in it {
+                if !f(item) {
+                    return false;
+                }
+            }
+            true
+        }),
+    }
+}
+
+impl<'v> Display for Generator<'v> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<generator {}>", self.def.name_for_call_stack())
+    }
+}
+
+impl Display for FrozenGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "<generator {}>",
+            self.def.to_value().name_for_call_stack()
+        )
+    }
+}
+
+impl<'v> AllocValue<'v> for Generator<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex(self)
+    }
+}
+
+impl<'v> Freeze for Generator<'v> {
+    type Frozen = FrozenGenerator;
+
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<FrozenGenerator> {
+        // The def may be already frozen, so it must be accessed only through the freezer.
+        let def = self.def.freeze(freezer)?;
+        match self.state.into_inner() {
+            GeneratorState::Finished => Ok(FrozenGenerator { def }),
+            GeneratorState::Suspended { .. } | GeneratorState::Running => {
+                Err(GeneratorError::FreezeUnfinished(def.to_value().name_for_call_stack()).into())
+            }
+        }
+    }
+}
+
+impl<'v> StarlarkValue<'v> for Generator<'v> {
+    starlark_type!("generator");
+
+    fn iterate<'a>(
+        &'a self,
+        _heap: &'v Heap,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = Value<'v>> + 'a>>
+    where
+        'v: 'a,
+    {
+        // Run the generator to the end before returning, because the iterator
+        // cannot report errors, and the caller may use the evaluator while iterating.
+        let items = self.with_current_evaluator(|eval| {
+            let mut items = Vec::new();
+            while let Some(item) = self.next(eval)? {
+                items.push(item);
+            }
+            Ok(items)
+        })?;
+        Ok(Box::new(items.into_iter()))
+    }
+
+    fn is_in(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        // Like Python, stop at the first equal item.
+        self.with_current_evaluator(|eval| {
+            while let Some(item) = self.next(eval)? {
+                if item.equals(other)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        })
+    }
+}
+
+impl<'v> StarlarkValue<'v> for FrozenGenerator {
+    starlark_type!("generator");
+
+    fn iterate<'a>(
+        &'a self,
+        _heap: &'v Heap,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = Value<'v>> + 'a>>
+    where
+        'v: 'a,
+    {
+        Ok(Box::new(iter::empty()))
+    }
+}
@@ -38,6 +38,8 @@ pub(crate) enum InstrControl<'v, 'b> {
     LoopContinue,
     /// Break from the loop.
     LoopBreak,
+    /// Suspend the generator at `yield` instruction at given address.
+    Yield(Value<'v>, BcPtrAddr<'b>),
     /// Error.
     Err(anyhow::Error),
 }
@@ -22,6 +22,7 @@ use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Write;
 
+use dupe::Dupe;
 use itertools::Itertools;
 
 use crate::collections::symbol_map::Symbol;
@@ -82,6 +83,20 @@ pub(crate) trait BcInstrArg: 'static {
     ) -> fmt::Result;
     /// Collect instruction jump addresses.
     fn visit_jump_addr(param: &Self, consumer: &mut dyn FnMut(BcAddrOffset));
+    /// Collect frame slots referenced by the argument, used by the bytecode verifier.
+    /// Arguments which do not reference slots can keep the default implementation.
+    fn visit_slots(_param: &Self, _consumer: &mut dyn FnMut(BcSlotAccess)) {}
+}
+
+/// Reference to a frame slot by an instruction argument.
+#[derive(Copy, Clone, Dupe, Debug)]
+pub(crate) enum BcSlotAccess {
+    /// Slot is read, like `BcSlotIn`.
+    Read(BcSlot),
+    /// Slot is written, like `BcSlotOut`.
+    Write(BcSlot),
+    /// Local variable is read or written, it may be not assigned at this point.
+    Local(BcSlot),
 }
 
 impl BcInstrArg for () {
@@ -139,6 +154,11 @@ impl<A: BcInstrArg, B: BcInstrArg> BcInstrArg for (A, B) {
         BcInstrArg::visit_jump_addr(a, consumer);
         BcInstrArg::visit_jump_addr(b, consumer);
     }
+
+    fn visit_slots((a, b): &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        BcInstrArg::visit_slots(a, consumer);
+        BcInstrArg::visit_slots(b, consumer);
+    }
 }
 
 impl<A: BcInstrArg, B: BcInstrArg, C: BcInstrArg> BcInstrArg for (A, B, C) {
@@ -159,6 +179,12 @@ impl<A: BcInstrArg, B: BcInstrArg, C: BcInstrArg> BcInstrArg for (A, B, C) {
         BcInstrArg::visit_jump_addr(b, consumer);
         BcInstrArg::visit_jump_addr(c, consumer);
     }
+
+    fn visit_slots((a, b, c): &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        BcInstrArg::visit_slots(a, consumer);
+        BcInstrArg::visit_slots(b, consumer);
+        BcInstrArg::visit_slots(c, consumer);
+    }
 }
 
 #[allow(clippy::many_single_char_names)]
@@ -182,6 +208,13 @@ impl<A: BcInstrArg, B: BcInstrArg, C: BcInstrArg, D: BcInstrArg> BcInstrArg for
         BcInstrArg::visit_jump_addr(c, consumer);
         BcInstrArg::visit_jump_addr(d, consumer);
     }
+
+    fn visit_slots((a, b, c, d): &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        BcInstrArg::visit_slots(a, consumer);
+        BcInstrArg::visit_slots(b, consumer);
+        BcInstrArg::visit_slots(c, consumer);
+        BcInstrArg::visit_slots(d, consumer);
+    }
 }
 
 #[allow(clippy::many_single_char_names)]
@@ -209,6 +242,14 @@ impl<A: BcInstrArg, B: BcInstrArg, C: BcInstrArg, D: BcInstrArg, E: BcInstrArg>
         BcInstrArg::visit_jump_addr(d, consumer);
         BcInstrArg::visit_jump_addr(e, consumer);
     }
+
+    fn visit_slots((a, b, c, d, e): &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        BcInstrArg::visit_slots(a, consumer);
+        BcInstrArg::visit_slots(b, consumer);
+        BcInstrArg::visit_slots(c, consumer);
+        BcInstrArg::visit_slots(d, consumer);
+        BcInstrArg::visit_slots(e, consumer);
+    }
 }
 
 #[allow(clippy::many_single_char_names)]
@@ -238,6 +279,15 @@ impl<A: BcInstrArg, B: BcInstrArg, C: BcInstrArg, D: BcInstrArg, E: BcInstrArg,
         BcInstrArg::visit_jump_addr(e, consumer);
         BcInstrArg::visit_jump_addr(f, consumer);
     }
+
+    fn visit_slots((a, b, c, d, e, f): &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        BcInstrArg::visit_slots(a, consumer);
+        BcInstrArg::visit_slots(b, consumer);
+        BcInstrArg::visit_slots(c, consumer);
+        BcInstrArg::visit_slots(d, consumer);
+        BcInstrArg::visit_slots(e, consumer);
+        BcInstrArg::visit_slots(f, consumer);
+    }
 }
 
 impl<A: BcInstrArg, const N: usize> BcInstrArg for [A; N] {
@@ -258,6 +308,12 @@ impl<A: BcInstrArg, const N: usize> BcInstrArg for [A; N] {
             BcInstrArg::visit_jump_addr(a, consumer);
         }
     }
+
+    fn visit_slots(param: &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        for a in param {
+            BcInstrArg::visit_slots(a, consumer);
+        }
+    }
 }
 
 impl BcInstrArg for BcAddrOffset {
@@ -332,6 +388,12 @@ impl<T: BcInstrArg> BcInstrArg for Option<T> {
             T::visit_jump_addr(param, consumer);
         }
     }
+
+    fn visit_slots(param: &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        if let Some(param) = param {
+            T::visit_slots(param, consumer);
+        }
+    }
 }
 
 impl BcInstrArg for String {
@@ -363,7 +425,7 @@ where
     fn visit_jump_addr(_param: &Self, _consumer: &mut dyn FnMut(BcAddrOffset)) {}
 }
 
-impl<T: Display> BcInstrArg for FrozenRef<'static, [T]>
+impl<T: Display + BcInstrArg> BcInstrArg for FrozenRef<'static, [T]>
 where
     FrozenRef<'static, T>: Copy,
 {
@@ -381,6 +443,12 @@ where
     }
 
     fn visit_jump_addr(_param: &Self, _consumer: &mut dyn FnMut(BcAddrOffset)) {}
+
+    fn visit_slots(param: &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        for v in param.iter() {
+            T::visit_slots(v, consumer);
+        }
+    }
 }
 
 impl<T: StarlarkValue<'static>> BcInstrArg for FrozenValueTyped<'static, T> {
@@ -434,6 +502,10 @@ impl BcInstrArg for LocalSlotId {
     }
 
     fn visit_jump_addr(_param: &Self, _consumer: &mut dyn FnMut(BcAddrOffset)) {}
+
+    fn visit_slots(param: &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        consumer(BcSlotAccess::Local(param.to_bc_slot()));
+    }
 }
 
 impl BcInstrArg for LocalCapturedSlotId {
@@ -447,6 +519,10 @@ impl BcInstrArg for LocalCapturedSlotId {
     }
 
     fn visit_jump_addr(_param: &Self, _consumer: &mut dyn FnMut(BcAddrOffset)) {}
+
+    fn visit_slots(param: &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        consumer(BcSlotAccess::Local(param.to_bc_slot()));
+    }
 }
 
 impl BcInstrArg for BcSlotIn {
@@ -460,6 +536,10 @@ impl BcInstrArg for BcSlotIn {
     }
 
     fn visit_jump_addr(_param: &Self, _consumer: &mut dyn FnMut(BcAddrOffset)) {}
+
+    fn visit_slots(param: &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        consumer(BcSlotAccess::Read(param.get()));
+    }
 }
 
 impl BcInstrArg for BcSlotOut {
@@ -473,6 +553,10 @@ impl BcInstrArg for BcSlotOut {
     }
 
     fn visit_jump_addr(_param: &Self, _consumer: &mut dyn FnMut(BcAddrOffset)) {}
+
+    fn visit_slots(param: &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        consumer(BcSlotAccess::Write(param.get()));
+    }
 }
 
 impl BcInstrArg for BcSlotInRange {
@@ -493,6 +577,12 @@ impl BcInstrArg for BcSlotInRange {
     }
 
     fn visit_jump_addr(_param: &Self, _consumer: &mut dyn FnMut(BcAddrOffset)) {}
+
+    fn visit_slots(param: &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        for slot in param.iter() {
+            consumer(BcSlotAccess::Read(slot.get()));
+        }
+    }
 }
 
 impl BcInstrArg for BcSlotInRangeFrom {
@@ -674,6 +764,12 @@ impl<S: ArgSymbol> BcInstrArg for BcCallArgsFull<S> {
     }
 
     fn visit_jump_addr(_param: &Self, _consumer: &mut dyn FnMut(BcAddrOffset)) {}
+
+    fn visit_slots(param: &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        BcInstrArg::visit_slots(&param.pos_named, consumer);
+        BcInstrArg::visit_slots(&param.args, consumer);
+        BcInstrArg::visit_slots(&param.kwargs, consumer);
+    }
 }
 
 impl BcInstrArg for BcCallArgsPos {
@@ -687,6 +783,10 @@ impl BcInstrArg for BcCallArgsPos {
     }
 
     fn visit_jump_addr(_param: &Self, _consumer: &mut dyn FnMut(BcAddrOffset)) {}
+
+    fn visit_slots(param: &Self, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        BcInstrArg::visit_slots(&param.pos, consumer);
+    }
 }
 
 impl BcInstrArg for BcInstrEndArg {
@@ -755,4 +855,21 @@ impl BcOpcode {
 
         self.dispatch(HandlerImpl { ptr, consumer });
     }
+
+    pub(crate) fn visit_slots(self, ptr: BcPtrAddr, consumer: &mut dyn FnMut(BcSlotAccess)) {
+        struct HandlerImpl<'b, 'c> {
+            ptr: BcPtrAddr<'b>,
+            consumer: &'c mut dyn FnMut(BcSlotAccess),
+        }
+
+        impl BcOpcodeHandler<()> for HandlerImpl<'_, '_> {
+            fn handle<I: BcInstr>(self) {
+                let HandlerImpl { ptr, consumer } = self;
+                let instr = ptr.get_instr::<I>();
+                I::Arg::visit_slots(&instr.arg, consumer);
+            }
+        }
+
+        self.dispatch(HandlerImpl { ptr, consumer });
+    }
 }
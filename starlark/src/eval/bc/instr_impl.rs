@@ -24,11 +24,13 @@ use std::time::Instant;
 
 use gazebo::coerce::coerce;
 
+use crate::codemap::FileSpan;
 use crate::collections::symbol_map::Symbol;
 use crate::collections::Hashed;
 use crate::collections::SmallMap;
 use crate::const_frozen_string;
 use crate::environment::slots::ModuleSlotId;
+use crate::eval::bc::addr::BcAddr;
 use crate::eval::bc::addr::BcAddrOffset;
 use crate::eval::bc::addr::BcPtrAddr;
 use crate::eval::bc::bytecode::run_block;
@@ -39,6 +41,7 @@ use crate::eval::bc::call::BcCallArgsForDef;
 use crate::eval::bc::call::BcCallArgsFull;
 use crate::eval::bc::call::BcCallArgsPos;
 use crate::eval::bc::frame::BcFramePtr;
+use crate::eval::bc::generator::Generator;
 use crate::eval::bc::instr::BcInstr;
 use crate::eval::bc::instr::InstrControl;
 use crate::eval::bc::instr_arg::BcInstrArg;
@@ -49,7 +52,7 @@ use crate::eval::bc::stack_ptr::BcSlotIn;
 use crate::eval::bc::stack_ptr::BcSlotInRange;
 use crate::eval::bc::stack_ptr::BcSlotInRangeFrom;
 use crate::eval::bc::stack_ptr::BcSlotOut;
-use crate::eval::compiler::add_span_to_expr_error;
+use crate::eval::compiler::add_span_to_raised_error;
 use crate::eval::compiler::def::Def;
 use crate::eval::compiler::def::FrozenDef;
 use crate::eval::compiler::def::ParameterCompiled;
@@ -68,7 +71,9 @@ use crate::eval::compiler::stmt::possible_gc;
 use crate::eval::compiler::stmt::AssignError;
 use crate::eval::compiler::EvalException;
 use crate::eval::runtime::arguments::ResolvedArgName;
+use crate::eval::runtime::explain::BindingEventKind;
 use crate::eval::runtime::frame_span::FrameSpan;
+use crate::eval::runtime::provenance::ProvenanceOp;
 use crate::eval::runtime::slots::LocalCapturedSlotId;
 use crate::eval::runtime::slots::LocalSlotId;
 use crate::eval::Arguments;
@@ -80,8 +85,10 @@ use crate::values::int::PointerI32;
 use crate::values::layout::value_not_special::FrozenValueNotSpecial;
 use crate::values::string::interpolation::format_one;
 use crate::values::string::interpolation::percent_s_one;
+use crate::values::tuple::TupleRef;
 use crate::values::types::known_methods::KnownMethod;
 use crate::values::types::list::value::ListData;
+use crate::values::types::range::Range;
 use crate::values::typing::TypeCompiled;
 use crate::values::FrozenRef;
 use crate::values::FrozenStringValue;
@@ -92,6 +99,7 @@ use crate::values::StarlarkValue;
 use crate::values::StringValue;
 use crate::values::StringValueLike;
 use crate::values::Value;
+use crate::values::ValueLike;
 
 /// Instructions which either fail or proceed to the following instruction,
 /// and it returns error with span.
@@ -158,6 +166,7 @@ pub(crate) struct InstrObjectFieldImpl;
 pub(crate) struct InstrObjectFieldRawImpl;
 pub(crate) struct InstrSetObjectFieldImpl;
 pub(crate) struct InstrSliceImpl;
+pub(crate) struct InstrSetSliceImpl;
 
 pub(crate) type InstrLoadLocal = InstrNoFlow<InstrLoadLocalImpl>;
 pub(crate) type InstrLoadLocalCaptured = InstrNoFlow<InstrLoadLocalCapturedImpl>;
@@ -174,6 +183,7 @@ pub(crate) type InstrObjectField = InstrNoFlow<InstrObjectFieldImpl>;
 pub(crate) type InstrObjectFieldRaw = InstrNoFlow<InstrObjectFieldRawImpl>;
 pub(crate) type InstrSetObjectField = InstrNoFlow<InstrSetObjectFieldImpl>;
 pub(crate) type InstrSlice = InstrNoFlow<InstrSliceImpl>;
+pub(crate) type InstrSetSlice = InstrNoFlow<InstrSetSliceImpl>;
 
 impl InstrNoFlowImpl for InstrLoadLocalImpl {
     type Arg = (LocalSlotId, BcSlotOut);
@@ -254,18 +264,25 @@ impl InstrNoFlowImpl for InstrStoreLocalCapturedImpl {
     }
 }
 
+/// Location of the instruction at `ip`, used in explain mode.
+#[cold]
+fn instr_span(ip: BcPtrAddr) -> FileSpan {
+    Bc::slow_arg_at_ptr(ip).span.span.to_original_file_span()
+}
+
 impl InstrNoFlowImpl for InstrStoreModuleAndExportImpl {
     type Arg = (BcSlotIn, ModuleSlotId, String);
 
     fn run_with_args<'v>(
         eval: &mut Evaluator<'v, '_>,
         frame: BcFramePtr<'v>,
-        _ip: BcPtrAddr,
+        ip: BcPtrAddr,
         (source, slot, name): &(BcSlotIn, ModuleSlotId, String),
     ) -> anyhow::Result<()> {
         let v = frame.get_bc_slot(*source);
         v.export_as(name.as_str(), eval);
         eval.set_slot_module(*slot, v);
+        eval.explain_assign(*slot, v, || instr_span(ip));
         Ok(())
     }
 }
@@ -276,11 +293,12 @@ impl InstrNoFlowImpl for InstrStoreModuleImpl {
     fn run_with_args<'v>(
         eval: &mut Evaluator<'v, '_>,
         frame: BcFramePtr<'v>,
-        _ip: BcPtrAddr,
+        ip: BcPtrAddr,
         (source, target): &(BcSlotIn, ModuleSlotId),
     ) -> anyhow::Result<()> {
         let v = frame.get_bc_slot(*source);
         eval.set_slot_module(*target, v);
+        eval.explain_assign(*target, v, || instr_span(ip));
         Ok(())
     }
 }
@@ -330,6 +348,7 @@ impl InstrNoFlowImpl for InstrArrayIndexImpl {
         let array = frame.get_bc_slot(*array);
         let index = frame.get_bc_slot(*index);
         let value = array.at(index, eval.heap())?;
+        eval.propagate_provenance(ProvenanceOp::Indexing, &[array], value);
         frame.set_bc_slot(*target, value);
         Ok(())
     }
@@ -340,15 +359,21 @@ impl InstrNoFlowImpl for InstrSetArrayIndexImpl {
 
     #[inline(always)]
     fn run_with_args<'v>(
-        _eval: &mut Evaluator<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
         frame: BcFramePtr<'v>,
-        _ip: BcPtrAddr,
+        ip: BcPtrAddr,
         (source, array, index): &(BcSlotIn, BcSlotIn, BcSlotIn),
     ) -> anyhow::Result<()> {
         let value = frame.get_bc_slot(*source);
         let array = frame.get_bc_slot(*array);
         let index = frame.get_bc_slot(*index);
-        array.set_at(index, value)
+        array.set_at(index, value)?;
+        eval.explain_mutation(
+            array,
+            || BindingEventKind::SetIndex,
+            |_| Some(instr_span(ip)),
+        );
+        Ok(())
     }
 }
 
@@ -357,15 +382,21 @@ impl InstrNoFlowImpl for InstrArrayIndexSetImpl {
 
     #[inline(always)]
     fn run_with_args<'v>(
-        _eval: &mut Evaluator<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
         frame: BcFramePtr<'v>,
-        _ip: BcPtrAddr,
+        ip: BcPtrAddr,
         (array, index, source): &(BcSlotIn, BcSlotIn, BcSlotIn),
     ) -> anyhow::Result<()> {
         let value = frame.get_bc_slot(*source);
         let array = frame.get_bc_slot(*array);
         let index = frame.get_bc_slot(*index);
-        array.set_at(index, value)
+        array.set_at(index, value)?;
+        eval.explain_mutation(
+            array,
+            || BindingEventKind::SetIndex,
+            |_| Some(instr_span(ip)),
+        );
+        Ok(())
     }
 }
 
@@ -381,6 +412,7 @@ impl InstrNoFlowImpl for InstrObjectFieldImpl {
     ) -> anyhow::Result<()> {
         let object = frame.get_bc_slot(*object);
         let value = get_attr_hashed_bind(object, field, eval.heap())?;
+        eval.propagate_provenance(ProvenanceOp::Indexing, &[object], value);
         frame.set_bc_slot(*target, value);
         Ok(())
     }
@@ -449,11 +481,43 @@ impl InstrNoFlowImpl for InstrSliceImpl {
         let stop = stop.map(|s| frame.get_bc_slot(s));
         let step = step.map(|s| frame.get_bc_slot(s));
         let value = list.slice(start, stop, step, eval.heap())?;
+        eval.propagate_provenance(ProvenanceOp::Indexing, &[list], value);
         frame.set_bc_slot(*target, value);
         Ok(())
     }
 }
 
+impl InstrNoFlowImpl for InstrSetSliceImpl {
+    type Arg = (
+        BcSlotIn,
+        BcSlotIn,
+        Option<BcSlotIn>,
+        Option<BcSlotIn>,
+        Option<BcSlotIn>,
+    );
+
+    #[inline(always)]
+    fn run_with_args<'v>(
+        eval: &mut Evaluator<'v, '_>,
+        frame: BcFramePtr<'v>,
+        _ip: BcPtrAddr,
+        (source, list, start, stop, step): &(
+            BcSlotIn,
+            BcSlotIn,
+            Option<BcSlotIn>,
+            Option<BcSlotIn>,
+            Option<BcSlotIn>,
+        ),
+    ) -> anyhow::Result<()> {
+        let value = frame.get_bc_slot(*source);
+        let list = frame.get_bc_slot(*list);
+        let start = start.map(|s| frame.get_bc_slot(s));
+        let stop = stop.map(|s| frame.get_bc_slot(s));
+        let step = step.map(|s| frame.get_bc_slot(s));
+        list.set_slice(start, stop, step, value, eval.heap())
+    }
+}
+
 pub(crate) struct InstrEqImpl;
 pub(crate) struct InstrEqConstImpl;
 pub(crate) struct InstrEqPtrImpl;
@@ -613,6 +677,7 @@ impl<I: InstrBinOpImpl> InstrNoFlowImpl for InstrBinOpWrapper<I> {
         let v0 = frame.get_bc_slot(*v0);
         let v1 = frame.get_bc_slot(*v1);
         let v = I::eval(v0, v1, eval.heap())?;
+        eval.propagate_provenance(ProvenanceOp::Operator, &[v0, v1], v);
         frame.set_bc_slot(*target, v);
         Ok(())
     }
@@ -630,6 +695,7 @@ impl<I: InstrUnOpImpl> InstrNoFlowImpl for InstrUnOpWrapper<I> {
     ) -> anyhow::Result<()> {
         let source = frame.get_bc_slot(*source);
         let value = I::eval(source, eval.heap())?;
+        eval.propagate_provenance(ProvenanceOp::Operator, &[source], value);
         frame.set_bc_slot(*target, value);
         Ok(())
     }
@@ -780,6 +846,7 @@ impl InstrNoFlowImpl for InstrPercentSOneImpl {
     ) -> anyhow::Result<()> {
         let arg = frame.get_bc_slot(*arg);
         let r = percent_s_one(before.as_str(), arg, after.as_str(), eval.heap())?;
+        eval.propagate_provenance(ProvenanceOp::Operator, &[arg], r.to_value());
         frame.set_bc_slot(*target, r.to_value());
         Ok(())
     }
@@ -797,6 +864,7 @@ impl InstrNoFlowImpl for InstrFormatOneImpl {
     ) -> anyhow::Result<()> {
         let arg = frame.get_bc_slot(*arg);
         let r = format_one(before.as_str(), arg, after.as_str(), eval.heap());
+        eval.propagate_provenance(ProvenanceOp::Operator, &[arg], r.to_value());
         frame.set_bc_slot(*target, r.to_value());
         Ok(())
     }
@@ -996,14 +1064,14 @@ impl InstrNoFlowImpl for InstrDictNPopImpl {
                 Ok(k) => k,
                 Err(e) => {
                     let spans = &Bc::slow_arg_at_ptr(ip).spans;
-                    return Err(add_span_to_expr_error(e, spans[i], eval).0);
+                    return Err(add_span_to_raised_error(e, spans[i], eval).0);
                 }
             };
             let prev = dict.insert_hashed(k, v);
             if prev.is_some() {
                 let e = EvalError::DuplicateDictionaryKey(k.key().to_string()).into();
                 let spans = &Bc::slow_arg_at_ptr(ip).spans;
-                return Err(add_span_to_expr_error(e, spans[i], eval).0);
+                return Err(add_span_to_raised_error(e, spans[i], eval).0);
             }
         }
         let dict = eval.heap().alloc(Dict::new(dict));
@@ -1197,8 +1265,40 @@ impl BcInstr for InstrIfNotBr {
 }
 
 pub(crate) struct InstrForLoop;
+pub(crate) struct InstrGenForLoop;
+pub(crate) struct InstrWhileLoop;
 pub(crate) struct InstrBreak;
 pub(crate) struct InstrContinue;
+pub(crate) struct InstrYield;
+
+impl InstrForLoop {
+    /// Loop over a generator, which needs the evaluator to produce the items.
+    fn run_generator<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        frame: BcFramePtr<'v>,
+        ip: BcPtrAddr<'b>,
+        generator: &Generator<'v>,
+        var: BcSlotOut,
+        loop_end: BcAddrOffset,
+    ) -> InstrControl<'v, 'b> {
+        let loop_start = ip.add_instr::<Self>();
+        loop {
+            match generator.next(eval) {
+                Ok(Some(item)) => frame.set_bc_slot(var, item),
+                Ok(None) => break,
+                Err(e) => return InstrControl::Err(e),
+            }
+            match run_block(eval, loop_start) {
+                RunBlockResult::Continue => {}
+                RunBlockResult::Break => break,
+                RunBlockResult::Return(v) => return InstrControl::Return(v),
+                RunBlockResult::Err(e) => return InstrControl::Err(e.0),
+                RunBlockResult::Yield(..) => unreachable!("yield in regular loop"),
+            }
+        }
+        InstrControl::Next(ip.add_rel(loop_end))
+    }
+}
 
 impl BcInstr for InstrForLoop {
     type Arg = (BcSlotIn, BcSlotOut, BcAddrOffset);
@@ -1210,6 +1310,9 @@ impl BcInstr for InstrForLoop {
         (over, var, loop_end): &(BcSlotIn, BcSlotOut, BcAddrOffset),
     ) -> InstrControl<'v, 'b> {
         let collection = frame.get_bc_slot(*over);
+        if let Some(generator) = collection.downcast_ref::<Generator>() {
+            return Self::run_generator(eval, frame, ip, generator, *var, *loop_end);
+        }
 
         enum LoopResult<'v> {
             Ok,
@@ -1226,6 +1329,7 @@ impl BcInstr for InstrForLoop {
                     RunBlockResult::Break => return LoopResult::Ok,
                     RunBlockResult::Return(v) => return LoopResult::Return(v),
                     RunBlockResult::Err(e) => return LoopResult::Err(e),
+                    RunBlockResult::Yield(..) => unreachable!("yield in regular loop"),
                 }
             }
             LoopResult::Ok
@@ -1239,6 +1343,241 @@ impl BcInstr for InstrForLoop {
     }
 }
 
+impl InstrGenForLoop {
+    /// Collections which are iterated by index or lazily rather than with `with_iterator`.
+    fn iterated_by_index(collection: Value) -> bool {
+        collection.downcast_ref::<Generator>().is_some()
+            || collection.downcast_ref::<Range>().is_some()
+            || TupleRef::from_value(collection).is_some()
+    }
+
+    /// Number of items already taken from the collection.
+    fn get_index(frame: BcFramePtr, index: BcSlotOut) -> i32 {
+        frame
+            .get_bc_slot(index.get().to_in())
+            .unpack_int()
+            .expect("loop index must be int")
+    }
+
+    /// Get the next item of a collection iterated by index and advance the loop state.
+    fn next_item_by_index<'v>(
+        eval: &mut Evaluator<'v, '_>,
+        frame: BcFramePtr<'v>,
+        items_value: Value<'v>,
+        index: BcSlotOut,
+    ) -> anyhow::Result<Option<Value<'v>>> {
+        if let Some(generator) = items_value.downcast_ref::<Generator>() {
+            return generator.next(eval);
+        }
+        let i = Self::get_index(frame, index);
+        if i >= items_value.length()? {
+            return Ok(None);
+        }
+        frame.set_bc_slot(index, Value::new_int(i + 1));
+        Ok(Some(items_value.at(Value::new_int(i), eval.heap())?))
+    }
+
+    /// Run the rest of the loop body with `body`, and then the loop body for the remaining items.
+    ///
+    /// Return `None` when the loop is finished and execution should continue after the loop.
+    fn continue_loop<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        frame: BcFramePtr<'v>,
+        ip: BcPtrAddr<'b>,
+        (_over, var, [items, index], _loop_end): &(
+            BcSlotIn,
+            BcSlotOut,
+            [BcSlotOut; 2],
+            BcAddrOffset,
+        ),
+        body: impl FnOnce(&mut Evaluator<'v, '_>) -> RunBlockResult<'v, 'b>,
+    ) -> Option<RunBlockResult<'v, 'b>> {
+        let items_value = frame.get_bc_slot(items.get().to_in());
+        if Self::iterated_by_index(items_value) {
+            let res = body(eval);
+            return Self::continue_loop_with(eval, frame, ip, *var, res, |eval| {
+                Self::next_item_by_index(eval, frame, items_value, *index)
+            });
+        }
+
+        // Iterate like `InstrForLoop`, so the collection cannot be mutated by the loop body.
+        // The iterator cannot be kept while the generator is suspended,
+        // so it is created again when the generator is resumed,
+        // the items taken before are skipped, and the rest of the body
+        // is run while the collection is locked.
+        let mut body = Some(body);
+        let iter_ret = items_value.with_iterator(eval.heap(), |it| {
+            let i = Self::get_index(frame, *index);
+            if i > 0 {
+                it.nth(i as usize - 1);
+            }
+            let body = body.take().expect("iterator callback is called once");
+            let res = body(eval);
+            Self::continue_loop_with(eval, frame, ip, *var, res, |_eval| {
+                let item = it.next();
+                if item.is_some() {
+                    let i = Self::get_index(frame, *index);
+                    frame.set_bc_slot(*index, Value::new_int(i + 1));
+                }
+                Ok(item)
+            })
+        });
+        match iter_ret {
+            Ok(res) => res,
+            Err(e) => Some(RunBlockResult::Err(Bc::wrap_error_for_instr_ptr(
+                ip, e, eval,
+            ))),
+        }
+    }
+
+    /// Run the loop body for the items produced by `next_item`.
+    fn continue_loop_with<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        frame: BcFramePtr<'v>,
+        ip: BcPtrAddr<'b>,
+        var: BcSlotOut,
+        mut res: RunBlockResult<'v, 'b>,
+        mut next_item: impl FnMut(&mut Evaluator<'v, '_>) -> anyhow::Result<Option<Value<'v>>>,
+    ) -> Option<RunBlockResult<'v, 'b>> {
+        loop {
+            match res {
+                RunBlockResult::Continue => {}
+                RunBlockResult::Break => return None,
+                res => return Some(res),
+            }
+            match next_item(eval) {
+                Ok(Some(item)) => frame.set_bc_slot(var, item),
+                Ok(None) => return None,
+                Err(e) => {
+                    return Some(RunBlockResult::Err(Bc::wrap_error_for_instr_ptr(
+                        ip, e, eval,
+                    )));
+                }
+            }
+            res = run_block(eval, ip.add_instr::<Self>());
+        }
+    }
+
+    /// Continue the loop at `ip` when the generator was resumed in the loop body,
+    /// and the rest of the body is run by `body`.
+    pub(crate) fn resume<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        ip: BcPtrAddr<'b>,
+        body: impl FnOnce(&mut Evaluator<'v, '_>) -> RunBlockResult<'v, 'b>,
+    ) -> RunBlockResult<'v, 'b> {
+        let arg = &ip.get_instr::<Self>().arg;
+        match Self::continue_loop(eval, eval.current_frame, ip, arg, body) {
+            None => run_block(eval, ip.add_rel(arg.3)),
+            Some(res) => res,
+        }
+    }
+}
+
+impl BcInstr for InstrGenForLoop {
+    /// Collection, loop variable, slots for loop state (items and index), loop end.
+    type Arg = (BcSlotIn, BcSlotOut, [BcSlotOut; 2], BcAddrOffset);
+
+    fn run<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        frame: BcFramePtr<'v>,
+        ip: BcPtrAddr<'b>,
+        arg: &(BcSlotIn, BcSlotOut, [BcSlotOut; 2], BcAddrOffset),
+    ) -> InstrControl<'v, 'b> {
+        let (over, _var, [items, index], loop_end) = arg;
+        // Loop state is stored in the frame slots instead of Rust iterator,
+        // so the loop can be continued after the generator is resumed.
+        frame.set_bc_slot(*items, frame.get_bc_slot(*over));
+        frame.set_bc_slot(*index, Value::new_int(0));
+        match Self::continue_loop(eval, frame, ip, arg, |_eval| RunBlockResult::Continue) {
+            None => InstrControl::Next(ip.add_rel(*loop_end)),
+            Some(RunBlockResult::Return(v)) => InstrControl::Return(v),
+            Some(RunBlockResult::Yield(v, ip)) => InstrControl::Yield(v, ip),
+            Some(RunBlockResult::Err(e)) => InstrControl::Err(e.0),
+            Some(RunBlockResult::Break | RunBlockResult::Continue) => {
+                unreachable!("handled by continue_loop")
+            }
+        }
+    }
+}
+
+impl InstrWhileLoop {
+    /// Run the loop body until the loop is finished, after the body finished with `res`.
+    ///
+    /// Return `None` when the loop is finished and execution should continue after the loop.
+    fn continue_loop<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        ip: BcPtrAddr<'b>,
+        mut res: RunBlockResult<'v, 'b>,
+    ) -> Option<RunBlockResult<'v, 'b>> {
+        loop {
+            match res {
+                RunBlockResult::Continue => {}
+                RunBlockResult::Break => return None,
+                res => return Some(res),
+            }
+            if let Err(e) = eval.consume_loop_fuel() {
+                return Some(RunBlockResult::Err(Bc::wrap_error_for_instr_ptr(
+                    ip, e, eval,
+                )));
+            }
+            res = run_block(eval, ip.add_instr::<Self>());
+        }
+    }
+
+    /// Continue the loop at `ip` when the generator was resumed in the loop body,
+    /// and the rest of the body is run by `body`.
+    pub(crate) fn resume<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        ip: BcPtrAddr<'b>,
+        body: impl FnOnce(&mut Evaluator<'v, '_>) -> RunBlockResult<'v, 'b>,
+    ) -> RunBlockResult<'v, 'b> {
+        let loop_end = ip.get_instr::<Self>().arg;
+        let res = body(eval);
+        match Self::continue_loop(eval, ip, res) {
+            None => run_block(eval, ip.add_rel(loop_end)),
+            Some(res) => res,
+        }
+    }
+}
+
+impl BcInstr for InstrWhileLoop {
+    /// Loop end. Loop body starts with the condition check which breaks off the loop.
+    type Arg = BcAddrOffset;
+
+    fn run<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        _frame: BcFramePtr<'v>,
+        ip: BcPtrAddr<'b>,
+        loop_end: &BcAddrOffset,
+    ) -> InstrControl<'v, 'b> {
+        match Self::continue_loop(eval, ip, RunBlockResult::Continue) {
+            None => InstrControl::Next(ip.add_rel(*loop_end)),
+            Some(RunBlockResult::Return(v)) => InstrControl::Return(v),
+            Some(RunBlockResult::Yield(v, ip)) => InstrControl::Yield(v, ip),
+            Some(RunBlockResult::Err(e)) => InstrControl::Err(e.0),
+            Some(RunBlockResult::Break | RunBlockResult::Continue) => {
+                unreachable!("handled by continue_loop")
+            }
+        }
+    }
+}
+
+impl BcInstr for InstrYield {
+    /// Yielded value, and addresses of `GenForLoop` and `WhileLoop` instructions
+    /// enclosing this `yield`, outermost first.
+    type Arg = (BcSlotIn, FrozenRef<'static, [BcAddr]>);
+
+    #[inline(always)]
+    fn run<'v, 'b>(
+        _eval: &mut Evaluator<'v, '_>,
+        frame: BcFramePtr<'v>,
+        ip: BcPtrAddr<'b>,
+        (value, _loops): &(BcSlotIn, FrozenRef<'static, [BcAddr]>),
+    ) -> InstrControl<'v, 'b> {
+        InstrControl::Yield(frame.get_bc_slot(*value), ip)
+    }
+}
+
 impl BcInstr for InstrBreak {
     type Arg = ();
 
@@ -1558,7 +1897,8 @@ impl<A: BcCallArgsForDef> InstrNoFlowImpl for InstrCallFrozenDefImpl<A> {
     ) -> anyhow::Result<()> {
         let arguments = args.pop_from_stack(frame);
         let r = eval.with_call_stack(fun.to_value(), Some(*span), |eval| {
-            fun.as_ref().invoke_with_args(&arguments, eval)
+            fun.as_ref()
+                .invoke_with_args(fun.to_value(), &arguments, eval)
         })?;
         frame.set_bc_slot(*target, r);
         Ok(())
@@ -1716,8 +2056,7 @@ impl InstrNoFlowImpl for InstrBeforeStmtImpl {
         _: BcPtrAddr,
         span: &Self::Arg,
     ) -> anyhow::Result<()> {
-        before_stmt(*span, eval);
-        Ok(())
+        before_stmt(*span, eval)
     }
 }
 
@@ -1747,6 +2086,7 @@ impl InstrNoFlowImpl for InstrRecordCallEnterImpl {
         let fun = frame.get_bc_slot(*fun);
         eval.heap_profile.record_call_enter(fun, eval.heap());
         eval.flame_profile.record_call_enter(fun);
+        eval.call_counts_profile.record_call_enter(fun);
         Ok(())
     }
 }
@@ -1762,6 +2102,7 @@ impl InstrNoFlowImpl for InstrRecordCallExitImpl {
     ) -> anyhow::Result<()> {
         eval.heap_profile.record_call_exit(eval.heap());
         eval.flame_profile.record_call_exit();
+        eval.call_counts_profile.record_call_exit();
         Ok(())
     }
 }
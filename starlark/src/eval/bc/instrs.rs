@@ -35,6 +35,8 @@ use crate::eval::bc::addr::BcPtrAddr;
 use crate::eval::bc::instr::BcInstr;
 use crate::eval::bc::instr_impl::InstrEnd;
 use crate::eval::bc::instr_impl::InstrForLoop;
+use crate::eval::bc::instr_impl::InstrGenForLoop;
+use crate::eval::bc::instr_impl::InstrWhileLoop;
 use crate::eval::bc::opcode::BcOpcode;
 use crate::eval::bc::opcode::BcOpcodeHandler;
 use crate::eval::bc::repr::BcInstrHeader;
@@ -191,7 +193,7 @@ impl BcInstrs {
         opcodes
     }
 
-    fn iter(&self) -> impl Iterator<Item = (BcPtrAddr, BcAddr)> {
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (BcPtrAddr<'_>, BcAddr)> {
         let mut next_ptr = self.start_ptr();
         iter::from_fn(move || {
             assert!(next_ptr <= self.end_ptr());
@@ -206,7 +208,7 @@ impl BcInstrs {
         })
     }
 
-    fn end_arg(&self) -> Option<&BcInstrEndArg> {
+    pub(crate) fn end_arg(&self) -> Option<&BcInstrEndArg> {
         self.iter()
             .find_map(|(ptr, _ip)| ptr.get_instr_checked::<InstrEnd>().map(|i| &i.arg))
     }
@@ -253,6 +255,12 @@ impl BcInstrs {
             if opcode == BcOpcode::ForLoop {
                 let for_loop = ptr.get_instr::<InstrForLoop>();
                 loop_ends.push(ip.offset(for_loop.arg.2));
+            } else if opcode == BcOpcode::GenForLoop {
+                let for_loop = ptr.get_instr::<InstrGenForLoop>();
+                loop_ends.push(ip.offset(for_loop.arg.3));
+            } else if opcode == BcOpcode::WhileLoop {
+                let while_loop = ptr.get_instr::<InstrWhileLoop>();
+                loop_ends.push(ip.offset(while_loop.arg));
             }
         }
         Ok(())
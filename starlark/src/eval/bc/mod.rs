@@ -23,6 +23,7 @@ pub(crate) mod call;
 pub(crate) mod compiler;
 pub(crate) mod definitely_assigned;
 pub(crate) mod frame;
+pub(crate) mod generator;
 pub(crate) mod if_debug;
 pub(crate) mod instr;
 pub(crate) mod instr_arg;
@@ -34,4 +35,5 @@ pub(crate) mod repr;
 pub(crate) mod slow_arg;
 pub(crate) mod stack_ptr;
 pub(crate) mod stack_values;
+pub(crate) mod verify;
 pub(crate) mod writer;
@@ -62,6 +62,8 @@ impl BcNativeFunction {
         args: &Arguments<'v, '_>,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>> {
-        self.imp.invoke(eval, args)
+        let res = self.imp.invoke(eval, args)?;
+        eval.propagate_provenance_to_call(None, args, res);
+        Ok(res)
     }
 }
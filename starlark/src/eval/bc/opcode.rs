@@ -44,6 +44,7 @@ pub(crate) enum BcOpcode {
     SetArrayIndex,
     ArrayIndexSet,
     Slice,
+    SetSlice,
     ObjectField,
     ObjectFieldRaw,
     SetObjectField,
@@ -94,8 +95,11 @@ pub(crate) enum BcOpcode {
     IfBr,
     IfNotBr,
     ForLoop,
+    GenForLoop,
+    WhileLoop,
     Break,
     Continue,
+    Yield,
     Return,
     ReturnConst,
     ReturnCheckType,
@@ -0,0 +1,196 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Check invariants of compiled bytecode.
+//!
+//! Bytecode is trusted by the interpreter, so a compiler bug results in a crash
+//! or a memory corruption at runtime. In debug builds bytecode is verified
+//! after compilation, so such bugs are reported at compile time with the bytecode dump.
+
+use std::collections::HashSet;
+
+use crate::eval::bc::addr::BcAddr;
+use crate::eval::bc::bytecode::Bc;
+use crate::eval::bc::instr_arg::BcSlotAccess;
+use crate::eval::bc::opcode::BcOpcode;
+use crate::eval::bc::stack_ptr::BcSlot;
+
+/// Bytecode invariant violation.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum BcVerifyError {
+    #[error("{0}: {1:?}: jump target {2} is not an instruction address")]
+    JumpTarget(BcAddr, BcOpcode, BcAddr),
+    #[error("{0}: {1:?}: slot {2} is outside of the frame of {3} locals and {4} stack slots")]
+    SlotOutsideFrame(BcAddr, BcOpcode, BcSlot, u32, u32),
+    #[error("{0}: {1:?}: local slot {2} refers to a stack slot, there are {3} locals")]
+    LocalIsStackSlot(BcAddr, BcOpcode, BcSlot, u32),
+    #[error("{0}: {1:?}: stack slot {2} is read before it is written")]
+    UninitializedStackSlot(BcAddr, BcOpcode, BcSlot),
+    #[error("{0}: {1:?}: instruction has no span")]
+    MissingSpan(BcAddr, BcOpcode),
+    #[error("span table entry {0} is not an instruction address or is out of order")]
+    InvalidSpanEntry(BcAddr),
+    #[error("bytecode does not end with `End` instruction")]
+    MissingEnd,
+}
+
+impl Bc {
+    /// Check the bytecode invariants:
+    /// * jump targets are instruction addresses
+    /// * referenced slots are within the frame
+    /// * stack slots are written before they are read, in the order of instructions
+    /// * span table covers every instruction which may fail
+    pub(crate) fn verify(&self) -> Result<(), BcVerifyError> {
+        let local_count = self.local_count;
+        let frame_size = local_count + self.max_stack_size;
+        let addrs: HashSet<BcAddr> = self.instrs.iter().map(|(_, ip)| ip).collect();
+        let end_arg = self.instrs.end_arg().ok_or(BcVerifyError::MissingEnd)?;
+
+        let mut written = vec![false; self.max_stack_size as usize];
+        for (ptr, ip) in self.instrs.iter() {
+            let opcode = ptr.get_opcode();
+            let mut error = None;
+            opcode.visit_jump_addr(ptr, &mut |offset| {
+                let target = ip.offset(offset);
+                if !addrs.contains(&target) {
+                    error.get_or_insert(BcVerifyError::JumpTarget(ip, opcode, target));
+                }
+            });
+            // Reads are checked before writes, because an instruction may reuse
+            // its input slot for the output.
+            let mut writes = Vec::new();
+            opcode.visit_slots(ptr, &mut |access| {
+                let slot = match access {
+                    BcSlotAccess::Read(slot)
+                    | BcSlotAccess::Write(slot)
+                    | BcSlotAccess::Local(slot) => slot,
+                };
+                let e = if slot.0 >= frame_size {
+                    Some(BcVerifyError::SlotOutsideFrame(
+                        ip,
+                        opcode,
+                        slot,
+                        local_count,
+                        self.max_stack_size,
+                    ))
+                } else if slot.0 < local_count {
+                    // Local variables may be not assigned, which is checked at runtime.
+                    None
+                } else {
+                    let stack_index = (slot.0 - local_count) as usize;
+                    match access {
+                        BcSlotAccess::Read(_) if !written[stack_index] => {
+                            Some(BcVerifyError::UninitializedStackSlot(ip, opcode, slot))
+                        }
+                        BcSlotAccess::Read(_) => None,
+                        BcSlotAccess::Write(_) => {
+                            writes.push(stack_index);
+                            None
+                        }
+                        BcSlotAccess::Local(_) => Some(BcVerifyError::LocalIsStackSlot(
+                            ip,
+                            opcode,
+                            slot,
+                            local_count,
+                        )),
+                    }
+                };
+                if let Some(e) = e {
+                    error.get_or_insert(e);
+                }
+            });
+            if let Some(error) = error {
+                return Err(error);
+            }
+            for stack_index in writes {
+                written[stack_index] = true;
+            }
+        }
+
+        let mut prev = None;
+        for (addr, _) in &end_arg.slow_args {
+            if !addrs.contains(addr) || prev >= Some(*addr) {
+                return Err(BcVerifyError::InvalidSpanEntry(*addr));
+            }
+            prev = Some(*addr);
+        }
+        let with_span: HashSet<BcAddr> = end_arg.slow_args.iter().map(|(addr, _)| *addr).collect();
+        for (ptr, ip) in self.instrs.iter() {
+            let opcode = ptr.get_opcode();
+            // Profiling instructions and the end marker do not fail.
+            if !matches!(opcode, BcOpcode::ProfileBc | BcOpcode::End) && !with_span.contains(&ip) {
+                return Err(BcVerifyError::MissingSpan(ip, opcode));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::bc::bytecode::Bc;
+    use crate::eval::bc::instr_impl::InstrConst;
+    use crate::eval::bc::instr_impl::InstrReturn;
+    use crate::eval::bc::instrs::BcInstrsWriter;
+    use crate::eval::bc::slow_arg::BcInstrSlowArg;
+    use crate::eval::bc::stack_ptr::BcSlot;
+    use crate::eval::bc::verify::BcVerifyError;
+    use crate::values::FrozenHeap;
+    use crate::values::FrozenValue;
+
+    /// Bytecode with one local and one stack slot,
+    /// which writes a constant to slot `write` and returns slot `ret`.
+    fn verify(write: u32, ret: u32, with_spans: bool) -> Result<(), BcVerifyError> {
+        let heap = FrozenHeap::new();
+        let local_names = heap
+            .alloc_any_display_from_debug(vec![heap.alloc_str("x")])
+            .map(|s| s.as_slice());
+        let mut instrs = BcInstrsWriter::new();
+        let (const_addr, _) =
+            instrs.write::<InstrConst>((FrozenValue::new_bool(true), BcSlot(write).to_out()));
+        let (return_addr, _) = instrs.write::<InstrReturn>(BcSlot(ret).to_in());
+        let mut slow_args = vec![(const_addr, BcInstrSlowArg::default())];
+        if with_spans {
+            slow_args.push((return_addr, BcInstrSlowArg::default()));
+        }
+        let bc = Bc {
+            instrs: instrs.finish(slow_args, local_names),
+            local_count: 1,
+            max_stack_size: 1,
+        };
+        bc.verify()
+    }
+
+    #[test]
+    fn test_verify() {
+        verify(1, 1, true).unwrap();
+        // Local variables are checked at runtime.
+        verify(1, 0, true).unwrap();
+        assert!(matches!(
+            verify(1, 2, true),
+            Err(BcVerifyError::SlotOutsideFrame(_, _, BcSlot(2), 1, 1))
+        ));
+        assert!(matches!(
+            verify(0, 1, true),
+            Err(BcVerifyError::UninitializedStackSlot(_, _, BcSlot(1)))
+        ));
+        assert!(matches!(
+            verify(1, 1, false),
+            Err(BcVerifyError::MissingSpan(..))
+        ));
+    }
+}
@@ -28,6 +28,7 @@ use crate::eval::bc::instr_impl::InstrBr;
 use crate::eval::bc::instr_impl::InstrConst;
 use crate::eval::bc::instr_impl::InstrContinue;
 use crate::eval::bc::instr_impl::InstrForLoop;
+use crate::eval::bc::instr_impl::InstrGenForLoop;
 use crate::eval::bc::instr_impl::InstrIfBr;
 use crate::eval::bc::instr_impl::InstrIfNotBr;
 use crate::eval::bc::instr_impl::InstrLoadLocal;
@@ -35,6 +36,8 @@ use crate::eval::bc::instr_impl::InstrLoadLocalCaptured;
 use crate::eval::bc::instr_impl::InstrMov;
 use crate::eval::bc::instr_impl::InstrProfileBc;
 use crate::eval::bc::instr_impl::InstrStoreLocalCaptured;
+use crate::eval::bc::instr_impl::InstrWhileLoop;
+use crate::eval::bc::instr_impl::InstrYield;
 use crate::eval::bc::instrs::BcInstrsWriter;
 use crate::eval::bc::instrs::PatchAddr;
 use crate::eval::bc::opcode::BcOpcode;
@@ -73,6 +76,10 @@ pub(crate) struct BcWriter<'f> {
     definitely_assigned: BcDefinitelyAssigned,
     /// Max observed stack size.
     max_stack_size: u32,
+    /// Addresses of `GenForLoop` and `WhileLoop` instructions enclosing current program point,
+    /// outermost first.
+    /// These are the loops to continue when a generator is resumed after `yield`.
+    generator_loops: Vec<BcAddr>,
 
     /// Allocate various objects here.
     pub(crate) heap: &'f FrozenHeap,
@@ -102,6 +109,7 @@ impl<'f> BcWriter<'f> {
             local_names,
             definitely_assigned,
             max_stack_size: 0,
+            generator_loops: Vec::new(),
             heap,
         }
     }
@@ -118,6 +126,7 @@ impl<'f> BcWriter<'f> {
             local_names,
             definitely_assigned,
             max_stack_size,
+            generator_loops,
             heap,
         } = self;
         let _ = has_before_instr;
@@ -125,6 +134,7 @@ impl<'f> BcWriter<'f> {
         let _ = heap;
         let _ = definitely_assigned;
         assert_eq!(stack_size, 0);
+        assert!(generator_loops.is_empty());
         // Drop lifetime.
         let local_names = unsafe {
             transmute!(
@@ -133,11 +143,17 @@ impl<'f> BcWriter<'f> {
                 local_names
             )
         };
-        Bc {
+        let bc = Bc {
             instrs: instrs.finish(spans, local_names),
             local_count: local_names.len().try_into().unwrap(),
             max_stack_size,
+        };
+        if cfg!(any(debug_assertions, test)) {
+            if let Err(e) = bc.verify() {
+                panic!("bytecode verification failed: {}\n{}", e, bc.dump_debug());
+            }
         }
+        bc
     }
 
     fn local_count(&self) -> u32 {
@@ -359,6 +375,59 @@ impl<'f> BcWriter<'f> {
         self.restore_definitely_assigned(definitely_assigned);
     }
 
+    /// Write a loop in a generator function.
+    ///
+    /// Unlike regular loop, the loop state is stored in frame slots,
+    /// so the loop can be continued after the generator is suspended in the loop body.
+    pub(crate) fn write_gen_for(
+        &mut self,
+        over: BcSlotIn,
+        var: BcSlotOut,
+        span: FrameSpan,
+        body: impl FnOnce(&mut BcWriter),
+    ) {
+        let definitely_assigned = self.save_definitely_assigned();
+
+        self.alloc_slots_c(|state: BcSlotsN<2>, bc| {
+            let state = [state.get::<0>().to_out(), state.get::<1>().to_out()];
+            let (addr, arg) = bc.write_instr_ret_arg::<InstrGenForLoop>(
+                span,
+                (over, var, state, BcAddrOffset::FORWARD),
+            );
+            let end_patch = bc.instrs.addr_to_patch(addr, unsafe { &(*arg).3 });
+            bc.generator_loops.push(addr);
+            body(bc);
+            bc.write_instr::<InstrContinue>(span, ());
+            bc.generator_loops.pop().unwrap();
+            bc.patch_addr(end_patch);
+        });
+
+        self.restore_definitely_assigned(definitely_assigned);
+    }
+
+    /// Write while loop. Loop body must break off the loop when the condition is false.
+    pub(crate) fn write_while(&mut self, span: FrameSpan, body: impl FnOnce(&mut Self)) {
+        let definitely_assigned = self.save_definitely_assigned();
+
+        let (addr, arg) = self.write_instr_ret_arg::<InstrWhileLoop>(span, BcAddrOffset::FORWARD);
+        let end_patch = self.instrs.addr_to_patch(addr, unsafe { &*arg });
+        self.generator_loops.push(addr);
+        body(self);
+        self.write_instr::<InstrContinue>(span, ());
+        self.generator_loops.pop().unwrap();
+        self.patch_addr(end_patch);
+
+        self.restore_definitely_assigned(definitely_assigned);
+    }
+
+    /// Write `yield` instruction.
+    pub(crate) fn write_yield(&mut self, span: FrameSpan, value: BcSlotIn) {
+        let loops = self
+            .heap
+            .alloc_any_slice_display_from_debug(&self.generator_loops);
+        self.write_instr::<InstrYield>(span, (value, loops));
+    }
+
     fn stack_add(&mut self, add: u32) {
         self.stack_size += add;
         self.max_stack_size = cmp::max(self.max_stack_size, self.stack_size);
@@ -36,15 +36,20 @@ use once_cell::sync::Lazy;
 use crate as starlark;
 use crate::codemap::CodeMap;
 use crate::collections::Hashed;
+use crate::collections::SmallSet;
 use crate::const_frozen_string;
 use crate::docs;
 use crate::docs::DocItem;
 use crate::docs::DocString;
 use crate::docs::DocStringKind;
+use crate::environment::slots::ModuleSlotId;
 use crate::environment::FrozenModuleRef;
 use crate::environment::Globals;
+use crate::eval::bc::addr::BcAddr;
 use crate::eval::bc::bytecode::Bc;
 use crate::eval::bc::frame::alloca_frame;
+use crate::eval::bc::generator::Generator;
+use crate::eval::bc::generator::GeneratorStep;
 use crate::eval::compiler::def_inline::inline_def_body;
 use crate::eval::compiler::def_inline::InlineDefBody;
 use crate::eval::compiler::expr::ExprCompiled;
@@ -66,13 +71,16 @@ use crate::eval::runtime::arguments::ResolvedArgName;
 use crate::eval::runtime::evaluator::Evaluator;
 use crate::eval::runtime::frame_span::FrameSpan;
 use crate::eval::runtime::frozen_file_span::FrozenFileSpan;
+use crate::eval::runtime::params::ParameterKind;
 use crate::eval::runtime::params::ParametersSpec;
 use crate::eval::runtime::slots::LocalSlotId;
 use crate::eval::runtime::slots::LocalSlotIdCapturedOrNot;
 use crate::eval::Arguments;
 use crate::syntax::ast::ParameterP;
+use crate::values::dict::DictRef;
 use crate::values::frozen_ref::AtomicFrozenRefOption;
 use crate::values::function::FUNCTION_TYPE;
+use crate::values::list::ListRef;
 use crate::values::typing::TypeCompiled;
 use crate::values::Freeze;
 use crate::values::Freezer;
@@ -220,6 +228,13 @@ impl<T> ParametersCompiled<T> {
         self.params.iter().any(|p| p.has_type())
     }
 
+    /// Any parameter has a default value?
+    pub(crate) fn has_default_values(&self) -> bool {
+        self.params
+            .iter()
+            .any(|p| matches!(p.node, ParameterCompiled::WithDefaultValue(..)))
+    }
+
     /// Has `*args` or `*kwargs` parameter? `*` is fine.
     pub(crate) fn has_args_or_kwargs(&self) -> bool {
         self.params.iter().any(|p| {
@@ -258,6 +273,9 @@ pub(crate) struct DefInfo {
     /// Slots to copy from the parent.
     /// Module-level identifiers are not copied over, to avoid excess copying.
     pub(crate) parent: FrozenRef<'static, [CopySlotFromParent]>,
+    /// Module slots the body of this function (or functions nested in it) may read.
+    /// Slots of values inlined during compilation are not included.
+    pub(crate) module_slots: Vec<ModuleSlotId>,
     /// Statement compiled for non-frozen def.
     #[derivative(Debug = "ignore")]
     stmt_compiled: Bc,
@@ -269,6 +287,12 @@ pub(crate) struct DefInfo {
     stmt_compile_context: StmtCompileContext,
     /// Function can be inlined.
     pub(crate) inline_def_body: Option<InlineDefBody>,
+    /// Optimize the body again when the module is frozen.
+    optimize_on_freeze: bool,
+    /// Function body contains `yield`, so calling the function creates a generator.
+    pub(crate) is_generator: bool,
+    /// List and dict default values are copied when used by a call.
+    pub(crate) fresh_defaults: bool,
     /// Globals captured during function or module creation.
     /// Only needed for debugger evaluation.
     pub(crate) globals: FrozenRef<'static, Globals>,
@@ -283,10 +307,14 @@ impl DefInfo {
             docstring: None,
             used: FrozenRef::new(&[]),
             parent: FrozenRef::new(&[]),
+            module_slots: Vec::new(),
             stmt_compiled: Bc::default(),
             body_stmts: StmtsCompiled::empty(),
             stmt_compile_context: StmtCompileContext::default(),
             inline_def_body: None,
+            optimize_on_freeze: false,
+            is_generator: false,
+            fresh_defaults: false,
             globals: FrozenRef::new(Globals::empty()),
         });
         FrozenRef::new(&EMPTY)
@@ -305,10 +333,14 @@ impl DefInfo {
             docstring: None,
             used: local_names,
             parent,
+            module_slots: Vec::new(),
             stmt_compiled: Bc::default(),
             body_stmts: StmtsCompiled::empty(),
             stmt_compile_context: StmtCompileContext::default(),
             inline_def_body: None,
+            optimize_on_freeze: false,
+            is_generator: false,
+            fresh_defaults: false,
             globals,
         }
     }
@@ -397,9 +429,17 @@ impl Compiler<'_, '_, '_> {
         let return_type = self.expr_for_type(return_type).map(Box::new);
 
         self.enter_scope(scope_id);
+        self.def_module_slots.push(SmallSet::new());
 
         let docstring = DocString::extract_raw_starlark_docstring(&suite);
+        let is_generator = suite.node.contains_yield();
         let body = self.stmt(suite, false);
+        let module_slots = self.def_module_slots.pop().unwrap();
+        if let Some(parent_module_slots) = self.def_module_slots.last_mut() {
+            // Nested function can only be created by calling this function,
+            // so this function needs the slots of the nested function too.
+            parent_module_slots.extend(module_slots.iter().copied());
+        }
         let scope_id = self.exit_scope();
         let scope_names = self.scope_data.get_scope(scope_id);
 
@@ -408,11 +448,22 @@ impl Compiler<'_, '_, '_> {
         let inline_def_body = if has_types {
             // It is harder to inline if a function declares parameter types or return type.
             None
+        } else if is_generator {
+            // Generator body is not executed on call.
+            None
+        } else if !self.eval.inline_defs {
+            None
+        } else if self.fresh_defaults && params.has_default_values() {
+            // Inlined body would see the shared default value.
+            None
         } else {
             inline_def_body(&params, &body)
         };
 
         let param_count = params.count_param_variables();
+        // Generator function returns a generator, not the value returned by the body.
+        let compile_context =
+            self.compile_context(return_type.is_some() && !is_generator, is_generator);
 
         let used = self
             .eval
@@ -428,15 +479,19 @@ impl Compiler<'_, '_, '_> {
                 .eval
                 .frozen_heap()
                 .alloc_any_slice_display_from_debug(&scope_names.parent),
+            module_slots: module_slots.into_iter().collect(),
             stmt_compiled: body.as_bc(
-                &self.compile_context(return_type.is_some()),
+                &compile_context,
                 used,
                 param_count,
                 self.eval.module_env.frozen_heap(),
             ),
             body_stmts: body,
             inline_def_body,
-            stmt_compile_context: self.compile_context(return_type.is_some()),
+            optimize_on_freeze: self.eval.optimize_on_freeze,
+            is_generator,
+            fresh_defaults: self.fresh_defaults,
+            stmt_compile_context: compile_context,
             globals: self.globals,
         });
 
@@ -600,11 +655,11 @@ where
 
     fn invoke(
         &self,
-        _me: Value<'v>,
+        me: Value<'v>,
         args: &Arguments<'v, '_>,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>> {
-        self.invoke_impl(&args.0, eval)
+        self.invoke_impl(me, &args.0, eval)
     }
 
     fn documentation(&self) -> Option<DocItem> {
@@ -670,6 +725,7 @@ where
     #[inline(always)]
     fn invoke_impl<'a, A: ArgumentsImpl<'v, 'a>>(
         &self,
+        me: Value<'v>,
         args: &A,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>>
@@ -680,12 +736,13 @@ where
         alloca_frame(eval, bc.local_count, bc.max_stack_size, |eval| {
             let slots = eval.current_frame.locals();
             self.parameters.collect_inline(args, slots, eval.heap())?;
-            self.invoke_raw(eval)
+            self.invoke_raw(me, eval)
         })
     }
 
     pub(crate) fn invoke_with_args<'a, A: ArgumentsImpl<'v, 'a>>(
         &self,
+        me: Value<'v>,
         args: &A,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>>
@@ -695,20 +752,72 @@ where
         // This is trivial function which delegates to `invoke_impl`.
         // `invoke_impl` is called from two places,
         // giving this function different name makes this function easier to see in profiler.
-        self.invoke_impl(args, eval)
+        self.invoke_impl(me, args, eval)
     }
 
     /// Invoke the function, assuming that:
     /// * the frame has been allocated and stored in `eval.current_frame`
     /// * the arguments have been collected into the frame
     #[inline(always)]
-    fn invoke_raw(&self, eval: &mut Evaluator<'v, '_>) -> anyhow::Result<Value<'v>> {
+    fn invoke_raw(&self, me: Value<'v>, eval: &mut Evaluator<'v, '_>) -> anyhow::Result<Value<'v>> {
         // println!("invoking {}", self.def.stmt.name.node);
 
+        self.init_frame(eval)?;
+
+        if self.def_info.is_generator {
+            return Ok(Generator::alloc(me, eval));
+        }
+
+        if Self::FROZEN {
+            debug_assert!(self.module.load_relaxed().is_some());
+        }
+        let res =
+            eval.with_function_context(self.module.load_relaxed(), |eval| self.bc().run(eval));
+
+        res.map_err(|EvalException(e)| e)
+    }
+
+    /// Resume the generator created by this function: restore the frame from `slots`,
+    /// and continue the execution after the `yield` at `resume` or from the start.
+    pub(crate) fn resume_generator(
+        &self,
+        me: Value<'v>,
+        slots: &[Option<Value<'v>>],
+        resume: Option<BcAddr>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<GeneratorStep<'v>> {
+        eval.with_call_stack(me, None, |eval| {
+            let bc = self.bc();
+            alloca_frame(eval, bc.local_count, bc.max_stack_size, |eval| {
+                eval.current_frame.restore_slots(slots);
+                let res = eval.with_function_context(self.module.load_relaxed(), |eval| {
+                    bc.resume(eval, resume)
+                });
+                match res {
+                    Ok(Some((value, addr))) => Ok(GeneratorStep::Yield(
+                        value,
+                        addr,
+                        eval.current_frame.save_slots(),
+                    )),
+                    Ok(None) => Ok(GeneratorStep::Finished),
+                    Err(EvalException(e)) => Err(e),
+                }
+            })
+        })
+    }
+
+    /// Check parameter types and fill the slots for captured variables
+    /// after the arguments have been collected into the frame.
+    #[inline(always)]
+    fn init_frame(&self, eval: &mut Evaluator<'v, '_>) -> anyhow::Result<()> {
         if !self.parameter_types.is_empty() {
             self.check_parameter_types(eval)?;
         }
 
+        if self.def_info.fresh_defaults {
+            self.copy_mutable_defaults(eval);
+        }
+
         // Parameters are collected into local slots without captures
         // (to avoid even more branches in parameter capture),
         // and this loop wraps captured parameters.
@@ -725,13 +834,32 @@ where
             }
         }
 
-        if Self::FROZEN {
-            debug_assert!(self.module.load_relaxed().is_some());
-        }
-        let res =
-            eval.with_function_context(self.module.load_relaxed(), |eval| self.bc().run(eval));
+        Ok(())
+    }
 
-        res.map_err(|EvalException(e)| e)
+    /// Replace list and dict default values in parameter slots with their copies,
+    /// so the function cannot mutate the value shared between calls.
+    #[cold]
+    fn copy_mutable_defaults(&self, eval: &mut Evaluator<'v, '_>) {
+        for (i, (_, kind)) in self.parameters.iter_params().enumerate() {
+            let default = match kind {
+                ParameterKind::Defaulted(d) => d.to_value(),
+                _ => continue,
+            };
+            let slot = LocalSlotId(i as u32).to_captured_or_not();
+            match eval.current_frame.get_slot(slot) {
+                Some(v) if v.ptr_eq(default) => {}
+                _ => continue,
+            }
+            let copy = if let Some(list) = ListRef::from_value(default) {
+                eval.heap().alloc_list(list.content())
+            } else if let Some(dict) = DictRef::from_value(default) {
+                eval.heap().alloc(dict.clone())
+            } else {
+                continue;
+            };
+            eval.current_frame.set_slot(slot, copy);
+        }
     }
 
     pub(crate) fn resolve_arg_name(&self, name: Hashed<&str>) -> ResolvedArgName {
@@ -750,6 +878,16 @@ where
 }
 
 impl FrozenDef {
+    /// Module slots this function may read from the module being frozen.
+    ///
+    /// Empty if the function was declared in another, already frozen, module.
+    pub(crate) fn module_slots_for_freeze(&self) -> &[ModuleSlotId] {
+        match self.module.load_relaxed() {
+            Some(_) => &[],
+            None => &self.def_info.module_slots,
+        }
+    }
+
     pub(crate) fn post_freeze(
         &self,
         module: FrozenRef<FrozenModuleRef>,
@@ -769,23 +907,27 @@ impl FrozenDef {
 
         // Now perform the optimization of function body with fully frozen module:
         // all module variables are frozen, so we can inline more aggressively.
-        let body_optimized = self
-            .def_info
-            .body_stmts
-            .optimize(&mut OptCtx::new(
+        // Otherwise the body is compiled to bytecode as is.
+        let optimized;
+        let body = if self.def_info.optimize_on_freeze {
+            optimized = self.def_info.body_stmts.optimize(&mut OptCtx::new(
                 &mut OptimizeOnFreezeContext {
                     module: def_module.as_ref(),
                     heap,
                     frozen_heap,
                 },
                 self.parameters.len().try_into().unwrap(),
-            ))
-            .as_bc(
-                &self.def_info.stmt_compile_context,
-                self.def_info.used,
-                self.parameters.len() as u32,
-                frozen_heap,
-            );
+            ));
+            &optimized
+        } else {
+            &self.def_info.body_stmts
+        };
+        let body_optimized = body.as_bc(
+            &self.def_info.stmt_compile_context,
+            self.def_info.used,
+            self.parameters.len() as u32,
+            frozen_heap,
+        );
 
         // Store the optimized body.
         // This is (relatively) safe because we know that during freeze
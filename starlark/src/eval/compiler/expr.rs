@@ -55,6 +55,7 @@ use crate::syntax::ast::BinOp;
 use crate::syntax::ast::ExprP;
 use crate::syntax::ast::LambdaP;
 use crate::syntax::ast::StmtP;
+use crate::syntax::lexer::BYTES_LITERAL_ATTR;
 use crate::syntax::lexer::TokenInt;
 use crate::values::function::BoundMethodGen;
 use crate::values::function::FrozenBoundMethod;
@@ -63,6 +64,7 @@ use crate::values::list::ListRef;
 use crate::values::string::interpolation::parse_percent_s_one;
 use crate::values::types::bigint::StarlarkBigInt;
 use crate::values::types::bool::StarlarkBool;
+use crate::values::types::bytes::StarlarkBytes;
 use crate::values::types::dict::Dict;
 use crate::values::types::float::StarlarkFloat;
 use crate::values::types::list::value::FrozenListData;
@@ -1157,6 +1159,9 @@ impl<'v, 'a, 'e> Compiler<'v, 'a, 'e> {
                     }
                 }
 
+                if let Some(module_slots) = self.def_module_slots.last_mut() {
+                    module_slots.insert(slot);
+                }
                 ExprCompiled::Module(slot)
             }
             ResolvedIdent::Global(v) => ExprCompiled::Value(v),
@@ -1206,6 +1211,20 @@ impl<'v, 'a, 'e> Compiler<'v, 'a, 'e> {
                 let else_expr = self.expr(else_expr);
                 return ExprCompiled::if_expr(cond, then_expr, else_expr);
             }
+            ExprP::Dot(left, right) if right.node == BYTES_LITERAL_ATTR => {
+                let payload = left
+                    .node
+                    .unpack_string_literal()
+                    .expect("bytes literal is parsed as a string literal");
+                // Each character of the payload is a byte.
+                let bytes: Vec<u8> = payload.chars().map(|c| c as u8).collect();
+                ExprCompiled::Value(
+                    self.eval
+                        .module_env
+                        .frozen_heap()
+                        .alloc(StarlarkBytes::new(bytes)),
+                )
+            }
             ExprP::Dot(left, right) => {
                 let left = self.expr(*left);
                 let s = Symbol::new(&right.node);
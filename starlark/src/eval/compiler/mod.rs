@@ -32,8 +32,11 @@ pub(crate) mod span;
 pub(crate) mod stmt;
 
 use std::fmt::Debug;
+use std::mem;
 
 use crate::codemap::CodeMap;
+use crate::collections::SmallSet;
+use crate::environment::slots::ModuleSlotId;
 use crate::environment::Globals;
 use crate::errors::Diagnostic;
 use crate::eval::compiler::scope::ScopeData;
@@ -66,6 +69,30 @@ pub(crate) fn add_span_to_expr_error(
     EvalException(add_span_to_error(e, span, eval))
 }
 
+/// Like [`add_span_to_expr_error`], for an error raised by the expression at `span`,
+/// rather than propagated from a function called there: runs the `on_error` hooks first.
+#[cold]
+#[inline(never)]
+pub(crate) fn add_span_to_raised_error(
+    e: anyhow::Error,
+    span: FrameSpan,
+    eval: &mut Evaluator,
+) -> EvalException {
+    let raised = match e.downcast_ref::<Diagnostic>() {
+        Some(d) => d.span.is_none(),
+        None => true,
+    };
+    if raised && !eval.before_stmt.on_error.is_empty() {
+        // Errors raised by the hooks themselves are not reported to them.
+        let fs = mem::take(&mut eval.before_stmt.on_error);
+        for f in &fs {
+            f(&e, span.span.file_span_ref(), eval)
+        }
+        eval.before_stmt.on_error = fs;
+    }
+    add_span_to_expr_error(e, span, eval)
+}
+
 /// Convert syntax error to spanned evaluation exception
 #[inline(always)]
 pub(crate) fn expr_throw<'v, T>(
@@ -83,11 +110,16 @@ pub(crate) struct Compiler<'v, 'a, 'e> {
     pub(crate) eval: &'e mut Evaluator<'v, 'a>,
     pub(crate) scope_data: ScopeData,
     pub(crate) locals: Vec<ScopeId>,
+    /// Module slots referenced by the bodies of the functions being compiled,
+    /// innermost function last.
+    pub(crate) def_module_slots: Vec<SmallSet<ModuleSlotId>>,
     pub(crate) globals: FrozenRef<'static, Globals>,
     pub(crate) codemap: FrozenRef<'static, CodeMap>,
     pub(crate) has_before_stmt: bool,
     pub(crate) bc_profile: bool,
     pub(crate) check_types: bool,
+    /// Copy mutable default values of parameters on each call.
+    pub(crate) fresh_defaults: bool,
 }
 
 impl Compiler<'_, '_, '_> {
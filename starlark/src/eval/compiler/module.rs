@@ -49,7 +49,13 @@ impl<'v> Compiler<'v, '_, '_> {
                     self.eval,
                 ));
             }
-            Some(loader) => expr_throw(loader.load(&name), span, self.eval)?,
+            Some(loader) => {
+                let module = match self.eval.recorder {
+                    Some(recorder) => recorder.record_load(&name, || loader.load(&name)),
+                    None => loader.load(&name),
+                };
+                expr_throw(module, span, self.eval)?
+            }
         };
 
         for (our_name, their_name) in load.node.args {
@@ -58,15 +64,15 @@ impl<'v> Compiler<'v, '_, '_> {
                 Slot::Local(..) => unreachable!("symbol need to be resolved to module"),
                 Slot::Module(slot) => slot,
             };
+            let span = FrozenFileSpan::new(self.codemap, our_name.span.merge(their_name.span));
             let value = expr_throw(
                 self.eval.module_env.load_symbol(&loadenv, &their_name.node),
-                FrameSpan::new(FrozenFileSpan::new(
-                    self.codemap,
-                    our_name.span.merge(their_name.span),
-                )),
+                FrameSpan::new(span),
                 self.eval,
             )?;
-            self.eval.set_slot_module(slot, value)
+            self.eval.set_slot_module(slot, value);
+            self.eval
+                .explain_assign(slot, value, || span.to_original_file_span());
         }
 
         Ok(())
@@ -95,7 +101,7 @@ impl<'v> Compiler<'v, '_, '_> {
             _ => {
                 let stmt = self.module_top_level_stmt(stmt);
                 let bc = stmt.as_bc(
-                    &self.compile_context(false),
+                    &self.compile_context(false, false),
                     local_names,
                     0,
                     self.eval.module_env.frozen_heap(),
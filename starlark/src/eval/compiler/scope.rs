@@ -54,8 +54,10 @@ use crate::syntax::ast::Stmt;
 use crate::syntax::ast::StmtP;
 use crate::syntax::ast::Visibility;
 use crate::syntax::payload_map::AstPayloadFunction;
+use crate::syntax::dialect::DialectError;
 use crate::syntax::uniplate::VisitMut;
 use crate::syntax::Dialect;
+use crate::syntax::DialectProfile;
 use crate::values::FrozenHeap;
 use crate::values::FrozenRef;
 use crate::values::FrozenStringValue;
@@ -72,6 +74,9 @@ pub(crate) struct Scope<'a> {
     unscopes: Vec<Unscope>,
     codemap: FrozenRef<'static, CodeMap>,
     globals: FrozenRef<'static, Globals>,
+    /// Can the predeclared `set` be used, see [`Dialect::enable_sets`].
+    enable_sets: bool,
+    dialect_profile: DialectProfile,
     pub(crate) errors: Vec<anyhow::Error>,
 }
 
@@ -266,6 +271,8 @@ impl<'a> Scope<'a> {
             unscopes: Vec::new(),
             codemap,
             globals,
+            enable_sets: dialect.enable_sets,
+            dialect_profile: dialect.profile,
             errors: Vec::new(),
         };
         scope.resolve_idents(code);
@@ -499,6 +506,14 @@ impl<'a> Scope<'a> {
                             self.errors.push(self.variable_not_found_err(ident));
                             return;
                         }
+                        Some(_) if ident.node == "set" && !self.enable_sets => {
+                            self.errors.push(Diagnostic::new(
+                                DialectError::Sets(self.dialect_profile),
+                                ident.span,
+                                &self.codemap,
+                            ));
+                            return;
+                        }
                         Some(v) => ResolvedIdent::Global(v),
                     }
                 }
@@ -657,6 +672,9 @@ impl Stmt {
                 Assign::collect_defines_lvalue(dest, InLoop::Yes, scope_data, frozen_heap, result);
                 StmtP::collect_defines(body, InLoop::Yes, scope_data, frozen_heap, result, dialect);
             }
+            StmtP::While(_cond, body) => {
+                StmtP::collect_defines(body, InLoop::Yes, scope_data, frozen_heap, result, dialect);
+            }
             StmtP::Def(DefP { name, .. }) => AssignIdent::collect_assign_ident(
                 name,
                 in_loop,
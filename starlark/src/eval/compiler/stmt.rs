@@ -24,6 +24,7 @@
 //! Bazel's BUILD file). The BUILD dialect does not allow `def` statements.
 
 use std::mem;
+use std::sync::atomic;
 
 use gazebo::prelude::*;
 use thiserror::Error;
@@ -47,7 +48,7 @@ use crate::eval::compiler::small_vec_1::SmallVec1;
 use crate::eval::compiler::span::IrSpanned;
 use crate::eval::compiler::Compiler;
 use crate::eval::runtime::evaluator::Evaluator;
-use crate::eval::runtime::evaluator::GC_THRESHOLD;
+use crate::eval::runtime::evaluator::EvaluatorError;
 use crate::eval::runtime::frame_span::FrameSpan;
 use crate::eval::runtime::frozen_file_span::FrozenFileSpan;
 use crate::eval::runtime::slots::LocalCapturedSlotId;
@@ -79,6 +80,8 @@ pub(crate) enum AssignModifyLhs {
 pub(crate) enum StmtCompiled {
     PossibleGc,
     Return(IrSpanned<ExprCompiled>),
+    /// `yield` in a generator function.
+    Yield(IrSpanned<ExprCompiled>),
     Expr(IrSpanned<ExprCompiled>),
     Assign(
         IrSpanned<AssignCompiledValue>,
@@ -94,6 +97,7 @@ pub(crate) enum StmtCompiled {
             StmtsCompiled,
         )>,
     ),
+    While(Box<(IrSpanned<ExprCompiled>, StmtsCompiled)>),
     Break,
     Continue,
 }
@@ -102,6 +106,8 @@ pub(crate) enum StmtCompiled {
 pub(crate) struct StmtCompileContext {
     /// Current function has return type.
     pub(crate) has_return_type: bool,
+    /// Current function is a generator, i.e. contains `yield`.
+    pub(crate) is_generator: bool,
     /// Insert `BeforeStmt` instruction before statement.
     pub(crate) has_before_stmt: bool,
     /// Instert bytecode profiling instructions.
@@ -143,6 +149,10 @@ impl IrSpanned<StmtCompiled> {
                 span,
                 node: StmtCompiled::Return(e.optimize(ctx)),
             }),
+            StmtCompiled::Yield(e) => StmtsCompiled::one(IrSpanned {
+                span,
+                node: StmtCompiled::Yield(e.optimize(ctx)),
+            }),
             StmtCompiled::Expr(expr) => {
                 let expr = expr.optimize(ctx);
                 StmtsCompiled::expr(expr)
@@ -170,6 +180,12 @@ impl IrSpanned<StmtCompiled> {
                 let body = body.optimize(ctx);
                 StmtsCompiled::for_stmt(span, var, over, body)
             }
+            StmtCompiled::While(cond_body) => {
+                let (cond, body) = &**cond_body;
+                let cond = cond.optimize(ctx);
+                let body = body.optimize(ctx);
+                StmtsCompiled::while_stmt(span, cond, body)
+            }
             s @ (StmtCompiled::PossibleGc | StmtCompiled::Break | StmtCompiled::Continue) => {
                 StmtsCompiled::one(IrSpanned {
                     span,
@@ -347,6 +363,20 @@ impl StmtsCompiled {
             node: StmtCompiled::For(Box::new((var, over, body))),
         })
     }
+
+    fn while_stmt(
+        span: FrameSpan,
+        cond: IrSpanned<ExprCompiled>,
+        body: StmtsCompiled,
+    ) -> StmtsCompiled {
+        if let ExprCompiledBool::Const(false) = ExprCompiledBool::new(cond.clone()).node {
+            return StmtsCompiled::empty();
+        }
+        StmtsCompiled::one(IrSpanned {
+            span,
+            node: StmtCompiled::While(Box::new((cond, body))),
+        })
+    }
 }
 
 #[derive(Debug, Error)]
@@ -360,6 +390,14 @@ pub(crate) enum AssignError {
 pub(crate) enum AssignCompiledValue {
     Dot(IrSpanned<ExprCompiled>, String),
     ArrayIndirection(IrSpanned<ExprCompiled>, IrSpanned<ExprCompiled>),
+    Slice(
+        Box<(
+            IrSpanned<ExprCompiled>,
+            Option<IrSpanned<ExprCompiled>>,
+            Option<IrSpanned<ExprCompiled>>,
+            Option<IrSpanned<ExprCompiled>>,
+        )>,
+    ),
     Tuple(Vec<IrSpanned<AssignCompiledValue>>),
     Local(LocalSlotId),
     LocalCaptured(LocalCapturedSlotId),
@@ -390,6 +428,14 @@ impl IrSpanned<AssignCompiledValue> {
                 let index = index.optimize(ctx);
                 AssignCompiledValue::ArrayIndirection(array, index)
             }
+            AssignCompiledValue::Slice(ref v_start_stop_step) => {
+                let (v, start, stop, step) = &**v_start_stop_step;
+                let v = v.optimize(ctx);
+                let start = start.as_ref().map(|x| x.optimize(ctx));
+                let stop = stop.as_ref().map(|x| x.optimize(ctx));
+                let step = step.as_ref().map(|x| x.optimize(ctx));
+                AssignCompiledValue::Slice(Box::new((v, start, stop, step)))
+            }
             AssignCompiledValue::Tuple(ref xs) => {
                 let xs = xs.map(|x| x.optimize(ctx));
                 AssignCompiledValue::Tuple(xs)
@@ -417,6 +463,13 @@ impl Compiler<'_, '_, '_> {
                 let idx = self.expr(idx);
                 AssignCompiledValue::ArrayIndirection(e, idx)
             }
+            AssignP::Slice(collection, start, stop, stride) => {
+                let collection = self.expr(*collection);
+                let start = start.map(|x| self.expr(*x));
+                let stop = stop.map(|x| self.expr(*x));
+                let stride = stride.map(|x| self.expr(*x));
+                AssignCompiledValue::Slice(Box::new((collection, start, stop, stride)))
+            }
             AssignP::Tuple(v) => {
                 let v = v.into_map(|x| self.assign(x));
                 AssignCompiledValue::Tuple(v)
@@ -513,15 +566,18 @@ impl Compiler<'_, '_, '_> {
             AssignP::Tuple(_) => {
                 unreachable!("Assign modify validates that the LHS is never a tuple")
             }
+            AssignP::Slice(..) => {
+                unreachable!("Assign modify validates that the LHS is never a slice")
+            }
         }
     }
 }
 
 // This function should be called before every meaningful statement.
-// The purposes are GC, profiling and debugging.
+// The purposes are GC, profiling, debugging and cancellation.
 //
 // This function is called only if `before_stmt` is set before compilation start.
-pub(crate) fn before_stmt(span: FrameSpan, eval: &mut Evaluator) {
+pub(crate) fn before_stmt(span: FrameSpan, eval: &mut Evaluator) -> anyhow::Result<()> {
     assert!(
         eval.before_stmt.enabled(),
         "this code should not be called if `before_stmt` is set"
@@ -535,6 +591,12 @@ pub(crate) fn before_stmt(span: FrameSpan, eval: &mut Evaluator) {
         added.is_empty(),
         "`before_stmt` cannot be modified during evaluation"
     );
+    if let Some(cancelled) = eval.before_stmt.cancelled {
+        if cancelled.load(atomic::Ordering::Relaxed) {
+            return Err(EvaluatorError::Cancelled.into());
+        }
+    }
+    Ok(())
 }
 
 // There are two requirements to perform a GC:
@@ -572,7 +634,7 @@ pub(crate) fn possible_gc(eval: &mut Evaluator) {
         // references to all values, so walking covers everything and the unsafe
         // is satisfied.
         unsafe { eval.garbage_collect() }
-        eval.next_gc_level = eval.heap().allocated_bytes() + GC_THRESHOLD;
+        eval.next_gc_level = eval.heap().allocated_bytes() + eval.gc_threshold;
     }
 }
 
@@ -652,9 +714,14 @@ pub(crate) fn add_assign<'v>(
 }
 
 impl Compiler<'_, '_, '_> {
-    pub(crate) fn compile_context(&self, has_return_type: bool) -> StmtCompileContext {
+    pub(crate) fn compile_context(
+        &self,
+        has_return_type: bool,
+        is_generator: bool,
+    ) -> StmtCompileContext {
         StmtCompileContext {
             has_return_type,
+            is_generator,
             has_before_stmt: self.has_before_stmt,
             bc_profile: self.bc_profile,
             record_call_enter_exit: self.eval.heap_or_flame_profile,
@@ -771,6 +838,11 @@ impl Compiler<'_, '_, '_> {
                 let st = self.stmt(body, false);
                 StmtsCompiled::for_stmt(span, var, over, st)
             }
+            StmtP::While(cond, body) => {
+                let cond = self.expr(cond);
+                let body = self.stmt(*body, false);
+                StmtsCompiled::while_stmt(span, cond, body)
+            }
             StmtP::Return(None) => StmtsCompiled::one(IrSpanned {
                 node: StmtCompiled::Return(IrSpanned {
                     span,
@@ -782,6 +854,17 @@ impl Compiler<'_, '_, '_> {
                 node: StmtCompiled::Return(self.expr(e)),
                 span,
             }),
+            StmtP::Yield(None) => StmtsCompiled::one(IrSpanned {
+                node: StmtCompiled::Yield(IrSpanned {
+                    span,
+                    node: ExprCompiled::Value(FrozenValue::new_none()),
+                }),
+                span,
+            }),
+            StmtP::Yield(Some(e)) => StmtsCompiled::one(IrSpanned {
+                node: StmtCompiled::Yield(self.expr(e)),
+                span,
+            }),
             StmtP::If(cond, then_block) => self.stmt_if(span, cond, *then_block, allow_gc),
             StmtP::IfElse(cond, then_block_else_block) => {
                 let (then_block, else_block) = *then_block_else_block;
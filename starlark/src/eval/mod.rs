@@ -27,20 +27,41 @@ use std::time::Instant;
 
 use dupe::Dupe;
 use gazebo::prelude::*;
+pub use runtime::alloc_args::AllocArgs;
+pub use runtime::alloc_args::AllocNamedArgs;
 pub use runtime::arguments::Arguments;
 pub use runtime::call_stack::CallStack;
+pub use runtime::deadline::eval_with_deadline;
+pub use runtime::eval_profile::EvalProfile;
+pub use runtime::eval_profile::EvalSettings;
 pub use runtime::evaluator::Evaluator;
+pub use runtime::explain::BindingEvent;
+pub use runtime::explain::BindingEventKind;
 pub use runtime::file_loader::FileLoader;
+pub use runtime::file_loader::FsFileLoader;
 pub use runtime::file_loader::ReturnFileLoader;
 pub use runtime::params::ParametersParser;
 pub use runtime::params::ParametersSpec;
 pub use runtime::params::ParametersSpecBuilder;
+pub use runtime::params::ParametersSpecItem;
 pub use runtime::profile::data::ProfileData;
+pub use runtime::profile::data::ProfileDataAccumulator;
 pub use runtime::profile::ProfileMode;
+pub use runtime::provenance::ProvenancePropagation;
+pub use runtime::recording::EvalRecorder;
+pub use runtime::recording::EvalRecording;
+pub use runtime::recording::RecordedFile;
+pub use runtime::recording::RecordedGlobal;
+pub use runtime::recording::RecordedLoad;
+pub use runtime::replay_trace::ReplayDivergence;
+pub use runtime::replay_trace::ReplayTrace;
+pub use runtime::replay_trace::TraceStep;
 
+pub use crate::analysis::StrictMode;
 use crate::collections::symbol_map::Symbol;
 use crate::docs::DocString;
 use crate::environment::Globals;
+use crate::eval::bc::generator::enter_evaluator;
 use crate::eval::compiler::def::DefInfo;
 use crate::eval::compiler::scope::CompilerAstMap;
 use crate::eval::compiler::scope::Scope;
@@ -49,9 +70,11 @@ use crate::eval::compiler::scope::ScopeId;
 use crate::eval::compiler::Compiler;
 use crate::eval::runtime::arguments::ArgNames;
 use crate::eval::runtime::arguments::ArgumentsFull;
+use crate::eval::runtime::evaluator::EvaluatorError;
 use crate::hint::unlikely;
 use crate::syntax::ast::AstModule;
 use crate::syntax::DialectTypes;
+use crate::values::UnpackValue;
 use crate::values::Value;
 
 impl<'v, 'a> Evaluator<'v, 'a> {
@@ -60,12 +83,22 @@ impl<'v, 'a> Evaluator<'v, 'a> {
     pub fn eval_module(&mut self, ast: AstModule, globals: &Globals) -> anyhow::Result<Value<'v>> {
         let start = Instant::now();
 
+        if self.strict_mode != StrictMode::default() {
+            ast.check_strict_mode(&self.strict_mode, globals)?;
+        }
+
+        self.module_env.add_binding_spans(ast.exported_symbols());
+
         let AstModule {
             codemap,
             statement,
             dialect,
         } = ast;
 
+        if let Some(recorder) = self.recorder {
+            recorder.enter_file(&codemap, &dialect, globals);
+        }
+
         let codemap = self
             .module_env
             .frozen_heap()
@@ -98,6 +131,9 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         scope.errors.truncate(1);
         if let Some(e) = scope.errors.pop() {
             // Static errors, reported even if the branch is not hit
+            if let Some(recorder) = self.recorder {
+                recorder.exit_file();
+            }
             return Err(e);
         }
 
@@ -127,37 +163,78 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             self.heap_profile
                 .record_call_enter(Value::new_none(), self.heap());
             self.flame_profile.record_call_enter(Value::new_none());
+            self.call_counts_profile
+                .record_call_enter(Value::new_none());
         }
 
         // Evaluation
+        let guard = enter_evaluator(self);
         let mut compiler = Compiler {
             scope_data,
             locals: Vec::new(),
+            def_module_slots: Vec::new(),
             globals,
             codemap,
             has_before_stmt: self.before_stmt.enabled(),
             bc_profile: self.bc_profile.enabled(),
             eval: self,
             check_types: dialect.enable_types == DialectTypes::Enable,
+            fresh_defaults: dialect.enable_fresh_defaults,
         };
 
         let res = compiler.eval_module(statement, local_names);
+        drop(guard);
 
         // Clean up the world, putting everything back
         self.call_stack.pop();
         if unlikely(self.heap_or_flame_profile) {
             self.heap_profile.record_call_exit(self.heap());
             self.flame_profile.record_call_exit();
+            self.call_counts_profile.record_call_exit();
         }
         self.module_def_info = old_def_info;
 
         self.module_env.add_eval_duration(start.elapsed());
+        if let Some(recorder) = self.recorder {
+            recorder.exit_file();
+        }
 
         // Return the result of evaluation
         res.map_err(|e| e.0)
     }
 
+    /// Call a function stored in a [`Value`] with the given arguments.
+    ///
+    /// This is how native functions should call back into Starlark
+    /// (for example, to invoke a user-supplied callback):
+    /// the call is pushed to the Starlark call stack, so recursion through native functions
+    /// is subject to the same call stack limit as plain Starlark recursion,
+    /// errors have the full call stack attached,
+    /// and the call is attributed to `function` by the heap and flame profilers.
+    pub fn call(
+        &mut self,
+        function: Value<'v>,
+        args: &Arguments<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        if unlikely(self.heap_or_flame_profile) {
+            self.heap_profile.record_call_enter(function, self.heap());
+            self.flame_profile.record_call_enter(function);
+            self.call_counts_profile.record_call_enter(function);
+        }
+        let guard = enter_evaluator(self);
+        let res = function.invoke(args, self);
+        drop(guard);
+        if unlikely(self.heap_or_flame_profile) {
+            self.heap_profile.record_call_exit(self.heap());
+            self.flame_profile.record_call_exit();
+            self.call_counts_profile.record_call_exit();
+        }
+        res
+    }
+
     /// Evaluate a function stored in a [`Value`], passing in `positional` and `named` arguments.
+    ///
+    /// This is a convenience wrapper around [`call`](Evaluator::call).
     pub fn eval_function(
         &mut self,
         function: Value<'v>,
@@ -173,6 +250,42 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             args: None,
             kwargs: None,
         });
-        function.invoke(&params, self)
+        self.call(function, &params)
+    }
+
+    /// Call `function` with arguments converted from Rust values,
+    /// and unpack the result to a Rust type.
+    ///
+    /// ```
+    /// # use starlark::environment::Globals;
+    /// # use starlark::environment::Module;
+    /// # use starlark::eval::Evaluator;
+    /// # use starlark::syntax::AstModule;
+    /// # use starlark::syntax::Dialect;
+    /// let module = Module::new();
+    /// let mut eval = Evaluator::new(&module);
+    /// let ast = AstModule::parse(
+    ///     "f.star",
+    ///     "def f(x, y, z = 0): return x * y + z".to_owned(),
+    ///     &Dialect::Standard,
+    /// )
+    /// .unwrap();
+    /// eval.eval_module(ast, &Globals::standard()).unwrap();
+    /// let f = module.get("f").unwrap();
+    /// let res: i32 = eval.call_function(f, (2, 3), (("z", 1),)).unwrap();
+    /// assert_eq!(7, res);
+    /// ```
+    pub fn call_function<'n, R: UnpackValue<'v>>(
+        &mut self,
+        function: Value<'v>,
+        positional: impl AllocArgs<'v>,
+        named: impl AllocNamedArgs<'v, 'n>,
+    ) -> anyhow::Result<R> {
+        let positional = positional.alloc_args(self.heap());
+        let named = named.alloc_named_args(self.heap());
+        let res = self.eval_function(function, &positional, &named)?;
+        R::unpack_value(res).ok_or_else(|| {
+            EvaluatorError::UnexpectedReturnType(R::expected(), res.get_type().to_owned()).into()
+        })
     }
 }
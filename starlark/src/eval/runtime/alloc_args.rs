@@ -0,0 +1,92 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Conversion of Rust values to function call arguments.
+
+use crate::values::AllocValue;
+use crate::values::Heap;
+use crate::values::Value;
+
+/// Positional arguments of [`Evaluator::call_function`](crate::eval::Evaluator::call_function),
+/// converted from Rust values.
+///
+/// Implemented for tuples of up to six [`AllocValue`] values,
+/// and for slices and vectors of [`Value`].
+pub trait AllocArgs<'v> {
+    /// Allocate the arguments on the heap.
+    fn alloc_args(self, heap: &'v Heap) -> Vec<Value<'v>>;
+}
+
+/// Named arguments of [`Evaluator::call_function`](crate::eval::Evaluator::call_function),
+/// converted from Rust values.
+///
+/// Implemented for tuples of up to six `(&str, impl AllocValue)` pairs,
+/// and for slices and vectors of `(&str, Value)` pairs.
+pub trait AllocNamedArgs<'v, 'n> {
+    /// Allocate the argument values on the heap.
+    fn alloc_named_args(self, heap: &'v Heap) -> Vec<(&'n str, Value<'v>)>;
+}
+
+impl<'v> AllocArgs<'v> for &[Value<'v>] {
+    fn alloc_args(self, _heap: &'v Heap) -> Vec<Value<'v>> {
+        self.to_vec()
+    }
+}
+
+impl<'v> AllocArgs<'v> for Vec<Value<'v>> {
+    fn alloc_args(self, _heap: &'v Heap) -> Vec<Value<'v>> {
+        self
+    }
+}
+
+impl<'v, 'n> AllocNamedArgs<'v, 'n> for &[(&'n str, Value<'v>)] {
+    fn alloc_named_args(self, _heap: &'v Heap) -> Vec<(&'n str, Value<'v>)> {
+        self.to_vec()
+    }
+}
+
+impl<'v, 'n> AllocNamedArgs<'v, 'n> for Vec<(&'n str, Value<'v>)> {
+    fn alloc_named_args(self, _heap: &'v Heap) -> Vec<(&'n str, Value<'v>)> {
+        self
+    }
+}
+
+macro_rules! impl_alloc_args_for_tuple {
+    ($($t:ident $i:tt),*) => {
+        impl<'v, $($t: AllocValue<'v>),*> AllocArgs<'v> for ($($t,)*) {
+            #[allow(unused_variables)]
+            fn alloc_args(self, heap: &'v Heap) -> Vec<Value<'v>> {
+                vec![$(self.$i.alloc_value(heap)),*]
+            }
+        }
+
+        impl<'v, 'n, $($t: AllocValue<'v>),*> AllocNamedArgs<'v, 'n> for ($((&'n str, $t),)*) {
+            #[allow(unused_variables)]
+            fn alloc_named_args(self, heap: &'v Heap) -> Vec<(&'n str, Value<'v>)> {
+                vec![$((self.$i.0, self.$i.1.alloc_value(heap))),*]
+            }
+        }
+    };
+}
+
+impl_alloc_args_for_tuple!();
+impl_alloc_args_for_tuple!(A 0);
+impl_alloc_args_for_tuple!(A 0, B 1);
+impl_alloc_args_for_tuple!(A 0, B 1, C 2);
+impl_alloc_args_for_tuple!(A 0, B 1, C 2, D 3);
+impl_alloc_args_for_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_alloc_args_for_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
@@ -26,6 +26,7 @@ use gazebo::coerce::Coerce;
 use gazebo::prelude::*;
 use thiserror::Error;
 
+use crate::collections::small_value_vec::SmallValueVec;
 use crate::collections::symbol_map::Symbol;
 use crate::collections::Hashed;
 use crate::collections::SmallMap;
@@ -173,6 +174,18 @@ pub(crate) struct ArgumentsFull<'v, 'a, S: ArgSymbol> {
     pub(crate) kwargs: Option<Value<'v>>,
 }
 
+impl<'v, 'a, S: ArgSymbol> ArgumentsFull<'v, 'a, S> {
+    /// All the argument values, with `*args` and `**kwargs` collections not expanded.
+    pub(crate) fn inputs(&self) -> Vec<Value<'v>> {
+        let mut res = Vec::with_capacity(self.pos.len() + self.named.len() + 2);
+        res.extend(self.pos);
+        res.extend(self.named);
+        res.extend(self.args);
+        res.extend(self.kwargs);
+        res
+    }
+}
+
 impl<'v, 'a, S: ArgSymbol> ArgumentsImpl<'v, 'a> for ArgumentsFull<'v, 'a, S> {
     type ArgSymbol = S;
 
@@ -418,14 +431,14 @@ impl<'v, 'a> Arguments<'v, 'a> {
             x: &Arguments<'v, '_>,
             heap: &'v Heap,
         ) -> anyhow::Result<[Value<'v>; N]> {
-            // Very sad that we allocate into a vector, but I expect calling into a small positional argument
-            // with a *args is very rare.
+            // I expect calling into a small positional argument with a *args is very rare,
+            // and in that case there are usually few enough arguments to avoid allocation.
             let xs =
                 x.0.pos
                     .iter()
                     .copied()
                     .chain(x.0.args.unwrap().iterate(heap)?)
-                    .collect::<Vec<_>>();
+                    .collect::<SmallValueVec<_>>();
             xs.as_slice().try_into().map_err(|_| {
                 FunctionError::WrongNumberOfArgs {
                     min: N,
@@ -464,13 +477,18 @@ impl<'v, 'a> Arguments<'v, 'a> {
             x: &Arguments<'v, '_>,
             heap: &'v Heap,
         ) -> anyhow::Result<([Value<'v>; REQUIRED], [Option<Value<'v>>; OPTIONAL])> {
-            // Very sad that we allocate into a vector, but I expect calling into a small positional argument
-            // with a *args is very rare.
+            // I expect calling into a small positional argument with a *args is very rare,
+            // and in that case there are usually few enough arguments to avoid allocation.
             let args = match x.0.args {
                 None => Box::new(None.into_iter()),
                 Some(args) => args.iterate(heap)?,
             };
-            let xs = x.0.pos.iter().copied().chain(args).collect::<Vec<_>>();
+            let xs =
+                x.0.pos
+                    .iter()
+                    .copied()
+                    .chain(args)
+                    .collect::<SmallValueVec<_>>();
             if xs.len() >= REQUIRED && xs.len() <= REQUIRED + OPTIONAL {
                 let required = xs[0..REQUIRED].try_into().unwrap();
                 let mut optional = [None; OPTIONAL];
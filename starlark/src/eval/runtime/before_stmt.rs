@@ -17,6 +17,8 @@
 
 //! Configuration of `BeforeStmt` instrumentation of bytecode.
 
+use std::sync::atomic::AtomicBool;
+
 use crate::codemap::FileSpanRef;
 use crate::eval::Evaluator;
 
@@ -29,10 +31,16 @@ pub(crate) struct BeforeStmt<'a> {
     /// even if no `before_stmt` functions are registered.
     /// This is needed when compiling dependencies of a file to be profiled.
     pub(crate) instrument: bool,
+    /// When set to `true`, evaluation fails before the next statement.
+    pub(crate) cancelled: Option<&'a AtomicBool>,
+    /// Functions to run when an error is raised, before the stack unwinds.
+    /// They don't need instrumentation.
+    pub(crate) on_error:
+        Vec<&'a dyn for<'v> Fn(&anyhow::Error, FileSpanRef, &mut Evaluator<'v, 'a>)>,
 }
 
 impl<'a> BeforeStmt<'a> {
     pub(crate) fn enabled(&self) -> bool {
-        self.instrument || !self.before_stmt.is_empty()
+        self.instrument || !self.before_stmt.is_empty() || self.cancelled.is_some()
     }
 }
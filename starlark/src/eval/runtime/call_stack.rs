@@ -52,7 +52,7 @@ struct CheapFrame<'v> {
 
 impl CheapFrame<'_> {
     fn location(&self) -> Option<FileSpan> {
-        self.span.map(|span| span.span.to_file_span())
+        self.span.map(|span| span.span.to_original_file_span())
     }
 
     fn extend_frames(&self, frames: &mut Vec<Frame>) {
@@ -187,9 +187,10 @@ impl<'v> CheapCallStack<'v> {
         CallStack { frames }
     }
 
-    /// List the entries on the stack as values
+    /// List the entries on the stack as values, empty outside evaluation.
     pub(crate) fn to_function_values(&self) -> Vec<Value<'v>> {
-        self.stack[1..self.count].map(|x| x.function)
+        let stack = self.stack.get(1..self.count).unwrap_or_default();
+        stack.map(|x| x.function)
     }
 }
 
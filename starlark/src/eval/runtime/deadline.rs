@@ -0,0 +1,120 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Evaluation with a timeout.
+
+use std::sync::atomic;
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::environment::Module;
+use crate::eval::Evaluator;
+
+#[derive(Debug, thiserror::Error)]
+enum DeadlineError {
+    #[error("Evaluation timed out after {0:?}")]
+    Timeout(Duration),
+}
+
+/// Evaluate `f` with an [`Evaluator`] for `module`, failing if it takes longer than `timeout`.
+///
+/// Evaluation runs on the current thread. A scoped watchdog thread sets the
+/// [cancellation flag](Evaluator::set_cancellation_flag) when the timeout expires,
+/// and evaluation then fails with an error before the next statement.
+/// The watchdog thread is always joined before this function returns,
+/// and the evaluation is unwound normally, so nothing keeps using the heap of `module`
+/// after the timeout. The module may contain partially evaluated state
+/// and usually should be dropped rather than frozen.
+///
+/// The same limitations as for [`Evaluator::set_cancellation_flag`] apply:
+/// functions from already frozen modules and native functions are not interrupted,
+/// so this function can return later than `timeout`.
+pub fn eval_with_deadline<'v, R>(
+    module: &'v Module,
+    timeout: Duration,
+    f: impl for<'a> FnOnce(&mut Evaluator<'v, 'a>) -> anyhow::Result<R>,
+) -> anyhow::Result<R> {
+    let cancelled = AtomicBool::new(false);
+    let (done_sender, done_receiver) = mpsc::channel::<()>();
+    thread::scope(|scope| {
+        let cancelled = &cancelled;
+        scope.spawn(move || {
+            // Sender is dropped when evaluation finishes.
+            if let Err(mpsc::RecvTimeoutError::Timeout) = done_receiver.recv_timeout(timeout) {
+                cancelled.store(true, atomic::Ordering::Relaxed);
+            }
+        });
+
+        let res = {
+            let mut eval = Evaluator::new(module);
+            eval.set_cancellation_flag(cancelled);
+            f(&mut eval)
+        };
+        drop(done_sender);
+
+        match res {
+            Err(_) if cancelled.load(atomic::Ordering::Relaxed) => {
+                Err(DeadlineError::Timeout(timeout).into())
+            }
+            res => res,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::eval::eval_with_deadline;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn eval(program: &str, timeout: Duration) -> anyhow::Result<String> {
+        let module = Module::new();
+        eval_with_deadline(&module, timeout, |eval| {
+            let ast = AstModule::parse("x.star", program.to_owned(), &Dialect::Standard)?;
+            Ok(eval.eval_module(ast, &Globals::standard())?.to_repr())
+        })
+    }
+
+    #[test]
+    fn test_eval_with_deadline_completes() {
+        assert_eq!("3", eval("1 + 2", Duration::from_secs(100)).unwrap());
+    }
+
+    #[test]
+    fn test_eval_with_deadline_times_out() {
+        let start = Instant::now();
+        let err = eval(
+            r"
+def f():
+    for i in range(1000000000):
+        x = i
+f()
+",
+            Duration::from_millis(10),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+        assert!(start.elapsed() < Duration::from_secs(100));
+    }
+}
@@ -0,0 +1,222 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Presets of evaluator settings, for garbage collection and optimizations.
+
+use dupe::Dupe;
+
+use crate::eval::runtime::evaluator::GC_THRESHOLD;
+
+/// Preset of [`EvalSettings`], applied with
+/// [`Evaluator::set_eval_profile`](crate::eval::Evaluator::set_eval_profile).
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq, Hash)]
+pub enum EvalProfile {
+    /// Don't collect garbage and don't optimize function bodies after compilation,
+    /// so values stay alive to be inspected, every function call is seen by breakpoints
+    /// and profiles, and frozen functions run the same bytecode as before the freeze.
+    Debug,
+    /// The settings of a new [`Evaluator`](crate::eval::Evaluator).
+    Default,
+    /// Collect garbage less often, using more memory to spend less time in collections.
+    Optimized,
+    /// Collect garbage often, to keep the heap small,
+    /// and don't copy bodies of inlined functions to their call sites.
+    MinMemory,
+}
+
+/// Settings of an [`Evaluator`](crate::eval::Evaluator), usually obtained from an
+/// [`EvalProfile`] and then adjusted:
+///
+/// ```
+/// use starlark::environment::Module;
+/// use starlark::eval::EvalProfile;
+/// use starlark::eval::Evaluator;
+///
+/// let module = Module::new();
+/// let mut eval = Evaluator::new(&module);
+/// let mut settings = EvalProfile::MinMemory.settings();
+/// settings.inline_defs = false;
+/// eval.set_eval_settings(settings).unwrap();
+/// assert_eq!(settings, eval.eval_settings());
+/// ```
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq, Hash)]
+pub struct EvalSettings {
+    /// Collect garbage between the top-level statements of a module.
+    /// Garbage collection cannot be enabled again once disabled,
+    /// see [`Evaluator::disable_gc`](crate::eval::Evaluator::disable_gc),
+    /// so applying settings with `gc` set then fails.
+    pub gc: bool,
+    /// Number of bytes allocated after a garbage collection before the next one.
+    pub gc_threshold: usize,
+    /// Inline calls to small functions defined while this is set, e.g. `def f(x): return x + 1`.
+    /// Inlined calls are faster, but are not seen by statement hooks and profiles.
+    pub inline_defs: bool,
+    /// Optimize the bodies of functions defined while this is set again when their module
+    /// is frozen, e.g. replacing reads of frozen module variables with their values.
+    pub optimize_on_freeze: bool,
+}
+
+impl EvalProfile {
+    /// All the profiles.
+    pub fn all() -> &'static [EvalProfile] {
+        &[
+            EvalProfile::Debug,
+            EvalProfile::Default,
+            EvalProfile::Optimized,
+            EvalProfile::MinMemory,
+        ]
+    }
+
+    /// The settings of this profile.
+    pub fn settings(self) -> EvalSettings {
+        match self {
+            EvalProfile::Debug => EvalSettings {
+                gc: false,
+                gc_threshold: GC_THRESHOLD,
+                inline_defs: false,
+                optimize_on_freeze: false,
+            },
+            EvalProfile::Default => EvalSettings {
+                gc: true,
+                gc_threshold: GC_THRESHOLD,
+                inline_defs: true,
+                optimize_on_freeze: true,
+            },
+            EvalProfile::Optimized => EvalSettings {
+                gc: true,
+                gc_threshold: GC_THRESHOLD * 10,
+                inline_defs: true,
+                optimize_on_freeze: true,
+            },
+            EvalProfile::MinMemory => EvalSettings {
+                gc: true,
+                gc_threshold: GC_THRESHOLD / 10,
+                inline_defs: false,
+                optimize_on_freeze: true,
+            },
+        }
+    }
+}
+
+impl Default for EvalSettings {
+    fn default() -> Self {
+        EvalProfile::Default.settings()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::eval::compiler::def::Def;
+    use crate::eval::compiler::def::FrozenDef;
+    use crate::eval::EvalProfile;
+    use crate::eval::EvalSettings;
+    use crate::eval::Evaluator;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+    use crate::values::ValueLike;
+
+    fn eval_with(profile: EvalProfile, program: &str) -> (Module, EvalSettings) {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.set_eval_profile(profile).unwrap();
+        let ast = AstModule::parse("x.star", program.to_owned(), &Dialect::Extended).unwrap();
+        eval.eval_module(ast, &Globals::standard()).unwrap();
+        let settings = eval.eval_settings();
+        drop(eval);
+        (module, settings)
+    }
+
+    #[test]
+    fn test_default_settings() {
+        let module = Module::new();
+        let eval = Evaluator::new(&module);
+        assert_eq!(EvalSettings::default(), eval.eval_settings());
+        for profile in EvalProfile::all() {
+            let settings = profile.settings();
+            assert_eq!(settings, eval_with(*profile, "").1);
+        }
+    }
+
+    #[test]
+    fn test_inline_defs() {
+        for profile in EvalProfile::all() {
+            let (module, _) = eval_with(*profile, "def f(x): return x + 1");
+            let f = module.get("f").unwrap();
+            let inlined = f
+                .downcast_ref::<Def>()
+                .unwrap()
+                .def_info
+                .inline_def_body
+                .is_some();
+            assert_eq!(profile.settings().inline_defs, inlined, "{:?}", profile);
+        }
+    }
+
+    #[test]
+    fn test_optimize_on_freeze() {
+        for profile in EvalProfile::all() {
+            let (module, _) = eval_with(*profile, "N = [1]\ndef f(): return N");
+            let module = module.freeze().unwrap();
+            let f = module.get("f").unwrap();
+            let bc = f.downcast::<FrozenDef>().unwrap().bc().dump_debug();
+            assert_eq!(
+                profile.settings().optimize_on_freeze,
+                !bc.contains("LoadModule"),
+                "{:?}: {}",
+                profile,
+                bc
+            );
+        }
+    }
+
+    #[test]
+    fn test_gc_threshold() {
+        let mut program = String::new();
+        for _ in 0..100 {
+            writeln!(program, "x = [str(i) for i in range(100)]").unwrap();
+        }
+        let peak = |profile| {
+            let (module, _) = eval_with(profile, &program);
+            module.heap().peak_allocated_bytes()
+        };
+        let debug = peak(EvalProfile::Debug);
+        let default = peak(EvalProfile::Default);
+        let min_memory = peak(EvalProfile::MinMemory);
+        assert!(min_memory < default, "{} < {}", min_memory, default);
+        assert!(default < debug, "{} < {}", default, debug);
+    }
+
+    #[test]
+    fn test_gc_cannot_be_enabled_again() {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.set_eval_profile(EvalProfile::Debug).unwrap();
+        let settings = eval.eval_settings();
+        let err = eval.set_eval_profile(EvalProfile::Default).unwrap_err();
+        assert!(
+            err.to_string().contains("cannot be enabled again"),
+            "{}",
+            err
+        );
+        assert_eq!(settings, eval.eval_settings());
+        eval.set_eval_profile(EvalProfile::Debug).unwrap();
+    }
+}
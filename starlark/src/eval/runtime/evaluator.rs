@@ -19,6 +19,7 @@ use std::collections::HashSet;
 use std::mem;
 use std::mem::MaybeUninit;
 use std::path::Path;
+use std::sync::atomic::AtomicBool;
 
 use dupe::Dupe;
 use gazebo::any::AnyLifetime;
@@ -30,10 +31,12 @@ use crate::codemap::FileSpanRef;
 use crate::codemap::ResolvedFileSpan;
 use crate::collections::alloca::Alloca;
 use crate::collections::string_pool::StringPool;
+use crate::collections::Hashed;
 use crate::environment::slots::ModuleSlotId;
 use crate::environment::EnvironmentError;
 use crate::environment::FrozenModuleRef;
 use crate::environment::Module;
+use crate::environment::ModuleBinding;
 use crate::errors::Diagnostic;
 use crate::eval::bc::frame::BcFramePtr;
 use crate::eval::compiler::def::CopySlotFromParent;
@@ -42,8 +45,14 @@ use crate::eval::compiler::def::DefInfo;
 use crate::eval::compiler::def::FrozenDef;
 use crate::eval::runtime::before_stmt::BeforeStmt;
 use crate::eval::runtime::call_stack::CheapCallStack;
+use crate::eval::runtime::eval_profile::EvalProfile;
+use crate::eval::runtime::eval_profile::EvalSettings;
+use crate::eval::runtime::explain::BindingEvent;
+use crate::eval::runtime::explain::BindingEventKind;
+use crate::eval::runtime::explain::Explain;
 use crate::eval::runtime::frame_span::FrameSpan;
 use crate::eval::runtime::inlined_frame::InlinedFrames;
+use crate::eval::runtime::profile::call_counts::CallCountsProfile;
 use crate::eval::runtime::profile::bc::BcProfile;
 use crate::eval::runtime::profile::data::ProfileData;
 use crate::eval::runtime::profile::heap::HeapProfile;
@@ -54,10 +63,18 @@ use crate::eval::runtime::profile::stmt::StmtProfile;
 use crate::eval::runtime::profile::time_flame::FlameProfile;
 use crate::eval::runtime::profile::typecheck::TypecheckProfile;
 use crate::eval::runtime::profile::ProfileMode;
+use crate::eval::runtime::provenance::Provenance;
+use crate::eval::runtime::provenance::ProvenanceError;
+use crate::eval::runtime::provenance::ProvenanceOp;
+use crate::eval::runtime::provenance::ProvenancePropagation;
 use crate::eval::runtime::slots::LocalCapturedSlotId;
 use crate::eval::runtime::slots::LocalSlotId;
+use crate::hint::unlikely;
+use crate::eval::Arguments;
 use crate::eval::CallStack;
+use crate::eval::EvalRecorder;
 use crate::eval::FileLoader;
+use crate::eval::StrictMode;
 use crate::stdlib::breakpoint::BreakpointConsole;
 use crate::stdlib::breakpoint::RealBreakpointConsole;
 use crate::stdlib::extra::PrintHandler;
@@ -100,11 +117,24 @@ pub(crate) enum EvaluatorError {
     CoverageNotImplemented,
     #[error("Coverage not enabled")]
     CoverageNotEnabled,
+    #[error("Type of function result mismatch, expected `{0}`, actual `{1}`")]
+    UnexpectedReturnType(String, String),
+    #[error("Evaluation cancelled")]
+    Cancelled,
+    #[error("Loop fuel exhausted: `while` loops executed more than {0} iterations")]
+    LoopFuelExhausted(u64),
+    #[error("Function `{0}` requires capability `{1}`, which this evaluation is not granted\n{2}")]
+    CapabilityDenied(String, String, CallStack),
+    #[error("Garbage collection cannot be enabled again once disabled")]
+    GcAlreadyDisabled,
 }
 
 /// Number of bytes to allocate between GC's.
 pub(crate) const GC_THRESHOLD: usize = 100000;
 
+/// Default number of `while` loop iterations an evaluator may execute.
+const DEFAULT_LOOP_FUEL: u64 = 1_000_000;
+
 /// Holds everything about an ongoing evaluation (local variables, globals, module resolution etc).
 pub struct Evaluator<'v, 'a> {
     // The module that is being used for this evaluation
@@ -117,6 +147,8 @@ pub struct Evaluator<'v, 'a> {
     pub(crate) current_frame: BcFramePtr<'v>,
     // How we deal with a `load` function.
     pub(crate) loader: Option<&'a dyn FileLoader>,
+    // Records the evaluated files and their loads, usually `None`.
+    pub(crate) recorder: Option<&'a EvalRecorder>,
     // `DefInfo` of currently executed module.
     // `DefInfo` of currently execution function can be obtained from call stack.
     pub(crate) module_def_info: FrozenRef<'static, DefInfo>,
@@ -124,7 +156,10 @@ pub struct Evaluator<'v, 'a> {
     pub(crate) heap_profile: HeapProfile,
     // Should we enable flame profiling or not
     pub(crate) flame_profile: FlameProfile<'v>,
-    // Is either heap or flame profiling enabled, or instrumentation for these profiles enabled.
+    // Call counts profile, if enabled.
+    pub(crate) call_counts_profile: CallCountsProfile<'v>,
+    // Is either heap, flame or call counts profiling enabled,
+    // or instrumentation for these profiles enabled.
     pub(crate) heap_or_flame_profile: bool,
     // Is GC disabled for some reason
     pub(crate) disable_gc: bool,
@@ -133,6 +168,23 @@ pub struct Evaluator<'v, 'a> {
     pub(crate) verbose_gc: bool,
     // Size of the heap when we should next perform a GC.
     pub(crate) next_gc_level: usize,
+    // Number of bytes to allocate between GC's.
+    pub(crate) gc_threshold: usize,
+    // Are functions defined from now on inlined at call sites.
+    pub(crate) inline_defs: bool,
+    // Are bodies of functions defined from now on optimized again on module freeze.
+    pub(crate) optimize_on_freeze: bool,
+    // Remaining number of `while` loop iterations, and the initial number for the error message.
+    loop_fuel: u64,
+    loop_fuel_limit: u64,
+    // Provenance tags of values, `None` unless provenance tracking is enabled.
+    pub(crate) provenance: Option<Box<Provenance<'v>>>,
+    // Histories of module bindings, `None` unless explain mode is enabled.
+    explain: Option<Box<Explain>>,
+    // Capabilities granted to this evaluation, `None` if all capabilities are granted.
+    capabilities: Option<HashSet<String>>,
+    // Lint findings which fail the evaluation of a module.
+    pub(crate) strict_mode: StrictMode,
     // Profiling or instrumentation enabled.
     pub(crate) profile_or_instrumentation_mode: ProfileOrInstrumentationMode,
     // Extra functions to run on each statement, usually empty
@@ -166,6 +218,8 @@ unsafe impl<'v> Trace<'v> for Evaluator<'v, '_> {
         self.current_frame.trace(tracer);
         self.call_stack.trace(tracer);
         self.flame_profile.trace(tracer);
+        self.call_counts_profile.trace(tracer);
+        self.provenance.trace(tracer);
     }
 }
 
@@ -181,8 +235,18 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             module_variables: None,
             current_frame: BcFramePtr::null(),
             loader: None,
+            recorder: None,
             extra: None,
             next_gc_level: GC_THRESHOLD,
+            gc_threshold: GC_THRESHOLD,
+            inline_defs: true,
+            optimize_on_freeze: true,
+            loop_fuel: DEFAULT_LOOP_FUEL,
+            loop_fuel_limit: DEFAULT_LOOP_FUEL,
+            provenance: None,
+            explain: None,
+            capabilities: None,
+            strict_mode: StrictMode::default(),
             disable_gc: false,
             alloca: Alloca::new(),
             profile_or_instrumentation_mode: ProfileOrInstrumentationMode::None,
@@ -191,6 +255,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             bc_profile: BcProfile::new(),
             typecheck_profile: TypecheckProfile::default(),
             flame_profile: FlameProfile::new(),
+            call_counts_profile: CallCountsProfile::default(),
             heap_or_flame_profile: false,
             before_stmt: BeforeStmt::default(),
             module_def_info: DefInfo::empty(), // Will be replaced before it is used
@@ -213,6 +278,42 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.verbose_gc = true;
     }
 
+    /// Apply the settings of `profile`, see [`set_eval_settings`](Evaluator::set_eval_settings).
+    pub fn set_eval_profile(&mut self, profile: EvalProfile) -> anyhow::Result<()> {
+        self.set_eval_settings(profile.settings())
+    }
+
+    /// Apply `settings`, usually before evaluating anything. Settings which affect compilation,
+    /// like [`inline_defs`](EvalSettings::inline_defs) and
+    /// [`optimize_on_freeze`](EvalSettings::optimize_on_freeze),
+    /// only affect code compiled afterwards.
+    ///
+    /// Fails without changing anything if the settings enable garbage collection
+    /// after it was disabled.
+    pub fn set_eval_settings(&mut self, settings: EvalSettings) -> anyhow::Result<()> {
+        if settings.gc && self.disable_gc {
+            return Err(EvaluatorError::GcAlreadyDisabled.into());
+        }
+        if !settings.gc {
+            self.disable_gc();
+        }
+        self.gc_threshold = settings.gc_threshold;
+        self.next_gc_level = self.heap().allocated_bytes() + settings.gc_threshold;
+        self.inline_defs = settings.inline_defs;
+        self.optimize_on_freeze = settings.optimize_on_freeze;
+        Ok(())
+    }
+
+    /// The current settings of this evaluator.
+    pub fn eval_settings(&self) -> EvalSettings {
+        EvalSettings {
+            gc: !self.disable_gc,
+            gc_threshold: self.gc_threshold,
+            inline_defs: self.inline_defs,
+            optimize_on_freeze: self.optimize_on_freeze,
+        }
+    }
+
     /// Set the [`FileLoader`] used to resolve `load()` statements.
     /// A list of all load statements can be obtained through
     /// [`AstModule::loads`](crate::syntax::AstModule::loads).
@@ -220,6 +321,12 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.loader = Some(loader);
     }
 
+    /// Record the modules evaluated by this evaluator and their `load()` statements
+    /// into `recorder`, see [`EvalRecorder`].
+    pub fn set_recorder(&mut self, recorder: &'a EvalRecorder) {
+        self.recorder = Some(recorder);
+    }
+
     /// Enable profiling, allowing [`Evaluator::write_profile`] to be used.
     /// Profilers add overhead, and while some profilers can be used together,
     /// it's better to run at most one profiler at a time.
@@ -268,6 +375,10 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             ProfileMode::Typecheck => {
                 self.typecheck_profile.enabled = true;
             }
+            ProfileMode::CallCounts => {
+                self.call_counts_profile.enable();
+                self.heap_or_flame_profile = true;
+            }
         }
         Ok(())
     }
@@ -295,7 +406,8 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             | ProfileMode::HeapSummaryRetained
             | ProfileMode::HeapFlameAllocated
             | ProfileMode::HeapFlameRetained
-            | ProfileMode::TimeFlame => {
+            | ProfileMode::TimeFlame
+            | ProfileMode::CallCounts => {
                 self.heap_or_flame_profile = true;
             }
             ProfileMode::Typecheck => {}
@@ -341,6 +453,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             ProfileMode::BytecodePairs => self.bc_profile.gen_bc_pairs_profile(),
             ProfileMode::TimeFlame => self.flame_profile.gen(),
             ProfileMode::Typecheck => self.typecheck_profile.gen(),
+            ProfileMode::CallCounts => self.call_counts_profile.gen(),
         }
     }
 
@@ -386,14 +499,243 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.before_stmt.before_stmt.push(f)
     }
 
-    /// This function is used by DAP, and it is not public API.
-    // TODO(nga): pull DAP into the crate, and hide this function.
-    #[doc(hidden)]
-    pub fn before_stmt_for_dap(
+    /// Run `f` when an error is raised by an expression, with the location of the expression,
+    /// before the error is propagated to the callers.
+    pub(crate) fn on_error(
         &mut self,
-        f: &'a dyn for<'v1> Fn(FileSpanRef, &mut Evaluator<'v1, 'a>),
+        f: &'a dyn for<'v1> Fn(&anyhow::Error, FileSpanRef, &mut Evaluator<'v1, 'a>),
+    ) {
+        self.before_stmt.on_error.push(f)
+    }
+
+    /// Set a flag which cancels the evaluation: when the flag is set to `true`
+    /// (possibly from another thread), evaluation fails before the next statement.
+    ///
+    /// Only code compiled by this evaluator after this call is interrupted:
+    /// functions defined in already frozen modules and native functions are not.
+    /// See [`eval_with_deadline`](crate::eval::eval_with_deadline) for a convenient wrapper.
+    pub fn set_cancellation_flag(&mut self, cancelled: &'a AtomicBool) {
+        self.before_stmt.cancelled = Some(cancelled);
+    }
+
+    /// Set the number of `while` loop iterations this evaluator may execute,
+    /// 1,000,000 by default. Each evaluation of a `while` loop condition consumes one unit
+    /// of fuel, and evaluation fails when no fuel is left.
+    ///
+    /// Fuel is shared by all the `while` loops executed by this evaluator,
+    /// including loops in functions from frozen modules.
+    pub fn set_loop_fuel(&mut self, fuel: u64) {
+        self.loop_fuel = fuel;
+        self.loop_fuel_limit = fuel;
+    }
+
+    /// Remaining number of `while` loop iterations this evaluator may execute.
+    pub fn loop_fuel(&self) -> u64 {
+        self.loop_fuel
+    }
+
+    /// Grant only these capabilities to this evaluation: calls of native functions which
+    /// require other capabilities fail. Functions are restricted with
+    /// [`require_capability`](crate::environment::GlobalsBuilder::require_capability).
+    /// By default all capabilities are granted.
+    ///
+    /// Capabilities are checked when a function is called, so functions of trusted modules
+    /// called by this evaluation are restricted as well.
+    pub fn set_capabilities<S: Into<String>>(&mut self, capabilities: impl IntoIterator<Item = S>) {
+        self.capabilities = Some(capabilities.into_iter().map(Into::into).collect());
+    }
+
+    /// The bindings assigned so far in the module being evaluated,
+    /// see [`Module::inspect`]. Can be called from a native function.
+    pub fn inspect_module(&self) -> Vec<ModuleBinding<'v>> {
+        self.module_env.inspect()
+    }
+
+    /// Make lint findings evaluation errors, e.g. `load()` of unused symbols,
+    /// see [`StrictMode`]. Checked when a module is evaluated with
+    /// [`eval_module`](Evaluator::eval_module).
+    pub fn set_strict_mode(&mut self, mode: StrictMode) {
+        self.strict_mode = mode;
+    }
+
+    /// Check that this evaluation may call the native function `name` requiring `capability`.
+    #[cold]
+    pub(crate) fn check_capability(&self, name: &str, capability: &str) -> anyhow::Result<()> {
+        match &self.capabilities {
+            Some(capabilities) if !capabilities.contains(capability) => {
+                Err(EvaluatorError::CapabilityDenied(
+                    name.to_owned(),
+                    capability.to_owned(),
+                    self.call_stack(),
+                )
+                .into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Enable provenance tracking: values can be tagged with [`tag_value`](Evaluator::tag_value),
+    /// and the results of operations on tagged values get their tags as configured by
+    /// `propagation`. Native functions can then check the tags of their arguments with
+    /// [`value_tags`](Evaluator::value_tags), e.g. to reject untrusted inputs.
+    ///
+    /// Tags are lost when a module is frozen. Tagged values are kept alive by the evaluator.
+    pub fn enable_provenance(&mut self, propagation: ProvenancePropagation) {
+        self.provenance = Some(Box::new(Provenance::new(propagation)));
+    }
+
+    /// Tag a value allocated during this evaluation, e.g. a string read from an untrusted source.
+    /// Fails if provenance tracking is not enabled, or for values which are shared,
+    /// like `None`, small integers or frozen values.
+    pub fn tag_value(&mut self, value: Value<'v>, tag: &str) -> anyhow::Result<()> {
+        match &mut self.provenance {
+            Some(provenance) => provenance.tag(value, tag),
+            None => Err(ProvenanceError::NotEnabled.into()),
+        }
+    }
+
+    /// Sorted provenance tags of a value, empty if provenance tracking is not enabled.
+    pub fn value_tags(&self, value: Value<'v>) -> Vec<String> {
+        match &self.provenance {
+            Some(provenance) => provenance.tags(value),
+            None => Vec::new(),
+        }
+    }
+
+    /// Propagate provenance tags from `inputs` to `result` of an operation.
+    #[inline]
+    pub(crate) fn propagate_provenance(
+        &mut self,
+        op: ProvenanceOp,
+        inputs: &[Value<'v>],
+        result: Value<'v>,
+    ) {
+        if let Some(provenance) = &mut self.provenance {
+            provenance.propagate(op, inputs, result);
+        }
+    }
+
+    /// Propagate provenance tags from the arguments of a native call to its result.
+    #[inline]
+    pub(crate) fn propagate_provenance_to_call(
+        &mut self,
+        this: Option<Value<'v>>,
+        args: &Arguments<'v, '_>,
+        result: Value<'v>,
+    ) {
+        if let Some(provenance) = &mut self.provenance {
+            provenance.propagate_to_call(this, args, result);
+        }
+    }
+
+    /// Enable explain mode: record the assignments and in-place mutations of the module
+    /// bindings `names`, with their locations and call stacks. After evaluation,
+    /// [`binding_history`](Evaluator::binding_history) tells how a binding got its final value.
+    ///
+    /// Mutations are recorded when a method called on the value of a binding
+    /// or an index assignment changes the `repr` of the value,
+    /// so explain mode is slow and meant for debugging.
+    pub fn explain_bindings<S: Into<String>>(&mut self, names: impl IntoIterator<Item = S>) {
+        self.explain = Some(Box::new(Explain::new(names.into_iter().map(Into::into))));
+    }
+
+    /// Assignments and mutations of a module binding in evaluation order,
+    /// `None` if the binding was not selected with
+    /// [`explain_bindings`](Evaluator::explain_bindings).
+    pub fn binding_history(&self, name: &str) -> Option<&[BindingEvent]> {
+        self.explain.as_ref()?.history(name)
+    }
+
+    /// Record an assignment of a module variable in explain mode.
+    #[inline]
+    pub(crate) fn explain_assign(
+        &mut self,
+        slot: ModuleSlotId,
+        value: Value<'v>,
+        span: impl FnOnce() -> FileSpan,
     ) {
-        self.before_stmt(f);
+        if unlikely(self.explain.is_some()) {
+            self.explain_assign_slow(slot, value, span());
+        }
+    }
+
+    #[cold]
+    fn explain_assign_slow(&mut self, slot: ModuleSlotId, value: Value<'v>, span: FileSpan) {
+        let names = self.explained_names(|s, _| s == slot);
+        self.explain_record(names, BindingEventKind::Assign, value, Some(span));
+    }
+
+    /// Record an in-place mutation of `this` in explain mode,
+    /// if it is the value of a selected binding.
+    #[inline]
+    pub(crate) fn explain_mutation(
+        &mut self,
+        this: Value<'v>,
+        kind: impl FnOnce() -> BindingEventKind,
+        span: impl FnOnce(&Self) -> Option<FileSpan>,
+    ) {
+        if unlikely(self.explain.is_some()) {
+            let names = self.explained_names(|_, v| v.is_some_and(|v| v.ptr_eq(this)));
+            if !names.is_empty() {
+                let span = span(self);
+                self.explain_record(names, kind(), this, span);
+            }
+        }
+    }
+
+    /// Names of the selected bindings matching the predicate on their slot and value.
+    #[cold]
+    fn explained_names(
+        &self,
+        pred: impl Fn(ModuleSlotId, Option<Value<'v>>) -> bool,
+    ) -> Vec<String> {
+        let explain = match &self.explain {
+            Some(explain) => explain,
+            None => return Vec::new(),
+        };
+        explain
+            .names()
+            .filter(
+                |name| match self.module_env.names().get_name(Hashed::new(name)) {
+                    Some((slot, _)) => pred(slot, self.module_env.slots().get_slot(slot)),
+                    None => false,
+                },
+            )
+            .map(|name| name.to_owned())
+            .collect()
+    }
+
+    #[cold]
+    fn explain_record(
+        &mut self,
+        names: Vec<String>,
+        kind: BindingEventKind,
+        value: Value<'v>,
+        span: Option<FileSpan>,
+    ) {
+        let event = BindingEvent {
+            kind,
+            span,
+            call_stack: self.call_stack(),
+            value: value.to_repr(),
+        };
+        if let Some(explain) = &mut self.explain {
+            for name in names {
+                explain.record(&name, event.clone());
+            }
+        }
+    }
+
+    /// Consume fuel for one `while` loop iteration.
+    #[inline]
+    pub(crate) fn consume_loop_fuel(&mut self) -> anyhow::Result<()> {
+        match self.loop_fuel.checked_sub(1) {
+            Some(fuel) => {
+                self.loop_fuel = fuel;
+                Ok(())
+            }
+            None => Err(EvaluatorError::LoopFuelExhausted(self.loop_fuel_limit).into()),
+        }
     }
 
     /// Set the handler invoked when `print` function is used.
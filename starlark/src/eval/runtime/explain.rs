@@ -0,0 +1,180 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Explain mode: the history of assignments and mutations of selected module bindings,
+//! to find out why a binding has its final value.
+
+use std::fmt;
+use std::fmt::Display;
+
+use starlark_map::small_map::SmallMap;
+
+use crate::codemap::FileSpan;
+use crate::eval::CallStack;
+
+/// Kind of [`BindingEvent`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindingEventKind {
+    /// The binding was assigned, e.g. with `x = ...`, `x += ...` or `load()`.
+    Assign,
+    /// The value of the binding was changed in place by a method, e.g. `x.append(...)`.
+    MethodCall(String),
+    /// The value of the binding was changed in place by `x[...] = ...`.
+    SetIndex,
+}
+
+/// An assignment or a mutation of a module binding recorded in explain mode,
+/// see [`Evaluator::explain_bindings`](crate::eval::Evaluator::explain_bindings).
+#[derive(Debug, Clone)]
+pub struct BindingEvent {
+    /// What happened to the binding.
+    pub kind: BindingEventKind,
+    /// Location of the assignment or the mutation, if known.
+    pub span: Option<FileSpan>,
+    /// Call stack of the assignment or the mutation, empty at the module top level.
+    pub call_stack: CallStack,
+    /// `repr` of the value of the binding after the event.
+    pub value: String,
+}
+
+impl Display for BindingEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.span {
+            Some(span) => write!(f, "{}: ", span)?,
+            None => write!(f, "<unknown>: ")?,
+        }
+        match &self.kind {
+            BindingEventKind::Assign => write!(f, "assigned")?,
+            BindingEventKind::MethodCall(method) => write!(f, "mutated by `{}`", method)?,
+            BindingEventKind::SetIndex => write!(f, "mutated by index assignment")?,
+        }
+        write!(f, ", value is now {}", self.value)?;
+        if !self.call_stack.is_empty() {
+            write!(f, "\n{}", self.call_stack)?;
+        }
+        Ok(())
+    }
+}
+
+/// Histories of the bindings selected for explain mode.
+#[derive(Debug, Default)]
+pub(crate) struct Explain {
+    histories: SmallMap<String, Vec<BindingEvent>>,
+}
+
+impl Explain {
+    pub(crate) fn new(names: impl IntoIterator<Item = String>) -> Explain {
+        Explain {
+            histories: names.into_iter().map(|name| (name, Vec::new())).collect(),
+        }
+    }
+
+    /// Names of the selected bindings.
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.histories.keys().map(|name| name.as_str())
+    }
+
+    /// Events of a binding in evaluation order, `None` if the binding is not selected.
+    pub(crate) fn history(&self, name: &str) -> Option<&[BindingEvent]> {
+        self.histories.get(name).map(|events| events.as_slice())
+    }
+
+    /// Record an event, unless it is a mutation which did not change the value.
+    pub(crate) fn record(&mut self, name: &str, event: BindingEvent) {
+        if let Some(events) = self.histories.get_mut(name) {
+            let unchanged = event.kind != BindingEventKind::Assign
+                && events.last().map(|last| last.value.as_str()) == Some(event.value.as_str());
+            if !unchanged {
+                events.push(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::environment::GlobalsBuilder;
+    use crate::environment::Module;
+    use crate::eval::BindingEventKind;
+    use crate::eval::Evaluator;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    #[test]
+    fn test_explain_bindings() {
+        let program = r#"
+deps = ["a"]
+other = []
+
+def add(x):
+    deps.append(x)
+
+add("b")
+deps += ["c"]
+deps.index("a")
+other.append(1)
+deps.remove("a")
+env = {}
+env["k"] = deps
+"#;
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.explain_bindings(["deps", "env", "missing"]);
+        let ast = AstModule::parse("a.star", program.to_owned(), &Dialect::Extended).unwrap();
+        eval.eval_module(ast, &GlobalsBuilder::standard().build())
+            .unwrap();
+
+        let deps = eval.binding_history("deps").unwrap();
+        let kinds: Vec<_> = deps.iter().map(|e| e.kind.clone()).collect();
+        assert_eq!(
+            vec![
+                BindingEventKind::Assign,
+                BindingEventKind::MethodCall("append".to_owned()),
+                BindingEventKind::Assign,
+                BindingEventKind::MethodCall("remove".to_owned()),
+            ],
+            kinds
+        );
+        let values: Vec<_> = deps.iter().map(|e| e.value.as_str()).collect();
+        assert_eq!(
+            vec![
+                r#"["a"]"#,
+                r#"["a", "b"]"#,
+                r#"["a", "b", "c"]"#,
+                r#"["b", "c"]"#
+            ],
+            values
+        );
+        assert!(deps[0].call_stack.is_empty());
+        assert!(!deps[1].call_stack.is_empty());
+        assert_eq!("a.star:6:5-19", deps[1].span.as_ref().unwrap().to_string());
+        assert_eq!("a.star:9:1-14", deps[2].span.as_ref().unwrap().to_string());
+        assert!(
+            deps[1].to_string().contains("mutated by `append`"),
+            "{}",
+            deps[1]
+        );
+
+        let env = eval.binding_history("env").unwrap();
+        assert_eq!(2, env.len());
+        assert_eq!(BindingEventKind::SetIndex, env[1].kind);
+        assert_eq!(r#"{"k": ["b", "c"]}"#, env[1].value);
+
+        assert_eq!(Some(0), eval.binding_history("missing").map(|e| e.len()));
+        assert!(eval.binding_history("other").is_none());
+    }
+}
@@ -18,11 +18,29 @@
 //! Define variants of the evaluation function with different support
 //! for the `load(...)` statement.
 
+use std::cell::Cell;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
 
 use dupe::Dupe;
+use itertools::Itertools;
 
+use crate::environment::FileKind;
+use crate::environment::FileKindResolver;
 use crate::environment::FrozenModule;
+use crate::environment::Module;
+use crate::eval::EvalRecorder;
+use crate::eval::Evaluator;
+use crate::syntax::AstModule;
+
+#[derive(Debug, thiserror::Error)]
+enum FileLoaderError {
+    #[error("Cycle in `load()` statements: {0}")]
+    Cycle(String),
+}
 
 /// A trait for turning a `path` given by a `load()` statement into a [`FrozenModule`].
 pub trait FileLoader {
@@ -51,3 +69,179 @@ impl<'a> FileLoader for ReturnFileLoader<'a> {
         }
     }
 }
+
+/// [`FileLoader`] which reads and evaluates files from the file system.
+///
+/// Paths given to `load()` are resolved against the root directory.
+/// Each file is parsed and evaluated with the dialect and globals chosen by
+/// the [`FileKindResolver`], and `load()` statements in it are resolved by the same loader.
+/// Loaded modules are cached, so each file is evaluated at most once.
+///
+/// For hot reloading, the loader records which files each file loads:
+/// [`invalidate`](FsFileLoader::invalidate) drops a changed file from the cache together with
+/// the files which depend on it, which are evaluated again when they are next loaded.
+/// Files evaluated by the caller are only tracked when they are loaded through the loader.
+pub struct FsFileLoader<'a> {
+    root: PathBuf,
+    resolver: &'a dyn FileKindResolver,
+    modules: RefCell<HashMap<PathBuf, FrozenModule>>,
+    /// Files being evaluated, to report cycles.
+    loading: RefCell<Vec<PathBuf>>,
+    /// Files loaded by each file, in the order of the `load()` statements.
+    dependencies: RefCell<HashMap<PathBuf, Vec<PathBuf>>>,
+    /// Generation of each cached module.
+    generations: RefCell<HashMap<PathBuf, u64>>,
+    next_generation: Cell<u64>,
+    recorder: Option<&'a EvalRecorder>,
+}
+
+impl<'a> FsFileLoader<'a> {
+    /// Create a loader resolving paths against `root`.
+    pub fn new(root: impl Into<PathBuf>, resolver: &'a dyn FileKindResolver) -> FsFileLoader<'a> {
+        FsFileLoader {
+            root: root.into(),
+            resolver,
+            modules: RefCell::new(HashMap::new()),
+            loading: RefCell::new(Vec::new()),
+            dependencies: RefCell::new(HashMap::new()),
+            generations: RefCell::new(HashMap::new()),
+            next_generation: Cell::new(0),
+            recorder: None,
+        }
+    }
+
+    /// Record the evaluation of the loaded files into `recorder`,
+    /// see [`EvalRecorder`].
+    pub fn set_recorder(&mut self, recorder: &'a EvalRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    fn insert(&self, path: PathBuf, module: FrozenModule) {
+        let generation = self.next_generation.get();
+        self.next_generation.set(generation + 1);
+        self.generations
+            .borrow_mut()
+            .insert(path.clone(), generation);
+        self.modules.borrow_mut().insert(path, module);
+    }
+
+    /// Generation of the cached module of the file at `path`, relative to the root,
+    /// or `None` if it is not loaded. Each evaluation or replacement of a file gets a
+    /// new generation, so a module with an old generation is out of date.
+    pub fn generation(&self, path: impl AsRef<Path>) -> Option<u64> {
+        self.generations
+            .borrow()
+            .get(&self.root.join(path))
+            .copied()
+    }
+
+    /// Files which load the file at `path`, directly or not, each after the files it loads.
+    fn dependents(&self, path: &Path) -> Vec<PathBuf> {
+        fn visit<'p>(
+            path: &'p Path,
+            dependents: &HashMap<&Path, Vec<&'p Path>>,
+            visited: &mut HashSet<&'p Path>,
+            res: &mut Vec<PathBuf>,
+        ) {
+            if !visited.insert(path) {
+                return;
+            }
+            for x in dependents.get(path).into_iter().flatten() {
+                visit(x, dependents, visited, res);
+            }
+            res.push(path.to_owned());
+        }
+
+        let dependencies = self.dependencies.borrow();
+        let mut dependents: HashMap<&Path, Vec<&Path>> = HashMap::new();
+        for (file, loads) in dependencies.iter() {
+            for x in loads {
+                dependents.entry(x).or_default().push(file);
+            }
+        }
+        let mut res = Vec::new();
+        visit(path, &dependents, &mut HashSet::new(), &mut res);
+        // Post-order puts dependents before the files they load.
+        res.reverse();
+        res.remove(0);
+        res
+    }
+
+    /// Drop the cached module of the file at `path`, relative to the root, for example
+    /// because the file changed, and the modules of the files which load it, directly or not.
+    ///
+    /// Returns the dependent files, each after the files it loads,
+    /// which is the order to evaluate them again in.
+    pub fn invalidate(&self, path: impl AsRef<Path>) -> Vec<PathBuf> {
+        let path = self.root.join(path);
+        let dependents = self.dependents(&path);
+        let mut modules = self.modules.borrow_mut();
+        let mut generations = self.generations.borrow_mut();
+        for x in dependents.iter().chain([&path]) {
+            modules.remove(x);
+            generations.remove(x);
+        }
+        dependents
+    }
+
+    /// Use `module` as the contents of the file at `path`, relative to the root,
+    /// instead of evaluating the file, and invalidate the files which load it.
+    /// Returns the dependent files like [`invalidate`](FsFileLoader::invalidate).
+    pub fn replace(&self, path: impl AsRef<Path>, module: FrozenModule) -> Vec<PathBuf> {
+        let path = self.root.join(path);
+        let dependents = self.invalidate(&path);
+        // The loads of the replaced contents are unknown.
+        self.dependencies.borrow_mut().remove(&path);
+        self.insert(path, module);
+        dependents
+    }
+
+    fn eval_file(&self, path: &Path) -> anyhow::Result<FrozenModule> {
+        let FileKind { dialect, globals } = self.resolver.resolve(path);
+        let ast = AstModule::parse_file(path, &dialect)?;
+        let module = Module::new();
+        {
+            let mut eval = Evaluator::new(&module);
+            eval.set_loader(self);
+            if let Some(recorder) = self.recorder {
+                eval.set_recorder(recorder);
+            }
+            eval.eval_module(ast, &globals)?;
+        }
+        module.freeze()
+    }
+}
+
+impl<'a> FileLoader for FsFileLoader<'a> {
+    fn load(&self, path: &str) -> anyhow::Result<FrozenModule> {
+        let path = self.root.join(path);
+        if let Some(parent) = self.loading.borrow().last() {
+            self.dependencies
+                .borrow_mut()
+                .entry(parent.clone())
+                .or_default()
+                .push(path.clone());
+        }
+        if let Some(module) = self.modules.borrow().get(&path) {
+            return Ok(module.dupe());
+        }
+        let loading = self.loading.borrow();
+        if let Some(start) = loading.iter().position(|x| *x == path) {
+            let cycle = loading[start..]
+                .iter()
+                .chain([&path])
+                .map(|x| x.display())
+                .join(" -> ");
+            return Err(FileLoaderError::Cycle(cycle).into());
+        }
+        drop(loading);
+
+        self.dependencies.borrow_mut().remove(&path);
+        self.loading.borrow_mut().push(path.clone());
+        let res = self.eval_file(&path);
+        self.loading.borrow_mut().pop();
+        let module = res?;
+        self.insert(path, module.dupe());
+        Ok(module)
+    }
+}
@@ -88,6 +88,11 @@ impl FrozenFileSpan {
         }
     }
 
+    /// The span in the original source for generated code, see [`CodeMap::original_file_span`].
+    pub(crate) fn to_original_file_span(&self) -> FileSpan {
+        self.file.original_file_span(self.span)
+    }
+
     pub(crate) fn merge(&self, other: &FrozenFileSpan) -> FrozenFileSpan {
         if self.file == other.file {
             FrozenFileSpan {
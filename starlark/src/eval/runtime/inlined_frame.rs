@@ -40,7 +40,7 @@ impl InlinedFrame {
     pub(crate) fn extend_frames(&self, frames: &mut Vec<Frame>) {
         frames.push(Frame {
             name: self.fun.to_value().name_for_call_stack(),
-            location: Some(self.span.span.to_file_span()),
+            location: Some(self.span.span.to_original_file_span()),
         });
         self.span.inlined_frames.extend_frames(frames);
     }
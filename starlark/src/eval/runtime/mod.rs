@@ -15,16 +15,23 @@
  * limitations under the License.
  */
 
+pub(crate) mod alloc_args;
 pub(crate) mod arguments;
 pub(crate) mod before_stmt;
 pub(crate) mod call_stack;
+pub(crate) mod deadline;
+pub(crate) mod eval_profile;
 pub(crate) mod evaluator;
+pub(crate) mod explain;
 pub(crate) mod file_loader;
 pub(crate) mod frame_span;
 pub(crate) mod frozen_file_span;
 pub(crate) mod inlined_frame;
 pub(crate) mod params;
 pub(crate) mod profile;
+pub(crate) mod provenance;
+pub(crate) mod recording;
+pub(crate) mod replay_trace;
 pub(crate) mod rust_loc;
 pub(crate) mod slots;
 pub(crate) mod small_duration;
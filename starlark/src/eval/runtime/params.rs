@@ -27,8 +27,10 @@ use gazebo::coerce::coerce;
 use gazebo::coerce::Coerce;
 use starlark_map::small_map::SmallMap;
 use starlark_map::Hashed;
+use thiserror::Error;
 
 use crate as starlark;
+use crate::collections::small_value_vec::SmallValueVec;
 use crate::collections::symbol_map::SymbolMap;
 use crate::docs;
 use crate::docs::DocString;
@@ -61,6 +63,43 @@ pub(crate) enum ParameterKind<V> {
     KWargs,
 }
 
+/// A parameter of a [`ParametersSpec`] described as data,
+/// for signatures which are only known at runtime.
+///
+/// The items correspond to the [`ParametersSpecBuilder`] calls,
+/// and are passed to [`ParametersSpec::from_items`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParametersSpecItem<V> {
+    /// See [`ParametersSpecBuilder::required`].
+    Required(String),
+    /// See [`ParametersSpecBuilder::optional`].
+    Optional(String),
+    /// See [`ParametersSpecBuilder::defaulted`].
+    Defaulted(String, V),
+    /// `/`, see [`ParametersSpecBuilder::no_more_positional_only_args`].
+    NoMorePositionalOnlyArgs,
+    /// `*`, see [`ParametersSpecBuilder::no_more_positional_args`].
+    NoMorePositionalArgs,
+    /// `*args`, see [`ParametersSpecBuilder::args`].
+    Args,
+    /// `**kwargs`, see [`ParametersSpecBuilder::kwargs`].
+    Kwargs,
+}
+
+#[derive(Debug, Error)]
+enum ParametersSpecError {
+    #[error("Repeated parameter `{0}`")]
+    RepeatedParameter(String),
+    #[error("Parameter `{0}` cannot follow `**kwargs`")]
+    ParameterAfterKwargs(String),
+    #[error("`/` must be used once, before `*`, `*args` and `**kwargs`")]
+    MisplacedPositionalOnly,
+    #[error("`*` or `*args` must be used once, before `**kwargs`")]
+    MisplacedArgs,
+    #[error("`**kwargs` must be used once")]
+    RepeatedKwargs,
+}
+
 #[derive(Debug, Copy, Clone, Dupe, PartialEq, Eq, PartialOrd, Ord)]
 enum CurrentParameterStyle {
     /// Parameter can be only filled positionally.
@@ -118,24 +157,39 @@ pub struct ParametersSpec<V> {
 unsafe impl<From: Coerce<To>, To> Coerce<ParametersSpec<To>> for ParametersSpec<From> {}
 
 impl<V: Copy> ParametersSpecBuilder<V> {
-    fn add(&mut self, name: &str, val: ParameterKind<V>) {
+    /// Panic on misuse of the builder, the signature is known at compile time.
+    fn check(res: Result<(), ParametersSpecError>) {
+        if let Err(e) = res {
+            panic!("{}", e);
+        }
+    }
+
+    fn try_add(&mut self, name: &str, val: ParameterKind<V>) -> Result<(), ParametersSpecError> {
         assert!(!matches!(val, ParameterKind::Args | ParameterKind::KWargs));
 
         // Regular arguments cannot follow `**kwargs`, but can follow `*args`.
-        assert!(self.current_style < CurrentParameterStyle::NoMore);
-        assert!(self.kwargs.is_none());
+        if self.current_style == CurrentParameterStyle::NoMore || self.kwargs.is_some() {
+            return Err(ParametersSpecError::ParameterAfterKwargs(name.to_owned()));
+        }
 
         let i = self.params.len();
-        self.params.push((name.to_owned(), val));
         if self.current_style != CurrentParameterStyle::PosOnly {
-            let old = self.names.insert(name, i.try_into().unwrap());
-            assert!(old.is_none(), "Repeated parameter `{}`", name);
+            if self.names.get_str(name).is_some() {
+                return Err(ParametersSpecError::RepeatedParameter(name.to_owned()));
+            }
+            self.names.insert(name, i.try_into().unwrap());
         }
+        self.params.push((name.to_owned(), val));
         if self.args.is_none() && self.current_style != CurrentParameterStyle::NamedOnly {
             // If you've already seen `args` or `no_args`, you can't enter these
             // positionally
             self.positional = i + 1;
         }
+        Ok(())
+    }
+
+    fn add(&mut self, name: &str, val: ParameterKind<V>) {
+        Self::check(self.try_add(name, val));
     }
 
     /// Add a required parameter. Will be an error if the caller doesn't supply
@@ -167,18 +221,38 @@ impl<V: Copy> ParametersSpecBuilder<V> {
     /// [`defaulted`](ParametersSpecBuilder::defaulted)
     /// parameters can _only_ be supplied by name.
     pub fn args(&mut self) {
-        assert!(self.args.is_none());
-        assert!(self.current_style < CurrentParameterStyle::NamedOnly);
-        assert!(self.kwargs.is_none());
+        Self::check(self.try_args());
+    }
+
+    fn try_args(&mut self) -> Result<(), ParametersSpecError> {
+        self.check_before_named_only()?;
         self.params.push(("*args".to_owned(), ParameterKind::Args));
         self.args = Some(self.params.len() - 1);
         self.current_style = CurrentParameterStyle::NamedOnly;
+        Ok(())
+    }
+
+    fn check_before_named_only(&self) -> Result<(), ParametersSpecError> {
+        if self.args.is_some()
+            || self.current_style >= CurrentParameterStyle::NamedOnly
+            || self.kwargs.is_some()
+        {
+            return Err(ParametersSpecError::MisplacedArgs);
+        }
+        Ok(())
     }
 
     /// Following parameters can be filled positionally or by name.
     pub fn no_more_positional_only_args(&mut self) {
-        assert_eq!(self.current_style, CurrentParameterStyle::PosOnly);
+        Self::check(self.try_no_more_positional_only_args());
+    }
+
+    fn try_no_more_positional_only_args(&mut self) -> Result<(), ParametersSpecError> {
+        if self.current_style != CurrentParameterStyle::PosOnly {
+            return Err(ParametersSpecError::MisplacedPositionalOnly);
+        }
         self.current_style = CurrentParameterStyle::PosOrNamed;
+        Ok(())
     }
 
     /// This function has no `*args` parameter, corresponds to the Python parameter `*`.
@@ -188,10 +262,13 @@ impl<V: Copy> ParametersSpecBuilder<V> {
     /// [`defaulted`](ParametersSpecBuilder::defaulted)
     /// parameters can _only_ be supplied by name.
     pub fn no_more_positional_args(&mut self) {
-        assert!(self.args.is_none());
-        assert!(self.current_style < CurrentParameterStyle::NamedOnly);
-        assert!(self.kwargs.is_none());
+        Self::check(self.try_no_more_positional_args());
+    }
+
+    fn try_no_more_positional_args(&mut self) -> Result<(), ParametersSpecError> {
+        self.check_before_named_only()?;
         self.current_style = CurrentParameterStyle::NamedOnly;
+        Ok(())
     }
 
     /// Add a `**kwargs` parameter which will be a dictionary, recorded into a [`SmallMap`].
@@ -202,11 +279,37 @@ impl<V: Copy> ParametersSpecBuilder<V> {
     /// [`defaulted`](ParametersSpecBuilder::defaulted)
     /// parameters can _only_ be supplied by position.
     pub fn kwargs(&mut self) {
-        assert!(self.kwargs.is_none());
+        Self::check(self.try_kwargs());
+    }
+
+    fn try_kwargs(&mut self) -> Result<(), ParametersSpecError> {
+        if self.kwargs.is_some() {
+            return Err(ParametersSpecError::RepeatedKwargs);
+        }
         self.params
             .push(("**kwargs".to_owned(), ParameterKind::KWargs));
         self.current_style = CurrentParameterStyle::NoMore;
         self.kwargs = Some(self.params.len() - 1);
+        Ok(())
+    }
+
+    /// Add a parameter described as data.
+    ///
+    /// Unlike the other builder functions, this returns an error
+    /// instead of panicking if the parameter is not valid at this position.
+    pub fn item(&mut self, item: ParametersSpecItem<V>) -> anyhow::Result<()> {
+        match item {
+            ParametersSpecItem::Required(name) => self.try_add(&name, ParameterKind::Required),
+            ParametersSpecItem::Optional(name) => self.try_add(&name, ParameterKind::Optional),
+            ParametersSpecItem::Defaulted(name, v) => {
+                self.try_add(&name, ParameterKind::Defaulted(v))
+            }
+            ParametersSpecItem::NoMorePositionalOnlyArgs => self.try_no_more_positional_only_args(),
+            ParametersSpecItem::NoMorePositionalArgs => self.try_no_more_positional_args(),
+            ParametersSpecItem::Args => self.try_args(),
+            ParametersSpecItem::Kwargs => self.try_kwargs(),
+        }?;
+        Ok(())
     }
 
     /// Construct the parameters specification.
@@ -252,6 +355,26 @@ impl<V> ParametersSpec<V> {
         }
     }
 
+    /// Create a [`ParametersSpec`] from parameters described as data,
+    /// for example for native functions generated at runtime.
+    ///
+    /// Returns an error if the parameters are not well-formed,
+    /// e.g. a parameter name is repeated or a parameter follows `**kwargs`.
+    pub fn from_items(
+        function_name: String,
+        items: impl IntoIterator<Item = ParametersSpecItem<V>>,
+    ) -> anyhow::Result<ParametersSpec<V>>
+    where
+        V: Copy,
+    {
+        let items = items.into_iter();
+        let mut builder = Self::with_capacity(function_name, items.size_hint().0);
+        for item in items {
+            builder.item(item)?;
+        }
+        Ok(builder.finish())
+    }
+
     /// Produce an approximate signature for the function, combining the name and arguments.
     pub fn signature(&self) -> String {
         let mut collector = String::new();
@@ -444,7 +567,7 @@ impl<'v, V: ValueLike<'v>> ParametersSpec<V> {
         // We might do unchecked stuff later on, so make sure we have as many slots as we expect
         assert!(slots.len() >= len);
 
-        let mut star_args = Vec::new();
+        let mut star_args = SmallValueVec::new();
         let mut kwargs = LazyKwargs::default();
         let mut next_position = 0;
 
@@ -697,6 +820,19 @@ impl<'v, V: ValueLike<'v>> ParametersSpec<V> {
         params
     }
 
+    /// Check the arguments can be passed to a function with this signature,
+    /// discarding the collected values.
+    ///
+    /// Useful for functions which forward the arguments elsewhere,
+    /// but want to report a signature mismatch early.
+    pub fn check_args(
+        &self,
+        args: &Arguments<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<()> {
+        self.parser(args, eval, |_, _| Ok(()))
+    }
+
     /// Create a [`ParametersParser`] for given arguments.
     pub fn parser<R, F>(
         &self,
@@ -772,7 +908,10 @@ mod tests {
     use crate::eval::compiler::def::FrozenDef;
     use crate::eval::runtime::params::ParameterKind;
     use crate::eval::ParametersSpec;
+    use crate::eval::ParametersSpecItem;
+    use crate::values::function::NativeFunction;
     use crate::values::FrozenValue;
+    use crate::values::Value;
 
     #[test]
     fn test_parameter_iteration() {
@@ -830,6 +969,89 @@ mod tests {
         assert_eq!(expected, params);
     }
 
+    #[test]
+    fn test_from_items() {
+        let p = ParametersSpec::<FrozenValue>::from_items(
+            "f".to_owned(),
+            [
+                ParametersSpecItem::Required("a".to_owned()),
+                ParametersSpecItem::NoMorePositionalOnlyArgs,
+                ParametersSpecItem::Defaulted("b".to_owned(), FrozenValue::new_none()),
+                ParametersSpecItem::Args,
+                ParametersSpecItem::Optional("c".to_owned()),
+                ParametersSpecItem::Kwargs,
+            ],
+        )
+        .unwrap();
+        assert_eq!("a, b = ..., *args, c = ..., **kwargs", p.parameters_str());
+        assert!(p.can_fill_with_args(3, &["c", "d"]));
+        assert!(!p.can_fill_with_args(0, &["a"]));
+
+        fn err(items: Vec<ParametersSpecItem<FrozenValue>>) -> String {
+            ParametersSpec::from_items("f".to_owned(), items)
+                .unwrap_err()
+                .to_string()
+        }
+        let x = || ParametersSpecItem::Required("x".to_owned());
+        assert_eq!(
+            "Repeated parameter `x`",
+            err(vec![ParametersSpecItem::NoMorePositionalOnlyArgs, x(), x()])
+        );
+        assert_eq!(
+            "Parameter `x` cannot follow `**kwargs`",
+            err(vec![ParametersSpecItem::Kwargs, x()])
+        );
+        assert_eq!(
+            "`*` or `*args` must be used once, before `**kwargs`",
+            err(vec![
+                ParametersSpecItem::Args,
+                ParametersSpecItem::NoMorePositionalArgs
+            ])
+        );
+        assert_eq!(
+            "`/` must be used once, before `*`, `*args` and `**kwargs`",
+            err(vec![
+                ParametersSpecItem::Args,
+                ParametersSpecItem::NoMorePositionalOnlyArgs
+            ])
+        );
+        assert_eq!(
+            "`**kwargs` must be used once",
+            err(vec![ParametersSpecItem::Kwargs, ParametersSpecItem::Kwargs])
+        );
+    }
+
+    #[test]
+    fn test_check_args() {
+        let p = ParametersSpec::<FrozenValue>::from_items(
+            "f".to_owned(),
+            [
+                ParametersSpecItem::NoMorePositionalOnlyArgs,
+                ParametersSpecItem::Required("a".to_owned()),
+                ParametersSpecItem::NoMorePositionalArgs,
+                ParametersSpecItem::Optional("b".to_owned()),
+            ],
+        )
+        .unwrap();
+        let mut a = Assert::new();
+        a.globals_add(|globals| {
+            globals.set(
+                "check",
+                NativeFunction::new_direct(
+                    move |eval, args| {
+                        p.check_args(args, eval)?;
+                        Ok(Value::new_none())
+                    },
+                    "check".to_owned(),
+                ),
+            )
+        });
+        a.pass("check(1)\ncheck(a = 1, b = 2)");
+        a.fail("check()", "Missing parameter `a`");
+        a.fail("check(1, 2)", "Found 1 extra positional");
+        a.fail("check(1, c = 2)", "extra named");
+    }
+
     #[test]
     fn test_documentation() -> anyhow::Result<()> {
         // Make sure that documentation for some odder parameter specs works properly.
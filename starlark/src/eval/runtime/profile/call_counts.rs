@@ -0,0 +1,243 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Call counts and time per function.
+//!
+//! Unlike the flame profile, which records every call, this profile only keeps
+//! aggregated numbers per function, so memory usage does not grow with the number of calls.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate as starlark;
+use crate::eval::runtime::profile::csv::CsvWriter;
+use crate::eval::runtime::profile::data::ProfileData;
+use crate::eval::runtime::small_duration::SmallDuration;
+use crate::eval::ProfileMode;
+use crate::values::layout::pointer::RawPointer;
+use crate::values::Trace;
+use crate::values::Tracer;
+use crate::values::Value;
+
+#[derive(Debug, thiserror::Error)]
+enum CallCountsProfileError {
+    #[error("Call counts profile not enabled")]
+    NotEnabled,
+}
+
+/// Maximum number of distinct functions tracked.
+/// Calls to functions beyond that are aggregated into a single row.
+const MAX_FUNCTIONS: usize = 10000;
+
+#[derive(Default, Clone, Copy)]
+struct CallStats {
+    calls: u64,
+    /// Time from enter to exit, not counting recursive calls twice.
+    total: SmallDuration,
+    /// Time excluding calls to other functions.
+    self_time: SmallDuration,
+    /// Number of active frames of this function, to detect recursion.
+    active: u32,
+}
+
+struct StackFrame {
+    /// Index in `CallCountsData::stats`.
+    index: usize,
+    start: Instant,
+    /// Time spent in the callees.
+    children: SmallDuration,
+}
+
+#[derive(Default)]
+struct CallCountsData<'v> {
+    /// Functions, the same length as `stats` except the overflow entry.
+    values: Vec<Value<'v>>,
+    /// Index of `values`, keyed by pointer. Rebuilt on GC.
+    map: HashMap<RawPointer, usize>,
+    /// Stats per function, and the last entry for other functions
+    /// when the number of functions exceeds `MAX_FUNCTIONS`.
+    stats: Vec<CallStats>,
+    stack: Vec<StackFrame>,
+}
+
+unsafe impl<'v> Trace<'v> for CallCountsData<'v> {
+    fn trace(&mut self, tracer: &Tracer<'v>) {
+        self.values.trace(tracer);
+        // Keyed by pointer which changes on GC.
+        self.map.clear();
+        for (i, x) in self.values.iter().enumerate() {
+            self.map.insert(x.ptr_value(), i);
+        }
+    }
+}
+
+impl<'v> CallCountsData<'v> {
+    fn index(&mut self, function: Value<'v>) -> usize {
+        if let Some(index) = self.map.get(&function.ptr_value()) {
+            return *index;
+        }
+        if self.values.len() == MAX_FUNCTIONS {
+            if self.stats.len() == MAX_FUNCTIONS {
+                self.stats.push(CallStats::default());
+            }
+            return MAX_FUNCTIONS;
+        }
+        let index = self.values.len();
+        self.values.push(function);
+        self.stats.push(CallStats::default());
+        self.map.insert(function.ptr_value(), index);
+        index
+    }
+}
+
+#[derive(Trace, Default)]
+pub(crate) struct CallCountsProfile<'v>(Option<Box<CallCountsData<'v>>>);
+
+impl<'v> CallCountsProfile<'v> {
+    pub(crate) fn enable(&mut self) {
+        self.0 = Some(Box::default());
+    }
+
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn record_call_enter(&mut self, function: Value<'v>) {
+        if let Some(x) = &mut self.0 {
+            let index = x.index(function);
+            let stats = &mut x.stats[index];
+            stats.calls += 1;
+            stats.active += 1;
+            x.stack.push(StackFrame {
+                index,
+                start: Instant::now(),
+                children: SmallDuration::default(),
+            });
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn record_call_exit(&mut self) {
+        if let Some(x) = &mut self.0 {
+            let frame = x.stack.pop().expect("call exit without enter");
+            let elapsed = frame.start.elapsed();
+            let stats = &mut x.stats[frame.index];
+            stats.active -= 1;
+            if stats.active == 0 {
+                stats.total += elapsed;
+            }
+            stats.self_time += SmallDuration {
+                nanos: SmallDuration::from_duration(elapsed)
+                    .nanos
+                    .saturating_sub(frame.children.nanos),
+            };
+            if let Some(parent) = x.stack.last_mut() {
+                parent.children += elapsed;
+            }
+        }
+    }
+
+    fn gen_csv(x: &CallCountsData) -> String {
+        let mut rows: Vec<(String, CallStats)> = x
+            .values
+            .iter()
+            .zip(&x.stats)
+            .map(|(v, s)| {
+                // Module top-level code is recorded as a call of `None`.
+                let name = if v.is_none() {
+                    "(module)".to_owned()
+                } else {
+                    v.to_repr()
+                };
+                (name, *s)
+            })
+            .collect();
+        if let Some(other) = x.stats.get(MAX_FUNCTIONS) {
+            rows.push(("<other functions>".to_owned(), *other));
+        }
+        rows.sort_by(|(a_name, a), (b_name, b)| {
+            (b.total.nanos, b.calls, a_name).cmp(&(a.total.nanos, a.calls, b_name))
+        });
+
+        let mut w = CsvWriter::new(["Function", "Calls", "Total time (s)", "Self time (s)"]);
+        for (name, stats) in &rows {
+            w.write_display(name);
+            w.write_value(stats.calls);
+            w.write_value(stats.total);
+            w.write_value(stats.self_time);
+            w.finish_row();
+        }
+        w.finish()
+    }
+
+    pub(crate) fn gen(&self) -> anyhow::Result<ProfileData> {
+        match &self.0 {
+            None => Err(CallCountsProfileError::NotEnabled.into()),
+            Some(x) => Ok(ProfileData::new(ProfileMode::CallCounts, Self::gen_csv(x))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::eval::Evaluator;
+    use crate::eval::ProfileMode;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    #[test]
+    fn test_call_counts_profile() -> anyhow::Result<()> {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        let program = r#"
+def f(x):
+    return str(x)
+
+def fact(n):
+    return 1 if n <= 1 else n * fact(n - 1)
+
+def g():
+    for i in range(0, 100):
+        f(i)
+    fact(5)
+
+g()
+"#;
+        let program = AstModule::parse("test.star", program.to_owned(), &Dialect::Extended)?;
+        eval.enable_profile(&ProfileMode::CallCounts)?;
+        eval.eval_module(program, &Globals::extended())?;
+
+        let csv = eval.gen_profile()?.gen()?;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!("Function,Calls,Total time (s),Self time (s)", lines[0]);
+        let calls = |name: &str| -> Option<&str> {
+            let line = lines
+                .iter()
+                .find(|l| l.starts_with(&format!("\"{}\",", name)))?;
+            line.split(',').nth(1)
+        };
+        assert_eq!(Some("1"), calls("(module)"), "{}", csv);
+        assert_eq!(Some("100"), calls("test.star.f"), "{}", csv);
+        assert_eq!(Some("100"), calls("str"), "{}", csv);
+        assert_eq!(Some("5"), calls("test.star.fact"), "{}", csv);
+        assert_eq!(Some("1"), calls("test.star.g"), "{}", csv);
+        // Sorted by total time, module code includes all the calls.
+        assert!(lines[1].starts_with("\"(module)\","), "{}", csv);
+        Ok(())
+    }
+}
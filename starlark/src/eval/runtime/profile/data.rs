@@ -25,6 +25,7 @@ use gazebo::prelude::*;
 use crate::eval::runtime::profile::bc::BcPairsProfileData;
 use crate::eval::runtime::profile::bc::BcProfileData;
 use crate::eval::runtime::profile::flamegraph::FlameGraphData;
+use crate::eval::runtime::profile::stmt::StmtProfileInfo;
 use crate::eval::ProfileMode;
 use crate::values::AggregateHeapProfileInfo;
 
@@ -47,6 +48,7 @@ pub(crate) enum ProfileDataImpl {
     AggregateHeapProfileInfo(Box<AggregateHeapProfileInfo>),
     /// Flame graph data is in milliseconds.
     TimeFlameProfile(FlameGraphData),
+    Stmt(StmtProfileInfo),
     Other(String),
 }
 
@@ -87,6 +89,8 @@ impl ProfileData {
             (ProfileDataImpl::TimeFlameProfile(_), _) => {
                 Err(ProfileDataError::ProfileDataNotConsistent.into())
             }
+            (ProfileDataImpl::Stmt(info), ProfileMode::Statement) => Ok(info.gen_csv()),
+            (ProfileDataImpl::Stmt(_), _) => Err(ProfileDataError::ProfileDataNotConsistent.into()),
         }
     }
 
@@ -103,6 +107,10 @@ impl ProfileData {
     }
 
     /// Merge profiles (aggregate).
+    ///
+    /// Functions defined in Starlark are identified by their file and name,
+    /// so calls of the same function from different modules are merged together.
+    /// Use [`ProfileDataAccumulator`] to merge many profiles without keeping them all in memory.
     pub fn merge<'a>(
         profiles: impl IntoIterator<Item = &'a ProfileData>,
     ) -> anyhow::Result<ProfileData> {
@@ -152,6 +160,16 @@ impl ProfileData {
                 let profile = FlameGraphData::merge(profiles);
                 ProfileDataImpl::TimeFlameProfile(profile)
             }
+            ProfileMode::Statement => {
+                let mut profile = StmtProfileInfo::default();
+                for p in profiles {
+                    match &p.profile {
+                        ProfileDataImpl::Stmt(info) => profile.merge(info),
+                        _ => return Err(ProfileDataError::ProfileDataNotConsistent.into()),
+                    }
+                }
+                ProfileDataImpl::Stmt(profile)
+            }
             profile_mode => {
                 return Err(ProfileDataError::MergeNotImplemented(profile_mode.dupe()).into());
             }
@@ -163,18 +181,73 @@ impl ProfileData {
     }
 }
 
+/// Merges profiles one at a time, for example the profiles of all the files evaluated
+/// in a build, so that only the merged profile is kept in memory.
+#[derive(Default, Debug)]
+pub struct ProfileDataAccumulator {
+    merged: Option<ProfileData>,
+    count: usize,
+}
+
+impl ProfileDataAccumulator {
+    /// Create an empty accumulator.
+    pub fn new() -> ProfileDataAccumulator {
+        ProfileDataAccumulator::default()
+    }
+
+    /// Merge a profile into the accumulated one.
+    ///
+    /// Fails if the profile mode is different from the mode of the profiles added before,
+    /// or if profiles of this mode cannot be merged. The accumulated profile is unchanged then.
+    pub fn add(&mut self, profile: &ProfileData) -> anyhow::Result<()> {
+        let merged = match &self.merged {
+            None => ProfileData::merge([profile])?,
+            Some(merged) => ProfileData::merge([merged, profile])?,
+        };
+        self.merged = Some(merged);
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Number of profiles added.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The merged profile.
+    pub fn finish(self) -> anyhow::Result<ProfileData> {
+        self.merged
+            .ok_or_else(|| ProfileDataError::EmptyProfileList.into())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use dupe::Dupe;
 
+    use crate::environment::Globals;
+    use crate::environment::Module;
     use crate::eval::runtime::profile::bc::BcPairsProfileData;
     use crate::eval::runtime::profile::bc::BcProfileData;
     use crate::eval::runtime::profile::data::ProfileDataImpl;
     use crate::eval::runtime::profile::flamegraph::FlameGraphData;
+    use crate::eval::Evaluator;
     use crate::eval::ProfileData;
+    use crate::eval::ProfileDataAccumulator;
     use crate::eval::ProfileMode;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
     use crate::values::AggregateHeapProfileInfo;
 
+    fn profile(mode: &ProfileMode, file: &str, program: &str) -> anyhow::Result<ProfileData> {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_profile(mode)?;
+        let ast = AstModule::parse(file, program.to_owned(), &Dialect::Extended)?;
+        eval.eval_module(ast, &Globals::standard())?;
+        eval.gen_profile()
+    }
+
     #[test]
     fn merge_bc() {
         let profile = ProfileData {
@@ -223,4 +296,69 @@ mod tests {
         // Smoke.
         ProfileData::merge([&profile, &profile]).unwrap();
     }
+
+    #[test]
+    fn accumulate_stmt() -> anyhow::Result<()> {
+        let mut accumulator = ProfileDataAccumulator::new();
+        for file in ["a.star", "b.star", "a.star"] {
+            accumulator.add(&profile(&ProfileMode::Statement, file, "x = 1")?)?;
+        }
+        assert_eq!(3, accumulator.count());
+        let csv = accumulator.finish()?.gen()?;
+        let mut rows = csv
+            .lines()
+            .skip(2)
+            .map(|x| x.split(',').map(str::to_owned).collect::<Vec<_>>())
+            .map(|x| (x[0].clone(), x[3].clone()))
+            .collect::<Vec<_>>();
+        rows.sort();
+        assert_eq!(
+            vec![
+                ("\"a.star\"".to_owned(), "2".to_owned()),
+                ("\"b.star\"".to_owned(), "1".to_owned()),
+            ],
+            rows
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn accumulate_heap_by_function() -> anyhow::Result<()> {
+        let program = "def f():\n    return [1]\nf()\nf()";
+        let mut accumulator = ProfileDataAccumulator::new();
+        for file in ["a.star", "b.star", "a.star"] {
+            let mode = ProfileMode::HeapSummaryAllocated;
+            accumulator.add(&profile(&mode, file, program)?)?;
+        }
+        let csv = accumulator.finish()?.gen()?;
+        let mut allocs = csv
+            .lines()
+            .map(|x| x.split(',').collect::<Vec<_>>())
+            .filter(|x| x[0].ends_with(".f\""))
+            .map(|x| (x[0].to_owned(), x[7].to_owned()))
+            .collect::<Vec<_>>();
+        allocs.sort();
+        assert_eq!(
+            vec![
+                ("\"a.star.f\"".to_owned(), "8".to_owned()),
+                ("\"b.star.f\"".to_owned(), "4".to_owned()),
+            ],
+            allocs
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn accumulate_different_modes() -> anyhow::Result<()> {
+        let mut accumulator = ProfileDataAccumulator::new();
+        let coverage = ProfileData::new(ProfileMode::Coverage, String::new());
+        assert!(accumulator.add(&coverage).is_err());
+        accumulator.add(&profile(&ProfileMode::Statement, "a.star", "x = 1")?)?;
+        let time_flame = profile(&ProfileMode::TimeFlame, "a.star", "x = 1")?;
+        assert!(accumulator.add(&time_flame).is_err());
+        assert_eq!(1, accumulator.count());
+        assert_eq!(ProfileMode::Statement, accumulator.finish()?.profile_mode);
+        assert!(ProfileDataAccumulator::new().finish().is_err());
+        Ok(())
+    }
 }
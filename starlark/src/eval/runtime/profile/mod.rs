@@ -22,6 +22,7 @@ use allocative::Allocative;
 use dupe::Dupe;
 
 pub(crate) mod bc;
+pub(crate) mod call_counts;
 pub(crate) mod csv;
 pub(crate) mod data;
 pub(crate) mod flamegraph;
@@ -59,6 +60,12 @@ pub enum ProfileMode {
     TimeFlame,
     /// Profile runtime typechecking.
     Typecheck,
+    /// Number of calls, total and self time per function.
+    ///
+    /// Only aggregated numbers are stored, so memory usage is bounded
+    /// by the number of distinct functions, not the number of calls,
+    /// and overhead is low enough to keep it enabled in production.
+    CallCounts,
 }
 
 impl Display for ProfileMode {
@@ -80,6 +87,7 @@ impl ProfileMode {
             ProfileMode::BytecodePairs => "bytecode-pairs",
             ProfileMode::TimeFlame => "time-flame",
             ProfileMode::Typecheck => "typecheck",
+            ProfileMode::CallCounts => "call-counts",
         }
     }
 }
@@ -99,6 +107,7 @@ impl FromStr for ProfileMode {
             ProfileMode::BytecodePairs,
             ProfileMode::TimeFlame,
             ProfileMode::Typecheck,
+            ProfileMode::CallCounts,
         ] {
             if s == mode.name() {
                 return Ok(mode);
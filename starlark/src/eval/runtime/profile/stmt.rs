@@ -22,15 +22,17 @@ use std::iter;
 use std::time::Instant;
 
 use dupe::Dupe;
+use starlark_map::small_map::SmallMap;
 
 use crate::codemap::CodeMap;
 use crate::codemap::CodeMapId;
-use crate::codemap::FileSpan;
 use crate::codemap::FileSpanRef;
 use crate::codemap::ResolvedFileSpan;
+use crate::codemap::ResolvedSpan;
 use crate::codemap::Span;
 use crate::eval::runtime::profile::csv::CsvWriter;
 use crate::eval::runtime::profile::data::ProfileData;
+use crate::eval::runtime::profile::data::ProfileDataImpl;
 use crate::eval::runtime::small_duration::SmallDuration;
 use crate::eval::ProfileMode;
 
@@ -103,7 +105,7 @@ impl StmtProfileData {
         }
     }
 
-    fn write_to_string(&self, now: Instant) -> String {
+    fn info(&self, now: Instant) -> StmtProfileInfo {
         // The statement that was running last won't have been properly updated.
         // However, at this point, we have probably run some post-execution code,
         // so it probably wouldn't have a "fair" timing anyway.
@@ -113,42 +115,15 @@ impl StmtProfileData {
         let mut data = self.clone();
         data.add_last(now);
 
-        struct Item {
-            span: FileSpan,
-            time: SmallDuration,
-            count: usize,
-        }
-        // There should be one EMPTY span entry
-        let mut items = Vec::with_capacity(data.stmts.len() - 1);
-        let mut total_time = SmallDuration::default();
-        let mut total_count = 0;
+        let mut info = StmtProfileInfo::default();
         for ((file, span), (count, time)) in data.stmts {
             // EMPTY represents the first time special-case
             if file != CodeMapId::EMPTY {
-                let span = data.files[&file].file_span(span);
-                total_time += time;
-                total_count += count;
-                items.push(Item { span, time, count })
+                let file = &data.files[&file];
+                info.add(file.filename(), file.resolve_span(span), count, time);
             }
         }
-        items.sort_by_key(|x| -(x.time.nanos as i128));
-
-        let mut csv = CsvWriter::new(["File", "Span", "Duration(s)", "Count"]);
-        csv.write_value("TOTAL");
-        csv.write_value("");
-        csv.write_value(total_time);
-        csv.write_value(total_count);
-        csv.finish_row();
-
-        for x in items {
-            csv.write_value(x.span.file.filename());
-            csv.write_display(x.span.file.resolve_span(x.span.span));
-            csv.write_value(x.time);
-            csv.write_value(x.count);
-            csv.finish_row();
-        }
-
-        csv.finish()
+        info
     }
 
     fn coverage(&self) -> HashSet<ResolvedFileSpan> {
@@ -167,6 +142,59 @@ impl StmtProfileData {
     }
 }
 
+/// Time spent in statements, keyed by the file name and the span of the statement,
+/// so the profiles of different evaluations can be merged.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StmtProfileInfo {
+    stmts: SmallMap<(String, ResolvedSpan), (usize, SmallDuration)>,
+}
+
+impl StmtProfileInfo {
+    fn add(&mut self, file: &str, span: ResolvedSpan, count: usize, time: SmallDuration) {
+        let entry = self
+            .stmts
+            .entry((file.to_owned(), span))
+            .or_insert((0, SmallDuration::default()));
+        entry.0 += count;
+        entry.1 += time;
+    }
+
+    /// Add the statements of another profile to this one.
+    pub(crate) fn merge(&mut self, other: &StmtProfileInfo) {
+        for ((file, span), (count, time)) in &other.stmts {
+            self.add(file, *span, *count, *time);
+        }
+    }
+
+    pub(crate) fn gen_csv(&self) -> String {
+        let total_time = self
+            .stmts
+            .values()
+            .map(|(_, time)| *time)
+            .sum::<SmallDuration>();
+        let total_count = self.stmts.values().map(|(count, _)| *count).sum::<usize>();
+        let mut items = Vec::from_iter(&self.stmts);
+        items.sort_by_key(|(_, (_, time))| -(time.nanos as i128));
+
+        let mut csv = CsvWriter::new(["File", "Span", "Duration(s)", "Count"]);
+        csv.write_value("TOTAL");
+        csv.write_value("");
+        csv.write_value(total_time);
+        csv.write_value(total_count);
+        csv.finish_row();
+
+        for ((file, span), (count, time)) in items {
+            csv.write_value(file.as_str());
+            csv.write_display(span);
+            csv.write_value(time);
+            csv.write_value(count);
+            csv.finish_row();
+        }
+
+        csv.finish()
+    }
+}
+
 impl StmtProfile {
     pub(crate) fn new() -> Self {
         Self(None)
@@ -186,10 +214,10 @@ impl StmtProfile {
     pub(crate) fn gen(&self) -> anyhow::Result<ProfileData> {
         let now = Instant::now();
         match &self.0 {
-            Some(data) => Ok(ProfileData::new(
-                ProfileMode::Statement,
-                data.write_to_string(now),
-            )),
+            Some(data) => Ok(ProfileData {
+                profile_mode: ProfileMode::Statement,
+                profile: ProfileDataImpl::Stmt(data.info(now)),
+            }),
             None => Err(StmtProfileError::NotEnabled.into()),
         }
     }
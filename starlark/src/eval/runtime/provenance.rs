@@ -0,0 +1,275 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Provenance tags attached to values and propagated through evaluation,
+//! e.g. to track untrusted inputs flowing into sensitive functions.
+
+use std::collections::HashMap;
+
+use dupe::Dupe;
+use starlark_map::small_set::SmallSet;
+
+use crate::eval::Arguments;
+use crate::values::dict::DictRef;
+use crate::values::list::ListRef;
+use crate::values::tuple::TupleRef;
+use crate::values::Trace;
+use crate::values::Tracer;
+use crate::values::Value;
+use crate::values::ValueIdentity;
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ProvenanceError {
+    #[error("Provenance tracking is not enabled")]
+    NotEnabled,
+    #[error("Cannot tag `{0}`, only values allocated during this evaluation can be tagged")]
+    SharedValue(String),
+}
+
+/// Which operations propagate provenance tags from their inputs to their results,
+/// see [`Evaluator::enable_provenance`](crate::eval::Evaluator::enable_provenance).
+///
+/// All operations propagate tags by default.
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq)]
+pub struct ProvenancePropagation {
+    /// Results of operators like `+`, `%` or `-x` get the tags of the operands.
+    pub operators: bool,
+    /// Results of `x[i]`, `x[i:j]` and `x.field` get the tags of `x`.
+    pub indexing: bool,
+    /// Results of native functions and methods get the tags of the arguments
+    /// (and of the object for methods), and of the elements of the arguments which are
+    /// lists, tuples or dicts, so that e.g. `" ".join(xs)` is tagged if an element of `xs` is.
+    pub native_calls: bool,
+}
+
+impl Default for ProvenancePropagation {
+    fn default() -> Self {
+        ProvenancePropagation {
+            operators: true,
+            indexing: true,
+            native_calls: true,
+        }
+    }
+}
+
+/// Kind of operation producing a value.
+#[derive(Debug, Clone, Copy, Dupe)]
+pub(crate) enum ProvenanceOp {
+    Operator,
+    Indexing,
+    NativeCall,
+}
+
+/// Tags of the values of an evaluation.
+///
+/// Uses standard collections rather than `SmallMap`, so that dropping an evaluator
+/// does not require its heap to be alive.
+pub(crate) struct Provenance<'v> {
+    propagation: ProvenancePropagation,
+    /// Tagged values and their tags.
+    tags: Vec<(Value<'v>, SmallSet<String>)>,
+    /// Index in `tags` by value identity, rebuilt when values move during garbage collection.
+    index: HashMap<ValueIdentity<'v>, usize>,
+}
+
+unsafe impl<'v> Trace<'v> for Provenance<'v> {
+    fn trace(&mut self, tracer: &Tracer<'v>) {
+        self.index.clear();
+        for (i, (value, _)) in self.tags.iter_mut().enumerate() {
+            value.trace(tracer);
+            self.index.insert(value.identity(), i);
+        }
+    }
+}
+
+impl<'v> Provenance<'v> {
+    pub(crate) fn new(propagation: ProvenancePropagation) -> Self {
+        Provenance {
+            propagation,
+            tags: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    fn get(&self, value: Value<'v>) -> Option<&SmallSet<String>> {
+        self.index.get(&value.identity()).map(|i| &self.tags[*i].1)
+    }
+
+    fn get_or_insert(&mut self, value: Value<'v>) -> &mut SmallSet<String> {
+        let tags = &mut self.tags;
+        let i = *self.index.entry(value.identity()).or_insert_with(|| {
+            tags.push((value, SmallSet::new()));
+            tags.len() - 1
+        });
+        &mut self.tags[i].1
+    }
+
+    /// Values shared with other evaluations or other uses in this evaluation,
+    /// like `None`, small integers or constants, cannot carry tags.
+    fn can_tag(value: Value<'v>) -> bool {
+        value.unpack_frozen().is_none()
+    }
+
+    pub(crate) fn tag(&mut self, value: Value<'v>, tag: &str) -> anyhow::Result<()> {
+        if !Provenance::can_tag(value) {
+            return Err(ProvenanceError::SharedValue(value.to_repr()).into());
+        }
+        self.get_or_insert(value).insert(tag.to_owned());
+        Ok(())
+    }
+
+    pub(crate) fn tags(&self, value: Value<'v>) -> Vec<String> {
+        let mut res: Vec<String> = match self.get(value) {
+            Some(tags) => tags.iter().cloned().collect(),
+            None => Vec::new(),
+        };
+        res.sort();
+        res
+    }
+
+    /// Add the tags of the arguments of a native call to its result.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn propagate_to_call(
+        &mut self,
+        this: Option<Value<'v>>,
+        args: &Arguments<'v, '_>,
+        result: Value<'v>,
+    ) {
+        let mut inputs = args.0.inputs();
+        inputs.extend(this);
+        self.propagate(ProvenanceOp::NativeCall, &inputs, result);
+    }
+
+    /// Add the tags of `inputs` to `result` if the operation propagates tags.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn propagate(&mut self, op: ProvenanceOp, inputs: &[Value<'v>], result: Value<'v>) {
+        let enabled = match op {
+            ProvenanceOp::Operator => self.propagation.operators,
+            ProvenanceOp::Indexing => self.propagation.indexing,
+            ProvenanceOp::NativeCall => self.propagation.native_calls,
+        };
+        if !enabled || !Provenance::can_tag(result) {
+            return;
+        }
+        let mut tags = Vec::new();
+        let mut add_tags = |x: Value<'v>| {
+            if let Some(x) = self.get(x) {
+                tags.extend(x.iter().cloned());
+            }
+        };
+        for x in inputs {
+            add_tags(*x);
+            if let ProvenanceOp::NativeCall = op {
+                if let Some(xs) = ListRef::from_value(*x) {
+                    xs.iter().for_each(&mut add_tags);
+                } else if let Some(xs) = TupleRef::from_value(*x) {
+                    xs.iter().for_each(&mut add_tags);
+                } else if let Some(xs) = DictRef::from_value(*x) {
+                    for (k, v) in xs.iter() {
+                        add_tags(k);
+                        add_tags(v);
+                    }
+                }
+            }
+        }
+        if !tags.is_empty() {
+            self.get_or_insert(result).extend(tags);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as starlark;
+    use crate::environment::GlobalsBuilder;
+    use crate::environment::Module;
+    use crate::eval::Evaluator;
+    use crate::eval::ProvenancePropagation;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+    use crate::values::Value;
+
+    #[starlark_module]
+    fn taint_globals(globals: &mut GlobalsBuilder) {
+        /// A copy of `x` tagged as untrusted.
+        fn untrusted<'v>(x: &str, eval: &mut Evaluator<'v, '_>) -> anyhow::Result<Value<'v>> {
+            let res = eval.heap().alloc_str(x).to_value();
+            eval.tag_value(res, "untrusted")?;
+            Ok(res)
+        }
+
+        fn run<'v>(command: Value<'v>, eval: &mut Evaluator<'v, '_>) -> anyhow::Result<String> {
+            if !eval.value_tags(command).is_empty() {
+                return Err(anyhow::anyhow!("Untrusted command: {}", command));
+            }
+            Ok(command.to_str())
+        }
+    }
+
+    fn eval(propagation: ProvenancePropagation, program: &str) -> anyhow::Result<()> {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_provenance(propagation);
+        let globals = GlobalsBuilder::extended().with(taint_globals).build();
+        let ast = AstModule::parse("a.star", program.to_owned(), &Dialect::Extended)?;
+        eval.eval_module(ast, &globals)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_propagation() {
+        let all = ProvenancePropagation::default();
+        eval(all, "run('ls ' + 'x')").unwrap();
+        for program in [
+            "run(untrusted('xy'))",
+            "run('ls ' + untrusted('xy'))",
+            "run('ls %s' % untrusted('xy'))",
+            "run('ls {}'.format(untrusted('xy')))",
+            "run(untrusted('ls x')[1:])",
+            "run(untrusted('xy') * 2)",
+            "run(' '.join(['ls', untrusted('xy')]))",
+            "run(untrusted(' ls ').strip())",
+            "run(repr(untrusted('xy')))",
+        ] {
+            let err = eval(all, program).expect_err(program);
+            assert!(err.to_string().contains("Untrusted command"), "{}", program);
+        }
+
+        let no_calls = ProvenancePropagation {
+            native_calls: false,
+            ..all
+        };
+        eval(no_calls, "run(untrusted(' ls ').strip())").unwrap();
+        assert!(eval(no_calls, "run('ls ' + untrusted('xy'))").is_err());
+    }
+
+    #[test]
+    fn test_tag_errors() {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        let x = module.heap().alloc_str("xy").to_value();
+        assert!(eval.tag_value(x, "t").is_err());
+        assert!(eval.value_tags(x).is_empty());
+        eval.enable_provenance(ProvenancePropagation::default());
+        assert!(eval.tag_value(Value::new_none(), "t").is_err());
+        eval.tag_value(x, "b").unwrap();
+        eval.tag_value(x, "a").unwrap();
+        assert_eq!(vec!["a", "b"], eval.value_tags(x));
+    }
+}
@@ -0,0 +1,380 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Recording of the inputs of evaluations, to evaluate them again elsewhere.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use dupe::Dupe;
+
+use crate::codemap::CodeMap;
+use crate::codemap::FileSpanRef;
+use crate::environment::FrozenModule;
+use crate::environment::Globals;
+use crate::environment::GlobalsBuilder;
+use crate::environment::Module;
+use crate::eval::runtime::replay_trace::Tracer;
+use crate::eval::Evaluator;
+use crate::eval::FileLoader;
+use crate::eval::ReplayDivergence;
+use crate::eval::ReplayTrace;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+use crate::values::dict::AllocDict;
+use crate::values::list::AllocList;
+use crate::values::AllocFrozenValue;
+use crate::values::FrozenHeap;
+use crate::values::FrozenValue;
+
+#[derive(Debug, thiserror::Error)]
+enum RecordingError {
+    #[error("Recording contains no files evaluated by the embedder")]
+    NoTopLevelFiles,
+    #[error("Module `{0}` loaded by `{1}` was not recorded")]
+    NotRecorded(String, String),
+}
+
+/// Everything evaluations consumed, recorded by an [`EvalRecorder`]:
+/// the evaluated files with their dialects, the globals and the modules they loaded.
+///
+/// It can be serialized, for example to JSON, and evaluated again with
+/// [`replay`](EvalRecording::replay) without access to the original files.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EvalRecording {
+    /// Evaluated files, in the order their evaluation started.
+    pub files: Vec<RecordedFile>,
+    /// Distinct globals the files were evaluated with.
+    pub globals: Vec<Vec<RecordedGlobal>>,
+    /// Seed of the random number generator of the embedder, see [`EvalRecorder::set_seed`].
+    pub seed: Option<u64>,
+}
+
+/// A file evaluated while recording.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RecordedFile {
+    /// File name, as given when parsing the file.
+    pub path: String,
+    /// Contents of the file.
+    pub contents: String,
+    /// Dialect the file was parsed with.
+    pub dialect: Dialect,
+    /// Index in [`EvalRecording::globals`] of the globals the file was evaluated with.
+    pub globals: usize,
+    /// Whether the file was evaluated by the embedder, not loaded by another file.
+    pub top_level: bool,
+    /// Modules loaded by the file, in the order of evaluation.
+    pub loads: Vec<RecordedLoad>,
+}
+
+/// A `load()` statement evaluated while recording.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RecordedLoad {
+    /// Path given to `load()`.
+    pub path: String,
+    /// Index in [`EvalRecording::files`] of the loaded file,
+    /// or `None` if the loaded module was not evaluated while recording,
+    /// for example because the embedder created it beforehand.
+    pub file: Option<usize>,
+}
+
+/// A global variable.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RecordedGlobal {
+    /// Name of the variable.
+    pub name: String,
+    /// Value serialized with its [`serde::Serialize`] implementation,
+    /// or `None` if it cannot be restored from the serialized form, like a native function.
+    pub value: Option<serde_json::Value>,
+}
+
+/// A serialized value, allocated as the Starlark value it was serialized from.
+struct SerializedValue(serde_json::Value);
+
+impl AllocFrozenValue for SerializedValue {
+    fn alloc_frozen_value(self, heap: &FrozenHeap) -> FrozenValue {
+        match self.0 {
+            serde_json::Value::Null => FrozenValue::new_none(),
+            serde_json::Value::Bool(x) => FrozenValue::new_bool(x),
+            serde_json::Value::Number(x) => match (x.as_i64(), x.as_u64()) {
+                (Some(x), _) => heap.alloc(x),
+                (None, Some(x)) => heap.alloc(x),
+                (None, None) => heap.alloc(x.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(x) => heap.alloc(x),
+            serde_json::Value::Array(x) => {
+                heap.alloc(AllocList(x.into_iter().map(SerializedValue)))
+            }
+            serde_json::Value::Object(x) => heap.alloc(AllocDict(
+                x.into_iter().map(|(k, v)| (k, SerializedValue(v))),
+            )),
+        }
+    }
+}
+
+/// Serialize a global, if the value allocated from the serialized form is the same.
+fn record_global(value: FrozenValue) -> Option<serde_json::Value> {
+    let serialized = serde_json::to_value(value.to_value()).ok()?;
+    let heap = FrozenHeap::new();
+    let restored = heap.alloc(SerializedValue(serialized.clone()));
+    (restored.to_value().to_repr() == value.to_value().to_repr()).then_some(serialized)
+}
+
+#[derive(Default)]
+struct RecorderState {
+    recording: EvalRecording,
+    /// Globals already recorded and their index in [`EvalRecording::globals`].
+    globals: Vec<(Globals, usize)>,
+    /// Modules produced by `load()` statements and their files.
+    modules: Vec<(FrozenModule, usize)>,
+    /// Files being evaluated.
+    stack: Vec<usize>,
+}
+
+/// Records the inputs of evaluations into an [`EvalRecording`],
+/// to reproduce their results, for example failures reported by users, elsewhere.
+///
+/// Install it on evaluators with [`Evaluator::set_recorder`],
+/// including the evaluators of loaded files, which [`FsFileLoader`](crate::eval::FsFileLoader)
+/// does with [`set_recorder`](crate::eval::FsFileLoader::set_recorder).
+/// Native functions are recorded by name only, so the replay has to provide them.
+#[derive(Default)]
+pub struct EvalRecorder(Mutex<RecorderState>);
+
+impl EvalRecorder {
+    /// Create a recorder with nothing recorded.
+    pub fn new() -> EvalRecorder {
+        EvalRecorder::default()
+    }
+
+    /// Record the seed of the random number generator of the embedder,
+    /// for native functions with random results.
+    pub fn set_seed(&self, seed: u64) {
+        self.0.lock().unwrap().recording.seed = Some(seed);
+    }
+
+    /// Start the evaluation of a file.
+    pub(crate) fn enter_file(&self, codemap: &CodeMap, dialect: &Dialect, globals: &Globals) {
+        let mut state = self.0.lock().unwrap();
+        let globals = match state.globals.iter().find(|(x, _)| x.ptr_eq(globals)) {
+            Some((_, index)) => *index,
+            None => {
+                let mut names: Vec<_> = globals.names().map(|x| x.as_str().to_owned()).collect();
+                names.sort();
+                let recorded: Vec<_> = names
+                    .into_iter()
+                    .map(|name| RecordedGlobal {
+                        value: globals.get_frozen(&name).and_then(record_global),
+                        name,
+                    })
+                    .collect();
+                // Embedders often create equal globals for each file.
+                let index = match state.recording.globals.iter().position(|x| *x == recorded) {
+                    Some(index) => index,
+                    None => {
+                        state.recording.globals.push(recorded);
+                        state.recording.globals.len() - 1
+                    }
+                };
+                state.globals.push((globals.dupe(), index));
+                index
+            }
+        };
+        let index = state.recording.files.len();
+        let top_level = state.stack.is_empty();
+        state.recording.files.push(RecordedFile {
+            path: codemap.filename().to_owned(),
+            contents: codemap.source().to_owned(),
+            dialect: dialect.clone(),
+            globals,
+            top_level,
+            loads: Vec::new(),
+        });
+        state.stack.push(index);
+    }
+
+    /// Finish the evaluation of the file started last.
+    pub(crate) fn exit_file(&self) {
+        self.0.lock().unwrap().stack.pop();
+    }
+
+    /// Record a `load()` statement of the file being evaluated, performed by `load`.
+    pub(crate) fn record_load(
+        &self,
+        path: &str,
+        load: impl FnOnce() -> anyhow::Result<FrozenModule>,
+    ) -> anyhow::Result<FrozenModule> {
+        let files = self.0.lock().unwrap().recording.files.len();
+        // Not locked, the loaded file may be evaluated with this recorder.
+        let res = load();
+        let mut state = self.0.lock().unwrap();
+        // The loaded file is the first file evaluated during the load.
+        let mut file = (state.recording.files.len() > files).then_some(files);
+        if let Ok(module) = &res {
+            match state.modules.iter().find(|(x, _)| x.ptr_eq(module)) {
+                Some((_, index)) => file = Some(*index),
+                None => {
+                    if let Some(file) = file {
+                        state.modules.push((module.dupe(), file));
+                    }
+                }
+            }
+        }
+        if let Some(current) = state.stack.last().copied() {
+            state.recording.files[current].loads.push(RecordedLoad {
+                path: path.to_owned(),
+                file,
+            });
+        }
+        res
+    }
+
+    /// The recording.
+    pub fn finish(self) -> EvalRecording {
+        self.0.into_inner().unwrap().recording
+    }
+}
+
+/// State of a replay shared by the loaders of all files.
+struct Replay<'r> {
+    recording: &'r EvalRecording,
+    globals: Vec<Globals>,
+    modules: RefCell<HashMap<usize, FrozenModule>>,
+    tracer: Option<&'r Tracer>,
+}
+
+impl<'r> Replay<'r> {
+    fn eval_file(&self, index: usize) -> anyhow::Result<FrozenModule> {
+        let file = &self.recording.files[index];
+        let ast = AstModule::parse(&file.path, file.contents.clone(), &file.dialect)?;
+        let module = Module::new();
+        {
+            let loader = ReplayLoader {
+                replay: self,
+                file: index,
+            };
+            let before_stmt = |span: FileSpanRef, eval: &mut Evaluator<'_, '_>| {
+                if let Some(tracer) = self.tracer {
+                    tracer.step(span.to_string(), eval);
+                }
+            };
+            let mut eval = Evaluator::new(&module);
+            eval.set_loader(&loader);
+            if self.tracer.is_some() {
+                eval.before_stmt(&before_stmt);
+            }
+            eval.eval_module(ast, &self.globals[file.globals])?;
+            if let Some(tracer) = self.tracer {
+                tracer.step(format!("end of {}", file.path), &eval);
+            }
+        }
+        module.freeze()
+    }
+}
+
+/// Resolves the `load()` statements of a file to the recorded files.
+struct ReplayLoader<'a, 'r> {
+    replay: &'a Replay<'r>,
+    file: usize,
+}
+
+impl<'a, 'r> FileLoader for ReplayLoader<'a, 'r> {
+    fn load(&self, path: &str) -> anyhow::Result<FrozenModule> {
+        let file = &self.replay.recording.files[self.file];
+        let index = file
+            .loads
+            .iter()
+            .find(|x| x.path == path)
+            .and_then(|x| x.file)
+            .ok_or_else(|| RecordingError::NotRecorded(path.to_owned(), file.path.clone()))?;
+        if let Some(module) = self.replay.modules.borrow().get(&index) {
+            return Ok(module.dupe());
+        }
+        let module = self.replay.eval_file(index)?;
+        self.replay
+            .modules
+            .borrow_mut()
+            .insert(index, module.dupe());
+        Ok(module)
+    }
+}
+
+impl EvalRecording {
+    /// Evaluate the files evaluated by the embedder again, in order, each in a new module,
+    /// with `load()` statements resolved to the recorded files.
+    ///
+    /// The recorded globals are set on builders created by `globals`,
+    /// which must provide the globals which could not be recorded, like native functions.
+    /// Returns the module of the last file, or the first error.
+    pub fn replay(&self, globals: impl Fn() -> GlobalsBuilder) -> anyhow::Result<FrozenModule> {
+        self.replay_with(globals, None)
+    }
+
+    /// [`replay`](EvalRecording::replay) the recording, tracing the executed statements
+    /// and the values of the variables they change.
+    pub fn replay_trace(&self, globals: impl Fn() -> GlobalsBuilder) -> ReplayTrace {
+        let tracer = Tracer::default();
+        let res = self.replay_with(globals, Some(&tracer));
+        tracer.finish(&res)
+    }
+
+    /// Replay the recording twice and compare the traces, to find nondeterminism
+    /// in the evaluation or in the native functions provided by `globals`.
+    /// Returns the first divergence, or `None` if both replays behaved the same.
+    pub fn check_deterministic(
+        &self,
+        globals: impl Fn() -> GlobalsBuilder,
+    ) -> Option<ReplayDivergence> {
+        let first = self.replay_trace(&globals);
+        let second = self.replay_trace(&globals);
+        first.first_divergence(&second)
+    }
+
+    fn replay_with(
+        &self,
+        globals: impl Fn() -> GlobalsBuilder,
+        tracer: Option<&Tracer>,
+    ) -> anyhow::Result<FrozenModule> {
+        let globals = self
+            .globals
+            .iter()
+            .map(|recorded| {
+                let mut builder = globals();
+                for x in recorded {
+                    if let Some(value) = &x.value {
+                        builder.set(&x.name, SerializedValue(value.clone()));
+                    }
+                }
+                builder.build()
+            })
+            .collect();
+        let replay = Replay {
+            recording: self,
+            globals,
+            modules: RefCell::new(HashMap::new()),
+            tracer,
+        };
+        let mut res = Err(RecordingError::NoTopLevelFiles.into());
+        for (index, file) in self.files.iter().enumerate() {
+            if file.top_level {
+                res = Ok(replay.eval_file(index)?);
+            }
+        }
+        res
+    }
+}
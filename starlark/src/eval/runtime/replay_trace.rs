@@ -0,0 +1,261 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Traces of replayed evaluations, to find where two replays diverge.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::Display;
+
+use crate::collections::SmallMap;
+use crate::eval::Evaluator;
+
+/// The statements executed by a replay of an [`EvalRecording`](crate::eval::EvalRecording)
+/// and the values of the variables they changed, produced by
+/// [`replay_trace`](crate::eval::EvalRecording::replay_trace).
+///
+/// Traces are serializable, so traces produced by different versions of the crate
+/// can be compared with [`first_divergence`](ReplayTrace::first_divergence).
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ReplayTrace {
+    /// Steps of the evaluation, in order.
+    pub steps: Vec<TraceStep>,
+    /// Error the evaluation failed with.
+    pub error: Option<String>,
+}
+
+/// A step of a [`ReplayTrace`]: a statement about to be executed, or the end of a file.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TraceStep {
+    /// Location of the statement, or `end of <file>`.
+    pub location: String,
+    /// Variables in scope whose `repr` changed since the previous step, with the new `repr`.
+    pub changed: Vec<(String, String)>,
+}
+
+impl TraceStep {
+    fn value(&self, name: &str) -> Option<&str> {
+        self.changed
+            .iter()
+            .find(|(x, _)| x == name)
+            .map(|(_, x)| x.as_str())
+    }
+
+    /// Describe how the steps differ, if they do.
+    fn difference(&self, other: &TraceStep) -> Option<(String, String)> {
+        if self.location != other.location {
+            return Some((self.location.clone(), other.location.clone()));
+        }
+        let names = self.changed.iter().chain(&other.changed).map(|(x, _)| x);
+        for name in names {
+            let (left, right) = (self.value(name), other.value(name));
+            if left != right {
+                let describe = |value: Option<&str>| match value {
+                    Some(value) => format!("`{}` = {}", name, value),
+                    None => format!("`{}` unchanged", name),
+                };
+                return Some((describe(left), describe(right)));
+            }
+        }
+        None
+    }
+}
+
+/// The first difference between two [`ReplayTrace`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayDivergence {
+    /// Index of the first step which differs.
+    pub step: usize,
+    /// Location of the last step both traces have in common,
+    /// usually the statement which behaved differently.
+    pub after: Option<String>,
+    /// The divergent step of the first trace.
+    pub left: String,
+    /// The divergent step of the second trace.
+    pub right: String,
+}
+
+impl Display for ReplayDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Replays diverge at step {}", self.step)?;
+        if let Some(after) = &self.after {
+            write!(f, " after {}", after)?;
+        }
+        write!(f, ": {} vs {}", self.left, self.right)
+    }
+}
+
+impl ReplayTrace {
+    /// Describe the end of the trace.
+    fn end(&self) -> String {
+        match &self.error {
+            Some(error) => format!("error: {}", error),
+            None => "end of evaluation".to_owned(),
+        }
+    }
+
+    /// The first step where this trace and `other` execute different statements,
+    /// or where a variable has a different value, or the different errors the replays
+    /// ended with. `None` if the traces are the same.
+    pub fn first_divergence(&self, other: &ReplayTrace) -> Option<ReplayDivergence> {
+        let len = self.steps.len().max(other.steps.len());
+        for step in 0..=len {
+            let (left, right) = match (self.steps.get(step), other.steps.get(step)) {
+                (Some(left), Some(right)) => match left.difference(right) {
+                    Some(difference) => difference,
+                    None => continue,
+                },
+                (left, right) => {
+                    let describe = |x: Option<&TraceStep>, trace: &ReplayTrace| match x {
+                        Some(x) => x.location.clone(),
+                        None => trace.end(),
+                    };
+                    (describe(left, self), describe(right, other))
+                }
+            };
+            if left == right {
+                return None;
+            }
+            return Some(ReplayDivergence {
+                step,
+                after: step.checked_sub(1).map(|x| self.steps[x].location.clone()),
+                left,
+                right,
+            });
+        }
+        None
+    }
+}
+
+/// Builds a [`ReplayTrace`] from the files evaluated by a replay.
+#[derive(Default)]
+pub(crate) struct Tracer {
+    trace: RefCell<ReplayTrace>,
+    /// `repr` of the variables in scope at the last step.
+    last: RefCell<SmallMap<String, String>>,
+}
+
+impl Tracer {
+    pub(crate) fn step(&self, location: String, eval: &Evaluator) {
+        let variables: SmallMap<String, String> = eval
+            .local_variables()
+            .into_iter()
+            .map(|(name, value)| (name, value.to_repr()))
+            .collect();
+        let mut last = self.last.borrow_mut();
+        let changed = variables
+            .iter()
+            .filter(|(name, value)| last.get(*name) != Some(*value))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        *last = variables;
+        self.trace
+            .borrow_mut()
+            .steps
+            .push(TraceStep { location, changed });
+    }
+
+    pub(crate) fn finish<T>(self, res: &anyhow::Result<T>) -> ReplayTrace {
+        let mut trace = self.trace.into_inner();
+        trace.error = res.as_ref().err().map(|e| format!("{:#}", e));
+        trace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicI32;
+    use std::sync::atomic::Ordering;
+
+    use crate as starlark;
+    use crate::environment::GlobalsBuilder;
+    use crate::environment::Module;
+    use crate::eval::EvalRecorder;
+    use crate::eval::EvalRecording;
+    use crate::eval::Evaluator;
+    use crate::eval::ReplayDivergence;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    static COUNTER: AtomicI32 = AtomicI32::new(0);
+
+    #[starlark_module]
+    fn counter_globals(globals: &mut GlobalsBuilder) {
+        fn next() -> anyhow::Result<i32> {
+            Ok(COUNTER.fetch_add(1, Ordering::SeqCst))
+        }
+    }
+
+    fn globals() -> GlobalsBuilder {
+        GlobalsBuilder::extended().with(counter_globals)
+    }
+
+    fn record(program: &str) -> EvalRecording {
+        let recorder = EvalRecorder::new();
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.set_recorder(&recorder);
+        let ast = AstModule::parse("a.star", program.to_owned(), &Dialect::Extended).unwrap();
+        // The evaluation may fail, the recording is still complete.
+        drop(eval.eval_module(ast, &globals().build()));
+        recorder.finish()
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let recording = record("def f(x):\n    return sorted(x)\ny = f({2: 1, 1: 2})");
+        assert_eq!(None, recording.check_deterministic(globals));
+        let trace = recording.replay_trace(globals);
+        assert_eq!(None, trace.error);
+        let last = trace.steps.last().unwrap();
+        assert_eq!("end of a.star", last.location);
+        assert!(
+            last.changed
+                .contains(&("y".to_owned(), "[1, 2]".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_nondeterministic() {
+        let recording = record("x = 1\ny = next()\nz = x + 1");
+        let divergence = recording.check_deterministic(globals).unwrap();
+        assert_eq!(2, divergence.step);
+        assert_eq!(Some("a.star:2:1-11"), divergence.after.as_deref());
+        assert!(divergence.left.starts_with("`y` = "), "{}", divergence);
+        assert_ne!(divergence.left, divergence.right);
+    }
+
+    #[test]
+    fn test_first_divergence() {
+        let trace = record("x = [1]\ny = x[0]").replay_trace(globals);
+        let other = record("x = [2]\ny = x[0]").replay_trace(globals);
+        assert_eq!(
+            Some(ReplayDivergence {
+                step: 1,
+                after: Some("a.star:1:1-8".to_owned()),
+                left: "`x` = [1]".to_owned(),
+                right: "`x` = [2]".to_owned(),
+            }),
+            trace.first_divergence(&other)
+        );
+        let failing = record("x = [1]\ny = x[1]").replay_trace(globals);
+        let divergence = trace.first_divergence(&failing).unwrap();
+        assert_eq!(2, divergence.step);
+        assert_eq!("end of a.star", divergence.left);
+        assert!(divergence.right.starts_with("error: "), "{}", divergence);
+    }
+}
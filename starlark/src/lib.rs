@@ -391,9 +391,11 @@ pub use starlark_derive::StarlarkDocs;
 
 pub(crate) mod analysis;
 pub mod assert;
+pub mod batch;
+pub mod bench;
 pub mod codemap;
 pub mod collections;
-mod debug;
+pub mod debug;
 pub mod docs;
 pub mod environment;
 pub mod errors;
@@ -402,6 +404,7 @@ pub mod lsp;
 mod private;
 pub mod read_line;
 mod sealed;
+pub mod stable;
 
 mod hint;
 mod stdlib;
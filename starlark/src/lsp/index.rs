@@ -0,0 +1,147 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Bookkeeping for the background analysis of all the files in the workspace.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::hash::Hasher;
+
+use crate::lsp::server::LspUrl;
+
+/// Files waiting to be analyzed, and the contents each file was last analyzed with,
+/// so that unchanged files are not analyzed again.
+#[derive(Default, Debug)]
+pub(crate) struct WorkspaceIndex {
+    queue: VecDeque<LspUrl>,
+    /// Files in the queue.
+    queued: HashSet<LspUrl>,
+    /// Result id of the last analysis of each file.
+    analyzed: HashMap<LspUrl, String>,
+    /// Incremented when the settings change, so all the files are analyzed again.
+    generation: u64,
+    /// Files taken from the queue since it was last empty.
+    done: usize,
+}
+
+impl WorkspaceIndex {
+    /// Queue files for analysis. Priority files, like the files open in the editor,
+    /// go to the front of the queue.
+    pub(crate) fn enqueue(&mut self, files: impl IntoIterator<Item = LspUrl>, priority: bool) {
+        for file in files {
+            if priority {
+                if self.queued.contains(&file) {
+                    self.queue.retain(|x| x != &file);
+                }
+                self.queue.push_front(file.clone());
+            } else if !self.queued.contains(&file) {
+                self.queue.push_back(file.clone());
+            }
+            self.queued.insert(file);
+        }
+    }
+
+    /// Take the next file to analyze.
+    pub(crate) fn next(&mut self) -> Option<LspUrl> {
+        let file = self.queue.pop_front()?;
+        self.queued.remove(&file);
+        self.done += 1;
+        Some(file)
+    }
+
+    /// Is there nothing to analyze?
+    pub(crate) fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Files analyzed and the total number of files, since the queue was last empty.
+    pub(crate) fn progress(&self) -> (usize, usize) {
+        (self.done, self.done + self.queue.len())
+    }
+
+    /// Reset the progress counter, called when the queue becomes empty.
+    pub(crate) fn reset_progress(&mut self) {
+        self.done = 0;
+    }
+
+    /// Identifier of the diagnostics for `contents` with the current settings.
+    /// Same contents and settings produce the same diagnostics.
+    pub(crate) fn result_id(&self, contents: &str) -> String {
+        let mut hasher = DefaultHasher::new();
+        contents.hash(&mut hasher);
+        format!("{}-{:x}", self.generation, hasher.finish())
+    }
+
+    /// Record that `file` is analyzed with `contents`.
+    /// Return `false` if it was already analyzed with the same contents and settings.
+    pub(crate) fn update(&mut self, file: &LspUrl, contents: &str) -> bool {
+        let result_id = self.result_id(contents);
+        match self.analyzed.get(file) {
+            Some(previous) if *previous == result_id => false,
+            _ => {
+                self.analyzed.insert(file.clone(), result_id);
+                true
+            }
+        }
+    }
+
+    /// The settings changed, so the previous analysis results are no longer valid.
+    pub(crate) fn invalidate(&mut self) {
+        self.generation += 1;
+        self.analyzed.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::lsp::index::WorkspaceIndex;
+    use crate::lsp::server::LspUrl;
+
+    fn file(name: &str) -> LspUrl {
+        LspUrl::File(PathBuf::from("/ws").join(name))
+    }
+
+    #[test]
+    fn test_priority_and_progress() {
+        let mut index = WorkspaceIndex::default();
+        index.enqueue([file("a"), file("b"), file("c")], false);
+        index.enqueue([file("c")], true);
+        index.enqueue([file("a")], false);
+        assert_eq!((0, 3), index.progress());
+        assert_eq!(Some(file("c")), index.next());
+        assert_eq!(Some(file("a")), index.next());
+        assert_eq!((2, 3), index.progress());
+        assert_eq!(Some(file("b")), index.next());
+        assert_eq!(None, index.next());
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn test_update() {
+        let mut index = WorkspaceIndex::default();
+        assert!(index.update(&file("a"), "x = 1"));
+        assert!(!index.update(&file("a"), "x = 1"));
+        assert!(index.update(&file("a"), "x = 2"));
+        index.invalidate();
+        assert!(index.update(&file("a"), "x = 2"));
+    }
+}
@@ -18,9 +18,12 @@
 //! Based on the reference lsp-server example at <https://github.com/rust-analyzer/lsp-server/blob/master/examples/goto_def.rs>.
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::RwLock;
 
@@ -35,42 +38,85 @@ use lsp_server::Request;
 use lsp_server::RequestId;
 use lsp_server::Response;
 use lsp_server::ResponseError;
+use lsp_types::notification::DidChangeConfiguration;
 use lsp_types::notification::DidChangeTextDocument;
+use lsp_types::notification::DidChangeWorkspaceFolders;
 use lsp_types::notification::DidCloseTextDocument;
 use lsp_types::notification::DidOpenTextDocument;
 use lsp_types::notification::LogMessage;
+use lsp_types::notification::Progress;
 use lsp_types::notification::PublishDiagnostics;
+use lsp_types::request::CallHierarchyIncomingCalls;
+use lsp_types::request::CallHierarchyOutgoingCalls;
+use lsp_types::request::CallHierarchyPrepare;
+use lsp_types::request::FoldingRangeRequest;
 use lsp_types::request::GotoDefinition;
+use lsp_types::request::SelectionRangeRequest;
+use lsp_types::request::WorkDoneProgressCreate;
+use lsp_types::CallHierarchyIncomingCall;
+use lsp_types::CallHierarchyIncomingCallsParams;
+use lsp_types::CallHierarchyItem;
+use lsp_types::CallHierarchyOutgoingCall;
+use lsp_types::CallHierarchyOutgoingCallsParams;
+use lsp_types::CallHierarchyPrepareParams;
+use lsp_types::CallHierarchyServerCapability;
 use lsp_types::DefinitionOptions;
 use lsp_types::Diagnostic;
+use lsp_types::DidChangeConfigurationParams;
 use lsp_types::DidChangeTextDocumentParams;
+use lsp_types::DidChangeWorkspaceFoldersParams;
 use lsp_types::DidCloseTextDocumentParams;
 use lsp_types::DidOpenTextDocumentParams;
+use lsp_types::FoldingRange;
+use lsp_types::FoldingRangeKind;
+use lsp_types::FoldingRangeParams;
+use lsp_types::FoldingRangeProviderCapability;
 use lsp_types::GotoDefinitionParams;
 use lsp_types::GotoDefinitionResponse;
 use lsp_types::InitializeParams;
 use lsp_types::LocationLink;
 use lsp_types::LogMessageParams;
 use lsp_types::MessageType;
+use lsp_types::NumberOrString;
 use lsp_types::OneOf;
+use lsp_types::ProgressParams;
+use lsp_types::ProgressParamsValue;
 use lsp_types::PublishDiagnosticsParams;
 use lsp_types::Range;
+use lsp_types::SelectionRange;
+use lsp_types::SelectionRangeParams;
+use lsp_types::SelectionRangeProviderCapability;
 use lsp_types::ServerCapabilities;
+use lsp_types::SymbolKind;
+use lsp_types::TextDocumentIdentifier;
+use lsp_types::TextDocumentPositionParams;
 use lsp_types::TextDocumentSyncCapability;
 use lsp_types::TextDocumentSyncKind;
 use lsp_types::Url;
+use lsp_types::WorkDoneProgress;
+use lsp_types::WorkDoneProgressBegin;
+use lsp_types::WorkDoneProgressCreateParams;
+use lsp_types::WorkDoneProgressEnd;
 use lsp_types::WorkDoneProgressOptions;
+use lsp_types::WorkDoneProgressReport;
+use lsp_types::WorkspaceFolder;
+use lsp_types::WorkspaceFoldersServerCapabilities;
+use lsp_types::WorkspaceServerCapabilities;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Deserializer;
 use serde::Serialize;
 use serde::Serializer;
 
+use crate::analysis::DefinedSymbol;
 use crate::analysis::Definition;
 use crate::analysis::DottedDefinition;
+use crate::analysis::FoldingRegionKind;
 use crate::analysis::IdentifierDefinition;
 use crate::analysis::LspModule;
 use crate::codemap::ResolvedSpan;
+use crate::collections::SmallMap;
+use crate::lsp::index::WorkspaceIndex;
 use crate::lsp::server::LoadContentsError::WrongScheme;
 use crate::syntax::AstModule;
 
@@ -97,6 +143,89 @@ struct StarlarkFileContentsResponse {
     contents: Option<String>,
 }
 
+/// The `textDocument/diagnostic` request, to pull the diagnostics of a file.
+/// It was added in LSP 3.17, so it is not yet available in `lsp_types`.
+struct DocumentDiagnosticRequest {}
+
+impl lsp_types::request::Request for DocumentDiagnosticRequest {
+    type Params = DocumentDiagnosticParams;
+    type Result = DocumentDiagnosticReport;
+    const METHOD: &'static str = "textDocument/diagnostic";
+}
+
+/// Params to pull the diagnostics of a file.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")] // camelCase to match idioms in LSP spec / typescript land.
+struct DocumentDiagnosticParams {
+    text_document: TextDocumentIdentifier,
+    /// The result id of the previous report for this file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    previous_result_id: Option<String>,
+}
+
+/// The diagnostics of a file, or a note that they did not change since the previous report.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum DocumentDiagnosticReport {
+    #[serde(rename_all = "camelCase")]
+    Full {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        result_id: Option<String>,
+        items: Vec<Diagnostic>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Unchanged { result_id: String },
+}
+
+/// The `textDocument/prepareTypeHierarchy` request, to find the type at a position.
+/// Type hierarchy was added in LSP 3.17, so it is not yet available in `lsp_types`.
+struct TypeHierarchyPrepareRequest {}
+
+impl lsp_types::request::Request for TypeHierarchyPrepareRequest {
+    type Params = TypeHierarchyPrepareParams;
+    type Result = Option<Vec<TypeHierarchyItem>>;
+    const METHOD: &'static str = "textDocument/prepareTypeHierarchy";
+}
+
+/// The `typeHierarchy/supertypes` request.
+struct TypeHierarchySupertypesRequest {}
+
+impl lsp_types::request::Request for TypeHierarchySupertypesRequest {
+    type Params = TypeHierarchyParams;
+    type Result = Option<Vec<TypeHierarchyItem>>;
+    const METHOD: &'static str = "typeHierarchy/supertypes";
+}
+
+/// The `typeHierarchy/subtypes` request.
+struct TypeHierarchySubtypesRequest {}
+
+impl lsp_types::request::Request for TypeHierarchySubtypesRequest {
+    type Params = TypeHierarchyParams;
+    type Result = Option<Vec<TypeHierarchyItem>>;
+    const METHOD: &'static str = "typeHierarchy/subtypes";
+}
+
+/// Params to find the type at a position.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")] // camelCase to match idioms in LSP spec / typescript land.
+struct TypeHierarchyPrepareParams {
+    #[serde(flatten)]
+    text_document_position_params: TextDocumentPositionParams,
+}
+
+/// Params to find the supertypes or the subtypes of a type.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")] // camelCase to match idioms in LSP spec / typescript land.
+struct TypeHierarchyParams {
+    item: TypeHierarchyItem,
+}
+
+/// A type hierarchy item has the same fields as a call hierarchy item.
+type TypeHierarchyItem = CallHierarchyItem;
+
+/// Token of the progress notifications of the background workspace analysis.
+const WORKSPACE_ANALYSIS_PROGRESS_TOKEN: &str = "starlark/workspaceAnalysis";
+
 /// Errors that can happen when converting LspUrl and Url to/from each other.
 #[derive(thiserror::Error, Debug)]
 pub enum LspUrlError {
@@ -232,18 +361,59 @@ pub struct LspEvalResult {
     pub ast: Option<AstModule>,
 }
 
+/// A root folder of the workspace opened in the client.
+///
+/// A client can open several roots at once, for example different projects of a monorepo,
+/// and the [`LspContext`] can keep separate settings and loaders for each of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LspWorkspaceRoot {
+    /// The name of the root in the client.
+    pub name: String,
+    /// The location of the root.
+    pub url: LspUrl,
+}
+
+impl LspWorkspaceRoot {
+    /// Find the innermost root containing the file at `url`.
+    pub fn find<'a>(roots: &'a [LspWorkspaceRoot], url: &LspUrl) -> Option<&'a LspWorkspaceRoot> {
+        roots
+            .iter()
+            .filter(|root| match (&root.url, url) {
+                (LspUrl::File(root), LspUrl::File(path)) => path.starts_with(root),
+                _ => false,
+            })
+            .max_by_key(|root| root.url.path().components().count())
+    }
+}
+
+impl TryFrom<WorkspaceFolder> for LspWorkspaceRoot {
+    type Error = LspUrlError;
+
+    fn try_from(folder: WorkspaceFolder) -> Result<Self, Self::Error> {
+        Ok(LspWorkspaceRoot {
+            name: folder.name,
+            url: folder.uri.try_into()?,
+        })
+    }
+}
+
 /// Settings that the LspContext can provide to change what capabilities the server enables
 /// or disables.
 #[derive(Dupe, Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct LspServerSettings {
     /// Whether goto definition should work.
     pub enable_goto_definition: bool,
+    /// Whether to analyze all the files of the workspace in the background,
+    /// and publish their diagnostics, not only the diagnostics of the open files.
+    #[serde(default)]
+    pub enable_workspace_analysis: bool,
 }
 
 impl Default for LspServerSettings {
     fn default() -> Self {
         Self {
             enable_goto_definition: true,
+            enable_workspace_analysis: false,
         }
     }
 }
@@ -251,6 +421,9 @@ impl Default for LspServerSettings {
 /// Various pieces of context to allow the LSP to interact with starlark parsers, etc.
 pub trait LspContext {
     /// Parse a file with the given contents. The filename is used in the diagnostics.
+    ///
+    /// Implementations which handle several kinds of files can choose the dialect
+    /// with a [`FileKindResolver`](crate::environment::FileKindResolver) shared with the evaluator.
     fn parse_file_with_contents(&self, uri: &LspUrl, content: String) -> LspEvalResult;
 
     /// Resolve a path given in a `load()` statement.
@@ -293,6 +466,28 @@ pub trait LspContext {
         current_file: &LspUrl,
         symbol: &str,
     ) -> anyhow::Result<Option<LspUrl>>;
+
+    /// The workspace roots opened in the client, called after initialization
+    /// and whenever the client adds or removes roots.
+    ///
+    /// Contexts serving several roots can use [`LspWorkspaceRoot::find`]
+    /// to choose the settings and the loader for a file.
+    fn set_workspace_roots(&self, _roots: &[LspWorkspaceRoot]) {}
+
+    /// The client changed the settings with `workspace/didChangeConfiguration`.
+    /// `settings` is the JSON sent by the client, its format is defined by the context.
+    ///
+    /// Open files are parsed again after this call, so their diagnostics use the new settings.
+    fn set_configuration(&self, _settings: &serde_json::Value) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// All the Starlark files in the workspace roots, for the background analysis enabled with
+    /// [`enable_workspace_analysis`](LspServerSettings::enable_workspace_analysis).
+    /// The files are read with [`get_load_contents`](LspContext::get_load_contents).
+    fn workspace_files(&self, _roots: &[LspWorkspaceRoot]) -> anyhow::Result<Vec<LspUrl>> {
+        Ok(Vec::new())
+    }
 }
 
 /// Errors when [`LspContext::resolve_load()`] cannot resolve a given path.
@@ -317,6 +512,17 @@ struct Backend<T: LspContext> {
     /// The `AstModule` from the last time that a file was opened / changed and parsed successfully.
     /// Entries are evicted when the file is closed.
     last_valid_parse: RwLock<HashMap<LspUrl, Arc<LspModule>>>,
+    /// Version and contents of the open files, to check them again when the settings change.
+    open_files: RwLock<HashMap<LspUrl, (Option<i64>, String)>>,
+    /// The workspace roots opened in the client.
+    roots: RwLock<Vec<LspWorkspaceRoot>>,
+    settings: LspServerSettings,
+    /// Files waiting for the background analysis.
+    index: RwLock<WorkspaceIndex>,
+    /// Whether the client shows progress started by the server.
+    work_done_progress: bool,
+    /// Id of the last request sent to the client.
+    last_request_id: AtomicI32,
 }
 
 /// The logic implementations of stuff
@@ -329,9 +535,20 @@ impl<T: LspContext> Backend<T> {
                 },
             })
         });
+        let workspace = WorkspaceServerCapabilities {
+            workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                supported: Some(true),
+                change_notifications: Some(OneOf::Left(true)),
+            }),
+            file_operations: None,
+        };
         ServerCapabilities {
             text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
             definition_provider,
+            call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+            selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+            folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+            workspace: Some(workspace),
             ..ServerCapabilities::default()
         }
     }
@@ -353,7 +570,21 @@ impl<T: LspContext> Backend<T> {
     }
 
     fn validate(&self, uri: Url, version: Option<i64>, text: String) -> anyhow::Result<()> {
-        let uri = uri.try_into()?;
+        let uri: LspUrl = uri.try_into()?;
+        {
+            let mut open_files = self.open_files.write().unwrap();
+            open_files.insert(uri.clone(), (version, text.clone()));
+        }
+        self.parse_and_publish(uri, version, text)
+    }
+
+    fn parse_and_publish(
+        &self,
+        uri: LspUrl,
+        version: Option<i64>,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.index.write().unwrap().update(&uri, &text);
         let eval_result = self.context.parse_file_with_contents(&uri, text);
         if let Some(ast) = eval_result.ast {
             let module = Arc::new(LspModule::new(ast));
@@ -383,14 +614,161 @@ impl<T: LspContext> Backend<T> {
     }
 
     fn did_close(&self, params: DidCloseTextDocumentParams) -> anyhow::Result<()> {
+        let uri = params.text_document.uri.clone().try_into()?;
         {
             let mut last_valid_parse = self.last_valid_parse.write().unwrap();
-            last_valid_parse.remove(&params.text_document.uri.clone().try_into()?);
+            last_valid_parse.remove(&uri);
         }
+        self.open_files.write().unwrap().remove(&uri);
         self.publish_diagnostics(params.text_document.uri, Vec::new(), None);
         Ok(())
     }
 
+    /// Parse all the open files again, after the settings that apply to them changed.
+    fn revalidate_open_files(&self) -> anyhow::Result<()> {
+        let open_files = self.open_files.read().unwrap().clone();
+        for (uri, (version, text)) in open_files {
+            self.parse_and_publish(uri, version, text)?;
+        }
+        Ok(())
+    }
+
+    fn set_workspace_roots(&self, roots: Vec<LspWorkspaceRoot>) {
+        self.context.set_workspace_roots(&roots);
+        *self.roots.write().unwrap() = roots;
+    }
+
+    fn did_change_workspace_folders(
+        &self,
+        params: DidChangeWorkspaceFoldersParams,
+    ) -> anyhow::Result<()> {
+        let removed = params
+            .event
+            .removed
+            .into_try_map(LspWorkspaceRoot::try_from)?;
+        let added = params
+            .event
+            .added
+            .into_try_map(LspWorkspaceRoot::try_from)?;
+        let mut roots = self.roots.read().unwrap().clone();
+        roots.retain(|root| !removed.contains(root));
+        roots.extend(added);
+        self.set_workspace_roots(roots);
+        self.index.write().unwrap().invalidate();
+        self.revalidate_open_files()?;
+        self.analyze_workspace();
+        Ok(())
+    }
+
+    fn did_change_configuration(&self, params: DidChangeConfigurationParams) -> anyhow::Result<()> {
+        // Keep the previous settings if the new ones are invalid, but tell the user.
+        if let Err(e) = self.context.set_configuration(&params.settings) {
+            self.log_message(MessageType::ERROR, &format!("Invalid settings: {:#}", e));
+        }
+        self.index.write().unwrap().invalidate();
+        self.revalidate_open_files()?;
+        self.analyze_workspace();
+        Ok(())
+    }
+
+    /// Queue all the files of the workspace for the background analysis, if it is enabled.
+    /// Open files go first.
+    fn analyze_workspace(&self) {
+        if !self.settings.enable_workspace_analysis {
+            return;
+        }
+        let files = match self.context.workspace_files(&self.roots.read().unwrap()) {
+            Ok(files) => files,
+            Err(e) => {
+                let message = format!("Cannot list workspace files: {:#}", e);
+                self.log_message(MessageType::ERROR, &message);
+                return;
+            }
+        };
+        let open_files = self
+            .open_files
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut index = self.index.write().unwrap();
+        let started = index.is_empty();
+        index.enqueue(files, false);
+        index.enqueue(open_files, true);
+        let started = started && !index.is_empty();
+        drop(index);
+        if started {
+            self.begin_progress();
+        }
+    }
+
+    /// Analyze the next file waiting for the background analysis, and publish its diagnostics
+    /// unless the file is unchanged since it was last analyzed.
+    fn analyze_next_file(&self) -> anyhow::Result<()> {
+        let uri = match self.index.write().unwrap().next() {
+            Some(uri) => uri,
+            None => return Ok(()),
+        };
+        let open_file = self.open_files.read().unwrap().get(&uri).cloned();
+        let (version, contents) = match open_file {
+            Some((version, text)) => (version, Some(text)),
+            None => match self.context.get_load_contents(&uri) {
+                Ok(contents) => (None, contents),
+                Err(e) => {
+                    let message = format!("Cannot read `{}`: {:#}", uri, e);
+                    self.log_message(MessageType::WARNING, &message);
+                    (None, None)
+                }
+            },
+        };
+        if let Some(contents) = contents {
+            if self.index.write().unwrap().update(&uri, &contents) {
+                let eval_result = self.context.parse_file_with_contents(&uri, contents);
+                self.publish_diagnostics(uri.try_into()?, eval_result.diagnostics, version);
+            }
+        }
+        self.report_progress();
+        Ok(())
+    }
+
+    /// Pull the diagnostics of a file, open or not.
+    fn document_diagnostic(&self, id: RequestId, params: DocumentDiagnosticParams) {
+        self.send_response(new_response(id, self.find_diagnostics(params)));
+    }
+
+    fn find_diagnostics(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> anyhow::Result<DocumentDiagnosticReport> {
+        let uri = params.text_document.uri.try_into()?;
+        let open_file = self.open_files.read().unwrap().get(&uri).cloned();
+        let contents = match open_file {
+            Some((_, text)) => Some(text),
+            None => self.context.get_load_contents(&uri)?,
+        };
+        let contents = match contents {
+            Some(contents) => contents,
+            None => {
+                return Ok(DocumentDiagnosticReport::Full {
+                    result_id: None,
+                    items: Vec::new(),
+                });
+            }
+        };
+
+        let result_id = self.index.read().unwrap().result_id(&contents);
+        if params.previous_result_id.as_ref() == Some(&result_id) {
+            return Ok(DocumentDiagnosticReport::Unchanged { result_id });
+        }
+        let eval_result = self.context.parse_file_with_contents(&uri, contents);
+        Ok(DocumentDiagnosticReport::Full {
+            result_id: Some(result_id),
+            items: eval_result.diagnostics,
+        })
+    }
+
     /// Go to the definition of the symbol at the current cursor if that definition is in
     /// the same file.
     ///
@@ -587,6 +965,311 @@ impl<T: LspContext> Backend<T> {
         };
         Ok(GotoDefinitionResponse::Link(response))
     }
+
+    /// Find the function at the cursor for the call hierarchy. The cursor can be on the name
+    /// of a top level `def`, or on a call of a function defined in this or another file.
+    fn prepare_call_hierarchy(&self, id: RequestId, params: CallHierarchyPrepareParams) {
+        let response = self.find_hierarchy_items(
+            &params.text_document_position_params,
+            LspModule::top_level_functions,
+        );
+        self.send_response(new_response(id, response));
+    }
+
+    /// Find the calls of a function, in the open files and in the files of the workspace.
+    fn incoming_calls(&self, id: RequestId, params: CallHierarchyIncomingCallsParams) {
+        self.send_response(new_response(id, self.find_incoming_calls(params.item)));
+    }
+
+    /// Find the calls to other functions from a function.
+    fn outgoing_calls(&self, id: RequestId, params: CallHierarchyOutgoingCallsParams) {
+        self.send_response(new_response(id, self.find_outgoing_calls(params.item)));
+    }
+
+    /// Find the type created by `record`, `enum` or `provider` at the cursor.
+    fn prepare_type_hierarchy(&self, id: RequestId, params: TypeHierarchyPrepareParams) {
+        let response = self.find_hierarchy_items(
+            &params.text_document_position_params,
+            LspModule::top_level_types,
+        );
+        self.send_response(new_response(id, response));
+    }
+
+    /// Types created by `record`, `enum` or `provider` cannot extend other types,
+    /// so they have no supertypes and no subtypes.
+    fn related_types(&self, id: RequestId) {
+        let response: anyhow::Result<Option<Vec<TypeHierarchyItem>>> = Ok(Some(Vec::new()));
+        self.send_response(new_response(id, response));
+    }
+
+    /// Expand the selection to the enclosing statements and expressions.
+    ///
+    /// NOTE: This uses the last valid parse of a file, like `goto_definition`.
+    fn selection_range(&self, id: RequestId, params: SelectionRangeParams) {
+        self.send_response(new_response(id, self.find_selection_ranges(params)));
+    }
+
+    /// Fold statement suites, multi-line expressions and `load()` statements.
+    fn folding_range(&self, id: RequestId, params: FoldingRangeParams) {
+        self.send_response(new_response(id, self.find_folding_ranges(params)));
+    }
+
+    fn find_selection_ranges(
+        &self,
+        params: SelectionRangeParams,
+    ) -> anyhow::Result<Option<Vec<SelectionRange>>> {
+        let uri = params.text_document.uri.try_into()?;
+        let ast = match self.get_ast(&uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        let ranges = params.positions.iter().map(|position| {
+            // Innermost range first, each with a parent containing it.
+            ast.selection_ranges(position.line, position.character)
+                .into_iter()
+                .rev()
+                .fold(None, |parent, span| {
+                    Some(SelectionRange {
+                        range: span.into(),
+                        parent: parent.map(Box::new),
+                    })
+                })
+                .unwrap_or_default()
+        });
+        Ok(Some(ranges.collect()))
+    }
+
+    fn find_folding_ranges(
+        &self,
+        params: FoldingRangeParams,
+    ) -> anyhow::Result<Option<Vec<FoldingRange>>> {
+        let uri = params.text_document.uri.try_into()?;
+        let ast = match self.get_ast(&uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        let ranges = ast
+            .folding_regions()
+            .into_iter()
+            .map(|region| FoldingRange {
+                start_line: region.start_line as u32,
+                start_character: None,
+                end_line: region.end_line as u32,
+                end_character: None,
+                kind: match region.kind {
+                    FoldingRegionKind::Imports => Some(FoldingRangeKind::Imports),
+                    FoldingRegionKind::Suite | FoldingRegionKind::Brackets => None,
+                },
+            });
+        Ok(Some(ranges.collect()))
+    }
+
+    fn find_hierarchy_items(
+        &self,
+        position: &TextDocumentPositionParams,
+        symbols: fn(&LspModule) -> Vec<DefinedSymbol>,
+    ) -> anyhow::Result<Option<Vec<CallHierarchyItem>>> {
+        match self.find_defined_symbol(position, symbols)? {
+            Some((uri, symbol)) => Ok(Some(vec![hierarchy_item(&uri, symbol)?])),
+            None => Ok(None),
+        }
+    }
+
+    /// Find the top level symbol, one of `symbols` of a module, at the position.
+    /// The position can be on the name of the symbol where it is defined,
+    /// or on a reference to it, including a reference to a symbol loaded from another file.
+    fn find_defined_symbol(
+        &self,
+        position: &TextDocumentPositionParams,
+        symbols: fn(&LspModule) -> Vec<DefinedSymbol>,
+    ) -> anyhow::Result<Option<(LspUrl, DefinedSymbol)>> {
+        let uri: LspUrl = position.text_document.uri.clone().try_into()?;
+        let (line, character) = (position.position.line, position.position.character);
+        let ast = match self.get_ast(&uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        if let Some(symbol) = ast.symbol_at(symbols(&ast), line, character) {
+            return Ok(Some((uri, symbol)));
+        }
+        let (uri, name) = match ast.find_definition(line, character) {
+            Definition::Identifier(IdentifierDefinition::Location { destination, .. }) => {
+                let symbol = symbols(&ast)
+                    .into_iter()
+                    .find(|x| x.name_span == destination);
+                return Ok(symbol.map(|symbol| (uri, symbol)));
+            }
+            Definition::Identifier(IdentifierDefinition::LoadedLocation { path, name, .. }) => {
+                (self.resolve_load_path(&path, &uri)?, name)
+            }
+            Definition::Identifier(IdentifierDefinition::Unresolved { name, .. }) => {
+                match self.context.get_url_for_global_symbol(&uri, &name)? {
+                    Some(uri) => (uri, name),
+                    None => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        };
+        self.find_symbol_in_file(uri, &name, symbols)
+    }
+
+    /// Find the top level symbol `name`, one of `symbols` of the module at `uri`.
+    fn find_symbol_in_file(
+        &self,
+        uri: LspUrl,
+        name: &str,
+        symbols: fn(&LspModule) -> Vec<DefinedSymbol>,
+    ) -> anyhow::Result<Option<(LspUrl, DefinedSymbol)>> {
+        let symbol = self
+            .get_ast_or_load_from_disk(&uri)?
+            .and_then(|ast| symbols(&ast).into_iter().find(|x| x.name == name));
+        Ok(symbol.map(|symbol| (uri, symbol)))
+    }
+
+    fn find_incoming_calls(
+        &self,
+        item: CallHierarchyItem,
+    ) -> anyhow::Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let target: LspUrl = item.uri.try_into()?;
+        let mut files = vec![target.clone()];
+        files.extend(self.open_files.read().unwrap().keys().cloned());
+        files.extend(self.context.workspace_files(&self.roots.read().unwrap())?);
+
+        let mut seen = HashSet::new();
+        let mut res = Vec::new();
+        for uri in files {
+            if !seen.insert(uri.clone()) {
+                continue;
+            }
+            // Files which cannot be parsed cannot call the function.
+            let ast = match self.get_ast_or_load_from_disk(&uri) {
+                Ok(Some(ast)) => ast,
+                _ => continue,
+            };
+            // Names of the function in this file.
+            let mut names = HashSet::new();
+            if uri == target {
+                names.insert(item.name.clone());
+            }
+            for loaded in ast.loaded_symbols() {
+                if loaded.name == item.name
+                    && self.resolve_load_path(&loaded.module, &uri).ok().as_ref() == Some(&target)
+                {
+                    names.insert(loaded.local);
+                }
+            }
+            if names.is_empty() {
+                continue;
+            }
+
+            let mut callers: SmallMap<Option<String>, Vec<Range>> = SmallMap::new();
+            for call in ast.function_calls() {
+                if names.contains(&call.callee) {
+                    callers
+                        .entry(call.caller)
+                        .or_default()
+                        .push(call.span.into());
+                }
+            }
+            let functions = ast.top_level_functions();
+            for (caller, from_ranges) in callers {
+                let caller = caller.and_then(|caller| functions.iter().find(|x| x.name == caller));
+                let from = match caller {
+                    Some(caller) => hierarchy_item(&uri, caller.clone())?,
+                    None => module_item(&uri)?,
+                };
+                res.push(CallHierarchyIncomingCall { from, from_ranges });
+            }
+        }
+        Ok(Some(res))
+    }
+
+    fn find_outgoing_calls(
+        &self,
+        item: CallHierarchyItem,
+    ) -> anyhow::Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let uri: LspUrl = item.uri.try_into()?;
+        let ast = match self.get_ast_or_load_from_disk(&uri)? {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+
+        let mut callees: SmallMap<String, Vec<Range>> = SmallMap::new();
+        for call in ast.function_calls() {
+            if call.caller.as_ref() == Some(&item.name) {
+                callees
+                    .entry(call.callee)
+                    .or_default()
+                    .push(call.span.into());
+            }
+        }
+
+        let functions = ast.top_level_functions();
+        let loaded = ast.loaded_symbols();
+        let mut res = Vec::new();
+        for (callee, from_ranges) in callees {
+            let target = if let Some(function) = functions.iter().find(|x| x.name == callee) {
+                Some((uri.clone(), function.clone()))
+            } else if let Some(loaded) = loaded.iter().find(|x| x.local == callee) {
+                let load_uri = self.resolve_load_path(&loaded.module, &uri)?;
+                self.find_symbol_in_file(load_uri, &loaded.name, LspModule::top_level_functions)?
+            } else {
+                match self.context.get_url_for_global_symbol(&uri, &callee)? {
+                    Some(global_uri) => self.find_symbol_in_file(
+                        global_uri,
+                        &callee,
+                        LspModule::top_level_functions,
+                    )?,
+                    // Builtin functions are not in the hierarchy.
+                    None => None,
+                }
+            };
+            if let Some((target_uri, function)) = target {
+                res.push(CallHierarchyOutgoingCall {
+                    to: hierarchy_item(&target_uri, function)?,
+                    from_ranges,
+                });
+            }
+        }
+        Ok(Some(res))
+    }
+}
+
+/// Call or type hierarchy item for a top level symbol.
+fn hierarchy_item(uri: &LspUrl, symbol: DefinedSymbol) -> anyhow::Result<CallHierarchyItem> {
+    let kind = match symbol.constructor.as_deref() {
+        None => SymbolKind::FUNCTION,
+        Some("enum") => SymbolKind::ENUM,
+        Some(_) => SymbolKind::STRUCT,
+    };
+    Ok(CallHierarchyItem {
+        name: symbol.name,
+        kind,
+        tags: None,
+        detail: symbol.constructor,
+        uri: uri.try_into()?,
+        range: symbol.span.into(),
+        selection_range: symbol.name_span.into(),
+        data: None,
+    })
+}
+
+/// Call hierarchy item for the top level statements of a module, which can call functions too.
+fn module_item(uri: &LspUrl) -> anyhow::Result<CallHierarchyItem> {
+    let name = uri
+        .path()
+        .file_name()
+        .map_or_else(|| uri.to_string(), |x| x.to_string_lossy().into_owned());
+    Ok(CallHierarchyItem {
+        name,
+        kind: SymbolKind::FILE,
+        tags: None,
+        detail: None,
+        uri: uri.try_into()?,
+        range: Range::default(),
+        selection_range: Range::default(),
+        data: None,
+    })
 }
 
 /// The library style pieces
@@ -615,9 +1298,99 @@ impl<T: LspContext> Backend<T> {
         ));
     }
 
-    fn main_loop(&self, _params: InitializeParams) -> anyhow::Result<()> {
+    fn send_request<R: lsp_types::request::Request>(&self, params: R::Params) {
+        let id = self.last_request_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let request = Request {
+            id: RequestId::from(id),
+            method: R::METHOD.to_owned(),
+            params: serde_json::to_value(params).unwrap(),
+        };
+        self.connection
+            .sender
+            .send(Message::Request(request))
+            .unwrap()
+    }
+
+    fn send_progress(&self, progress: WorkDoneProgress) {
+        self.send_notification(new_notification::<Progress>(ProgressParams {
+            token: NumberOrString::String(WORKSPACE_ANALYSIS_PROGRESS_TOKEN.to_owned()),
+            value: ProgressParamsValue::WorkDone(progress),
+        }));
+    }
+
+    fn begin_progress(&self) {
+        if !self.work_done_progress {
+            return;
+        }
+        self.send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+            token: NumberOrString::String(WORKSPACE_ANALYSIS_PROGRESS_TOKEN.to_owned()),
+        });
+        self.send_progress(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: "Analyzing Starlark files".to_owned(),
+            cancellable: Some(false),
+            message: None,
+            percentage: Some(0),
+        }));
+    }
+
+    /// Report the progress of the background analysis, and end it when the queue is empty.
+    fn report_progress(&self) {
+        let mut index = self.index.write().unwrap();
+        let (done, total) = index.progress();
+        let finished = index.is_empty();
+        if finished {
+            index.reset_progress();
+        }
+        drop(index);
+
+        if !self.work_done_progress {
+            return;
+        }
+        if finished {
+            self.send_progress(WorkDoneProgress::End(WorkDoneProgressEnd {
+                message: Some(format!("Analyzed {} files", done)),
+            }));
+        } else {
+            self.send_progress(WorkDoneProgress::Report(WorkDoneProgressReport {
+                cancellable: Some(false),
+                message: Some(format!("{}/{} files", done, total)),
+                percentage: Some((done * 100 / total) as u32),
+            }));
+        }
+    }
+
+    /// Wait for the next message from the client, analyzing the workspace files while idle.
+    /// Return `None` when the connection is closed.
+    fn next_message(&self) -> anyhow::Result<Option<Message>> {
+        loop {
+            if self.index.read().unwrap().is_empty() {
+                return Ok(self.connection.receiver.recv().ok());
+            }
+            match self.connection.receiver.try_recv() {
+                Ok(message) => return Ok(Some(message)),
+                Err(e) if e.is_disconnected() => return Ok(None),
+                Err(_) => self.analyze_next_file()?,
+            }
+        }
+    }
+
+    fn main_loop(&self, params: InitializeParams) -> anyhow::Result<()> {
         self.log_message(MessageType::INFO, "Starlark server initialised");
-        for msg in &self.connection.receiver {
+        let roots = match (params.workspace_folders, params.root_uri) {
+            (Some(folders), _) => folders.into_try_map(LspWorkspaceRoot::try_from)?,
+            (None, Some(root_uri)) => {
+                let url = LspUrl::try_from(root_uri)?;
+                let name = url
+                    .path()
+                    .file_name()
+                    .map_or_else(String::new, |x| x.to_string_lossy().into_owned());
+                vec![LspWorkspaceRoot { name, url }]
+            }
+            (None, None) => Vec::new(),
+        };
+        self.set_workspace_roots(roots);
+        self.analyze_workspace();
+        while let Some(msg) = self.next_message()? {
             match msg {
                 Message::Request(req) => {
                     // TODO(nmj): Also implement DocumentSymbols so that some logic can
@@ -626,6 +1399,24 @@ impl<T: LspContext> Backend<T> {
                         self.goto_definition(req.id, params);
                     } else if let Some(params) = as_request::<StarlarkFileContentsRequest>(&req) {
                         self.get_starlark_file_contents(req.id, params);
+                    } else if let Some(params) = as_request::<DocumentDiagnosticRequest>(&req) {
+                        self.document_diagnostic(req.id, params);
+                    } else if let Some(params) = as_request::<CallHierarchyPrepare>(&req) {
+                        self.prepare_call_hierarchy(req.id, params);
+                    } else if let Some(params) = as_request::<CallHierarchyIncomingCalls>(&req) {
+                        self.incoming_calls(req.id, params);
+                    } else if let Some(params) = as_request::<CallHierarchyOutgoingCalls>(&req) {
+                        self.outgoing_calls(req.id, params);
+                    } else if let Some(params) = as_request::<TypeHierarchyPrepareRequest>(&req) {
+                        self.prepare_type_hierarchy(req.id, params);
+                    } else if as_request::<TypeHierarchySupertypesRequest>(&req).is_some()
+                        || as_request::<TypeHierarchySubtypesRequest>(&req).is_some()
+                    {
+                        self.related_types(req.id);
+                    } else if let Some(params) = as_request::<SelectionRangeRequest>(&req) {
+                        self.selection_range(req.id, params);
+                    } else if let Some(params) = as_request::<FoldingRangeRequest>(&req) {
+                        self.folding_range(req.id, params);
                     } else if self.connection.handle_shutdown(&req)? {
                         return Ok(());
                     }
@@ -638,6 +1429,10 @@ impl<T: LspContext> Backend<T> {
                         self.did_change(params)?;
                     } else if let Some(params) = as_notification::<DidCloseTextDocument>(&x) {
                         self.did_close(params)?;
+                    } else if let Some(params) = as_notification::<DidChangeWorkspaceFolders>(&x) {
+                        self.did_change_workspace_folders(params)?;
+                    } else if let Some(params) = as_notification::<DidChangeConfiguration>(&x) {
+                        self.did_change_configuration(params)?;
                     }
                 }
                 Message::Response(_) => {
@@ -672,13 +1467,25 @@ pub fn server_with_connection<T: LspContext>(
     let (init_request_id, init_value) = connection.initialize_start()?;
 
     let initialization_params: InitializeParams = serde_json::from_value(init_value)?;
-    let server_settings = initialization_params
+    let server_settings: LspServerSettings = initialization_params
         .initialization_options
         .as_ref()
         .and_then(|opts| serde_json::from_value(opts.clone()).ok())
         .unwrap_or_default();
-    let capabilities_payload = Backend::<T>::server_capabilities(server_settings);
-    let server_capabilities = serde_json::to_value(&capabilities_payload).unwrap();
+    let capabilities_payload = Backend::<T>::server_capabilities(server_settings.dupe());
+    let mut server_capabilities = serde_json::to_value(&capabilities_payload).unwrap();
+    // `lsp_types` does not support pull diagnostics yet.
+    server_capabilities["diagnosticProvider"] = serde_json::json!({
+        "interFileDependencies": false,
+        "workspaceDiagnostics": false,
+    });
+    server_capabilities["typeHierarchyProvider"] = serde_json::json!(true);
+    let work_done_progress = initialization_params
+        .capabilities
+        .window
+        .as_ref()
+        .and_then(|window| window.work_done_progress)
+        .unwrap_or(false);
 
     let initialize_data = serde_json::json!({
             "capabilities": server_capabilities,
@@ -689,6 +1496,12 @@ pub fn server_with_connection<T: LspContext>(
         connection,
         context,
         last_valid_parse: RwLock::default(),
+        open_files: RwLock::default(),
+        roots: RwLock::default(),
+        settings: server_settings,
+        index: RwLock::default(),
+        work_done_progress,
+        last_request_id: AtomicI32::new(0),
     }
     .main_loop(initialization_params)?;
 
@@ -764,30 +1577,65 @@ where
 //            some paths. Revisit later.
 #[cfg(all(test, not(windows)))]
 mod test {
+    use std::collections::HashMap;
     use std::path::Path;
     use std::path::PathBuf;
 
     use anyhow::Context;
     use lsp_server::Request;
     use lsp_server::RequestId;
+    use lsp_types::notification::DidChangeConfiguration;
+    use lsp_types::notification::DidChangeWorkspaceFolders;
+    use lsp_types::notification::PublishDiagnostics;
+    use lsp_types::request::CallHierarchyIncomingCalls;
+    use lsp_types::request::CallHierarchyOutgoingCalls;
+    use lsp_types::request::CallHierarchyPrepare;
+    use lsp_types::request::FoldingRangeRequest;
     use lsp_types::request::GotoDefinition;
+    use lsp_types::request::SelectionRangeRequest;
+    use lsp_types::CallHierarchyIncomingCall;
+    use lsp_types::CallHierarchyIncomingCallsParams;
+    use lsp_types::CallHierarchyItem;
+    use lsp_types::CallHierarchyOutgoingCall;
+    use lsp_types::CallHierarchyOutgoingCallsParams;
+    use lsp_types::CallHierarchyPrepareParams;
+    use lsp_types::DidChangeConfigurationParams;
+    use lsp_types::DidChangeWorkspaceFoldersParams;
+    use lsp_types::FoldingRange;
+    use lsp_types::FoldingRangeKind;
+    use lsp_types::FoldingRangeParams;
     use lsp_types::GotoDefinitionParams;
     use lsp_types::GotoDefinitionResponse;
     use lsp_types::LocationLink;
     use lsp_types::Position;
     use lsp_types::Range;
+    use lsp_types::SelectionRange;
+    use lsp_types::SelectionRangeParams;
+    use lsp_types::SymbolKind;
     use lsp_types::TextDocumentIdentifier;
     use lsp_types::TextDocumentPositionParams;
     use lsp_types::Url;
+    use lsp_types::WorkspaceFolder;
+    use lsp_types::WorkspaceFoldersChangeEvent;
     use textwrap::dedent;
 
     use crate::analysis::FixtureWithRanges;
     use crate::codemap::ResolvedSpan;
+    use crate::lsp::server::new_notification;
+    use crate::lsp::server::DocumentDiagnosticParams;
+    use crate::lsp::server::DocumentDiagnosticReport;
+    use crate::lsp::server::DocumentDiagnosticRequest;
     use crate::lsp::server::LspServerSettings;
     use crate::lsp::server::LspUrl;
+    use crate::lsp::server::LspWorkspaceRoot;
     use crate::lsp::server::StarlarkFileContentsParams;
     use crate::lsp::server::StarlarkFileContentsRequest;
     use crate::lsp::server::StarlarkFileContentsResponse;
+    use crate::lsp::server::TypeHierarchyItem;
+    use crate::lsp::server::TypeHierarchyParams;
+    use crate::lsp::server::TypeHierarchyPrepareParams;
+    use crate::lsp::server::TypeHierarchyPrepareRequest;
+    use crate::lsp::server::TypeHierarchySupertypesRequest;
     use crate::lsp::test::TestServer;
 
     fn goto_definition_request(
@@ -1566,6 +2414,7 @@ mod test {
     fn disables_goto_definition() -> anyhow::Result<()> {
         let server = TestServer::new_with_settings(Some(LspServerSettings {
             enable_goto_definition: false,
+            ..LspServerSettings::default()
         }))?;
 
         let goto_definition_disabled = server
@@ -1579,6 +2428,7 @@ mod test {
 
         let server = TestServer::new_with_settings(Some(LspServerSettings {
             enable_goto_definition: true,
+            ..LspServerSettings::default()
         }))?;
 
         let goto_definition_enabled = server
@@ -1805,4 +2655,372 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    fn finds_innermost_workspace_root() -> anyhow::Result<()> {
+        let root = |name: &str, path: &str| -> anyhow::Result<LspWorkspaceRoot> {
+            Ok(LspWorkspaceRoot {
+                name: name.to_owned(),
+                url: temp_file_uri(path).try_into()?,
+            })
+        };
+        let roots = vec![root("outer", "repo")?, root("inner", "repo/inner")?];
+
+        let file = |path: &str| -> anyhow::Result<LspUrl> { Ok(temp_file_uri(path).try_into()?) };
+        let find = |path: &str| -> anyhow::Result<Option<String>> {
+            Ok(LspWorkspaceRoot::find(&roots, &file(path)?).map(|x| x.name.clone()))
+        };
+        assert_eq!(Some("outer".to_owned()), find("repo/a.star")?);
+        assert_eq!(Some("inner".to_owned()), find("repo/inner/b/a.star")?);
+        assert_eq!(Some("outer".to_owned()), find("repo/inner_not/a.star")?);
+        assert_eq!(None, find("other/a.star")?);
+        Ok(())
+    }
+
+    #[test]
+    fn applies_configuration_per_workspace_root() -> anyhow::Result<()> {
+        let mut server = TestServer::new()?;
+        let folder = |name: &str| WorkspaceFolder {
+            uri: temp_file_uri(name),
+            name: name.to_owned(),
+        };
+        server.send_notification(new_notification::<DidChangeWorkspaceFolders>(
+            DidChangeWorkspaceFoldersParams {
+                event: WorkspaceFoldersChangeEvent {
+                    added: vec![folder("a"), folder("b")],
+                    removed: Vec::new(),
+                },
+            },
+        ))?;
+
+        // Keyword-only arguments are only allowed in the extended dialect.
+        let contents = "def f(*, x):\n    return x\n";
+        let a = temp_file_uri("a/file.star");
+        let b = temp_file_uri("b/file.star");
+        server.open_file(a.clone(), contents.to_owned())?;
+        server.open_file(b.clone(), contents.to_owned())?;
+
+        server.send_notification(new_notification::<DidChangeConfiguration>(
+            DidChangeConfigurationParams {
+                settings: serde_json::json!({"standardDialectRoots": ["a"]}),
+            },
+        ))?;
+        let mut diagnostics = HashMap::new();
+        for _ in 0..2 {
+            let params = server.get_notification::<PublishDiagnostics>()?;
+            diagnostics.insert(params.uri, params.diagnostics.len());
+        }
+        assert_eq!(Some(&1), diagnostics.get(&a));
+        assert_eq!(Some(&0), diagnostics.get(&b));
+        Ok(())
+    }
+
+    #[test]
+    fn pulls_diagnostics() -> anyhow::Result<()> {
+        let mut server = TestServer::new()?;
+        let uri = temp_file_uri("pulled.star");
+        server.set_file_contents(PathBuf::from(uri.path()), "x = \n".to_owned())?;
+
+        let mut pull = |previous_result_id: Option<String>| {
+            let req = server.new_request::<DocumentDiagnosticRequest>(DocumentDiagnosticParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                previous_result_id,
+            });
+            let id = server.send_request(req)?;
+            server.get_response::<DocumentDiagnosticReport>(id)
+        };
+
+        let result_id = match pull(None)? {
+            DocumentDiagnosticReport::Full {
+                result_id: Some(result_id),
+                items,
+            } if items.len() == 1 => result_id,
+            report => return Err(anyhow::anyhow!("Unexpected report: {:?}", report)),
+        };
+        assert_eq!(
+            DocumentDiagnosticReport::Unchanged {
+                result_id: result_id.clone()
+            },
+            pull(Some(result_id))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn analyzes_workspace_in_background() -> anyhow::Result<()> {
+        let mut server = TestServer::new_with_settings(Some(LspServerSettings {
+            enable_workspace_analysis: true,
+            ..LspServerSettings::default()
+        }))?;
+        let good = temp_file_uri("ws/good.star");
+        let bad = temp_file_uri("ws/bad.star");
+        server.set_file_contents(PathBuf::from(good.path()), "x = 1\n".to_owned())?;
+        server.set_file_contents(PathBuf::from(bad.path()), "x = \n".to_owned())?;
+        server.send_notification(new_notification::<DidChangeWorkspaceFolders>(
+            DidChangeWorkspaceFoldersParams {
+                event: WorkspaceFoldersChangeEvent {
+                    added: vec![WorkspaceFolder {
+                        uri: temp_file_uri("ws"),
+                        name: "ws".to_owned(),
+                    }],
+                    removed: Vec::new(),
+                },
+            },
+        ))?;
+
+        let mut diagnostics = HashMap::new();
+        for _ in 0..2 {
+            let params = server.get_notification::<PublishDiagnostics>()?;
+            diagnostics.insert(params.uri, params.diagnostics.len());
+        }
+        assert_eq!(Some(&0), diagnostics.get(&good));
+        assert_eq!(Some(&1), diagnostics.get(&bad));
+        Ok(())
+    }
+
+    fn add_workspace_folder(server: &TestServer, uri: Url, name: &str) -> anyhow::Result<()> {
+        server.send_notification(new_notification::<DidChangeWorkspaceFolders>(
+            DidChangeWorkspaceFoldersParams {
+                event: WorkspaceFoldersChangeEvent {
+                    added: vec![WorkspaceFolder {
+                        uri,
+                        name: name.to_owned(),
+                    }],
+                    removed: Vec::new(),
+                },
+            },
+        ))
+    }
+
+    #[test]
+    fn finds_call_hierarchy() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("calls/foo.star");
+        let bar_uri = temp_file_uri("calls/bar.star");
+        let foo = FixtureWithRanges::from_fixture(
+            foo_uri.path(),
+            dedent(
+                r#"
+                <f_def>def <f>f</f>(x):
+                    return len(x)</f_def>
+
+                <g_def>def <g>g</g>():
+                    return <g_f>f</g_f>([]) + <g_f2>f</g_f2>([1])</g_def>
+
+                <top_g>g</top_g>()
+                "#,
+            )
+            .trim(),
+        )?;
+        let bar = FixtureWithRanges::from_fixture(
+            bar_uri.path(),
+            dedent(
+                r#"
+                load("foo.star", foo_f = "f")
+                <h_def>def <h>h</h>():
+                    return <h_f>foo_f</h_f>("x")</h_def>
+                "#,
+            )
+            .trim(),
+        )?;
+        let item = |uri: &Url, fixture: &FixtureWithRanges, name: &str| CallHierarchyItem {
+            name: name.to_owned(),
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            detail: None,
+            uri: uri.clone(),
+            range: fixture.span(&format!("{}_def", name)).into(),
+            selection_range: fixture.span(name).into(),
+            data: None,
+        };
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+        server.set_file_contents(PathBuf::from(bar_uri.path()), bar.program())?;
+        add_workspace_folder(&server, temp_file_uri("calls"), "calls")?;
+
+        let req = server.new_request::<CallHierarchyPrepare>(CallHierarchyPrepareParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier {
+                    uri: foo_uri.clone(),
+                },
+                position: Position::new(foo.begin_line("g_f"), foo.begin_column("g_f")),
+            },
+            work_done_progress_params: Default::default(),
+        });
+        let id = server.send_request(req)?;
+        let items = server.get_response::<Option<Vec<CallHierarchyItem>>>(id)?;
+        assert_eq!(Some(vec![item(&foo_uri, &foo, "f")]), items);
+
+        let req =
+            server.new_request::<CallHierarchyIncomingCalls>(CallHierarchyIncomingCallsParams {
+                item: item(&foo_uri, &foo, "f"),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            });
+        let id = server.send_request(req)?;
+        let calls = server.get_response::<Option<Vec<CallHierarchyIncomingCall>>>(id)?;
+        let expected = vec![
+            CallHierarchyIncomingCall {
+                from: item(&foo_uri, &foo, "g"),
+                from_ranges: vec![foo.span("g_f").into(), foo.span("g_f2").into()],
+            },
+            CallHierarchyIncomingCall {
+                from: item(&bar_uri, &bar, "h"),
+                from_ranges: vec![bar.span("h_f").into()],
+            },
+        ];
+        assert_eq!(Some(expected), calls);
+
+        let req =
+            server.new_request::<CallHierarchyIncomingCalls>(CallHierarchyIncomingCallsParams {
+                item: item(&foo_uri, &foo, "g"),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            });
+        let id = server.send_request(req)?;
+        let calls = server.get_response::<Option<Vec<CallHierarchyIncomingCall>>>(id)?;
+        let calls = calls.unwrap_or_default();
+        assert_eq!(1, calls.len());
+        assert_eq!(SymbolKind::FILE, calls[0].from.kind);
+        assert_eq!(vec![Range::from(foo.span("top_g"))], calls[0].from_ranges);
+
+        let req =
+            server.new_request::<CallHierarchyOutgoingCalls>(CallHierarchyOutgoingCallsParams {
+                item: item(&bar_uri, &bar, "h"),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            });
+        let id = server.send_request(req)?;
+        let calls = server.get_response::<Option<Vec<CallHierarchyOutgoingCall>>>(id)?;
+        let expected = vec![CallHierarchyOutgoingCall {
+            to: item(&foo_uri, &foo, "f"),
+            from_ranges: vec![bar.span("h_f").into()],
+        }];
+        assert_eq!(Some(expected), calls);
+        Ok(())
+    }
+
+    #[test]
+    fn finds_type_hierarchy() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("types/foo.star");
+        let bar_uri = temp_file_uri("types/bar.star");
+        let foo = FixtureWithRanges::from_fixture(
+            foo_uri.path(),
+            "<info_def><info>Info</info> = provider(fields = [\"x\"])</info_def>",
+        )?;
+        let bar = FixtureWithRanges::from_fixture(
+            bar_uri.path(),
+            "load(\"foo.star\", \"Info\")\nx = <info>Info</info>(x = 1)",
+        )?;
+        let expected = TypeHierarchyItem {
+            name: "Info".to_owned(),
+            kind: SymbolKind::STRUCT,
+            tags: None,
+            detail: Some("provider".to_owned()),
+            uri: foo_uri.clone(),
+            range: foo.span("info_def").into(),
+            selection_range: foo.span("info").into(),
+            data: None,
+        };
+
+        let mut server = TestServer::new()?;
+        server.open_file(bar_uri.clone(), bar.program())?;
+        server.set_file_contents(PathBuf::from(foo_uri.path()), foo.program())?;
+
+        let req = server.new_request::<TypeHierarchyPrepareRequest>(TypeHierarchyPrepareParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: bar_uri },
+                position: Position::new(bar.begin_line("info"), bar.begin_column("info")),
+            },
+        });
+        let id = server.send_request(req)?;
+        let items = server.get_response::<Option<Vec<TypeHierarchyItem>>>(id)?;
+        assert_eq!(Some(vec![expected.clone()]), items);
+
+        let req = server
+            .new_request::<TypeHierarchySupertypesRequest>(TypeHierarchyParams { item: expected });
+        let id = server.send_request(req)?;
+        let items = server.get_response::<Option<Vec<TypeHierarchyItem>>>(id)?;
+        assert_eq!(Some(Vec::new()), items);
+        Ok(())
+    }
+
+    #[test]
+    fn expands_selection() -> anyhow::Result<()> {
+        let uri = temp_file_uri("selection.star");
+        let fixture = FixtureWithRanges::from_fixture(
+            uri.path(),
+            concat!(
+                "<all><def>def f():\n",
+                "    <stmt>return <call>g(<x>x</x>)</call></stmt></def>\n",
+                "x = 1</all>",
+            ),
+        )?;
+        let mut server = TestServer::new()?;
+        server.open_file(uri.clone(), fixture.program())?;
+
+        let req = server.new_request::<SelectionRangeRequest>(SelectionRangeParams {
+            text_document: TextDocumentIdentifier { uri },
+            positions: vec![Position::new(
+                fixture.begin_line("x"),
+                fixture.begin_column("x"),
+            )],
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let id = server.send_request(req)?;
+        let ranges = server.get_response::<Option<Vec<SelectionRange>>>(id)?;
+        let expected = ["all", "def", "stmt", "call", "x"]
+            .iter()
+            .fold(None, |parent, name| {
+                Some(SelectionRange {
+                    range: fixture.span(name).into(),
+                    parent: parent.map(Box::new),
+                })
+            })
+            .unwrap();
+        assert_eq!(Some(vec![expected]), ranges);
+        Ok(())
+    }
+
+    #[test]
+    fn folds_ranges() -> anyhow::Result<()> {
+        let uri = temp_file_uri("folding.star");
+        let program = dedent(
+            r#"
+            load("a.star", "a")
+            load("b.star", "b")
+            def f():
+                return a(
+                    b,
+                )
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let mut server = TestServer::new()?;
+        server.open_file(uri.clone(), program)?;
+
+        let req = server.new_request::<FoldingRangeRequest>(FoldingRangeParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let id = server.send_request(req)?;
+        let ranges = server.get_response::<Option<Vec<FoldingRange>>>(id)?;
+        let range = |start_line, end_line, kind| FoldingRange {
+            start_line,
+            start_character: None,
+            end_line,
+            end_character: None,
+            kind,
+        };
+        let expected = vec![
+            range(0, 1, Some(FoldingRangeKind::Imports)),
+            range(2, 5, None),
+            range(3, 4, None),
+        ];
+        assert_eq!(Some(expected), ranges);
+        Ok(())
+    }
 }
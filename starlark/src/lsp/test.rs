@@ -70,6 +70,7 @@ use crate::lsp::server::LspContext;
 use crate::lsp::server::LspEvalResult;
 use crate::lsp::server::LspServerSettings;
 use crate::lsp::server::LspUrl;
+use crate::lsp::server::LspWorkspaceRoot;
 use crate::lsp::server::StringLiteralResult;
 use crate::syntax::AstModule;
 use crate::syntax::Dialect;
@@ -119,13 +120,35 @@ struct TestServerContext {
     dirs: Arc<RwLock<HashSet<PathBuf>>>,
     builtin_docs: Arc<HashMap<LspUrl, String>>,
     builtin_symbols: Arc<HashMap<String, LspUrl>>,
+    roots: RwLock<Vec<LspWorkspaceRoot>>,
+    /// Names of the roots using the standard dialect, set with
+    /// `{"standardDialectRoots": ["name", ...]}`. Other roots use the extended dialect.
+    standard_dialect_roots: RwLock<HashSet<String>>,
+}
+
+impl TestServerContext {
+    fn dialect(&self, uri: &LspUrl) -> Dialect {
+        let roots = self.roots.read().unwrap();
+        match LspWorkspaceRoot::find(&roots, uri) {
+            Some(root)
+                if self
+                    .standard_dialect_roots
+                    .read()
+                    .unwrap()
+                    .contains(&root.name) =>
+            {
+                Dialect::Standard
+            }
+            _ => Dialect::Extended,
+        }
+    }
 }
 
 impl LspContext for TestServerContext {
     fn parse_file_with_contents(&self, uri: &LspUrl, content: String) -> LspEvalResult {
         match uri {
             LspUrl::File(path) | LspUrl::Starlark(path) => {
-                match AstModule::parse(&path.to_string_lossy(), content, &Dialect::Extended) {
+                match AstModule::parse(&path.to_string_lossy(), content, &self.dialect(uri)) {
                     Ok(ast) => {
                         let diagnostics = ast.lint(None).into_map(|l| EvalMessage::from(l).into());
                         LspEvalResult {
@@ -226,6 +249,30 @@ impl LspContext for TestServerContext {
     ) -> anyhow::Result<Option<LspUrl>> {
         Ok(self.builtin_symbols.get(symbol).cloned())
     }
+
+    fn set_workspace_roots(&self, roots: &[LspWorkspaceRoot]) {
+        *self.roots.write().unwrap() = roots.to_vec();
+    }
+
+    fn set_configuration(&self, settings: &serde_json::Value) -> anyhow::Result<()> {
+        let roots: HashSet<String> =
+            serde_json::from_value(settings["standardDialectRoots"].clone())?;
+        *self.standard_dialect_roots.write().unwrap() = roots;
+        Ok(())
+    }
+
+    fn workspace_files(&self, roots: &[LspWorkspaceRoot]) -> anyhow::Result<Vec<LspUrl>> {
+        let mut files = self
+            .file_contents
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|path| roots.iter().any(|root| path.starts_with(root.url.path())))
+            .map(|path| LspUrl::File(path.clone()))
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a.path().cmp(b.path()));
+        Ok(files)
+    }
 }
 
 /// A server for use in testing that provides helpers for sending requests, correlating
@@ -381,6 +428,8 @@ impl TestServer {
             dirs: dirs.dupe(),
             builtin_docs: builtin_docs.dupe(),
             builtin_symbols,
+            roots: RwLock::default(),
+            standard_dialect_roots: RwLock::default(),
         };
 
         let server_thread = std::thread::spawn(|| {
@@ -0,0 +1,202 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::docs::DocString;
+use crate::docs::DocStringKind;
+use crate::stable::Span;
+use crate::syntax::AstModule;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::DefP;
+use crate::syntax::ast::ParameterP;
+use crate::syntax::ast::Stmt;
+
+/// A symbol imported by a [`Load`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct LoadSymbol {
+    /// Name in the loading module.
+    pub local: String,
+    /// Name in the loaded module.
+    pub exported: String,
+}
+
+/// A `load` statement.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Load {
+    /// The loaded module, as written.
+    pub module: String,
+    /// The imported symbols.
+    pub symbols: Vec<LoadSymbol>,
+    /// Location of the statement.
+    pub span: Span,
+}
+
+/// Whether an [`Item`] is a function or a variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ItemKind {
+    /// Defined with `def`.
+    Function,
+    /// Defined with an assignment.
+    Variable,
+}
+
+/// A top-level definition.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Item {
+    /// Name of the defined symbol.
+    pub name: String,
+    /// Whether the item is a function or a variable.
+    pub kind: ItemKind,
+    /// Location of the name.
+    pub span: Span,
+    /// Parameters of a function as written, e.g. `*args`, without types and defaults.
+    pub params: Vec<String>,
+    /// Summary of the docstring of a function.
+    pub summary: Option<String>,
+}
+
+/// The top-level structure of a module, found without evaluating it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ModuleOutline {
+    /// Summary of the module docstring.
+    pub summary: Option<String>,
+    /// `load` statements, in order.
+    pub loads: Vec<Load>,
+    /// Definitions at the top level, including private ones and redefinitions, in order.
+    pub items: Vec<Item>,
+}
+
+fn summary(body: &AstStmt) -> Option<String> {
+    let docstring = DocString::extract_raw_starlark_docstring(body)?;
+    Some(DocString::from_docstring(DocStringKind::Starlark, &docstring)?.summary)
+}
+
+impl From<&AstModule> for ModuleOutline {
+    fn from(module: &AstModule) -> ModuleOutline {
+        let span = |x| Span::from(&module.file_span(x));
+        let mut loads = Vec::new();
+        let mut items = Vec::new();
+        module.statement.visit_stmt(|x| match &x.node {
+            Stmt::Load(load) => loads.push(Load {
+                module: load.module.node.clone(),
+                symbols: load
+                    .args
+                    .iter()
+                    .map(|(local, exported)| LoadSymbol {
+                        local: local.node.0.clone(),
+                        exported: exported.node.clone(),
+                    })
+                    .collect(),
+                span: span(x.span),
+            }),
+            Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) => lhs.visit_lvalue(|name| {
+                items.push(Item {
+                    name: name.node.0.clone(),
+                    kind: ItemKind::Variable,
+                    span: span(name.span),
+                    params: Vec::new(),
+                    summary: None,
+                })
+            }),
+            Stmt::Def(DefP {
+                name, params, body, ..
+            }) => items.push(Item {
+                name: name.node.0.clone(),
+                kind: ItemKind::Function,
+                span: span(name.span),
+                params: params
+                    .iter()
+                    .map(|p| match &p.node {
+                        ParameterP::Normal(name, _) | ParameterP::WithDefaultValue(name, _, _) => {
+                            name.node.0.clone()
+                        }
+                        ParameterP::NoArgs => "*".to_owned(),
+                        ParameterP::Args(name, _) => format!("*{}", name.node.0),
+                        ParameterP::KwArgs(name, _) => format!("**{}", name.node.0),
+                    })
+                    .collect(),
+                summary: summary(body),
+            }),
+            _ => {}
+        });
+        ModuleOutline {
+            summary: summary(&module.statement),
+            loads,
+            items,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stable::ItemKind;
+    use crate::stable::ModuleOutline;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    #[test]
+    fn test_module_outline() {
+        let program = r#"
+"""Module summary."""
+load("lib.star", "a", b = "c")
+def f(x, y = 1, *args, z = 2, **kwargs):
+    """Function summary.
+
+    Details.
+    """
+    pass
+X, _y = 1, 2
+"#;
+        let ast = AstModule::parse("x.star", program.to_owned(), &Dialect::Extended).unwrap();
+        let outline = ModuleOutline::from(&ast);
+        assert_eq!(Some("Module summary."), outline.summary.as_deref());
+        assert_eq!("lib.star", outline.loads[0].module);
+        assert_eq!(
+            vec![("a", "a"), ("b", "c")],
+            outline.loads[0]
+                .symbols
+                .iter()
+                .map(|x| (x.local.as_str(), x.exported.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                ("f", ItemKind::Function),
+                ("X", ItemKind::Variable),
+                ("_y", ItemKind::Variable),
+            ],
+            outline
+                .items
+                .iter()
+                .map(|x| (x.name.as_str(), x.kind))
+                .collect::<Vec<_>>()
+        );
+        let f = &outline.items[0];
+        assert_eq!(vec!["x", "y", "*args", "z", "**kwargs"], f.params);
+        assert_eq!(Some("Function summary."), f.summary.as_deref());
+        assert_eq!(3, f.span.begin.line);
+        assert_eq!(4, f.span.begin.column);
+    }
+}
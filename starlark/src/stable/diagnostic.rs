@@ -0,0 +1,200 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::codemap::ResolvedFileSpan;
+use crate::errors;
+use crate::errors::EvalMessage;
+use crate::errors::EvalSeverity;
+use crate::errors::Lint;
+use crate::stable::Span;
+
+/// How bad a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Severity {
+    /// The code failed to parse or evaluate.
+    Error,
+    /// The code is likely to be wrong.
+    Warning,
+    /// The code could be improved.
+    Advice,
+    /// A lint which is not shown by default.
+    Disabled,
+}
+
+impl From<EvalSeverity> for Severity {
+    fn from(severity: EvalSeverity) -> Severity {
+        match severity {
+            EvalSeverity::Error => Severity::Error,
+            EvalSeverity::Warning => Severity::Warning,
+            EvalSeverity::Advice => Severity::Advice,
+            EvalSeverity::Disabled => Severity::Disabled,
+        }
+    }
+}
+
+/// A frame of the call stack of an error.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Frame {
+    /// Name of the function.
+    pub name: String,
+    /// Location of the call, or `None` for native functions.
+    pub span: Option<Span>,
+}
+
+/// An error or a lint.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    /// How bad the problem is.
+    pub severity: Severity,
+    /// kebab-case name of the lint, e.g. `missing-return`, `None` for errors.
+    pub code: Option<String>,
+    /// Description of the problem.
+    pub message: String,
+    /// Location of the problem.
+    pub span: Option<Span>,
+    /// Call stack of an evaluation error, the most recent call last.
+    pub call_stack: Vec<Frame>,
+}
+
+impl Diagnostic {
+    /// Convert an error returned by parsing or evaluation.
+    pub fn from_error(error: &anyhow::Error) -> Diagnostic {
+        match error.downcast_ref::<errors::Diagnostic>() {
+            Some(d) => Diagnostic::from(d),
+            None => Diagnostic {
+                severity: Severity::Error,
+                code: None,
+                message: format!("{:#}", error),
+                span: None,
+                call_stack: Vec::new(),
+            },
+        }
+    }
+}
+
+impl From<&errors::Diagnostic> for Diagnostic {
+    fn from(d: &errors::Diagnostic) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code: None,
+            message: format!("{:#}", d.message),
+            span: d.span.as_ref().map(Span::from),
+            call_stack: d
+                .call_stack
+                .clone()
+                .into_frames()
+                .into_iter()
+                .map(|x| Frame {
+                    name: x.name,
+                    span: x.location.as_ref().map(Span::from),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Lints which are not serious are disabled, like in [`EvalMessage`].
+impl From<&Lint> for Diagnostic {
+    fn from(lint: &Lint) -> Diagnostic {
+        Diagnostic {
+            severity: if lint.serious {
+                Severity::Warning
+            } else {
+                Severity::Disabled
+            },
+            code: Some(lint.short_name.clone()),
+            message: lint.problem.clone(),
+            span: Some(Span::from(&lint.location)),
+            call_stack: Vec::new(),
+        }
+    }
+}
+
+impl From<&EvalMessage> for Diagnostic {
+    fn from(x: &EvalMessage) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::from(x.severity),
+            code: match x.severity {
+                EvalSeverity::Error => None,
+                _ => Some(x.name.clone()),
+            },
+            message: x.description.clone(),
+            span: x.span.map(|span| {
+                Span::from(&ResolvedFileSpan {
+                    file: x.path.clone(),
+                    span,
+                })
+            }),
+            call_stack: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::eval::Evaluator;
+    use crate::stable::Diagnostic;
+    use crate::stable::Severity;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    #[test]
+    fn test_diagnostic_from_error() {
+        let program = "def f():\n    fail('bad')\nf()\n";
+        let ast = AstModule::parse("x.star", program.to_owned(), &Dialect::Extended).unwrap();
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        let err = eval.eval_module(ast, &Globals::standard()).unwrap_err();
+        let d = Diagnostic::from_error(&err);
+        assert_eq!(Severity::Error, d.severity);
+        assert_eq!("fail: bad", d.message);
+        assert_eq!(1, d.span.unwrap().begin.line);
+        assert_eq!(
+            vec!["f", "fail"],
+            d.call_stack
+                .iter()
+                .map(|x| x.name.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_from_lint() {
+        let ast = AstModule::parse(
+            "x.star",
+            "def f():\n    return\n".to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        let lints = ast.lint(None);
+        let d = Diagnostic::from(&lints[0]);
+        assert_eq!(Severity::Disabled, d.severity);
+        assert_eq!(Some("redundant-return"), d.code.as_deref());
+        let json = serde_json::to_value(&d).unwrap();
+        assert_eq!("disabled", json["severity"]);
+        assert_eq!(1, json["span"]["begin"]["line"]);
+    }
+}
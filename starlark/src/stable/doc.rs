@@ -0,0 +1,227 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::docs;
+use crate::docs::DocItem;
+use crate::docs::DocString;
+use crate::docs::Member;
+
+/// What is documented by a [`Doc`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum DocKind {
+    /// A module.
+    Module,
+    /// An object with members.
+    Object,
+    /// A function or a method.
+    Function,
+    /// A property of an object.
+    Property,
+}
+
+/// Kind of a [`Param`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ParamKind {
+    /// A regular parameter.
+    Normal,
+    /// The `*` separator before named-only parameters, it has an empty name.
+    NoArgs,
+    /// `*args`.
+    Args,
+    /// `**kwargs`.
+    Kwargs,
+}
+
+/// A parameter of a function.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Param {
+    /// Name of the parameter, without stars.
+    pub name: String,
+    /// Kind of the parameter.
+    pub kind: ParamKind,
+    /// The type as written in the code.
+    #[serde(rename = "type")]
+    pub typ: Option<String>,
+    /// `repr()` of the default value.
+    pub default_value: Option<String>,
+    /// Documentation of the parameter.
+    pub docs: Option<String>,
+}
+
+/// Documentation of a module, an object, or their members.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Doc {
+    /// Name of the documented item.
+    pub name: String,
+    /// What the item is.
+    pub kind: DocKind,
+    /// The first line of the documentation.
+    pub summary: Option<String>,
+    /// The rest of the documentation.
+    pub details: Option<String>,
+    /// Parameters of a function.
+    pub params: Vec<Param>,
+    /// Return type of a function, or type of a property.
+    #[serde(rename = "type")]
+    pub typ: Option<String>,
+    /// Members of an object, in order.
+    pub members: Vec<Doc>,
+    /// Custom attributes for documentation tools.
+    pub custom_attrs: BTreeMap<String, String>,
+}
+
+fn render(docs: &Option<DocString>) -> Option<String> {
+    let docs = docs.as_ref()?;
+    Some(match &docs.details {
+        Some(details) => format!("{}\n\n{}", docs.summary, details),
+        None => docs.summary.clone(),
+    })
+}
+
+impl Param {
+    fn new(param: &docs::Param) -> Param {
+        let (name, kind, docs, typ, default_value) = match param {
+            docs::Param::Arg {
+                name,
+                docs,
+                typ,
+                default_value,
+            } => (
+                name.as_str(),
+                ParamKind::Normal,
+                docs,
+                typ,
+                default_value.clone(),
+            ),
+            docs::Param::NoArgs => ("", ParamKind::NoArgs, &None, &None, None),
+            docs::Param::Args { name, docs, typ } => {
+                (name.as_str(), ParamKind::Args, docs, typ, None)
+            }
+            docs::Param::Kwargs { name, docs, typ } => {
+                (name.as_str(), ParamKind::Kwargs, docs, typ, None)
+            }
+        };
+        Param {
+            name: name.to_owned(),
+            kind,
+            typ: typ.as_ref().map(|x| x.raw_type.clone()),
+            default_value,
+            docs: render(docs),
+        }
+    }
+}
+
+impl Doc {
+    fn new(name: &str, kind: DocKind, docs: &Option<DocString>) -> Doc {
+        Doc {
+            name: name.to_owned(),
+            kind,
+            summary: docs.as_ref().map(|x| x.summary.clone()),
+            details: docs.as_ref().and_then(|x| x.details.clone()),
+            params: Vec::new(),
+            typ: None,
+            members: Vec::new(),
+            custom_attrs: BTreeMap::new(),
+        }
+    }
+
+    fn function(name: &str, f: &docs::Function) -> Doc {
+        Doc {
+            params: f.params.iter().map(Param::new).collect(),
+            typ: f.ret.typ.as_ref().map(|x| x.raw_type.clone()),
+            ..Doc::new(name, DocKind::Function, &f.docs)
+        }
+    }
+
+    /// Convert documentation of an item with the given name.
+    pub fn from_item(name: &str, item: &DocItem) -> Doc {
+        match item {
+            DocItem::Module(m) => Doc::new(name, DocKind::Module, &m.docs),
+            DocItem::Function(f) => Doc::function(name, f),
+            DocItem::Object(o) => Doc {
+                members: o
+                    .members
+                    .iter()
+                    .map(|(name, member)| match member {
+                        Member::Function(f) => Doc::function(name, f),
+                        Member::Property(p) => Doc {
+                            typ: p.typ.as_ref().map(|x| x.raw_type.clone()),
+                            ..Doc::new(name, DocKind::Property, &p.docs)
+                        },
+                    })
+                    .collect(),
+                ..Doc::new(name, DocKind::Object, &o.docs)
+            },
+        }
+    }
+}
+
+impl From<&docs::Doc> for Doc {
+    fn from(doc: &docs::Doc) -> Doc {
+        Doc {
+            custom_attrs: doc
+                .custom_attrs
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            ..Doc::from_item(&doc.id.name, &doc.item)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::environment::Globals;
+    use crate::stable::Doc;
+    use crate::stable::DocKind;
+    use crate::stable::ParamKind;
+
+    #[test]
+    fn test_doc_from_globals() {
+        let doc = Doc::from_item("globals", &Globals::standard().documentation());
+        assert_eq!(DocKind::Object, doc.kind);
+        let sorted = doc.members.iter().find(|x| x.name == "sorted").unwrap();
+        assert_eq!(DocKind::Function, sorted.kind);
+        assert!(sorted.summary.is_some());
+        assert_eq!(
+            vec![
+                ("x", ParamKind::Normal),
+                ("", ParamKind::NoArgs),
+                ("key", ParamKind::Normal),
+                ("reverse", ParamKind::Normal),
+            ],
+            sorted
+                .params
+                .iter()
+                .map(|x| (x.name.as_str(), x.kind))
+                .collect::<Vec<_>>()
+        );
+        let json = serde_json::to_value(sorted).unwrap();
+        assert_eq!("function", json["kind"]);
+    }
+}
@@ -0,0 +1,53 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Snapshots of diagnostics, documentation, spans and module outlines
+//! with a representation which is stable across releases.
+//!
+//! The types in the rest of the crate change as the implementation evolves,
+//! so tools which only need to inspect the results (e.g. linters, documentation generators
+//! and editors) can convert them to the types in this module instead.
+//!
+//! The types in this module follow these rules:
+//!
+//! * Fields and enum variants are only added, never removed, renamed or changed,
+//!   so the types are marked `#[non_exhaustive]`.
+//! * The serialized representation is the same, except for the added fields.
+//! * [`VERSION`] is incremented when anything is added.
+
+mod ast;
+mod diagnostic;
+mod doc;
+mod span;
+
+pub use ast::Item;
+pub use ast::ItemKind;
+pub use ast::Load;
+pub use ast::LoadSymbol;
+pub use ast::ModuleOutline;
+pub use diagnostic::Diagnostic;
+pub use diagnostic::Frame;
+pub use diagnostic::Severity;
+pub use doc::Doc;
+pub use doc::DocKind;
+pub use doc::Param;
+pub use doc::ParamKind;
+pub use span::Position;
+pub use span::Span;
+
+/// Version of the types in this module, incremented when fields or variants are added.
+pub const VERSION: u32 = 1;
@@ -0,0 +1,66 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::codemap::FileSpan;
+use crate::codemap::ResolvedFileSpan;
+
+/// A position in a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Position {
+    /// 0-based line number.
+    pub line: usize,
+    /// 0-based column number.
+    pub column: usize,
+}
+
+/// A file and a range within it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Span {
+    /// File name.
+    pub file: String,
+    /// Beginning of the range.
+    pub begin: Position,
+    /// End of the range, exclusive.
+    pub end: Position,
+}
+
+impl From<&ResolvedFileSpan> for Span {
+    fn from(span: &ResolvedFileSpan) -> Span {
+        Span {
+            file: span.file.clone(),
+            begin: Position {
+                line: span.span.begin_line,
+                column: span.span.begin_column,
+            },
+            end: Position {
+                line: span.span.end_line,
+                column: span.span.end_column,
+            },
+        }
+    }
+}
+
+impl From<&FileSpan> for Span {
+    fn from(span: &FileSpan) -> Span {
+        Span::from(&span.resolve())
+    }
+}
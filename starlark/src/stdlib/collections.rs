@@ -0,0 +1,165 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Collection utilities of [`LibraryExtension::Collections`](crate::stdlib::LibraryExtension).
+
+use thiserror::Error;
+
+use crate as starlark;
+use crate::collections::SmallSet;
+use crate::environment::GlobalsBuilder;
+use crate::values::Heap;
+use crate::values::Value;
+
+#[derive(Debug, Error)]
+enum CollectionsError {
+    #[error("Chunk size must be positive, got {0}")]
+    ChunkSize(i32),
+}
+
+#[starlark_module]
+pub(crate) fn collections(builder: &mut GlobalsBuilder) {
+    /// Concatenate the elements of `seq`, which are iterables, into a list.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// flatten([[1, 2], (3,), []]) == [1, 2, 3]
+    /// # "#);
+    /// ```
+    #[starlark(return_type = "[\"\"]")]
+    fn flatten<'v>(
+        #[starlark(require = pos, type = "iter(\"\")")] seq: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        let mut iterables = Vec::new();
+        let mut len = 0;
+        for x in seq.iterate(heap)? {
+            let it = x.iterate(heap)?;
+            len += it.size_hint().0;
+            iterables.push(it);
+        }
+        let mut res = Vec::with_capacity(len);
+        for it in iterables {
+            res.extend(it);
+        }
+        Ok(heap.alloc_list(&res))
+    }
+
+    /// The distinct elements of `seq` in the order of their first occurrence.
+    /// The elements must be hashable.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// unique([3, 1, 3, 2, 1]) == [3, 1, 2]
+    /// # "#);
+    /// ```
+    #[starlark(return_type = "[\"\"]")]
+    fn unique<'v>(
+        #[starlark(require = pos, type = "iter(\"\")")] seq: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        let it = seq.iterate(heap)?;
+        let mut res = SmallSet::with_capacity(it.size_hint().0);
+        for x in it {
+            res.insert_hashed(x.get_hashed()?);
+        }
+        Ok(heap.alloc_list_iter(res))
+    }
+
+    /// Split `seq` into lists of `size` elements, the last list may be shorter.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// chunks([1, 2, 3, 4, 5], 2) == [[1, 2], [3, 4], [5]]
+    /// # "#);
+    /// ```
+    #[starlark(return_type = "[[\"\"]]")]
+    fn chunks<'v>(
+        #[starlark(require = pos, type = "iter(\"\")")] seq: Value<'v>,
+        #[starlark(require = pos)] size: i32,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        if size <= 0 {
+            return Err(CollectionsError::ChunkSize(size).into());
+        }
+        let size = size as usize;
+        let elems: Vec<Value> = seq.iterate(heap)?.collect();
+        let mut res = Vec::with_capacity(elems.len().div_ceil(size));
+        for chunk in elems.chunks(size) {
+            res.push(heap.alloc_list(chunk));
+        }
+        Ok(heap.alloc_list(&res))
+    }
+
+    /// Like `zip`, but continues until the longest iterable is exhausted,
+    /// using `fillvalue` for the missing elements of shorter iterables.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// zip_longest([1, 2], ["a"], fillvalue = 0) == [(1, "a"), (2, 0)]
+    /// # "#);
+    /// ```
+    #[starlark(return_type = "[(\"\")]")]
+    fn zip_longest<'v>(
+        #[starlark(args)] args: Vec<Value<'v>>,
+        #[starlark(require = named)] fillvalue: Option<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        let fillvalue = fillvalue.unwrap_or_else(Value::new_none);
+        let columns = args
+            .iter()
+            .map(|x| Ok(x.iterate(heap)?.collect::<Vec<_>>()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let len = columns.iter().map(|x| x.len()).max().unwrap_or_default();
+        let mut row = Vec::with_capacity(columns.len());
+        let mut res = Vec::with_capacity(len);
+        for i in 0..len {
+            row.clear();
+            row.extend(
+                columns
+                    .iter()
+                    .map(|x| x.get(i).copied().unwrap_or(fillvalue)),
+            );
+            res.push(heap.alloc_tuple(&row));
+        }
+        Ok(heap.alloc_list(&res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+
+    #[test]
+    fn test_collections() {
+        assert::all_true(
+            r#"
+flatten([]) == []
+flatten([{"a": 1}, range(2)]) == ["a", 0, 1]
+unique([]) == []
+unique(["b", "a", "b"]) == ["b", "a"]
+chunks([], 3) == []
+chunks(range(4), 4) == [[0, 1, 2, 3]]
+zip_longest() == []
+zip_longest([1], [2, 3]) == [(1, 2), (None, 3)]
+"#,
+        );
+        assert::fail("flatten([1])", "not supported");
+        assert::fail("unique([[1]])", "not hashable");
+        assert::fail("chunks([1], 0)", "Chunk size must be positive, got 0");
+    }
+}
@@ -15,8 +15,11 @@
  * limitations under the License.
  */
 
+use std::cell::RefCell;
 use std::fmt;
 use std::fmt::Display;
+use std::sync::atomic;
+use std::sync::atomic::AtomicUsize;
 
 use allocative::Allocative;
 use gazebo::any::ProvidesStaticType;
@@ -24,16 +27,21 @@ use gazebo::coerce::coerce;
 use gazebo::coerce::Coerce;
 use gazebo::prelude::*;
 use itertools::Itertools;
+use starlark_map::small_map::SmallMap;
+use starlark_map::Hashed;
 
 use crate as starlark;
 use crate::collections::symbol_map::Symbol;
+use crate::docs::DocItem;
 use crate::environment::GlobalsBuilder;
 use crate::eval::runtime::arguments::ArgNames;
 use crate::eval::runtime::arguments::ArgumentsFull;
 use crate::eval::runtime::rust_loc::rust_loc;
 use crate::eval::Arguments;
 use crate::eval::Evaluator;
+use crate::values::bytes::StarlarkBytes;
 use crate::values::dict::DictRef;
+use crate::values::label::Label;
 use crate::values::function::FUNCTION_TYPE;
 use crate::values::layout::typed::string::StringValueLike;
 use crate::values::none::NoneType;
@@ -43,6 +51,7 @@ use crate::values::Freeze;
 use crate::values::Freezer;
 use crate::values::FrozenStringValue;
 use crate::values::FrozenValue;
+use crate::values::Heap;
 use crate::values::StarlarkValue;
 use crate::values::StringValue;
 use crate::values::Trace;
@@ -118,6 +127,32 @@ pub fn partial(builder: &mut GlobalsBuilder) {
     }
 }
 
+#[starlark_module]
+pub fn memo(builder: &mut GlobalsBuilder) {
+    /// Wrap a function into a function which caches its results.
+    ///
+    /// The cache is keyed by the arguments of the call, so `func` should be pure.
+    /// Calls with unhashable arguments or with `*args`/`**kwargs` are not cached.
+    /// When `max_size` is given, the least recently used entry is evicted
+    /// once the cache grows larger than `max_size`.
+    ///
+    /// The returned function has attributes `cache_hits`, `cache_misses` and `cache_size`.
+    /// After the module is frozen, the cache is read-only: cached results are still returned,
+    /// and calls with other arguments invoke `func` without caching.
+    fn memo<'v>(
+        #[starlark(require = pos)] func: Value<'v>,
+        #[starlark(require = named)] max_size: Option<usize>,
+    ) -> anyhow::Result<Memo<'v>> {
+        Ok(Memo {
+            func,
+            max_size,
+            cache: MemoEntries::default(),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        })
+    }
+}
+
 #[starlark_module]
 pub fn debug(builder: &mut GlobalsBuilder) {
     /// Print the value with full debug formatting. The result may not be stable over time,
@@ -137,6 +172,39 @@ pub fn regex(builder: &mut GlobalsBuilder) {
     }
 }
 
+#[starlark_module]
+pub fn label(builder: &mut GlobalsBuilder) {
+    /// Parses an absolute build target label like `//package:name`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// label("//foo").name == "foo"
+    /// # "#);
+    /// ```
+    fn label(#[starlark(require = pos)] label: &str) -> anyhow::Result<Label> {
+        Label::parse(label)
+    }
+}
+
+#[starlark_module]
+pub fn bytes(builder: &mut GlobalsBuilder) {
+    /// Creates bytes from a string (UTF-8 encoded), other bytes or an iterable of ints
+    /// between 0 and 255.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// bytes([104, 105]) == bytes("hi")
+    /// # "#);
+    /// ```
+    #[starlark(type = StarlarkBytes::TYPE)]
+    fn bytes<'v>(
+        #[starlark(require = pos)] x: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<StarlarkBytes> {
+        StarlarkBytes::from_starlark(x, heap)
+    }
+}
+
 struct PrintWrapper<'a, 'b>(&'a Vec<Value<'b>>);
 impl fmt::Display for PrintWrapper<'_, '_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -292,6 +360,176 @@ where
     }
 }
 
+/// Storage of the results of a memoized function.
+trait MemoCache<'v>: fmt::Debug + Allocative {
+    fn lookup(&self, key: Hashed<Value<'v>>, max_size: Option<usize>) -> Option<Value<'v>>;
+    fn store(&self, key: Hashed<Value<'v>>, value: Value<'v>, max_size: Option<usize>);
+    fn size(&self) -> usize;
+}
+
+#[derive(Debug, Default, Trace, ProvidesStaticType, Allocative)]
+struct MemoEntries<'v>(RefCell<SmallMap<Value<'v>, Value<'v>>>);
+
+#[derive(Debug, Trace, ProvidesStaticType, Allocative)]
+struct FrozenMemoEntries(SmallMap<FrozenValue, FrozenValue>);
+
+impl<'v> MemoCache<'v> for MemoEntries<'v> {
+    fn lookup(&self, key: Hashed<Value<'v>>, max_size: Option<usize>) -> Option<Value<'v>> {
+        let mut cache = self.0.borrow_mut();
+        if max_size.is_some() {
+            // Move the entry to the end, so the first entry is the least recently used.
+            let (key, value) = cache.remove_hashed_entry(key.as_ref())?;
+            cache.insert_hashed(Hashed::new_unchecked(key_hash(&key), key), value);
+            Some(value)
+        } else {
+            cache.get_hashed_by_value(key).copied()
+        }
+    }
+
+    fn store(&self, key: Hashed<Value<'v>>, value: Value<'v>, max_size: Option<usize>) {
+        let mut cache = self.0.borrow_mut();
+        cache.insert_hashed(key, value);
+        if let Some(max_size) = max_size {
+            while cache.len() > max_size {
+                let oldest = cache.iter_hashed().next().unwrap().0.copied();
+                cache.remove_hashed(oldest.as_ref());
+            }
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.0.borrow().len()
+    }
+}
+
+impl<'v> MemoCache<'v> for FrozenMemoEntries {
+    fn lookup(&self, key: Hashed<Value<'v>>, _max_size: Option<usize>) -> Option<Value<'v>> {
+        self.0.get_hashed_by_value(key).map(|v| v.to_value())
+    }
+
+    fn store(&self, _key: Hashed<Value<'v>>, _value: Value<'v>, _max_size: Option<usize>) {
+        // Frozen cache is read-only.
+    }
+
+    fn size(&self) -> usize {
+        self.0.len()
+    }
+}
+
+fn key_hash(key: &Value) -> starlark_map::StarlarkHashValue {
+    // Keys are only stored if they are hashable.
+    key.get_hashed().unwrap().hash()
+}
+
+#[derive(Debug, Trace, NoSerialize, ProvidesStaticType, Allocative)]
+struct MemoGen<V, C> {
+    func: V,
+    max_size: Option<usize>,
+    cache: C,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl<V: Display, C> Display for MemoGen<V, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "memo({})", self.func)
+    }
+}
+
+type Memo<'v> = MemoGen<Value<'v>, MemoEntries<'v>>;
+type FrozenMemo = MemoGen<FrozenValue, FrozenMemoEntries>;
+starlark_complex_values!(Memo);
+
+impl<'v> Freeze for Memo<'v> {
+    type Frozen = FrozenMemo;
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        Ok(FrozenMemo {
+            func: self.func.freeze(freezer)?,
+            max_size: self.max_size,
+            cache: FrozenMemoEntries(self.cache.0.into_inner().freeze(freezer)?),
+            hits: self.hits,
+            misses: self.misses,
+        })
+    }
+}
+
+impl<V, C> MemoGen<V, C> {
+    /// Key for the cache, or `None` if the call should not be cached.
+    fn key<'v>(args: &Arguments<'v, '_>, heap: &'v Heap) -> Option<Hashed<Value<'v>>> {
+        if args.0.args.is_some() || args.0.kwargs.is_some() {
+            return None;
+        }
+        let named = heap.alloc_tuple_iter(
+            args.0
+                .names
+                .names()
+                .iter()
+                .zip(args.0.named)
+                .flat_map(|((_, name), value)| [name.to_value(), *value]),
+        );
+        let key = heap.alloc_tuple(&[heap.alloc_tuple(args.0.pos), named]);
+        key.get_hashed().ok()
+    }
+}
+
+impl<'v, V: ValueLike<'v> + 'v, C: MemoCache<'v> + 'v> StarlarkValue<'v> for MemoGen<V, C>
+where
+    Self: ProvidesStaticType,
+{
+    starlark_type!(FUNCTION_TYPE);
+
+    fn name_for_call_stack(&self, _me: Value<'v>) -> String {
+        "memo".to_owned()
+    }
+
+    fn documentation(&self) -> Option<DocItem> {
+        // The wrapped function, e.g. decorated with `@memo`, keeps its docs.
+        self.func.to_value().documentation()
+    }
+
+    fn invoke(
+        &self,
+        _me: Value<'v>,
+        args: &Arguments<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        let key = Self::key(args, eval.heap());
+        if let Some(key) = key {
+            if let Some(value) = self.cache.lookup(key, self.max_size) {
+                self.hits.fetch_add(1, atomic::Ordering::Relaxed);
+                return Ok(value);
+            }
+        }
+        self.misses.fetch_add(1, atomic::Ordering::Relaxed);
+        let value = self
+            .func
+            .to_value()
+            .invoke_with_loc(Some(rust_loc!()), args, eval)?;
+        if let Some(key) = key {
+            self.cache.store(key, value, self.max_size);
+        }
+        Ok(value)
+    }
+
+    fn get_attr(&self, attribute: &str, heap: &'v Heap) -> Option<Value<'v>> {
+        let count = match attribute {
+            "cache_hits" => self.hits.load(atomic::Ordering::Relaxed),
+            "cache_misses" => self.misses.load(atomic::Ordering::Relaxed),
+            "cache_size" => self.cache.size(),
+            _ => return None,
+        };
+        Some(heap.alloc(count as i32))
+    }
+
+    fn dir_attr(&self) -> Vec<String> {
+        vec![
+            "cache_hits".to_owned(),
+            "cache_misses".to_owned(),
+            "cache_size".to_owned(),
+        ]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::cell::RefCell;
@@ -339,6 +577,86 @@ assert_eq(["11",8], map(double, ["1",4]))
         );
     }
 
+    #[test]
+    fn test_memo() {
+        assert::pass(
+            r#"
+calls = []
+def slow_square(x, y = 0):
+    calls.append(x)
+    return x * x + y
+
+f = memo(slow_square)
+assert_eq(4, f(2))
+assert_eq(4, f(2))
+assert_eq(9, f(3))
+assert_eq(5, f(2, y = 1))
+assert_eq(5, f(2, y = 1))
+assert_eq([2, 3, 2], calls)
+assert_eq(2, f.cache_hits)
+assert_eq(3, f.cache_misses)
+assert_eq(3, f.cache_size)
+
+# Unhashable arguments are not cached.
+g = memo(len)
+assert_eq(2, g([1, 2]))
+assert_eq(2, g([1, 2]))
+assert_eq(0, g.cache_hits)
+assert_eq(0, g.cache_size)
+
+def fib(n):
+    return n if n < 2 else memo_fib(n - 1) + memo_fib(n - 2)
+memo_fib = memo(fib)
+assert_eq(6765, memo_fib(20))
+assert_eq(21, memo_fib.cache_misses)
+"#,
+        );
+    }
+
+    #[test]
+    fn test_memo_max_size() {
+        assert::pass(
+            r#"
+calls = []
+def f(x):
+    calls.append(x)
+    return x
+
+g = memo(f, max_size = 2)
+g(1)
+g(2)
+g(1)
+g(3) # evicts 2
+g(1)
+g(2)
+assert_eq([1, 2, 3, 2], calls)
+assert_eq(2, g.cache_size)
+"#,
+        );
+    }
+
+    #[test]
+    fn test_memo_frozen() {
+        let mut a = Assert::new();
+        a.module(
+            "m.star",
+            r#"
+def square(x):
+    return x * x
+f = memo(square)
+f(2)
+"#,
+        );
+        a.pass(
+            r#"
+load("m.star", "f")
+assert_eq(4, f(2))
+assert_eq(9, f(3))
+assert_eq(1, f.cache_size)
+"#,
+        );
+    }
+
     #[test]
     fn test_partial() {
         assert::pass(
@@ -26,9 +26,13 @@ use std::num::NonZeroI32;
 use crate as starlark;
 use crate::collections::SmallMap;
 use crate::environment::GlobalsBuilder;
+use crate::eval::bc::generator::collect_generator;
+use crate::eval::bc::generator::Generator;
+use crate::eval::bc::generator::iterate_while;
 use crate::eval::Arguments;
 use crate::eval::Evaluator;
 use crate::values::bool::BOOL_TYPE;
+use crate::values::bytes::StarlarkBytes;
 use crate::values::dict::Dict;
 use crate::values::dict::DictRef;
 use crate::values::float::StarlarkFloat;
@@ -50,6 +54,11 @@ use crate::values::Value;
 use crate::values::ValueError;
 use crate::values::ValueLike;
 
+/// Error raised by `fail()`.
+#[derive(Debug, thiserror::Error)]
+#[error("fail:{0}")]
+pub(crate) struct FailError(String);
+
 fn unpack_pair<'v>(pair: Value<'v>, heap: &'v Heap) -> anyhow::Result<(Value<'v>, Value<'v>)> {
     pair.with_iterator(heap, |it| {
         if let Some(first) = it.next() {
@@ -117,7 +126,7 @@ fn min_max<'v>(
     min: bool,
 ) -> anyhow::Result<Value<'v>> {
     if args.len() == 1 {
-        args.swap_remove(0)
+        collect_generator(args.swap_remove(0), eval)?
             .with_iterator(eval.heap(), |it| min_max_iter(it, key, eval, min))?
     } else {
         min_max_iter(args.into_iter(), key, eval, min)
@@ -150,7 +159,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
                 None => x.collect_repr(&mut s),
             }
         }
-        Err(anyhow::anyhow!("fail:{}", s))
+        Err(FailError(s).into())
     }
 
     /// [any](
@@ -172,16 +181,9 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     #[starlark(speculative_exec_safe)]
     fn any<'v>(
         #[starlark(require = pos, type = "iter(\"\")")] x: Value<'v>,
-        heap: &'v Heap,
+        eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<bool> {
-        x.with_iterator(heap, |it| {
-            for i in it {
-                if i.to_bool() {
-                    return true;
-                }
-            }
-            false
-        })
+        Ok(!iterate_while(x, eval, |i| !i.to_bool())?)
     }
 
     /// [all](
@@ -206,16 +208,9 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     #[starlark(speculative_exec_safe)]
     fn all<'v>(
         #[starlark(require = pos, type = "iter(\"\")")] x: Value<'v>,
-        heap: &'v Heap,
+        eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<bool> {
-        x.with_iterator(heap, |it| {
-            for i in it {
-                if !i.to_bool() {
-                    return false;
-                }
-            }
-            true
-        })
+        iterate_while(x, eval, |i| i.to_bool())
     }
 
     /// [bool](
@@ -404,10 +399,10 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     fn enumerate<'v>(
         #[starlark(require = pos, type = "iter(\"\")")] it: Value<'v>,
         #[starlark(default = 0)] start: i32,
-        heap: &'v Heap,
+        eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<impl AllocValue<'v>> {
-        let v = it
-            .iterate(heap)?
+        let v = collect_generator(it, eval)?
+            .iterate(eval.heap())?
             .enumerate()
             .map(move |(k, v)| (k as i32 + start, v));
         Ok(AllocList(v))
@@ -770,11 +765,15 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     #[starlark(type = ListRef::TYPE, speculative_exec_safe, return_type = "[\"\"]")]
     fn list<'v>(
         #[starlark(require = pos, type = "iter(\"\")")] a: Option<Value<'v>>,
-        heap: &'v Heap,
+        eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>> {
+        let heap = eval.heap();
         Ok(if let Some(a) = a {
             if let Some(xs) = ListRef::from_value(a) {
                 heap.alloc_list(xs.content())
+            } else if a.downcast_ref::<Generator>().is_some() {
+                // A fresh list, not shared with anything.
+                collect_generator(a, eval)?
             } else {
                 a.with_iterator(heap, |it| heap.alloc(AllocList(it)))?
             }
@@ -969,9 +968,9 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     #[starlark(speculative_exec_safe, return_type = "[\"\"]")]
     fn reversed<'v>(
         #[starlark(require = pos, type = "iter(\"\")")] a: Value<'v>,
-        heap: &'v Heap,
+        eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Vec<Value<'v>>> {
-        let mut v: Vec<Value> = a.iterate(heap)?.collect();
+        let mut v: Vec<Value> = collect_generator(a, eval)?.iterate(eval.heap())?.collect();
         v.reverse();
         Ok(v)
     }
@@ -1007,7 +1006,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
         #[starlark(require = named, default = false)] reverse: bool,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<AllocList<impl IntoIterator<Item = Value<'v>>>> {
-        let it = x.iterate(eval.heap())?;
+        let it = collect_generator(x, eval)?.iterate(eval.heap())?;
         let mut it = match key {
             None => it.map(|x| (x, x)).collect(),
             Some(key) => {
@@ -1064,6 +1063,8 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
         if let Some(a) = StringValue::new(a) {
             // Special case that can avoid reallocating, but is equivalent.
             Ok(a)
+        } else if let Some(a) = StarlarkBytes::from_value(a) {
+            Ok(eval.heap().alloc_str(&a.to_str_lossy()))
         } else {
             let mut s = eval.string_pool.alloc();
             a.collect_repr(&mut s);
@@ -1088,14 +1089,15 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     #[starlark(type = Tuple::TYPE, speculative_exec_safe)]
     fn tuple<'v>(
         #[starlark(require = pos, type = "iter(\"\")")] a: Option<Value<'v>>,
-        heap: &'v Heap,
+        eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>> {
+        let heap = eval.heap();
         if let Some(a) = a {
             if TupleRef::from_value(a).is_some() {
                 return Ok(a);
             }
 
-            a.with_iterator(heap, |it| heap.alloc_tuple_iter(it))
+            collect_generator(a, eval)?.with_iterator(heap, |it| heap.alloc_tuple_iter(it))
         } else {
             Ok(heap.alloc(AllocTuple::EMPTY))
         }
@@ -1140,13 +1142,14 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     #[starlark(speculative_exec_safe, return_type = "[\"\"]")]
     fn zip<'v>(
         #[starlark(args)] args: Vec<Value<'v>>,
-        heap: &'v Heap,
+        eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Vec<Value<'v>>> {
+        let heap = eval.heap();
         let mut v = Vec::new();
         let mut first = true;
         for arg in args {
             let mut idx = 0;
-            for e in arg.iterate(heap)? {
+            for e in collect_generator(arg, eval)?.iterate(heap)? {
                 if first {
                     v.push(heap.alloc((e,)));
                     idx += 1;
@@ -36,7 +36,10 @@ enum JsonError {
     UnrepresentableNumber(String),
 }
 
-fn serde_to_starlark<'v>(x: serde_json::Value, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+pub(crate) fn serde_to_starlark<'v>(
+    x: serde_json::Value,
+    heap: &'v Heap,
+) -> anyhow::Result<Value<'v>> {
     match x {
         serde_json::Value::Null => Ok(Value::new_none()),
         serde_json::Value::Bool(x) => Ok(Value::new_bool(x)),
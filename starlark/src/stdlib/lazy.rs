@@ -0,0 +1,222 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Deferred values of [`LibraryExtension::Lazy`](crate::stdlib::LibraryExtension).
+//!
+//! `lazy(f)` creates a thunk which calls `f` the first time it is passed to `force`,
+//! so values which are expensive to compute and rarely read are only computed when needed.
+
+use std::cell::Cell;
+use std::fmt;
+use std::fmt::Display;
+
+use allocative::Allocative;
+use either::Either;
+use gazebo::any::ProvidesStaticType;
+use thiserror::Error;
+
+use crate as starlark;
+use crate::environment::GlobalsBuilder;
+use crate::eval::Evaluator;
+use crate::values::Freeze;
+use crate::values::Freezer;
+use crate::values::FrozenValue;
+use crate::values::StarlarkValue;
+use crate::values::Trace;
+use crate::values::Value;
+
+#[derive(Debug, Error)]
+enum LazyError {
+    #[error("Cycle detected when forcing `{0}`, its function forces the value itself")]
+    Cycle(String),
+}
+
+#[starlark_module]
+pub(crate) fn lazy(builder: &mut GlobalsBuilder) {
+    /// Create a lazy value, which calls `func` without arguments when it is first forced.
+    ///
+    /// The result is cached, so `func` is called at most once. Forcing the value again
+    /// while `func` is running is an error. If `func` fails, nothing is cached.
+    ///
+    /// When the module is frozen, a forced value keeps its result. A value which was never
+    /// forced keeps `func` instead, and forcing it from another module calls `func` each time,
+    /// because the frozen value can't be updated.
+    fn lazy<'v>(#[starlark(require = pos)] func: Value<'v>) -> anyhow::Result<Lazy<'v>> {
+        Ok(Lazy {
+            func,
+            value: Cell::new(None),
+            forcing: Cell::new(false),
+        })
+    }
+
+    /// Compute the value of a value created by `lazy`, any other value is returned as is.
+    fn force<'v>(
+        #[starlark(require = pos)] x: Value<'v>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        match Lazy::from_value(x) {
+            None => Ok(x),
+            Some(Either::Left(x)) => x.force(eval),
+            Some(Either::Right(x)) => x.force(eval),
+        }
+    }
+}
+
+#[derive(Debug, Trace, NoSerialize, ProvidesStaticType, Allocative)]
+struct Lazy<'v> {
+    func: Value<'v>,
+    /// The result, once forced.
+    #[allocative(skip)]
+    value: Cell<Option<Value<'v>>>,
+    /// Whether `func` is running, used to detect cycles.
+    #[allocative(skip)]
+    forcing: Cell<bool>,
+}
+
+#[derive(Debug, NoSerialize, ProvidesStaticType, Allocative)]
+struct FrozenLazy {
+    func: FrozenValue,
+    value: Option<FrozenValue>,
+}
+
+starlark_complex_values!(Lazy);
+
+impl<'v> Lazy<'v> {
+    fn force(&self, eval: &mut Evaluator<'v, '_>) -> anyhow::Result<Value<'v>> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        if self.forcing.replace(true) {
+            return Err(LazyError::Cycle(self.to_string()).into());
+        }
+        let res = eval.eval_function(self.func, &[], &[]);
+        self.forcing.set(false);
+        let value = res?;
+        self.value.set(Some(value));
+        Ok(value)
+    }
+}
+
+impl FrozenLazy {
+    fn force<'v>(&self, eval: &mut Evaluator<'v, '_>) -> anyhow::Result<Value<'v>> {
+        match self.value {
+            Some(value) => Ok(value.to_value()),
+            None => eval.eval_function(self.func.to_value(), &[], &[]),
+        }
+    }
+}
+
+impl<'v> Display for Lazy<'v> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "lazy({})", self.func)
+    }
+}
+
+impl Display for FrozenLazy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "lazy({})", self.func)
+    }
+}
+
+impl<'v> Freeze for Lazy<'v> {
+    type Frozen = FrozenLazy;
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        Ok(FrozenLazy {
+            func: self.func.freeze(freezer)?,
+            value: self.value.get().freeze(freezer)?,
+        })
+    }
+}
+
+impl<'v> StarlarkValue<'v> for Lazy<'v> {
+    starlark_type!("lazy");
+}
+
+impl<'v> StarlarkValue<'v> for FrozenLazy {
+    starlark_type!("lazy");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert::Assert;
+    use crate::stdlib::LibraryExtension;
+
+    fn assert() -> Assert<'static> {
+        let mut a = Assert::new();
+        a.globals_add(|g| LibraryExtension::Lazy.add(g));
+        a
+    }
+
+    #[test]
+    fn test_lazy() {
+        assert().pass(
+            r#"
+calls = []
+def f():
+    calls.append(1)
+    return [1, 2]
+x = lazy(f)
+assert_eq([], calls)
+assert_eq([1, 2], force(x))
+assert_eq([1, 2], force(x))
+assert_eq([1], calls)
+assert_eq(3, force(3))
+assert_eq("lazy", type(x))
+"#,
+        );
+    }
+
+    #[test]
+    fn test_lazy_cycle() {
+        let a = assert();
+        a.fail(
+            r#"
+def f():
+    return force(x)
+x = lazy(f)
+force(x)
+"#,
+            "Cycle detected",
+        );
+    }
+
+    #[test]
+    fn test_lazy_frozen() {
+        let mut a = assert();
+        a.module(
+            "m",
+            r#"
+calls = []
+def f():
+    calls.append(1)
+    return len(calls)
+forced = lazy(f)
+force(forced)
+unforced = lazy(lambda: 42)
+"#,
+        );
+        a.pass(
+            r#"
+load("m", "forced", "unforced", "calls")
+assert_eq(1, force(forced))
+assert_eq(42, force(unforced))
+assert_eq(42, force(unforced))
+assert_eq([1], calls)
+"#,
+        );
+    }
+}
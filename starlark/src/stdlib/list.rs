@@ -19,6 +19,8 @@
 
 use crate as starlark;
 use crate::environment::MethodsBuilder;
+use crate::eval::bc::generator::collect_generator;
+use crate::eval::Evaluator;
 use crate::stdlib::util::convert_index;
 use crate::stdlib::util::convert_indices;
 use crate::values::list::ListRef;
@@ -105,8 +107,11 @@ pub(crate) fn list_methods(builder: &mut MethodsBuilder) {
     fn extend<'v>(
         this: Value<'v>,
         #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
-        heap: &'v Heap,
+        eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<NoneType> {
+        let heap = eval.heap();
+        // Run a generator first, it may access the list.
+        let other = collect_generator(other, eval)?;
         let res = ListData::from_value_mut(this)?;
         if this.ptr_eq(other) {
             // If the types alias, we can't borrow the `other` for iteration.
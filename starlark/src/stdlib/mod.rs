@@ -21,16 +21,21 @@
 use dupe::Dupe;
 
 use crate::environment::GlobalsBuilder;
+use crate::environment::StarlarkGlobalsStatic;
 
 pub(crate) mod breakpoint;
+pub(crate) mod collections;
 pub(crate) mod dict;
 pub(crate) mod enumeration;
 pub(crate) mod extra;
-mod funcs;
+pub(crate) mod funcs;
 pub(crate) mod json;
+pub(crate) mod lazy;
 
 pub(crate) mod list;
+pub(crate) mod parallel;
 pub(crate) mod record;
+pub(crate) mod set;
 pub(crate) mod string;
 pub(crate) mod structs;
 pub(crate) mod util;
@@ -46,6 +51,10 @@ pub(crate) fn standard_environment() -> GlobalsBuilder {
     GlobalsBuilder::new().with(funcs::global_functions)
 }
 
+/// Helper functions of [`LibraryExtension::Prelude`].
+static PRELUDE: StarlarkGlobalsStatic =
+    StarlarkGlobalsStatic::new("prelude.star", include_str!("prelude.star"));
+
 /// The extra library definitions available in this Starlark implementation, but not in the standard.
 #[derive(PartialEq, Eq, Copy, Clone, Dupe)]
 pub enum LibraryExtension {
@@ -55,6 +64,9 @@ pub enum LibraryExtension {
     RecordType,
     /// Definitions to support the `enum` type, the `enum()` constructor.
     EnumType,
+    /// Definitions to support the `set` type, the `set()` constructor.
+    /// Only usable in dialects with [`enable_sets`](crate::syntax::Dialect::enable_sets).
+    SetType,
     /// A function `map(f, xs)` which applies `f` to each element of `xs` and returns the result.
     Map,
     /// A function `filter(f, xs)` which applies `f` to each element of `xs` and returns those for which `f` returns `True`.
@@ -63,6 +75,8 @@ pub enum LibraryExtension {
     /// Partially apply a function, `partial(f, *args, **kwargs)` will create a function where those `args` `kwargs`
     /// are already applied to `f`.
     Partial,
+    /// Wrap a function into a function which caches its results, `memo(f, max_size = None)`.
+    Memo,
     /// Create a regex from a string.
     ExperimentalRegex,
     /// Add a function `debug(x)` which shows the Rust [`Debug`](std::fmt::Debug) representation of a value.
@@ -78,6 +92,25 @@ pub enum LibraryExtension {
     Json,
     /// Add a function `abs()` which will take the absolute value of an int.
     Abs,
+    /// Add a function `label()` which creates an interned build target label,
+    /// see [`Label`](crate::values::label::Label).
+    Label,
+    /// Add a function `bytes()` which creates a byte string,
+    /// see [`StarlarkBytes`](crate::values::bytes::StarlarkBytes).
+    Bytes,
+    /// Helper functions written in Starlark: `group_by(key, xs)` which groups `xs` into
+    /// a dict by `key(x)`, and `partition(predicate, xs)` which splits `xs` into the elements
+    /// which match `predicate` and the others.
+    Prelude,
+    /// Collection functions `flatten(xs)`, `unique(xs)`, `chunks(xs, size)`
+    /// and `zip_longest(*args, fillvalue = None)`.
+    Collections,
+    /// A namespace `parallel` with a function `parallel.map(f, items)` which calls
+    /// a frozen function for each item on several threads.
+    Parallel,
+    /// Functions `lazy(f)` which creates a value computed by calling `f` when first needed,
+    /// and `force(x)` which computes it.
+    Lazy,
     // Make sure if you add anything new, you add it to `all` below.
 }
 
@@ -89,9 +122,11 @@ impl LibraryExtension {
             StructType,
             RecordType,
             EnumType,
+            SetType,
             Map,
             Filter,
             Partial,
+            Memo,
             ExperimentalRegex,
             Debug,
             Print,
@@ -99,6 +134,12 @@ impl LibraryExtension {
             Breakpoint,
             Json,
             Abs,
+            Label,
+            Bytes,
+            Prelude,
+            Collections,
+            Parallel,
+            Lazy,
         ]
     }
 
@@ -109,9 +150,11 @@ impl LibraryExtension {
             StructType => structs::global(builder),
             RecordType => record::global(builder),
             EnumType => enumeration::global(builder),
+            SetType => set::global(builder),
             Map => extra::map(builder),
             Filter => extra::filter(builder),
             Partial => extra::partial(builder),
+            Memo => extra::memo(builder),
             ExperimentalRegex => extra::regex(builder),
             Debug => extra::debug(builder),
             Print => extra::print(builder),
@@ -119,6 +162,12 @@ impl LibraryExtension {
             Breakpoint => breakpoint::global(builder),
             Json => json::json(builder),
             Abs => extra::abs(builder),
+            Label => extra::label(builder),
+            Bytes => extra::bytes(builder),
+            Prelude => builder.set_starlark_globals(&PRELUDE),
+            Collections => collections::collections(builder),
+            Parallel => builder.struct_("parallel", parallel::parallel),
+            Lazy => lazy::lazy(builder),
         }
     }
 }
@@ -131,11 +180,13 @@ mod tests {
     use gazebo::any::ProvidesStaticType;
 
     use crate as starlark;
+    use crate::assert;
     use crate::assert::Assert;
     use crate::environment::GlobalsBuilder;
     use crate::environment::Methods;
     use crate::environment::MethodsBuilder;
     use crate::environment::MethodsStatic;
+    use crate::environment::StarlarkGlobalsStatic;
     use crate::values::none::NoneType;
     use crate::values::StarlarkValue;
     use crate::values::UnpackValue;
@@ -231,4 +282,35 @@ getattr(True2, "invert2")() == False2
 "#,
         );
     }
+
+    #[test]
+    fn test_prelude() {
+        assert::all_true(
+            r#"
+group_by(len, ["a", "bc", "d"]) == {1: ["a", "d"], 2: ["bc"]}
+partition(lambda x: x > 1, [3, 1, 2]) == ([3, 2], [1])
+"#,
+        );
+    }
+
+    #[test]
+    fn test_starlark_globals() {
+        static HELPERS: StarlarkGlobalsStatic = StarlarkGlobalsStatic::new(
+            "helpers.star",
+            "def triple(x):\n    return x * _factor()\ndef _factor():\n    return 3\n",
+        );
+        let globals = || {
+            GlobalsBuilder::standard()
+                .with(|builder| builder.set_starlark_globals(&HELPERS))
+                .build()
+        };
+        let (x, y) = (globals(), globals());
+        assert!(x.get("_factor").is_none());
+        // Both globals share the evaluated module.
+        assert!(x.get("triple").unwrap().ptr_eq(y.get("triple").unwrap()));
+
+        let mut a = Assert::new();
+        a.globals(x);
+        a.eq("6", "triple(2)");
+    }
 }
@@ -0,0 +1,179 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parallel evaluation of [`LibraryExtension::Parallel`](crate::stdlib::LibraryExtension).
+//!
+//! A function is called for each item on a thread pool, each call in a fresh module with
+//! its own heap. The function must be frozen, e.g. loaded from another module, so the calls
+//! only share frozen, read-only state. The calls are granted no capabilities, so they can't
+//! call natives restricted with
+//! [`require_capability`](crate::environment::GlobalsBuilder::require_capability).
+
+use std::thread;
+
+use anyhow::Context;
+use thiserror::Error;
+
+use crate as starlark;
+use crate::batch::map_on_threads;
+use crate::environment::GlobalsBuilder;
+use crate::environment::Module;
+use crate::eval::Evaluator;
+use crate::stdlib::json::serde_to_starlark;
+use crate::values::FrozenValue;
+use crate::values::OwnedFrozenValue;
+use crate::values::Value;
+
+/// Name of the variable holding the result in the module of a call.
+const RESULT: &str = "result";
+
+#[derive(Debug, Error)]
+enum ParallelError {
+    #[error(
+        "`parallel.map` requires a frozen function, e.g. one loaded from another module, got `{0}`"
+    )]
+    NotFrozen(String),
+}
+
+/// An item passed to a call on another thread.
+enum Item {
+    /// A frozen value, shared as is.
+    Frozen(FrozenValue),
+    /// Any other value, copied through its JSON representation.
+    Json(serde_json::Value),
+}
+
+impl Item {
+    fn new(x: Value) -> anyhow::Result<Self> {
+        match x.unpack_frozen() {
+            Some(x) => Ok(Item::Frozen(x)),
+            None => Ok(Item::Json(serde_json::to_value(x).with_context(|| {
+                format!(
+                    "`parallel.map` items must be frozen or convertible to JSON, got `{}`",
+                    x.get_type()
+                )
+            })?)),
+        }
+    }
+}
+
+fn call(function: FrozenValue, item: &Item) -> anyhow::Result<OwnedFrozenValue> {
+    let module = Module::new();
+    let x = match item {
+        Item::Frozen(x) => x.to_value(),
+        Item::Json(x) => serde_to_starlark(x.clone(), module.heap())?,
+    };
+    let mut eval = Evaluator::new(&module);
+    eval.set_capabilities(Vec::<String>::new());
+    let result = eval.eval_function(function.to_value(), &[x], &[])?;
+    module.set(RESULT, result);
+    module.freeze()?.get(RESULT)
+}
+
+#[starlark_module]
+pub(crate) fn parallel(builder: &mut GlobalsBuilder) {
+    /// Call `f` with each of `items` on several threads, returning the results in order.
+    ///
+    /// `f` must be frozen, i.e. loaded from another module, and can't call functions which
+    /// require a capability. Items which are not frozen are copied to the threads
+    /// through their JSON representation. Fails with the error of the first failed call.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// parallel.map(str, [1, 2]) == ["1", "2"]
+    /// # "#);
+    /// ```
+    #[starlark(return_type = "[\"\"]")]
+    fn map<'v>(
+        #[starlark(require = pos)] f: Value<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] items: Value<'v>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        let function = f
+            .unpack_frozen()
+            .ok_or_else(|| ParallelError::NotFrozen(f.to_repr()))?;
+        let items = items
+            .iterate(eval.heap())?
+            .map(Item::new)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let mut res = Vec::with_capacity(items.len());
+        for x in map_on_threads(&items, threads, |x| call(function, x)) {
+            res.push(x?.owned_value(eval.frozen_heap()));
+        }
+        Ok(eval.heap().alloc_list(&res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert::Assert;
+
+    fn assert() -> Assert<'static> {
+        let mut a = Assert::new();
+        a.module(
+            "lib.star",
+            r#"
+SUFFIX = "!"
+def describe(x):
+    if x < 0:
+        fail("negative")
+    return {"x": x, "text": str(x) + SUFFIX}
+XS = []
+def append(x):
+    XS.append(x)
+"#,
+        );
+        a
+    }
+
+    #[test]
+    fn test_map() {
+        let a = assert();
+        a.pass(
+            r#"
+load("lib.star", "describe")
+res = parallel.map(describe, range(100))
+assert_eq(len(res), 100)
+assert_eq(res[7], {"x": 7, "text": "7!"})
+assert_eq(parallel.map(describe, []), [])
+assert_eq(parallel.map(str, [None, (1, 2)]), ["None", "(1, 2)"])
+"#,
+        );
+    }
+
+    #[test]
+    fn test_map_errors() {
+        let a = assert();
+        a.fail(
+            "load('lib.star', 'describe')\nparallel.map(describe, [1, -1])",
+            "negative",
+        );
+        a.fail(
+            "load('lib.star', 'append')\nparallel.map(append, [1])",
+            "Immutable",
+        );
+        a.fail(
+            "def f(x): return x\nparallel.map(f, [1])",
+            "requires a frozen function",
+        );
+        a.fail(
+            "load('lib.star', 'describe')\nparallel.map(describe, [describe, lambda x: x])",
+            "convertible to JSON",
+        );
+    }
+}
@@ -0,0 +1,145 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Implementation of `set` function.
+use crate as starlark;
+use crate::environment::GlobalsBuilder;
+use crate::eval::bc::generator::collect_generator;
+use crate::eval::Evaluator;
+use crate::values::set::SetData;
+use crate::values::Value;
+
+#[starlark_module]
+pub fn global(builder: &mut GlobalsBuilder) {
+    /// Create a set, `set()` is empty and `set(xs)` contains the distinct elements of
+    /// the iterable `xs` in order. The elements must be hashable.
+    #[starlark(type = SetData::TYPE)]
+    fn set<'v>(
+        #[starlark(require = pos, type = "iter(\"\")")] a: Option<Value<'v>>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<SetData<'v>> {
+        let mut res = SetData::default();
+        if let Some(a) = a {
+            for x in collect_generator(a, eval)?.iterate(eval.heap())? {
+                res.add(x)?;
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert::Assert;
+    use crate::stdlib::LibraryExtension;
+    use crate::syntax::Dialect;
+
+    fn assert() -> Assert<'static> {
+        let mut a = Assert::new();
+        a.globals_add(|g| LibraryExtension::SetType.add(g));
+        a
+    }
+
+    #[test]
+    fn test_set() {
+        assert().pass(
+            r#"
+s = set([3, 1, 3, 2])
+assert_eq(3, len(s))
+assert_eq([3, 1, 2], list(s))
+assert_eq("set([3, 1, 2])", repr(s))
+assert_eq("set()", str(set()))
+assert_eq("set", type(s))
+assert_true(1 in s)
+assert_true(4 not in s)
+assert_true(s)
+assert_true(not set())
+assert_eq(set([1, 2, 3]), s)
+assert_true(set([1, 2]) != s)
+assert_true(set() != [])
+
+def gen():
+    yield 2
+    yield 2
+    yield 1
+assert_eq([2, 1], list(set(gen())))
+"#,
+        );
+    }
+
+    #[test]
+    fn test_set_methods() {
+        let a = assert();
+        a.pass(
+            r#"
+s = set()
+s.add(1)
+s.add("x")
+s.add(1)
+assert_eq([1, "x"], list(s))
+s.remove(1)
+assert_eq(["x"], list(s))
+u = s.union([1], (2, "x"))
+assert_eq(["x", 1, 2], list(u))
+assert_eq(["x"], list(s))
+"#,
+        );
+        a.fail("set([1]).remove(2)", "not found");
+        a.fail("set([[]])", "not hashable");
+        a.fail(
+            r#"
+s = set([1])
+for x in s:
+    s.add(2)
+"#,
+            "mutate an iterable",
+        );
+    }
+
+    #[test]
+    fn test_set_dialect() {
+        let mut a = assert();
+        a.dialect(&Dialect::Standard);
+        a.fail("set([1])", "`set` is not allowed in this dialect");
+        a.pass("def set(xs): return xs\nassert_eq([1], set([1]))");
+        a.dialect(&Dialect {
+            enable_sets: true,
+            ..Dialect::Standard
+        });
+        a.pass("assert_eq([1], list(set([1])))");
+    }
+
+    #[test]
+    fn test_set_frozen() {
+        let mut a = assert();
+        a.module("m", "s = set([1, 2])");
+        a.pass(
+            r#"
+load("m", "s")
+assert_true(2 in s)
+assert_eq([1, 2, 3], list(s.union([3])))
+"#,
+        );
+        a.fail(
+            r#"
+load("m", "s")
+s.add(3)
+"#,
+            "Immutable",
+        );
+    }
+}
@@ -23,6 +23,7 @@ use gazebo::prelude::*;
 
 use crate as starlark;
 use crate::environment::MethodsBuilder;
+use crate::eval::bc::generator::collect_generator;
 use crate::eval::Arguments;
 use crate::eval::Evaluator;
 use crate::stdlib::string::fast_string::convert_str_indices;
@@ -674,14 +675,15 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
     fn join<'v>(
         this: &str,
         #[starlark(require = pos, type = "iter(str.type)")] to_join: Value<'v>,
-        heap: &'v Heap,
+        eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>> {
+        let heap = eval.heap();
         #[inline(always)]
         fn as_str<'v>(x: Value<'v>) -> anyhow::Result<&'v str> {
             <&str>::unpack_named_param(x, "to_join")
         }
 
-        to_join.with_iterator(heap, |it| {
+        collect_generator(to_join, eval)?.with_iterator(heap, |it| {
             match it.next() {
                 None => Ok(Value::new_empty_string()),
                 Some(x1) => {
@@ -210,6 +210,12 @@ pub(crate) enum AssignP<P: AstPayload> {
     // as these have the same semantics in Starlark.
     Tuple(Vec<AstAssignP<P>>),
     ArrayIndirection(Box<(AstExprP<P>, AstExprP<P>)>),
+    Slice(
+        Box<AstExprP<P>>,
+        Option<Box<AstExprP<P>>>,
+        Option<Box<AstExprP<P>>>,
+        Option<Box<AstExprP<P>>>,
+    ),
     Dot(Box<AstExprP<P>>, AstString),
     Identifier(AstAssignIdentP<P>),
 }
@@ -311,6 +317,7 @@ pub(crate) enum StmtP<P: AstPayload> {
     Continue,
     Pass,
     Return(Option<AstExprP<P>>),
+    Yield(Option<AstExprP<P>>),
     Expression(AstExprP<P>),
     // LHS : TYPE = RHS for the fields
     Assign(AstAssignP<P>, Box<(Option<AstExprP<P>>, AstExprP<P>)>),
@@ -319,6 +326,7 @@ pub(crate) enum StmtP<P: AstPayload> {
     If(AstExprP<P>, Box<AstStmtP<P>>),
     IfElse(AstExprP<P>, Box<(AstStmtP<P>, AstStmtP<P>)>),
     For(AstAssignP<P>, Box<(AstExprP<P>, AstStmtP<P>)>),
+    While(AstExprP<P>, Box<AstStmtP<P>>),
     Def(DefP<P>),
     // The Visibility of a Load is implicit from the Dialect, not written by a user
     Load(LoadP<P>),
@@ -539,6 +547,20 @@ impl Display for Assign {
                 let (e, i) = &**e_i;
                 write!(f, "{}[{}]", e.node, i.node)
             }
+            Assign::Slice(e, i1, i2, i3) => {
+                write!(f, "{}[", e.node)?;
+                if let Some(x) = i1 {
+                    write!(f, "{}", x.node)?
+                }
+                f.write_str(":")?;
+                if let Some(x) = i2 {
+                    write!(f, "{}", x.node)?
+                }
+                if let Some(x) = i3 {
+                    write!(f, ":{}", x.node)?
+                }
+                f.write_str("]")
+            }
             Assign::Identifier(s) => write!(f, "{}", s.node),
         }
     }
@@ -604,6 +626,8 @@ impl Stmt {
             Stmt::Pass => writeln!(f, "{}pass", tab),
             Stmt::Return(Some(e)) => writeln!(f, "{}return {}", tab, e.node),
             Stmt::Return(None) => writeln!(f, "{}return", tab),
+            Stmt::Yield(Some(e)) => writeln!(f, "{}yield {}", tab, e.node),
+            Stmt::Yield(None) => writeln!(f, "{}yield", tab),
             Stmt::Expression(e) => writeln!(f, "{}{}", tab, e.node),
             Stmt::Assign(l, ty_r) => {
                 let (ty, r) = &**ty_r;
@@ -636,6 +660,10 @@ impl Stmt {
                 writeln!(f, "{}for {} in {}:", tab, bind.node, coll.node)?;
                 suite.node.fmt_with_tab(f, tab + "  ")
             }
+            Stmt::While(cond, suite) => {
+                writeln!(f, "{}while {}:", tab, cond.node)?;
+                suite.node.fmt_with_tab(f, tab + "  ")
+            }
             Stmt::Def(DefP {
                 name,
                 params,
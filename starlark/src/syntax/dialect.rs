@@ -16,6 +16,8 @@
  */
 
 use dupe::Dupe;
+use serde::Deserialize;
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::codemap::CodeMap;
@@ -23,24 +25,42 @@ use crate::codemap::Pos;
 use crate::codemap::Span;
 use crate::codemap::Spanned;
 use crate::errors::Diagnostic;
+use crate::syntax::ast::Argument;
+use crate::syntax::ast::AstArgument;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::Expr;
 use crate::syntax::ast::Visibility;
 
 #[derive(Error, Debug)]
 pub(crate) enum DialectError {
-    #[error("`def` is not allowed in this dialect")]
-    Def,
-    #[error("`lambda` is not allowed in this dialect")]
-    Lambda,
-    #[error("`load` is not allowed in this dialect")]
-    Load,
-    #[error("* keyword-only-arguments is not allowed in this dialect")]
-    KeywordOnlyArguments,
-    #[error("type annotations are not allowed in this dialect")]
-    Types,
+    #[error("`def` is not allowed in {}", .0.files())]
+    Def(DialectProfile),
+    #[error("`lambda` is not allowed in {}", .0.files())]
+    Lambda(DialectProfile),
+    #[error("`load` is not allowed in {}", .0.files())]
+    Load(DialectProfile),
+    #[error("* keyword-only-arguments is not allowed in {}", .0.files())]
+    KeywordOnlyArguments(DialectProfile),
+    #[error("type annotations are not allowed in {}", .0.files())]
+    Types(DialectProfile),
+    #[error("multiple subscripts `x[a, b]` are not allowed in {}", .0.files())]
+    MultipleSubscripts(DialectProfile),
+    #[error("`yield` is not allowed in {}", .0.files())]
+    Generators(DialectProfile),
+    #[error("`while` is not allowed in {}", .0.files())]
+    While(DialectProfile),
+    #[error("`*args` and `**kwargs` arguments are not allowed in {}", .0.files())]
+    StarArgs(DialectProfile),
+    #[error("decorators are not allowed in {}", .0.files())]
+    Decorators(DialectProfile),
+    #[error("f-strings are not allowed in {}", .0.files())]
+    FStrings(DialectProfile),
+    #[error("`set` is not allowed in {}", .0.files())]
+    Sets(DialectProfile),
 }
 
 /// How to handle type annotations in Starlark.
-#[derive(Debug, Clone, Copy, Dupe, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Copy, Dupe, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum DialectTypes {
     /// Prohibit types at parse time.
     Disable,
@@ -50,8 +70,36 @@ pub enum DialectTypes {
     Enable,
 }
 
+/// Kind of files a [`Dialect`] is intended for.
+/// Features disabled by the dialect are reported as not allowed in these files.
+#[derive(Debug, Clone, Copy, Dupe, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum DialectProfile {
+    /// Starlark modules, e.g. `.bzl` files.
+    Module,
+    /// Bazel `BUILD` files, which declare targets and cannot define functions.
+    Build,
+}
+
+impl DialectProfile {
+    /// Where disabled features are not allowed, for diagnostics.
+    pub(crate) fn files(self) -> &'static str {
+        match self {
+            DialectProfile::Module => "this dialect",
+            DialectProfile::Build => "BUILD files",
+        }
+    }
+
+    /// Where statements not allowed at the top level can be used, for diagnostics.
+    pub(crate) fn top_level_stmt(self) -> &'static str {
+        match self {
+            DialectProfile::Module => "outside `def` in this dialect",
+            DialectProfile::Build => "in BUILD files",
+        }
+    }
+}
+
 /// Starlark language features to enable, e.g. [`Standard`](Dialect::Standard) to follow the Starlark standard.
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct Dialect {
     /// Are `def` statements permitted.
     /// Enabled in both [`Standard`](Dialect::Standard) and [`Extended`](Dialect::Extended).
@@ -78,6 +126,49 @@ pub struct Dialect {
     /// Are `for`, `if` and other statements allowed at the top level.
     /// Only enabled in [`Extended`](Dialect::Extended).
     pub enable_top_level_stmt: bool,
+    /// Is `x[a, b]` permitted, indexing `x` with the tuple `(a, b)`.
+    /// Native types can use it to provide e.g. matrix-like indexing,
+    /// see [`StarlarkValue::at`](crate::values::StarlarkValue::at).
+    /// Enabled in both [`Standard`](Dialect::Standard) and [`Extended`](Dialect::Extended).
+    pub enable_multiple_subscripts: bool,
+    /// Are generator functions, i.e. `def` statements containing `yield`, permitted.
+    /// Only enabled in [`Extended`](Dialect::Extended).
+    pub enable_generators: bool,
+    /// Are `while` loops permitted.
+    /// Each iteration of a `while` loop consumes loop fuel of the evaluator,
+    /// see [`Evaluator::set_loop_fuel`](crate::eval::Evaluator::set_loop_fuel).
+    /// Disabled in both [`Standard`](Dialect::Standard) and [`Extended`](Dialect::Extended).
+    pub enable_while: bool,
+    /// Are `*args` and `**kwargs` arguments permitted in function calls.
+    /// Enabled in both [`Standard`](Dialect::Standard) and [`Extended`](Dialect::Extended),
+    /// disabled in [`Build`](Dialect::Build).
+    pub enable_star_args: bool,
+    /// Can a top-level variable be bound more than once, e.g. by a second assignment or `+=`.
+    /// Enabled in both [`Standard`](Dialect::Standard) and [`Extended`](Dialect::Extended),
+    /// disabled in [`Build`](Dialect::Build).
+    pub enable_global_reassign: bool,
+    /// Are decorators `@wrapper` above `def f` permitted, binding `f` to `wrapper(f)`.
+    /// Decorators are applied after the function is defined, from the innermost one.
+    /// Only enabled in [`Extended`](Dialect::Extended).
+    pub enable_decorators: bool,
+    /// Are mutable default values (lists and dicts) of `def` parameters copied on each call
+    /// which uses them, so mutations are not visible in later calls.
+    /// Defaults are evaluated once when the function is defined, as in Python,
+    /// in all the predefined dialects.
+    pub enable_fresh_defaults: bool,
+    /// Are f-strings `f"x = {x}"` permitted, formatting the expressions in curlies
+    /// like `"x = {}".format(x)`. Format specifications such as `{x:.2f}` are not supported.
+    /// Only enabled in [`Extended`](Dialect::Extended).
+    pub enable_f_strings: bool,
+    /// Can the predeclared `set` function be used, e.g. the one of
+    /// [`LibraryExtension::SetType`](crate::stdlib::LibraryExtension::SetType).
+    /// Modules can still define their own `set`.
+    /// Only enabled in [`Extended`](Dialect::Extended).
+    pub enable_sets: bool,
+    /// Kind of files the dialect is for, used in diagnostics.
+    /// [`Build`](DialectProfile::Build) in [`Build`](Dialect::Build),
+    /// [`Module`](DialectProfile::Module) otherwise.
+    pub profile: DialectProfile,
 }
 
 // These are morally enumerations, so give them enumeration-like names
@@ -94,6 +185,16 @@ impl Dialect {
         enable_tabs: true,
         enable_load_reexport: true, // But they plan to change it
         enable_top_level_stmt: false,
+        enable_multiple_subscripts: true,
+        enable_generators: false,
+        enable_while: false,
+        enable_star_args: true,
+        enable_global_reassign: true,
+        enable_decorators: false,
+        enable_fresh_defaults: false,
+        enable_f_strings: false,
+        enable_sets: false,
+        profile: DialectProfile::Module,
     };
 
     /// A superset of [`Standard`](Dialect::Standard), including extra features (types, top-level statements etc).
@@ -106,6 +207,41 @@ impl Dialect {
         enable_tabs: true,
         enable_load_reexport: true,
         enable_top_level_stmt: true,
+        enable_multiple_subscripts: true,
+        enable_generators: true,
+        enable_while: false,
+        enable_star_args: true,
+        enable_global_reassign: true,
+        enable_decorators: true,
+        enable_fresh_defaults: false,
+        enable_f_strings: true,
+        enable_sets: true,
+        profile: DialectProfile::Module,
+    };
+
+    /// Restrictions of Bazel `BUILD` files on top of [`Standard`](Dialect::Standard):
+    /// no `def`, no `*args` and `**kwargs` in calls, and top-level variables cannot be rebound.
+    /// As in [`Standard`](Dialect::Standard), `if` and `for` are not allowed at the top level.
+    /// Errors are reported as not allowed in `BUILD` files.
+    pub const Build: Self = Self {
+        enable_def: false,
+        enable_lambda: true,
+        enable_load: true,
+        enable_keyword_only_arguments: false,
+        enable_types: DialectTypes::Disable,
+        enable_tabs: true,
+        enable_load_reexport: true,
+        enable_top_level_stmt: false,
+        enable_multiple_subscripts: true,
+        enable_generators: false,
+        enable_while: false,
+        enable_star_args: false,
+        enable_global_reassign: false,
+        enable_decorators: false,
+        enable_fresh_defaults: false,
+        enable_f_strings: false,
+        enable_sets: false,
+        profile: DialectProfile::Build,
     };
 }
 
@@ -122,7 +258,7 @@ impl Dialect {
         if self.enable_lambda {
             Ok(x)
         } else {
-            err(codemap, x.span, DialectError::Lambda)
+            err(codemap, x.span, DialectError::Lambda(self.profile))
         }
     }
 
@@ -134,7 +270,56 @@ impl Dialect {
         if self.enable_def {
             Ok(x)
         } else {
-            err(codemap, x.span, DialectError::Def)
+            err(codemap, x.span, DialectError::Def(self.profile))
+        }
+    }
+
+    pub(crate) fn check_generators<T>(
+        &self,
+        codemap: &CodeMap,
+        x: Spanned<T>,
+    ) -> anyhow::Result<Spanned<T>> {
+        if self.enable_generators {
+            Ok(x)
+        } else {
+            err(codemap, x.span, DialectError::Generators(self.profile))
+        }
+    }
+
+    pub(crate) fn check_while<T>(
+        &self,
+        codemap: &CodeMap,
+        x: Spanned<T>,
+    ) -> anyhow::Result<Spanned<T>> {
+        if self.enable_while {
+            Ok(x)
+        } else {
+            err(codemap, x.span, DialectError::While(self.profile))
+        }
+    }
+
+    pub(crate) fn check_decorators<T>(
+        &self,
+        codemap: &CodeMap,
+        x: Spanned<T>,
+    ) -> anyhow::Result<Spanned<T>> {
+        if self.enable_decorators {
+            Ok(x)
+        } else {
+            err(codemap, x.span, DialectError::Decorators(self.profile))
+        }
+    }
+
+    pub(crate) fn check_argument(
+        &self,
+        codemap: &CodeMap,
+        x: AstArgument,
+    ) -> anyhow::Result<AstArgument> {
+        match x.node {
+            Argument::Args(_) | Argument::KwArgs(_) if !self.enable_star_args => {
+                err(codemap, x.span, DialectError::StarArgs(self.profile))
+            }
+            _ => Ok(x),
         }
     }
 
@@ -149,7 +334,11 @@ impl Dialect {
         if self.enable_keyword_only_arguments {
             Ok(x)
         } else {
-            err(codemap, span, DialectError::KeywordOnlyArguments)
+            err(
+                codemap,
+                span,
+                DialectError::KeywordOnlyArguments(self.profile),
+            )
         }
     }
 
@@ -161,7 +350,27 @@ impl Dialect {
         if self.enable_types != DialectTypes::Disable {
             Ok(x)
         } else {
-            err(codemap, x.span, DialectError::Types)
+            err(codemap, x.span, DialectError::Types(self.profile))
+        }
+    }
+
+    /// Check the index of `x[...]`, which starts at `begin`.
+    /// A tuple which is not parenthesized means multiple subscripts.
+    pub(crate) fn check_subscript(
+        &self,
+        codemap: &CodeMap,
+        begin: usize,
+        x: AstExpr,
+    ) -> anyhow::Result<AstExpr> {
+        let multiple = matches!(x.node, Expr::Tuple(_)) && x.span.begin() == Pos::new(begin as u32);
+        if !multiple || self.enable_multiple_subscripts {
+            Ok(x)
+        } else {
+            err(
+                codemap,
+                x.span,
+                DialectError::MultipleSubscripts(self.profile),
+            )
         }
     }
 
@@ -19,7 +19,10 @@ use gazebo::prelude::*;
 
 use crate::assert;
 use crate::assert::Assert;
+use crate::errors::Diagnostic;
 use crate::syntax::ast::Stmt;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
 
 #[test]
 fn test_empty() {
@@ -95,6 +98,19 @@ fn test_top_level_def() {
     assert_eq!(assert::parse("def t():\n\n  pass"), "def t():\n  pass\n");
 }
 
+#[test]
+fn test_multiple_subscripts() {
+    assert_eq!(assert::parse("x[1, 2]"), "x[(1, 2)]\n");
+    assert::eq("3", "{(1, 2): 3}[1, 2]");
+
+    let mut a = Assert::new();
+    a.dialect_set(|x| x.enable_multiple_subscripts = false);
+    a.parse_fail("x[!1, 2!]");
+    a.parse_fail("x[!(1), 2!]");
+    assert_eq!(a.parse("x[(1, 2)]"), "x[(1, 2)]\n");
+    assert_eq!(a.parse("x[1]"), "x[1]\n");
+}
+
 #[test]
 fn test_top_level_statements() {
     let mut a = Assert::new();
@@ -114,6 +130,80 @@ fn test_top_level_statements() {
     assert_eq!(assert::parse("pass"), "pass\n");
 }
 
+fn parse_fail_message(a: &Assert, program: &str) -> String {
+    let err = a.parse_fail(program);
+    err.downcast_ref::<Diagnostic>()
+        .unwrap()
+        .message
+        .to_string()
+}
+
+#[test]
+fn test_build_dialect() {
+    let mut a = Assert::new();
+    a.dialect(&Dialect::Build);
+    assert_eq!(
+        a.parse("load(\"a.bzl\", \"rule\")\nX = [1]\nrule(name = \"x\", deps = X + [2])"),
+        "load(\"a.bzl\"rule = \"rule\")\nX = [1]\nrule(name = \"x\", deps = (X + [2]))\n"
+    );
+    assert_eq!(a.parse("f(lambda x: x)"), "f((lambda x: x))\n");
+
+    assert_eq!(
+        "`def` is not allowed in BUILD files",
+        parse_fail_message(&a, "!def f():\n  pass\n!")
+    );
+    assert_eq!(
+        "`if` cannot be used in BUILD files",
+        parse_fail_message(&a, "x = 1\n!if x == 1:\n  x = 2\n!")
+    );
+    assert_eq!(
+        "`for` cannot be used in BUILD files",
+        parse_fail_message(&a, "!for x in []:\n   pass\n!")
+    );
+    assert_eq!(
+        "`*args` and `**kwargs` arguments are not allowed in BUILD files",
+        parse_fail_message(&a, "f(!*args!)")
+    );
+    a.parse_fail("f(x, !**kwargs!)");
+    assert_eq!(
+        "top-level variable `x` cannot be reassigned in BUILD files",
+        parse_fail_message(&a, "x = 1\n!x! = 2")
+    );
+    a.parse_fail("x = []\n!x! += [1]");
+    a.parse_fail("load(\"a.bzl\", \"x\")\n(y, !x!) = (1, 2)");
+    // Only rebinding is prohibited, mutation is fine.
+    assert_eq!(a.parse("x = {}\nx[1] = 2"), "x = {}\nx[1] = 2\n");
+
+    // Same restrictions in the standard dialect are reported differently.
+    let mut a = Assert::new();
+    a.dialect_set(|x| {
+        x.enable_def = false;
+        x.enable_top_level_stmt = false;
+        x.enable_star_args = false;
+        x.enable_global_reassign = false;
+    });
+    assert_eq!(
+        "`def` is not allowed in this dialect",
+        parse_fail_message(&a, "!def f():\n  pass\n!")
+    );
+    assert_eq!(
+        "`if` cannot be used outside `def` in this dialect",
+        parse_fail_message(&a, "!if True:\n  pass\n!")
+    );
+    assert_eq!(
+        "`*args` and `**kwargs` arguments are not allowed in this dialect",
+        parse_fail_message(&a, "f(!*args!)")
+    );
+    assert_eq!(
+        "top-level variable `x` cannot be reassigned in this dialect",
+        parse_fail_message(&a, "x = 1\n!x! = 2")
+    );
+    assert_eq!(
+        assert::parse("x = 1\nx = 2\nf(*x, **x)"),
+        "x = 1\nx = 2\nf(*x, **x)\n"
+    );
+}
+
 #[test]
 fn test_top_level_def_with_docstring() {
     assert_eq!(
@@ -301,3 +391,40 @@ fn test_bad_assignment() {
     assert::parse_fail("[!x or y!] = 1");
     assert::parse_fail("![x]! += 1");
 }
+
+#[test]
+fn test_semantic_fingerprint() {
+    let fingerprint = |program: &str| {
+        AstModule::parse("a.star", program.to_owned(), &Dialect::Extended)
+            .unwrap()
+            .semantic_fingerprint()
+    };
+    let original = fingerprint("def f(x, y = 0x10):\n    return (x + y) * 2\nz = f(1, 'a')\n");
+    let same = [
+        concat!(
+            "# Comment\ndef f(x, y=16):  # Comment\n  return ((x + y)) * 2\n\n\n",
+            "z = f(\n  1,\n  \"a\",\n)",
+        ),
+        "def f(x, y = 16):\n    return (x + y) * \\\n        2\nz = f(1, 'a')",
+    ];
+    for x in same {
+        assert_eq!(original, fingerprint(x), "{}", x);
+    }
+    let different = [
+        "def f(x, y = 17):\n    return (x + y) * 2\nz = f(1, 'a')\n",
+        "def f(x, y = 16):\n    return x + y * 2\nz = f(1, 'a')\n",
+        "def f(x, y = 16):\n    return (x + y) * 2\nz = f(1, 'b')\n",
+        "def f(x, y = 16):\n    return (x + y) * 2\nz = f(1, a)\n",
+        "def f(x, y = 16):\n    return (x + y) * 2\nif True:\n    z = f(1, 'a')\n",
+    ];
+    for x in different {
+        assert_ne!(original, fingerprint(x), "{}", x);
+    }
+    // Programs which print the same but differ in the syntax tree.
+    let different_pairs = [("x = 1", "x = 1.0"), ("-x.y", "(-x).y")];
+    for (x, y) in different_pairs {
+        assert_ne!(fingerprint(x), fingerprint(y), "{} vs {}", x, y);
+    }
+    // Stable across runs and platforms.
+    assert_eq!(7671938873731008747, fingerprint("x = 1"));
+}
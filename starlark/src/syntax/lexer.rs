@@ -21,6 +21,7 @@ use std::fmt;
 use std::fmt::Display;
 
 use derive_more::Display;
+use dupe::Dupe;
 use logos::Logos;
 use num_bigint::BigInt;
 use num_traits::Num;
@@ -33,6 +34,8 @@ use crate::errors::Diagnostic;
 use crate::syntax::cursors::CursorBytes;
 use crate::syntax::cursors::CursorChars;
 use crate::syntax::dialect::Dialect;
+use crate::syntax::dialect::DialectError;
+use crate::syntax::dialect::DialectProfile;
 
 #[derive(Error, Debug)]
 pub(crate) enum LexemeError {
@@ -52,6 +55,12 @@ pub(crate) enum LexemeError {
     StartsZero(String),
     #[error("Parse error: failed to parse integer: `{0}`")]
     IntParse(String),
+    #[error("Parse error: f-string expression is missing a closing `}}`")]
+    UnfinishedFStringExpression,
+    #[error("Parse error: f-string expression cannot be empty")]
+    EmptyFStringExpression,
+    #[error("Parse error: single `}}` is not allowed in f-string, use `}}}}`")]
+    FStringClosingCurly,
 }
 
 type Lexeme = anyhow::Result<(usize, Token, usize)>;
@@ -67,6 +76,8 @@ pub(crate) struct Lexer<'a> {
     lexer: logos::Lexer<'a, Token>,
     done: bool,
     dialect_allow_tabs: bool,
+    dialect_allow_f_strings: bool,
+    dialect_profile: DialectProfile,
 }
 
 impl<'a> Lexer<'a> {
@@ -81,6 +92,8 @@ impl<'a> Lexer<'a> {
             parens: 0,
             done: false,
             dialect_allow_tabs: dialect.enable_tabs,
+            dialect_allow_f_strings: dialect.enable_f_strings,
+            dialect_profile: dialect.profile,
         };
         if let Err(e) = lexer2.calculate_indent() {
             lexer2.buffer.push_back(Err(e));
@@ -380,6 +393,225 @@ impl<'a> Lexer<'a> {
         )
     }
 
+    /// Lexer for an expression inside an f-string, starting at `start` and not reading past
+    /// the end of `input`. It is inside parentheses, so newlines are ignored.
+    fn nested(&self, input: &'a str, start: usize) -> Lexer<'a> {
+        let mut lexer = Token::lexer(input);
+        lexer.bump(start);
+        Lexer {
+            codemap: self.codemap.dupe(),
+            indent_levels: Vec::new(),
+            buffer: VecDeque::new(),
+            parens: 1,
+            lexer,
+            done: false,
+            dialect_allow_tabs: self.dialect_allow_tabs,
+            dialect_allow_f_strings: self.dialect_allow_f_strings,
+            dialect_profile: self.dialect_profile,
+        }
+    }
+
+    // An f-string `f"a{x}b{y}"` is desugared into the tokens of `"a{}b{}".format((x), (y))`.
+    // The tokens of the expressions keep their positions, so errors point inside the f-string,
+    // while the other tokens span the whole f-string.
+    fn f_string(&mut self) -> Lexeme {
+        let start = self.lexer.span().start;
+        if !self.dialect_allow_f_strings {
+            return Err(Diagnostic::new(
+                DialectError::FStrings(self.dialect_profile),
+                Span::new(
+                    Pos::new(start as u32),
+                    Pos::new(self.lexer.span().end as u32),
+                ),
+                &self.codemap,
+            ));
+        }
+        let quote = if self.lexer.slice().ends_with('"') {
+            '"'
+        } else {
+            '\''
+        };
+        let triple = self
+            .lexer
+            .remainder()
+            .starts_with(if quote == '"' { "\"\"" } else { "''" });
+        // Find the end of the f-string, and check the escapes, in the same way as for strings.
+        let mut qs = 0;
+        let (_, _, end) = self.string(triple, false, |c| {
+            if c == quote {
+                qs += 1;
+                !triple || qs == 3
+            } else {
+                qs = 0;
+                false
+            }
+        })?;
+        let quotes = if triple { 3 } else { 1 };
+        let input = &self.lexer.source()[..end - quotes];
+
+        let mut format = String::new();
+        let mut args = Vec::new();
+        let mut it = CursorChars::new_offset(input, start + 1 + quotes);
+        while let Some(c) = it.next() {
+            match c {
+                '{' if it.peek() == Some('{') => {
+                    it.next();
+                    format.push_str("{{");
+                }
+                '}' if it.peek() == Some('}') => {
+                    it.next();
+                    format.push_str("}}");
+                }
+                '}' => {
+                    return self.err_span(LexemeError::FStringClosingCurly, it.pos() - 1, it.pos());
+                }
+                '{' => {
+                    let open = it.pos() - 1;
+                    let mut nested = self.nested(input, it.pos());
+                    let mut tokens = Vec::new();
+                    let mut depth = 0;
+                    let close = loop {
+                        match nested.next() {
+                            Some(Ok((l, Token::ClosingCurly, r))) if depth == 0 => break (l, r),
+                            Some(Ok((_, Token::Newline, _))) | None => {
+                                return self.err_span(
+                                    LexemeError::UnfinishedFStringExpression,
+                                    open,
+                                    input.len(),
+                                );
+                            }
+                            Some(Ok(token)) => {
+                                match token.1 {
+                                    Token::OpeningCurly
+                                    | Token::OpeningRound
+                                    | Token::OpeningSquare => depth += 1,
+                                    Token::ClosingCurly
+                                    | Token::ClosingRound
+                                    | Token::ClosingSquare => depth -= 1,
+                                    _ => {}
+                                }
+                                tokens.push(Ok(token));
+                            }
+                            Some(Err(e)) => return Err(e),
+                        }
+                    };
+                    if tokens.is_empty() {
+                        return self.err_span(LexemeError::EmptyFStringExpression, open, close.1);
+                    }
+                    // Parenthesize, so `f"{a, b}"` formats a tuple, as in Python.
+                    args.push(Ok((open, Token::OpeningRound, open + 1)));
+                    args.extend(tokens);
+                    args.push(Ok((close.0, Token::ClosingRound, close.1)));
+                    args.push(Ok((close.0, Token::Comma, close.1)));
+                    format.push_str("{}");
+                    it = CursorChars::new_offset(input, close.1);
+                }
+                '\\' => {
+                    let mut escaped = String::new();
+                    let pos = it.pos();
+                    if Self::escape(&mut it, &mut escaped).is_err() {
+                        return self.err_span(
+                            LexemeError::InvalidEscapeSequence(input[pos..it.pos()].to_owned()),
+                            pos - 1,
+                            it.pos(),
+                        );
+                    }
+                    // Escaped curlies are literal text, not placeholders.
+                    format.push_str(&escaped.replace('{', "{{").replace('}', "}}"));
+                }
+                '\r' => {}
+                c => format.push(c),
+            }
+        }
+
+        if args.is_empty() {
+            // Without fields all the curlies are doubled, and no `format` call undoubles them.
+            let literal = format.replace("{{", "{").replace("}}", "}");
+            self.buffer
+                .push_back(Ok((start, Token::String(literal), end)));
+        } else {
+            self.buffer
+                .push_back(Ok((start, Token::String(format), end)));
+            self.buffer.push_back(Ok((start, Token::Dot, end)));
+            self.buffer
+                .push_back(Ok((start, Token::Identifier("format".to_owned()), end)));
+            self.buffer.push_back(Ok((start, Token::OpeningRound, end)));
+            self.buffer.extend(args);
+            self.buffer.push_back(Ok((start, Token::ClosingRound, end)));
+        }
+        self.buffer.pop_front().unwrap()
+    }
+
+    // A bytes literal `b"..."` has the escapes of strings, but `\x` and octal escapes
+    // give a single byte, and other characters are UTF-8 encoded.
+    fn bytes(&mut self) -> Lexeme {
+        let start = self.lexer.span().start;
+        let prefix = self.lexer.span().len() - 1;
+        let raw = self.lexer.slice().starts_with('r');
+        let quote = if self.lexer.slice().ends_with('"') {
+            '"'
+        } else {
+            '\''
+        };
+        let triple = self
+            .lexer
+            .remainder()
+            .starts_with(if quote == '"' { "\"\"" } else { "''" });
+        // Find the end of the literal, and check the escapes, in the same way as for strings.
+        let mut qs = 0;
+        let (_, token, end) = self.string(triple, raw, |c| {
+            if c == quote {
+                qs += 1;
+                !triple || qs == 3
+            } else {
+                qs = 0;
+                false
+            }
+        })?;
+        if raw {
+            let s = match token {
+                Token::String(s) => s,
+                _ => unreachable!("string() only produces strings"),
+            };
+            return Ok((start, Token::Bytes(s.into_bytes()), end));
+        }
+
+        let quotes = if triple { 3 } else { 1 };
+        let input = &self.lexer.source()[..end - quotes];
+        let mut res = Vec::new();
+        let mut it = CursorChars::new_offset(input, start + prefix + quotes);
+        while let Some(c) = it.next() {
+            match c {
+                '\\' => {
+                    let byte = matches!(it.peek(), Some('x' | '0'..='7'));
+                    let mut escaped = String::new();
+                    let pos = it.pos();
+                    // Already checked by `string`, except for the range of octal escapes.
+                    let ok = Self::escape(&mut it, &mut escaped).is_ok();
+                    if byte {
+                        match escaped.chars().next().map(u8::try_from) {
+                            Some(Ok(b)) if ok => res.push(b),
+                            _ => {
+                                return self.err_span(
+                                    LexemeError::InvalidEscapeSequence(
+                                        input[pos..it.pos()].to_owned(),
+                                    ),
+                                    pos - 1,
+                                    it.pos(),
+                                );
+                            }
+                        }
+                    } else {
+                        res.extend_from_slice(escaped.as_bytes());
+                    }
+                }
+                '\r' => {}
+                c => res.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+        Ok((start, Token::Bytes(res), end))
+    }
+
     fn int(&self, s: &str, radix: u32) -> Lexeme {
         let span = self.lexer.span();
         match i32::from_str_radix(s, radix) {
@@ -492,6 +724,8 @@ impl<'a> Lexer<'a> {
                                 Some(self.string(false, raw, |c| c == '\''))
                             }
                         }
+                        Token::RawFQuote => Some(self.f_string()),
+                        Token::RawBQuote => Some(self.bytes()),
                         Token::OpeningCurly | Token::OpeningRound | Token::OpeningSquare => {
                             self.parens += 1;
                             self.wrap(token)
@@ -508,6 +742,16 @@ impl<'a> Lexer<'a> {
     }
 }
 
+/// A bytes literal `b"..."` is parsed as this attribute of a string literal, whose characters
+/// are the bytes, see [`bytes_literal_payload`]. The compiler turns it into the bytes value.
+/// It is not an identifier, so it cannot be written in source code.
+pub(crate) const BYTES_LITERAL_ATTR: &str = "<bytes>";
+
+/// The string literal for the bytes of a bytes literal, one character per byte.
+pub(crate) fn bytes_literal_payload(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Display)]
 pub enum TokenInt {
     I32(i32),
@@ -541,10 +785,16 @@ pub enum Token {
     #[token("\"")]
     #[token("r\"")]
     RawDoubleQuote,
+    #[token("f'")]
+    #[token("f\"")]
+    RawFQuote,
+    #[token("b'")]
+    #[token("b\"")]
+    #[token("rb'")]
+    #[token("rb\"")]
+    RawBQuote,
 
-    #[regex(
-        "as|import|is|class|nonlocal|del|raise|except|try|finally|while|from|with|global|yield"
-    )]
+    #[regex("as|import|is|class|nonlocal|del|raise|except|try|finally|from|with|global")]
     Reserved, // One of the reserved keywords
 
     #[regex(
@@ -569,6 +819,7 @@ pub enum Token {
     Float(f64), // A float literal (3.14, .3, 1e6, 0.)
 
     String(String), // A string literal
+    Bytes(Vec<u8>), // A bytes literal
 
     // Keywords
     #[token("and")]
@@ -601,6 +852,10 @@ pub enum Token {
     Return,
     #[token("lambda")]
     Lambda,
+    #[token("yield")]
+    Yield,
+    #[token("while")]
+    While,
     // Symbols
     #[token(",")]
     Comma,
@@ -664,6 +919,8 @@ pub enum Token {
     GreaterGreater,
     #[token("~")]
     Tilde,
+    #[token("@")]
+    At,
     #[token("&=")]
     AmpersandEqual,
     #[token("|=")]
@@ -703,6 +960,7 @@ impl Token {
                 // Reuse the StarlarkValue implementation since it's close to hand.
                 serde_json::to_string(x).unwrap()
             }
+            Token::Bytes(x) => format!("b\"{}\"", x.escape_ascii()),
             _ => {
                 let s = self.to_string();
                 // Out display is often: keyword 'lambda'
@@ -741,6 +999,8 @@ impl Display for Token {
             Token::Elif => write!(f, "keyword 'elif'"),
             Token::Return => write!(f, "keyword 'return'"),
             Token::Lambda => write!(f, "keyword 'lambda'"),
+            Token::Yield => write!(f, "keyword 'yield'"),
+            Token::While => write!(f, "keyword 'while'"),
             Token::Comma => write!(f, "symbol ','"),
             Token::Semicolon => write!(f, "symbol ';'"),
             Token::Colon => write!(f, "symbol ':'"),
@@ -772,6 +1032,7 @@ impl Display for Token {
             Token::LessLess => write!(f, "symbol '<<'"),
             Token::GreaterGreater => write!(f, "symbol '>>'"),
             Token::Tilde => write!(f, "symbol '~'"),
+            Token::At => write!(f, "symbol '@'"),
             Token::AmpersandEqual => write!(f, "symbol '&='"),
             Token::PipeEqual => write!(f, "symbol '|='"),
             Token::CaretEqual => write!(f, "symbol '^='"),
@@ -794,6 +1055,9 @@ impl Display for Token {
             Token::String(s) => write!(f, "string literal '{}'", s),
             Token::RawSingleQuote => write!(f, "starting '"),
             Token::RawDoubleQuote => write!(f, "starting \""),
+            Token::RawFQuote => write!(f, "starting f-string"),
+            Token::Bytes(b) => write!(f, "bytes literal '{}'", String::from_utf8_lossy(b)),
+            Token::RawBQuote => write!(f, "starting bytes literal"),
             Token::Tabs => Ok(()),
         }
     }
@@ -77,12 +77,14 @@ fn test_number_collated_with_keywords_or_identifier() {
 
 #[test]
 fn test_reserved() {
-    let reserved =
-        "as import is class nonlocal del raise except try finally while from with global yield"
-            .split_whitespace();
+    let reserved = "as import is class nonlocal del raise except try finally from with global"
+        .split_whitespace();
     for x in reserved {
         assert::parse_fail(&format!("!{}! = 1", x));
     }
+    // `yield` and `while` are keywords, not reserved words.
+    assert::parse_fail("yield !=! 1");
+    assert::parse_fail("while !=! 1");
 }
 
 #[test]
@@ -144,6 +146,16 @@ fn test_string_escape() {
     assert::parse_fail("test 'more !\\x0!");
 }
 
+#[test]
+fn test_bytes_lit() {
+    assert_eq!(
+        assert::lex(r#"b'abc' b"\x00\xff" rb'\x' b'''\n''' b'\377éé'"#),
+        r#"b"abc" b"\x00\xff" b"\\x" b"\n" b"\xff\xc3\xa9\xc3\xa9" "#.to_owned() + "\n"
+    );
+    assert::parse_fail(r"b'!\400!'");
+    assert::parse_fail("test + !b'unfinished!");
+}
+
 #[test]
 fn test_simple_example() {
     assert_eq!(
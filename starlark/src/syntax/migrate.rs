@@ -0,0 +1,527 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Source-to-source migrations of Starlark code, e.g. away from deprecated idioms.
+//!
+//! A [`Codemod`] finds the code to rewrite in the AST and replaces the source of these nodes,
+//! so the rest of the file, including comments and formatting, is kept as written.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::codemap::Span;
+use crate::syntax::ast::Argument;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::BinOp;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::Stmt;
+use crate::syntax::uniplate::Visit;
+use crate::syntax::AstModule;
+
+/// Number of times a codemod is applied to its own output, for nested matches.
+const MAX_PASSES: usize = 10;
+
+#[derive(Debug, thiserror::Error)]
+enum MigrateError {
+    #[error("Codemod `{0}` produced code which does not parse: {1}")]
+    InvalidEdit(String, anyhow::Error),
+}
+
+/// Replacement of the source between the byte offsets `begin` and `end`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceEdit {
+    /// Offset of the first replaced byte.
+    pub begin: usize,
+    /// Offset after the last replaced byte.
+    pub end: usize,
+    /// The new source.
+    pub replacement: String,
+}
+
+impl SourceEdit {
+    fn new(span: Span, replacement: String) -> SourceEdit {
+        SourceEdit {
+            begin: span.begin().get() as usize,
+            end: span.end().get() as usize,
+            replacement,
+        }
+    }
+}
+
+/// A migration of Starlark code, applied with [`AstModule::migrate`].
+///
+/// Built-in codemods are [`PercentFormat`], [`DictUnion`] and [`LoadRenames`].
+pub trait Codemod {
+    /// Name of the codemod, for error messages.
+    fn name(&self) -> &str;
+
+    /// Edits of `source`, the source of `module`. Edits overlapping earlier edits
+    /// are dropped, and made again when the codemod is applied to the edited source.
+    fn edits(&self, source: &str, module: &AstModule) -> Vec<SourceEdit>;
+}
+
+/// Apply the edits which do not overlap an edit before them,
+/// preferring the outermost of edits starting at the same offset.
+fn apply_edits(source: &str, mut edits: Vec<SourceEdit>) -> String {
+    edits.sort_by_key(|x| (x.begin, std::cmp::Reverse(x.end)));
+    let mut res = String::with_capacity(source.len());
+    let mut pos = 0;
+    for x in edits {
+        if x.begin < pos {
+            continue;
+        }
+        res.push_str(&source[pos..x.begin]);
+        res.push_str(&x.replacement);
+        pos = x.end;
+    }
+    res.push_str(&source[pos..]);
+    res
+}
+
+impl AstModule {
+    /// Apply `codemods` in order, each until it makes no more edits, and return the new source.
+    /// Code not edited by the codemods, including comments and formatting, is kept as written.
+    ///
+    /// Fails if a codemod produces code which does not parse with the dialect of this module.
+    pub fn migrate(&self, codemods: &[&dyn Codemod]) -> anyhow::Result<String> {
+        let mut migrated: Option<AstModule> = None;
+        for codemod in codemods {
+            for _ in 0..MAX_PASSES {
+                let module = migrated.as_ref().unwrap_or(self);
+                let source = module.codemap.source();
+                let edits = codemod.edits(source, module);
+                if edits.is_empty() {
+                    break;
+                }
+                let edited = apply_edits(source, edits);
+                if edited == source {
+                    break;
+                }
+                let parsed = AstModule::parse(self.codemap.filename(), edited, &self.dialect)
+                    .map_err(|e| MigrateError::InvalidEdit(codemod.name().to_owned(), e))?;
+                migrated = Some(parsed);
+            }
+        }
+        Ok(migrated
+            .as_ref()
+            .unwrap_or(self)
+            .codemap
+            .source()
+            .to_owned())
+    }
+
+    fn source_of(&self, x: &AstExpr) -> &str {
+        self.codemap.source_span(x.span)
+    }
+}
+
+/// Call `f` with every expression of the module.
+fn visit_exprs<'a>(module: &'a AstModule, mut f: impl FnMut(&'a AstExpr)) {
+    fn visit<'a>(node: Visit<'a, AstNoPayload>, f: &mut impl FnMut(&'a AstExpr)) {
+        if let Visit::Expr(x) = node {
+            f(x);
+        }
+        node.visit_children(|x| visit(x, f));
+    }
+    visit(Visit::Stmt(&module.statement), &mut f)
+}
+
+/// Spans of the expressions which can be replaced by any expression without parentheses,
+/// like the right-hand side of an assignment or a call argument.
+fn unparenthesized(module: &AstModule) -> HashSet<Span> {
+    fn visit(node: Visit<AstNoPayload>, res: &mut HashSet<Span>) {
+        match node {
+            Visit::Stmt(x) => match &x.node {
+                Stmt::Expression(x) | Stmt::Return(Some(x)) => {
+                    res.insert(x.span);
+                }
+                Stmt::Assign(_, ty_rhs) => {
+                    res.insert(ty_rhs.1.span);
+                }
+                Stmt::AssignModify(_, _, rhs) => {
+                    res.insert(rhs.span);
+                }
+                _ => {}
+            },
+            Visit::Expr(x) => match &x.node {
+                Expr::Call(_, args) => res.extend(args.iter().map(|x| x.expr().span)),
+                Expr::List(xs) | Expr::Tuple(xs) => res.extend(xs.iter().map(|x| x.span)),
+                Expr::Dict(xs) => res.extend(xs.iter().flat_map(|(k, v)| [k.span, v.span])),
+                _ => {}
+            },
+        }
+        node.visit_children(|x| visit(x, res));
+    }
+    let mut res = HashSet::new();
+    visit(Visit::Stmt(&module.statement), &mut res);
+    res
+}
+
+/// Rewrites `%` formatting of string literals to `.format()`,
+/// e.g. `"%s: %r" % (k, v)` to `"{}: {!r}".format(k, v)`.
+///
+/// Only the `%s` and `%r` conversions are rewritten, others like `%d` format
+/// differently with `.format()`. As `"%s" % x` formats the elements of `x` when it is a tuple,
+/// the right-hand side must be a tuple or a literal, unless
+/// [`single_values`](PercentFormat::single_values) is set.
+#[derive(Debug, Clone, Default)]
+pub struct PercentFormat {
+    /// Also rewrite `"%s" % x` for any `x` which is not a tuple literal,
+    /// assuming its value is never a tuple.
+    pub single_values: bool,
+}
+
+/// The `.format()` string for a `%` format string and its number of placeholders,
+/// or `None` if it has conversions other than `%s` and `%r`.
+fn percent_to_format(s: &str) -> Option<(String, usize)> {
+    let mut res = String::with_capacity(s.len());
+    let mut placeholders = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => match chars.next()? {
+                '%' => res.push('%'),
+                's' => {
+                    res.push_str("{}");
+                    placeholders += 1;
+                }
+                'r' => {
+                    res.push_str("{!r}");
+                    placeholders += 1;
+                }
+                _ => return None,
+            },
+            '{' => res.push_str("{{"),
+            '}' => res.push_str("}}"),
+            c => res.push(c),
+        }
+    }
+    Some((res, placeholders))
+}
+
+impl PercentFormat {
+    fn edit(&self, module: &AstModule, x: &AstExpr) -> Option<SourceEdit> {
+        let (lhs, rhs) = match &x.node {
+            Expr::Op(lhs, BinOp::Percent, rhs) => (lhs, rhs),
+            _ => return None,
+        };
+        let value = match &lhs.node {
+            Expr::Literal(AstLiteral::String(s)) => &s.node,
+            _ => return None,
+        };
+        let args: Vec<&AstExpr> = match &rhs.node {
+            Expr::Tuple(xs) => xs.iter().collect(),
+            Expr::Literal(_) | Expr::List(_) | Expr::Dict(_) => vec![rhs],
+            _ if self.single_values => vec![rhs],
+            _ => return None,
+        };
+        // The format string is rewritten in the source, which is only correct
+        // if no escape sequence in the literal produces one of the special characters.
+        let source = module.source_of(lhs);
+        if ['%', '{', '}']
+            .iter()
+            .any(|c| source.matches(*c).count() != value.matches(*c).count())
+        {
+            return None;
+        }
+        let (format, placeholders) = percent_to_format(source)?;
+        if placeholders != args.len() {
+            return None;
+        }
+        let args: Vec<&str> = args.iter().map(|x| module.source_of(x)).collect();
+        Some(SourceEdit::new(
+            x.span,
+            format!("{}.format({})", format, args.join(", ")),
+        ))
+    }
+}
+
+impl Codemod for PercentFormat {
+    fn name(&self) -> &str {
+        "percent-format"
+    }
+
+    fn edits(&self, _source: &str, module: &AstModule) -> Vec<SourceEdit> {
+        let mut res = Vec::new();
+        visit_exprs(module, |x| res.extend(self.edit(module, x)));
+        res
+    }
+}
+
+/// Rewrites idioms merging dicts to the `|` operator:
+/// `dict(a.items() + b.items())` and `dict({...}, **b)` to `a | b`.
+///
+/// `dict(a, **b)` is only rewritten when `a` is a dict literal, comprehension or `dict()` call,
+/// because `a` may also be a list of pairs.
+#[derive(Debug, Clone, Default)]
+pub struct DictUnion;
+
+impl DictUnion {
+    /// The dicts merged by `x`, if it is a merge idiom.
+    fn operands(x: &AstExpr) -> Option<Vec<&AstExpr>> {
+        fn is_dict_call(x: &AstExpr) -> bool {
+            match &x.node {
+                Expr::Call(f, _) => matches!(&f.node, Expr::Identifier(f, _) if f.node == "dict"),
+                _ => false,
+            }
+        }
+
+        fn items_receivers<'a>(x: &'a AstExpr, res: &mut Vec<&'a AstExpr>) -> Option<()> {
+            match &x.node {
+                Expr::Op(lhs, BinOp::Add, rhs) => {
+                    items_receivers(lhs, res)?;
+                    items_receivers(rhs, res)
+                }
+                Expr::Call(f, args) if args.is_empty() => match &f.node {
+                    Expr::Dot(receiver, name) if name.node == "items" => {
+                        res.push(receiver);
+                        Some(())
+                    }
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+
+        if !is_dict_call(x) {
+            return None;
+        }
+        let args = match &x.node {
+            Expr::Call(_, args) => args,
+            _ => return None,
+        };
+        match args.as_slice() {
+            [a, b] => match (&a.node, &b.node) {
+                (Argument::Positional(a), Argument::KwArgs(b))
+                    if is_dict_call(a)
+                        || matches!(a.node, Expr::Dict(_) | Expr::DictComprehension(..)) =>
+                {
+                    Some(vec![a, b])
+                }
+                _ => None,
+            },
+            [sum] => match &sum.node {
+                Argument::Positional(sum) => {
+                    let mut res = Vec::new();
+                    items_receivers(sum, &mut res)?;
+                    (res.len() >= 2).then_some(res)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Source of `x` as an operand of `|`.
+    fn operand(module: &AstModule, x: &AstExpr) -> String {
+        let parenthesize = match &x.node {
+            Expr::Lambda(_) | Expr::If(_) | Expr::Not(_) | Expr::Tuple(_) => true,
+            Expr::Op(_, op, _) => matches!(
+                op,
+                BinOp::Or
+                    | BinOp::And
+                    | BinOp::Equal
+                    | BinOp::NotEqual
+                    | BinOp::Less
+                    | BinOp::Greater
+                    | BinOp::LessOrEqual
+                    | BinOp::GreaterOrEqual
+                    | BinOp::In
+                    | BinOp::NotIn
+                    | BinOp::BitOr
+            ),
+            _ => false,
+        };
+        if parenthesize {
+            format!("({})", module.source_of(x))
+        } else {
+            module.source_of(x).to_owned()
+        }
+    }
+}
+
+impl Codemod for DictUnion {
+    fn name(&self) -> &str {
+        "dict-union"
+    }
+
+    fn edits(&self, _source: &str, module: &AstModule) -> Vec<SourceEdit> {
+        let unparenthesized = unparenthesized(module);
+        let mut res = Vec::new();
+        visit_exprs(module, |x| {
+            if let Some(operands) = Self::operands(x) {
+                let union = operands
+                    .iter()
+                    .map(|x| Self::operand(module, x))
+                    .collect::<Vec<_>>()
+                    .join(" | ");
+                let union = if unparenthesized.contains(&x.span) {
+                    union
+                } else {
+                    format!("({})", union)
+                };
+                res.push(SourceEdit::new(x.span, union));
+            }
+        });
+        res
+    }
+}
+
+/// Renames the modules loaded by `load()` statements, e.g. after files were moved.
+///
+/// A rename applies to the paths equal to its old path, or, if the old path ends with
+/// `/` or `:`, to the paths starting with it, replacing that prefix.
+/// The longest matching old path is used.
+#[derive(Debug, Clone, Default)]
+pub struct LoadRenames {
+    renames: HashMap<String, String>,
+}
+
+impl LoadRenames {
+    /// Renames from old paths to new paths.
+    pub fn new(renames: HashMap<String, String>) -> LoadRenames {
+        LoadRenames { renames }
+    }
+
+    /// Renames from a JSON object mapping old paths to new paths.
+    pub fn from_json(json: &str) -> anyhow::Result<LoadRenames> {
+        Ok(LoadRenames::new(serde_json::from_str(json)?))
+    }
+
+    fn rename(&self, path: &str) -> Option<String> {
+        if let Some(x) = self.renames.get(path) {
+            return Some(x.clone());
+        }
+        self.renames
+            .iter()
+            .filter(|(old, _)| old.ends_with(['/', ':']) && path.starts_with(old.as_str()))
+            .max_by_key(|(old, _)| old.len())
+            .map(|(old, new)| format!("{}{}", new, &path[old.len()..]))
+    }
+}
+
+impl Codemod for LoadRenames {
+    fn name(&self) -> &str {
+        "load-renames"
+    }
+
+    fn edits(&self, _source: &str, module: &AstModule) -> Vec<SourceEdit> {
+        let mut res = Vec::new();
+        module.statement.visit_stmt(|x| {
+            if let Stmt::Load(load) = &x.node {
+                if let Some(new) = self.rename(&load.module.node) {
+                    // Keep the quotes of the literal when no escaping is needed.
+                    let quote = module.codemap.source_span(load.module.span).chars().next();
+                    let literal = match quote {
+                        Some(q @ ('"' | '\'')) if !new.contains([q, '\\', '\n']) => {
+                            format!("{}{}{}", q, new, q)
+                        }
+                        _ => serde_json::to_string(&new).unwrap(),
+                    };
+                    res.push(SourceEdit::new(load.module.span, literal));
+                }
+            }
+        });
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::syntax::AstModule;
+    use crate::syntax::Codemod;
+    use crate::syntax::Dialect;
+    use crate::syntax::DictUnion;
+    use crate::syntax::LoadRenames;
+    use crate::syntax::PercentFormat;
+
+    fn migrate(codemod: &dyn Codemod, program: &str) -> String {
+        AstModule::parse("a.star", program.to_owned(), &Dialect::Extended)
+            .unwrap()
+            .migrate(&[codemod])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_percent_format() {
+        let codemod = PercentFormat::default();
+        assert_eq!(
+            "x = \"{}: {!r} 100% {{}}\".format(k, v)  # comment",
+            migrate(&codemod, "x = \"%s: %r 100%% {}\" % (k, v)  # comment")
+        );
+        assert_eq!(
+            "f('<{}>'.format('{}'.format(x)), '[{}]'.format([1]))",
+            migrate(&codemod, "f('<%s>' % ('%s' % (x,),), '[%s]' % [1])")
+        );
+        // Not rewritten: other conversions, a possible tuple, a wrong number of arguments.
+        for program in ["'%d' % (x,)", "'%s' % x", "'%s %s' % (x,)", "x % (y,)"] {
+            assert_eq!(program, migrate(&codemod, program));
+        }
+        let codemod = PercentFormat {
+            single_values: true,
+        };
+        assert_eq!("'{}'.format(x)", migrate(&codemod, "'%s' % x"));
+    }
+
+    #[test]
+    fn test_dict_union() {
+        let codemod = DictUnion;
+        assert_eq!(
+            "x = a | b | (c or d)\nf(a | b)\ny = (a | b).keys()",
+            migrate(
+                &codemod,
+                "x = dict(a.items() + b.items() + (c or d).items())\n\
+                 f(dict(a.items() + b.items()))\n\
+                 y = dict(a.items() + b.items()).keys()"
+            )
+        );
+        assert_eq!(
+            "x = {'a': 1} | b\ny = dict(a, **b)\nz = dict(a.items())",
+            migrate(
+                &codemod,
+                "x = dict({'a': 1}, **b)\ny = dict(a, **b)\nz = dict(a.items())"
+            )
+        );
+    }
+
+    #[test]
+    fn test_load_renames() {
+        let codemod = LoadRenames::from_json(
+            r#"{"//old:defs.bzl": "//new:defs.bzl", "//pkg/": "//lib/pkg/", "//pkg/x/": "//x/"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            "load('//new:defs.bzl', 'a')\n\
+             load(\"//lib/pkg/y:z.bzl\", 'b')\n\
+             load(\"//x/z.bzl\", 'c')\n\
+             load('//other.bzl', 'd')",
+            migrate(
+                &codemod,
+                "load('//old:defs.bzl', 'a')\n\
+                 load(\"//pkg/y:z.bzl\", 'b')\n\
+                 load(\"//pkg/x/z.bzl\", 'c')\n\
+                 load('//other.bzl', 'd')"
+            )
+        );
+        let codemod = LoadRenames::new(HashMap::from([("a".to_owned(), "it's".to_owned())]));
+        assert_eq!("load(\"it's\", 'x')", migrate(&codemod, "load('a', 'x')"));
+    }
+}
@@ -19,7 +19,18 @@
 
 pub use ast::AstModule;
 pub use dialect::Dialect;
+pub use dialect::DialectProfile;
 pub use dialect::DialectTypes;
+pub use migrate::Codemod;
+pub use migrate::DictUnion;
+pub use migrate::LoadRenames;
+pub use migrate::PercentFormat;
+pub use migrate::SourceEdit;
+pub use query::Query;
+pub use query::QueryMatch;
+
+pub use crate::analysis::ExportedSymbol;
+pub use crate::analysis::ExportedSymbolKind;
 
 #[cfg(test)]
 mod grammar_tests;
@@ -30,9 +41,11 @@ mod testcases;
 
 pub(crate) mod ast;
 pub(crate) mod cursors;
-mod dialect;
+pub(crate) mod dialect;
 pub(crate) mod lexer;
+mod migrate;
 pub(crate) mod payload_map;
+mod query;
 pub(crate) mod validate;
 
 #[allow(clippy::all)]
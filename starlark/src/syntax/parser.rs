@@ -17,23 +17,37 @@
 
 use std::fmt::Write;
 use std::fs;
+use std::hash::Hasher;
 use std::path::Path;
 
 use dupe::Dupe;
 use lalrpop_util as lu;
+use starlark_map::StarlarkHasher;
 
 use crate::codemap::CodeMap;
 use crate::codemap::FileSpan;
 use crate::codemap::Pos;
+use crate::codemap::SourceMap;
 use crate::codemap::Span;
 use crate::errors::Diagnostic;
+use crate::syntax::ast::Argument;
+use crate::syntax::ast::Assign;
+use crate::syntax::ast::AstAssign;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
 use crate::syntax::ast::AstModule;
+use crate::syntax::ast::AstParameter;
 use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::Clause;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::ForClause;
+use crate::syntax::ast::Parameter;
 use crate::syntax::ast::Stmt;
 use crate::syntax::dialect::Dialect;
 use crate::syntax::grammar::StarlarkParser;
 use crate::syntax::lexer::Lexer;
 use crate::syntax::lexer::Token;
+use crate::syntax::lexer::TokenInt;
 
 fn one_of(expected: &[String]) -> String {
     let mut result = String::new();
@@ -132,7 +146,22 @@ impl AstModule {
     /// assert_eq!(err.span.unwrap().to_string(), "filename:2:11");
     /// ```
     pub fn parse(filename: &str, content: String, dialect: &Dialect) -> anyhow::Result<Self> {
-        let codemap = CodeMap::new(filename.to_owned(), content);
+        Self::parse_codemap(CodeMap::new(filename.to_owned(), content), dialect)
+    }
+
+    /// Parse generated Starlark code, with the locations of the code it was generated from,
+    /// which diagnostics and stack traces show instead of the locations in the generated code.
+    pub fn parse_with_source_map(
+        filename: &str,
+        content: String,
+        dialect: &Dialect,
+        source_map: SourceMap,
+    ) -> anyhow::Result<Self> {
+        let codemap = CodeMap::new_with_source_map(filename.to_owned(), content, Some(source_map));
+        Self::parse_codemap(codemap, dialect)
+    }
+
+    fn parse_codemap(codemap: CodeMap, dialect: &Dialect) -> anyhow::Result<Self> {
         let lexer = Lexer::new(codemap.source(), dialect, codemap.dupe());
         match StarlarkParser::new().parse(&codemap, dialect, lexer) {
             Ok(v) => Ok(AstModule::create(codemap, v, dialect)?),
@@ -168,8 +197,378 @@ impl AstModule {
         loads
     }
 
+    /// Fingerprint of the semantics of the module, to use as a key of incremental caches.
+    ///
+    /// The fingerprint only depends on the syntax tree: it doesn't change when comments,
+    /// whitespace, line breaks, redundant parentheses, trailing commas, string quotes,
+    /// or the notation of number literals change. Any other change gives a different
+    /// fingerprint, barring hash collisions. The file name and the dialect are not included.
+    ///
+    /// The fingerprint is the same on all platforms and in all runs, but may change
+    /// between versions of this crate: persistent caches should include the version in the key.
+    pub fn semantic_fingerprint(&self) -> u64 {
+        let mut hasher = SemanticHasher(StarlarkHasher::new());
+        hasher.stmt(&self.statement);
+        hasher.0.finish()
+    }
+
     /// Look up a [`Span`] contained in this module to a [`FileSpan`].
     pub(crate) fn file_span(&self, x: Span) -> FileSpan {
         self.codemap.file_span(x)
     }
 }
+
+/// Feeds the syntax tree to a hasher for [`AstModule::semantic_fingerprint`].
+///
+/// Every node writes a distinct tag before its children, and strings are prefixed
+/// with their length, so different trees don't produce the same byte stream.
+/// Numbers are little-endian to give the same fingerprint on all platforms.
+struct SemanticHasher(StarlarkHasher);
+
+impl SemanticHasher {
+    fn tag(&mut self, tag: u8) {
+        self.0.write(&[tag]);
+    }
+
+    fn len(&mut self, len: usize) {
+        self.0.write(&(len as u64).to_le_bytes());
+    }
+
+    fn str(&mut self, s: &str) {
+        self.len(s.len());
+        self.0.write(s.as_bytes());
+    }
+
+    fn opt_expr(&mut self, x: Option<&AstExpr>) {
+        match x {
+            None => self.tag(0),
+            Some(x) => {
+                self.tag(1);
+                self.expr(x);
+            }
+        }
+    }
+
+    fn exprs(&mut self, xs: &[AstExpr]) {
+        self.len(xs.len());
+        for x in xs {
+            self.expr(x);
+        }
+    }
+
+    fn literal(&mut self, x: &AstLiteral) {
+        match x {
+            AstLiteral::Int(x) => match &x.node {
+                TokenInt::I32(i) => {
+                    self.tag(0);
+                    self.0.write(&i.to_le_bytes());
+                }
+                TokenInt::BigInt(i) => {
+                    self.tag(1);
+                    self.str(&i.to_string());
+                }
+            },
+            AstLiteral::Float(x) => {
+                self.tag(2);
+                self.0.write(&x.node.to_bits().to_le_bytes());
+            }
+            AstLiteral::String(x) => {
+                self.tag(3);
+                self.str(&x.node);
+            }
+        }
+    }
+
+    fn params(&mut self, params: &[AstParameter]) {
+        self.len(params.len());
+        for p in params {
+            match &p.node {
+                Parameter::Normal(name, ty) => {
+                    self.tag(0);
+                    self.str(&name.0);
+                    self.opt_expr(ty.as_deref());
+                }
+                Parameter::WithDefaultValue(name, ty, default) => {
+                    self.tag(1);
+                    self.str(&name.0);
+                    self.opt_expr(ty.as_deref());
+                    self.expr(default);
+                }
+                Parameter::NoArgs => self.tag(2),
+                Parameter::Args(name, ty) => {
+                    self.tag(3);
+                    self.str(&name.0);
+                    self.opt_expr(ty.as_deref());
+                }
+                Parameter::KwArgs(name, ty) => {
+                    self.tag(4);
+                    self.str(&name.0);
+                    self.opt_expr(ty.as_deref());
+                }
+            }
+        }
+    }
+
+    fn for_clause(&mut self, x: &ForClause) {
+        self.assign(&x.var);
+        self.expr(&x.over);
+    }
+
+    fn clauses(&mut self, xs: &[Clause]) {
+        self.len(xs.len());
+        for x in xs {
+            match x {
+                Clause::For(x) => {
+                    self.tag(0);
+                    self.for_clause(x);
+                }
+                Clause::If(x) => {
+                    self.tag(1);
+                    self.expr(x);
+                }
+            }
+        }
+    }
+
+    fn slice(
+        &mut self,
+        x: &AstExpr,
+        start: Option<&AstExpr>,
+        stop: Option<&AstExpr>,
+        stride: Option<&AstExpr>,
+    ) {
+        self.expr(x);
+        self.opt_expr(start);
+        self.opt_expr(stop);
+        self.opt_expr(stride);
+    }
+
+    fn expr(&mut self, x: &AstExpr) {
+        match &x.node {
+            Expr::Tuple(xs) => {
+                self.tag(0);
+                self.exprs(xs);
+            }
+            Expr::Dot(x, attr) => {
+                self.tag(1);
+                self.expr(x);
+                self.str(attr);
+            }
+            Expr::Call(f, args) => {
+                self.tag(2);
+                self.expr(f);
+                self.len(args.len());
+                for arg in args {
+                    match &arg.node {
+                        Argument::Positional(x) => {
+                            self.tag(0);
+                            self.expr(x);
+                        }
+                        Argument::Named(name, x) => {
+                            self.tag(1);
+                            self.str(name);
+                            self.expr(x);
+                        }
+                        Argument::Args(x) => {
+                            self.tag(2);
+                            self.expr(x);
+                        }
+                        Argument::KwArgs(x) => {
+                            self.tag(3);
+                            self.expr(x);
+                        }
+                    }
+                }
+            }
+            Expr::ArrayIndirection(x_i) => {
+                let (x, i) = &**x_i;
+                self.tag(3);
+                self.expr(x);
+                self.expr(i);
+            }
+            Expr::Slice(x, start, stop, stride) => {
+                self.tag(4);
+                self.slice(x, start.as_deref(), stop.as_deref(), stride.as_deref());
+            }
+            Expr::Identifier(name, ()) => {
+                self.tag(5);
+                self.str(name);
+            }
+            Expr::Lambda(lambda) => {
+                self.tag(6);
+                self.params(&lambda.params);
+                self.expr(&lambda.body);
+            }
+            Expr::Literal(x) => {
+                self.tag(7);
+                self.literal(x);
+            }
+            Expr::Not(x) => {
+                self.tag(8);
+                self.expr(x);
+            }
+            Expr::Minus(x) => {
+                self.tag(9);
+                self.expr(x);
+            }
+            Expr::Plus(x) => {
+                self.tag(10);
+                self.expr(x);
+            }
+            Expr::BitNot(x) => {
+                self.tag(11);
+                self.expr(x);
+            }
+            Expr::Op(l, op, r) => {
+                self.tag(12);
+                self.tag(*op as u8);
+                self.expr(l);
+                self.expr(r);
+            }
+            Expr::If(cond_then_else) => {
+                let (cond, then_expr, else_expr) = &**cond_then_else;
+                self.tag(13);
+                self.expr(cond);
+                self.expr(then_expr);
+                self.expr(else_expr);
+            }
+            Expr::List(xs) => {
+                self.tag(14);
+                self.exprs(xs);
+            }
+            Expr::Dict(xs) => {
+                self.tag(15);
+                self.len(xs.len());
+                for (k, v) in xs {
+                    self.expr(k);
+                    self.expr(v);
+                }
+            }
+            Expr::ListComprehension(x, for_, clauses) => {
+                self.tag(16);
+                self.expr(x);
+                self.for_clause(for_);
+                self.clauses(clauses);
+            }
+            Expr::DictComprehension(k_v, for_, clauses) => {
+                let (k, v) = &**k_v;
+                self.tag(17);
+                self.expr(k);
+                self.expr(v);
+                self.for_clause(for_);
+                self.clauses(clauses);
+            }
+        }
+    }
+
+    fn assign(&mut self, x: &AstAssign) {
+        match &x.node {
+            Assign::Tuple(xs) => {
+                self.tag(0);
+                self.len(xs.len());
+                for x in xs {
+                    self.assign(x);
+                }
+            }
+            Assign::ArrayIndirection(x_i) => {
+                let (x, i) = &**x_i;
+                self.tag(1);
+                self.expr(x);
+                self.expr(i);
+            }
+            Assign::Slice(x, start, stop, stride) => {
+                self.tag(2);
+                self.slice(x, start.as_deref(), stop.as_deref(), stride.as_deref());
+            }
+            Assign::Dot(x, attr) => {
+                self.tag(3);
+                self.expr(x);
+                self.str(attr);
+            }
+            Assign::Identifier(name) => {
+                self.tag(4);
+                self.str(&name.0);
+            }
+        }
+    }
+
+    fn stmt(&mut self, x: &AstStmt) {
+        match &x.node {
+            Stmt::Break => self.tag(0),
+            Stmt::Continue => self.tag(1),
+            Stmt::Pass => self.tag(2),
+            Stmt::Return(x) => {
+                self.tag(3);
+                self.opt_expr(x.as_ref());
+            }
+            Stmt::Yield(x) => {
+                self.tag(4);
+                self.opt_expr(x.as_ref());
+            }
+            Stmt::Expression(x) => {
+                self.tag(5);
+                self.expr(x);
+            }
+            Stmt::Assign(lhs, ty_rhs) => {
+                let (ty, rhs) = &**ty_rhs;
+                self.tag(6);
+                self.assign(lhs);
+                self.opt_expr(ty.as_ref());
+                self.expr(rhs);
+            }
+            Stmt::AssignModify(lhs, op, rhs) => {
+                self.tag(7);
+                self.tag(*op as u8);
+                self.assign(lhs);
+                self.expr(rhs);
+            }
+            Stmt::Statements(xs) => {
+                self.tag(8);
+                self.len(xs.len());
+                for x in xs {
+                    self.stmt(x);
+                }
+            }
+            Stmt::If(cond, then_block) => {
+                self.tag(9);
+                self.expr(cond);
+                self.stmt(then_block);
+            }
+            Stmt::IfElse(cond, then_else) => {
+                let (then_block, else_block) = &**then_else;
+                self.tag(10);
+                self.expr(cond);
+                self.stmt(then_block);
+                self.stmt(else_block);
+            }
+            Stmt::For(var, over_body) => {
+                let (over, body) = &**over_body;
+                self.tag(11);
+                self.assign(var);
+                self.expr(over);
+                self.stmt(body);
+            }
+            Stmt::While(cond, body) => {
+                self.tag(12);
+                self.expr(cond);
+                self.stmt(body);
+            }
+            Stmt::Def(def) => {
+                self.tag(13);
+                self.str(&def.name.0);
+                self.params(&def.params);
+                self.opt_expr(def.return_type.as_deref());
+                self.stmt(&def.body);
+            }
+            Stmt::Load(load) => {
+                self.tag(14);
+                self.str(&load.module);
+                self.len(load.args.len());
+                for (local, their) in &load.args {
+                    self.str(&local.0);
+                    self.str(their);
+                }
+            }
+        }
+    }
+}
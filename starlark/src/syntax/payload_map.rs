@@ -63,6 +63,8 @@ impl<A: AstPayload> StmtP<A> {
             StmtP::Pass => StmtP::Pass,
             StmtP::Return(None) => StmtP::Return(None),
             StmtP::Return(Some(e)) => StmtP::Return(Some(e.into_map_payload(f))),
+            StmtP::Yield(None) => StmtP::Yield(None),
+            StmtP::Yield(Some(e)) => StmtP::Yield(Some(e.into_map_payload(f))),
             StmtP::Expression(e) => StmtP::Expression(e.into_map_payload(f)),
             StmtP::Assign(lhs, ty_rhs) => {
                 let (ty, rhs) = *ty_rhs;
@@ -100,6 +102,9 @@ impl<A: AstPayload> StmtP<A> {
                     Box::new((coll.into_map_payload(f), body.into_map_payload(f))),
                 )
             }
+            StmtP::While(cond, body) => {
+                StmtP::While(cond.into_map_payload(f), Box::new(body.into_map_payload(f)))
+            }
             StmtP::Def(DefP {
                 name,
                 params,
@@ -206,6 +211,12 @@ impl<A: AstPayload> AssignP<A> {
                     index.into_map_payload(f),
                 )))
             }
+            AssignP::Slice(x, a, b, c) => AssignP::Slice(
+                Box::new(x.into_map_payload(f)),
+                a.map(|e| Box::new(e.into_map_payload(f))),
+                b.map(|e| Box::new(e.into_map_payload(f))),
+                c.map(|e| Box::new(e.into_map_payload(f))),
+            ),
             AssignP::Dot(object, field) => {
                 AssignP::Dot(Box::new(object.into_map_payload(f)), field)
             }
@@ -0,0 +1,698 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Queries finding nodes of the syntax tree by their kind and properties,
+//! e.g. for lint rules and codemods, run with [`AstModule::query`].
+//!
+//! A query is a sequence of selectors:
+//!
+//! * `kind` matches the nodes of a kind, `*` matches any node.
+//! * `kind('text')` also requires the main property of the node, the first one listed below,
+//!   to be `text`, e.g. `name('glob')` matches the identifier `glob`.
+//! * `kind[property]` requires a node to have a property, e.g. `call[arg.exclude]`.
+//! * `kind[property='text']` requires a property to be `text`.
+//!   Properties which are nodes are compared by their source.
+//! * `kind[property~='regex']` requires a property to match a regular expression.
+//! * `kind[property=selector]` requires a property to be a node matching the selector,
+//!   e.g. `call[function=dot[attribute='format']]`.
+//! * `a b` matches the `b` nodes anywhere inside an `a` node,
+//!   and `a > b` the `b` nodes directly inside an `a` node.
+//!
+//! The kinds of nodes and their properties are:
+//!
+//! | Kind | Properties |
+//! |------|------------|
+//! | `assign` | `target`, `value` |
+//! | `aug_assign` | `target`, `op`, `value` |
+//! | `binop` | `op`, `lhs`, `rhs` |
+//! | `break`, `continue`, `pass`, `list_comp`, `dict_comp` | |
+//! | `call` | `function`, `arg` (any argument), `arg.NAME` (the argument named `NAME`) |
+//! | `conditional` | `condition`, `then`, `else` |
+//! | `def` | `name`, `param` |
+//! | `dict` | `key`, `value` |
+//! | `dot` | `attribute`, `object` |
+//! | `expr_stmt`, `return`, `yield` | `value` |
+//! | `for` | `target`, `iter` |
+//! | `if`, `while` | `condition` |
+//! | `index` | `object`, `index` |
+//! | `int`, `float`, `string` | `value` |
+//! | `lambda` | `param`, `body` |
+//! | `list`, `tuple` | `elem` |
+//! | `load` | `module`, `symbol` |
+//! | `name` | `name` |
+//! | `slice` | `object` |
+//! | `unop` | `op`, `operand` |
+//!
+//! ```
+//! use starlark::syntax::AstModule;
+//! use starlark::syntax::Dialect;
+//!
+//! let module = AstModule::parse(
+//!     "BUILD",
+//!     "srcs = glob(['*.rs'])\ndeps = native.glob(['*.h'], exclude = [])".to_owned(),
+//!     &Dialect::Standard,
+//! )
+//! .unwrap();
+//! let matches = module.query("assign > call[function~='glob$']").unwrap();
+//! let calls: Vec<&str> = matches.iter().map(|x| x.span.source_span()).collect();
+//! assert_eq!(vec!["glob(['*.rs'])", "native.glob(['*.h'], exclude = [])"], calls);
+//!
+//! let matches = module.query("call[arg.exclude]").unwrap();
+//! assert_eq!(1, matches.len());
+//! ```
+
+use regex::Regex;
+
+use crate::codemap::FileSpan;
+use crate::codemap::Span;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::Stmt;
+use crate::syntax::uniplate::Visit;
+use crate::syntax::AstModule;
+
+/// Kinds of nodes and their properties, the main property first.
+const KINDS: &[(&str, &[&str])] = &[
+    ("assign", &["target", "value"]),
+    ("aug_assign", &["target", "op", "value"]),
+    ("binop", &["op", "lhs", "rhs"]),
+    ("break", &[]),
+    ("call", &["function", "arg"]),
+    ("conditional", &["condition", "then", "else"]),
+    ("continue", &[]),
+    ("def", &["name", "param"]),
+    ("dict", &["key", "value"]),
+    ("dict_comp", &[]),
+    ("dot", &["attribute", "object"]),
+    ("expr_stmt", &["value"]),
+    ("float", &["value"]),
+    ("for", &["target", "iter"]),
+    ("if", &["condition"]),
+    ("index", &["object", "index"]),
+    ("int", &["value"]),
+    ("lambda", &["param", "body"]),
+    ("list", &["elem"]),
+    ("list_comp", &[]),
+    ("load", &["module", "symbol"]),
+    ("name", &["name"]),
+    ("pass", &[]),
+    ("return", &["value"]),
+    ("slice", &["object"]),
+    ("string", &["value"]),
+    ("tuple", &["elem"]),
+    ("unop", &["op", "operand"]),
+    ("while", &["condition"]),
+    ("yield", &["value"]),
+];
+
+/// Prefix of the properties of `call` nodes for named arguments.
+const NAMED_ARG: &str = "arg.";
+
+#[derive(Debug, thiserror::Error)]
+enum QueryError {
+    #[error("Invalid query `{0}`, expected {2} at offset {1}")]
+    Syntax(String, usize, &'static str),
+    #[error("Unknown node kind `{0}` in query")]
+    UnknownKind(String),
+    #[error("Node kind `{0}` has no property `{1}`")]
+    UnknownProperty(String, String),
+    #[error("Node kind `{0}` has no properties")]
+    NoProperties(String),
+}
+
+/// A parsed query over the syntax tree, see the [module docs](self) for the syntax.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Selectors, each with whether the node it matches must be a direct child
+    /// of the node matched by the previous selector.
+    steps: Vec<(bool, Selector)>,
+}
+
+/// A node found by a [`Query`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryMatch {
+    /// Kind of the node, e.g. `call`.
+    pub kind: &'static str,
+    /// Span of the node, without trailing whitespace.
+    pub span: FileSpan,
+}
+
+#[derive(Debug, Clone)]
+struct Selector {
+    /// `None` for any kind.
+    kind: Option<&'static str>,
+    predicates: Vec<Predicate>,
+}
+
+#[derive(Debug, Clone)]
+struct Predicate {
+    property: String,
+    test: Test,
+}
+
+#[derive(Debug, Clone)]
+enum Test {
+    Exists,
+    Equals(String),
+    Matches(Regex),
+    Node(Box<Selector>),
+}
+
+#[derive(Clone, Copy)]
+enum Node<'a> {
+    Stmt(&'a AstStmt),
+    Expr(&'a AstExpr),
+}
+
+/// Value of a property of a node.
+enum Property<'a> {
+    Text(&'a str),
+    /// Text from the source.
+    Source(Span),
+    Node(Node<'a>),
+}
+
+impl<'a> Node<'a> {
+    fn span(self) -> Span {
+        match self {
+            Node::Stmt(x) => x.span,
+            Node::Expr(x) => x.span,
+        }
+    }
+
+    /// Kind of this node, `None` for nodes which are not matched, like statement lists.
+    fn kind(self) -> Option<&'static str> {
+        Some(match self {
+            Node::Stmt(x) => match &x.node {
+                Stmt::Break => "break",
+                Stmt::Continue => "continue",
+                Stmt::Pass => "pass",
+                Stmt::Return(_) => "return",
+                Stmt::Yield(_) => "yield",
+                Stmt::Expression(_) => "expr_stmt",
+                Stmt::Assign(..) => "assign",
+                Stmt::AssignModify(..) => "aug_assign",
+                Stmt::Statements(_) => return None,
+                Stmt::If(..) | Stmt::IfElse(..) => "if",
+                Stmt::For(..) => "for",
+                Stmt::While(..) => "while",
+                Stmt::Def(_) => "def",
+                Stmt::Load(_) => "load",
+            },
+            Node::Expr(x) => match &x.node {
+                Expr::Tuple(_) => "tuple",
+                Expr::Dot(..) => "dot",
+                Expr::Call(..) => "call",
+                Expr::ArrayIndirection(_) => "index",
+                Expr::Slice(..) => "slice",
+                Expr::Identifier(..) => "name",
+                Expr::Lambda(_) => "lambda",
+                Expr::Literal(AstLiteral::Int(_)) => "int",
+                Expr::Literal(AstLiteral::Float(_)) => "float",
+                Expr::Literal(AstLiteral::String(_)) => "string",
+                Expr::Not(_) | Expr::Minus(_) | Expr::Plus(_) | Expr::BitNot(_) => "unop",
+                Expr::Op(..) => "binop",
+                Expr::If(_) => "conditional",
+                Expr::List(_) => "list",
+                Expr::Dict(_) => "dict",
+                Expr::ListComprehension(..) => "list_comp",
+                Expr::DictComprehension(..) => "dict_comp",
+            },
+        })
+    }
+
+    fn visit_children(self, mut f: impl FnMut(Node<'a>)) {
+        let visit = match self {
+            Node::Stmt(x) => Visit::Stmt(x),
+            Node::Expr(x) => Visit::Expr(x),
+        };
+        visit.visit_children(|x| match x {
+            Visit::Stmt(x) => f(Node::Stmt(x)),
+            Visit::Expr(x) => f(Node::Expr(x)),
+        })
+    }
+
+    /// Values of the property `name` of this node.
+    fn properties(self, name: &str, res: &mut Vec<Property<'a>>) {
+        let mut node = |x: &'a AstExpr| res.push(Property::Node(Node::Expr(x)));
+        match (self, name) {
+            (Node::Stmt(x), _) => match (&x.node, name) {
+                (Stmt::Return(Some(x)) | Stmt::Yield(Some(x)), "value") => node(x),
+                (Stmt::Expression(x), "value") => node(x),
+                (Stmt::Assign(lhs, _), "target") | (Stmt::AssignModify(lhs, _, _), "target") => {
+                    res.push(Property::Source(lhs.span))
+                }
+                (Stmt::Assign(_, ty_rhs), "value") => node(&ty_rhs.1),
+                (Stmt::AssignModify(_, _, rhs), "value") => node(rhs),
+                (Stmt::AssignModify(lhs, _, rhs), "op") => res.push(Property::Source(Span::new(
+                    lhs.span.end(),
+                    rhs.span.begin(),
+                ))),
+                (Stmt::If(x, _) | Stmt::IfElse(x, _) | Stmt::While(x, _), "condition") => node(x),
+                (Stmt::For(lhs, _), "target") => res.push(Property::Source(lhs.span)),
+                (Stmt::For(_, over_body), "iter") => node(&over_body.0),
+                (Stmt::Def(def), "name") => res.push(Property::Text(&def.name.0)),
+                (Stmt::Def(def), "param") => res.extend(
+                    def.params
+                        .iter()
+                        .filter_map(|x| Some(Property::Text(&x.split().0?.0))),
+                ),
+                (Stmt::Load(load), "module") => res.push(Property::Text(&load.module.node)),
+                (Stmt::Load(load), "symbol") => {
+                    res.extend(load.args.iter().map(|x| Property::Text(&x.0.0)))
+                }
+                _ => {}
+            },
+            (Node::Expr(x), _) => match (&x.node, name) {
+                (Expr::Tuple(xs) | Expr::List(xs), "elem") => xs.iter().for_each(node),
+                (Expr::Dot(x, _), "object") | (Expr::Slice(x, ..), "object") => node(x),
+                (Expr::Dot(_, attribute), "attribute") => res.push(Property::Text(&attribute.node)),
+                (Expr::Call(f, _), "function") => node(f),
+                (Expr::Call(_, args), "arg") => args.iter().for_each(|x| node(x.expr())),
+                (Expr::Call(_, args), _) if name.starts_with(NAMED_ARG) => {
+                    for x in args {
+                        if let ArgumentP::Named(arg, x) = &x.node {
+                            if arg.node == name[NAMED_ARG.len()..] {
+                                node(x);
+                            }
+                        }
+                    }
+                }
+                (Expr::ArrayIndirection(x), "object") => node(&x.0),
+                (Expr::ArrayIndirection(x), "index") => node(&x.1),
+                (Expr::Identifier(x, _), "name") => res.push(Property::Text(&x.node)),
+                (Expr::Lambda(x), "param") => res.extend(
+                    x.params
+                        .iter()
+                        .filter_map(|x| Some(Property::Text(&x.split().0?.0))),
+                ),
+                (Expr::Lambda(x), "body") => node(&x.body),
+                (Expr::Literal(AstLiteral::String(x)), "value") => {
+                    res.push(Property::Text(&x.node))
+                }
+                (Expr::Literal(AstLiteral::Int(x)), "value") => res.push(Property::Source(x.span)),
+                (Expr::Literal(AstLiteral::Float(x)), "value") => {
+                    res.push(Property::Source(x.span))
+                }
+                (Expr::Not(_), "op") => res.push(Property::Text("not")),
+                (Expr::Minus(_), "op") => res.push(Property::Text("-")),
+                (Expr::Plus(_), "op") => res.push(Property::Text("+")),
+                (Expr::BitNot(_), "op") => res.push(Property::Text("~")),
+                (Expr::Not(x) | Expr::Minus(x) | Expr::Plus(x) | Expr::BitNot(x), "operand") => {
+                    node(x)
+                }
+                (Expr::Op(lhs, _, rhs), "op") => res.push(Property::Source(Span::new(
+                    lhs.span.end(),
+                    rhs.span.begin(),
+                ))),
+                (Expr::Op(lhs, _, _), "lhs") => node(lhs),
+                (Expr::Op(_, _, rhs), "rhs") => node(rhs),
+                (Expr::If(x), "condition") => node(&x.0),
+                (Expr::If(x), "then") => node(&x.1),
+                (Expr::If(x), "else") => node(&x.2),
+                (Expr::Dict(xs), "key") => xs.iter().for_each(|x| node(&x.0)),
+                (Expr::Dict(xs), "value") => xs.iter().for_each(|x| node(&x.1)),
+                _ => {}
+            },
+        }
+    }
+}
+
+fn properties_of(kind: &str) -> Option<&'static [&'static str]> {
+    KINDS.iter().find(|x| x.0 == kind).map(|x| x.1)
+}
+
+fn has_property(kind: Option<&str>, property: &str) -> bool {
+    let named_arg = property.starts_with(NAMED_ARG) && property.len() > NAMED_ARG.len();
+    match kind {
+        Some(kind) => {
+            properties_of(kind).is_some_and(|x| x.contains(&property))
+                || (kind == "call" && named_arg)
+        }
+        None => named_arg || KINDS.iter().any(|x| x.1.contains(&property)),
+    }
+}
+
+impl Test {
+    fn test(&self, module: &AstModule, value: &Property) -> bool {
+        let text = match value {
+            Property::Text(x) => x,
+            Property::Source(x) => module.codemap.source_span(*x).trim(),
+            Property::Node(x) => match self {
+                Test::Node(selector) => return selector.matches(module, *x),
+                _ => module.codemap.source_span(x.span()),
+            },
+        };
+        match self {
+            Test::Exists => true,
+            Test::Equals(x) => text == x,
+            Test::Matches(x) => x.is_match(text),
+            Test::Node(_) => false,
+        }
+    }
+}
+
+impl Selector {
+    fn matches(&self, module: &AstModule, node: Node) -> bool {
+        let kind = match node.kind() {
+            Some(kind) => kind,
+            None => return false,
+        };
+        if self.kind.is_some_and(|x| x != kind) {
+            return false;
+        }
+        let mut values = Vec::new();
+        self.predicates.iter().all(|x| {
+            values.clear();
+            node.properties(&x.property, &mut values);
+            values.iter().any(|v| x.test.test(module, v))
+        })
+    }
+}
+
+struct Parser<'a> {
+    query: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.query[self.pos..]
+    }
+
+    fn error(&self, expected: &'static str) -> anyhow::Error {
+        QueryError::Syntax(self.query.to_owned(), self.pos, expected).into()
+    }
+
+    /// Skip whitespace, return whether there was any.
+    fn skip_whitespace(&mut self) -> bool {
+        let rest = self.rest();
+        let trimmed = rest.trim_start();
+        self.pos += rest.len() - trimmed.len();
+        trimmed.len() != rest.len()
+    }
+
+    fn eat(&mut self, x: &str) -> bool {
+        let res = self.rest().starts_with(x);
+        if res {
+            self.pos += x.len();
+        }
+        res
+    }
+
+    fn expect(&mut self, x: &str, expected: &'static str) -> anyhow::Result<()> {
+        if self.eat(x) {
+            Ok(())
+        } else {
+            Err(self.error(expected))
+        }
+    }
+
+    /// Identifier, including `.` for named arguments.
+    fn ident(&mut self, expected: &'static str) -> anyhow::Result<&'a str> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error(expected));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let quote = match self.rest().chars().next() {
+            Some(c @ ('\'' | '"')) => c,
+            _ => return Err(self.error("a quoted string")),
+        };
+        self.pos += 1;
+        let mut res = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, c)) => res.push(c),
+                    None => break,
+                },
+                c if c == quote => {
+                    self.pos += i + 1;
+                    return Ok(res);
+                }
+                c => res.push(c),
+            }
+        }
+        self.pos = self.query.len();
+        Err(self.error("the closing quote"))
+    }
+
+    fn selector(&mut self) -> anyhow::Result<Selector> {
+        let kind = if self.eat("*") {
+            None
+        } else {
+            let kind = self.ident("a node kind or `*`")?;
+            match KINDS.iter().find(|x| x.0 == kind) {
+                Some(x) => Some(x.0),
+                None => return Err(QueryError::UnknownKind(kind.to_owned()).into()),
+            }
+        };
+        let mut predicates = Vec::new();
+        if self.eat("(") {
+            let main = kind
+                .and_then(|x| properties_of(x)?.first())
+                .ok_or_else(|| QueryError::NoProperties(kind.unwrap_or("*").to_owned()))?;
+            self.skip_whitespace();
+            predicates.push(Predicate {
+                property: (*main).to_owned(),
+                test: Test::Equals(self.string()?),
+            });
+            self.skip_whitespace();
+            self.expect(")", "`)`")?;
+        }
+        while self.eat("[") {
+            self.skip_whitespace();
+            let property = self.ident("a property")?;
+            if !has_property(kind, property) {
+                return Err(QueryError::UnknownProperty(
+                    kind.unwrap_or("*").to_owned(),
+                    property.to_owned(),
+                )
+                .into());
+            }
+            self.skip_whitespace();
+            let test = if self.rest().starts_with(']') {
+                Test::Exists
+            } else if self.eat("~=") {
+                self.skip_whitespace();
+                Test::Matches(Regex::new(&self.string()?)?)
+            } else {
+                self.expect("=", "`=` or `~=`")?;
+                self.skip_whitespace();
+                if self.rest().starts_with(['\'', '"']) {
+                    Test::Equals(self.string()?)
+                } else {
+                    Test::Node(Box::new(self.selector()?))
+                }
+            };
+            self.skip_whitespace();
+            self.expect("]", "`]`")?;
+            predicates.push(Predicate {
+                property: property.to_owned(),
+                test,
+            });
+        }
+        Ok(Selector { kind, predicates })
+    }
+
+    fn query(&mut self) -> anyhow::Result<Query> {
+        self.skip_whitespace();
+        let mut steps = vec![(false, self.selector()?)];
+        loop {
+            let whitespace = self.skip_whitespace();
+            if self.rest().is_empty() {
+                return Ok(Query { steps });
+            }
+            let child = self.eat(">");
+            if !child && !whitespace {
+                return Err(self.error("`>`, whitespace or the end"));
+            }
+            self.skip_whitespace();
+            steps.push((child, self.selector()?));
+        }
+    }
+}
+
+impl Query {
+    /// Parse a query, see the [module docs](self) for the syntax.
+    pub fn new(query: &str) -> anyhow::Result<Query> {
+        Parser { query, pos: 0 }.query()
+    }
+
+    /// Whether `node`, inside `ancestors`, matches the first `steps`.
+    fn matches_at(&self, module: &AstModule, steps: usize, node: Node, ancestors: &[Node]) -> bool {
+        let (child, selector) = &self.steps[steps - 1];
+        if !selector.matches(module, node) {
+            return false;
+        }
+        if steps == 1 {
+            return true;
+        }
+        if *child {
+            match ancestors.split_last() {
+                Some((parent, ancestors)) => self.matches_at(module, steps - 1, *parent, ancestors),
+                None => false,
+            }
+        } else {
+            (0..ancestors.len())
+                .any(|i| self.matches_at(module, steps - 1, ancestors[i], &ancestors[..i]))
+        }
+    }
+
+    /// Nodes of `module` matching this query, in the order of the source, outer nodes first.
+    pub fn find(&self, module: &AstModule) -> Vec<QueryMatch> {
+        fn visit<'a>(
+            query: &Query,
+            module: &'a AstModule,
+            node: Node<'a>,
+            ancestors: &mut Vec<Node<'a>>,
+            res: &mut Vec<QueryMatch>,
+        ) {
+            let kind = node.kind();
+            if let Some(kind) = kind {
+                if query.matches_at(module, query.steps.len(), node, ancestors) {
+                    let span = node.span();
+                    let text = module.codemap.source_span(span);
+                    let span = Span::new(span.begin(), span.begin() + text.trim_end().len() as u32);
+                    res.push(QueryMatch {
+                        kind,
+                        span: module.file_span(span),
+                    });
+                }
+                ancestors.push(node);
+            }
+            node.visit_children(|x| visit(query, module, x, ancestors, res));
+            if kind.is_some() {
+                ancestors.pop();
+            }
+        }
+
+        let mut res = Vec::new();
+        visit(
+            self,
+            module,
+            Node::Stmt(&module.statement),
+            &mut Vec::new(),
+            &mut res,
+        );
+        res
+    }
+}
+
+impl AstModule {
+    /// Nodes of this module matching the [`Query`] `query`.
+    pub fn query(&self, query: &str) -> anyhow::Result<Vec<QueryMatch>> {
+        Ok(Query::new(query)?.find(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    const PROGRAM: &str = r#"
+load("rules.star", "rule", build_rule = "rule")
+X = [1, "a"]
+X += [2.5]
+def f(x, *args):
+    for y in x:
+        if not y:
+            return rule(name = "y", srcs = glob(["*.rs"]))
+    return "{}".format(x - 1)
+"#;
+
+    fn query(query: &str) -> Vec<String> {
+        let module = AstModule::parse("test.star", PROGRAM.to_owned(), &Dialect::Extended).unwrap();
+        module
+            .query(query)
+            .unwrap()
+            .into_iter()
+            .map(|x| format!("{} {}", x.kind, x.span.source_span()))
+            .collect()
+    }
+
+    #[test]
+    fn test_query() {
+        assert_eq!(vec!["name glob"], query("name('glob')"));
+        assert_eq!(
+            vec![r#"call glob(["*.rs"])"#],
+            query("call[function=name('glob')]")
+        );
+        assert_eq!(vec![r#"string "y""#], query("call[arg.name] > string"));
+        assert_eq!(
+            vec![r#"call "{}".format(x - 1)"#],
+            query("call[function=dot('format')]")
+        );
+        assert_eq!(
+            vec!["binop x - 1"],
+            query("def('f') return binop[op='-'][rhs='1']")
+        );
+        assert_eq!(vec!["int 1", "int 1"], query("int('1')"));
+        assert_eq!(vec!["float 2.5"], query("aug_assign[op='+='] float"));
+        assert_eq!(vec![r#"assign X = [1, "a"]"#], query("assign('X')"));
+        assert_eq!(
+            vec!["unop not y"],
+            query("for[target='y'] if > unop[op='not']")
+        );
+        assert_eq!(1, query("load[symbol~='^build_']").len());
+        assert!(query("load[symbol='build']").is_empty());
+        assert_eq!(
+            vec!["for", "return"],
+            query("def > *")
+                .iter()
+                .map(|x| x.split(' ').next().unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_query_errors() {
+        let module = AstModule::parse("test.star", PROGRAM.to_owned(), &Dialect::Extended).unwrap();
+        let error = |query: &str| module.query(query).unwrap_err().to_string();
+        assert_eq!("Unknown node kind `fn` in query", error("fn"));
+        assert_eq!(
+            "Node kind `name` has no property `value`",
+            error("name[value='x']")
+        );
+        assert_eq!("Node kind `pass` has no properties", error("pass('x')"));
+        assert_eq!(
+            "Invalid query `call[function=`, expected a node kind or `*` at offset 14",
+            error("call[function=")
+        );
+        assert_eq!(
+            "Invalid query `name('x`, expected the closing quote at offset 7",
+            error("name('x")
+        );
+        assert_eq!(
+            "Invalid query `call)`, expected `>`, whitespace or the end at offset 4",
+            error("call)")
+        );
+    }
+}
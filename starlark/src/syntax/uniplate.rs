@@ -57,7 +57,7 @@ impl<P: AstPayload> StmtP<P> {
     pub(crate) fn visit_children<'a>(&'a self, mut f: impl FnMut(Visit<'a, P>)) {
         match self {
             StmtP::Statements(xs) => xs.iter().for_each(|x| f(Visit::Stmt(x))),
-            StmtP::If(condition, then_block) => {
+            StmtP::If(condition, then_block) | StmtP::While(condition, then_block) => {
                 f(Visit::Expr(condition));
                 f(Visit::Stmt(then_block));
             }
@@ -90,7 +90,7 @@ impl<P: AstPayload> StmtP<P> {
             StmtP::Break => {}
             StmtP::Continue => {}
             StmtP::Pass => {}
-            StmtP::Return(ret) => {
+            StmtP::Return(ret) | StmtP::Yield(ret) => {
                 ret.iter().for_each(|x| f(Visit::Expr(x)));
             }
             StmtP::Expression(e) => f(Visit::Expr(e)),
@@ -111,7 +111,7 @@ impl<P: AstPayload> StmtP<P> {
     pub(crate) fn visit_children_mut<'a>(&'a mut self, mut f: impl FnMut(VisitMut<'a, P>)) {
         match self {
             StmtP::Statements(xs) => xs.iter_mut().for_each(|x| f(VisitMut::Stmt(x))),
-            StmtP::If(condition, then_block) => {
+            StmtP::If(condition, then_block) | StmtP::While(condition, then_block) => {
                 f(VisitMut::Expr(condition));
                 f(VisitMut::Stmt(then_block));
             }
@@ -144,7 +144,7 @@ impl<P: AstPayload> StmtP<P> {
             StmtP::Break => {}
             StmtP::Continue => {}
             StmtP::Pass => {}
-            StmtP::Return(ret) => {
+            StmtP::Return(ret) | StmtP::Yield(ret) => {
                 ret.iter_mut().for_each(|x| f(VisitMut::Expr(x)));
             }
             StmtP::Expression(e) => f(VisitMut::Expr(e)),
@@ -396,6 +396,12 @@ impl<P: AstPayload> AssignP<P> {
                     f(a);
                     f(b);
                 }
+                AssignP::Slice(a, b, c, d) => {
+                    f(a);
+                    b.iter().for_each(|x| f(x));
+                    c.iter().for_each(|x| f(x));
+                    d.iter().for_each(|x| f(x));
+                }
                 AssignP::Identifier(..) => {}
             }
         }
@@ -415,6 +421,12 @@ impl<P: AstPayload> AssignP<P> {
                     f(a);
                     f(b);
                 }
+                AssignP::Slice(a, b, c, d) => {
+                    f(a);
+                    b.iter_mut().for_each(|x| f(x));
+                    c.iter_mut().for_each(|x| f(x));
+                    d.iter_mut().for_each(|x| f(x));
+                }
                 AssignP::Identifier(..) => {}
             }
         }
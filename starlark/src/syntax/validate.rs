@@ -36,32 +36,43 @@ use crate::syntax::ast::AstAssignIdent;
 use crate::syntax::ast::AstExpr;
 use crate::syntax::ast::AstParameter;
 use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::AstPayload;
 use crate::syntax::ast::AstString;
 use crate::syntax::ast::DefP;
 use crate::syntax::ast::Expr;
 use crate::syntax::ast::LambdaP;
 use crate::syntax::ast::Parameter;
 use crate::syntax::ast::Stmt;
+use crate::syntax::ast::StmtP;
 use crate::syntax::dialect::DialectError;
+use crate::syntax::dialect::DialectProfile;
 use crate::syntax::Dialect;
 
 #[derive(Error, Debug)]
 enum ValidateError {
-    #[error("`break` cannot be used outside of a `for` loop")]
+    #[error("`break` cannot be used outside of a loop")]
     BreakOutsideLoop,
-    #[error("`continue` cannot be used outside of a `for` loop")]
+    #[error("`continue` cannot be used outside of a loop")]
     ContinueOutsideLoop,
     #[error("`return` cannot be used outside of a `def` function")]
     ReturnOutsideDef,
+    #[error("`yield` cannot be used outside of a `def` function")]
+    YieldOutsideDef,
+    #[error("`return` with a value cannot be used in a generator function")]
+    ReturnValueInGenerator,
     #[error("`load` must only occur at the top of a module")]
     LoadNotTop,
-    #[error("`if` cannot be used outside `def` in this dialect")]
-    NoTopLevelIf,
-    #[error("`for` cannot be used outside `def` in this dialect")]
-    NoTopLevelFor,
+    #[error("`if` cannot be used {}", .0.top_level_stmt())]
+    NoTopLevelIf(DialectProfile),
+    #[error("`for` cannot be used {}", .0.top_level_stmt())]
+    NoTopLevelFor(DialectProfile),
+    #[error("`while` cannot be used {}", .0.top_level_stmt())]
+    NoTopLevelWhile(DialectProfile),
+    #[error("top-level variable `{0}` cannot be reassigned in {}", .1.files())]
+    GlobalReassign(String, DialectProfile),
     #[error("left-hand-side of assignment must take the form `a`, `a.b` or `a[b]`")]
     InvalidLhs,
-    #[error("left-hand-side of modifying assignment cannot be a list or tuple")]
+    #[error("left-hand-side of modifying assignment cannot be a list, tuple or slice")]
     InvalidModifyLhs,
     #[error("type annotations not allowed on augmented assignments")]
     TypeAnnotationOnAssignOp,
@@ -258,6 +269,65 @@ impl Expr {
 }
 
 impl Stmt {
+    /// A block of statements, inlining nested blocks like the lowered decorated `def`,
+    /// so analyses looking at the statements of a block see all of them.
+    pub(crate) fn statements(stmts: Vec<AstStmt>) -> Stmt {
+        if !stmts.iter().any(|x| matches!(x.node, Stmt::Statements(_))) {
+            return Stmt::Statements(stmts);
+        }
+        let mut res = Vec::with_capacity(stmts.len());
+        for x in stmts {
+            match x.node {
+                Stmt::Statements(xs) => res.extend(xs),
+                _ => res.push(x),
+            }
+        }
+        Stmt::Statements(res)
+    }
+
+    /// Lower `@d1 @d2 def f(...)` to `def f(...)` followed by `f = d1(d2(f))`.
+    ///
+    /// The assignment spans the decorators, so it begins before the `def`,
+    /// which is how [`is_decorator_assign`](StmtP::is_decorator_assign) recognizes it.
+    pub(crate) fn decorate_def(decorators: Vec<AstExpr>, def: AstStmt) -> Stmt {
+        let name = match &def.node {
+            Stmt::Def(DefP { name, .. }) => name.clone(),
+            _ => unreachable!("decorators only apply to `def`"),
+        };
+        let span = decorators
+            .first()
+            .unwrap()
+            .span
+            .merge(decorators.last().unwrap().span);
+        let mut value = Spanned {
+            span: name.span,
+            node: Expr::Identifier(name.clone().into_map(|x| x.0), ()),
+        };
+        for decorator in decorators.into_iter().rev() {
+            value = Spanned {
+                span: decorator.span,
+                node: Expr::Call(
+                    Box::new(decorator),
+                    vec![Spanned {
+                        span: value.span,
+                        node: Argument::Positional(value),
+                    }],
+                ),
+            };
+        }
+        let assign = Spanned {
+            span,
+            node: Stmt::Assign(
+                Spanned {
+                    span: name.span,
+                    node: Assign::Identifier(name),
+                },
+                Box::new((None, value)),
+            ),
+        };
+        Stmt::Statements(vec![def, assign])
+    }
+
     pub(crate) fn check_def(
         name: AstString,
         params: Vec<AstParameter>,
@@ -285,6 +355,7 @@ impl Stmt {
                 }
                 Expr::Dot(a, b) => Assign::Dot(a, b),
                 Expr::ArrayIndirection(a_b) => Assign::ArrayIndirection(a_b),
+                Expr::Slice(a, b, c, d) => Assign::Slice(a, b, c, d),
                 Expr::Identifier(x, ()) => Assign::Identifier(x.into_map(|s| AssignIdentP(s, ()))),
                 _ => {
                     return Err(Diagnostic::new(ValidateError::InvalidLhs, x.span, codemap));
@@ -301,9 +372,9 @@ impl Stmt {
         rhs: AstExpr,
     ) -> anyhow::Result<Stmt> {
         if op.is_some() {
-            // for augmented assignment, Starlark doesn't allow tuple/list
+            // for augmented assignment, Starlark doesn't allow tuple/list or slice
             match &lhs.node {
-                Expr::Tuple(_) | Expr::List(_) => {
+                Expr::Tuple(_) | Expr::List(_) | Expr::Slice(..) => {
                     return Err(Diagnostic::new(
                         ValidateError::InvalidModifyLhs,
                         lhs.span,
@@ -338,8 +409,8 @@ impl Stmt {
         stmt: &AstStmt,
         dialect: &Dialect,
     ) -> anyhow::Result<()> {
-        // Inside a for, we allow continue/break, unless we go beneath a def.
-        // Inside a def, we allow return.
+        // Inside a for or while, we allow continue/break, unless we go beneath a def.
+        // Inside a def, we allow return and yield, but not return with a value after a yield.
         // All load's must occur at the top-level.
         // At the top-level we only allow for/if when the dialect permits it.
         fn f(
@@ -349,46 +420,161 @@ impl Stmt {
             top_level: bool,
             inside_for: bool,
             inside_def: bool,
+            inside_generator: bool,
         ) -> anyhow::Result<()> {
             let err = |x: anyhow::Error| Err(Diagnostic::new(x, stmt.span, codemap));
 
             match &stmt.node {
-                Stmt::Def(DefP { body, .. }) => f(codemap, dialect, body, false, false, true),
+                Stmt::Def(DefP { body, .. }) => {
+                    let generator = body.node.contains_yield();
+                    f(codemap, dialect, body, false, false, true, generator)
+                }
                 Stmt::For(_, over_body) => {
                     let (_, body) = &**over_body;
                     if top_level && !dialect.enable_top_level_stmt {
-                        err(ValidateError::NoTopLevelFor.into())
+                        err(ValidateError::NoTopLevelFor(dialect.profile).into())
+                    } else {
+                        f(
+                            codemap,
+                            dialect,
+                            body,
+                            false,
+                            true,
+                            inside_def,
+                            inside_generator,
+                        )
+                    }
+                }
+                Stmt::While(_, body) => {
+                    if top_level && !dialect.enable_top_level_stmt {
+                        err(ValidateError::NoTopLevelWhile(dialect.profile).into())
                     } else {
-                        f(codemap, dialect, body, false, true, inside_def)
+                        f(
+                            codemap,
+                            dialect,
+                            body,
+                            false,
+                            true,
+                            inside_def,
+                            inside_generator,
+                        )
                     }
                 }
                 Stmt::If(..) | Stmt::IfElse(..) => {
                     if top_level && !dialect.enable_top_level_stmt {
-                        err(ValidateError::NoTopLevelIf.into())
+                        err(ValidateError::NoTopLevelIf(dialect.profile).into())
                     } else {
                         stmt.node.visit_stmt_result(|x| {
-                            f(codemap, dialect, x, false, inside_for, inside_def)
+                            f(
+                                codemap,
+                                dialect,
+                                x,
+                                false,
+                                inside_for,
+                                inside_def,
+                                inside_generator,
+                            )
                         })
                     }
                 }
                 Stmt::Break if !inside_for => err(ValidateError::BreakOutsideLoop.into()),
                 Stmt::Continue if !inside_for => err(ValidateError::ContinueOutsideLoop.into()),
                 Stmt::Return(_) if !inside_def => err(ValidateError::ReturnOutsideDef.into()),
+                Stmt::Return(Some(_)) if inside_generator => {
+                    err(ValidateError::ReturnValueInGenerator.into())
+                }
+                Stmt::Yield(_) if !inside_def => err(ValidateError::YieldOutsideDef.into()),
                 Stmt::Load(..) => {
                     if !top_level {
                         return err(ValidateError::LoadNotTop.into());
                     }
                     if !dialect.enable_load {
-                        return err(DialectError::Load.into());
+                        return err(DialectError::Load(dialect.profile).into());
                     }
                     Ok(())
                 }
                 _ => stmt.node.visit_stmt_result(|x| {
-                    f(codemap, dialect, x, top_level, inside_for, inside_def)
+                    f(
+                        codemap,
+                        dialect,
+                        x,
+                        top_level,
+                        inside_for,
+                        inside_def,
+                        inside_generator,
+                    )
                 }),
             }
         }
 
-        f(codemap, dialect, stmt, true, false, false)
+        f(codemap, dialect, stmt, true, false, false, false)?;
+        if !dialect.enable_global_reassign {
+            Self::validate_global_reassign(codemap, stmt, dialect.profile)?;
+        }
+        Ok(())
+    }
+
+    /// Check that each top-level variable is bound at most once.
+    fn validate_global_reassign(
+        codemap: &CodeMap,
+        stmt: &AstStmt,
+        profile: DialectProfile,
+    ) -> anyhow::Result<()> {
+        // Collect names bound outside of `def`, in program order.
+        fn f<'a>(stmt: &'a AstStmt, idents: &mut Vec<&'a AstAssignIdent>) {
+            match &stmt.node {
+                Stmt::Def(DefP { name, .. }) => idents.push(name),
+                // Rebinds the name of the decorated `def`.
+                Stmt::Assign(..) if Stmt::is_decorator_assign(stmt) => {}
+                Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) => {
+                    lhs.visit_lvalue(|x| idents.push(x))
+                }
+                Stmt::For(var, _) => {
+                    var.visit_lvalue(|x| idents.push(x));
+                    stmt.visit_stmt(|x| f(x, idents));
+                }
+                Stmt::Load(load) => idents.extend(load.args.iter().map(|(x, _)| x)),
+                _ => stmt.visit_stmt(|x| f(x, idents)),
+            }
+        }
+
+        let mut idents = Vec::new();
+        f(stmt, &mut idents);
+        let mut bound = HashSet::new();
+        for x in idents {
+            if !bound.insert(x.0.as_str()) {
+                return Err(Diagnostic::new(
+                    ValidateError::GlobalReassign(x.0.clone(), profile),
+                    x.span,
+                    codemap,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<P: AstPayload> StmtP<P> {
+    /// Is this the assignment `f = d(f)` a decorated `def f` is lowered to,
+    /// see [`decorate_def`](Stmt::decorate_def).
+    pub(crate) fn is_decorator_assign(stmt: &Spanned<StmtP<P>>) -> bool {
+        match &stmt.node {
+            StmtP::Assign(lhs, _) => lhs.span.begin() >= stmt.span.end(),
+            _ => false,
+        }
+    }
+
+    /// Does this statement contain `yield`, not counting nested `def` statements.
+    /// A `def` whose body contains `yield` is a generator function.
+    pub(crate) fn contains_yield(&self) -> bool {
+        match self {
+            StmtP::Yield(_) => true,
+            StmtP::Def(_) => false,
+            _ => {
+                let mut res = false;
+                self.visit_stmt(|x| res = res || x.node.contains_yield());
+                res
+            }
+        }
     }
 }
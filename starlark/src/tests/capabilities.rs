@@ -0,0 +1,82 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Tests for capabilities required by native functions.
+
+use crate as starlark;
+use crate::environment::GlobalsBuilder;
+use crate::environment::Module;
+use crate::eval::Evaluator;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+#[starlark_module]
+fn fs_functions(builder: &mut GlobalsBuilder) {
+    #[starlark(speculative_exec_safe)]
+    fn read_file(path: &str) -> anyhow::Result<String> {
+        Ok(format!("contents of {}", path))
+    }
+}
+
+#[starlark_module]
+fn pure_functions(builder: &mut GlobalsBuilder) {
+    fn double(x: i32) -> anyhow::Result<i32> {
+        Ok(x * 2)
+    }
+}
+
+fn globals(builder: &mut GlobalsBuilder) {
+    builder.require_capability("fs", fs_functions);
+    builder.struct_("io", |builder| {
+        builder.require_capability("fs", fs_functions);
+    });
+    pure_functions(builder);
+}
+
+fn eval(program: &str, capabilities: Option<&[&str]>) -> anyhow::Result<String> {
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    if let Some(capabilities) = capabilities {
+        eval.set_capabilities(capabilities.iter().copied());
+    }
+    let globals = GlobalsBuilder::standard().with(globals).build();
+    let ast = AstModule::parse("x.star", program.to_owned(), &Dialect::Extended)?;
+    Ok(eval.eval_module(ast, &globals)?.to_str())
+}
+
+#[test]
+fn test_capabilities() {
+    for program in ["read_file('a')", "io.read_file('a')"] {
+        assert_eq!("contents of a", eval(program, None).unwrap());
+        assert_eq!("contents of a", eval(program, Some(&["fs"])).unwrap());
+        let err = eval(program, Some(&["net"])).unwrap_err().to_string();
+        assert!(
+            err.contains("Function `read_file` requires capability `fs`"),
+            "{}",
+            err
+        );
+    }
+    assert_eq!("4", eval("double(2)", Some(&[])).unwrap());
+}
+
+#[test]
+fn test_capability_call_stack() {
+    let program = "def load_config():\n    return read_file('config')\nload_config()";
+    let err = eval(program, Some(&[])).unwrap_err().to_string();
+    assert!(err.contains("Traceback"), "{}", err);
+    assert!(err.contains("in load_config"), "{}", err);
+}
@@ -0,0 +1,144 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Tests for decorators on `def`.
+
+use crate::assert;
+use crate::assert::Assert;
+use crate::docs::DocItem;
+use crate::environment::Globals;
+use crate::environment::Module;
+use crate::eval::Evaluator;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+#[test]
+fn test_decorator() {
+    assert::pass(
+        r#"
+calls = []
+
+def trace(f):
+    def wrapper(*args):
+        calls.append(args)
+        return f(*args)
+    return wrapper
+
+def twice(f):
+    return lambda x: f(f(x))
+
+@trace
+@twice
+def inc(x):
+    return x + 1
+
+assert_eq(7, inc(5))
+assert_eq([(5,)], calls)
+"#,
+    );
+}
+
+#[test]
+fn test_decorator_expressions() {
+    assert::pass(
+        r#"
+def add(n):
+    return lambda f: lambda x: f(x) + n
+
+wrappers = struct(double = lambda f: lambda x: 2 * f(x))
+
+@add(10)
+@wrappers.double
+def f(x):
+    return x
+
+def g():
+    @add(1)
+    def h(x):
+        return x
+    return h(1)
+
+assert_eq(16, f(3))
+assert_eq(2, g())
+"#,
+    );
+}
+
+#[test]
+fn test_decorator_memo_docs() {
+    let program = r#"
+@memo
+def square(x):
+    """Square a number."""
+    return x * x
+
+square(3)
+square(3)
+"#;
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    let ast = AstModule::parse("x.star", program.to_owned(), &Dialect::Extended).unwrap();
+    eval.eval_module(ast, &Globals::extended()).unwrap();
+    let square = module.get("square").unwrap();
+    let hits = square.get_attr("cache_hits", module.heap()).unwrap();
+    assert_eq!(Some(1), hits.and_then(|x| x.unpack_int()));
+    match square.documentation() {
+        Some(DocItem::Function(f)) => {
+            assert_eq!("Square a number.", f.docs.unwrap().summary);
+        }
+        x => panic!("Expected function docs, got {:?}", x),
+    }
+}
+
+#[test]
+fn test_decorator_errors() {
+    assert::fail(
+        r#"
+def bad(f, extra):
+    return f
+
+@bad
+def f():
+    pass
+"#,
+        "Missing parameter `extra`",
+    );
+    // Only `def` can be decorated.
+    assert::parse_fail("@memo\n!x! = 1\n");
+
+    let mut a = Assert::new();
+    a.dialect(&Dialect::Standard);
+    a.fail(
+        "@memo\ndef f():\n    pass\n",
+        "decorators are not allowed in this dialect",
+    );
+}
+
+#[test]
+fn test_decorator_global_reassign() {
+    // The decorated name is bound once, by the `def`.
+    let mut a = Assert::new();
+    a.dialect(&Dialect {
+        enable_global_reassign: false,
+        ..Dialect::Extended
+    });
+    a.pass("@memo\ndef f():\n    return 1\nassert_eq(1, f())");
+    a.fail(
+        "@memo\ndef f():\n    pass\nf = 1",
+        "top-level variable `f` cannot be reassigned",
+    );
+}
@@ -21,6 +21,7 @@ use crate::assert;
 use crate::assert::Assert;
 use crate::environment::Module;
 use crate::eval::Evaluator;
+use crate::syntax::Dialect;
 
 #[test]
 fn test_lambda() {
@@ -213,3 +214,74 @@ G = f(1)
 
     a.pass("load('x.bzl', 'G')\nG()");
 }
+
+#[test]
+fn test_default_shared() {
+    assert::pass(
+        r#"
+def f(x = []):
+    x.append(1)
+    return x
+
+f()
+assert_eq([1, 1], f())
+"#,
+    );
+}
+
+#[test]
+fn test_fresh_defaults() {
+    let mut a = Assert::new();
+    a.dialect(&Dialect {
+        enable_fresh_defaults: true,
+        ..Dialect::Extended
+    });
+    a.pass(
+        r#"
+def f(x = [], y = {}):
+    x.append(1)
+    y[len(y)] = 1
+    return (x, y)
+
+f()
+assert_eq(([1], {0: 1}), f())
+
+# Explicitly passed values are not copied.
+l = []
+assert_eq([1], f(l)[0])
+assert_eq([1, 1], f(l)[0])
+
+# Captured parameter.
+def g(x = []):
+    def h():
+        x.append(1)
+        return x
+    return h()
+
+g()
+assert_eq([1], g())
+
+# Typed parameter.
+def t(x: list.type = []) -> list.type:
+    x.append(1)
+    return x
+
+t()
+assert_eq([1], t())
+
+k = lambda x = []: x
+k().append(1)
+assert_eq([], k())
+"#,
+    );
+    a.module(
+        "x.bzl",
+        r#"
+def f(x = []):
+    x.append(1)
+    return x
+"#,
+    );
+    // Defaults of frozen functions are copied too, so can be mutated.
+    a.pass("load('x.bzl', 'f')\nf()\nassert_eq([1], f())");
+}
@@ -0,0 +1,61 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Tests for f-strings.
+
+use crate::assert;
+use crate::assert::Assert;
+use crate::syntax::Dialect;
+
+#[test]
+fn test_f_string() {
+    assert::pass(
+        r#"
+name = "world"
+xs = [1, 2]
+assert_eq("hello world", f"hello {name}")
+assert_eq('hello world!', f'hello {name}!')
+assert_eq("3 [1, 2] 2", f"{1 + 2} {xs} {len(xs)}")
+assert_eq("{name} {world}", f"{{name}} {{{name}}}")
+assert_eq("(1, 2)", f"{1, 2}")
+assert_eq("ab", f"{'a' + {'k': 'b'}['k']}")
+assert_eq("x\n{y}", f"x\n\x7by\x7d")
+assert_eq("no {fields}", f"no {{fields}}")
+assert_eq("HELLO WORLD", f"hello {name}".upper())
+assert_eq("1\n2", f"""{xs[0]}
+{xs[
+    1
+]}""")
+assert_eq("[1]", f"{[f'{x}' for x in [1]][0]}".join(["[", "]"]))
+"#,
+    );
+}
+
+#[test]
+fn test_f_string_errors() {
+    assert::fail("x = f\"a {undefined}\"", "Variable `undefined` not found");
+    assert::parse_fail("x = f\"{1 +!}!\"");
+    assert::parse_fail("x = f\"{1 !?!}\"");
+    assert::fail("f\"{}\"", "f-string expression cannot be empty");
+    assert::fail("f\"a }\"", "single `}` is not allowed in f-string");
+    assert::fail("f\"{x\"", "f-string expression is missing a closing `}`");
+    assert::fail("f\"{x:.2f}\"", "Parse error");
+
+    let mut a = Assert::new();
+    a.dialect(&Dialect::Standard);
+    a.fail("x = f\"{1}\"", "f-strings are not allowed in this dialect");
+}
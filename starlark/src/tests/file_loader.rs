@@ -0,0 +1,223 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+
+use crate::environment::FileKind;
+use crate::environment::Globals;
+use crate::environment::GlobalsBuilder;
+use crate::environment::Module;
+use crate::eval::EvalRecorder;
+use crate::eval::EvalRecording;
+use crate::eval::Evaluator;
+use crate::eval::FileLoader;
+use crate::eval::FsFileLoader;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+use crate::syntax::DialectProfile;
+
+/// `.bzl` files are extended Starlark, everything else is a `BUILD` file.
+fn file_kind(path: &Path) -> FileKind {
+    if path.extension().is_some_and(|x| x == "bzl") {
+        FileKind {
+            dialect: Dialect::Extended,
+            globals: Globals::extended(),
+        }
+    } else {
+        FileKind {
+            dialect: Dialect::Build,
+            globals: Globals::standard(),
+        }
+    }
+}
+
+fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("starlark-{}-{}", test, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for (name, content) in files {
+        fs::write(dir.join(name), content).unwrap();
+    }
+    dir
+}
+
+fn eval(dir: &Path, program: &str) -> anyhow::Result<String> {
+    let loader = FsFileLoader::new(dir, &file_kind);
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    eval.set_loader(&loader);
+    let ast = AstModule::parse("main.bzl", program.to_owned(), &Dialect::Extended)?;
+    Ok(eval.eval_module(ast, &Globals::extended())?.to_repr())
+}
+
+#[test]
+fn test_fs_file_loader() {
+    let dir = write_files(
+        "fs-file-loader",
+        &[
+            (
+                "defs.bzl",
+                "load('consts', 'X')\ndef f():\n    return X + 1",
+            ),
+            ("consts", "X = 10"),
+            ("bad", "def g():\n    pass"),
+        ],
+    );
+    assert_eq!("11", eval(&dir, "load('defs.bzl', 'f')\nf()").unwrap());
+    let err = eval(&dir, "load('bad', 'g')").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("`def` is not allowed in BUILD files"),
+        "{:#}",
+        err
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_fs_file_loader_cycle() {
+    let dir = write_files(
+        "fs-file-loader-cycle",
+        &[
+            ("a.bzl", "load('b.bzl', 'b')\na = 1"),
+            ("b.bzl", "load('a.bzl', 'a')\nb = 1"),
+        ],
+    );
+    let err = eval(&dir, "load('a.bzl', 'a')").unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("Cycle in `load()` statements"),
+        "{:#}",
+        err
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_fs_file_loader_invalidate() {
+    let dir = write_files(
+        "fs-file-loader-invalidate",
+        &[
+            ("a.bzl", "A = 1"),
+            ("b.bzl", "load('a.bzl', 'A')\nB = A + 1"),
+            ("c.bzl", "load('b.bzl', 'B')\nload('a.bzl', 'A')\nC = A + B"),
+            ("d.bzl", "D = 1"),
+        ],
+    );
+    let loader = FsFileLoader::new(&dir, &file_kind);
+    let get = |path: &str, name: &str| loader.load(path).unwrap().get(name).unwrap().to_string();
+    assert_eq!("3", get("c.bzl", "C"));
+    assert_eq!("1", get("d.bzl", "D"));
+    let generation = loader.generation("a.bzl").unwrap();
+    let d_generation = loader.generation("d.bzl");
+
+    fs::write(dir.join("a.bzl"), "A = 10").unwrap();
+    assert_eq!(
+        vec![dir.join("b.bzl"), dir.join("c.bzl")],
+        loader.invalidate("a.bzl")
+    );
+    assert_eq!(None, loader.generation("b.bzl"));
+    assert_eq!("21", get("c.bzl", "C"));
+    assert!(loader.generation("a.bzl").unwrap() > generation);
+    assert_eq!(d_generation, loader.generation("d.bzl"));
+
+    let module = Module::new();
+    module.set("B", module.heap().alloc(100));
+    assert_eq!(
+        vec![dir.join("c.bzl")],
+        loader.replace("b.bzl", module.freeze().unwrap())
+    );
+    assert_eq!("110", get("c.bzl", "C"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_record_and_replay() {
+    let dir = write_files(
+        "record",
+        &[
+            (
+                "defs.bzl",
+                "load('consts', 'X')\ndef f():\n    return X + OFFSET",
+            ),
+            ("consts", "X = len(NAME)"),
+        ],
+    );
+    let globals = |builder: GlobalsBuilder| {
+        builder
+            .with(|x| {
+                x.set("OFFSET", 40);
+                x.set("NAME", "ab");
+            })
+            .build()
+    };
+    let file_kind = |path: &Path| {
+        let FileKind { dialect, .. } = file_kind(path);
+        let globals = match dialect.profile {
+            DialectProfile::Build => globals(GlobalsBuilder::standard()),
+            DialectProfile::Module => globals(GlobalsBuilder::extended()),
+        };
+        FileKind { dialect, globals }
+    };
+
+    let recorder = EvalRecorder::new();
+    recorder.set_seed(7);
+    {
+        let mut loader = FsFileLoader::new(&dir, &file_kind);
+        loader.set_recorder(&recorder);
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.set_loader(&loader);
+        eval.set_recorder(&recorder);
+        let ast = AstModule::parse(
+            "main.bzl",
+            "load('defs.bzl', 'f')\nresult = f()".to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        let globals = globals(GlobalsBuilder::extended());
+        eval.eval_module(ast, &globals).unwrap();
+        assert_eq!("42", module.get("result").unwrap().to_repr());
+    }
+    let recording = recorder.finish();
+    assert_eq!(
+        vec![
+            (PathBuf::from("main.bzl"), true),
+            (dir.join("defs.bzl"), false),
+            (dir.join("consts"), false),
+        ],
+        recording
+            .files
+            .iter()
+            .map(|x| (PathBuf::from(&x.path), x.top_level))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(Some(1), recording.files[0].loads[0].file);
+    assert_eq!(Some(2), recording.files[1].loads[0].file);
+    assert_eq!(Dialect::Build, recording.files[2].dialect);
+    // Extended and standard globals.
+    assert_eq!(2, recording.globals.len());
+    assert_eq!(Some(7), recording.seed);
+
+    // The files are not needed to replay.
+    fs::remove_dir_all(&dir).unwrap();
+    let json = serde_json::to_string(&recording).unwrap();
+    let recording: EvalRecording = serde_json::from_str(&json).unwrap();
+    let module = recording.replay(GlobalsBuilder::extended).unwrap();
+    assert_eq!("42", module.get("result").unwrap().value().to_repr());
+}
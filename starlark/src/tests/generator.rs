@@ -0,0 +1,408 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Tests for generator functions, i.e. `def` containing `yield`.
+
+use crate::assert;
+use crate::assert::Assert;
+use crate::environment::Globals;
+use crate::environment::Module;
+use crate::eval::Evaluator;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+#[test]
+fn test_generator_simple() {
+    assert::pass(
+        r#"
+def gen():
+    yield 1
+    yield 2
+    yield
+
+def test():
+    return [x for x in gen()]
+
+assert_eq([1, 2, None], test())
+assert_eq("generator", type(gen()))
+assert_eq("<generator gen>", str(gen()))
+"#,
+    );
+}
+
+#[test]
+fn test_generator_loops() {
+    assert::pass(
+        r#"
+def gen(n):
+    for i in range(n):
+        if i % 2 == 0:
+            continue
+        for j in [10, 20, 30]:
+            if j == 30:
+                break
+            yield i * j
+        if i == 5:
+            return
+    yield -1
+
+def test():
+    return [x for x in gen(4)], [x for x in gen(10)]
+
+assert_eq(([10, 20, 30, 60, -1], [10, 20, 30, 60, 50, 100]), test())
+"#,
+    );
+}
+
+#[test]
+fn test_generator_locals_preserved() {
+    assert::pass(
+        r#"
+def gen(xs):
+    total = 0
+    for x in xs:
+        total += x
+        yield total
+    yield total * 100
+
+def test():
+    res = []
+    for x in gen((1, 2, 3)):
+        res.append(x)
+    return res
+
+assert_eq([1, 3, 6, 600], test())
+"#,
+    );
+}
+
+#[test]
+fn test_generator_lazy() {
+    assert::pass(
+        r#"
+log = []
+
+def gen():
+    for i in range(3):
+        log.append("yield {}".format(i))
+        yield i
+
+def test():
+    for x in gen():
+        log.append("got {}".format(x))
+        if x == 1:
+            break
+    return log
+
+assert_eq(["yield 0", "got 0", "yield 1", "got 1"], test())
+"#,
+    );
+}
+
+#[test]
+fn test_generator_of_generator() {
+    assert::pass(
+        r#"
+def numbers():
+    for i in range(1000000000):
+        yield i
+
+def squares():
+    for i in numbers():
+        yield i * i
+
+def test():
+    res = []
+    for x in squares():
+        if x > 50:
+            break
+        res.append(x)
+    return res
+
+assert_eq([0, 1, 4, 9, 16, 25, 36, 49], test())
+"#,
+    );
+}
+
+#[test]
+fn test_generator_error() {
+    assert::fail(
+        r#"
+def gen():
+    yield 1
+    fail("boom")
+
+def test():
+    return [x for x in gen()]
+
+test()
+"#,
+        "boom",
+    );
+    assert::fail(
+        r#"
+def gen():
+    yield 1
+    fail("boom")
+
+def test():
+    return list(gen())
+
+test()
+"#,
+        "boom",
+    );
+}
+
+#[test]
+fn test_generator_builtins() {
+    assert::pass(
+        r#"
+def gen(n):
+    for i in range(n):
+        yield n - i
+
+def names():
+    yield "a"
+    yield "b"
+
+def test():
+    assert_eq([3, 2, 1], list(gen(3)))
+    assert_eq((3, 2, 1), tuple(gen(3)))
+    assert_eq([1, 2, 3], sorted(gen(3)))
+    assert_eq([3, 2, 1], sorted(gen(3), key = lambda x: -x))
+    assert_eq([1, 2, 3], reversed(gen(3)))
+    assert_eq([(0, 2), (1, 1)], enumerate(gen(2)))
+    assert_eq([(2, "a"), (1, "b")], zip(gen(2), ["a", "b", "c"]))
+    assert_eq(1, min(gen(3)))
+    assert_eq(3, max(gen(3)))
+    assert_eq("a,b", ",".join(names()))
+    xs = [0]
+    xs.extend(gen(2))
+    assert_eq([0, 2, 1], xs)
+    assert_eq([], list(gen(0)))
+
+test()
+"#,
+    );
+}
+
+#[test]
+fn test_generator_any_all_lazy() {
+    // `any` and `all` stop resuming the generator once the result is known.
+    assert::pass(
+        r#"
+def gen(xs, seen):
+    for x in xs:
+        seen.append(x)
+        yield x
+
+def test():
+    seen = []
+    assert_eq(True, any(gen([False, True, False], seen)))
+    assert_eq([False, True], seen)
+    seen = []
+    assert_eq(False, all(gen([True, False, True], seen)))
+    assert_eq([True, False], seen)
+    assert_eq(True, all(gen([], [])))
+    assert_eq(False, any(gen([], [])))
+
+test()
+"#,
+    );
+}
+
+#[test]
+fn test_generator_iterate() {
+    // Natives which only have the heap and `in` iterate generators too.
+    assert::pass(
+        r#"
+def pairs(n):
+    for i in range(n):
+        yield (i, str(i))
+
+def gen(xs, seen):
+    for x in xs:
+        seen.append(x)
+        yield x
+
+def test():
+    assert_eq({0: "0", 1: "1"}, dict(pairs(2)))
+    d = {5: "x"}
+    d.update(pairs(2))
+    assert_eq({5: "x", 0: "0", 1: "1"}, d)
+    assert_eq(set([0, 1]), set([i for i, _ in pairs(2)]))
+    seen = []
+    assert_true(2 in gen([1, 2, 3], seen))
+    assert_eq([1, 2], seen)
+    assert_true(4 not in gen([1, 2, 3], []))
+
+test()
+"#,
+    );
+}
+
+#[test]
+fn test_generator_loop_mutation() {
+    assert::pass(
+        r#"
+def gen(xs):
+    for x in xs:
+        yield x
+
+def keys(d):
+    for k in d:
+        yield k
+
+def test():
+    xs = [1, 2, 3]
+    assert_eq([1, 2, 3], [x for x in gen(xs)])
+    assert_eq(["a", "b"], list(keys({"a": 1, "b": 2})))
+    # The collection is only locked while the generator is running.
+    g = gen(xs)
+    for x in g:
+        assert_eq(1, x)
+        break
+    xs.append(4)
+    assert_eq([2, 3, 4], list(g))
+
+test()
+"#,
+    );
+    assert::fail(
+        r#"
+def gen(xs):
+    for x in xs:
+        xs.append(x)
+        yield x
+
+def test():
+    return list(gen([1, 2]))
+
+test()
+"#,
+        "mutate an iterable for an iterator while iterating",
+    );
+    assert::fail(
+        r#"
+def gen(d):
+    for k in d:
+        yield k
+        d[k + "x"] = 1
+
+def test():
+    return list(gen({"a": 1}))
+
+test()
+"#,
+        "mutate an iterable for an iterator while iterating",
+    );
+    assert::fail(
+        r#"
+def gen(xs):
+    for x in xs:
+        for y in [x]:
+            yield y
+            xs.append(y)
+
+def test():
+    return list(gen([1]))
+
+test()
+"#,
+        "mutate an iterable for an iterator while iterating",
+    );
+}
+
+#[test]
+fn test_generator_freeze() {
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    let ast = AstModule::parse(
+        "gen.star",
+        "def gen():\n    yield 1\ng = gen()\n".to_owned(),
+        &Dialect::Extended,
+    )
+    .unwrap();
+    eval.eval_module(ast, &Globals::standard()).unwrap();
+    let err = module.freeze().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Cannot freeze unfinished generator `gen`"),
+        "{}",
+        err
+    );
+
+    let mut a = Assert::new();
+    a.module(
+        "m",
+        r#"
+def gen():
+    yield 1
+
+def consume():
+    g = gen()
+    for _x in g:
+        pass
+    return g
+
+g = consume()
+"#,
+    );
+    a.pass(
+        r#"
+load("m", "g")
+def test():
+    return [x for x in g]
+assert_eq([], test())
+"#,
+    );
+}
+
+#[test]
+fn test_generator_syntax_errors() {
+    assert::fail("yield 1", "cannot be used outside of a `def`");
+    assert::fail(
+        r#"
+def gen():
+    yield 1
+    return 2
+"#,
+        "`return` with a value cannot be used in a generator",
+    );
+    // `yield` in nested `def` does not make the outer function a generator.
+    assert::pass(
+        r#"
+def outer():
+    def inner():
+        yield 1
+    return 2
+
+assert_eq(2, outer())
+"#,
+    );
+
+    let mut a = Assert::new();
+    a.dialect(&Dialect::Standard);
+    a.fail(
+        r#"
+def gen():
+    yield 1
+"#,
+        "`yield` is not allowed in this dialect",
+    );
+}
@@ -30,14 +30,20 @@ use crate as starlark;
 use crate::assert;
 use crate::assert::Assert;
 use crate::collections::SmallMap;
+use crate::docs;
+use crate::docs::DocItem;
 use crate::environment::GlobalsBuilder;
 use crate::environment::Module;
+use crate::eval::Arguments;
 use crate::eval::Evaluator;
+use crate::eval::ParametersSpec;
+use crate::eval::ProfileMode;
 use crate::syntax::AstModule;
 use crate::syntax::Dialect;
 use crate::values::any::StarlarkAny;
 use crate::values::none::NoneType;
 use crate::values::Freeze;
+use crate::values::Heap;
 use crate::values::NoSerialize;
 use crate::values::StarlarkValue;
 use crate::values::Value;
@@ -155,6 +161,59 @@ fn test_load_public_symbols_does_not_reexport() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_call_from_native() {
+    #[starlark_module]
+    fn apply_module(builder: &mut GlobalsBuilder) {
+        fn apply<'v>(
+            f: Value<'v>,
+            x: Value<'v>,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<Value<'v>> {
+            eval.eval_function(f, &[x], &[])
+        }
+    }
+
+    let mut a = Assert::new();
+    a.globals_add(apply_module);
+    a.eq("4", "apply(lambda x: x * 2, 2)");
+    // Recursion through a native function hits the call stack limit
+    // instead of overflowing the native stack.
+    a.fail(
+        r#"
+def f(x):
+    return apply(f, x)
+f(1)
+"#,
+        "call stack overflow",
+    );
+
+    // Allocations in the callback are attributed to the callback.
+    let module = Module::new();
+    let globals = GlobalsBuilder::standard().with(apply_module).build();
+    let mut eval = Evaluator::new(&module);
+    eval.enable_profile(&ProfileMode::HeapSummaryAllocated)
+        .unwrap();
+    eval.eval_module(
+        AstModule::parse(
+            "x.star",
+            r#"
+def callback(x):
+    return [x]
+
+apply(callback, 1)
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap(),
+        &globals,
+    )
+    .unwrap();
+    let profile = eval.gen_profile().unwrap().gen().unwrap();
+    assert!(profile.contains("\"x.star.callback\""), "{}", profile);
+}
+
 #[test]
 // Test that we can express something that loads symbols into the exported module,
 // but not using the very dubious `set_module_variable_at_some_point`.
@@ -213,6 +272,40 @@ fn test_load_symbols_extra() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_call_function_typed() -> anyhow::Result<()> {
+    let mut a = Assert::new();
+    let frozen = a.module(
+        "m",
+        "def f(x, y = 1): return [x] * y\ndef _private(): return 1",
+    );
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    let res: Vec<String> = frozen.call_function(&mut eval, "f", ("a",), (("y", 2),))?;
+    assert_eq!(vec!["a".to_owned(), "a".to_owned()], res);
+    let res: Vec<i32> = frozen.call_function(&mut eval, "f", (3,), ())?;
+    assert_eq!(vec![3], res);
+
+    let err = frozen
+        .call_function::<i32>(&mut eval, "f", (3,), ())
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("result mismatch, expected `int.type`, actual `list`"),
+        "{}",
+        err
+    );
+    let err = frozen
+        .call_function::<i32>(&mut eval, "_private", (), ())
+        .unwrap_err();
+    assert!(err.to_string().contains("not exported"), "{}", err);
+    let err = frozen
+        .call_function::<i32>(&mut eval, "f", (), ())
+        .unwrap_err();
+    assert!(err.to_string().contains("Missing parameter `x`"), "{}", err);
+    Ok(())
+}
+
 #[test]
 fn test_repr_str() {
     #[derive(ProvidesStaticType, Debug, Display)]
@@ -248,3 +341,47 @@ fun
         .unwrap();
     assert_eq!(v.unpack_str(), Some("(8, \"hello\", 1)"))
 }
+
+#[test]
+fn test_alloc_callable() {
+    #[starlark_module]
+    fn callable_module(builder: &mut GlobalsBuilder) {
+        fn make_adder<'v>(n: i32, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+            Ok(heap.alloc_callable("adder", move |eval, args| {
+                args.no_named_args()?;
+                let x: i32 = Arguments::check_required("x", Some(args.positional1(eval.heap())?))?;
+                Ok(Value::new_int(n + x))
+            }))
+        }
+
+        fn make_scale<'v>(n: i32, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+            let mut signature = ParametersSpec::new("scale".to_owned());
+            signature.no_more_positional_only_args();
+            signature.required("x");
+            Ok(heap.alloc_callable_with_signature(
+                "scale",
+                signature.finish(),
+                move |_eval, mut parser| {
+                    let x: i32 = parser.next("x")?;
+                    Ok(Value::new_int(n * x))
+                },
+            ))
+        }
+    }
+
+    let mut a = Assert::new();
+    a.globals_add(callable_module);
+    a.eq("5", "make_adder(2)(3)");
+    a.eq("[11, 12]", "[make_adder(n)(10) for n in [1, 2]]");
+    a.eq("'function'", "type(make_adder(1))");
+    a.eq("12", "make_scale(3)(x = 4)");
+    a.fail("make_scale(3)(4, 5)", "extra positional argument");
+
+    let f = a.pass("make_scale(3)");
+    match f.value().documentation() {
+        Some(DocItem::Function(docs::Function { params, .. })) => {
+            assert_eq!(1, params.len());
+        }
+        docs => panic!("unexpected documentation: {:?}", docs),
+    }
+}
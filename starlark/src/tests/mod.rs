@@ -19,14 +19,20 @@ mod basic;
 mod bc;
 mod before_stmt;
 mod call;
+mod capabilities;
 mod comprehension;
+mod decorator;
 mod def;
 mod derive;
 mod docstring;
+mod f_string;
+mod file_loader;
 mod freeze_access_value;
+mod generator;
 mod go;
 mod interop;
 mod opt;
 mod runtime;
 mod type_annot;
 mod uncategorized;
+mod while_loop;
@@ -0,0 +1,190 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Tests for `while` loops.
+
+use crate::assert;
+use crate::assert::Assert;
+use crate::environment::Globals;
+use crate::environment::Module;
+use crate::eval::Evaluator;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+fn assert_with_while() -> Assert<'static> {
+    let mut a = Assert::new();
+    a.dialect_set(|d| d.enable_while = true);
+    a
+}
+
+fn eval_with_fuel(program: &str, fuel: u64) -> (anyhow::Result<String>, u64) {
+    let dialect = Dialect {
+        enable_while: true,
+        ..Dialect::Extended
+    };
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    eval.set_loop_fuel(fuel);
+    let res = AstModule::parse("x.star", program.to_owned(), &dialect)
+        .and_then(|ast| Ok(eval.eval_module(ast, &Globals::standard())?.to_repr()));
+    (res, eval.loop_fuel())
+}
+
+#[test]
+fn test_while() {
+    assert_with_while().pass(
+        r#"
+def collatz(n):
+    steps = 0
+    while n != 1:
+        if n % 2 == 0:
+            n //= 2
+        else:
+            n = 3 * n + 1
+        steps += 1
+    return steps
+
+def skip_and_stop():
+    res = []
+    i = 0
+    while True:
+        i += 1
+        if i % 2 == 0:
+            continue
+        if i > 7:
+            break
+        res.append(i)
+    return res
+
+def nested():
+    res = []
+    i = 0
+    while i < 3:
+        for x in ["a", "b"]:
+            j = 0
+            while j < i:
+                res.append(x + str(j))
+                j += 1
+        i += 1
+    return res
+
+def return_from_loop(xs):
+    i = 0
+    while i < len(xs):
+        if xs[i] < 0:
+            return i
+        i += 1
+    return None
+
+assert_eq(111, collatz(27))
+assert_eq([1, 3, 5, 7], skip_and_stop())
+assert_eq(["a0", "b0", "a0", "a1", "b0", "b1"], nested())
+assert_eq(2, return_from_loop([1, 2, -3, 4]))
+assert_eq(None, return_from_loop([1, 2]))
+
+n = 0
+while n < 10:
+    n += 3
+assert_eq(12, n)
+
+while False:
+    fail("unreachable")
+"#,
+    );
+}
+
+#[test]
+fn test_while_in_generator() {
+    assert_with_while().pass(
+        r#"
+def countdown(n):
+    while n > 0:
+        yield n
+        n -= 1
+    yield "done"
+
+def test():
+    return [x for x in countdown(3)]
+
+assert_eq([3, 2, 1, "done"], test())
+"#,
+    );
+}
+
+#[test]
+fn test_while_fuel() {
+    let program = r#"
+def loop(n):
+    i = 0
+    while i < n:
+        i += 1
+    return i
+loop(10)
+"#;
+    // Condition is evaluated 11 times.
+    let (res, fuel) = eval_with_fuel(program, 100);
+    assert_eq!("10", res.unwrap());
+    assert_eq!(89, fuel);
+
+    let (res, fuel) = eval_with_fuel(program, 5);
+    let err = res.unwrap_err().to_string();
+    assert!(
+        err.contains("`while` loops executed more than 5 iterations"),
+        "{}",
+        err
+    );
+    assert_eq!(0, fuel);
+
+    // Fuel is shared by all the loops.
+    let (res, _) = eval_with_fuel(&format!("{}\nloop(10)", program), 15);
+    assert!(res.is_err());
+
+    // Infinite loop fails when the fuel is exhausted.
+    let (res, _) = eval_with_fuel("while True:\n    pass\n", 1_000_000);
+    assert!(res.is_err());
+}
+
+#[test]
+fn test_while_disabled() {
+    assert::fail(
+        r#"
+def f():
+    while True:
+        pass
+"#,
+        "`while` is not allowed in this dialect",
+    );
+    let mut a = Assert::new();
+    a.dialect(&Dialect::Standard);
+    a.fail(
+        r#"
+def f():
+    while True:
+        pass
+"#,
+        "`while` is not allowed in this dialect",
+    );
+    let mut a = Assert::new();
+    a.dialect(&Dialect {
+        enable_while: true,
+        ..Dialect::Standard
+    });
+    a.fail(
+        "while True:\n    pass\n",
+        "`while` cannot be used outside `def` in this dialect",
+    );
+}
@@ -0,0 +1,319 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Storage for the content of mutable complex values, like `list` and `dict`.
+//!
+//! A mutable value is usually a type generic over its storage, e.g. `MyGen<C>`, where
+//! the unfrozen value is `MyGen<ValueCell<T>>` and the frozen value is
+//! `MyGen<FrozenValueCell<T::Frozen>>`. The [`StarlarkValue`](crate::values::StarlarkValue)
+//! implementation is written once for any `C: ValueCellLike<T>`:
+//!
+//! * Reading uses [`ValueCellLike::borrow`], which works for both.
+//! * Mutating uses [`ValueCellLike::borrow_mut`], which fails with
+//!   [`CannotMutateImmutableValue`](ValueError::CannotMutateImmutableValue) once frozen,
+//!   and with [`MutationDuringIteration`](ValueError::MutationDuringIteration)
+//!   while the content is borrowed, e.g. by an iterator.
+//!
+//! Code written against the unfrozen type alone can't mutate a frozen value at all,
+//! because [`FrozenValueCell`] has no mutable access.
+//!
+//! ```
+//! use allocative::Allocative;
+//! use starlark::values::cell::FrozenValueCell;
+//! use starlark::values::cell::ValueCell;
+//! use starlark::values::cell::ValueCellLike;
+//! use starlark::values::Freeze;
+//! use starlark::values::Freezer;
+//! use starlark::values::FrozenValue;
+//! use starlark::values::Value;
+//!
+//! #[derive(Debug, Allocative)]
+//! struct StackGen<C>(C);
+//!
+//! impl<'v, C: ValueCellLike<Vec<Value<'v>>>> StackGen<C> {
+//!     fn push(&self, x: Value<'v>) -> anyhow::Result<()> {
+//!         self.0.borrow_mut()?.push(x);
+//!         Ok(())
+//!     }
+//!
+//!     fn len(&self) -> usize {
+//!         self.0.borrow().len()
+//!     }
+//! }
+//!
+//! impl<'v> Freeze for StackGen<ValueCell<Vec<Value<'v>>>> {
+//!     type Frozen = StackGen<FrozenValueCell<Vec<FrozenValue>>>;
+//!
+//!     fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+//!         Ok(StackGen(self.0.freeze(freezer)?))
+//!     }
+//! }
+//!
+//! let stack = StackGen(ValueCell::new(Vec::new()));
+//! stack.push(Value::new_none()).unwrap();
+//! assert_eq!(1, stack.len());
+//! ```
+
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::cell::RefMut;
+use std::fmt;
+use std::fmt::Debug;
+
+use allocative::Allocative;
+use gazebo::cell::ARef;
+use gazebo::coerce::coerce;
+use gazebo::coerce::Coerce;
+
+use crate::values::Freeze;
+use crate::values::Freezer;
+use crate::values::ProvidesStaticType;
+use crate::values::Trace;
+use crate::values::Tracer;
+use crate::values::ValueError;
+
+/// Access to the content of a mutable value, implemented by [`ValueCell`] before freezing
+/// and by [`FrozenValueCell`] after.
+pub trait ValueCellLike<T> {
+    /// Borrow the content for reading.
+    ///
+    /// Panics if the content is being mutated, which can only happen if the native code
+    /// mutating it reads it again before releasing the [`RefMut`].
+    fn borrow(&self) -> ARef<'_, T>;
+
+    /// Borrow the content for mutation.
+    fn borrow_mut(&self) -> anyhow::Result<RefMut<'_, T>>;
+}
+
+/// Content of a complex value which can be mutated until the value is frozen,
+/// see the [module docs](self).
+#[derive(Default, ProvidesStaticType, Allocative)]
+pub struct ValueCell<T>(RefCell<T>);
+
+/// Content of a frozen complex value, which can't be mutated, see the [module docs](self).
+#[derive(Default, ProvidesStaticType, Allocative)]
+pub struct FrozenValueCell<T>(T);
+
+impl<T> ValueCell<T> {
+    /// Create a cell with the given content.
+    pub fn new(x: T) -> Self {
+        ValueCell(RefCell::new(x))
+    }
+
+    /// Borrow the content for reading, see [`ValueCellLike::borrow`].
+    #[track_caller]
+    pub fn borrow(&self) -> Ref<'_, T> {
+        match self.0.try_borrow() {
+            Ok(x) => x,
+            Err(_) => panic!(
+                "content of a `{}` value read while it is being mutated",
+                std::any::type_name::<T>()
+            ),
+        }
+    }
+
+    /// Borrow the content for mutation, fails while the content is borrowed.
+    pub fn borrow_mut(&self) -> anyhow::Result<RefMut<'_, T>> {
+        self.0
+            .try_borrow_mut()
+            .map_err(|_| ValueError::MutationDuringIteration.into())
+    }
+
+    /// Mutable access to the content without a dynamic check, e.g. while tracing.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut()
+    }
+
+    /// The content.
+    pub fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+}
+
+impl<T> FrozenValueCell<T> {
+    /// Create a frozen cell with the given content.
+    pub fn new(x: T) -> Self {
+        FrozenValueCell(x)
+    }
+
+    /// The content.
+    pub fn get(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> ValueCellLike<T> for ValueCell<T> {
+    #[track_caller]
+    fn borrow(&self) -> ARef<'_, T> {
+        ARef::new_ref(ValueCell::borrow(self))
+    }
+
+    fn borrow_mut(&self) -> anyhow::Result<RefMut<'_, T>> {
+        ValueCell::borrow_mut(self)
+    }
+}
+
+impl<T, F: Coerce<T>> ValueCellLike<T> for FrozenValueCell<F> {
+    fn borrow(&self) -> ARef<'_, T> {
+        ARef::new_ptr(coerce(&self.0))
+    }
+
+    fn borrow_mut(&self) -> anyhow::Result<RefMut<'_, T>> {
+        Err(ValueError::CannotMutateImmutableValue.into())
+    }
+}
+
+impl<T: Debug> Debug for ValueCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.try_borrow() {
+            Ok(x) => Debug::fmt(&*x, f),
+            Err(_) => f.write_str("<mutating>"),
+        }
+    }
+}
+
+impl<T: Debug> Debug for FrozenValueCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.0, f)
+    }
+}
+
+unsafe impl<'v, T: Trace<'v>> Trace<'v> for ValueCell<T> {
+    fn trace(&mut self, tracer: &Tracer<'v>) {
+        self.get_mut().trace(tracer)
+    }
+}
+
+impl<T: Freeze> Freeze for ValueCell<T> {
+    type Frozen = FrozenValueCell<T::Frozen>;
+
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        Ok(FrozenValueCell(self.into_inner().freeze(freezer)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+    use std::fmt::Debug;
+    use std::fmt::Display;
+
+    use allocative::Allocative;
+
+    use crate as starlark;
+    use crate::assert::Assert;
+    use crate::environment::GlobalsBuilder;
+    use crate::values::cell::FrozenValueCell;
+    use crate::values::cell::ValueCell;
+    use crate::values::cell::ValueCellLike;
+    use crate::values::Freeze;
+    use crate::values::Freezer;
+    use crate::values::FrozenValue;
+    use crate::values::Heap;
+    use crate::values::NoSerialize;
+    use crate::values::ProvidesStaticType;
+    use crate::values::StarlarkValue;
+    use crate::values::Trace;
+    use crate::values::Value;
+
+    /// Fixed number of mutable slots.
+    #[derive(Debug, Trace, ProvidesStaticType, NoSerialize, Allocative)]
+    struct SlotsGen<C>(C);
+
+    impl<C> Display for SlotsGen<C> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "slots()")
+        }
+    }
+
+    impl<'v> Freeze for SlotsGen<ValueCell<Vec<Value<'v>>>> {
+        type Frozen = SlotsGen<FrozenValueCell<Vec<FrozenValue>>>;
+
+        fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+            Ok(SlotsGen(self.0.freeze(freezer)?))
+        }
+    }
+
+    impl<'v, C> StarlarkValue<'v> for SlotsGen<C>
+    where
+        C: ValueCellLike<Vec<Value<'v>>> + Debug + Allocative + 'v,
+        Self: ProvidesStaticType,
+    {
+        starlark_type!("slots");
+
+        fn length(&self) -> anyhow::Result<i32> {
+            Ok(self.0.borrow().len() as i32)
+        }
+
+        fn at(&self, index: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+            let i = index.to_int()? as usize;
+            Ok(self.0.borrow()[i])
+        }
+
+        fn set_at(&self, index: Value<'v>, new_value: Value<'v>) -> anyhow::Result<()> {
+            let i = index.to_int()? as usize;
+            self.0.borrow_mut()?[i] = new_value;
+            Ok(())
+        }
+
+        fn with_iterator(
+            &self,
+            _heap: &'v Heap,
+            f: &mut dyn FnMut(&mut dyn Iterator<Item = Value<'v>>) -> anyhow::Result<()>,
+        ) -> anyhow::Result<()> {
+            f(&mut self.0.borrow().iter().copied())
+        }
+    }
+
+    #[starlark_module]
+    fn slots_global(builder: &mut GlobalsBuilder) {
+        fn slots<'v>(n: i32, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+            let slots = vec![Value::new_none(); n as usize];
+            Ok(heap.alloc_complex(SlotsGen(ValueCell::new(slots))))
+        }
+    }
+
+    #[test]
+    fn test_value_cell() {
+        let mut a = Assert::new();
+        a.globals_add(slots_global);
+        a.module("m", "FROZEN = slots(2)\nFROZEN[0] = 'x'");
+        a.pass(
+            r#"
+xs = slots(2)
+xs[1] = [1]
+xs[1].append(2)
+assert_eq([None, [1, 2]], list(xs))
+load("m", "FROZEN")
+assert_eq(["x", None], list(FROZEN))
+"#,
+        );
+        a.fail("load('m', 'FROZEN')\nFROZEN[1] = 1", "Immutable");
+        a.fail(
+            "xs = slots(1)\nfor x in xs:\n    xs[0] = 1",
+            "mutate an iterable",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "read while it is being mutated")]
+    fn test_value_cell_read_while_mutating() {
+        let cell = ValueCell::new(1);
+        let _mutating = cell.borrow_mut().unwrap();
+        cell.borrow();
+    }
+}
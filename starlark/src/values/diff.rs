@@ -0,0 +1,248 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Structural differences between values.
+
+use std::fmt;
+use std::fmt::Display;
+
+use crate::values::dict::DictRef;
+use crate::values::list::ListRef;
+use crate::values::record::Record;
+use crate::values::structs::StructRef;
+use crate::values::tuple::TupleRef;
+use crate::values::Value;
+use crate::values::ValueIdentity;
+
+/// A step of the path from a compared value to a difference inside it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize)]
+pub enum DiffPathItem {
+    /// Module binding with this name.
+    Binding(String),
+    /// Element of a list or a tuple.
+    Index(usize),
+    /// Dict entry, with the `repr` of the key.
+    Key(String),
+    /// Field of a struct or a record.
+    Field(String),
+}
+
+/// A value at one side of a difference.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct DiffValue {
+    /// Starlark type of the value, e.g. `int`.
+    pub type_name: String,
+    /// `repr` of the value.
+    pub repr: String,
+}
+
+impl DiffValue {
+    fn new(value: Value) -> DiffValue {
+        DiffValue {
+            type_name: value.get_type().to_owned(),
+            repr: value.to_repr(),
+        }
+    }
+}
+
+/// How a value differs.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub enum ValueChange {
+    /// Only in the new value.
+    Added(DiffValue),
+    /// Only in the old value.
+    Removed(DiffValue),
+    /// Old and new values, which differ in type or `repr`.
+    Changed(DiffValue, DiffValue),
+}
+
+/// A difference between two values, produced by [`Value::diff`] or
+/// [`FrozenModule::diff`](crate::environment::FrozenModule::diff).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ValueDiff {
+    /// Path to the difference from the compared values, empty if they differ as a whole.
+    pub path: Vec<DiffPathItem>,
+    /// The difference.
+    pub change: ValueChange,
+}
+
+impl Display for ValueDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "(value)")?;
+        }
+        for x in &self.path {
+            match x {
+                DiffPathItem::Binding(x) => write!(f, "{}", x)?,
+                DiffPathItem::Index(x) => write!(f, "[{}]", x)?,
+                DiffPathItem::Key(x) => write!(f, "[{}]", x)?,
+                DiffPathItem::Field(x) => write!(f, ".{}", x)?,
+            }
+        }
+        match &self.change {
+            ValueChange::Added(x) => write!(f, ": added {}", x.repr),
+            ValueChange::Removed(x) => write!(f, ": removed {}", x.repr),
+            ValueChange::Changed(old, new) if old.type_name != new.type_name => write!(
+                f,
+                ": {} ({}) -> {} ({})",
+                old.repr, old.type_name, new.repr, new.type_name
+            ),
+            ValueChange::Changed(old, new) => write!(f, ": {} -> {}", old.repr, new.repr),
+        }
+    }
+}
+
+/// Compares values recursively, collecting the differences.
+#[derive(Default)]
+pub(crate) struct Differ<'v> {
+    path: Vec<DiffPathItem>,
+    /// Pairs of containers being compared, to stop at cycles.
+    stack: Vec<(ValueIdentity<'v>, ValueIdentity<'v>)>,
+    diffs: Vec<ValueDiff>,
+}
+
+impl<'v> Differ<'v> {
+    fn push(&mut self, change: ValueChange) {
+        self.diffs.push(ValueDiff {
+            path: self.path.clone(),
+            change,
+        });
+    }
+
+    pub(crate) fn added(&mut self, item: DiffPathItem, new: Value<'v>) {
+        self.path.push(item);
+        self.push(ValueChange::Added(DiffValue::new(new)));
+        self.path.pop();
+    }
+
+    pub(crate) fn removed(&mut self, item: DiffPathItem, old: Value<'v>) {
+        self.path.push(item);
+        self.push(ValueChange::Removed(DiffValue::new(old)));
+        self.path.pop();
+    }
+
+    pub(crate) fn diff_at(&mut self, item: DiffPathItem, old: Value<'v>, new: Value<'v>) {
+        self.path.push(item);
+        self.diff(old, new);
+        self.path.pop();
+    }
+
+    pub(crate) fn diff(&mut self, old: Value<'v>, new: Value<'v>) {
+        if old.ptr_eq(new) {
+            return;
+        }
+        let pair = (old.identity(), new.identity());
+        if self.stack.contains(&pair) {
+            // A cycle, the differences are reported by the outer comparison.
+            return;
+        }
+        if old.get_type() == new.get_type() {
+            self.stack.push(pair);
+            let compared = self.diff_elements(old, new);
+            self.stack.pop();
+            if compared {
+                return;
+            }
+        }
+        // Compare other values by `repr`, so that functions of different modules
+        // which are written the same are equal.
+        if old.get_type() != new.get_type() || old.to_repr() != new.to_repr() {
+            self.push(ValueChange::Changed(
+                DiffValue::new(old),
+                DiffValue::new(new),
+            ));
+        }
+    }
+
+    /// Compare the elements of containers of the same type,
+    /// returns `false` if the values are not containers.
+    fn diff_elements(&mut self, old: Value<'v>, new: Value<'v>) -> bool {
+        if let (Some(old), Some(new)) = (DictRef::from_value(old), DictRef::from_value(new)) {
+            for (k, v) in old.iter() {
+                match new.get(k) {
+                    Ok(Some(x)) => self.diff_at(DiffPathItem::Key(k.to_repr()), v, x),
+                    _ => self.removed(DiffPathItem::Key(k.to_repr()), v),
+                }
+            }
+            for (k, v) in new.iter() {
+                if !matches!(old.get(k), Ok(Some(_))) {
+                    self.added(DiffPathItem::Key(k.to_repr()), v);
+                }
+            }
+        } else if let (Some(old), Some(new)) = (ListRef::from_value(old), ListRef::from_value(new))
+        {
+            self.diff_slices(old.content(), new.content());
+        } else if let (Some(old), Some(new)) =
+            (TupleRef::from_value(old), TupleRef::from_value(new))
+        {
+            self.diff_slices(old.content(), new.content());
+        } else if let (Some(old), Some(new)) =
+            (StructRef::from_value(old), StructRef::from_value(new))
+        {
+            for (name, v) in old.iter() {
+                let field = DiffPathItem::Field(name.as_str().to_owned());
+                match new.iter().find(|(x, _)| *x == name) {
+                    Some((_, x)) => self.diff_at(field, v, x),
+                    None => self.removed(field, v),
+                }
+            }
+            for (name, v) in new.iter() {
+                if !old.iter().any(|(x, _)| x == name) {
+                    self.added(DiffPathItem::Field(name.as_str().to_owned()), v);
+                }
+            }
+        } else if let (Some(old), Some(new)) = (Record::from_value(old), Record::from_value(new)) {
+            // Records of different types are compared as a whole.
+            if !old.iter().map(|x| x.0).eq(new.iter().map(|x| x.0)) {
+                return false;
+            }
+            for ((name, old), (_, new)) in old.iter().zip(new.iter()) {
+                self.diff_at(DiffPathItem::Field(name.to_owned()), old, new);
+            }
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn diff_slices(&mut self, old: &[Value<'v>], new: &[Value<'v>]) {
+        for (i, (old, new)) in old.iter().zip(new).enumerate() {
+            self.diff_at(DiffPathItem::Index(i), *old, *new);
+        }
+        for (i, x) in old.iter().enumerate().skip(new.len()) {
+            self.removed(DiffPathItem::Index(i), *x);
+        }
+        for (i, x) in new.iter().enumerate().skip(old.len()) {
+            self.added(DiffPathItem::Index(i), *x);
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<ValueDiff> {
+        self.diffs
+    }
+}
+
+impl<'v> Value<'v> {
+    /// Structural differences from this value to `new`: dicts, lists, tuples, structs
+    /// and records of the same type are compared element by element, other values by
+    /// type and `repr`. Returns an empty list if the values are the same.
+    pub fn diff(self, new: Value<'v>) -> Vec<ValueDiff> {
+        let mut differ = Differ::default();
+        differ.diff(self, new);
+        differ.finish()
+    }
+}
@@ -20,6 +20,7 @@ use std::any::TypeId;
 use std::cmp;
 use std::fmt::Debug;
 use std::mem;
+use std::sync::Arc;
 
 use allocative::Allocative;
 use derive_more::Display;
@@ -44,12 +45,14 @@ use crate::values::layout::heap::repr::AValueForward;
 use crate::values::layout::heap::repr::AValueHeader;
 use crate::values::layout::heap::repr::AValueRepr;
 use crate::values::layout::heap::repr::ForwardPtr;
+use crate::values::layout::str_page::StarlarkStrPage;
 use crate::values::layout::vtable::AValueDyn;
 use crate::values::layout::vtable::AValueVTable;
 use crate::values::list::value::ListGen;
 use crate::values::none::NoneType;
 use crate::values::num::Num;
 use crate::values::string::StarlarkStr;
+use crate::values::string::StarlarkStrExternalKind;
 use crate::values::traits::StarlarkValueDyn;
 use crate::values::types::any_array::AnyArray;
 use crate::values::types::array::Array;
@@ -184,6 +187,17 @@ pub(crate) fn starlark_str<'v>(
     AValueImpl(Direct, unsafe { StarlarkStr::new(len, hash) })
 }
 
+pub(crate) fn starlark_str_external<'v>(
+    len: usize,
+    hash: StarlarkHashValue,
+) -> impl AValue<'v, ExtraElem = usize> + Send + Sync {
+    AValueImpl(Direct, unsafe { StarlarkStr::new_external(len, hash) })
+}
+
+pub(crate) fn str_page_avalue<'v>(len: usize) -> impl AValue<'v, ExtraElem = usize> + Send + Sync {
+    AValueImpl(Direct, unsafe { StarlarkStrPage::new(len) })
+}
+
 pub(crate) fn tuple_avalue<'v>(len: usize) -> impl AValue<'v, ExtraElem = Value<'v>> {
     AValueImpl(Direct, unsafe { Tuple::new(len) })
 }
@@ -346,7 +360,7 @@ impl<'v> AValue<'v> for AValueImpl<Direct, StarlarkStr> {
     type ExtraElem = usize;
 
     fn extra_len(&self) -> usize {
-        StarlarkStr::payload_len_for_len(self.1.len())
+        self.1.payload_len()
     }
 
     fn offset_of_extra() -> usize {
@@ -365,7 +379,22 @@ impl<'v> AValue<'v> for AValueImpl<Direct, StarlarkStr> {
         );
 
         let s = (*me).payload.1.as_str();
-        let fv = freezer.alloc(s);
+        let fv = match (*me).payload.1.external_kind() {
+            Some(StarlarkStrExternalKind::Static) => freezer
+                .heap
+                .alloc_str_static(&*(s as *const str))
+                .to_frozen_value(),
+            Some(StarlarkStrExternalKind::Arc) => {
+                // The unfrozen heap holds a strong reference, take another one for the frozen heap.
+                Arc::increment_strong_count(s as *const str);
+                freezer
+                    .heap
+                    .alloc_str_arc(Arc::from_raw(s as *const str))
+                    .to_frozen_value()
+            }
+            // Borrowed strings are only guaranteed to outlive the unfrozen heap.
+            Some(StarlarkStrExternalKind::Borrowed) | None => freezer.alloc(s),
+        };
         debug_assert!(fv.is_str());
         AValueHeader::overwrite_with_forward::<Self>(me, ForwardPtr::new(fv.0.raw().ptr_value()));
         Ok(fv)
@@ -378,7 +407,11 @@ impl<'v> AValue<'v> for AValueImpl<Direct, StarlarkStr> {
         );
 
         let s = (*me).payload.1.as_str();
-        let v = tracer.alloc_str(s);
+        let v = match (*me).payload.1.external_kind() {
+            // Owner of the external body is unchanged by GC, so keep pointing at it.
+            Some(kind) => tracer.alloc_str_external(s, kind),
+            None => tracer.alloc_str(s),
+        };
         debug_assert!(v.is_str());
         AValueHeader::overwrite_with_forward::<Self>(
             me,
@@ -392,6 +425,31 @@ impl<'v> AValue<'v> for AValueImpl<Direct, StarlarkStr> {
     }
 }
 
+impl<'v> AValue<'v> for AValueImpl<Direct, StarlarkStrPage> {
+    type StarlarkValue = StarlarkStrPage;
+
+    type ExtraElem = usize;
+
+    fn extra_len(&self) -> usize {
+        self.1.len()
+    }
+
+    fn offset_of_extra() -> usize {
+        StarlarkStrPage::offset_of_content()
+    }
+
+    unsafe fn heap_freeze(
+        _me: *mut AValueRepr<Self>,
+        _freezer: &Freezer,
+    ) -> anyhow::Result<FrozenValue> {
+        panic!("string pages are only allocated on frozen heap")
+    }
+
+    unsafe fn heap_copy(_me: *mut AValueRepr<Self>, _tracer: &Tracer<'v>) -> Value<'v> {
+        panic!("string pages are only allocated on frozen heap")
+    }
+}
+
 impl<'v> AValue<'v> for AValueImpl<Direct, Tuple<'v>> {
     type StarlarkValue = Tuple<'v>;
 
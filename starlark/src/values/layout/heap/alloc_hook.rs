@@ -0,0 +1,187 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Callbacks fired when a heap crosses allocation thresholds.
+
+use std::cell::Cell;
+use std::cell::RefCell;
+
+/// Summary passed to a heap allocation hook,
+/// see [`Heap::set_allocation_hook`](crate::values::Heap::set_allocation_hook).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapAllocationStats {
+    /// The largest multiple of the hook step crossed by this allocation.
+    pub threshold_bytes: usize,
+    /// Bytes currently allocated by the heap, as reported by `allocated_bytes`.
+    pub allocated_bytes: usize,
+    /// Bytes allocated by the heap since it was created,
+    /// including memory since released by garbage collection.
+    pub total_allocated_bytes: usize,
+}
+
+pub(crate) struct AllocHook {
+    /// Fire the callback every `step` bytes.
+    step: Cell<usize>,
+    /// Next threshold to cross.
+    next: Cell<usize>,
+    /// Bytes allocated in arenas discarded by garbage collection.
+    collected: Cell<usize>,
+    callback: RefCell<Box<dyn FnMut(&HeapAllocationStats) + Send>>,
+}
+
+impl AllocHook {
+    pub(crate) fn new() -> AllocHook {
+        AllocHook {
+            step: Cell::new(usize::MAX),
+            next: Cell::new(usize::MAX),
+            collected: Cell::new(0),
+            callback: RefCell::new(Box::new(|_| {})),
+        }
+    }
+
+    /// Replace the callback, counting thresholds from the current total.
+    pub(crate) fn set(
+        &self,
+        step: usize,
+        allocated: usize,
+        callback: Box<dyn FnMut(&HeapAllocationStats) + Send>,
+    ) {
+        assert!(step > 0, "allocation hook step must be positive");
+        *self.callback.borrow_mut() = callback;
+        self.step.set(step);
+        self.next
+            .set(self.next_threshold(self.collected.get() + allocated));
+    }
+
+    /// Record bytes released when an arena was replaced by garbage collection.
+    pub(crate) fn collected(&self, bytes: usize) {
+        self.collected.set(self.collected.get() + bytes);
+    }
+
+    fn next_threshold(&self, total: usize) -> usize {
+        let step = self.step.get();
+        (total / step)
+            .checked_add(1)
+            .and_then(|n| n.checked_mul(step))
+            .unwrap_or(usize::MAX)
+    }
+
+    #[inline]
+    pub(crate) fn after_alloc(&self, allocated: usize) {
+        let total = self.collected.get() + allocated;
+        if total >= self.next.get() {
+            self.fire(allocated, total);
+        }
+    }
+
+    #[cold]
+    fn fire(&self, allocated: usize, total: usize) {
+        // Several thresholds may be crossed by one large chunk, report the last one.
+        let threshold = total / self.step.get() * self.step.get();
+        self.next.set(self.next_threshold(total));
+        // The callback cannot reach the heap, but be defensive about reentrancy.
+        if let Ok(mut callback) = self.callback.try_borrow_mut() {
+            callback(&HeapAllocationStats {
+                threshold_bytes: threshold,
+                allocated_bytes: allocated,
+                total_allocated_bytes: total,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use dupe::Dupe;
+
+    use crate::values::FrozenHeap;
+    use crate::values::Heap;
+    use crate::values::HeapAllocationStats;
+
+    fn recorder() -> (
+        Arc<Mutex<Vec<HeapAllocationStats>>>,
+        impl FnMut(&HeapAllocationStats) + Send + 'static,
+    ) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let log2 = log.dupe();
+        (log, move |s: &HeapAllocationStats| {
+            log2.lock().unwrap().push(*s)
+        })
+    }
+
+    fn check_thresholds(log: &[HeapAllocationStats], step: usize) {
+        assert!(!log.is_empty());
+        let mut prev = 0;
+        for s in log {
+            assert_eq!(0, s.threshold_bytes % step);
+            assert!(s.threshold_bytes > prev);
+            assert!(s.total_allocated_bytes >= s.threshold_bytes);
+            prev = s.threshold_bytes;
+        }
+    }
+
+    #[test]
+    fn test_heap_allocation_hook() {
+        let heap = Heap::new();
+        let (log, callback) = recorder();
+        heap.set_allocation_hook(16 * 1024, callback);
+        for i in 0..10000 {
+            heap.alloc(format!("string number {}", i));
+        }
+        let log = log.lock().unwrap();
+        check_thresholds(&log, 16 * 1024);
+        assert!(log.last().unwrap().total_allocated_bytes <= heap.allocated_bytes());
+    }
+
+    #[test]
+    fn test_heap_allocation_hook_counts_collected() {
+        let heap = Heap::new();
+        let (log, callback) = recorder();
+        heap.set_allocation_hook(16 * 1024, callback);
+        for _ in 0..3 {
+            for i in 0..10000 {
+                heap.alloc(format!("string number {}", i));
+            }
+            // Nothing is rooted, so everything is released.
+            unsafe { heap.garbage_collect(|_| {}) };
+        }
+        let log = log.lock().unwrap();
+        check_thresholds(&log, 16 * 1024);
+        let last = log.last().unwrap();
+        assert!(last.total_allocated_bytes > 2 * last.allocated_bytes);
+    }
+
+    #[test]
+    fn test_frozen_heap_allocation_hook() {
+        let heap = FrozenHeap::new();
+        let (log, callback) = recorder();
+        heap.set_allocation_hook(4 * 1024, callback);
+        for i in 0..1000 {
+            heap.alloc(format!("string number {}", i));
+        }
+        let fired = log.lock().unwrap().len();
+        assert!(fired > 0);
+        check_thresholds(&log.lock().unwrap(), 4 * 1024);
+        // The hook is not carried over into the frozen heap reference.
+        let heap_ref = heap.into_ref();
+        assert!(heap_ref.allocated_bytes() > 0);
+        assert_eq!(1, Arc::strong_count(&log));
+    }
+}
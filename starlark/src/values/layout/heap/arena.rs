@@ -41,12 +41,15 @@ use allocative::Visitor;
 use bumpalo::Bump;
 use dupe::Dupe;
 use either::Either;
+use once_cell::unsync::OnceCell;
 use starlark_map::small_map::SmallMap;
 
 use crate::collections::StarlarkHashValue;
 use crate::values::layout::avalue::starlark_str;
+use crate::values::layout::avalue::starlark_str_external;
 use crate::values::layout::avalue::AValue;
 use crate::values::layout::avalue::BlackHole;
+use crate::values::layout::heap::alloc_hook::AllocHook;
 use crate::values::layout::heap::call_enter_exit::CallEnter;
 use crate::values::layout::heap::call_enter_exit::CallExit;
 use crate::values::layout::heap::call_enter_exit::NeedsDrop;
@@ -60,6 +63,7 @@ use crate::values::layout::heap::repr::AValueOrForward;
 use crate::values::layout::heap::repr::AValueRepr;
 use crate::values::layout::vtable::AValueVTable;
 use crate::values::string::StarlarkStr;
+use crate::values::string::StarlarkStrExternalKind;
 use crate::values::Value;
 use crate::values::ValueLike;
 
@@ -82,6 +86,8 @@ pub(crate) struct Arena {
     non_drop: Bump,
     /// Arena for things which might need dropping (e.g. Vec, with memory on heap)
     drop: Bump,
+    /// Callback fired when allocations cross a threshold, see `alloc_hook.rs`.
+    hook: OnceCell<Box<AllocHook>>,
 }
 
 /// Reservation is morally a Reservation<T>, but we treat is as an
@@ -156,6 +162,26 @@ impl Arena {
         self.drop.chunk_capacity() + self.non_drop.chunk_capacity()
     }
 
+    /// Allocation hook of this arena, created on first use.
+    pub(crate) fn alloc_hook(&self) -> &AllocHook {
+        self.hook.get_or_init(|| Box::new(AllocHook::new()))
+    }
+
+    pub(crate) fn take_alloc_hook(&mut self) -> Option<Box<AllocHook>> {
+        self.hook.take()
+    }
+
+    pub(crate) fn set_alloc_hook(&mut self, hook: Box<AllocHook>) {
+        self.hook = OnceCell::with_value(hook);
+    }
+
+    #[inline]
+    fn after_alloc(&self) {
+        if let Some(hook) = self.hook.get() {
+            hook.after_alloc(self.allocated_bytes());
+        }
+    }
+
     fn alloc_uninit<'v, 'v2: 'v, T: AValue<'v2>>(
         bump: &'v Bump,
         extra_len: usize,
@@ -210,6 +236,7 @@ impl Arena {
         assert!(!T::IS_STR);
 
         let (p, extra) = Self::alloc_uninit::<T>(self.bump_for_type::<T>(), extra_len);
+        self.after_alloc();
         // If we don't have a vtable we can't skip over missing elements to drop,
         // so very important to put in a current vtable
         // We always alloc at least one pointer worth of space, so can write in a one-ST blackhole
@@ -246,6 +273,7 @@ impl Arena {
         let bump = self.bump_for_type::<T>();
         let (p, extra) = Self::alloc_uninit::<T>(bump, 0);
         debug_assert!(extra.is_empty());
+        self.after_alloc();
         p.write(AValueRepr {
             header: AValueHeader::new::<T>(),
             payload: x,
@@ -261,6 +289,7 @@ impl Arena {
     ) -> (*mut AValueRepr<T>, &'v mut [MaybeUninit<T::ExtraElem>]) {
         let bump = self.bump_for_type::<T>();
         let (p, extra) = Self::alloc_uninit::<T>(bump, x.extra_len());
+        self.after_alloc();
         let p = p.write(AValueRepr {
             header: AValueHeader::new::<T>(),
             payload: x,
@@ -292,6 +321,24 @@ impl Arena {
         })
     }
 
+    /// Allocate a string header pointing at a body stored outside of the arena.
+    ///
+    /// The caller is responsible for keeping the body alive as long as the arena.
+    #[inline]
+    pub(crate) unsafe fn alloc_str_external(
+        &self,
+        x: &str,
+        kind: StarlarkStrExternalKind,
+    ) -> *mut AValueHeader {
+        assert!(x.len() > 1);
+        let (v, extra) =
+            self.alloc_extra::<_>(starlark_str_external(x.len(), StarlarkStr::UNINIT_HASH));
+        debug_assert_eq!(StarlarkStr::EXTERNAL_PAYLOAD_LEN, extra.len());
+        extra[0].write(x.as_ptr() as usize);
+        extra[1].write(kind as usize);
+        &mut (*v).header
+    }
+
     fn iter_chunk<'a>(chunk: &'a [MaybeUninit<u8>]) -> ChunkIter<'a> {
         ChunkIter { chunk }
     }
@@ -441,7 +488,11 @@ impl Drop for Arena {
 
 impl Allocative for Arena {
     fn visit<'a, 'b: 'a>(&self, visitor: &'a mut allocative::Visitor<'b>) {
-        let Arena { drop, non_drop } = self;
+        let Arena {
+            drop,
+            non_drop,
+            hook: _,
+        } = self;
 
         fn visit_bump<'a, 'b: 'a>(bump: &Bump, visitor: &'a mut Visitor<'b>) {
             let mut visitor =
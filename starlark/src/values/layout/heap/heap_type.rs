@@ -47,6 +47,10 @@ use crate::collections::maybe_uninit_backport::maybe_uninit_write_slice_cloned;
 use crate::collections::Hashed;
 use crate::collections::StarlarkHashValue;
 use crate::eval::compiler::def::FrozenDef;
+use crate::eval::Arguments;
+use crate::eval::Evaluator;
+use crate::eval::ParametersParser;
+use crate::eval::ParametersSpec;
 use crate::values::any::StarlarkAny;
 use crate::values::array::Array;
 use crate::values::layout::avalue::any_array_avalue;
@@ -58,11 +62,13 @@ use crate::values::layout::avalue::frozen_list_avalue;
 use crate::values::layout::avalue::frozen_tuple_avalue;
 use crate::values::layout::avalue::list_avalue;
 use crate::values::layout::avalue::simple;
+use crate::values::layout::avalue::str_page_avalue;
 use crate::values::layout::avalue::tuple_avalue;
 use crate::values::layout::avalue::AValue;
 use crate::values::layout::avalue::VALUE_EMPTY_ARRAY;
 use crate::values::layout::avalue::VALUE_EMPTY_FROZEN_LIST;
 use crate::values::layout::avalue::VALUE_EMPTY_TUPLE;
+use crate::values::layout::heap::alloc_hook::HeapAllocationStats;
 use crate::values::layout::heap::arena::Arena;
 use crate::values::layout::heap::arena::ArenaVisitor;
 use crate::values::layout::heap::arena::Reservation;
@@ -75,12 +81,19 @@ use crate::values::layout::heap::maybe_uninit_slice_util::maybe_uninit_write_fro
 use crate::values::layout::heap::profile::by_type::HeapSummary;
 use crate::values::layout::heap::repr::AValueRepr;
 use crate::values::layout::static_string::constant_string;
+use crate::values::layout::str_page::StarlarkStrPage;
 use crate::values::layout::typed::string::StringValueLike;
 use crate::values::layout::value::FrozenValue;
 use crate::values::layout::value::Value;
 use crate::values::string::intern::interner::FrozenStringInterner;
 use crate::values::string::StarlarkStr;
+use crate::values::string::StarlarkStrExternalKind;
+use crate::values::types::external::ExternalRef;
+use crate::values::types::external::ExternalResource;
+use crate::values::types::external::ExternalSlot;
+use crate::values::types::stream::Stream;
 use crate::values::types::float::StarlarkFloat;
+use crate::values::types::function::NativeFunction;
 use crate::values::AllocFrozenValue;
 use crate::values::AllocValue;
 use crate::values::ComplexValue;
@@ -105,7 +118,24 @@ pub(crate) enum HeapKind {
 pub struct Heap {
     /// Peak memory seen when a garbage collection takes place (may be lower than currently allocated)
     peak_allocated: Cell<usize>,
+    /// Number of garbage collections performed. Value identities change on each.
+    gc_generation: Cell<u64>,
     arena: FastCell<Arena>,
+    /// Owners of externally stored string bodies.
+    str_owners: RefCell<Vec<Arc<str>>>,
+    /// Host resources referenced by values, in the order of allocation.
+    externals: RefCell<Vec<Arc<ExternalSlot>>>,
+}
+
+impl Drop for Heap {
+    fn drop(&mut self) {
+        // Resources which were frozen are owned by the frozen heap.
+        for x in self.externals.get_mut().drain(..).rev() {
+            if !x.is_frozen() {
+                x.release();
+            }
+        }
+    }
 }
 
 impl Debug for Heap {
@@ -129,6 +159,13 @@ pub struct FrozenHeap {
     refs: RefCell<SmallSet<FrozenHeapRef>>,
     /// String interner.
     str_interner: RefCell<FrozenStringInterner>,
+    /// Owners of externally stored string bodies.
+    str_owners: RefCell<Vec<Arc<str>>>,
+    /// Host resources referenced by values, in the order of freezing.
+    externals: RefCell<Vec<Arc<ExternalSlot>>>,
+    /// Free space in the current string page (see `StarlarkStrPage`):
+    /// address of the next free byte and of the end of the page.
+    str_page: Cell<(usize, usize)>,
 }
 
 /// `FrozenHeap` when it is no longer modified and can be share between threads.
@@ -138,6 +175,9 @@ pub struct FrozenHeap {
 struct FrozenFrozenHeap {
     arena: Arena,
     refs: SmallSet<FrozenHeapRef>,
+    str_owners: Vec<Arc<str>>,
+    #[allocative(skip)]
+    externals: Vec<Arc<ExternalSlot>>,
 }
 
 // Safe because we never mutate the Arena other than with &mut
@@ -146,8 +186,21 @@ unsafe impl Send for FrozenFrozenHeap {}
 
 impl FrozenFrozenHeap {
     fn is_empty(&self) -> bool {
-        let FrozenFrozenHeap { arena, refs } = self;
-        arena.is_empty() && refs.is_empty()
+        let FrozenFrozenHeap {
+            arena,
+            refs,
+            str_owners,
+            externals,
+        } = self;
+        arena.is_empty() && refs.is_empty() && str_owners.is_empty() && externals.is_empty()
+    }
+}
+
+impl Drop for FrozenFrozenHeap {
+    fn drop(&mut self) {
+        for x in self.externals.drain(..).rev() {
+            x.release();
+        }
     }
 }
 
@@ -237,12 +290,26 @@ impl FrozenHeap {
     /// [`FrozenHeapRef`] which can be [`clone`](Clone::clone)d, shared between threads,
     /// and ensures the underlying values allocated on the [`FrozenHeap`] remain valid.
     pub fn into_ref(self) -> FrozenHeapRef {
-        let FrozenHeap { arena, refs, .. } = self;
+        let FrozenHeap {
+            mut arena,
+            refs,
+            str_owners,
+            externals,
+            ..
+        } = self;
+        arena.take_alloc_hook();
         let refs = refs.into_inner();
-        if arena.is_empty() && refs.is_empty() {
+        let str_owners = str_owners.into_inner();
+        let externals = externals.into_inner();
+        if arena.is_empty() && refs.is_empty() && str_owners.is_empty() && externals.is_empty() {
             FrozenHeapRef::default()
         } else {
-            FrozenHeapRef(Arc::new(FrozenFrozenHeap { arena, refs }))
+            FrozenHeapRef(Arc::new(FrozenFrozenHeap {
+                arena,
+                refs,
+                str_owners,
+                externals,
+            }))
         }
     }
 
@@ -260,6 +327,11 @@ impl FrozenHeap {
         }
     }
 
+    /// Take the ownership of a frozen host resource.
+    pub(crate) fn add_external(&self, slot: Arc<ExternalSlot>) {
+        self.externals.borrow_mut().push(slot);
+    }
+
     fn alloc_raw(&self, x: impl AValue<'static, ExtraElem = ()> + Send + Sync) -> FrozenValue {
         let v: &AValueRepr<_> = self.arena.alloc(x);
         unsafe { FrozenValue::new_repr(cast::ptr_lifetime(v)) }
@@ -272,6 +344,10 @@ impl FrozenHeap {
         hash: StarlarkHashValue,
         init: impl FnOnce(*mut u8),
     ) -> FrozenStringValue {
+        if len <= StarlarkStrPage::MAX_STR_LEN {
+            return self.alloc_str_packed(len, hash, init);
+        }
+
         let v = self.arena.alloc_str_init(len, hash, init);
 
         unsafe {
@@ -280,6 +356,37 @@ impl FrozenHeap {
         }
     }
 
+    /// Allocate a short string in the current string page, starting a new page if it is full.
+    fn alloc_str_packed(
+        &self,
+        len: usize,
+        hash: StarlarkHashValue,
+        init: impl FnOnce(*mut u8),
+    ) -> FrozenStringValue {
+        assert!(len > 1);
+        let size = StarlarkStrPage::size_of_str(len);
+        let (mut next, mut end) = self.str_page.get();
+        if end - next < size {
+            let (_, extra) = self
+                .arena
+                .alloc_extra::<_>(str_page_avalue(StarlarkStrPage::LEN));
+            // Zero the page so that string bodies are padded with zeros.
+            for x in extra.iter_mut() {
+                x.write(0);
+            }
+            next = extra.as_mut_ptr() as usize;
+            end = next + mem::size_of_val(extra);
+        }
+        self.str_page.set((next + size, end));
+
+        unsafe {
+            ptr::write(next as *mut StarlarkStr, StarlarkStr::new(len, hash));
+            init((next + StarlarkStr::offset_of_content()) as *mut u8);
+            let value = FrozenValue::new_str_ptr_usize(StarlarkStrPage::str_ptr(next));
+            FrozenStringValue::new_unchecked(value)
+        }
+    }
+
     fn alloc_str_impl(&self, x: &str, hash: StarlarkHashValue) -> FrozenStringValue {
         if let Some(x) = constant_string(x) {
             x
@@ -296,6 +403,33 @@ impl FrozenHeap {
         self.alloc_str_impl(x, StarlarkStr::UNINIT_HASH)
     }
 
+    fn alloc_str_external(&self, x: &str, kind: StarlarkStrExternalKind) -> FrozenStringValue {
+        if let Some(x) = constant_string(x) {
+            x
+        } else {
+            unsafe {
+                let v = self.arena.alloc_str_external(x, kind);
+                FrozenStringValue::new_unchecked(FrozenValue::new_ptr(&*v, true))
+            }
+        }
+    }
+
+    /// Allocate a string on this heap without copying it,
+    /// the string body stays in static memory.
+    pub fn alloc_str_static(&self, x: &'static str) -> FrozenStringValue {
+        self.alloc_str_external(x, StarlarkStrExternalKind::Static)
+    }
+
+    /// Allocate a string on this heap without copying it.
+    /// The heap keeps the [`Arc`] alive until it is dropped.
+    pub fn alloc_str_arc(&self, x: Arc<str>) -> FrozenStringValue {
+        let v = self.alloc_str_external(&x, StarlarkStrExternalKind::Arc);
+        if constant_string(&x).is_none() {
+            self.str_owners.borrow_mut().push(x);
+        }
+        v
+    }
+
     /// Intern string.
     pub(crate) fn alloc_str_intern(&self, s: &str) -> FrozenStringValue {
         if let Some(s) = constant_string(s) {
@@ -478,6 +612,20 @@ impl FrozenHeap {
         self.arena.available_bytes()
     }
 
+    /// Call `callback` each time the number of bytes allocated on this heap
+    /// crosses a multiple of `every_bytes`, replacing any previous hook.
+    ///
+    /// The hook is dropped when the heap is converted into a [`FrozenHeapRef`].
+    pub fn set_allocation_hook(
+        &self,
+        every_bytes: usize,
+        callback: impl FnMut(&HeapAllocationStats) + Send + 'static,
+    ) {
+        self.arena
+            .alloc_hook()
+            .set(every_bytes, self.allocated_bytes(), Box::new(callback));
+    }
+
     /// Obtain a summary of how much memory is currently allocated by this heap.
     pub fn allocated_summary(&self) -> HeapSummary {
         self.arena.allocated_summary()
@@ -487,6 +635,11 @@ impl FrozenHeap {
     pub(crate) fn unused_capacity(&self) -> usize {
         self.arena.unused_capacity()
     }
+
+    /// Memory used for allocation of starlark values.
+    pub(crate) fn used_bytes(&self) -> usize {
+        self.allocated_bytes() - self.unused_capacity()
+    }
 }
 
 /// Used to `freeze` values by [`Freeze::freeze`](crate::values::Freeze::freeze).
@@ -586,6 +739,23 @@ impl Heap {
         self.arena.borrow().available_bytes()
     }
 
+    /// Call `callback` each time the number of bytes allocated on this heap
+    /// crosses a multiple of `every_bytes`, replacing any previous hook.
+    ///
+    /// Bytes released by garbage collection still count towards the total,
+    /// so long evaluations keep reporting progress without polling.
+    pub fn set_allocation_hook(
+        &self,
+        every_bytes: usize,
+        callback: impl FnMut(&HeapAllocationStats) + Send + 'static,
+    ) {
+        self.arena.borrow().alloc_hook().set(
+            every_bytes,
+            self.allocated_bytes(),
+            Box::new(callback),
+        );
+    }
+
     fn alloc_raw<'v, 'v2: 'v2>(&'v self, x: impl AValue<'v2, ExtraElem = ()>) -> Value<'v> {
         let arena = self.arena.borrow();
         let v: &AValueRepr<_> = arena.alloc(x);
@@ -634,6 +804,67 @@ impl Heap {
         }
     }
 
+    fn alloc_str_external<'v>(&'v self, x: &str, kind: StarlarkStrExternalKind) -> StringValue<'v> {
+        if let Some(x) = constant_string(x) {
+            x.to_string_value()
+        } else {
+            let arena = self.arena.borrow();
+            unsafe {
+                let v = arena.alloc_str_external(x, kind);
+                StringValue::new_unchecked(Value::new_ptr(&*v, true))
+            }
+        }
+    }
+
+    /// Allocate a string on the heap without copying it,
+    /// the string body stays in static memory.
+    pub fn alloc_str_static<'v>(&'v self, x: &'static str) -> StringValue<'v> {
+        self.alloc_str_external(x, StarlarkStrExternalKind::Static)
+    }
+
+    /// Allocate a string on the heap without copying it.
+    /// The heap keeps the [`Arc`] alive until it is dropped,
+    /// and freezing shares the [`Arc`] with the frozen heap.
+    pub fn alloc_str_arc<'v>(&'v self, x: Arc<str>) -> StringValue<'v> {
+        let v = self.alloc_str_external(&x, StarlarkStrExternalKind::Arc);
+        if constant_string(&x).is_none() {
+            self.str_owners.borrow_mut().push(x);
+        }
+        v
+    }
+
+    /// Allocate a value referencing a resource owned by the host, which is released
+    /// deterministically, see [`external`](crate::values::external) for the lifecycle.
+    pub fn alloc_external<'v>(&'v self, resource: impl ExternalResource) -> Value<'v> {
+        let slot = Arc::new(ExternalSlot::new(resource));
+        self.externals.borrow_mut().push(slot.dupe());
+        self.alloc_complex(ExternalRef::new(slot))
+    }
+
+    /// Allocate a value which yields the elements of the iterator lazily when iterated
+    /// in Starlark, see [`stream`](crate::values::stream). The value cannot be frozen.
+    pub fn alloc_stream<'v, I>(&'v self, iter: I) -> Value<'v>
+    where
+        I: Iterator + 'static,
+        I::Item: for<'x> AllocValue<'x>,
+    {
+        let mut iter = iter;
+        self.alloc_complex_no_freeze(Stream::new(Box::new(move |heap| {
+            iter.next().map(|x| heap.alloc(x))
+        })))
+    }
+
+    /// Allocate a string on the heap pointing at a buffer owned by the caller,
+    /// without copying it. The string is copied when the heap is frozen.
+    ///
+    /// # Safety
+    ///
+    /// The buffer must not be freed or modified while this heap
+    /// (or any value allocated on it) is alive.
+    pub unsafe fn alloc_str_borrowed<'v>(&'v self, x: &str) -> StringValue<'v> {
+        self.alloc_str_external(x, StarlarkStrExternalKind::Borrowed)
+    }
+
     /// Allocate a string on the heap, based on two concatenated strings.
     pub fn alloc_str_concat<'v>(&'v self, x: &str, y: &str) -> StringValue<'v> {
         if x.is_empty() {
@@ -797,6 +1028,45 @@ impl Heap {
         ValueTyped::new(self.alloc(x)).expect("just allocated value must have the right type")
     }
 
+    /// Allocate a native function implemented by a Rust closure.
+    ///
+    /// Unlike functions defined with [`#[starlark_module]`](macro@crate::starlark_module),
+    /// the closure can capture state at runtime, e.g. per-request data exposed to a script.
+    /// The closure is responsible for validating the arguments,
+    /// see [`alloc_callable_with_signature`](Heap::alloc_callable_with_signature)
+    /// for a version which does it.
+    pub fn alloc_callable<'v, F>(&'v self, name: &str, function: F) -> Value<'v>
+    where
+        F: for<'a> Fn(&mut Evaluator<'a, '_>, &Arguments<'a, '_>) -> anyhow::Result<Value<'a>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.alloc(NativeFunction::new_direct(function, name.to_owned()))
+    }
+
+    /// Allocate a native function implemented by a Rust closure,
+    /// with arguments parsed according to `signature`.
+    ///
+    /// The signature is also used to produce the documentation of the function.
+    pub fn alloc_callable_with_signature<'v, F>(
+        &'v self,
+        name: &str,
+        signature: ParametersSpec<FrozenValue>,
+        function: F,
+    ) -> Value<'v>
+    where
+        F: for<'a> Fn(
+                &mut Evaluator<'a, '_>,
+                ParametersParser<'a, '_>,
+            ) -> anyhow::Result<Value<'a>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.alloc(NativeFunction::new(function, name.to_owned(), signature))
+    }
+
     /// Allocate a value and return [`ValueOf`] of it.
     pub fn alloc_value_of<'v, T>(&'v self, x: T) -> ValueOf<'v, &'v T>
     where
@@ -823,21 +1093,53 @@ impl Heap {
     pub(crate) unsafe fn garbage_collect<'v>(&'v self, f: impl FnOnce(&Tracer<'v>)) {
         // Record the highest peak, so it never decreases
         self.peak_allocated.set(self.peak_allocated_bytes());
-        self.garbage_collect_internal(f)
+        self.garbage_collect_internal(f);
+        self.release_unreachable_externals();
+    }
+
+    /// Release the resources which are no longer referenced by a value after a garbage
+    /// collection, and forget those which were frozen.
+    fn release_unreachable_externals(&self) {
+        let mut unreachable = Vec::new();
+        self.externals.borrow_mut().retain(|x| {
+            if x.is_frozen() {
+                false
+            } else if Arc::strong_count(x) == 1 {
+                unreachable.push(x.dupe());
+                false
+            } else {
+                true
+            }
+        });
+        for x in unreachable.into_iter().rev() {
+            x.release();
+        }
     }
 
     unsafe fn garbage_collect_internal<'v>(&'v self, f: impl FnOnce(&Tracer<'v>)) {
         // Must rewrite all Value's so they point at the new heap.
         // Take the arena out of the heap to make sure nobody allocates in it,
         // but hold the reference until the GC is done.
-        let _arena = self.arena.take();
+        let mut old_arena = self.arena.take();
 
+        let gc_generation = self.gc_generation.get() + 1;
+        self.gc_generation.set(gc_generation);
         let tracer = Tracer::<'v> {
             arena: Arena::default(),
+            gc_generation,
             phantom: PhantomData,
         };
         f(&tracer);
-        self.arena.set(tracer.arena);
+        let mut arena = tracer.arena;
+        if let Some(hook) = old_arena.take_alloc_hook() {
+            hook.collected(
+                old_arena
+                    .allocated_bytes()
+                    .saturating_sub(arena.allocated_bytes()),
+            );
+            arena.set_alloc_hook(hook);
+        }
+        self.arena.set(arena);
     }
 
     /// Obtain a summary of how much memory is currently allocated by this heap.
@@ -845,6 +1147,11 @@ impl Heap {
         self.arena.borrow().allocated_summary()
     }
 
+    /// Number of garbage collections performed on this heap.
+    pub(crate) fn gc_generation(&self) -> u64 {
+        self.gc_generation.get()
+    }
+
     pub(crate) fn record_call_enter<'v>(&'v self, function: Value<'v>) {
         let time = Instant::now();
         assert!(mem::needs_drop::<CallEnter<NeedsDrop>>());
@@ -884,6 +1191,8 @@ impl Heap {
 /// Used to perform garbage collection by [`Trace::trace`](crate::values::Trace::trace).
 pub struct Tracer<'v> {
     arena: Arena,
+    /// Generation of the heap after this garbage collection.
+    gc_generation: u64,
     phantom: PhantomData<&'v ()>,
 }
 
@@ -901,6 +1210,10 @@ impl<'v> Tracer<'v> {
         let _ = value;
     }
 
+    pub(crate) fn gc_generation(&self) -> u64 {
+        self.gc_generation
+    }
+
     pub(crate) fn reserve<'a, 'v2: 'v + 'a, T: AValue<'v2, ExtraElem = ()>>(
         &'a self,
     ) -> (Value<'v>, Reservation<'a, 'v2, T>) {
@@ -928,6 +1241,15 @@ impl<'v> Tracer<'v> {
         unsafe { Value::new_ptr(&*v, true) }
     }
 
+    pub(crate) unsafe fn alloc_str_external(
+        &self,
+        x: &str,
+        kind: StarlarkStrExternalKind,
+    ) -> Value<'v> {
+        let v = self.arena.alloc_str_external(x, kind);
+        Value::new_ptr(&*v, true)
+    }
+
     fn adjust(&self, value: Value<'v>) -> Value<'v> {
         // Case 1, doesn't point at the old arena
         if !value.0.is_unfrozen() {
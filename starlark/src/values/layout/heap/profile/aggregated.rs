@@ -41,6 +41,7 @@ use crate::values::layout::heap::arena::ArenaVisitor;
 use crate::values::layout::heap::heap_type::HeapKind;
 use crate::values::layout::heap::profile::alloc_counts::AllocCounts;
 use crate::values::layout::heap::profile::arc_str::ArcStr;
+use crate::values::layout::heap::profile::by_module::RetainedByBinding;
 use crate::values::layout::heap::profile::by_type::HeapSummary;
 use crate::values::layout::heap::profile::string_index::StringId;
 use crate::values::layout::heap::profile::string_index::StringIndex;
@@ -410,6 +411,7 @@ impl AggregateHeapProfileInfo {
 pub(crate) struct RetainedHeapProfile {
     pub(crate) info: AggregateHeapProfileInfo,
     pub(crate) mode: RetainedHeapProfileMode,
+    pub(crate) by_binding: RetainedByBinding,
 }
 
 impl RetainedHeapProfile {
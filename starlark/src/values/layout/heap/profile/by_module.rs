@@ -0,0 +1,143 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Memory retained by frozen modules, by module and by binding.
+
+use allocative::Allocative;
+use starlark_map::small_map::SmallMap;
+
+use crate::environment::FrozenModule;
+use crate::eval::runtime::profile::csv::CsvWriter;
+
+/// Name of the row for memory of a module not retained by any binding.
+const OTHER: &str = "(other)";
+
+/// Memory retained by the bindings of a module, recorded when the module is frozen.
+#[derive(Debug, Default, Clone, Allocative)]
+pub(crate) struct RetainedByBinding {
+    /// Bytes allocated in the frozen heap when freezing each binding.
+    pub(crate) bindings: SmallMap<String, usize>,
+    /// Bytes allocated in the frozen heap not when freezing a binding,
+    /// for example constants allocated when the module was compiled.
+    pub(crate) other: usize,
+}
+
+impl RetainedByBinding {
+    fn total(&self) -> usize {
+        self.bindings.values().sum::<usize>() + self.other
+    }
+
+    fn merge(&mut self, other: &RetainedByBinding) {
+        for (name, bytes) in &other.bindings {
+            *self.bindings.entry(name.clone()).or_default() += bytes;
+        }
+        self.other += other.other;
+    }
+
+    /// Bindings and their bytes, largest first.
+    fn sorted(&self) -> Vec<(&str, usize)> {
+        let mut bindings: Vec<(&str, usize)> = self
+            .bindings
+            .iter()
+            .map(|(name, bytes)| (name.as_str(), *bytes))
+            .chain((self.other != 0).then_some((OTHER, self.other)))
+            .collect();
+        bindings.sort_by_key(|(_, bytes)| usize::MAX - bytes);
+        bindings
+    }
+}
+
+/// Memory retained by frozen modules, by module and by the binding which retains it,
+/// to find the modules which are responsible for the memory use of a program.
+///
+/// The modules must be frozen with [`ProfileMode::HeapSummaryRetained`](crate::eval::ProfileMode)
+/// or [`ProfileMode::HeapFlameRetained`](crate::eval::ProfileMode) enabled.
+///
+/// Bindings are frozen in the order they are defined,
+/// and a value reachable from several bindings is attributed to the first of them.
+/// Values from loaded modules are attributed to the module which defines them.
+#[derive(Debug, Default, Clone)]
+pub struct RetainedMemoryByModule {
+    modules: SmallMap<String, RetainedByBinding>,
+}
+
+impl RetainedMemoryByModule {
+    /// Create an empty summary.
+    pub fn new() -> RetainedMemoryByModule {
+        RetainedMemoryByModule::default()
+    }
+
+    /// Add the memory retained by a frozen module, named `name` in the output.
+    /// Memory of modules added with the same name is added together.
+    ///
+    /// Fails if retained memory profiling was not enabled for the module.
+    pub fn add(&mut self, name: &str, module: &FrozenModule) -> anyhow::Result<()> {
+        let retained = &module.retained_heap_profile()?.by_binding;
+        match self.modules.get_mut(name) {
+            Some(module) => module.merge(retained),
+            None => {
+                self.modules.insert(name.to_owned(), retained.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Modules and their retained memory, largest first.
+    fn sorted(&self) -> Vec<(&str, &RetainedByBinding)> {
+        let mut modules: Vec<_> = self
+            .modules
+            .iter()
+            .map(|(name, module)| (name.as_str(), module))
+            .collect();
+        modules.sort_by_key(|(_, module)| usize::MAX - module.total());
+        modules
+    }
+
+    /// Per-module summary in CSV format: the bytes retained by each module,
+    /// and the binding retaining the most of them.
+    pub fn gen_csv(&self) -> String {
+        let mut csv = CsvWriter::new(["Module", "Bytes", "TopBinding", "TopBindingBytes"]);
+        csv.write_value("TOTAL");
+        csv.write_value(self.modules.values().map(|x| x.total()).sum::<usize>());
+        csv.write_value("");
+        csv.write_value(0);
+        csv.finish_row();
+        for (name, module) in self.sorted() {
+            let (top, top_bytes) = module.sorted().first().copied().unwrap_or(("", 0));
+            csv.write_value(name);
+            csv.write_value(module.total());
+            csv.write_value(top);
+            csv.write_value(top_bytes);
+            csv.finish_row();
+        }
+        csv.finish()
+    }
+
+    /// Bytes retained by each binding of each module in CSV format.
+    pub fn gen_bindings_csv(&self) -> String {
+        let mut csv = CsvWriter::new(["Module", "Binding", "Bytes"]);
+        for (name, module) in self.sorted() {
+            for (binding, bytes) in module.sorted() {
+                csv.write_value(name);
+                csv.write_value(binding);
+                csv.write_value(bytes);
+                csv.finish_row();
+            }
+        }
+        csv.finish()
+    }
+}
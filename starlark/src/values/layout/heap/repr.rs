@@ -27,10 +27,13 @@ use gazebo::any::AnyLifetime;
 use gazebo::cast;
 
 use crate::values::layout::avalue::AValue;
+use crate::values::layout::avalue::StarlarkStrAValue;
+use crate::values::layout::avalue::VALUE_STR_A_VALUE_PTR;
 use crate::values::layout::heap::arena::MIN_ALLOC;
 use crate::values::layout::heap::heap_type::HeapKind;
 use crate::values::layout::vtable::AValueDyn;
 use crate::values::layout::vtable::AValueVTable;
+use crate::values::string::StarlarkStr;
 use crate::values::FrozenValue;
 use crate::values::StarlarkValue;
 use crate::values::Value;
@@ -160,6 +163,26 @@ impl AValueOrForward {
         &self.header
     }
 
+    /// Payload of a string.
+    ///
+    /// Strings are accessed without reading the header: short frozen strings
+    /// are packed into pages and don't have headers of their own
+    /// (see `StarlarkStrPage`), the word before their payload can be anything.
+    #[inline]
+    pub(crate) unsafe fn unpack_str_unchecked(&self) -> &StarlarkStr {
+        let repr = self as *const AValueOrForward as *const AValueRepr<StarlarkStrAValue>;
+        &(*repr).payload.1
+    }
+
+    /// Like `AValueHeader::unpack`, but for strings, see `unpack_str_unchecked`.
+    #[inline]
+    pub(crate) unsafe fn unpack_str_dyn_unchecked(&self) -> AValueDyn<'_> {
+        AValueDyn {
+            value: &*(self.unpack_str_unchecked() as *const StarlarkStr as *const ()),
+            vtable: VALUE_STR_A_VALUE_PTR.0,
+        }
+    }
+
     pub(crate) fn unpack_header(&self) -> Option<&AValueHeader> {
         self.unpack().left()
     }
@@ -15,17 +15,29 @@
  * limitations under the License.
  */
 
+use std::fmt;
+use std::fmt::Debug;
 use std::marker::PhantomData;
+use std::mem;
 
+use allocative::Allocative;
 use dupe::Dupe;
+use starlark_map::small_map::SmallMap;
 
 use crate::values::layout::pointer::RawPointer;
+use crate::values::Freeze;
+use crate::values::Freezer;
+use crate::values::FrozenValue;
+use crate::values::Heap;
+use crate::values::Trace;
+use crate::values::Tracer;
 use crate::values::Value;
 
 /// An opaque value representing the identity of a given Value. Two values have the same identity
 /// if and only if [`Value::ptr_eq`] would return [`true`] on them.
-#[derive(Eq, PartialEq, Copy, Clone, Dupe, Hash, Debug)]
+#[derive(Eq, PartialEq, Copy, Clone, Dupe, Hash, Debug, Allocative)]
 pub struct ValueIdentity<'v> {
+    #[allocative(skip)]
     identity: RawPointer,
     phantom: PhantomData<&'v ()>,
 }
@@ -38,4 +50,270 @@ impl<'v> ValueIdentity<'v> {
             phantom: PhantomData,
         }
     }
+
+    /// Identity with the lifetime erased, used as a key for frozen values.
+    #[inline]
+    fn to_static(self) -> ValueIdentity<'static> {
+        ValueIdentity {
+            identity: self.identity,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Map keyed by [`Value`] identity, e.g. to attach side data to values in native code.
+///
+/// Value identities change on garbage collection. If the map is traced
+/// (e.g. it is a part of a value), keys are updated during garbage collection.
+/// Otherwise entries with unfrozen keys are dropped on the first access after garbage collection.
+/// Freezing the map rekeys it with the frozen values.
+#[derive(Allocative)]
+pub struct ValueIdentityMap<'v, T> {
+    /// Garbage collection generation of the heap the keys are valid for.
+    gc_generation: u64,
+    entries: SmallMap<ValueIdentity<'v>, (Value<'v>, T)>,
+}
+
+// Not derived: keys of an untraced map may point to values freed by garbage collection,
+// so the entries must not be formatted.
+impl<'v, T> Debug for ValueIdentityMap<'v, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValueIdentityMap")
+            .field("gc_generation", &self.gc_generation)
+            .field("len", &self.entries.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'v, T> Default for ValueIdentityMap<'v, T> {
+    fn default() -> Self {
+        ValueIdentityMap::new()
+    }
+}
+
+impl<'v, T> ValueIdentityMap<'v, T> {
+    /// Empty map.
+    pub fn new() -> Self {
+        ValueIdentityMap {
+            gc_generation: 0,
+            entries: SmallMap::new(),
+        }
+    }
+
+    /// Keys are valid for the current state of the heap.
+    fn is_valid(&self, heap: &'v Heap) -> bool {
+        self.gc_generation == heap.gc_generation()
+    }
+
+    /// Drop entries with keys invalidated by garbage collection.
+    fn invalidate_stale(&mut self, gc_generation: u64) {
+        if self.gc_generation != gc_generation {
+            self.entries = ValueIdentityMap::frozen_entries(&mut self.entries);
+            self.gc_generation = gc_generation;
+        }
+    }
+
+    /// Take the entries with frozen keys, which are not affected by garbage collection.
+    fn frozen_entries(
+        entries: &mut SmallMap<ValueIdentity<'v>, (Value<'v>, T)>,
+    ) -> SmallMap<ValueIdentity<'v>, (Value<'v>, T)> {
+        mem::take(entries)
+            .into_iter()
+            .filter(|(_, (k, _))| k.unpack_frozen().is_some())
+            .collect()
+    }
+
+    /// Get the data attached to the value.
+    pub fn get(&self, key: Value<'v>, heap: &'v Heap) -> Option<&T> {
+        if !self.is_valid(heap) && key.unpack_frozen().is_none() {
+            return None;
+        }
+        self.entries.get(&key.identity()).map(|(_, v)| v)
+    }
+
+    /// Get the data attached to the value, or attach the data created by `f`.
+    pub fn get_or_insert_with(
+        &mut self,
+        key: Value<'v>,
+        heap: &'v Heap,
+        f: impl FnOnce() -> T,
+    ) -> &mut T {
+        self.invalidate_stale(heap.gc_generation());
+        &mut self
+            .entries
+            .entry(key.identity())
+            .or_insert_with(|| (key, f()))
+            .1
+    }
+
+    /// Attach the data to the value, returning the previously attached data.
+    pub fn insert(&mut self, key: Value<'v>, value: T, heap: &'v Heap) -> Option<T> {
+        self.invalidate_stale(heap.gc_generation());
+        self.entries
+            .insert(key.identity(), (key, value))
+            .map(|(_, v)| v)
+    }
+
+    /// Remove the data attached to the value.
+    pub fn remove(&mut self, key: Value<'v>, heap: &'v Heap) -> Option<T> {
+        self.invalidate_stale(heap.gc_generation());
+        self.entries.remove(&key.identity()).map(|(_, v)| v)
+    }
+
+    /// Remove all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+unsafe impl<'v, T: Trace<'v>> Trace<'v> for ValueIdentityMap<'v, T> {
+    fn trace(&mut self, tracer: &Tracer<'v>) {
+        // Keys of the previous generation point to the heap being collected,
+        // keys of older generations are dangling.
+        let entries = if self.gc_generation + 1 == tracer.gc_generation() {
+            mem::take(&mut self.entries)
+        } else {
+            ValueIdentityMap::frozen_entries(&mut self.entries)
+        };
+        for (_, (mut k, mut v)) in entries {
+            k.trace(tracer);
+            v.trace(tracer);
+            self.entries.insert(k.identity(), (k, v));
+        }
+        self.gc_generation = tracer.gc_generation();
+    }
+}
+
+impl<'v, T: Freeze> Freeze for ValueIdentityMap<'v, T> {
+    type Frozen = FrozenValueIdentityMap<T::Frozen>;
+
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<FrozenValueIdentityMap<T::Frozen>> {
+        let mut entries = SmallMap::with_capacity(self.entries.len());
+        for (_, (k, v)) in self.entries {
+            let k = k.freeze(freezer)?;
+            let v = v.freeze(freezer)?;
+            entries.insert(k.to_value().identity().to_static(), (k, v));
+        }
+        Ok(FrozenValueIdentityMap { entries })
+    }
+}
+
+/// Frozen [`ValueIdentityMap`].
+#[derive(Debug, Allocative)]
+pub struct FrozenValueIdentityMap<T> {
+    entries: SmallMap<ValueIdentity<'static>, (FrozenValue, T)>,
+}
+
+impl<T> FrozenValueIdentityMap<T> {
+    /// Get the data attached to the value.
+    pub fn get(&self, key: Value) -> Option<&T> {
+        self.entries
+            .get(&key.identity().to_static())
+            .map(|(_, v)| v)
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Is the map empty?
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the entries.
+    pub fn iter(&self) -> impl Iterator<Item = (FrozenValue, &T)> {
+        self.entries.values().map(|(k, v)| (*k, v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::environment::Module;
+    use crate::values::layout::identity::ValueIdentityMap;
+    use crate::values::Freeze;
+    use crate::values::Freezer;
+    use crate::values::FrozenHeap;
+    use crate::values::Heap;
+    use crate::values::Trace;
+    use crate::values::Tracer;
+    use crate::values::Value;
+
+    #[test]
+    fn test_value_identity_map() {
+        let heap = Heap::new();
+        let x = heap.alloc_list(&[]);
+        let y = heap.alloc_list(&[]);
+        let mut map = ValueIdentityMap::new();
+        assert_eq!(None, map.insert(x, 1, &heap));
+        assert_eq!(Some(&1), map.get(x, &heap));
+        assert_eq!(None, map.get(y, &heap));
+        assert_eq!(&mut 2, map.get_or_insert_with(y, &heap, || 2));
+        assert_eq!(Some(1), map.remove(x, &heap));
+        assert_eq!(None, map.get(x, &heap));
+    }
+
+    #[test]
+    fn test_value_identity_map_gc() {
+        let module = Module::new();
+        let heap = module.heap();
+        let mut x = heap.alloc_list(&[]);
+        let mut traced: ValueIdentityMap<Value> = ValueIdentityMap::new();
+        let mut untraced = ValueIdentityMap::new();
+        traced.insert(x, Value::new_int(1), heap);
+        untraced.insert(x, 1, heap);
+        untraced.insert(Value::new_none(), 2, heap);
+
+        unsafe {
+            heap.garbage_collect(|tracer: &Tracer| {
+                tracer.trace(&mut x);
+                traced.trace(tracer);
+            })
+        };
+        assert_eq!(Some(&Value::new_int(1)), traced.get(x, heap));
+        // Formatting does not touch the stale keys.
+        assert_eq!(
+            "ValueIdentityMap { gc_generation: 0, len: 2, .. }",
+            format!("{:?}", untraced)
+        );
+        // Unfrozen keys are invalidated, frozen keys are not.
+        assert_eq!(None, untraced.get(x, heap));
+        assert_eq!(Some(&2), untraced.get(Value::new_none(), heap));
+    }
+
+    #[test]
+    fn test_value_identity_map_freeze() {
+        let heap = Heap::new();
+        let x = heap.alloc_list(&[]);
+        let mut map = ValueIdentityMap::new();
+        map.insert(x, 1u32, &heap);
+
+        let freezer = Freezer::new(FrozenHeap::new());
+        let map = map.freeze(&freezer).unwrap();
+        let x = x.freeze(&freezer).unwrap();
+        assert_eq!(Some(&1), map.get(x.to_value()));
+        assert_eq!(1, map.len());
+    }
+
+    #[test]
+    fn test_value_identity_map_freeze_equal_strings() {
+        let heap = Heap::new();
+        let x = heap.alloc_str_concat("ab", "c").to_value();
+        let y = heap.alloc_str_concat("a", "bc").to_value();
+        let mut map = ValueIdentityMap::new();
+        map.insert(x, 1u32, &heap);
+        map.insert(y, 2u32, &heap);
+
+        // Short strings are packed when frozen, but stay distinct values.
+        let freezer = Freezer::new(FrozenHeap::new());
+        let map = map.freeze(&freezer).unwrap();
+        let x = x.freeze(&freezer).unwrap();
+        let y = y.freeze(&freezer).unwrap();
+        assert_eq!(x, y);
+        assert!(!x.to_value().ptr_eq(y.to_value()));
+        assert_eq!(Some(&1), map.get(x.to_value()));
+        assert_eq!(Some(&2), map.get(y.to_value()));
+        assert_eq!(2, map.len());
+    }
 }
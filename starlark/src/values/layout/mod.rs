@@ -25,6 +25,7 @@ pub(crate) mod heap;
 pub(crate) mod identity;
 pub(crate) mod pointer;
 pub(crate) mod static_string;
+pub(crate) mod str_page;
 pub(crate) mod typed;
 pub(crate) mod value;
 pub(crate) mod value_captured;
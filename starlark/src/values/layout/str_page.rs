@@ -0,0 +1,104 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Pages of short frozen strings.
+
+use std::mem;
+
+use allocative::Allocative;
+use gazebo::any::ProvidesStaticType;
+
+use crate as starlark;
+use crate::private::Private;
+use crate::values::layout::heap::repr::AValueHeader;
+use crate::values::string::StarlarkStr;
+use crate::values::StarlarkValue;
+
+/// Short strings allocated on a frozen heap are packed into pages:
+/// a page is a single heap object holding strings one after another,
+/// each string is stored as its [`StarlarkStr`] payload, without a header.
+///
+/// For strings of up to 8 bytes this saves a third of the memory:
+/// the header, the length and the padded body take a word each,
+/// and the header is not needed.
+///
+/// A packed string value points to the word before the payload,
+/// where the header would be, so each string still has its own address,
+/// and pointer identity is the same as for strings allocated individually.
+/// This works because string values are accessed without reading the header
+/// (see `AValueOrForward::unpack_str_unchecked`), and frozen values are never
+/// overwritten with forwards.
+///
+/// The page itself is never exposed as a value.
+#[derive(
+    Debug,
+    derive_more::Display,
+    ProvidesStaticType,
+    NoSerialize,
+    Allocative
+)]
+#[display(fmt = "StarlarkStrPage")]
+#[repr(C)]
+pub(crate) struct StarlarkStrPage {
+    /// Page size in words, not including this field.
+    len: usize,
+    content: [usize; 0],
+}
+
+impl StarlarkStrPage {
+    /// Page size in words.
+    pub(crate) const LEN: usize = 64;
+
+    /// Strings up to this length in bytes are packed.
+    /// Longer strings would leave too much space unused at the end of a page.
+    pub(crate) const MAX_STR_LEN: usize = 32;
+
+    /// Unsafe because the content must be allocated after the page.
+    pub(crate) const unsafe fn new(len: usize) -> StarlarkStrPage {
+        StarlarkStrPage { len, content: [] }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn offset_of_content() -> usize {
+        memoffset::offset_of!(StarlarkStrPage, content)
+    }
+
+    /// Size in bytes a string of given length takes in a page.
+    pub(crate) fn size_of_str(len: usize) -> usize {
+        StarlarkStr::offset_of_content()
+            + StarlarkStr::payload_len_for_len(len) * mem::size_of::<usize>()
+    }
+
+    /// Value pointer of a string whose payload is placed at the given address.
+    pub(crate) fn str_ptr(payload: usize) -> usize {
+        payload - mem::size_of::<AValueHeader>()
+    }
+}
+
+impl<'v> StarlarkValue<'v> for StarlarkStrPage {
+    starlark_type!("string_page");
+
+    fn is_special(_: Private) -> bool
+    where
+        Self: Sized,
+    {
+        true
+    }
+}
@@ -170,6 +170,14 @@ impl<'v, T: StarlarkValue<'v>> ValueTyped<'v, T> {
                     PointerI32::new(self.0.0.unpack_int_unchecked())
                 )
             }
+        } else if T::static_type_id() == StarlarkStr::static_type_id() {
+            unsafe {
+                &*(self
+                    .0
+                    .0
+                    .unpack_ptr_no_int_unchecked()
+                    .unpack_str_unchecked() as *const StarlarkStr as *const T)
+            }
         } else {
             unsafe {
                 &*(self
@@ -238,8 +246,7 @@ impl<'v, T: StarlarkValue<'v>> FrozenValueTyped<'v, T> {
                     .0
                     .0
                     .unpack_ptr_no_int_unchecked()
-                    .unpack_header_unchecked()
-                    .payload_ptr() as *const T)
+                    .unpack_str_unchecked() as *const StarlarkStr as *const T)
             }
         } else {
             // When a frozen pointer is not str and not int,
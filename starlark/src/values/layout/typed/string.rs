@@ -259,7 +259,10 @@ impl Ord for FrozenStringValue {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use crate::collections::Hashed;
+    use crate::environment::Module;
     use crate::values::FrozenHeap;
     use crate::values::FrozenStringValue;
     use crate::values::FrozenValue;
@@ -284,4 +287,87 @@ mod tests {
         let fv: FrozenValue = heap.alloc_str("xyz").to_frozen_value();
         assert_eq!(expected, fv.get_hashed().unwrap().hash());
     }
+
+    #[test]
+    fn test_alloc_str_external() {
+        let heap = Heap::new();
+        let copied = heap.alloc_str("hello world");
+        let s = heap.alloc_str_static("hello world");
+        let a = heap.alloc_str_arc(Arc::from("hello world"));
+        let buf = String::from("hello world");
+        let b = unsafe { heap.alloc_str_borrowed(&buf) };
+        for v in [s, a, b] {
+            assert_eq!("hello world", v.as_str());
+            assert_eq!(copied, v);
+            assert!(copied.to_value().equals(v.to_value()).unwrap());
+            assert_eq!(Hashed::new(copied).hash(), Hashed::new(v).hash());
+        }
+        assert_eq!(buf.as_ptr(), b.as_str().as_ptr());
+        // Short strings are still allocated statically.
+        assert_eq!("x", heap.alloc_str_arc(Arc::from("x")).as_str());
+
+        let heap = FrozenHeap::new();
+        let fs = heap.alloc_str_static("hello world");
+        let fa = heap.alloc_str_arc(Arc::from("hello world"));
+        assert_eq!(fs, fa);
+        assert_eq!(heap.alloc_str("hello world"), fs);
+    }
+
+    #[test]
+    fn test_alloc_str_external_freeze() {
+        let arc: Arc<str> = Arc::from("arc string");
+        let buf = String::from("borrowed string");
+        let module = Module::new();
+        module.set(
+            "s",
+            module.heap().alloc_str_static("static string").to_value(),
+        );
+        module.set("a", module.heap().alloc_str_arc(arc.clone()).to_value());
+        module.set(
+            "b",
+            unsafe { module.heap().alloc_str_borrowed(&buf) }.to_value(),
+        );
+        let module = module.freeze().unwrap();
+        drop(buf);
+
+        let get = |name| {
+            module
+                .get(name)
+                .unwrap()
+                .value()
+                .unpack_str()
+                .unwrap()
+                .to_owned()
+        };
+        assert_eq!("static string", get("s"));
+        assert_eq!("arc string", get("a"));
+        assert_eq!("borrowed string", get("b"));
+        // The frozen heap shares the `Arc` rather than copying it.
+        let a = module.get("a").unwrap();
+        assert_eq!(arc.as_ptr(), a.value().unpack_str().unwrap().as_ptr());
+    }
+
+    #[test]
+    fn test_frozen_short_strings_packed() {
+        let heap = FrozenHeap::new();
+        let strings: Vec<FrozenStringValue> = (0..64)
+            .map(|i| heap.alloc_str(&format!("s{:02}", i % 32)))
+            .collect();
+        for (i, s) in strings.iter().enumerate() {
+            assert_eq!(format!("s{:02}", i % 32), s.as_str());
+            assert_eq!(Hashed::new(s.as_str()).hash(), s.get_hashed().hash());
+            assert_eq!("string", s.to_value().get_type());
+        }
+        // Equal strings are still distinct values.
+        assert_eq!(strings[0], strings[32]);
+        assert!(!strings[0].to_value().ptr_eq(strings[32].to_value()));
+
+        // 32 strings of up to 8 bytes take 16 bytes each and fit into a page,
+        // strings allocated individually would take 24 bytes each.
+        let summary = heap.allocated_summary().summary();
+        assert_eq!(None, summary.get("string"));
+        let (count, bytes) = summary["string_page"];
+        assert_eq!(2, count);
+        assert!(bytes < 64 * 24, "{}", bytes);
+    }
 }
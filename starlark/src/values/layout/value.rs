@@ -75,7 +75,6 @@ use crate::values::function::FUNCTION_TYPE;
 use crate::values::int::PointerI32;
 use crate::values::layout::avalue::basic_ref;
 use crate::values::layout::avalue::AValue;
-use crate::values::layout::avalue::StarlarkStrAValue;
 use crate::values::layout::avalue::VALUE_FALSE;
 use crate::values::layout::avalue::VALUE_NONE;
 use crate::values::layout::avalue::VALUE_TRUE;
@@ -376,17 +375,7 @@ impl<'v> Value<'v> {
     #[inline]
     pub fn unpack_starlark_str(self) -> Option<&'v StarlarkStr> {
         if self.is_str() {
-            unsafe {
-                Some(
-                    &self
-                        .0
-                        .unpack_ptr_no_int_unchecked()
-                        .unpack_header_unchecked()
-                        .as_repr::<StarlarkStrAValue>()
-                        .payload
-                        .1,
-                )
-            }
+            unsafe { Some(self.0.unpack_ptr_no_int_unchecked().unpack_str_unchecked()) }
         } else {
             None
         }
@@ -402,6 +391,7 @@ impl<'v> Value<'v> {
     pub(crate) fn get_ref(self) -> AValueDyn<'v> {
         unsafe {
             match self.0.unpack() {
+                Either::Left(x) if self.is_str() => x.unpack_str_dyn_unchecked(),
                 Either::Left(x) => x.unpack_header_unchecked().unpack(),
                 Either::Right(x) => basic_ref(x),
             }
@@ -418,6 +408,9 @@ impl<'v> Value<'v> {
                 &T,
                 PointerI32::new(self.0.unpack_int_unchecked())
             )
+        } else if T::static_type_id() == StarlarkStr::static_type_id() {
+            &*(self.0.unpack_ptr_no_int_unchecked().unpack_str_unchecked() as *const StarlarkStr
+                as *const T)
         } else {
             self.0
                 .unpack_ptr_no_int_unchecked()
@@ -451,6 +444,9 @@ impl<'v> Value<'v> {
     ///    compare equal.
     /// 2. If two [`Value]` have [`ValueIdentity`]  that compare equal, then [`Value::ptr_eq`] and
     ///    [`Value::equals`]  will also consider them to be equal.
+    ///
+    /// Use [`ValueIdentityMap`](crate::values::ValueIdentityMap) to key data by identity
+    /// across garbage collections.
     #[inline]
     pub fn identity(self) -> ValueIdentity<'v> {
         ValueIdentity::new(self)
@@ -616,6 +612,7 @@ impl<'v> Value<'v> {
     }
 
     /// Invoke a function with only positional arguments.
+    /// Used by native functions to call callbacks, see [`Evaluator::call`].
     pub(crate) fn invoke_pos(
         self,
         pos: &[Value<'v>],
@@ -625,7 +622,7 @@ impl<'v> Value<'v> {
             pos,
             ..ArgumentsFull::default()
         });
-        self.invoke(&params, eval)
+        eval.call(self, &params)
     }
 
     /// `type(x)`.
@@ -715,6 +712,19 @@ impl<'v> Value<'v> {
         self.get_ref().set_at(index, alloc_value)
     }
 
+    /// Forwards to [`StarlarkValue::set_slice`].
+    pub fn set_slice(
+        self,
+        start: Option<Value<'v>>,
+        stop: Option<Value<'v>>,
+        stride: Option<Value<'v>>,
+        alloc_value: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<()> {
+        self.get_ref()
+            .set_slice(start, stop, stride, alloc_value, heap)
+    }
+
     /// Forwards to [`StarlarkValue::documentation`].
     pub fn documentation(self) -> Option<DocItem> {
         self.get_ref().documentation()
@@ -868,6 +878,12 @@ impl FrozenValue {
         Self(FrozenPointer::new_frozen_usize_with_str_tag(x))
     }
 
+    /// String at the address without a header, see `StarlarkStrPage`.
+    #[inline]
+    pub(crate) fn new_str_ptr_usize(x: usize) -> Self {
+        Self(FrozenPointer::new_frozen_usize(x, true))
+    }
+
     #[inline]
     pub(crate) fn new_ptr_value(x: usize) -> Self {
         unsafe { Self(FrozenPointer::new(x)) }
@@ -537,6 +537,25 @@ impl<'v> AValueDyn<'v> {
         (self.vtable.starlark_value.set_at)(StarlarkValueRawPtr::new(self.value), index, new_value)
     }
 
+    #[inline]
+    pub(crate) fn set_slice(
+        self,
+        start: Option<Value<'v>>,
+        stop: Option<Value<'v>>,
+        step: Option<Value<'v>>,
+        new_value: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<()> {
+        (self.vtable.starlark_value.set_slice)(
+            StarlarkValueRawPtr::new(self.value),
+            start,
+            stop,
+            step,
+            new_value,
+            heap,
+        )
+    }
+
     #[inline]
     pub(crate) fn set_attr(self, attribute: &str, new_value: Value<'v>) -> anyhow::Result<()> {
         (self.vtable.starlark_value.set_attr)(
@@ -42,16 +42,24 @@ pub use starlark_derive::Trace;
 pub use crate::values::alloc_value::AllocFrozenValue;
 pub use crate::values::alloc_value::AllocValue;
 pub use crate::values::demand::Demand;
+pub use crate::values::diff::DiffPathItem;
+pub use crate::values::diff::DiffValue;
+pub use crate::values::diff::ValueChange;
+pub use crate::values::diff::ValueDiff;
 pub use crate::values::error::ValueError;
 pub use crate::values::freeze::Freeze;
 pub use crate::values::frozen_ref::FrozenRef;
+pub use crate::values::layout::heap::alloc_hook::HeapAllocationStats;
 pub use crate::values::layout::heap::heap_type::Freezer;
 pub use crate::values::layout::heap::heap_type::FrozenHeap;
 pub use crate::values::layout::heap::heap_type::FrozenHeapRef;
 pub use crate::values::layout::heap::heap_type::Heap;
 pub use crate::values::layout::heap::heap_type::Tracer;
 pub use crate::values::layout::heap::profile::aggregated::AggregateHeapProfileInfo;
+pub use crate::values::layout::heap::profile::by_module::RetainedMemoryByModule;
+pub use crate::values::layout::identity::FrozenValueIdentityMap;
 pub use crate::values::layout::identity::ValueIdentity;
+pub use crate::values::layout::identity::ValueIdentityMap;
 pub use crate::values::layout::static_string::constant_string;
 pub use crate::values::layout::static_string::StarlarkStrNRepr;
 pub use crate::values::layout::typed::string::FrozenStringValue;
@@ -70,16 +78,22 @@ pub use crate::values::traits::StarlarkValue;
 pub use crate::values::types::any;
 pub use crate::values::types::array;
 pub use crate::values::types::bool;
+pub use crate::values::types::bytes;
 pub use crate::values::types::dict;
 pub use crate::values::types::enumeration;
+pub use crate::values::types::external;
 pub use crate::values::types::float;
 pub use crate::values::types::function;
 pub use crate::values::types::int;
+pub use crate::values::types::interop;
+pub use crate::values::types::label;
 pub use crate::values::types::list;
 pub use crate::values::types::none;
 pub use crate::values::types::range;
 pub use crate::values::types::record;
 pub use crate::values::types::regex;
+pub use crate::values::types::set;
+pub use crate::values::types::stream;
 pub use crate::values::types::string;
 pub use crate::values::types::structs;
 pub use crate::values::types::tuple;
@@ -88,8 +102,10 @@ pub use crate::values::unpack::ValueOf;
 
 mod alloc_value;
 pub(crate) mod basic;
+pub mod cell;
 mod comparison;
 pub(crate) mod demand;
+pub(crate) mod diff;
 pub(crate) mod error;
 mod freeze;
 pub(crate) mod frozen_ref;
@@ -221,4 +221,15 @@ mod tests {
             Num::Float("25e-2".parse().unwrap()).get_hash_64()
         );
     }
+
+    // Int hashes may be persisted, so they must match stable hash functions.
+    #[test]
+    fn test_int_hash_is_stable() {
+        for i in [0, 1, -1, i32::MIN, i32::MAX] {
+            assert_eq!(
+                StarlarkHashValue::hash_64(i as i64 as u64),
+                Num::Int(i).get_hash()
+            );
+        }
+    }
 }
@@ -380,6 +380,9 @@ pub trait StarlarkValue<'v>:
     }
 
     /// Return the result of `a[index]` if `a` is indexable.
+    ///
+    /// Multiple subscripts `a[i, j]` are passed as a tuple `index`,
+    /// which can be unpacked with [`UnpackValue`](crate::values::UnpackValue) for `(I, J)`.
     fn at(&self, index: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         ValueError::unsupported_with(self, "[]", index)
     }
@@ -709,6 +712,28 @@ pub trait StarlarkValue<'v>:
         Err(ValueError::CannotMutateImmutableValue.into())
     }
 
+    /// Replace a slice of the current value with the new value (e.g. `a[1:3] = value`).
+    /// Parameters are the same as for [`slice`](StarlarkValue::slice).
+    ///
+    /// ```rust
+    /// # starlark::assert::is_true(r#"
+    /// v = [1, 2, 3, 4]
+    /// v[1:3] = ["a"]
+    /// v[::2] = [0, 0]
+    /// v == [0, "a", 0]
+    /// # "#);
+    /// ```
+    fn set_slice(
+        &self,
+        _start: Option<Value<'v>>,
+        _stop: Option<Value<'v>>,
+        _stride: Option<Value<'v>>,
+        _new_value: Value<'v>,
+        _heap: &'v Heap,
+    ) -> anyhow::Result<()> {
+        ValueError::unsupported(self, "[::]=")
+    }
+
     /// Set the attribute named `attribute` of the current value to
     /// `value` (e.g. `a.attribute = value`).
     fn set_attr(&self, attribute: &str, _new_value: Value<'v>) -> anyhow::Result<()> {
@@ -276,6 +276,11 @@ impl<'a, 'v> Iterator for ArrayIter<'a, 'v> {
         let rem = self.len();
         (rem, Some(rem))
     }
+
+    fn nth(&mut self, n: usize) -> Option<Value<'v>> {
+        self.next = self.next.saturating_add(n);
+        self.next()
+    }
 }
 
 impl<'a, 'v> ExactSizeIterator for ArrayIter<'a, 'v> {
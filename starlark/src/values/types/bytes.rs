@@ -0,0 +1,289 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `bytes` type, an immutable sequence of bytes, see [`StarlarkBytes`].
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Write;
+use std::hash::Hash;
+
+use allocative::Allocative;
+use gazebo::any::ProvidesStaticType;
+use serde::Serialize;
+
+use crate as starlark;
+use crate::collections::StarlarkHasher;
+use crate::environment::Methods;
+use crate::environment::MethodsBuilder;
+use crate::environment::MethodsStatic;
+use crate::values::index::apply_slice;
+use crate::values::index::convert_index;
+use crate::values::Heap;
+use crate::values::StarlarkValue;
+use crate::values::UnpackValue;
+use crate::values::Value;
+use crate::values::ValueError;
+
+#[derive(Debug, thiserror::Error)]
+enum BytesError {
+    #[error("Byte value `{0}` is out of range, expected an int between 0 and 255")]
+    OutOfRange(i32),
+    #[error("Expected a `str`, `bytes` or an iterable of ints, got `{0}`")]
+    InvalidArgument(&'static str),
+}
+
+/// An immutable sequence of bytes.
+///
+/// In Starlark, bytes are written as literals `b"..."`, where `\x` and octal escapes give
+/// a single byte, or created with `bytes()` from a string (UTF-8 encoded),
+/// other bytes or an iterable of ints. They support indexing (giving ints), slicing,
+/// `+`, `*`, comparison and `in`. They are not iterable, `elems()` gives the ints,
+/// and `str()` decodes them as UTF-8, replacing invalid sequences with U+FFFD.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    ProvidesStaticType,
+    StarlarkDocs,
+    Allocative
+)]
+#[starlark_docs(builtin = "extension")]
+pub struct StarlarkBytes(Box<[u8]>);
+
+starlark_simple_value!(StarlarkBytes);
+
+impl StarlarkBytes {
+    /// The result of calling `type()` on bytes.
+    pub const TYPE: &'static str = "bytes";
+
+    /// Create bytes from their content.
+    pub fn new(content: impl Into<Box<[u8]>>) -> StarlarkBytes {
+        StarlarkBytes(content.into())
+    }
+
+    /// Create bytes from a string, an existing bytes value or an iterable of ints.
+    pub fn from_starlark<'v>(x: Value<'v>, heap: &'v Heap) -> anyhow::Result<StarlarkBytes> {
+        if let Some(s) = x.unpack_str() {
+            Ok(StarlarkBytes::new(s.as_bytes()))
+        } else if let Some(b) = StarlarkBytes::from_value(x) {
+            Ok(b.clone())
+        } else {
+            let it = x
+                .iterate(heap)
+                .map_err(|_| BytesError::InvalidArgument(x.get_type()))?;
+            let mut res = Vec::new();
+            for x in it {
+                let i = i32::unpack_param(x)?;
+                res.push(u8::try_from(i).map_err(|_| BytesError::OutOfRange(i))?);
+            }
+            Ok(StarlarkBytes::new(res))
+        }
+    }
+
+    /// The content of the bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Decode the bytes as UTF-8, replacing invalid sequences with U+FFFD.
+    pub fn to_str_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.0).into_owned()
+    }
+}
+
+impl Display for StarlarkBytes {
+    /// Like Python, `b"..."` with non-printable bytes escaped.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("b\"")?;
+        for &b in self.0.iter() {
+            match b {
+                b'"' => f.write_str("\\\"")?,
+                b'\\' => f.write_str("\\\\")?,
+                b'\n' => f.write_str("\\n")?,
+                b'\r' => f.write_str("\\r")?,
+                b'\t' => f.write_str("\\t")?,
+                b' '..=b'~' => f.write_char(b as char)?,
+                _ => write!(f, "\\x{:02x}", b)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+impl Serialize for StarlarkBytes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'v> StarlarkValue<'v> for StarlarkBytes {
+    starlark_type!(StarlarkBytes::TYPE);
+
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(bytes_methods)
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    fn equals(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        Ok(StarlarkBytes::from_value(other) == Some(self))
+    }
+
+    fn compare(&self, other: Value<'v>) -> anyhow::Result<Ordering> {
+        match StarlarkBytes::from_value(other) {
+            Some(other) => Ok(self.0.cmp(&other.0)),
+            None => ValueError::unsupported_with(self, "compare", other),
+        }
+    }
+
+    fn write_hash(&self, hasher: &mut StarlarkHasher) -> anyhow::Result<()> {
+        self.0.hash(hasher);
+        Ok(())
+    }
+
+    fn at(&self, index: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        let i = convert_index(index, self.0.len() as i32)?;
+        Ok(Value::new_int(self.0[i as usize] as i32))
+    }
+
+    fn slice(
+        &self,
+        start: Option<Value>,
+        stop: Option<Value>,
+        stride: Option<Value>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        Ok(heap.alloc(StarlarkBytes::new(apply_slice(
+            &self.0, start, stop, stride,
+        )?)))
+    }
+
+    fn length(&self) -> anyhow::Result<i32> {
+        Ok(self.0.len() as i32)
+    }
+
+    fn is_in(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        if let Some(i) = other.unpack_int() {
+            let b = u8::try_from(i).map_err(|_| BytesError::OutOfRange(i))?;
+            Ok(self.0.contains(&b))
+        } else if let Some(other) = StarlarkBytes::from_value(other) {
+            Ok(other.0.is_empty() || self.0.windows(other.0.len()).any(|x| *x == *other.0))
+        } else {
+            ValueError::unsupported_owned(other.get_type(), "in", Some(StarlarkBytes::TYPE))
+        }
+    }
+
+    fn add(&self, rhs: Value<'v>, heap: &'v Heap) -> Option<anyhow::Result<Value<'v>>> {
+        let rhs = StarlarkBytes::from_value(rhs)?;
+        let content = [&*self.0, &*rhs.0].concat();
+        Some(Ok(heap.alloc(StarlarkBytes::new(content))))
+    }
+
+    fn mul(&self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        let n = i32::unpack_param(other)?;
+        Ok(heap.alloc(StarlarkBytes::new(self.0.repeat(n.max(0) as usize))))
+    }
+}
+
+#[starlark_module]
+fn bytes_methods(builder: &mut MethodsBuilder) {
+    /// The bytes as a list of ints between 0 and 255.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// bytes("AB").elems() == [65, 66]
+    /// # "#);
+    /// ```
+    fn elems<'v>(this: &StarlarkBytes, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        Ok(heap.alloc_list_iter(this.0.iter().map(|&b| Value::new_int(b as i32))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+
+    #[test]
+    fn test_bytes() {
+        assert::pass(
+            r#"
+b = bytes("héllo")
+assert_eq(6, len(b))
+assert_eq(104, b[0])
+assert_eq(111, b[-1])
+assert_eq(bytes("ll"), b[3:5])
+assert_eq(bytes("cba"), bytes("abc")[::-1])
+assert_eq(bytes("abc"), bytes("a") + bytes("bc"))
+assert_eq("bytes", type(b))
+assert_eq("héllo", str(b))
+assert_eq(b, bytes(b))
+assert_eq(b, bytes(b.elems()))
+assert_eq(bytes([0, 255]), bytes((0, 255)))
+assert_eq('b"\\x00\\xff"', repr(bytes([0, 255])))
+assert_eq('b"a\\"\\n"', repr(bytes('a"\n')))
+assert_eq("�", str(bytes([255])))
+assert_true(104 in b)
+assert_true(bytes("ll") in b)
+assert_true(bytes("lo") not in bytes("ol"))
+assert_true(bytes("a") < bytes("b"))
+assert_eq(bytes("abab"), bytes("ab") * 2)
+assert_true(not bytes(""))
+assert_eq({bytes("a"): 1}[bytes("a")], 1)
+"#,
+        );
+    }
+
+    #[test]
+    fn test_bytes_literal() {
+        assert::pass(
+            r#"
+assert_eq(bytes("héllo"), b"héllo")
+assert_eq(bytes([0, 255, 10]), b'\x00\xff\n')
+assert_eq(bytes([255, 0]), b"\377\0")
+assert_eq(bytes("\\x"), rb"\x")
+assert_eq(bytes("a\nb"), b"""a
+b""")
+assert_eq("bytes", type(b""))
+assert_eq(104, b"hi"[0])
+assert_eq([104, 105], b"hi".elems())
+def f():
+    return b"ab" + b"c"
+assert_eq(bytes("abc"), f())
+"#,
+        );
+        assert::fail(r"b'\400'", "invalid string escape sequence");
+    }
+
+    #[test]
+    fn test_bytes_fail() {
+        assert::fail("bytes([256])", "out of range");
+        assert::fail("bytes(1)", "Expected a `str`");
+        assert::fail("bytes('a')[1]", "out of bound");
+        assert::fail("[x for x in bytes('a')]", "`(iter)` not supported");
+        assert::fail("bytes('a') + 'a'", "not supported");
+    }
+}
@@ -0,0 +1,282 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Values referencing resources owned by the host, like file handles or database cursors,
+//! which need to be released at a predictable point rather than whenever a value is dropped.
+//!
+//! A resource is put on a heap with [`Heap::alloc_external`](crate::values::Heap::alloc_external),
+//! which returns a value of type [`ExternalRef`]. The lifecycle of the resource is:
+//!
+//! * When the value is frozen, [`ExternalResource::frozen`] is called, and from then on
+//!   the resource is owned by the frozen heap instead of the [`Heap`](crate::values::Heap).
+//! * [`ExternalResource::release`] is called exactly once, at the first of:
+//!   * an explicit [`ExternalRef::release`];
+//!   * the garbage collection which finds the value unreachable;
+//!   * the drop of the heap owning the resource, which is the frozen heap for frozen values,
+//!     dropped once the last [`FrozenHeapRef`](crate::values::FrozenHeapRef) to it is gone.
+//! * The resources owned by a heap are released when it is dropped in the reverse order of
+//!   their allocation, before any value on the heap is dropped.
+
+use std::any::Any;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::sync::atomic;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use allocative::Allocative;
+use dupe::Dupe;
+use gazebo::any::ProvidesStaticType;
+
+use crate as starlark;
+use crate::values::Freeze;
+use crate::values::Freezer;
+use crate::values::NoSerialize;
+use crate::values::StarlarkValue;
+use crate::values::Trace;
+use crate::values::Value;
+use crate::values::ValueLike;
+
+/// A resource owned by the host, referenced by an [`ExternalRef`] value.
+pub trait ExternalResource: Any + Debug + Send {
+    /// Called once when the value referencing the resource is frozen,
+    /// after which the resource can be shared between threads, but only used
+    /// by one thread at a time.
+    fn frozen(&mut self) {}
+
+    /// Release the resource. Called exactly once, see the [module docs](self) for when.
+    fn release(&mut self);
+}
+
+/// The resource of an [`ExternalRef`], shared with the heap which owns it.
+#[derive(Debug)]
+pub(crate) struct ExternalSlot {
+    type_name: &'static str,
+    /// `None` once released.
+    resource: Mutex<Option<Box<dyn ExternalResource>>>,
+    frozen: AtomicBool,
+}
+
+impl ExternalSlot {
+    pub(crate) fn new<T: ExternalResource>(resource: T) -> Self {
+        let type_name = std::any::type_name::<T>();
+        ExternalSlot {
+            type_name: type_name.rsplit("::").next().unwrap_or(type_name),
+            resource: Mutex::new(Some(Box::new(resource))),
+            frozen: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn is_frozen(&self) -> bool {
+        self.frozen.load(atomic::Ordering::Acquire)
+    }
+
+    fn freeze(&self) {
+        if !self.frozen.swap(true, atomic::Ordering::AcqRel) {
+            if let Some(x) = self.resource.lock().unwrap().as_mut() {
+                x.frozen();
+            }
+        }
+    }
+
+    pub(crate) fn release(&self) {
+        // Take the resource first, so it is not locked while releasing.
+        let resource = self.resource.lock().unwrap().take();
+        if let Some(mut x) = resource {
+            x.release();
+        }
+    }
+}
+
+impl Drop for ExternalSlot {
+    fn drop(&mut self) {
+        // Only reached for resources of heaps which were dropped without being released,
+        // e.g. a frozen heap which was never finished.
+        self.release();
+    }
+}
+
+/// A value referencing a resource owned by the host, see the [module docs](self).
+#[derive(Debug, Trace, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct ExternalRef {
+    #[trace(unsafe_ignore)]
+    #[allocative(skip)]
+    slot: Arc<ExternalSlot>,
+}
+
+impl ExternalRef {
+    /// The type of values returned by
+    /// [`Heap::alloc_external`](crate::values::Heap::alloc_external).
+    pub const TYPE: &'static str = "external";
+
+    pub(crate) fn new(slot: Arc<ExternalSlot>) -> Self {
+        ExternalRef { slot }
+    }
+
+    /// Obtain the [`ExternalRef`] from a [`Value`], if it is one.
+    pub fn from_value<'v>(x: Value<'v>) -> Option<&'v ExternalRef> {
+        x.downcast_ref::<ExternalRef>()
+    }
+
+    /// Call `f` with the resource, `None` if it was released or is not a `T`.
+    pub fn with<T: ExternalResource, R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut resource = self.slot.resource.lock().unwrap();
+        let resource: &mut dyn Any = &mut **resource.as_mut()?;
+        resource.downcast_mut().map(f)
+    }
+
+    /// Whether the resource was released.
+    pub fn is_released(&self) -> bool {
+        self.slot.resource.lock().unwrap().is_none()
+    }
+
+    /// Release the resource now rather than when its heap is dropped.
+    /// Does nothing if it was already released.
+    pub fn release(&self) {
+        self.slot.release()
+    }
+}
+
+impl Display for ExternalRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_released() {
+            write!(f, "<{} {} (released)>", Self::TYPE, self.slot.type_name)
+        } else {
+            write!(f, "<{} {}>", Self::TYPE, self.slot.type_name)
+        }
+    }
+}
+
+impl Freeze for ExternalRef {
+    type Frozen = ExternalRef;
+
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<ExternalRef> {
+        self.slot.freeze();
+        freezer.frozen_heap().add_external(self.slot.dupe());
+        Ok(self)
+    }
+}
+
+impl<'v> StarlarkValue<'v> for ExternalRef {
+    starlark_type!(ExternalRef::TYPE);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::eval::Evaluator;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+    use crate::values::external::ExternalRef;
+    use crate::values::external::ExternalResource;
+    use crate::values::Heap;
+
+    #[derive(Debug)]
+    struct Cursor {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ExternalResource for Cursor {
+        fn frozen(&mut self) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("frozen {}", self.name));
+        }
+
+        fn release(&mut self) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("release {}", self.name));
+        }
+    }
+
+    fn cursor(name: &'static str, log: &Arc<Mutex<Vec<String>>>) -> Cursor {
+        Cursor {
+            name,
+            log: log.clone(),
+        }
+    }
+
+    fn take(log: &Arc<Mutex<Vec<String>>>) -> Vec<String> {
+        std::mem::take(&mut *log.lock().unwrap())
+    }
+
+    #[test]
+    fn test_release_on_heap_drop() {
+        let log = Arc::default();
+        let heap = Heap::new();
+        let a = heap.alloc_external(cursor("a", &log));
+        heap.alloc_external(cursor("b", &log));
+        heap.alloc_external(cursor("c", &log));
+        assert_eq!("<external Cursor>", a.to_str());
+        let a = ExternalRef::from_value(a).unwrap();
+        assert_eq!(Some("a"), a.with(|x: &mut Cursor| x.name));
+        a.release();
+        assert!(a.is_released());
+        assert_eq!(None, a.with(|x: &mut Cursor| x.name));
+        assert_eq!(vec!["release a"], take(&log));
+        drop(heap);
+        assert_eq!(vec!["release c", "release b"], take(&log));
+    }
+
+    #[test]
+    fn test_release_on_frozen_heap_drop() -> anyhow::Result<()> {
+        let log = Arc::default();
+        let module = Module::new();
+        module.set("a", module.heap().alloc_external(cursor("a", &log)));
+        module.heap().alloc_external(cursor("b", &log));
+        let frozen = module.freeze()?;
+        assert_eq!(vec!["frozen a", "release b"], take(&log));
+        let a = frozen.get("a")?;
+        drop(frozen);
+        assert!(take(&log).is_empty());
+        let name = ExternalRef::from_value(a.value())
+            .unwrap()
+            .with(|x: &mut Cursor| x.name);
+        assert_eq!(Some("a"), name);
+        drop(a);
+        assert_eq!(vec!["release a"], take(&log));
+        Ok(())
+    }
+
+    #[test]
+    fn test_release_on_garbage_collection() -> anyhow::Result<()> {
+        let log = Arc::default();
+        let module = Module::new();
+        module.set("a", module.heap().alloc_external(cursor("a", &log)));
+        module.heap().alloc_external(cursor("b", &log));
+        module.heap().alloc_external(cursor("c", &log));
+        let mut eval = Evaluator::new(&module);
+        eval.trigger_gc();
+        let ast = AstModule::parse("gc.star", "x = 1\ny = 2".to_owned(), &Dialect::Standard)?;
+        eval.eval_module(ast, &Globals::standard())?;
+        assert_eq!(vec!["release c", "release b"], take(&log));
+        drop(eval);
+        drop(module);
+        assert_eq!(vec!["release a"], take(&log));
+        Ok(())
+    }
+}
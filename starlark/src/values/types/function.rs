@@ -30,6 +30,7 @@ use crate::docs;
 use crate::docs::DocItem;
 use crate::docs::DocStringKind;
 use crate::eval::Arguments;
+use crate::eval::BindingEventKind;
 use crate::eval::Evaluator;
 use crate::eval::ParametersParser;
 use crate::eval::ParametersSpec;
@@ -190,6 +191,8 @@ impl NativeFunction {
     }
 
     /// Create a new [`NativeFunction`] from the Rust function, plus the parameter specification.
+    ///
+    /// The parameter specification is also used as the documented signature of the function.
     pub fn new<F>(function: F, name: String, parameters: ParametersSpec<FrozenValue>) -> Self
     where
         F: for<'v> Fn(
@@ -200,12 +203,20 @@ impl NativeFunction {
             + Sync
             + 'static,
     {
-        Self::new_direct(
+        let raw_docs = NativeCallableRawDocs {
+            rust_docstring: None,
+            signature: parameters.clone(),
+            parameter_types: HashMap::new(),
+            return_type: None,
+        };
+        let mut native_function = Self::new_direct(
             move |eval, params| {
                 parameters.parser(params, eval, |parser, eval| function(eval, parser))
             },
             name,
-        )
+        );
+        native_function.raw_docs = Some(raw_docs);
+        native_function
     }
 
     /// A `.type` value, if one exists. Specified using `#[starlark(type = "the_type")]`.
@@ -230,7 +241,9 @@ impl<'v> StarlarkValue<'v> for NativeFunction {
         args: &Arguments<'v, '_>,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>> {
-        self.function.invoke(eval, args)
+        let res = self.function.invoke(eval, args)?;
+        eval.propagate_provenance_to_call(None, args, res);
+        Ok(res)
     }
 
     fn get_attr(&self, attribute: &str, _heap: &'v Heap) -> Option<Value<'v>> {
@@ -289,7 +302,14 @@ impl<'v> StarlarkValue<'v> for NativeMethod {
         eval: &mut Evaluator<'v, '_>,
         _: Private,
     ) -> anyhow::Result<Value<'v>> {
-        self.function.invoke(eval, this, args)
+        let res = self.function.invoke(eval, this, args)?;
+        eval.propagate_provenance_to_call(Some(this), args, res);
+        eval.explain_mutation(
+            this,
+            || BindingEventKind::MethodCall(self.name.clone()),
+            |eval| eval.call_stack_top_location(),
+        );
+        Ok(res)
     }
 
     fn documentation(&self) -> Option<DocItem> {
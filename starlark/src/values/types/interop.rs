@@ -0,0 +1,259 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! [`AllocValue`] and [`UnpackValue`] implementations for common Rust types
+//! which have no direct Starlark equivalent.
+//!
+//! All of them are represented as Starlark strings, so that values produced by one host
+//! can be consumed by another one without sharing any custom value types:
+//!
+//! * [`PathBuf`]: the path, with non-UTF-8 sequences replaced with `U+FFFD`.
+//! * `chrono::DateTime<Utc>` and `chrono::DateTime<FixedOffset>` (feature `chrono`):
+//!   RFC 3339, e.g. `"2022-11-21T10:30:00+00:00"`.
+//! * `chrono::NaiveDate` (feature `chrono`): ISO 8601 date, e.g. `"2022-11-21"`.
+//! * `uuid::Uuid` (feature `uuid`): lowercase hyphenated,
+//!   e.g. `"67e55044-10b1-426f-9247-bb680e5fe0c8"`.
+//! * `rust_decimal::Decimal` (feature `rust_decimal`): the number, e.g. `"12.50"`,
+//!   a string rather than a `float` to keep the precision.
+//!
+//! Unpacking parses the same representation, and for date times accepts any offset.
+//! `Decimal` can also be unpacked from an `int`.
+
+use std::path::PathBuf;
+
+use crate::values::type_repr::StarlarkTypeRepr;
+use crate::values::AllocValue;
+use crate::values::Heap;
+use crate::values::UnpackValue;
+use crate::values::Value;
+
+/// Type represented as a Starlark string.
+trait StringRepr: Sized {
+    fn format(&self) -> String;
+    fn parse(s: &str) -> Option<Self>;
+}
+
+/// Implement [`AllocValue`] and [`UnpackValue`] for a type implementing [`StringRepr`].
+macro_rules! impl_string_repr {
+    ($ty:ty) => {
+        impl StarlarkTypeRepr for $ty {
+            fn starlark_type_repr() -> String {
+                String::starlark_type_repr()
+            }
+        }
+
+        impl<'v> AllocValue<'v> for $ty {
+            fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+                heap.alloc_str(&StringRepr::format(&self)).to_value()
+            }
+        }
+
+        impl<'v> UnpackValue<'v> for $ty {
+            fn expected() -> String {
+                concat!("str representing ", stringify!($ty)).to_owned()
+            }
+
+            fn unpack_value(value: Value<'v>) -> Option<Self> {
+                <$ty as StringRepr>::parse(value.unpack_str()?)
+            }
+        }
+    };
+}
+
+impl StringRepr for PathBuf {
+    fn format(&self) -> String {
+        self.to_string_lossy().into_owned()
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        Some(PathBuf::from(s))
+    }
+}
+
+impl_string_repr!(PathBuf);
+
+#[cfg(feature = "chrono")]
+mod chrono_impls {
+    use chrono::DateTime;
+    use chrono::FixedOffset;
+    use chrono::NaiveDate;
+    use chrono::Utc;
+
+    use super::*;
+
+    impl StringRepr for DateTime<Utc> {
+        fn format(&self) -> String {
+            self.to_rfc3339()
+        }
+
+        fn parse(s: &str) -> Option<Self> {
+            Some(DateTime::parse_from_rfc3339(s).ok()?.with_timezone(&Utc))
+        }
+    }
+
+    impl StringRepr for DateTime<FixedOffset> {
+        fn format(&self) -> String {
+            self.to_rfc3339()
+        }
+
+        fn parse(s: &str) -> Option<Self> {
+            DateTime::parse_from_rfc3339(s).ok()
+        }
+    }
+
+    impl StringRepr for NaiveDate {
+        fn format(&self) -> String {
+            self.format("%Y-%m-%d").to_string()
+        }
+
+        fn parse(s: &str) -> Option<Self> {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+        }
+    }
+
+    impl_string_repr!(DateTime<Utc>);
+    impl_string_repr!(DateTime<FixedOffset>);
+    impl_string_repr!(NaiveDate);
+}
+
+#[cfg(feature = "uuid")]
+mod uuid_impls {
+    use uuid::Uuid;
+
+    use super::*;
+
+    impl StringRepr for Uuid {
+        fn format(&self) -> String {
+            self.hyphenated().to_string()
+        }
+
+        fn parse(s: &str) -> Option<Self> {
+            Uuid::parse_str(s).ok()
+        }
+    }
+
+    impl_string_repr!(Uuid);
+}
+
+#[cfg(feature = "rust_decimal")]
+mod decimal_impls {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+
+    use super::*;
+
+    impl StarlarkTypeRepr for Decimal {
+        fn starlark_type_repr() -> String {
+            String::starlark_type_repr()
+        }
+    }
+
+    impl<'v> AllocValue<'v> for Decimal {
+        fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+            heap.alloc_str(&self.to_string()).to_value()
+        }
+    }
+
+    impl<'v> UnpackValue<'v> for Decimal {
+        fn expected() -> String {
+            "int or str representing Decimal".to_owned()
+        }
+
+        fn unpack_value(value: Value<'v>) -> Option<Self> {
+            if let Some(s) = value.unpack_str() {
+                Decimal::from_str(s).ok()
+            } else {
+                Some(Decimal::from(i64::unpack_value(value)?))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::values::Heap;
+    use crate::values::UnpackValue;
+
+    #[test]
+    fn test_path_buf() {
+        let heap = Heap::new();
+        let v = heap.alloc(PathBuf::from("a/b.txt"));
+        assert_eq!(Some("a/b.txt"), v.unpack_str());
+        assert_eq!(Some(PathBuf::from("a/b.txt")), PathBuf::unpack_value(v));
+        assert_eq!(None, PathBuf::unpack_value(heap.alloc(1)));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        use chrono::DateTime;
+        use chrono::FixedOffset;
+        use chrono::NaiveDate;
+        use chrono::TimeZone;
+        use chrono::Utc;
+
+        let heap = Heap::new();
+        let d = Utc.with_ymd_and_hms(2022, 11, 21, 10, 30, 0).unwrap();
+        let v = heap.alloc(d);
+        assert_eq!(Some("2022-11-21T10:30:00+00:00"), v.unpack_str());
+        assert_eq!(Some(d), DateTime::<Utc>::unpack_value(v));
+        let v = heap.alloc("2022-11-21T12:30:00+02:00");
+        assert_eq!(Some(d), DateTime::<Utc>::unpack_value(v));
+        let offset = DateTime::<FixedOffset>::unpack_value(v).unwrap();
+        assert_eq!(7200, offset.offset().local_minus_utc());
+        assert_eq!(None, DateTime::<Utc>::unpack_value(heap.alloc("yesterday")));
+
+        let d = NaiveDate::from_ymd_opt(2022, 11, 21).unwrap();
+        let v = heap.alloc(d);
+        assert_eq!(Some("2022-11-21"), v.unpack_str());
+        assert_eq!(Some(d), NaiveDate::unpack_value(v));
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn test_uuid() {
+        use uuid::Uuid;
+
+        let heap = Heap::new();
+        let s = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let u = Uuid::parse_str(s).unwrap();
+        let v = heap.alloc(u);
+        assert_eq!(Some(s), v.unpack_str());
+        assert_eq!(Some(u), Uuid::unpack_value(v));
+        assert_eq!(None, Uuid::unpack_value(heap.alloc("67e55044")));
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn test_decimal() {
+        use rust_decimal::Decimal;
+
+        let heap = Heap::new();
+        let d = Decimal::new(1250, 2);
+        let v = heap.alloc(d);
+        assert_eq!(Some("12.50"), v.unpack_str());
+        assert_eq!(Some(d), Decimal::unpack_value(v));
+        assert_eq!(
+            Some(Decimal::from(17)),
+            Decimal::unpack_value(heap.alloc(17))
+        );
+        assert_eq!(None, Decimal::unpack_value(heap.alloc(1.5)));
+    }
+}
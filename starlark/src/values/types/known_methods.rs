@@ -21,6 +21,7 @@ use once_cell::sync::Lazy;
 
 use crate::environment::Methods;
 use crate::eval::Arguments;
+use crate::eval::BindingEventKind;
 use crate::eval::Evaluator;
 use crate::values::dict::value::dict_methods;
 use crate::values::function::NativeMeth;
@@ -55,7 +56,14 @@ impl KnownMethod {
         args: &Arguments<'v, '_>,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>> {
-        self.imp.invoke(eval, this, args)
+        let res = self.imp.invoke(eval, this, args)?;
+        eval.propagate_provenance_to_call(Some(this), args, res);
+        eval.explain_mutation(
+            this,
+            || BindingEventKind::MethodCall(self.method.name.clone()),
+            |eval| eval.call_stack_top_location(),
+        );
+        Ok(res)
     }
 }
 
@@ -0,0 +1,377 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Build target labels like `@repo//package:name`, a type [`Label`] which
+//! build systems embedding Starlark can share.
+//!
+//! Labels are interned for the whole process: each distinct label is stored once,
+//! a label value is a single pointer, and labels are compared by pointer.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::sync::Mutex;
+
+use allocative::Allocative;
+use dupe::Dupe;
+use gazebo::any::ProvidesStaticType;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate as starlark;
+use crate::collections::StarlarkHasher;
+use crate::environment::Methods;
+use crate::environment::MethodsBuilder;
+use crate::environment::MethodsStatic;
+use crate::values::StarlarkValue;
+use crate::values::Value;
+use crate::values::ValueError;
+
+#[derive(Debug, thiserror::Error)]
+enum LabelError {
+    #[error("Invalid label `{0}`: {1}")]
+    Invalid(String, &'static str),
+}
+
+/// Canonical text of a label with the positions of its components.
+#[derive(Debug)]
+struct LabelData {
+    /// `@repo//package:name`, or `//package:name` for the main repository.
+    text: Box<str>,
+    /// Offset of `//`.
+    package_start: usize,
+    /// Offset of `:`.
+    name_start: usize,
+}
+
+/// Interned labels by their canonical text. Labels are never freed.
+static LABELS: Lazy<Mutex<HashMap<&'static str, &'static LabelData>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A label of a build target, `@repo//package:name`.
+///
+/// Labels are canonicalized (`//foo` is `//foo:foo`, `@//foo:bar` is `//foo:bar`) and
+/// interned, so equal labels are the same pointer and comparing them is cheap.
+/// In Starlark, labels are created with `label()`, their `str()` is the canonical text,
+/// and they have the attributes `repository` (`None` for the main repository),
+/// `package` and `name`, and the method `relative(label)`.
+#[derive(ProvidesStaticType, Clone, Copy, Dupe, StarlarkDocs, Allocative)]
+#[starlark_docs(builtin = "extension")]
+pub struct Label(#[allocative(skip)] &'static LabelData);
+
+starlark_simple_value!(Label);
+
+fn check_segments(label: &str, path: &str, what: &'static str) -> Result<(), LabelError> {
+    if path
+        .split('/')
+        .any(|x| x.is_empty() || x == "." || x == "..")
+    {
+        return Err(LabelError::Invalid(label.to_owned(), what));
+    }
+    Ok(())
+}
+
+impl Label {
+    /// The result of calling `type()` on a label.
+    pub const TYPE: &'static str = "label";
+
+    /// Parse an absolute label: `@repo//package:name`, `//package:name`,
+    /// or `//package` which is short for `//package:last_component_of_package`.
+    pub fn parse(label: &str) -> anyhow::Result<Label> {
+        let invalid = |what| LabelError::Invalid(label.to_owned(), what);
+        let (repository, rest) = match label.strip_prefix('@') {
+            Some(rest) => match rest.find("//") {
+                Some(i) => (&rest[..i], &rest[i..]),
+                None => return Err(invalid("expected `//` after the repository").into()),
+            },
+            None => ("", label),
+        };
+        if !repository
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || matches!(x, '_' | '-' | '.'))
+        {
+            return Err(invalid("invalid repository name").into());
+        }
+        let rest = match rest.strip_prefix("//") {
+            Some(rest) => rest,
+            None => return Err(invalid("expected an absolute label starting with `//`").into()),
+        };
+        let (package, name) = match rest.split_once(':') {
+            Some((package, name)) => (package, name),
+            None => (rest, rest.rsplit('/').next().unwrap_or_default()),
+        };
+        if !package.is_empty() {
+            check_segments(label, package, "invalid package")?;
+        }
+        if name.is_empty() {
+            return Err(invalid("empty target name").into());
+        }
+        if name.contains(':') {
+            return Err(invalid("target name contains `:`").into());
+        }
+        check_segments(label, name, "invalid target name")?;
+        Ok(Self::intern(repository, package, name))
+    }
+
+    /// Parse a label relative to this one: `:name` or `name` is a target in the same
+    /// package, other labels are parsed with [`Label::parse`].
+    pub fn relative(self, label: &str) -> anyhow::Result<Label> {
+        if label.starts_with("//") || label.starts_with('@') {
+            return Label::parse(label);
+        }
+        let name = label.strip_prefix(':').unwrap_or(label);
+        let mut absolute = String::new();
+        if let Some(repository) = self.repository() {
+            absolute.push('@');
+            absolute.push_str(repository);
+        }
+        absolute.push_str("//");
+        absolute.push_str(self.package());
+        absolute.push(':');
+        absolute.push_str(name);
+        Label::parse(&absolute)
+            .map_err(|_| LabelError::Invalid(label.to_owned(), "invalid relative label").into())
+    }
+
+    fn intern(repository: &str, package: &str, name: &str) -> Label {
+        let mut text = String::with_capacity(repository.len() + package.len() + name.len() + 4);
+        if !repository.is_empty() {
+            text.push('@');
+            text.push_str(repository);
+        }
+        let package_start = text.len();
+        text.push_str("//");
+        text.push_str(package);
+        let name_start = text.len();
+        text.push(':');
+        text.push_str(name);
+
+        let mut labels = LABELS.lock().unwrap();
+        if let Some(data) = labels.get(text.as_str()) {
+            return Label(data);
+        }
+        let data: &'static LabelData = Box::leak(Box::new(LabelData {
+            text: text.into_boxed_str(),
+            package_start,
+            name_start,
+        }));
+        labels.insert(&data.text, data);
+        Label(data)
+    }
+
+    /// The canonical text of the label.
+    pub fn as_str(self) -> &'static str {
+        &self.0.text
+    }
+
+    /// The repository, `None` for the main repository.
+    pub fn repository(self) -> Option<&'static str> {
+        match self.0.package_start {
+            0 => None,
+            i => Some(&self.0.text[1..i]),
+        }
+    }
+
+    /// The package, empty for the root package.
+    pub fn package(self) -> &'static str {
+        &self.0.text[self.0.package_start + 2..self.0.name_start]
+    }
+
+    /// The target name.
+    pub fn name(self) -> &'static str {
+        &self.0.text[self.0.name_start + 1..]
+    }
+
+    fn components(self) -> (Option<&'static str>, &'static str, &'static str) {
+        (self.repository(), self.package(), self.name())
+    }
+}
+
+impl PartialEq for Label {
+    fn eq(&self, other: &Label) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Label {}
+
+impl Hash for Label {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(self.0, state)
+    }
+}
+
+impl PartialOrd for Label {
+    fn partial_cmp(&self, other: &Label) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Label {
+    /// Labels are ordered by repository, package and name.
+    fn cmp(&self, other: &Label) -> Ordering {
+        if self == other {
+            Ordering::Equal
+        } else {
+            self.components().cmp(&other.components())
+        }
+    }
+}
+
+impl fmt::Debug for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Label").field(&self.as_str()).finish()
+    }
+}
+
+impl Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Label {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'v> StarlarkValue<'v> for Label {
+    starlark_type!(Label::TYPE);
+
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(label_methods)
+    }
+
+    fn equals(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        Ok(Label::from_value(other) == Some(self))
+    }
+
+    fn compare(&self, other: Value<'v>) -> anyhow::Result<Ordering> {
+        match Label::from_value(other) {
+            Some(other) => Ok(self.cmp(other)),
+            None => ValueError::unsupported_with(self, "compare", other),
+        }
+    }
+
+    fn write_hash(&self, hasher: &mut StarlarkHasher) -> anyhow::Result<()> {
+        // Hash the text rather than the pointer, so hashes are the same across processes.
+        self.as_str().hash(hasher);
+        Ok(())
+    }
+}
+
+#[starlark_module]
+fn label_methods(builder: &mut MethodsBuilder) {
+    /// The repository of the label, `None` for the main repository.
+    #[starlark(attribute)]
+    fn repository<'v>(this: &Label) -> anyhow::Result<Option<&'v str>> {
+        Ok(this.repository())
+    }
+
+    /// The package of the label, an empty string for the root package.
+    #[starlark(attribute)]
+    fn package<'v>(this: &Label) -> anyhow::Result<&'v str> {
+        Ok(this.package())
+    }
+
+    /// The target name of the label.
+    #[starlark(attribute)]
+    fn name<'v>(this: &Label) -> anyhow::Result<&'v str> {
+        Ok(this.name())
+    }
+
+    /// Resolve a label relative to this one, `:name` is a target in the same package.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// label("//foo:bar").relative(":baz") == label("//foo:baz")
+    /// # "#);
+    /// ```
+    fn relative(this: &Label, #[starlark(require = pos)] label: &str) -> anyhow::Result<Label> {
+        this.relative(label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+    use crate::values::label::Label;
+
+    #[test]
+    fn test_parse() {
+        let label = Label::parse("@repo//foo/bar:baz/qux").unwrap();
+        assert_eq!(Some("repo"), label.repository());
+        assert_eq!("foo/bar", label.package());
+        assert_eq!("baz/qux", label.name());
+        assert_eq!("@repo//foo/bar:baz/qux", label.as_str());
+
+        assert_eq!("//foo/bar:bar", Label::parse("//foo/bar").unwrap().as_str());
+        assert_eq!("//:x", Label::parse("@//:x").unwrap().as_str());
+        assert_eq!(None, Label::parse("//:x").unwrap().repository());
+        assert_eq!("", Label::parse("//:x").unwrap().package());
+
+        for bad in [
+            "", "foo", ":x", "//", "//foo/:x", "//foo:", "//a/../b", "@r:x", "@r!//a",
+        ] {
+            assert!(Label::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_interned() {
+        let x = Label::parse("//interned:x").unwrap();
+        assert!(std::ptr::eq(
+            x.0,
+            Label::parse("//interned")
+                .unwrap()
+                .relative(":x")
+                .unwrap()
+                .0
+        ));
+        assert!(x < Label::parse("//interned:y").unwrap());
+        assert!(x < Label::parse("@a//interned:x").unwrap());
+    }
+
+    #[test]
+    fn test_starlark() {
+        assert::all_true(
+            r#"
+label("//foo") == label("//foo:foo")
+label("//foo") != label("//foo:bar")
+str(label("@//foo")) == "//foo:foo"
+type(label("//foo")) == "label"
+label("@r//a/b:c").repository == "r"
+label("//a/b:c").repository == None
+label("//a/b:c").package == "a/b"
+label("//a/b:c").name == "c"
+label("//a:b").relative("c") == label("//a:c")
+label("//a:b").relative("//d") == label("//d:d")
+sorted([label("//b"), label("//a:z"), label("//a")]) == [label(x) for x in ["//a", "//a:z", "//b"]]
+{label("//a"): 1}[label("//a:a")] == 1
+"#,
+        );
+        assert::fail("label(':x')", "Invalid label `:x`");
+    }
+}
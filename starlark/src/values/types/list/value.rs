@@ -44,6 +44,7 @@ use crate::values::comparison::equals_slice;
 use crate::values::error::ValueError;
 use crate::values::index::apply_slice;
 use crate::values::index::convert_index;
+use crate::values::index::convert_slice_indices;
 use crate::values::list::ListRef;
 use crate::values::type_repr::StarlarkTypeRepr;
 use crate::values::AllocFrozenValue;
@@ -323,10 +324,24 @@ impl Display for FrozenListData {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+enum ListError {
+    #[error("Cannot assign {0} values to extended slice of length {1}")]
+    ExtendedSliceLength(usize, usize),
+}
+
 // This trait need to be `pub(crate)` because `ListGen<T>` is.
 pub(crate) trait ListLike<'v>: Debug + Allocative {
     fn content(&self) -> &[Value<'v>];
     fn set_at(&self, i: usize, v: Value<'v>) -> anyhow::Result<()>;
+    /// Replace elements `start..stop` with `values`.
+    fn splice(
+        &self,
+        start: usize,
+        stop: usize,
+        values: &[Value<'v>],
+        heap: &'v Heap,
+    ) -> anyhow::Result<()>;
     fn iterate<'a>(&'a self) -> Box<dyn Iterator<Item = Value<'v>> + 'a>
     where
         'v: 'a;
@@ -347,6 +362,24 @@ impl<'v> ListLike<'v> for ListData<'v> {
         Ok(())
     }
 
+    fn splice(
+        &self,
+        start: usize,
+        stop: usize,
+        values: &[Value<'v>],
+        heap: &'v Heap,
+    ) -> anyhow::Result<()> {
+        self.check_can_mutate()?;
+        let content = self.content();
+        let mut new_content = Vec::with_capacity(content.len() - (stop - start) + values.len());
+        new_content.extend_from_slice(&content[..start]);
+        new_content.extend_from_slice(values);
+        new_content.extend_from_slice(&content[stop..]);
+        self.clear();
+        self.extend(new_content, heap);
+        Ok(())
+    }
+
     fn iterate<'a>(&'a self) -> Box<dyn Iterator<Item = Value<'v>> + 'a>
     where
         'v: 'a,
@@ -371,6 +404,16 @@ impl<'v> ListLike<'v> for FrozenListData {
         Err(ValueError::CannotMutateImmutableValue.into())
     }
 
+    fn splice(
+        &self,
+        _start: usize,
+        _stop: usize,
+        _values: &[Value<'v>],
+        _heap: &'v Heap,
+    ) -> anyhow::Result<()> {
+        Err(ValueError::CannotMutateImmutableValue.into())
+    }
+
     fn iterate<'a>(&'a self) -> Box<dyn Iterator<Item = Value<'v>> + 'a>
     where
         'v: 'a,
@@ -518,6 +561,44 @@ where
         let i = convert_index(index, self.0.content().len() as i32)? as usize;
         self.0.set_at(i, alloc_value)
     }
+
+    fn set_slice(
+        &self,
+        start: Option<Value<'v>>,
+        stop: Option<Value<'v>>,
+        stride: Option<Value<'v>>,
+        alloc_value: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<()> {
+        let (start, stop, stride) =
+            convert_slice_indices(self.0.content().len() as i32, start, stop, stride)?;
+        // Collect first: the assigned value may be this list.
+        let values = alloc_value.iterate_collect(heap)?;
+        if stride == 1 {
+            let stop = cmp::max(start, stop);
+            return self.0.splice(start as usize, stop as usize, &values, heap);
+        }
+
+        let indices: Vec<usize> = if stride > 0 {
+            (start..stop)
+                .step_by(stride as usize)
+                .map(|i| i as usize)
+                .collect()
+        } else {
+            (stop + 1..=start)
+                .rev()
+                .step_by(stride.unsigned_abs() as usize)
+                .map(|i| i as usize)
+                .collect()
+        };
+        if indices.len() != values.len() {
+            return Err(ListError::ExtendedSliceLength(values.len(), indices.len()).into());
+        }
+        for (i, v) in indices.into_iter().zip(values) {
+            self.0.set_at(i, v)?;
+        }
+        Ok(())
+    }
 }
 
 impl<'v, T: ListLike<'v>> Serialize for ListGen<T> {
@@ -563,6 +644,39 @@ v == [1, 1, [2, 3]]
         );
     }
 
+    #[test]
+    fn test_set_slice() {
+        assert::is_true(
+            r#"
+v = [0, 1, 2, 3, 4]
+v[1:3] = ["a", "b", "c"]
+v == [0, "a", "b", "c", 3, 4]
+"#,
+        );
+        assert::is_true(
+            r#"
+v = [0, 1, 2, 3, 4]
+v[3:1] = ["x"]
+v[:] = v + v[:1]
+v == [0, 1, 2, "x", 3, 4, 0]
+"#,
+        );
+        assert::is_true(
+            r#"
+v = [0, 1, 2, 3, 4]
+v[::2] = ["a", "b", "c"]
+v[::-3] = [10, 20]
+v == ["a", 20, "b", 3, 10]
+"#,
+        );
+        assert::fail("v = [0, 1, 2]\nv[::2] = [1]", "extended slice of length 2");
+        assert::fail("v = (0, 1, 2)\nv[1:2] = [1]", "[::]=");
+        assert::fail(
+            "v = [0, 1, 2]\nv[1:2] += [1]",
+            "cannot be a list, tuple or slice",
+        );
+    }
+
     #[test]
     fn test_arithmetic_on_list() {
         assert::all_true(
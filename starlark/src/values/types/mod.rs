@@ -20,17 +20,23 @@ pub mod any_array;
 pub mod array;
 pub mod bigint;
 pub mod bool;
+pub mod bytes;
 pub mod dict;
 pub mod enumeration;
+pub mod external;
 pub mod float;
 pub mod function;
 pub mod int;
+pub mod interop;
 pub(crate) mod known_methods;
+pub mod label;
 pub mod list;
 pub mod none;
 pub mod range;
 pub mod record;
 pub mod regex;
+pub mod set;
+pub mod stream;
 pub mod string;
 pub mod structs;
 pub mod tuple;
@@ -0,0 +1,313 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `set` type, available with [`LibraryExtension::SetType`](crate::stdlib::LibraryExtension).
+//!
+//! A mutable collection of distinct hashable values, iterated in insertion order.
+
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::cell::RefMut;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+
+use allocative::Allocative;
+use gazebo::any::ProvidesStaticType;
+use gazebo::cell::ARef;
+use gazebo::coerce::coerce;
+use gazebo::coerce::Coerce;
+use gazebo::display::display_container;
+use serde::Serialize;
+
+use crate as starlark;
+use crate::collections::SmallMap;
+use crate::environment::Methods;
+use crate::environment::MethodsBuilder;
+use crate::environment::MethodsStatic;
+use crate::values::error::ValueError;
+use crate::values::iter::ARefIterator;
+use crate::values::none::NoneType;
+use crate::values::type_repr::StarlarkTypeRepr;
+use crate::values::AllocValue;
+use crate::values::Freeze;
+use crate::values::Freezer;
+use crate::values::FrozenValue;
+use crate::values::Heap;
+use crate::values::StarlarkValue;
+use crate::values::Trace;
+use crate::values::Value;
+use crate::values::ValueLike;
+
+#[derive(Clone, Default, Trace, Debug, ProvidesStaticType, Allocative)]
+pub(crate) struct SetGen<T>(pub(crate) T);
+
+/// Define the set type.
+#[derive(Clone, Default, Trace, Debug, ProvidesStaticType, Allocative)]
+#[repr(transparent)]
+pub struct SetData<'v> {
+    /// The elements of the set, all of them hashable.
+    content: SmallMap<Value<'v>, ()>,
+}
+
+#[derive(Clone, Default, Debug, ProvidesStaticType, Allocative)]
+#[repr(transparent)]
+pub(crate) struct FrozenSetData {
+    content: SmallMap<FrozenValue, ()>,
+}
+
+unsafe impl<'v> Coerce<SetData<'v>> for FrozenSetData {}
+
+impl<'v> SetData<'v> {
+    /// The result of calling `type()` on sets.
+    pub const TYPE: &'static str = "set";
+
+    /// Borrow a value if it is a set.
+    pub fn from_value(x: Value<'v>) -> Option<ARef<'v, SetData<'v>>> {
+        if x.unpack_frozen().is_some() {
+            x.downcast_ref::<SetGen<FrozenSetData>>()
+                .map(|x| ARef::new_ptr(coerce(&x.0)))
+        } else {
+            let ptr = x.downcast_ref::<SetGen<RefCell<SetData<'v>>>>()?;
+            Some(ARef::new_ref(ptr.0.borrow()))
+        }
+    }
+
+    /// Mutably borrow a value if it is a mutable set.
+    pub fn from_value_mut(x: Value<'v>) -> anyhow::Result<RefMut<'v, SetData<'v>>> {
+        match x.downcast_ref::<SetGen<RefCell<SetData<'v>>>>() {
+            Some(x) => {
+                x.0.try_borrow_mut()
+                    .map_err(|_| ValueError::MutationDuringIteration.into())
+            }
+            None if x.downcast_ref::<SetGen<FrozenSetData>>().is_some() => {
+                Err(ValueError::CannotMutateImmutableValue.into())
+            }
+            None => Err(ValueError::IncorrectParameterTypeNamed("this".to_owned()).into()),
+        }
+    }
+
+    /// Number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.content.len()
+    }
+
+    /// Is the set empty?
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    /// Iterate through the elements in insertion order.
+    pub fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = Value<'v>> + 'a {
+        self.content.keys().copied()
+    }
+
+    /// Is the value an element of the set? Fails if the value is unhashable.
+    pub fn contains(&self, x: Value<'v>) -> anyhow::Result<bool> {
+        Ok(self.content.contains_key_hashed_by_value(x.get_hashed()?))
+    }
+
+    /// Add an element, returning `false` if it was already present.
+    pub fn add(&mut self, x: Value<'v>) -> anyhow::Result<bool> {
+        Ok(self.content.insert_hashed(x.get_hashed()?, ()).is_none())
+    }
+
+    /// Remove an element, returning `false` if it was not present.
+    pub fn remove(&mut self, x: Value<'v>) -> anyhow::Result<bool> {
+        Ok(self
+            .content
+            .shift_remove_hashed(x.get_hashed()?.as_ref())
+            .is_some())
+    }
+}
+
+impl<'v> Display for SetData<'v> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            write!(f, "set()")
+        } else {
+            display_container(f, "set([", "])", self.iter())
+        }
+    }
+}
+
+impl<'v, T: SetLike<'v>> Display for SetGen<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&*self.0.content(), f)
+    }
+}
+
+impl<'v> StarlarkTypeRepr for SetData<'v> {
+    fn starlark_type_repr() -> String {
+        SetData::TYPE.to_owned()
+    }
+}
+
+impl<'v> AllocValue<'v> for SetData<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex(SetGen(RefCell::new(self)))
+    }
+}
+
+impl<'v> Freeze for SetGen<RefCell<SetData<'v>>> {
+    type Frozen = SetGen<FrozenSetData>;
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        let content = self.0.into_inner().content.freeze(freezer)?;
+        Ok(SetGen(FrozenSetData { content }))
+    }
+}
+
+pub(crate) trait SetLike<'v>: Debug + Allocative {
+    fn content(&self) -> ARef<'_, SetData<'v>>;
+}
+
+impl<'v> SetLike<'v> for RefCell<SetData<'v>> {
+    fn content(&self) -> ARef<'_, SetData<'v>> {
+        ARef::new_ref(Ref::map(self.borrow(), |x| x))
+    }
+}
+
+impl<'v> SetLike<'v> for FrozenSetData {
+    fn content(&self) -> ARef<'_, SetData<'v>> {
+        ARef::new_ptr(coerce(self))
+    }
+}
+
+impl<'v, T: SetLike<'v> + 'v> StarlarkValue<'v> for SetGen<T>
+where
+    Self: ProvidesStaticType,
+{
+    starlark_type!(SetData::TYPE);
+
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(set_methods)
+    }
+
+    fn collect_repr(&self, r: &mut String) {
+        let content = self.0.content();
+        if content.is_empty() {
+            r.push_str("set()");
+            return;
+        }
+        r.push_str("set([");
+        for (i, x) in content.iter().enumerate() {
+            if i != 0 {
+                r.push_str(", ");
+            }
+            x.collect_repr(r);
+        }
+        r.push_str("])");
+    }
+
+    fn collect_repr_cycle(&self, collector: &mut String) {
+        collector.push_str("set(...)");
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.0.content().is_empty()
+    }
+
+    fn equals(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        match SetData::from_value(other) {
+            None => Ok(false),
+            Some(other) => {
+                let content = self.0.content();
+                // Elements are hashable, so lookups can't fail.
+                Ok(content.len() == other.len()
+                    && content.iter().all(|x| other.contains(x).unwrap_or(false)))
+            }
+        }
+    }
+
+    fn length(&self) -> anyhow::Result<i32> {
+        Ok(self.0.content().len() as i32)
+    }
+
+    fn is_in(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        self.0.content().contains(other)
+    }
+
+    fn iterate<'a>(
+        &'a self,
+        _heap: &'v Heap,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = Value<'v>> + 'a>>
+    where
+        'v: 'a,
+    {
+        Ok(Box::new(ARefIterator::new(self.0.content(), |x| x.iter())))
+    }
+
+    fn with_iterator(
+        &self,
+        _heap: &'v Heap,
+        f: &mut dyn FnMut(&mut dyn Iterator<Item = Value<'v>>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        f(&mut self.0.content().iter())
+    }
+}
+
+impl<'v, T: SetLike<'v>> Serialize for SetGen<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_seq(self.0.content().iter())
+    }
+}
+
+#[starlark_module]
+fn set_methods(builder: &mut MethodsBuilder) {
+    /// Add an element to the set, doing nothing if it is already present.
+    ///
+    /// Fails if the set is frozen or being iterated, or if the element is unhashable.
+    fn add<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        SetData::from_value_mut(this)?.add(value)?;
+        Ok(NoneType)
+    }
+
+    /// Remove an element from the set, failing if it is not present.
+    fn remove<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        if SetData::from_value_mut(this)?.remove(value)? {
+            Ok(NoneType)
+        } else {
+            Err(ValueError::KeyNotFound(value.to_repr()).into())
+        }
+    }
+
+    /// Return a new set with the elements of this set followed by the elements
+    /// of each of `others`, which can be any iterables.
+    fn union<'v>(
+        this: Value<'v>,
+        #[starlark(args)] others: Vec<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<SetData<'v>> {
+        let mut res = SetData::clone(&SetData::from_value(this).unwrap());
+        for other in others {
+            for x in other.iterate(heap)? {
+                res.add(x)?;
+            }
+        }
+        Ok(res)
+    }
+}
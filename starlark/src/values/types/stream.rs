@@ -0,0 +1,190 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Values produced lazily by a host iterator, so that natives can return
+//! large sequences without materializing them.
+//!
+//! A stream is allocated with [`Heap::alloc_stream`](crate::values::Heap::alloc_stream),
+//! and can be iterated in Starlark, e.g. with a `for` loop or `list()`.
+//! The host iterator is advanced only when Starlark asks for the next element,
+//! and is dropped once it is exhausted.
+//!
+//! A stream can be iterated only once: a second iteration continues where the first
+//! one stopped. A stream has no length, and cannot be frozen, so it is an error
+//! to leave one in a module global. Convert it to a `list` to keep the elements.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+
+use allocative::Allocative;
+use gazebo::any::ProvidesStaticType;
+
+use crate as starlark;
+use crate::values::Heap;
+use crate::values::NoSerialize;
+use crate::values::StarlarkValue;
+use crate::values::Trace;
+use crate::values::Value;
+use crate::values::ValueLike;
+
+/// Produce the next element of a stream, or `None` when it is exhausted.
+type StreamNext = Box<dyn for<'v> FnMut(&'v Heap) -> Option<Value<'v>>>;
+
+/// A value yielding elements of a host iterator on demand, see the [module docs](self).
+#[derive(Trace, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct Stream {
+    /// `None` once exhausted.
+    #[trace(unsafe_ignore)]
+    #[allocative(skip)]
+    next: RefCell<Option<StreamNext>>,
+}
+
+impl Stream {
+    /// The type of values returned by [`Heap::alloc_stream`].
+    pub const TYPE: &'static str = "stream";
+
+    pub(crate) fn new(next: StreamNext) -> Self {
+        Stream {
+            next: RefCell::new(Some(next)),
+        }
+    }
+
+    /// Obtain the [`Stream`] from a [`Value`], if it is one.
+    pub fn from_value<'v>(x: Value<'v>) -> Option<&'v Stream> {
+        x.downcast_ref::<Stream>()
+    }
+
+    /// Whether the host iterator was exhausted.
+    pub fn is_exhausted(&self) -> bool {
+        self.next.borrow().is_none()
+    }
+
+    fn next<'v>(&self, heap: &'v Heap) -> Option<Value<'v>> {
+        let mut next = self.next.borrow_mut();
+        let res = next.as_mut()?(heap);
+        if res.is_none() {
+            *next = None;
+        }
+        res
+    }
+}
+
+impl Debug for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stream")
+            .field("exhausted", &self.is_exhausted())
+            .finish()
+    }
+}
+
+impl Display for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_exhausted() {
+            write!(f, "<{} (exhausted)>", Self::TYPE)
+        } else {
+            write!(f, "<{}>", Self::TYPE)
+        }
+    }
+}
+
+impl<'v> StarlarkValue<'v> for Stream {
+    starlark_type!(Stream::TYPE);
+
+    fn iterate<'a>(
+        &'a self,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = Value<'v>> + 'a>>
+    where
+        'v: 'a,
+    {
+        Ok(Box::new(std::iter::from_fn(move || self.next(heap))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use starlark_derive::starlark_module;
+
+    use crate as starlark;
+    use crate::assert::Assert;
+    use crate::environment::GlobalsBuilder;
+    use crate::environment::Module;
+    use crate::values::stream::Stream;
+    use crate::values::Heap;
+    use crate::values::Value;
+
+    #[starlark_module]
+    fn rows(builder: &mut GlobalsBuilder) {
+        fn rows<'v>(n: i32, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+            Ok(heap.alloc_stream((0..n).map(|i| format!("row{}", i))))
+        }
+    }
+
+    #[test]
+    fn test_stream() {
+        let mut a = Assert::new();
+        a.globals_add(rows);
+        a.eq("['row0', 'row1']", "list(rows(2))");
+        a.eq("'stream'", "type(rows(2))");
+        a.eq("'<stream>'", "repr(rows(2))");
+        a.pass(
+            r#"
+s = rows(1000000)
+found = None
+for x in s:
+    if x == "row2":
+        found = x
+        break
+assert_eq(found, "row2")
+assert_eq([x for x in s][:2], ["row3", "row4"])
+assert_eq(list(s), [])
+assert_eq(repr(s), "<stream (exhausted)>")
+s = None
+"#,
+        );
+        a.fail("len(rows(1))", "len()");
+    }
+
+    #[test]
+    fn test_stream_lazy() {
+        let produced = Rc::new(Cell::new(0));
+        let heap = Heap::new();
+        let counter = produced.clone();
+        let s = heap.alloc_stream((0..).inspect(move |_| counter.set(counter.get() + 1)));
+        let taken: Vec<i32> = s
+            .iterate(&heap)
+            .unwrap()
+            .take(3)
+            .map(|x| x.unpack_int().unwrap())
+            .collect();
+        assert_eq!(vec![0, 1, 2], taken);
+        assert_eq!(3, produced.get());
+        assert!(!Stream::from_value(s).unwrap().is_exhausted());
+    }
+
+    #[test]
+    fn test_stream_cannot_be_frozen() {
+        let module = Module::new();
+        module.set("s", module.heap().alloc_stream(0..3));
+        assert!(module.freeze().is_err());
+    }
+}
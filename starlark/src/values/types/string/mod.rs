@@ -92,7 +92,8 @@ pub const STRING_TYPE: &str = "string";
 pub(crate) struct StarlarkStrN<const N: usize> {
     // Lazily-initialized cached hash code.
     pub(crate) hash: atomic::AtomicU32,
-    // Length in bytes.
+    // Length in bytes, with the top bit set if the body is stored externally
+    // (see `StarlarkStr::EXTERNAL_FLAG`).
     pub(crate) len: u32,
     // Followed by an unsized block, meaning this type is unsized.
     // But we can't mark it as such since we really want &StarlarkStr to
@@ -119,10 +120,27 @@ impl Deref for StarlarkStr {
 
 impl PartialEq for StarlarkStr {
     fn eq(&self, other: &Self) -> bool {
-        self.as_aligned_padded_str() == other.as_aligned_padded_str()
+        if self.is_external() || other.is_external() {
+            // External bodies are not padded, so fall back to plain comparison.
+            self.as_str() == other.as_str()
+        } else {
+            self.as_aligned_padded_str() == other.as_aligned_padded_str()
+        }
     }
 }
 
+/// Who owns the body of an externally stored string.
+#[derive(Copy, Clone, Dupe, Debug, Eq, PartialEq)]
+#[repr(usize)]
+pub(crate) enum StarlarkStrExternalKind {
+    /// Body is `&'static str`.
+    Static = 0,
+    /// Body is an `Arc<str>`, a strong reference to which is held by the owning heap.
+    Arc = 1,
+    /// Body is owned by the embedder, which guarantees it outlives the heap.
+    Borrowed = 2,
+}
+
 impl Eq for StarlarkStr {}
 
 impl PartialOrd for StarlarkStr {
@@ -154,10 +172,19 @@ impl StarlarkStr {
         (len + mem::size_of::<usize>() - 1) / mem::size_of::<usize>()
     }
 
+    /// Bit of `len` which marks the string body as stored outside of the heap.
+    /// External strings store a pointer to the data and a `StarlarkStrExternalKind`
+    /// in the first two words of the body.
+    const EXTERNAL_FLAG: u32 = 1 << 31;
+
+    /// Number of body words used by external strings.
+    pub(crate) const EXTERNAL_PAYLOAD_LEN: usize = 2;
+
     /// Unsafe because if you do `unpack` on this it will blow up
     #[inline]
     pub(crate) const unsafe fn new(len: usize, hash: StarlarkHashValue) -> Self {
         assert!(len as u32 as usize == len, "len overflow");
+        assert!(len as u32 & Self::EXTERNAL_FLAG == 0, "len overflow");
         StarlarkStr {
             str: StarlarkStrN {
                 hash: atomic::AtomicU32::new(hash.get()),
@@ -167,16 +194,56 @@ impl StarlarkStr {
         }
     }
 
+    /// Header of a string whose body is stored outside of the heap.
+    /// The caller must write the data pointer and the kind into the body.
+    #[inline]
+    pub(crate) unsafe fn new_external(len: usize, hash: StarlarkHashValue) -> Self {
+        let mut s = Self::new(len, hash);
+        s.str.len |= Self::EXTERNAL_FLAG;
+        s
+    }
+
+    /// Is the string body stored outside of the heap?
+    #[inline]
+    pub(crate) fn is_external(&self) -> bool {
+        self.str.len & Self::EXTERNAL_FLAG != 0
+    }
+
+    /// Owner of the body of an external string.
+    #[inline]
+    pub(crate) fn external_kind(&self) -> Option<StarlarkStrExternalKind> {
+        if self.is_external() {
+            Some(match unsafe { *self.str.body.as_ptr().add(1) } {
+                0 => StarlarkStrExternalKind::Static,
+                1 => StarlarkStrExternalKind::Arc,
+                _ => StarlarkStrExternalKind::Borrowed,
+            })
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn data_ptr(&self) -> *const u8 {
+        if self.is_external() {
+            unsafe { *self.str.body.as_ptr() as *const u8 }
+        } else {
+            self.str.body.as_ptr() as *const u8
+        }
+    }
+
     /// Get a Rust string reference from this Starlark string.
     pub fn as_str(&self) -> &str {
         unsafe {
-            let slice = slice::from_raw_parts(self.str.body.as_ptr() as *const u8, self.len());
+            let slice = slice::from_raw_parts(self.data_ptr(), self.len());
             str::from_utf8_unchecked(slice)
         }
     }
 
+    /// Only valid for strings stored inline.
     #[inline]
     pub(crate) fn as_aligned_padded_str(&self) -> AlignedPaddedStr {
+        debug_assert!(!self.is_external());
         unsafe { AlignedPaddedStr::new(self.len(), self.str.body.as_ptr()) }
     }
 
@@ -187,9 +254,7 @@ impl StarlarkStr {
         if hash != 0 {
             StarlarkHashValue::new_unchecked(hash)
         } else {
-            let mut s = StarlarkHasher::new();
-            hash_string_value(self.as_str(), &mut s);
-            let hash = s.finish_small();
+            let hash = StarlarkHashValue::hash_str(self.as_str());
             // If hash is zero, we are unlucky, but it is highly improbable.
             self.str.hash.store(hash.get(), atomic::Ordering::Relaxed);
             hash
@@ -203,12 +268,22 @@ impl StarlarkStr {
 
     /// String length, in bytes.
     pub fn len(&self) -> usize {
-        self.str.len as usize
+        (self.str.len & !Self::EXTERNAL_FLAG) as usize
     }
 
     /// Is this string empty?
     pub fn is_empty(&self) -> bool {
-        self.str.len == 0
+        self.len() == 0
+    }
+
+    /// Number of words this string occupies after the header on the heap.
+    #[inline]
+    pub(crate) fn payload_len(&self) -> usize {
+        if self.is_external() {
+            Self::EXTERNAL_PAYLOAD_LEN
+        } else {
+            Self::payload_len_for_len(self.len())
+        }
     }
 
     pub(crate) fn offset_of_content() -> usize {
@@ -216,10 +291,12 @@ impl StarlarkStr {
     }
 }
 
-/// How to hash a string in a way that is compatible with Value
+/// How to hash a string in a way that is compatible with Value:
+/// the bytes followed by a `0xff` terminator, as [`StarlarkHashValue::hash_str`].
 #[inline]
 pub(crate) fn hash_string_value<H: Hasher>(x: &str, state: &mut H) {
-    x.hash(state)
+    state.write(x.as_bytes());
+    state.write(&[0xff]);
 }
 
 impl Display for StarlarkStr {
@@ -388,7 +465,10 @@ impl Serialize for StarlarkStr {
 #[cfg(test)]
 mod tests {
     use crate::assert;
+    use crate::collections::StarlarkHashValue;
+    use crate::collections::StarlarkHasher;
     use crate::values::index::apply_slice;
+    use crate::values::string::hash_string_value;
     use crate::values::Heap;
     use crate::values::Value;
 
@@ -441,6 +521,28 @@ mod tests {
         }
     }
 
+    // Value hashes may be persisted, so they must match stable hash functions.
+    #[test]
+    fn test_string_hash_is_stable() {
+        let heap = Heap::new();
+        for x in EXAMPLES {
+            assert_eq!(
+                StarlarkHashValue::hash_str(x),
+                heap.alloc(*x).get_hash().unwrap()
+            );
+        }
+    }
+
+    // Dictionary lookups by `&str` hash the string without allocating a value.
+    #[test]
+    fn test_string_lookup_hash_matches_value_hash() {
+        for x in EXAMPLES {
+            let mut hasher = StarlarkHasher::new();
+            hash_string_value(x, &mut hasher);
+            assert_eq!(StarlarkHashValue::hash_str(x), hasher.finish_small());
+        }
+    }
+
     // If hash was zero, we'd need to mask the value in the hash cache.
     #[test]
     fn test_zero_length_string_hash_is_not_zero() {
@@ -0,0 +1,115 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use proc_macro2::TokenStream;
+use quote::quote_spanned;
+use syn::parse::ParseStream;
+use syn::parse_macro_input;
+use syn::spanned::Spanned;
+use syn::Attribute;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Error;
+use syn::Fields;
+use syn::LitStr;
+use syn::Token;
+
+pub fn derive_from_frozen_module(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match derive_impl(&input) {
+        Ok(gen) => gen.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn derive_impl(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    s.fields.span(),
+                    "#[derive(FromFrozenModule)] requires a struct with named fields",
+                ));
+            }
+        },
+        Data::Enum(e) => {
+            return Err(Error::new(
+                e.enum_token.span(),
+                "#[derive(FromFrozenModule)] does not support enums",
+            ));
+        }
+        Data::Union(u) => {
+            return Err(Error::new(
+                u.union_token.span(),
+                "#[derive(FromFrozenModule)] does not support unions",
+            ));
+        }
+    };
+
+    let mut inits = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let name = match extract_rename(&field.attrs)? {
+            Some(name) => name,
+            None => ident.to_string(),
+        };
+        inits.push(quote_spanned! {field.span()=>
+            #ident: <#ty as starlark::environment::FrozenModuleField>::from_frozen_module_binding(module, #name)?
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote_spanned! {input.span()=>
+        impl #impl_generics starlark::environment::FromFrozenModule for #name #ty_generics #where_clause {
+            fn from_frozen_module(
+                module: &starlark::environment::FrozenModule,
+            ) -> anyhow::Result<Self> {
+                Ok(Self {
+                    #(#inits,)*
+                })
+            }
+        }
+    })
+}
+
+/// Parse a `#[starlark(rename = "NAME")]` annotation.
+#[cfg_attr(feature = "gazebo_lint", allow(gazebo_lint_impl_dupe))] // The custom_keyword macro
+fn extract_rename(attrs: &[Attribute]) -> syn::Result<Option<String>> {
+    syn::custom_keyword!(rename);
+
+    let mut res = None;
+    for attr in attrs.iter() {
+        if !attr.path.is_ident("starlark") {
+            continue;
+        }
+
+        attr.parse_args_with(|input: ParseStream| {
+            input.parse::<rename>()?;
+            if res.is_some() {
+                return Err(input.error("`rename` was set twice"));
+            }
+            input.parse::<Token![=]>()?;
+            res = Some(input.parse::<LitStr>()?.value());
+            Ok(())
+        })?;
+    }
+
+    Ok(res)
+}
@@ -31,6 +31,7 @@ mod bc;
 mod docs;
 mod for_each_field;
 mod freeze;
+mod from_frozen_module;
 mod module;
 mod serde;
 mod trace;
@@ -124,6 +125,15 @@ pub fn derive_freeze(input: proc_macro::TokenStream) -> proc_macro::TokenStream
     freeze::derive_freeze(input)
 }
 
+/// Derive the `FromFrozenModule` trait.
+///
+/// Each field is read from the exported module binding of the same name,
+/// or the name given with `#[starlark(rename = "NAME")]`.
+#[proc_macro_derive(FromFrozenModule, attributes(starlark))]
+pub fn derive_from_frozen_module(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    from_frozen_module::derive_from_frozen_module(input)
+}
+
 /// Derive the `NoSerialize` trait for serde.
 #[proc_macro_derive(NoSerialize)]
 pub fn derive_no_serialize(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -0,0 +1,123 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Benchmarks for `SmallMap` and `SmallSet`, compared to `hashbrown::HashMap`.
+
+use std::hint::black_box;
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use starlark_map::small_map::SmallMap;
+use starlark_map::small_set::SmallSet;
+use starlark_map::StarlarkHasherBuilder;
+
+/// Sizes around the linear scan threshold, and the mid-size range where
+/// the index lookup dominates.
+const SIZES: &[usize] = &[4, 8, 16, 32, 64, 128, 200, 1000];
+
+fn keys(n: usize) -> Vec<String> {
+    (0..n).map(|i| format!("key_{}", i)).collect()
+}
+
+fn bench_get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get");
+    for &n in SIZES {
+        let keys = keys(n);
+        let small_map: SmallMap<String, usize> = keys
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, k)| (k, i))
+            .collect();
+        let hash_map: hashbrown::HashMap<String, usize, StarlarkHasherBuilder> = keys
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, k)| (k, i))
+            .collect();
+
+        group.bench_with_input(BenchmarkId::new("SmallMap", n), &keys, |b, keys| {
+            b.iter(|| {
+                for k in keys {
+                    black_box(small_map.get(k.as_str()));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("SmallMap_miss", n), &keys, |b, keys| {
+            b.iter(|| {
+                for k in keys {
+                    black_box(small_map.get(&k[1..]));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("hashbrown", n), &keys, |b, keys| {
+            b.iter(|| {
+                for k in keys {
+                    black_box(hash_map.get(k.as_str()));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for &n in SIZES {
+        let keys = keys(n);
+        group.bench_with_input(BenchmarkId::new("SmallMap", n), &keys, |b, keys| {
+            b.iter(|| {
+                let mut map = SmallMap::new();
+                for (i, k) in keys.iter().enumerate() {
+                    map.insert(k.as_str(), i);
+                }
+                map
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("hashbrown", n), &keys, |b, keys| {
+            b.iter(|| {
+                let mut map = hashbrown::HashMap::with_hasher(StarlarkHasherBuilder);
+                for (i, k) in keys.iter().enumerate() {
+                    map.insert(k.as_str(), i);
+                }
+                map
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_set(c: &mut Criterion) {
+    let mut group = c.benchmark_group("set_contains");
+    for &n in SIZES {
+        let keys = keys(n);
+        let set: SmallSet<String> = keys.iter().cloned().collect();
+        group.bench_with_input(BenchmarkId::new("SmallSet", n), &keys, |b, keys| {
+            b.iter(|| {
+                for k in keys {
+                    black_box(set.contains(k.as_str()));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_get, bench_insert, bench_set);
+criterion_main!(benches);
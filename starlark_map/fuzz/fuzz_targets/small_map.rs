@@ -0,0 +1,94 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Apply a sequence of operations to two `SmallMap`s and to two `IndexMap`s,
+//! and check that results, contents and comparisons agree.
+//!
+//! Run with `cargo fuzz run small_map` from the `starlark_map` directory.
+
+#![no_main]
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+
+use arbitrary::Arbitrary;
+use indexmap::IndexMap;
+use libfuzzer_sys::fuzz_target;
+use starlark_map::small_map::SmallMap;
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Insert(u8, u32),
+    Remove(u8),
+    SwapRemove(u8),
+    SwapRemoveIndex(u8),
+    Pop,
+    OrInsert(u8, u32),
+    Clear,
+    ShrinkToFit,
+    SortKeys,
+}
+
+fn hash_ordered(map: &SmallMap<u8, u32>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    map.hash_ordered(&mut hasher);
+    hasher.finish()
+}
+
+fn apply(map: &mut SmallMap<u8, u32>, model: &mut IndexMap<u8, u32>, op: &Op) {
+    match *op {
+        Op::Insert(k, v) => assert_eq!(model.insert(k, v), map.insert(k, v)),
+        Op::Remove(k) => assert_eq!(model.shift_remove(&k), map.remove(&k)),
+        Op::SwapRemove(k) => assert_eq!(model.swap_remove(&k), map.swap_remove(&k)),
+        Op::SwapRemoveIndex(i) => {
+            let i = i as usize;
+            assert_eq!(model.swap_remove_index(i), map.swap_remove_index(i))
+        }
+        Op::Pop => assert_eq!(model.pop(), map.pop()),
+        Op::OrInsert(k, v) => assert_eq!(*model.entry(k).or_insert(v), *map.entry(k).or_insert(v)),
+        Op::Clear => {
+            model.clear();
+            map.clear();
+        }
+        Op::ShrinkToFit => map.shrink_to_fit(),
+        Op::SortKeys => {
+            model.sort_keys();
+            map.sort_keys();
+        }
+    }
+    assert!(model.iter().eq(map.iter()));
+    for (i, k) in model.keys().enumerate() {
+        assert_eq!(Some(i), map.get_index_of(k));
+    }
+}
+
+fuzz_target!(|ops: Vec<(bool, Op)>| {
+    let mut maps = [SmallMap::new(), SmallMap::new()];
+    let mut models = [IndexMap::new(), IndexMap::new()];
+    for (second, op) in &ops {
+        let i = *second as usize;
+        apply(&mut maps[i], &mut models[i], op);
+
+        let [a, b] = &maps;
+        assert_eq!(models[0] == models[1], a == b);
+        let eq_ordered = models[0].iter().eq(models[1].iter());
+        assert_eq!(eq_ordered, a.eq_ordered(b));
+        if eq_ordered {
+            assert_eq!(hash_ordered(a), hash_ordered(b));
+        }
+    }
+});
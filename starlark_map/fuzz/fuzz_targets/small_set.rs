@@ -0,0 +1,67 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Apply a sequence of operations to two `SmallSet`s and to two `IndexSet`s,
+//! and check that results, contents and set operations agree.
+//!
+//! Run with `cargo fuzz run small_set` from the `starlark_map` directory.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use indexmap::IndexSet;
+use libfuzzer_sys::fuzz_target;
+use starlark_map::small_set::SmallSet;
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Insert(u8),
+    Remove(u8),
+    SwapRemove(u8),
+    Pop,
+    Clear,
+}
+
+fuzz_target!(|ops: Vec<(bool, Op)>| {
+    let mut sets = [SmallSet::new(), SmallSet::new()];
+    let mut models = [IndexSet::new(), IndexSet::new()];
+    for (second, op) in &ops {
+        let i = *second as usize;
+        let (set, model) = (&mut sets[i], &mut models[i]);
+        match *op {
+            Op::Insert(x) => assert_eq!(model.insert(x), set.insert(x)),
+            Op::Remove(x) => assert_eq!(model.shift_remove(&x), set.remove(&x)),
+            Op::SwapRemove(x) => assert_eq!(model.swap_remove(&x), set.swap_remove(&x)),
+            Op::Pop => assert_eq!(model.pop(), set.pop()),
+            Op::Clear => {
+                model.clear();
+                set.clear();
+            }
+        }
+        assert!(model.iter().eq(set.iter()));
+
+        let [a, b] = &sets;
+        let [ma, mb] = &models;
+        assert_eq!(ma == mb, a == b);
+        assert!(ma.union(mb).eq(a.union(b)));
+        assert!(ma.intersection(mb).eq(a.intersection(b)));
+        assert!(ma.difference(mb).eq(a.difference(b)));
+        assert!(ma.symmetric_difference(mb).eq(a.symmetric_difference(b)));
+        assert_eq!(ma.is_subset(mb), a.is_subset(b));
+        assert_eq!(ma.is_disjoint(mb), a.is_disjoint(b));
+    }
+});
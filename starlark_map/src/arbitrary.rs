@@ -0,0 +1,144 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! [`arbitrary`] support for the collections of this crate, enabled by the `arbitrary` feature.
+//!
+//! Collections are built from an arbitrary sequence of elements, inserted in order,
+//! so for keys repeated in the sequence the first position and the last value are used.
+
+use std::hash::BuildHasher;
+use std::hash::Hash;
+
+use arbitrary::Arbitrary;
+use arbitrary::Result;
+use arbitrary::Unstructured;
+
+use crate::small_map::SmallMap;
+use crate::small_set::SmallSet;
+use crate::vec2::Vec2;
+
+impl<'a, K, V, S> Arbitrary<'a> for SmallMap<K, V, S>
+where
+    K: Arbitrary<'a> + Hash + Eq,
+    V: Arbitrary<'a>,
+    S: BuildHasher + Default,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut res = SmallMap::with_hasher(S::default());
+        for entry in u.arbitrary_iter()? {
+            let (k, v) = entry?;
+            res.insert(k, v);
+        }
+        Ok(res)
+    }
+
+    fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+        let mut res = SmallMap::with_hasher(S::default());
+        for entry in u.arbitrary_take_rest_iter()? {
+            let (k, v) = entry?;
+            res.insert(k, v);
+        }
+        Ok(res)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        <Vec<(K, V)> as Arbitrary>::size_hint(depth)
+    }
+}
+
+impl<'a, T, S> Arbitrary<'a> for SmallSet<T, S>
+where
+    T: Arbitrary<'a> + Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut res = SmallSet::with_hasher(S::default());
+        for x in u.arbitrary_iter()? {
+            res.insert(x?);
+        }
+        Ok(res)
+    }
+
+    fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+        let mut res = SmallSet::with_hasher(S::default());
+        for x in u.arbitrary_take_rest_iter()? {
+            res.insert(x?);
+        }
+        Ok(res)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        <Vec<T> as Arbitrary>::size_hint(depth)
+    }
+}
+
+impl<'a, A: Arbitrary<'a>, B: Arbitrary<'a>> Arbitrary<'a> for Vec2<A, B> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        <Vec<(A, B)> as Arbitrary>::size_hint(depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::Arbitrary;
+    use arbitrary::Unstructured;
+
+    use crate::small_map::SmallMap;
+    use crate::small_set::SmallSet;
+    use crate::vec2::Vec2;
+
+    const DATA: &[u8] = &[
+        1, 3, 10, 1, 7, 11, 1, 3, 12, 1, 5, 13, 1, 9, 14, 1, 2, 15, 0, 42, 17, 99,
+    ];
+
+    #[test]
+    fn test_small_map() {
+        let m = SmallMap::<u8, u8>::arbitrary(&mut Unstructured::new(DATA)).unwrap();
+        assert_eq!(
+            vec![(&3, &12), (&7, &11), (&5, &13), (&9, &14), (&2, &15)],
+            m.iter().collect::<Vec<_>>()
+        );
+        for (k, v) in &m {
+            assert_eq!(Some(v), m.get(k));
+        }
+    }
+
+    #[test]
+    fn test_small_set() {
+        let data = [1, 3, 1, 7, 1, 3, 1, 5, 0];
+        let s = SmallSet::<u8>::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        assert_eq!(vec![&3, &7, &5], s.iter().collect::<Vec<_>>());
+        for x in &s {
+            assert!(s.contains(x));
+        }
+    }
+
+    #[test]
+    fn test_vec2() {
+        let v = Vec2::<u8, u16>::arbitrary_take_rest(Unstructured::new(DATA)).unwrap();
+        assert!(!v.is_empty());
+        assert_eq!(v.aaa().len(), v.bbb().len());
+    }
+}
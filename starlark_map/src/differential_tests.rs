@@ -0,0 +1,322 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Differential tests: random sequences of operations are applied to
+//! [`SmallMap`]/[`SmallSet`] and to [`IndexMap`]/[`IndexSet`],
+//! and the results and contents are compared after each operation.
+//!
+//! Keys are drawn from a small range, so operations often hit existing keys,
+//! and maps grow past the size where the index is created.
+//! The same operations are used by the fuzz targets in `fuzz/`.
+
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::rc::Rc;
+
+use indexmap::IndexMap;
+use indexmap::IndexSet;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::test_runner::Config;
+use proptest::test_runner::TestRunner;
+
+use crate::small_map::SmallMap;
+use crate::small_set::SmallSet;
+
+/// Number of distinct keys.
+const KEYS: u8 = 48;
+
+#[derive(Debug, Clone)]
+enum MapOp {
+    Insert(u8, u32),
+    Remove(u8),
+    SwapRemove(u8),
+    SwapRemoveIndex(usize),
+    Pop,
+    OrInsert(u8, u32),
+    Clear,
+    ShrinkToFit,
+    SortKeys,
+}
+
+fn key() -> impl Strategy<Value = u8> {
+    0..KEYS
+}
+
+fn map_op() -> impl Strategy<Value = MapOp> {
+    prop_oneof![
+        8 => (key(), any::<u32>()).prop_map(|(k, v)| MapOp::Insert(k, v)),
+        3 => key().prop_map(MapOp::Remove),
+        2 => key().prop_map(MapOp::SwapRemove),
+        1 => (0..KEYS as usize).prop_map(MapOp::SwapRemoveIndex),
+        1 => Just(MapOp::Pop),
+        2 => (key(), any::<u32>()).prop_map(|(k, v)| MapOp::OrInsert(k, v)),
+        1 => Just(MapOp::ShrinkToFit),
+        1 => Just(MapOp::SortKeys),
+        // Rare, so maps get large.
+        1 => prop_oneof![9 => Just(MapOp::ShrinkToFit), 1 => Just(MapOp::Clear)],
+    ]
+}
+
+fn hash_ordered(map: &SmallMap<u8, u32>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    map.hash_ordered(&mut hasher);
+    hasher.finish()
+}
+
+fn check_map(map: &SmallMap<u8, u32>, model: &IndexMap<u8, u32>) {
+    assert_eq!(model.len(), map.len());
+    assert!(model.iter().eq(map.iter()), "{:?} != {:?}", model, map);
+    for k in 0..KEYS {
+        assert_eq!(model.get_index_of(&k), map.get_index_of(&k));
+        assert_eq!(model.get(&k), map.get(&k));
+    }
+    assert_eq!(model.first(), map.first());
+    assert_eq!(model.last(), map.last());
+}
+
+fn apply_map_op(map: &mut SmallMap<u8, u32>, model: &mut IndexMap<u8, u32>, op: &MapOp) {
+    match *op {
+        MapOp::Insert(k, v) => assert_eq!(model.insert(k, v), map.insert(k, v)),
+        MapOp::Remove(k) => assert_eq!(model.shift_remove(&k), map.remove(&k)),
+        MapOp::SwapRemove(k) => assert_eq!(model.swap_remove(&k), map.swap_remove(&k)),
+        MapOp::SwapRemoveIndex(i) => {
+            assert_eq!(model.swap_remove_index(i), map.swap_remove_index(i))
+        }
+        MapOp::Pop => assert_eq!(model.pop(), map.pop()),
+        MapOp::OrInsert(k, v) => {
+            assert_eq!(*model.entry(k).or_insert(v), *map.entry(k).or_insert(v))
+        }
+        MapOp::Clear => {
+            model.clear();
+            map.clear();
+        }
+        MapOp::ShrinkToFit => map.shrink_to_fit(),
+        MapOp::SortKeys => {
+            model.sort_keys();
+            map.sort_keys();
+        }
+    }
+}
+
+/// Apply operations to one of two maps, and compare both maps with each other
+/// as well as with the models.
+fn check_map_ops(ops: &[(bool, MapOp)]) {
+    let mut maps = [SmallMap::new(), SmallMap::new()];
+    let mut models = [IndexMap::new(), IndexMap::new()];
+    for (second, op) in ops {
+        let i = *second as usize;
+        apply_map_op(&mut maps[i], &mut models[i], op);
+        check_map(&maps[i], &models[i]);
+
+        let [a, b] = &maps;
+        assert_eq!(models[0] == models[1], a == b);
+        let eq_ordered = models[0].iter().eq(models[1].iter());
+        assert_eq!(eq_ordered, a.eq_ordered(b));
+        if eq_ordered {
+            assert_eq!(hash_ordered(a), hash_ordered(b));
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SetOp {
+    Insert(u8),
+    Remove(u8),
+    SwapRemove(u8),
+    Pop,
+    Clear,
+}
+
+fn set_op() -> impl Strategy<Value = SetOp> {
+    prop_oneof![
+        8 => key().prop_map(SetOp::Insert),
+        3 => key().prop_map(SetOp::Remove),
+        2 => key().prop_map(SetOp::SwapRemove),
+        1 => prop_oneof![9 => Just(SetOp::Pop), 1 => Just(SetOp::Clear)],
+    ]
+}
+
+fn check_set_ops(ops: &[(bool, SetOp)]) {
+    let mut sets = [SmallSet::new(), SmallSet::new()];
+    let mut models = [IndexSet::new(), IndexSet::new()];
+    for (second, op) in ops {
+        let i = *second as usize;
+        let (set, model) = (&mut sets[i], &mut models[i]);
+        match *op {
+            SetOp::Insert(x) => assert_eq!(model.insert(x), set.insert(x)),
+            SetOp::Remove(x) => assert_eq!(model.shift_remove(&x), set.remove(&x)),
+            SetOp::SwapRemove(x) => assert_eq!(model.swap_remove(&x), set.swap_remove(&x)),
+            SetOp::Pop => assert_eq!(model.pop(), set.pop()),
+            SetOp::Clear => {
+                model.clear();
+                set.clear();
+            }
+        }
+        assert!(model.iter().eq(set.iter()));
+        for x in 0..KEYS {
+            assert_eq!(model.contains(&x), set.contains(&x));
+        }
+
+        let [a, b] = &sets;
+        let [ma, mb] = &models;
+        assert_eq!(ma == mb, a == b);
+        assert!(ma.union(mb).eq(a.union(b)));
+        assert!(ma.intersection(mb).eq(a.intersection(b)));
+        assert!(ma.difference(mb).eq(a.difference(b)));
+        assert!(ma.symmetric_difference(mb).eq(a.symmetric_difference(b)));
+        assert_eq!(ma.is_subset(mb), a.is_subset(b));
+        assert_eq!(ma.is_disjoint(mb), a.is_disjoint(b));
+    }
+}
+
+thread_local! {
+    /// The key whose `Hash` and `Eq` panic.
+    static PANIC_KEY: Cell<Option<u8>> = const { Cell::new(None) };
+}
+
+/// Key with user-supplied `Hash` and `Eq` which panic for [`PANIC_KEY`].
+#[derive(Debug, Clone, Copy)]
+struct PanicKey(u8);
+
+impl PanicKey {
+    fn check(self) {
+        if PANIC_KEY.with(|k| k.get()) == Some(self.0) {
+            panic!("panic on key {}", self.0);
+        }
+    }
+}
+
+impl Hash for PanicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.check();
+        self.0.hash(state);
+    }
+}
+
+impl PartialEq for PanicKey {
+    fn eq(&self, other: &PanicKey) -> bool {
+        self.check();
+        other.check();
+        self.0 == other.0
+    }
+}
+
+impl Eq for PanicKey {}
+
+/// When `Hash` or `Eq` panic, the map is left unchanged, and values are not dropped twice.
+fn check_panic_safety(ops: &[(Option<u8>, MapOp)]) {
+    let value = Rc::new(());
+    let mut map: SmallMap<PanicKey, (u32, Rc<()>)> = SmallMap::new();
+    let mut model: IndexMap<u8, u32> = IndexMap::new();
+    for (panic_key, op) in ops {
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            PANIC_KEY.with(|k| k.set(*panic_key));
+            let v = |v| (v, value.clone());
+            let res = match *op {
+                MapOp::Insert(k, x) => map.insert(PanicKey(k), v(x)).map(|x| x.0),
+                MapOp::Remove(k) => map.remove(&PanicKey(k)).map(|x| x.0),
+                MapOp::SwapRemove(k) => map.swap_remove(&PanicKey(k)).map(|x| x.0),
+                MapOp::OrInsert(k, x) => Some(map.entry(PanicKey(k)).or_insert_with(|| v(x)).0),
+                _ => None,
+            };
+            res
+        }));
+        PANIC_KEY.with(|k| k.set(None));
+        match res {
+            Ok(res) => {
+                let expected = match *op {
+                    MapOp::Insert(k, x) => model.insert(k, x),
+                    MapOp::Remove(k) => model.shift_remove(&k),
+                    MapOp::SwapRemove(k) => model.swap_remove(&k),
+                    MapOp::OrInsert(k, x) => Some(*model.entry(k).or_insert(x)),
+                    _ => None,
+                };
+                assert_eq!(expected, res);
+            }
+            Err(_) => {
+                // Probing may compare the key with any stored key sharing hash bits.
+                let panic_key = panic_key.unwrap();
+                assert!(panic_key == op_key(op) || model.contains_key(&panic_key));
+            }
+        }
+        assert!(model.iter().eq(map.iter().map(|(k, v)| (&k.0, &v.0))));
+        for (i, k) in model.keys().enumerate() {
+            assert_eq!(Some(i), map.get_index_of(&PanicKey(*k)));
+        }
+        assert_eq!(1 + map.len(), Rc::strong_count(&value));
+    }
+    drop(map);
+    assert_eq!(1, Rc::strong_count(&value));
+}
+
+fn op_key(op: &MapOp) -> u8 {
+    match *op {
+        MapOp::Insert(k, _) | MapOp::Remove(k) | MapOp::SwapRemove(k) | MapOp::OrInsert(k, _) => k,
+        _ => unreachable!("no key"),
+    }
+}
+
+fn runner() -> TestRunner {
+    TestRunner::new(Config {
+        cases: 64,
+        failure_persistence: None,
+        ..Config::default()
+    })
+}
+
+#[test]
+fn test_small_map_differential() {
+    runner()
+        .run(&vec((any::<bool>(), map_op()), 0..400), |ops| {
+            check_map_ops(&ops);
+            Ok(())
+        })
+        .unwrap();
+}
+
+#[test]
+fn test_small_set_differential() {
+    runner()
+        .run(&vec((any::<bool>(), set_op()), 0..400), |ops| {
+            check_set_ops(&ops);
+            Ok(())
+        })
+        .unwrap();
+}
+
+#[test]
+fn test_small_map_panic_safety() {
+    let op = prop_oneof![
+        (key(), any::<u32>()).prop_map(|(k, v)| MapOp::Insert(k, v)),
+        key().prop_map(MapOp::Remove),
+        key().prop_map(MapOp::SwapRemove),
+        (key(), any::<u32>()).prop_map(|(k, v)| MapOp::OrInsert(k, v)),
+    ];
+    // Panic for a key in about one operation out of 20.
+    let panic_key = prop_oneof![19 => Just(None), 1 => key().prop_map(Some)];
+    runner()
+        .run(&vec((panic_key, op), 0..200), |ops| {
+            check_panic_safety(&ops);
+            Ok(())
+        })
+        .unwrap();
+}
@@ -16,7 +16,9 @@
  */
 
 use std::fmt::Debug;
+use std::hash::BuildHasher;
 use std::hash::Hash;
+use std::hash::Hasher;
 
 use allocative::Allocative;
 use dupe::Dupe;
@@ -25,10 +27,29 @@ use crate::hasher::StarlarkHasher;
 use crate::mix_u32::mix_u32;
 
 /// A hash value.
+///
+/// # Stability
+///
+/// Hash values produced by [`new`](StarlarkHashValue::new),
+/// [`hash_str`](StarlarkHashValue::hash_str), [`hash_64`](StarlarkHashValue::hash_64)
+/// and [`combine`](StarlarkHashValue::combine) are stable: they do not depend on
+/// the platform or the process, and are not changed between crate versions
+/// unless [`ALGORITHM_VERSION`](StarlarkHashValue::ALGORITHM_VERSION) is changed.
+/// Users persisting hashes (e.g. in on-disk caches) should store the algorithm version
+/// alongside the hashes, and discard the hashes when the version does not match.
+///
+/// Note [`new`](StarlarkHashValue::new) depends on the [`Hash`] implementation of the key,
+/// which is only stable for types which hash stably (e.g. `str`, integers, tuples of those).
 #[derive(Clone, Copy, Dupe, PartialEq, Eq, Hash, Debug, Allocative)]
 pub struct StarlarkHashValue(u32);
 
 impl StarlarkHashValue {
+    /// Version of the hashing algorithm.
+    ///
+    /// This is incremented each time any of the stable hash functions
+    /// produces a different value for the same input.
+    pub const ALGORITHM_VERSION: u32 = 1;
+
     /// Create a new [`StarlarkHashValue`] using the [`Hash`] trait
     /// for given key.
     #[inline]
@@ -38,6 +59,31 @@ impl StarlarkHashValue {
         hasher.finish_small()
     }
 
+    /// Create a new [`StarlarkHashValue`] using the [`Hash`] trait
+    /// and given hasher for given key.
+    ///
+    /// The result is the same as [`new`](StarlarkHashValue::new)
+    /// for [`StarlarkHasherBuilder`](crate::StarlarkHasherBuilder).
+    #[inline]
+    pub fn new_with_hasher<S: BuildHasher, K: Hash + ?Sized>(hasher: &S, key: &K) -> Self {
+        // Lower 32 bits, same as `StarlarkHasher::finish_small`.
+        StarlarkHashValue::new_unchecked(hasher.hash_one(key) as u32)
+    }
+
+    /// Hash a string.
+    ///
+    /// This is the hash of a Starlark string value with the same content.
+    ///
+    /// The bytes of the string are hashed followed by a `0xff` terminator, as `str` hashes
+    /// in current Rust, but spelled out so the result does not depend on the Rust version.
+    #[inline]
+    pub fn hash_str(s: &str) -> Self {
+        let mut hasher = StarlarkHasher::default();
+        hasher.write(s.as_bytes());
+        hasher.write(&[0xff]);
+        hasher.finish_small()
+    }
+
     /// Directly create a new [`StarlarkHashValue`] using a hash.
     /// The expectation is that the key will be well-swizzled,
     /// or there may be many hash collisions.
@@ -49,6 +95,9 @@ impl StarlarkHashValue {
     /// Hash 64-bit integer.
     ///
     /// Input can also be a non-well swizzled hash to create better hash.
+    ///
+    /// Integer Starlark values are hashed with this function applied to the value
+    /// sign-extended to 64 bits.
     #[inline]
     pub const fn hash_64(h: u64) -> Self {
         // `fmix64` function from MurMur3 hash (which is in public domain).
@@ -63,6 +112,14 @@ impl StarlarkHashValue {
         StarlarkHashValue::new_unchecked(h as u32)
     }
 
+    /// Combine two hashes into one, e.g. to hash a pair.
+    ///
+    /// The result depends on the order of arguments.
+    #[inline]
+    pub const fn combine(self, other: StarlarkHashValue) -> Self {
+        StarlarkHashValue::hash_64(((self.0 as u64) << 32) | (other.0 as u64))
+    }
+
     /// Get the integer hash value.
     #[inline]
     pub const fn get(self) -> u32 {
@@ -77,3 +134,31 @@ impl StarlarkHashValue {
         mix_u32(self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::StarlarkHashValue;
+
+    // These values are persisted by users, changing them requires
+    // incrementing `StarlarkHashValue::ALGORITHM_VERSION`.
+    #[test]
+    fn test_stable_hashes() {
+        assert_eq!(1, StarlarkHashValue::ALGORITHM_VERSION);
+        assert_eq!(2248338286, StarlarkHashValue::hash_str("").get());
+        assert_eq!(2720056732, StarlarkHashValue::hash_str("hello").get());
+        assert_eq!(2720056732, StarlarkHashValue::new("hello").get());
+        assert_eq!(0, StarlarkHashValue::hash_64(0).get());
+        assert_eq!(2386713036, StarlarkHashValue::hash_64(42).get());
+        assert_eq!(1266835233, StarlarkHashValue::hash_64(-1i64 as u64).get());
+        assert_eq!(
+            337744490,
+            StarlarkHashValue::new_unchecked(1)
+                .combine(StarlarkHashValue::new_unchecked(2))
+                .get()
+        );
+        assert_eq!(
+            0x9e3779b97f4a7c15,
+            StarlarkHashValue::new_unchecked(1).promote()
+        );
+    }
+}
@@ -0,0 +1,395 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Map which keeps a few entries inline, see [`InlineMap`].
+
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::iter::FusedIterator;
+use std::mem;
+use std::slice;
+
+use crate::equivalent::Equivalent;
+use crate::hashed::Hashed;
+use crate::small_map;
+use crate::small_map::SmallMap;
+
+/// Map which stores up to `N` entries inline, without a heap allocation,
+/// and moves them to a [`SmallMap`] when it grows larger.
+///
+/// Inline entries are searched linearly, comparing hashes first.
+/// Like [`SmallMap`], iteration order is insertion order.
+///
+/// This is an opt-in alternative to [`SmallMap`] for short-lived maps which are
+/// usually tiny, e.g. keyword arguments of a call. The inline entries make the map
+/// itself larger than [`SmallMap`], so it is not a good fit for maps stored in bulk.
+///
+/// Once the entries are moved to a [`SmallMap`], they stay there when entries
+/// are removed, until [`clear`](InlineMap::clear).
+#[derive(Clone)]
+pub struct InlineMap<K, V, const N: usize = 4> {
+    repr: Repr<K, V, N>,
+}
+
+#[derive(Clone)]
+enum Repr<K, V, const N: usize> {
+    /// The first `len` entries are `Some`, the rest are `None`.
+    Inline {
+        len: usize,
+        entries: [Option<(Hashed<K>, V)>; N],
+    },
+    Heap(SmallMap<K, V>),
+}
+
+impl<K, V, const N: usize> Default for InlineMap<K, V, N> {
+    #[inline]
+    fn default() -> Self {
+        InlineMap::new()
+    }
+}
+
+impl<K: Debug, V: Debug, const N: usize> Debug for InlineMap<K, V, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, const N: usize> InlineMap<K, V, N> {
+    const NONE: Option<(Hashed<K>, V)> = None;
+
+    /// Empty map. Does not allocate.
+    #[inline]
+    pub const fn new() -> Self {
+        InlineMap {
+            repr: Repr::Inline {
+                len: 0,
+                entries: [Self::NONE; N],
+            },
+        }
+    }
+
+    /// Number of entries.
+    #[inline]
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            Repr::Inline { len, .. } => *len,
+            Repr::Heap(map) => map.len(),
+        }
+    }
+
+    /// Is the map empty?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Are the entries stored inline, rather than in a [`SmallMap`]?
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        matches!(self.repr, Repr::Inline { .. })
+    }
+
+    /// Position of the key among the inline entries.
+    #[inline]
+    fn inline_index_of<Q>(entries: &[Option<(Hashed<K>, V)>], key: Hashed<&Q>) -> Option<usize>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        entries
+            .iter()
+            .flatten()
+            .position(|(k, _)| k.hash() == key.hash() && key.key().equivalent(k.key()))
+    }
+
+    /// Query the map by a prehashed key.
+    #[inline]
+    pub fn get_hashed<Q>(&self, key: Hashed<&Q>) -> Option<&V>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        match &self.repr {
+            Repr::Inline { len, entries } => {
+                let i = Self::inline_index_of(&entries[..*len], key)?;
+                entries[i].as_ref().map(|(_, v)| v)
+            }
+            Repr::Heap(map) => map.get_hashed(key),
+        }
+    }
+
+    /// Query the map by a given key.
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_hashed(Hashed::new(key))
+    }
+
+    /// Mutable reference to the value by a prehashed key.
+    #[inline]
+    pub fn get_mut_hashed<Q>(&mut self, key: Hashed<&Q>) -> Option<&mut V>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        match &mut self.repr {
+            Repr::Inline { len, entries } => {
+                let i = Self::inline_index_of(&entries[..*len], key)?;
+                entries[i].as_mut().map(|(_, v)| v)
+            }
+            Repr::Heap(map) => map.get_mut_hashed(key),
+        }
+    }
+
+    /// Mutable reference to the value by a given key.
+    #[inline]
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_mut_hashed(Hashed::new(key))
+    }
+
+    /// Does the map contain the key?
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Insert a prehashed key-value pair, returning the previous value for the key.
+    pub fn insert_hashed(&mut self, key: Hashed<K>, val: V) -> Option<V>
+    where
+        K: Eq,
+    {
+        match &mut self.repr {
+            Repr::Inline { len, entries } => {
+                if let Some(i) = Self::inline_index_of(&entries[..*len], key.as_ref()) {
+                    return entries[i].as_mut().map(|(_, v)| mem::replace(v, val));
+                }
+                if *len < N {
+                    entries[*len] = Some((key, val));
+                    *len += 1;
+                    return None;
+                }
+                let mut map = SmallMap::with_capacity(N + 1);
+                for (k, v) in entries.iter_mut().filter_map(Option::take) {
+                    map.insert_hashed_unique_unchecked(k, v);
+                }
+                map.insert_hashed_unique_unchecked(key, val);
+                self.repr = Repr::Heap(map);
+                None
+            }
+            Repr::Heap(map) => map.insert_hashed(key, val),
+        }
+    }
+
+    /// Insert a key-value pair, returning the previous value for the key.
+    #[inline]
+    pub fn insert(&mut self, key: K, val: V) -> Option<V>
+    where
+        K: Hash + Eq,
+    {
+        self.insert_hashed(Hashed::new(key), val)
+    }
+
+    /// Remove the entry for a prehashed key, preserving the order of the other entries.
+    pub fn remove_hashed<Q>(&mut self, key: Hashed<&Q>) -> Option<V>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        match &mut self.repr {
+            Repr::Inline { len, entries } => {
+                let i = Self::inline_index_of(&entries[..*len], key)?;
+                let (_, v) = entries[i].take()?;
+                entries[i..*len].rotate_left(1);
+                *len -= 1;
+                Some(v)
+            }
+            Repr::Heap(map) => map.remove_hashed(key),
+        }
+    }
+
+    /// Remove the entry for a key, preserving the order of the other entries.
+    #[inline]
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_hashed(Hashed::new(key))
+    }
+
+    /// Remove all the entries. Frees the [`SmallMap`], if the entries were moved there.
+    #[inline]
+    pub fn clear(&mut self) {
+        *self = InlineMap::new();
+    }
+
+    /// Iterate over the entries in insertion order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            iter: match &self.repr {
+                Repr::Inline { len, entries } => IterRepr::Inline(entries[..*len].iter()),
+                Repr::Heap(map) => IterRepr::Heap(map.iter()),
+            },
+        }
+    }
+
+    /// Convert into a [`SmallMap`], preserving the order.
+    pub fn into_small_map(self) -> SmallMap<K, V> {
+        match self.repr {
+            Repr::Inline { len, entries } => {
+                let mut map = SmallMap::with_capacity(len);
+                for (k, v) in entries.into_iter().flatten() {
+                    map.insert_hashed_unique_unchecked(k, v);
+                }
+                map
+            }
+            Repr::Heap(map) => map,
+        }
+    }
+}
+
+/// Iterator over the entries of an [`InlineMap`].
+pub struct Iter<'a, K, V> {
+    iter: IterRepr<'a, K, V>,
+}
+
+enum IterRepr<'a, K, V> {
+    Inline(slice::Iter<'a, Option<(Hashed<K>, V)>>),
+    Heap(small_map::Iter<'a, K, V>),
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.iter {
+            IterRepr::Inline(iter) => {
+                let (k, v) = iter.next()?.as_ref()?;
+                Some((k.key(), v))
+            }
+            IterRepr::Heap(iter) => iter.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.iter {
+            IterRepr::Inline(iter) => iter.size_hint(),
+            IterRepr::Heap(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V, const N: usize> IntoIterator for &'a InlineMap<K, V, N> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Hash + Eq, V, const N: usize> Extend<(K, V)> for InlineMap<K, V, N> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, const N: usize> FromIterator<(K, V)> for InlineMap<K, V, N> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = InlineMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inline_map::InlineMap;
+    use crate::small_map::SmallMap;
+
+    #[test]
+    fn test_inline() {
+        let mut m = InlineMap::<String, u32, 2>::new();
+        assert_eq!(None, m.insert("a".to_owned(), 1));
+        assert_eq!(None, m.insert("b".to_owned(), 2));
+        assert_eq!(Some(2), m.insert("b".to_owned(), 20));
+        assert!(m.is_inline());
+        assert_eq!(Some(&1), m.get("a"));
+        assert_eq!(None, m.get("c"));
+        *m.get_mut("a").unwrap() += 10;
+        assert_eq!(
+            vec![(&"a".to_owned(), &11), (&"b".to_owned(), &20)],
+            m.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_move_to_small_map() {
+        let mut m = InlineMap::<u32, u32, 2>::from_iter([(1, 10), (2, 20)]);
+        assert!(m.is_inline());
+        m.insert(3, 30);
+        assert!(!m.is_inline());
+        assert_eq!(3, m.len());
+        assert_eq!(Some(&20), m.get(&2));
+        assert_eq!(Some(20), m.remove(&2));
+        assert!(!m.is_inline());
+        assert_eq!(vec![(&1, &10), (&3, &30)], m.iter().collect::<Vec<_>>());
+        m.clear();
+        assert!(m.is_inline());
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn test_remove_preserves_order() {
+        let mut m = InlineMap::<u32, (), 4>::from_iter([(1, ()), (2, ()), (3, ()), (4, ())]);
+        assert_eq!(Some(()), m.remove(&2));
+        assert_eq!(None, m.remove(&2));
+        assert_eq!(vec![1, 3, 4], m.iter().map(|(k, _)| *k).collect::<Vec<_>>());
+        m.insert(5, ());
+        assert!(m.is_inline());
+        assert_eq!(
+            vec![1, 3, 4, 5],
+            m.iter().map(|(k, _)| *k).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_into_small_map() {
+        let m = InlineMap::<u32, u32>::from_iter([(3, 30), (1, 10)]);
+        let expected = SmallMap::from_iter([(3, 30), (1, 10)]);
+        assert!(expected.eq_ordered(&m.clone().into_small_map()));
+        let m = InlineMap::<u32, u32, 0>::from_iter([(3, 30), (1, 10)]);
+        assert!(!m.is_inline());
+        assert!(expected.eq_ordered(&m.into_small_map()));
+    }
+}
@@ -24,16 +24,31 @@
 #![cfg_attr(rust_nightly, feature(core_intrinsics))]
 #![cfg_attr(rust_nightly, feature(portable_simd))]
 
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(test)]
+mod differential_tests;
 mod equivalent;
 mod hash_value;
 mod hashed;
 mod hasher;
+pub mod inline_map;
 mod iter;
 mod mix_u32;
+pub mod persistent_map;
+pub mod persistent_set;
+#[cfg(feature = "proptest")]
+mod proptest;
+#[cfg(feature = "serde")]
+mod serde;
 pub mod small_map;
 pub mod small_set;
+pub mod sorted_map;
+pub mod sorted_set;
+pub mod sorted_vec_map;
 pub(crate) mod sorting;
-pub(crate) mod vec2;
+mod try_reserve_error;
+pub mod vec2;
 pub(crate) mod vec_map;
 
 pub use equivalent::Equivalent;
@@ -41,3 +56,4 @@ pub use hash_value::StarlarkHashValue;
 pub use hashed::Hashed;
 pub use hasher::StarlarkHasher;
 pub use hasher::StarlarkHasherBuilder;
+pub use try_reserve_error::TryReserveError;
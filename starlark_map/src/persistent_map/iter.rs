@@ -0,0 +1,189 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::iter::FusedIterator;
+use std::slice;
+
+use dupe::Clone_;
+
+use crate::iter::def_iter;
+use crate::persistent_map::Child;
+use crate::persistent_map::Node;
+use crate::Hashed;
+use crate::StarlarkHashValue;
+
+#[derive(Clone_)]
+enum NodeIter<'a, K, V> {
+    Branch(slice::Iter<'a, Child<K, V>>),
+    Collision(StarlarkHashValue, slice::Iter<'a, (K, V)>),
+}
+
+impl<'a, K, V> NodeIter<'a, K, V> {
+    #[inline]
+    fn new(node: &'a Node<K, V>) -> Self {
+        match node {
+            Node::Branch { children, .. } => NodeIter::Branch(children.iter()),
+            Node::Collision { hash, entries } => NodeIter::Collision(*hash, entries.iter()),
+        }
+    }
+}
+
+/// Iterator over the hashed entries of [`PersistentMap`](crate::persistent_map::PersistentMap).
+#[derive(Clone_)]
+pub struct IterHashed<'a, K, V> {
+    /// Iterators over the nodes on the path from the root to the current entry.
+    stack: Vec<NodeIter<'a, K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> IterHashed<'a, K, V> {
+    pub(super) fn new(root: Option<&'a Node<K, V>>, len: usize) -> Self {
+        IterHashed {
+            stack: root.map(NodeIter::new).into_iter().collect(),
+            remaining: len,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for IterHashed<'a, K, V> {
+    type Item = (Hashed<&'a K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let next = match self.stack.last_mut()? {
+                NodeIter::Branch(iter) => match iter.next() {
+                    Some(Child::Leaf(hash, k, v)) => Some((Hashed::new_unchecked(*hash, k), v)),
+                    Some(Child::Node(node)) => {
+                        self.stack.push(NodeIter::new(node));
+                        continue;
+                    }
+                    None => None,
+                },
+                NodeIter::Collision(hash, iter) => iter
+                    .next()
+                    .map(|(k, v)| (Hashed::new_unchecked(*hash, k), v)),
+            };
+            match next {
+                Some(next) => {
+                    self.remaining -= 1;
+                    return Some(next);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterHashed<'a, K, V> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl<'a, K, V> FusedIterator for IterHashed<'a, K, V> {}
+
+/// Iterator over the entries of [`PersistentMap`](crate::persistent_map::PersistentMap).
+#[derive(Clone_)]
+pub struct Iter<'a, K, V> {
+    pub(crate) iter: IterHashed<'a, K, V>,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    #[inline]
+    fn map((k, v): (Hashed<&'a K>, &'a V)) -> <Self as Iterator>::Item {
+        (k.into_key(), v)
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    def_iter!();
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+/// Iterator over the keys of [`PersistentMap`](crate::persistent_map::PersistentMap).
+#[derive(Clone_)]
+pub struct Keys<'a, K, V> {
+    pub(crate) iter: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Keys<'a, K, V> {
+    #[inline]
+    fn map((k, _v): (&'a K, &'a V)) -> <Self as Iterator>::Item {
+        k
+    }
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    def_iter!();
+}
+
+impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, K, V> FusedIterator for Keys<'a, K, V> {}
+
+/// Iterator over the values of [`PersistentMap`](crate::persistent_map::PersistentMap).
+#[derive(Clone_)]
+pub struct Values<'a, K, V> {
+    pub(crate) iter: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Values<'a, K, V> {
+    #[inline]
+    fn map((_k, v): (&'a K, &'a V)) -> <Self as Iterator>::Item {
+        v
+    }
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    def_iter!();
+}
+
+impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, K, V> FusedIterator for Values<'a, K, V> {}
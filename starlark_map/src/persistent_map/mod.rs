@@ -0,0 +1,777 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Persistent map with structural sharing.
+
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use allocative::Allocative;
+
+use crate::equivalent::Equivalent;
+use crate::hashed::Hashed;
+pub use crate::persistent_map::iter::Iter;
+pub use crate::persistent_map::iter::IterHashed;
+pub use crate::persistent_map::iter::Keys;
+pub use crate::persistent_map::iter::Values;
+use crate::small_map::SmallMap;
+use crate::StarlarkHashValue;
+
+mod iter;
+
+/// Number of hash bits consumed by each level of the tree.
+const BITS: u32 = 5;
+/// Shift of the last level which still branches on hash bits.
+/// Keys with equal hashes below that level are stored in a collision node.
+const MAX_SHIFT: u32 = 30;
+
+/// Persistent hash map: cloning is `O(1)`,
+/// and modifications of a clone copy only the path to the modified entry.
+///
+/// Implemented as a hash array mapped trie (HAMT) over [`StarlarkHashValue`],
+/// so a map can be cheaply derived from another map which differs in a few entries,
+/// while both maps share most of their memory.
+///
+/// Unlike [`SmallMap`], iteration order is not insertion order:
+/// entries are iterated in the order of their hashes
+/// (the order is still deterministic because Starlark hashes are stable).
+pub struct PersistentMap<K, V> {
+    root: Option<Arc<Node<K, V>>>,
+    len: usize,
+}
+
+#[derive(Clone, Allocative)]
+enum Node<K, V> {
+    /// Children are sorted by the hash fragment at this level,
+    /// `bitmap` has a bit set for each present fragment.
+    Branch {
+        bitmap: u32,
+        children: Vec<Child<K, V>>,
+    },
+    /// Entries with identical hash which could not be split by hash bits.
+    Collision {
+        hash: StarlarkHashValue,
+        entries: Vec<(K, V)>,
+    },
+}
+
+#[derive(Clone, Allocative)]
+enum Child<K, V> {
+    Leaf(StarlarkHashValue, K, V),
+    Node(Arc<Node<K, V>>),
+}
+
+#[inline]
+fn fragment_bit(hash: StarlarkHashValue, shift: u32) -> u32 {
+    1 << ((hash.get() >> shift) & ((1 << BITS) - 1))
+}
+
+#[inline]
+fn child_pos(bitmap: u32, bit: u32) -> usize {
+    (bitmap & (bit - 1)).count_ones() as usize
+}
+
+impl<K, V> Node<K, V> {
+    fn single(hash: StarlarkHashValue, key: K, value: V) -> Node<K, V> {
+        Node::Branch {
+            bitmap: fragment_bit(hash, 0),
+            children: vec![Child::Leaf(hash, key, value)],
+        }
+    }
+
+    /// Node containing two entries with different keys.
+    fn pair(shift: u32, a: (StarlarkHashValue, K, V), b: (StarlarkHashValue, K, V)) -> Node<K, V> {
+        if shift > MAX_SHIFT {
+            debug_assert!(a.0 == b.0);
+            return Node::Collision {
+                hash: a.0,
+                entries: vec![(a.1, a.2), (b.1, b.2)],
+            };
+        }
+        let a_bit = fragment_bit(a.0, shift);
+        let b_bit = fragment_bit(b.0, shift);
+        if a_bit == b_bit {
+            Node::Branch {
+                bitmap: a_bit,
+                children: vec![Child::Node(Arc::new(Node::pair(shift + BITS, a, b)))],
+            }
+        } else {
+            let a = Child::Leaf(a.0, a.1, a.2);
+            let b = Child::Leaf(b.0, b.1, b.2);
+            Node::Branch {
+                bitmap: a_bit | b_bit,
+                children: if a_bit < b_bit {
+                    vec![a, b]
+                } else {
+                    vec![b, a]
+                },
+            }
+        }
+    }
+
+    fn get<Q>(&self, key: Hashed<&Q>) -> Option<(&K, &V)>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        let mut node = self;
+        let mut shift = 0;
+        loop {
+            match node {
+                Node::Branch { bitmap, children } => {
+                    let bit = fragment_bit(key.hash(), shift);
+                    if bitmap & bit == 0 {
+                        return None;
+                    }
+                    match &children[child_pos(*bitmap, bit)] {
+                        Child::Leaf(hash, k, v) => {
+                            return if *hash == key.hash() && key.key().equivalent(k) {
+                                Some((k, v))
+                            } else {
+                                None
+                            };
+                        }
+                        Child::Node(child) => {
+                            node = child;
+                            shift += BITS;
+                        }
+                    }
+                }
+                Node::Collision { hash, entries } => {
+                    if *hash != key.hash() {
+                        return None;
+                    }
+                    return entries
+                        .iter()
+                        .find(|(k, _)| key.key().equivalent(k))
+                        .map(|(k, v)| (k, v));
+                }
+            }
+        }
+    }
+
+    /// Insert an entry, return the previous value if the key was present.
+    fn insert(&mut self, shift: u32, key: Hashed<K>, value: V) -> Option<V>
+    where
+        K: Eq + Clone,
+        V: Clone,
+    {
+        match self {
+            Node::Branch { bitmap, children } => {
+                let bit = fragment_bit(key.hash(), shift);
+                let pos = child_pos(*bitmap, bit);
+                if *bitmap & bit == 0 {
+                    *bitmap |= bit;
+                    children.insert(pos, Child::Leaf(key.hash(), key.into_key(), value));
+                    return None;
+                }
+                match &mut children[pos] {
+                    Child::Leaf(hash, k, v) if *hash == key.hash() && *k == *key.key() => {
+                        return Some(std::mem::replace(v, value));
+                    }
+                    Child::Leaf(..) => {}
+                    Child::Node(child) => {
+                        return Arc::make_mut(child).insert(shift + BITS, key, value);
+                    }
+                }
+                let old = match children.remove(pos) {
+                    Child::Leaf(hash, k, v) => (hash, k, v),
+                    Child::Node(_) => unreachable!(),
+                };
+                let new = (key.hash(), key.into_key(), value);
+                children.insert(
+                    pos,
+                    Child::Node(Arc::new(Node::pair(shift + BITS, old, new))),
+                );
+                None
+            }
+            Node::Collision { hash, entries } => {
+                debug_assert!(*hash == key.hash());
+                match entries.iter_mut().find(|(k, _)| *k == *key.key()) {
+                    Some((_, v)) => Some(std::mem::replace(v, value)),
+                    None => {
+                        entries.push((key.into_key(), value));
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remove an entry which is known to be present.
+    fn remove<Q>(&mut self, shift: u32, key: Hashed<&Q>) -> (K, V)
+    where
+        Q: Equivalent<K> + ?Sized,
+        K: Clone,
+        V: Clone,
+    {
+        match self {
+            Node::Branch { bitmap, children } => {
+                let bit = fragment_bit(key.hash(), shift);
+                assert!(*bitmap & bit != 0, "key must be present");
+                let pos = child_pos(*bitmap, bit);
+                match &mut children[pos] {
+                    Child::Leaf(..) => {}
+                    Child::Node(child) => {
+                        let child_mut = Arc::make_mut(child);
+                        let removed = child_mut.remove(shift + BITS, key);
+                        // Pull up the last entry of a child, so the tree stays compact.
+                        if let Some(leaf) = child_mut.take_single_leaf() {
+                            children[pos] = leaf;
+                        }
+                        return removed;
+                    }
+                }
+                *bitmap &= !bit;
+                match children.remove(pos) {
+                    Child::Leaf(_, k, v) => (k, v),
+                    Child::Node(_) => unreachable!(),
+                }
+            }
+            Node::Collision { entries, .. } => {
+                let pos = entries
+                    .iter()
+                    .position(|(k, _)| key.key().equivalent(k))
+                    .expect("key must be present");
+                entries.remove(pos)
+            }
+        }
+    }
+
+    /// If this node contains only one entry, take it out.
+    fn take_single_leaf(&mut self) -> Option<Child<K, V>> {
+        match self {
+            Node::Branch { children, .. } => match children.as_slice() {
+                [Child::Leaf(..)] => children.pop(),
+                _ => None,
+            },
+            Node::Collision { hash, entries } => {
+                if entries.len() == 1 {
+                    let (k, v) = entries.pop().unwrap();
+                    Some(Child::Leaf(*hash, k, v))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Node::Branch { children, .. } => children.is_empty(),
+            Node::Collision { entries, .. } => entries.is_empty(),
+        }
+    }
+}
+
+impl<K, V> Clone for PersistentMap<K, V> {
+    #[inline]
+    fn clone(&self) -> Self {
+        PersistentMap {
+            root: self.root.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<K, V> Default for PersistentMap<K, V> {
+    #[inline]
+    fn default() -> Self {
+        PersistentMap::new()
+    }
+}
+
+impl<K: Allocative, V: Allocative> Allocative for PersistentMap<K, V> {
+    fn visit<'a, 'b: 'a>(&self, visitor: &'a mut allocative::Visitor<'b>) {
+        let mut visitor = visitor.enter_self_sized::<Self>();
+        visitor.visit_field(allocative::Key::new("root"), &self.root);
+        visitor.exit();
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for PersistentMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V> PersistentMap<K, V> {
+    /// Empty map.
+    #[inline]
+    pub const fn new() -> Self {
+        PersistentMap { root: None, len: 0 }
+    }
+
+    /// Number of entries.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Is the map empty?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the entries.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            iter: self.iter_hashed(),
+        }
+    }
+
+    /// Iterate over the entries, with keys hashed.
+    #[inline]
+    pub fn iter_hashed(&self) -> IterHashed<'_, K, V> {
+        IterHashed::new(self.root.as_deref(), self.len)
+    }
+
+    /// Iterate over the keys.
+    #[inline]
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { iter: self.iter() }
+    }
+
+    /// Iterate over the values.
+    #[inline]
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { iter: self.iter() }
+    }
+
+    /// Do the maps share the same memory?
+    ///
+    /// When this returns `true`, the maps are equal,
+    /// but the maps may be equal even if this returns `false`.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.root, &other.root) {
+            (None, None) => true,
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Find the entry by the key, with the key hashed.
+    #[inline]
+    pub fn get_full_hashed<Q>(&self, key: Hashed<&Q>) -> Option<(&K, &V)>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        self.root.as_ref()?.get(key)
+    }
+
+    /// Find the value by the key, with the key hashed.
+    #[inline]
+    pub fn get_hashed<Q>(&self, key: Hashed<&Q>) -> Option<&V>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        self.get_full_hashed(key).map(|(_, v)| v)
+    }
+
+    /// Find the value by the key.
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_hashed(Hashed::new(key))
+    }
+
+    /// Check if the map contains the key, with the key hashed.
+    #[inline]
+    pub fn contains_key_hashed<Q>(&self, key: Hashed<&Q>) -> bool
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        self.get_full_hashed(key).is_some()
+    }
+
+    /// Check if the map contains the key.
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.contains_key_hashed(Hashed::new(key))
+    }
+
+    /// Insert an entry into the map with the key hashed.
+    /// Return the previous value if the key was present.
+    ///
+    /// Nodes shared with other maps are copied on the path to the entry.
+    pub fn insert_hashed(&mut self, key: Hashed<K>, value: V) -> Option<V>
+    where
+        K: Eq + Clone,
+        V: Clone,
+    {
+        let old = match &mut self.root {
+            None => {
+                self.root = Some(Arc::new(Node::single(key.hash(), key.into_key(), value)));
+                None
+            }
+            Some(root) => Arc::make_mut(root).insert(0, key, value),
+        };
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Insert an entry into the map.
+    /// Return the previous value if the key was present.
+    #[inline]
+    pub fn insert(&mut self, key: K, value: V) -> Option<V>
+    where
+        K: Hash + Eq + Clone,
+        V: Clone,
+    {
+        self.insert_hashed(Hashed::new(key), value)
+    }
+
+    /// Remove the entry by the key with the key hashed, returning the removed entry.
+    ///
+    /// Nodes shared with other maps are copied on the path to the entry.
+    /// The map is not modified if the key is not present.
+    pub fn remove_hashed_entry<Q>(&mut self, key: Hashed<&Q>) -> Option<(K, V)>
+    where
+        Q: Equivalent<K> + ?Sized,
+        K: Clone,
+        V: Clone,
+    {
+        // Check first to avoid copying shared nodes when the key is not present.
+        if !self.contains_key_hashed(key) {
+            return None;
+        }
+        let root = self.root.as_mut().unwrap();
+        let removed = Arc::make_mut(root).remove(0, key);
+        if root.is_empty() {
+            self.root = None;
+        }
+        self.len -= 1;
+        Some(removed)
+    }
+
+    /// Remove the entry by the key with the key hashed.
+    #[inline]
+    pub fn remove_hashed<Q>(&mut self, key: Hashed<&Q>) -> Option<V>
+    where
+        Q: Equivalent<K> + ?Sized,
+        K: Clone,
+        V: Clone,
+    {
+        self.remove_hashed_entry(key).map(|(_, v)| v)
+    }
+
+    /// Remove the entry by the key.
+    #[inline]
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        K: Clone,
+        V: Clone,
+    {
+        self.remove_hashed(Hashed::new(key))
+    }
+
+    /// Return a copy of this map with the entry inserted.
+    #[inline]
+    pub fn update(&self, key: K, value: V) -> Self
+    where
+        K: Hash + Eq + Clone,
+        V: Clone,
+    {
+        let mut map = self.clone();
+        map.insert(key, value);
+        map
+    }
+
+    /// Return a copy of this map without the entry.
+    #[inline]
+    pub fn without<Q>(&self, key: &Q) -> Self
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        K: Clone,
+        V: Clone,
+    {
+        let mut map = self.clone();
+        map.remove(key);
+        map
+    }
+
+    /// Copy the entries into a [`SmallMap`], in the iteration order of this map.
+    pub fn to_small_map(&self) -> SmallMap<K, V>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let mut map = SmallMap::with_capacity(self.len);
+        for (k, v) in self.iter_hashed() {
+            // Keys are unique in this map.
+            map.insert_hashed_unique_unchecked(k.cloned(), v.clone());
+        }
+        map
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> From<SmallMap<K, V>> for PersistentMap<K, V> {
+    fn from(map: SmallMap<K, V>) -> Self {
+        let mut res = PersistentMap::new();
+        for (k, v) in map.into_iter_hashed() {
+            res.insert_hashed(k, v);
+        }
+        res
+    }
+}
+
+impl<K: Clone, V: Clone> From<&PersistentMap<K, V>> for SmallMap<K, V> {
+    fn from(map: &PersistentMap<K, V>) -> Self {
+        map.to_small_map()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> FromIterator<(K, V)> for PersistentMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = PersistentMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Extend<(K, V)> for PersistentMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a PersistentMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Eq, V: PartialEq> PartialEq for PersistentMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && (self.ptr_eq(other)
+                || self
+                    .iter_hashed()
+                    .all(|(k, v)| other.get_hashed(k) == Some(v)))
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for PersistentMap<K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl<K: Hash + Eq, V> PersistentMap<K, V> {
+        fn assert_invariants(&self) {
+            fn check<K: Hash + Eq, V>(node: &Node<K, V>, shift: u32, is_root: bool) -> usize {
+                match node {
+                    Node::Branch { bitmap, children } => {
+                        assert_eq!(bitmap.count_ones() as usize, children.len());
+                        if !is_root {
+                            assert!(!matches!(children.as_slice(), [Child::Leaf(..)]));
+                        }
+                        let mut len = 0;
+                        for child in children {
+                            len += match child {
+                                Child::Leaf(hash, k, _) => {
+                                    assert_eq!(*hash, StarlarkHashValue::new(k));
+                                    assert!(bitmap & fragment_bit(*hash, shift) != 0);
+                                    1
+                                }
+                                Child::Node(node) => check(node, shift + BITS, false),
+                            };
+                        }
+                        len
+                    }
+                    Node::Collision { hash, entries } => {
+                        assert!(shift > MAX_SHIFT);
+                        assert!(entries.len() >= 2);
+                        for (k, _) in entries {
+                            assert_eq!(*hash, StarlarkHashValue::new(k));
+                        }
+                        entries.len()
+                    }
+                }
+            }
+
+            match &self.root {
+                None => assert_eq!(0, self.len),
+                Some(root) => {
+                    assert!(!root.is_empty());
+                    assert_eq!(self.len, check(root, 0, true));
+                }
+            }
+            assert_eq!(self.len, self.iter().count());
+            assert_eq!(self.len, self.iter().len());
+        }
+    }
+
+    /// Key with a poor hash to exercise collision nodes.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct BadHash(u32);
+
+    impl Hash for BadHash {
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            (self.0 % 3).hash(state)
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        let m = PersistentMap::<u32, u32>::new();
+        assert!(m.is_empty());
+        assert_eq!(None, m.get(&1));
+        assert_eq!(0, m.iter().count());
+        m.assert_invariants();
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut m = PersistentMap::new();
+        for i in 0..1000 {
+            assert_eq!(None, m.insert(i, i * 10));
+        }
+        m.assert_invariants();
+        assert_eq!(1000, m.len());
+        for i in 0..1000 {
+            assert_eq!(Some(&(i * 10)), m.get(&i));
+        }
+        assert_eq!(None, m.get(&1000));
+        assert_eq!(Some(50), m.insert(5, 51));
+        assert_eq!(Some(&51), m.get(&5));
+        for i in (0..1000).step_by(2) {
+            assert_eq!(Some(i * 10), m.remove(&i));
+        }
+        m.assert_invariants();
+        assert_eq!(500, m.len());
+        assert_eq!(None, m.remove(&0));
+        for i in (1..1000).step_by(2) {
+            m.remove(&i);
+        }
+        m.assert_invariants();
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn test_structural_sharing() {
+        let base: PersistentMap<String, u32> = (0..100).map(|i| (i.to_string(), i)).collect();
+        let updated = base.update("5".to_owned(), 500);
+        let removed = base.without("7");
+        assert_eq!(Some(&5), base.get("5"));
+        assert_eq!(Some(&500), updated.get("5"));
+        assert_eq!(Some(&7), base.get("7"));
+        assert_eq!(None, removed.get("7"));
+        assert_eq!(99, removed.len());
+        assert_eq!(100, base.len());
+        base.assert_invariants();
+        updated.assert_invariants();
+        removed.assert_invariants();
+
+        let clone = base.clone();
+        assert!(clone.ptr_eq(&base));
+        assert_eq!(clone, base);
+        assert_ne!(updated, base);
+        assert_eq!(base.without("100"), base);
+        assert!(base.without("100").ptr_eq(&base));
+    }
+
+    #[test]
+    fn test_collisions() {
+        let mut m = PersistentMap::new();
+        for i in 0..30 {
+            m.insert(BadHash(i), i);
+        }
+        m.assert_invariants();
+        for i in 0..30 {
+            assert_eq!(Some(&i), m.get(&BadHash(i)));
+        }
+        assert_eq!(None, m.get(&BadHash(30)));
+        let copy = m.clone();
+        for i in 0..29 {
+            assert_eq!(Some(i), m.remove(&BadHash(i)));
+            m.assert_invariants();
+        }
+        assert_eq!(1, m.len());
+        assert_eq!(Some(&29), m.get(&BadHash(29)));
+        assert_eq!(30, copy.len());
+        copy.assert_invariants();
+    }
+
+    #[test]
+    fn test_small_map_conversion() {
+        let small: SmallMap<String, u32> = (0..50).map(|i| (i.to_string(), i)).collect();
+        let persistent = PersistentMap::from(small.clone());
+        persistent.assert_invariants();
+        assert_eq!(50, persistent.len());
+        let back = persistent.to_small_map();
+        assert_eq!(small.len(), back.len());
+        for (k, v) in &small {
+            assert_eq!(Some(v), back.get(k));
+        }
+        // Iteration order is the same for equal maps.
+        let other: PersistentMap<String, u32> = small.into_iter().rev().collect();
+        assert!(persistent.iter().eq(other.iter()));
+    }
+
+    #[test]
+    fn test_against_small_map() {
+        // Linear congruential generator, deterministic.
+        let mut state = 1u64;
+        let mut next = move || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as u32
+        };
+        let mut expected = SmallMap::new();
+        let mut m = PersistentMap::new();
+        let mut snapshots = Vec::new();
+        for i in 0..3000 {
+            let key = next() % 300;
+            if next() % 3 == 0 {
+                assert_eq!(expected.remove(&key), m.remove(&key));
+            } else {
+                assert_eq!(expected.insert(key, i), m.insert(key, i));
+            }
+            if i % 500 == 0 {
+                snapshots.push((expected.clone(), m.clone()));
+            }
+        }
+        snapshots.push((expected, m));
+        for (expected, m) in snapshots {
+            m.assert_invariants();
+            assert_eq!(expected.len(), m.len());
+            for (k, v) in &expected {
+                assert_eq!(Some(v), m.get(k));
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Persistent set with structural sharing.
+
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::iter::FusedIterator;
+
+use allocative::Allocative;
+use dupe::Clone_;
+use gazebo::prelude::*;
+
+use crate::equivalent::Equivalent;
+use crate::hashed::Hashed;
+use crate::persistent_map;
+use crate::persistent_map::PersistentMap;
+use crate::small_set::SmallSet;
+
+/// Persistent hash set, based on [`PersistentMap`].
+///
+/// Like [`PersistentMap`], iteration order is the order of hashes, not insertion order.
+#[derive(Clone_, Default_)]
+pub struct PersistentSet<T>(PersistentMap<T, ()>);
+
+impl<T: Allocative> Allocative for PersistentSet<T> {
+    fn visit<'a, 'b: 'a>(&self, visitor: &'a mut allocative::Visitor<'b>) {
+        let mut visitor = visitor.enter_self_sized::<Self>();
+        visitor.visit_field(allocative::Key::new("0"), &self.0);
+        visitor.exit();
+    }
+}
+
+impl<T: Eq> PartialEq for PersistentSet<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: Eq> Eq for PersistentSet<T> {}
+
+impl<T: Debug> Debug for PersistentSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T> PersistentSet<T> {
+    /// Empty set.
+    #[inline]
+    pub const fn new() -> Self {
+        PersistentSet(PersistentMap::new())
+    }
+
+    /// Number of elements.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Is the set empty?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate over the elements.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            iter: self.0.keys(),
+        }
+    }
+
+    /// Do the sets share the same memory?
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.0.ptr_eq(&other.0)
+    }
+
+    /// Check if the set contains the element, with the element hashed.
+    #[inline]
+    pub fn contains_hashed<Q>(&self, value: Hashed<&Q>) -> bool
+    where
+        Q: Equivalent<T> + ?Sized,
+    {
+        self.0.contains_key_hashed(value)
+    }
+
+    /// Check if the set contains the element.
+    #[inline]
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+    {
+        self.0.contains_key(value)
+    }
+
+    /// Insert the element with the element hashed.
+    /// Return `true` if the element was not present.
+    #[inline]
+    pub fn insert_hashed(&mut self, value: Hashed<T>) -> bool
+    where
+        T: Eq + Clone,
+    {
+        self.0.insert_hashed(value, ()).is_none()
+    }
+
+    /// Insert the element.
+    /// Return `true` if the element was not present.
+    #[inline]
+    pub fn insert(&mut self, value: T) -> bool
+    where
+        T: Hash + Eq + Clone,
+    {
+        self.insert_hashed(Hashed::new(value))
+    }
+
+    /// Remove the element.
+    /// Return `true` if the element was present.
+    #[inline]
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+        T: Clone,
+    {
+        self.0.remove(value).is_some()
+    }
+
+    /// Copy the elements into a [`SmallSet`], in the iteration order of this set.
+    pub fn to_small_set(&self) -> SmallSet<T>
+    where
+        T: Clone,
+    {
+        let mut set = SmallSet::with_capacity(self.len());
+        for (k, ()) in self.0.iter_hashed() {
+            // Elements are unique in this set.
+            set.insert_hashed_unique_unchecked(k.cloned());
+        }
+        set
+    }
+}
+
+impl<T: Hash + Eq + Clone> From<SmallSet<T>> for PersistentSet<T> {
+    fn from(set: SmallSet<T>) -> Self {
+        let mut res = PersistentSet::new();
+        for x in set.into_iter_hashed() {
+            res.insert_hashed(x);
+        }
+        res
+    }
+}
+
+impl<T: Clone> From<&PersistentSet<T>> for SmallSet<T> {
+    fn from(set: &PersistentSet<T>) -> Self {
+        set.to_small_set()
+    }
+}
+
+impl<T: Hash + Eq + Clone> FromIterator<T> for PersistentSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        PersistentSet(iter.into_iter().map(|x| (x, ())).collect())
+    }
+}
+
+impl<T: Hash + Eq + Clone> Extend<T> for PersistentSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.0.extend(iter.into_iter().map(|x| (x, ())))
+    }
+}
+
+impl<'a, T> IntoIterator for &'a PersistentSet<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the elements of [`PersistentSet`].
+#[derive(Clone_)]
+pub struct Iter<'a, T> {
+    iter: persistent_map::Keys<'a, T, ()>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, T> FusedIterator for Iter<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_persistent_set() {
+        let base: PersistentSet<String> = (0..100).map(|i| i.to_string()).collect();
+        let mut derived = base.clone();
+        assert!(derived.ptr_eq(&base));
+        assert!(!derived.insert("5".to_owned()));
+        assert!(derived.insert("100".to_owned()));
+        assert!(derived.remove("0"));
+        assert!(!derived.remove("0"));
+        assert_eq!(100, base.len());
+        assert_eq!(100, derived.len());
+        assert!(base.contains("0"));
+        assert!(!derived.contains("0"));
+        assert!(derived.contains("100"));
+        assert_ne!(base, derived);
+
+        let small = derived.to_small_set();
+        assert_eq!(100, small.len());
+        assert_eq!(derived, PersistentSet::from(small));
+    }
+}
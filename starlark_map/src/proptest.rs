@@ -0,0 +1,163 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! [`proptest`] support for the collections of this crate, enabled by the `proptest` feature.
+//!
+//! Collections are generated from a vector of elements inserted in order,
+//! so for keys repeated in the vector the first position and the last value are used.
+//! Shrinking shrinks the vector, which removes entries or simplifies them,
+//! but never reorders the remaining entries.
+
+use std::fmt::Debug;
+use std::hash::BuildHasher;
+use std::hash::Hash;
+
+use proptest::arbitrary::any_with;
+use proptest::arbitrary::Arbitrary;
+use proptest::collection::vec;
+use proptest::collection::SizeRange;
+use proptest::collection::VecStrategy;
+use proptest::strategy::Map;
+use proptest::strategy::Strategy;
+
+use crate::small_map::SmallMap;
+use crate::small_set::SmallSet;
+use crate::vec2::Vec2;
+
+fn small_map_from_entries<K: Hash + Eq, V, S: BuildHasher + Default>(
+    entries: Vec<(K, V)>,
+) -> SmallMap<K, V, S> {
+    let mut res = SmallMap::with_capacity_and_hasher(entries.len(), S::default());
+    for (k, v) in entries {
+        res.insert(k, v);
+    }
+    res
+}
+
+fn small_set_from_elements<T: Hash + Eq, S: BuildHasher + Default>(
+    elements: Vec<T>,
+) -> SmallSet<T, S> {
+    let mut res = SmallSet::with_capacity_and_hasher(elements.len(), S::default());
+    for x in elements {
+        res.insert(x);
+    }
+    res
+}
+
+fn vec2_from_elements<A, B>(elements: Vec<(A, B)>) -> Vec2<A, B> {
+    elements.into_iter().collect()
+}
+
+impl<K, V, S> Arbitrary for SmallMap<K, V, S>
+where
+    K: Arbitrary + Hash + Eq,
+    V: Arbitrary,
+    S: BuildHasher + Default + Debug,
+{
+    type Parameters = (SizeRange, (K::Parameters, V::Parameters));
+    type Strategy = Map<VecStrategy<<(K, V) as Arbitrary>::Strategy>, fn(Vec<(K, V)>) -> Self>;
+
+    fn arbitrary_with((size, args): Self::Parameters) -> Self::Strategy {
+        vec(any_with::<(K, V)>(args), size).prop_map(small_map_from_entries)
+    }
+}
+
+impl<T, S> Arbitrary for SmallSet<T, S>
+where
+    T: Arbitrary + Hash + Eq,
+    S: BuildHasher + Default + Debug,
+{
+    type Parameters = (SizeRange, T::Parameters);
+    type Strategy = Map<VecStrategy<T::Strategy>, fn(Vec<T>) -> Self>;
+
+    fn arbitrary_with((size, args): Self::Parameters) -> Self::Strategy {
+        vec(any_with::<T>(args), size).prop_map(small_set_from_elements)
+    }
+}
+
+impl<A: Arbitrary, B: Arbitrary> Arbitrary for Vec2<A, B> {
+    type Parameters = (SizeRange, (A::Parameters, B::Parameters));
+    type Strategy = Map<VecStrategy<<(A, B) as Arbitrary>::Strategy>, fn(Vec<(A, B)>) -> Self>;
+
+    fn arbitrary_with((size, args): Self::Parameters) -> Self::Strategy {
+        vec(any_with::<(A, B)>(args), size).prop_map(vec2_from_elements)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::arbitrary::any;
+    use proptest::strategy::Strategy;
+    use proptest::strategy::ValueTree;
+    use proptest::test_runner::TestRunner;
+
+    use crate::small_map::SmallMap;
+    use crate::small_set::SmallSet;
+    use crate::vec2::Vec2;
+
+    /// Whether `xs` is a subsequence of `ys`.
+    fn is_subsequence<T: PartialEq>(xs: &[T], ys: &[T]) -> bool {
+        let mut ys = ys.iter();
+        xs.iter().all(|x| ys.any(|y| y == x))
+    }
+
+    #[test]
+    fn test_small_map_shrink_keeps_order() {
+        let mut runner = TestRunner::deterministic();
+        for _ in 0..20 {
+            let mut tree = any::<SmallMap<u16, u8>>().new_tree(&mut runner).unwrap();
+            let mut prev: Vec<u16> = tree.current().keys().copied().collect();
+            while tree.simplify() {
+                let map = tree.current();
+                let keys: Vec<u16> = map.keys().copied().collect();
+                for (k, v) in &map {
+                    assert_eq!(Some(v), map.get(k));
+                }
+                // Entries are only removed, or keys shrunk towards zero.
+                assert!(keys.len() <= prev.len());
+                if keys.len() < prev.len() {
+                    assert!(is_subsequence(&keys, &prev));
+                }
+                prev = keys;
+            }
+        }
+    }
+
+    #[test]
+    fn test_small_set() {
+        let mut runner = TestRunner::deterministic();
+        for _ in 0..20 {
+            let set = any::<SmallSet<u8>>()
+                .new_tree(&mut runner)
+                .unwrap()
+                .current();
+            for x in &set {
+                assert!(set.contains(x));
+            }
+        }
+    }
+
+    #[test]
+    fn test_vec2() {
+        let mut runner = TestRunner::deterministic();
+        let v = any::<Vec2<u8, String>>()
+            .new_tree(&mut runner)
+            .unwrap()
+            .current();
+        assert_eq!(v.aaa().len(), v.bbb().len());
+    }
+}
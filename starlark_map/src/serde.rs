@@ -0,0 +1,202 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! [`serde`] support for the collections of this crate, enabled by the `serde` feature.
+//!
+//! Maps are serialized as maps and sets as sequences, in iteration order,
+//! so [`SmallMap`] and [`SmallSet`] keep their insertion order on a round-trip.
+//! [`Hashed`] is serialized as its key, the hash is recomputed on deserialization.
+
+use std::fmt;
+use std::hash::BuildHasher;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+use crate::small_map::SmallMap;
+use crate::small_set::SmallSet;
+use crate::sorted_vec_map::SortedVecMap;
+use crate::Hashed;
+
+impl<K: Serialize, V: Serialize, S> Serialize for SmallMap<K, V, S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+impl<'de, K, V, S> Deserialize<'de> for SmallMap<K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MapVisitor<K, V, S>(PhantomData<SmallMap<K, V, S>>);
+
+        impl<'de, K, V, S> Visitor<'de> for MapVisitor<K, V, S>
+        where
+            K: Deserialize<'de> + Hash + Eq,
+            V: Deserialize<'de>,
+            S: BuildHasher + Default,
+        {
+            type Value = SmallMap<K, V, S>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a map")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut res = SmallMap::with_capacity_and_hasher(
+                    map.size_hint().unwrap_or_default(),
+                    S::default(),
+                );
+                while let Some((k, v)) = map.next_entry()? {
+                    res.insert(k, v);
+                }
+                Ok(res)
+            }
+        }
+
+        deserializer.deserialize_map(MapVisitor(PhantomData))
+    }
+}
+
+impl<T: Serialize, S> Serialize for SmallSet<T, S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de, T, S> Deserialize<'de> for SmallSet<T, S>
+where
+    T: Deserialize<'de> + Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SetVisitor<T, S>(PhantomData<SmallSet<T, S>>);
+
+        impl<'de, T, S> Visitor<'de> for SetVisitor<T, S>
+        where
+            T: Deserialize<'de> + Hash + Eq,
+            S: BuildHasher + Default,
+        {
+            type Value = SmallSet<T, S>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a sequence")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut res = SmallSet::with_capacity_and_hasher(
+                    seq.size_hint().unwrap_or_default(),
+                    S::default(),
+                );
+                while let Some(x) = seq.next_element()? {
+                    res.insert(x);
+                }
+                Ok(res)
+            }
+        }
+
+        deserializer.deserialize_seq(SetVisitor(PhantomData))
+    }
+}
+
+impl<K: Serialize, V: Serialize> Serialize for SortedVecMap<K, V> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+impl<'de, K, V> Deserialize<'de> for SortedVecMap<K, V>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Entries are sorted by hash, so collect them in any order.
+        let map = SmallMap::<K, V>::deserialize(deserializer)?;
+        Ok(map.into_iter().collect())
+    }
+}
+
+impl<K: Serialize> Serialize for Hashed<K> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        self.key().serialize(serializer)
+    }
+}
+
+impl<'de, K: Deserialize<'de> + Hash> Deserialize<'de> for Hashed<K> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Hashed::new(K::deserialize(deserializer)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::small_map::SmallMap;
+    use crate::small_set::SmallSet;
+    use crate::sorted_vec_map::SortedVecMap;
+    use crate::Hashed;
+
+    #[test]
+    fn test_small_map() {
+        let mut m = SmallMap::new();
+        for i in (0..20).rev() {
+            m.insert(i.to_string(), i);
+        }
+        let json = serde_json::to_string(&m).unwrap();
+        assert!(json.starts_with(r#"{"19":19,"18":18,"#));
+        let m2: SmallMap<String, i32> = serde_json::from_str(&json).unwrap();
+        assert!(m.eq_ordered(&m2));
+        assert_eq!(Some(&5), m2.get("5"));
+    }
+
+    #[test]
+    fn test_small_set() {
+        let s: SmallSet<&str> = ["c", "a", "b"].into_iter().collect();
+        let json = serde_json::to_string(&s).unwrap();
+        assert_eq!(r#"["c","a","b"]"#, json);
+        let s2: SmallSet<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(vec!["c", "a", "b"], s2.iter().collect::<Vec<_>>());
+        assert!(s2.contains("a"));
+    }
+
+    #[test]
+    fn test_sorted_vec_map() {
+        let m: SortedVecMap<String, u32> = (0..10).map(|i| (i.to_string(), i)).collect();
+        let json = serde_json::to_string(&m).unwrap();
+        let m2: SortedVecMap<String, u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(m, m2);
+        assert_eq!(Some(&3), m2.get("3"));
+    }
+
+    #[test]
+    fn test_hashed() {
+        let x = Hashed::new("x".to_owned());
+        let json = serde_json::to_string(&x).unwrap();
+        assert_eq!(r#""x""#, json);
+        assert_eq!(x, serde_json::from_str(&json).unwrap());
+    }
+}
@@ -15,6 +15,8 @@
  * limitations under the License.
  */
 
+use std::iter::FusedIterator;
+
 use dupe::Clone_;
 
 use crate::iter::def_double_ended_iter;
@@ -48,6 +50,8 @@ impl<'a, K, V> ExactSizeIterator for IterHashed<'a, K, V> {
     }
 }
 
+impl<'a, K, V> FusedIterator for IterHashed<'a, K, V> {}
+
 impl<'a, K, V> DoubleEndedIterator for IterHashed<'a, K, V> {
     def_double_ended_iter!();
 }
@@ -78,6 +82,8 @@ impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {
     }
 }
 
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
 impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
     def_double_ended_iter!();
 }
@@ -121,6 +127,8 @@ impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {
     }
 }
 
+impl<'a, K, V> FusedIterator for IterMut<'a, K, V> {}
+
 impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
     def_double_ended_iter!();
 }
@@ -138,6 +146,8 @@ impl<'a, K, V> ExactSizeIterator for IterMutUnchecked<'a, K, V> {
     }
 }
 
+impl<'a, K, V> FusedIterator for IterMutUnchecked<'a, K, V> {}
+
 impl<'a, K, V> DoubleEndedIterator for IterMutUnchecked<'a, K, V> {
     def_double_ended_iter!();
 }
@@ -167,6 +177,8 @@ impl<K, V> ExactSizeIterator for IntoIterHashed<K, V> {
     }
 }
 
+impl<K, V> FusedIterator for IntoIterHashed<K, V> {}
+
 impl<K, V> DoubleEndedIterator for IntoIterHashed<K, V> {
     def_double_ended_iter!();
 }
@@ -196,10 +208,44 @@ impl<K, V> ExactSizeIterator for IntoIter<K, V> {
     }
 }
 
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
 impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
     def_double_ended_iter!();
 }
 
+/// Iterator that moves entries out of a [`SmallMap`](crate::small_map::SmallMap),
+/// leaving the map empty.
+pub struct Drain<'a, K, V> {
+    pub(crate) iter: vec_map::Drain<'a, K, V>,
+}
+
+impl<'a, K, V> Drain<'a, K, V> {
+    #[inline]
+    fn map((k, v): (K, V)) -> <Self as Iterator>::Item {
+        (k, v)
+    }
+}
+
+impl<'a, K, V> Iterator for Drain<'a, K, V> {
+    type Item = (K, V);
+
+    def_iter!();
+}
+
+impl<'a, K, V> ExactSizeIterator for Drain<'a, K, V> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, K, V> FusedIterator for Drain<'a, K, V> {}
+
+impl<'a, K, V> DoubleEndedIterator for Drain<'a, K, V> {
+    def_double_ended_iter!();
+}
+
 /// Iterator over a [`SmallMap`](crate::small_map::SmallMap) keys.
 #[derive(Clone_)]
 pub struct Keys<'a, K, V> {
@@ -226,6 +272,8 @@ impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {
     }
 }
 
+impl<'a, K, V> FusedIterator for Keys<'a, K, V> {}
+
 impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
     def_double_ended_iter!();
 }
@@ -256,6 +304,8 @@ impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {
     }
 }
 
+impl<'a, K, V> FusedIterator for Values<'a, K, V> {}
+
 impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
     def_double_ended_iter!();
 }
@@ -285,6 +335,8 @@ impl<'a, K, V> ExactSizeIterator for ValuesMut<'a, K, V> {
     }
 }
 
+impl<'a, K, V> FusedIterator for ValuesMut<'a, K, V> {}
+
 impl<'a, K, V> DoubleEndedIterator for ValuesMut<'a, K, V> {
     def_double_ended_iter!();
 }
@@ -303,6 +355,9 @@ fn _assert_iterators_sync_send() {
     fn test_into_iter(iter: IntoIter<String, u32>) {
         assert_sync_send(iter);
     }
+    fn test_drain(iter: Drain<String, u32>) {
+        assert_sync_send(iter);
+    }
     fn test_keys(iter: Keys<String, u32>) {
         assert_sync_send(iter);
     }
@@ -20,20 +20,25 @@
 //! * no index is created for small maps
 //! * short hashes are stored next to keys
 
+use std::cmp;
 use std::fmt;
 use std::fmt::Debug;
+use std::hash::BuildHasher;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::mem;
+use std::ops::Range;
+use std::ops::RangeBounds;
 
 use allocative::Allocative;
+use allocative::Visitor;
 use gazebo::coerce::Coerce;
 use gazebo::coerce::CoerceKey;
-use gazebo::prelude::*;
 use hashbrown::raw::RawTable;
 
 use crate::equivalent::Equivalent;
 use crate::hashed::Hashed;
+pub use crate::small_map::iter::Drain;
 pub use crate::small_map::iter::IntoIter;
 pub use crate::small_map::iter::IntoIterHashed;
 pub use crate::small_map::iter::Iter;
@@ -45,6 +50,8 @@ pub use crate::small_map::iter::Values;
 pub use crate::small_map::iter::ValuesMut;
 use crate::vec_map::VecMap;
 use crate::StarlarkHashValue;
+use crate::StarlarkHasherBuilder;
+use crate::TryReserveError;
 
 mod iter;
 
@@ -64,13 +71,43 @@ const NO_INDEX_THRESHOLD: usize = 16;
 /// * Variants which take an already hashed value, e.g. [`get_hashed`](SmallMap::get_hashed).
 ///
 /// * Functions which work with the position, e.g. [`get_index_of`](SmallMap::get_index_of).
+///
+/// Keys are hashed with [`StarlarkHasherBuilder`] by default, which is fast and stable,
+/// but not resistant to collision attacks. A different hasher can be chosen with
+/// [`with_hasher`](SmallMap::with_hasher), e.g. a keyed hasher when keys come from untrusted input.
+/// Note the functions which take already hashed values (e.g. [`get_hashed`](SmallMap::get_hashed))
+/// expect the hash to be computed with the map hasher (see [`hash_key`](SmallMap::hash_key)).
+///
+/// Maps which are usually tiny and not stored for long can use
+/// [`InlineMap`](crate::inline_map::InlineMap) instead, which does not allocate for a few entries.
 #[repr(C)]
-#[derive(Clone, Default_, Allocative)]
-pub struct SmallMap<K, V> {
+#[derive(Clone)]
+pub struct SmallMap<K, V, S = StarlarkHasherBuilder> {
     entries: VecMap<K, V>,
     /// Map a key to the index in `entries`.
     /// This field is initialized when the size of the map exceeds `NO_INDEX_THRESHOLD`.
     index: Option<Box<RawTable<usize>>>,
+    /// Hasher used to hash keys, zero-sized for the default hasher.
+    hasher: S,
+}
+
+// `SmallMap` is embedded in many values, e.g. dicts, keep it small.
+const _: () = assert!(mem::size_of::<SmallMap<u64, u64>>() <= 4 * mem::size_of::<usize>());
+
+impl<K: Allocative, V: Allocative, S> Allocative for SmallMap<K, V, S> {
+    fn visit<'a, 'b: 'a>(&self, visitor: &'a mut Visitor<'b>) {
+        let mut visitor = visitor.enter_self_sized::<Self>();
+        visitor.visit_field(allocative::Key::new("entries"), &self.entries);
+        visitor.visit_field(allocative::Key::new("index"), &self.index);
+        visitor.exit();
+    }
+}
+
+impl<K, V, S: Default> Default for SmallMap<K, V, S> {
+    #[inline]
+    fn default() -> Self {
+        SmallMap::with_hasher(S::default())
+    }
 }
 
 unsafe impl<FromK, FromV, ToK, ToV> Coerce<SmallMap<ToK, ToV>> for SmallMap<FromK, FromV>
@@ -80,7 +117,7 @@ where
 {
 }
 
-impl<K: Debug, V: Debug> Debug for SmallMap<K, V> {
+impl<K: Debug, V: Debug, S> Debug for SmallMap<K, V, S> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_map().entries(self.iter()).finish()
     }
@@ -93,25 +130,87 @@ impl<K, V> SmallMap<K, V> {
         Self {
             entries: VecMap::new(),
             index: None,
+            hasher: StarlarkHasherBuilder,
         }
     }
 
     /// Create an empty map with specified capacity.
     #[inline]
     pub fn with_capacity(n: usize) -> Self {
+        Self::with_capacity_and_hasher(n, StarlarkHasherBuilder)
+    }
+
+    /// Create an empty map with specified capacity,
+    /// return an error if memory cannot be allocated.
+    #[inline]
+    pub fn try_with_capacity(n: usize) -> Result<Self, TryReserveError> {
+        Self::try_with_capacity_and_hasher(n, StarlarkHasherBuilder)
+    }
+}
+
+impl<K, V, S> SmallMap<K, V, S> {
+    /// Empty map which uses the given hasher to hash keys.
+    #[inline]
+    pub const fn with_hasher(hasher: S) -> Self {
+        SmallMap {
+            entries: VecMap::new(),
+            index: None,
+            hasher,
+        }
+    }
+
+    /// Create an empty map with specified capacity and hasher.
+    #[inline]
+    pub fn with_capacity_and_hasher(n: usize, hasher: S) -> Self {
         if n <= NO_INDEX_THRESHOLD {
             SmallMap {
                 entries: VecMap::with_capacity(n),
                 index: None,
+                hasher,
             }
         } else {
             SmallMap {
                 entries: VecMap::with_capacity(n),
                 index: Some(Box::new(RawTable::with_capacity(n))),
+                hasher,
             }
         }
     }
 
+    /// Create an empty map with specified capacity and hasher,
+    /// return an error if memory cannot be allocated.
+    pub fn try_with_capacity_and_hasher(n: usize, hasher: S) -> Result<Self, TryReserveError> {
+        let entries = VecMap::try_with_capacity(n)?;
+        let index = if n <= NO_INDEX_THRESHOLD {
+            None
+        } else {
+            Some(Box::new(RawTable::try_with_capacity(n)?))
+        };
+        Ok(SmallMap {
+            entries,
+            index,
+            hasher,
+        })
+    }
+
+    /// Hasher used to hash keys of this map.
+    #[inline]
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Hash the key with the hasher of this map.
+    ///
+    /// The result can be passed to the functions which take prehashed keys.
+    #[inline]
+    pub fn hash_key<Q>(&self, key: Q) -> Hashed<Q>
+    where
+        Q: Hash,
+        S: BuildHasher,
+    {
+        Hashed::new_unchecked(StarlarkHashValue::new_with_hasher(&self.hasher, &key), key)
+    }
+
     /// Verify that the map is internally consistent.
     #[cfg(test)]
     fn assert_invariants(&self)
@@ -177,6 +276,21 @@ impl<K, V> SmallMap<K, V> {
         }
     }
 
+    /// Entry references iterator over the entries with indices in the range.
+    #[inline]
+    pub(crate) fn iter_range(&self, range: Range<usize>) -> Iter<'_, K, V> {
+        Iter {
+            iter: self.entries.iter_range(range),
+        }
+    }
+
+    /// Index of the first entry whose key does not satisfy the predicate,
+    /// assuming the entries are partitioned by it.
+    #[inline]
+    pub(crate) fn partition_point(&self, pred: impl FnMut(&K) -> bool) -> usize {
+        self.entries.partition_point(pred)
+    }
+
     /// Entry references with hashes iterator.
     #[inline]
     pub fn iter_hashed(&self) -> IterHashed<K, V> {
@@ -246,8 +360,9 @@ impl<K, V> SmallMap<K, V> {
     pub fn get<Q>(&self, key: &Q) -> Option<&V>
     where
         Q: Hash + Equivalent<K> + ?Sized,
+        S: BuildHasher,
     {
-        self.get_hashed(Hashed::new(key))
+        self.get_hashed(self.hash_key(key))
     }
 
     /// Query the map by a given key, return an index of the entry
@@ -256,8 +371,9 @@ impl<K, V> SmallMap<K, V> {
     pub fn get_full<Q>(&self, key: &Q) -> Option<(usize, &K, &V)>
     where
         Q: Hash + Equivalent<K> + ?Sized,
+        S: BuildHasher,
     {
-        self.get_full_hashed(Hashed::new(key))
+        self.get_full_hashed(self.hash_key(key))
     }
 
     /// Query the map by a given key, return an index of the entry
@@ -273,6 +389,23 @@ impl<K, V> SmallMap<K, V> {
         })
     }
 
+    /// Query the map by a precomputed hash and a key equality function,
+    /// return an index of the entry along with the entry key and value.
+    ///
+    /// The key is not hashed, so the same hash can be used to probe several maps,
+    /// as long as they use the same hasher.
+    #[inline]
+    pub fn get_full_from_hash(
+        &self,
+        hash: StarlarkHashValue,
+        eq: impl FnMut(&K) -> bool,
+    ) -> Option<(usize, &K, &V)> {
+        self.get_index_of_hashed_raw(hash, eq).map(|index| {
+            let (key, value) = unsafe { self.entries.get_unchecked(index) };
+            (index, *key.key(), value)
+        })
+    }
+
     #[inline]
     fn get_index_of_hashed_raw_with_index(
         &self,
@@ -323,13 +456,25 @@ impl<K, V> SmallMap<K, V> {
         self.entries.get_index(index)
     }
 
+    /// Find an entry by an index, with a mutable reference to the value.
+    #[inline]
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        if index < self.entries.len() {
+            let (key, value) = unsafe { self.entries.get_unchecked_mut(index) };
+            Some((key.into_key(), value))
+        } else {
+            None
+        }
+    }
+
     /// The an entry index by a given key.
     #[inline]
     pub fn get_index_of<Q>(&self, key: &Q) -> Option<usize>
     where
         Q: Hash + Equivalent<K> + ?Sized,
+        S: BuildHasher,
     {
-        self.get_index_of_hashed(Hashed::new(key))
+        self.get_index_of_hashed(self.hash_key(key))
     }
 
     /// Find a mutable value by a hashed key.
@@ -348,8 +493,42 @@ impl<K, V> SmallMap<K, V> {
     pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
     where
         Q: Hash + Equivalent<K> + ?Sized,
+        S: BuildHasher,
+    {
+        self.get_mut_hashed(self.hash_key(key))
+    }
+
+    /// Find mutable values by hashed keys.
+    ///
+    /// Returns `None` if any key is missing or if any two keys refer to the same entry.
+    pub fn get_many_mut_hashed<Q, const N: usize>(
+        &mut self,
+        keys: [Hashed<&Q>; N],
+    ) -> Option<[&mut V; N]>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        let mut indices = [0; N];
+        for (i, key) in keys.into_iter().enumerate() {
+            let index = self.get_index_of_hashed(key)?;
+            if indices[..i].contains(&index) {
+                return None;
+            }
+            indices[i] = index;
+        }
+        Some(unsafe { self.entries.get_many_unchecked_mut(indices) })
+    }
+
+    /// Find mutable values by keys, like [`get_mut`](SmallMap::get_mut)
+    /// but allowing to hold references to several values at once.
+    ///
+    /// Returns `None` if any key is missing or if any two keys refer to the same entry.
+    pub fn get_many_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> Option<[&mut V; N]>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        S: BuildHasher,
     {
-        self.get_mut_hashed(Hashed::new(key))
+        self.get_many_mut_hashed(keys.map(|key| self.hash_key(key)))
     }
 
     /// Find if an entry by a given prehashed key exists.
@@ -375,8 +554,9 @@ impl<K, V> SmallMap<K, V> {
     pub fn contains_key<Q>(&self, key: &Q) -> bool
     where
         Q: Hash + Equivalent<K> + ?Sized,
+        S: BuildHasher,
     {
-        self.contains_key_hashed(Hashed::new(key))
+        self.contains_key_hashed(self.hash_key(key))
     }
 
     /// Reserve capacity for at least `additional` more elements to be inserted.
@@ -387,12 +567,73 @@ impl<K, V> SmallMap<K, V> {
     {
         self.entries.reserve(additional);
         if let Some(index) = &mut self.index {
-            index.reserve(additional, Self::hasher(&self.entries));
+            index.reserve(additional, Self::index_hasher(&self.entries));
         } else if self.len() + additional > NO_INDEX_THRESHOLD {
             self.create_index(self.len() + additional);
         }
     }
 
+    /// Like [`reserve`](SmallMap::reserve), but return an error
+    /// instead of panicking when memory cannot be allocated.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.entries.try_reserve(additional)?;
+        if let Some(index) = &mut self.index {
+            index.try_reserve(additional, Self::index_hasher(&self.entries))?;
+        } else if self.len() + additional > NO_INDEX_THRESHOLD {
+            self.init_index(RawTable::try_with_capacity(self.len() + additional)?);
+        }
+        Ok(())
+    }
+
+    /// Reserve capacity for exactly `additional` more entries,
+    /// without the amortized growth of [`reserve`](SmallMap::reserve).
+    ///
+    /// The hash index, if any, may still over-allocate.
+    pub fn reserve_exact(&mut self, additional: usize)
+    where
+        K: Eq,
+    {
+        self.entries.reserve_exact(additional);
+        if let Some(index) = &mut self.index {
+            index.reserve(additional, Self::index_hasher(&self.entries));
+        } else if self.len() + additional > NO_INDEX_THRESHOLD {
+            self.create_index(self.len() + additional);
+        }
+    }
+
+    /// Like [`reserve_exact`](SmallMap::reserve_exact), but return an error
+    /// instead of panicking when memory cannot be allocated.
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.entries.try_reserve_exact(additional)?;
+        if let Some(index) = &mut self.index {
+            index.try_reserve(additional, Self::index_hasher(&self.entries))?;
+        } else if self.len() + additional > NO_INDEX_THRESHOLD {
+            self.init_index(RawTable::try_with_capacity(self.len() + additional)?);
+        }
+        Ok(())
+    }
+
+    /// Shrink the capacity of the map as much as possible.
+    ///
+    /// This also drops the index if the map is small enough to not need it.
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    /// Shrink the capacity of the map with a lower limit.
+    ///
+    /// The capacity remains at least as large as both the length and `min_capacity`.
+    /// Like [`shrink_to_fit`](SmallMap::shrink_to_fit), this drops the index
+    /// if the map is small enough to not need it.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.entries.shrink_to(min_capacity);
+        self.maybe_drop_index();
+        if let Some(index) = &mut self.index {
+            let min_capacity = cmp::max(self.entries.len(), min_capacity);
+            index.shrink_to(min_capacity, Self::index_hasher(&self.entries));
+        }
+    }
+
     /// Current map capacity.
     #[inline]
     pub fn capacity(&self) -> usize {
@@ -411,9 +652,14 @@ impl<K, V> SmallMap<K, V> {
 
     #[cold]
     fn create_index(&mut self, capacity: usize) {
-        debug_assert!(self.index.is_none());
         debug_assert!(capacity >= self.entries.len());
-        let mut index = RawTable::with_capacity(capacity);
+        self.init_index(RawTable::with_capacity(capacity));
+    }
+
+    /// Populate the given empty index with the entries and install it.
+    fn init_index(&mut self, mut index: RawTable<usize>) {
+        debug_assert!(self.index.is_none());
+        assert!(index.capacity() >= self.entries.len());
         for (i, (k, _)) in self.entries.iter_hashed().enumerate() {
             // SAFETY: capacity >= self.entries.len()
             unsafe { index.insert_no_grow(k.hash().promote(), i) };
@@ -423,13 +669,22 @@ impl<K, V> SmallMap<K, V> {
 
     /// Hasher for index resize.
     #[inline(always)]
-    fn hasher(entries: &VecMap<K, V>) -> impl Fn(&usize) -> u64 + '_ {
+    fn index_hasher(entries: &VecMap<K, V>) -> impl Fn(&usize) -> u64 + '_ {
         move |&index| {
             debug_assert!(index < entries.len());
             unsafe { entries.get_unchecked(index).0.hash().promote() }
         }
     }
 
+    /// Replace the key of the entry at the index, returning the old key.
+    ///
+    /// The index must be in bounds, and the new key must be equal to the old one.
+    #[inline]
+    pub(crate) unsafe fn replace_key_unchecked(&mut self, index: usize, key: Hashed<K>) -> K {
+        debug_assert!(key.hash() == self.entries.get_unchecked(index).0.hash());
+        self.entries.replace_key_unchecked(index, key.into_key())
+    }
+
     /// Insert an entry into the map without checking for a duplicate key.
     #[inline]
     pub fn insert_hashed_unique_unchecked(&mut self, key: Hashed<K>, val: V) -> (&K, &mut V) {
@@ -437,7 +692,11 @@ impl<K, V> SmallMap<K, V> {
         let entry_index = self.entries.len();
         self.entries.insert_hashed_unique_unchecked(key, val);
         if let Some(index) = &mut self.index {
-            index.insert(hash.promote(), entry_index, Self::hasher(&self.entries));
+            index.insert(
+                hash.promote(),
+                entry_index,
+                Self::index_hasher(&self.entries),
+            );
         } else if self.entries.len() == NO_INDEX_THRESHOLD + 1 {
             self.create_index(self.entries.len());
         } else {
@@ -473,8 +732,9 @@ impl<K, V> SmallMap<K, V> {
     pub fn insert(&mut self, key: K, val: V) -> Option<V>
     where
         K: Hash + Eq,
+        S: BuildHasher,
     {
-        self.insert_hashed(Hashed::new(key), val)
+        self.insert_hashed(self.hash_key(key), val)
     }
 
     /// Remove the entry for the key.
@@ -521,11 +781,13 @@ impl<K, V> SmallMap<K, V> {
     /// Remove the entry for the key.
     ///
     /// Time complexity of this operation is *O(N)* where *N* is the number of entries in the map.
+    /// Use [`swap_remove`](SmallMap::swap_remove) if the order of entries does not matter.
     pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
     where
         Q: ?Sized + Hash + Equivalent<K>,
+        S: BuildHasher,
     {
-        self.remove_hashed(Hashed::new(key))
+        self.remove_hashed(self.hash_key(key))
     }
 
     /// Remove the entry for the key.
@@ -534,13 +796,113 @@ impl<K, V> SmallMap<K, V> {
     pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
     where
         Q: ?Sized + Hash + Equivalent<K>,
+        S: BuildHasher,
+    {
+        self.remove_hashed_entry(self.hash_key(key))
+    }
+
+    /// Remove the entry for the key, shifting the following entries to preserve the order.
+    ///
+    /// This is the same as [`remove`](SmallMap::remove), the name makes the choice explicit.
+    /// Time complexity of this operation is *O(N)* where *N* is the number of entries in the map.
+    #[inline]
+    pub fn shift_remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+        S: BuildHasher,
+    {
+        self.remove(key)
+    }
+
+    /// Remove the entry for the key, shifting the following entries to preserve the order.
+    ///
+    /// This is the same as [`remove_hashed`](SmallMap::remove_hashed).
+    #[inline]
+    pub fn shift_remove_hashed<Q>(&mut self, key: Hashed<&Q>) -> Option<V>
+    where
+        Q: ?Sized + Equivalent<K>,
+    {
+        self.remove_hashed(key)
+    }
+
+    /// Remove the entry at the index, replacing it with the last entry.
+    ///
+    /// This does not preserve the order, but is *O(1)*.
+    /// Returns `None` if the index is out of bounds.
+    pub fn swap_remove_index(&mut self, i: usize) -> Option<(K, V)> {
+        if i >= self.entries.len() {
+            return None;
+        }
+        if let Some(index) = &mut self.index {
+            let last = self.entries.len() - 1;
+            unsafe {
+                let hash = self.entries.get_unchecked(i).0.hash();
+                let removed = index.remove_entry(hash.promote(), |&j| j == i);
+                debug_assert!(removed == Some(i));
+                if i != last {
+                    let last_hash = self.entries.get_unchecked(last).0.hash();
+                    let moved = index.get_mut(last_hash.promote(), |&j| j == last);
+                    debug_assert!(moved.is_some());
+                    if let Some(moved) = moved {
+                        *moved = i;
+                    }
+                }
+            }
+        }
+        let (key, value) = self.entries.swap_remove(i);
+        Some((key.into_key(), value))
+    }
+
+    /// Remove the entry for the key, replacing it with the last entry.
+    ///
+    /// This does not preserve the order, but only needs to find the key:
+    /// other entries are not shifted and the index is updated in *O(1)*.
+    pub fn swap_remove_hashed_entry<Q>(&mut self, key: Hashed<&Q>) -> Option<(K, V)>
+    where
+        Q: ?Sized + Equivalent<K>,
+    {
+        let i = self.get_index_of_hashed(key)?;
+        self.swap_remove_index(i)
+    }
+
+    /// Remove the entry for the key, replacing it with the last entry.
+    ///
+    /// See [`swap_remove_hashed_entry`](SmallMap::swap_remove_hashed_entry).
+    #[inline]
+    pub fn swap_remove_hashed<Q>(&mut self, key: Hashed<&Q>) -> Option<V>
+    where
+        Q: ?Sized + Equivalent<K>,
+    {
+        self.swap_remove_hashed_entry(key).map(|(_k, v)| v)
+    }
+
+    /// Remove the entry for the key, replacing it with the last entry.
+    ///
+    /// See [`swap_remove_hashed_entry`](SmallMap::swap_remove_hashed_entry).
+    #[inline]
+    pub fn swap_remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+        S: BuildHasher,
+    {
+        self.swap_remove_hashed(self.hash_key(key))
+    }
+
+    /// Remove the entry for the key, replacing it with the last entry.
+    ///
+    /// See [`swap_remove_hashed_entry`](SmallMap::swap_remove_hashed_entry).
+    #[inline]
+    pub fn swap_remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+        S: BuildHasher,
     {
-        self.remove_hashed_entry(Hashed::new(key))
+        self.swap_remove_hashed_entry(self.hash_key(key))
     }
 
     /// Get the entry (occupied or not) for the key.
     #[inline]
-    pub fn entry_hashed(&mut self, key: Hashed<K>) -> Entry<'_, K, V>
+    pub fn entry_hashed(&mut self, key: Hashed<K>) -> Entry<'_, K, V, S>
     where
         K: Eq,
     {
@@ -556,7 +918,31 @@ impl<K, V> SmallMap<K, V> {
         }
     }
 
+    /// Get the entry (occupied or not) by a precomputed hash and a key equality function.
+    ///
+    /// Unlike [`entry_hashed`](SmallMap::entry_hashed), this does not need an owned key,
+    /// the key is only provided when inserting into a vacant entry.
+    #[inline]
+    pub fn raw_entry_from_hash(
+        &mut self,
+        hash: StarlarkHashValue,
+        eq: impl FnMut(&K) -> bool,
+    ) -> RawEntryMut<'_, K, V, S> {
+        match self.get_index_of_hashed_raw(hash, eq) {
+            Some(i) => {
+                let (key, value) = unsafe { self.entries.get_unchecked_mut(i) };
+                RawEntryMut::Occupied(OccupiedEntry {
+                    key: key.key(),
+                    value,
+                })
+            }
+            None => RawEntryMut::Vacant(RawVacantEntryMut { hash, map: self }),
+        }
+    }
+
     /// Remove the last element.
+    ///
+    /// This is cheap: the entries are not shifted, and the key is not hashed again.
     pub fn pop(&mut self) -> Option<(K, V)> {
         match self.entries.pop() {
             None => None,
@@ -573,11 +959,12 @@ impl<K, V> SmallMap<K, V> {
 
     /// Get the entry (occupied or not) for the key.
     #[inline]
-    pub fn entry(&mut self, key: K) -> Entry<'_, K, V>
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S>
     where
         K: Eq + Hash,
+        S: BuildHasher,
     {
-        self.entry_hashed(Hashed::new(key))
+        self.entry_hashed(self.hash_key(key))
     }
 
     /// Is the map empty?
@@ -607,6 +994,43 @@ impl<K, V> SmallMap<K, V> {
         }
     }
 
+    /// Remove all entries from the map, returning them in an iterator in insertion order.
+    ///
+    /// Retain the capacity, so the map can be refilled without reallocating.
+    /// If the iterator is dropped before it is exhausted, the remaining entries are dropped.
+    #[inline]
+    pub fn drain(&mut self) -> Drain<'_, K, V> {
+        if let Some(index) = &mut self.index {
+            index.clear();
+        }
+        Drain {
+            iter: self.entries.drain(),
+        }
+    }
+
+    /// Remove the entries in the range, returning them in an iterator in insertion order.
+    ///
+    /// The entries after the range are shifted once, and the index is rebuilt.
+    /// Unlike [`drain`](SmallMap::drain), the entries are removed before this function returns,
+    /// so dropping the iterator early only drops the removed entries.
+    ///
+    /// Panics if the start of the range is greater than the end,
+    /// or the end is greater than the length.
+    pub fn drain_range(&mut self, range: impl RangeBounds<usize>) -> IntoIter<K, V> {
+        let drained = self.entries.drain_range(range);
+        if !drained.is_empty() {
+            if self.entries.len() <= NO_INDEX_THRESHOLD {
+                self.index = None;
+            } else if let Some(mut index) = self.index.take() {
+                index.clear();
+                self.init_index(*index);
+            }
+        }
+        IntoIter {
+            iter: drained.into_iter(),
+        }
+    }
+
     /// Basic check the map invariants are hold.
     #[cfg(test)]
     fn state_check(&self) {
@@ -633,6 +1057,9 @@ impl<K, V> SmallMap<K, V> {
     }
 
     /// Sort entries by key.
+    ///
+    /// The sort is stable and in place: entries are not copied to a temporary buffer,
+    /// but the index (if any) is rebuilt.
     pub fn sort_keys(&mut self)
     where
         K: Ord,
@@ -644,11 +1071,11 @@ impl<K, V> SmallMap<K, V> {
         }
 
         // Rebuild index on drop to make this code panic-safe.
-        struct RebuildIndexOnDrop<'a, K, V> {
-            map: &'a mut SmallMap<K, V>,
+        struct RebuildIndexOnDrop<'a, K, V, S> {
+            map: &'a mut SmallMap<K, V, S>,
         }
 
-        impl<'a, K, V> Drop for RebuildIndexOnDrop<'a, K, V> {
+        impl<'a, K, V, S> Drop for RebuildIndexOnDrop<'a, K, V, S> {
             fn drop(&mut self) {
                 if let Some(index) = &mut self.map.index {
                     index.clear();
@@ -664,6 +1091,113 @@ impl<K, V> SmallMap<K, V> {
         map.map.entries.sort_keys();
     }
 
+    /// Transform the values, keeping the keys and their order.
+    ///
+    /// Neither keys are rehashed nor the index is rebuilt.
+    pub fn map_values<V2>(self, f: impl FnMut(V) -> V2) -> SmallMap<K, V2, S> {
+        SmallMap {
+            entries: self.entries.map_values(f),
+            index: self.index,
+            hasher: self.hasher,
+        }
+    }
+
+    /// Add the entries of `other` to this map.
+    ///
+    /// When a key is present in both maps, `f` is called with the key,
+    /// the value in this map to update, and the value from `other`.
+    /// The keys of this map keep their positions, followed by the new keys
+    /// in the order of `other`.
+    pub fn merge(&mut self, other: SmallMap<K, V, S>, mut f: impl FnMut(&K, &mut V, V))
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        self.reserve(other.len());
+        for (k, v) in other.entries.into_iter() {
+            // Rehash, the hasher of `other` may be seeded differently.
+            let k = self.hash_key(k);
+            match self.get_mut_hashed(k.as_ref()) {
+                Some(old) => f(k.key(), old, v),
+                None => {
+                    self.insert_hashed_unique_unchecked(k, v);
+                }
+            }
+        }
+    }
+
+    /// Split the map into the entries which satisfy the predicate, and the rest.
+    ///
+    /// Both maps preserve the order of the entries. Keys are not rehashed.
+    pub fn partition(self, mut pred: impl FnMut(&K, &V) -> bool) -> (Self, Self)
+    where
+        S: Clone,
+    {
+        let mut yes = SmallMap::with_hasher(self.hasher.clone());
+        let mut no = SmallMap::with_hasher(self.hasher);
+        for (k, v) in self.entries.into_iter_hashed() {
+            if pred(k.key(), &v) {
+                yes.insert_hashed_unique_unchecked(k, v);
+            } else {
+                no.insert_hashed_unique_unchecked(k, v);
+            }
+        }
+        (yes, no)
+    }
+
+    /// Split the map in two at the given position. Returns the entries `[at, len)`,
+    /// and leaves `[0, at)` in `self`.
+    ///
+    /// Keys are not rehashed. The index of `self` is updated
+    /// by removing the tail entries, and the index of the result is built from scratch.
+    ///
+    /// Panics if `at > len`.
+    pub fn split_off(&mut self, at: usize) -> Self
+    where
+        S: Clone,
+    {
+        let tail = self.entries.split_off(at);
+        if self.entries.len() <= NO_INDEX_THRESHOLD {
+            self.index = None;
+        } else if let Some(index) = &mut self.index {
+            for (i, (k, _)) in tail.iter_hashed().enumerate() {
+                let removed = index.remove_entry(k.hash().promote(), |&j| j == at + i);
+                debug_assert!(removed.is_some());
+            }
+        }
+        let mut res = SmallMap {
+            entries: tail,
+            index: None,
+            hasher: self.hasher.clone(),
+        };
+        if res.entries.len() > NO_INDEX_THRESHOLD {
+            res.create_index(res.entries.len());
+        }
+        res
+    }
+
+    /// Group the keys by their values.
+    ///
+    /// The groups are ordered by the first occurrence of the value,
+    /// and the keys in each group are in the map order.
+    pub fn group_by_value(self) -> SmallMap<V, Vec<K>, S>
+    where
+        V: Hash + Eq,
+        S: BuildHasher,
+    {
+        let mut groups: SmallMap<V, Vec<K>, S> = SmallMap::with_hasher(self.hasher);
+        for (k, v) in self.entries.into_iter() {
+            let v = groups.hash_key(v);
+            match groups.get_mut_hashed(v.as_ref()) {
+                Some(keys) => keys.push(k),
+                None => {
+                    groups.insert_hashed_unique_unchecked(v, vec![k]);
+                }
+            }
+        }
+        groups
+    }
+
     /// Equal if the keys and values are equal in the iteration order.
     pub fn eq_ordered(&self, other: &Self) -> bool
     where
@@ -696,17 +1230,17 @@ pub struct OccupiedEntry<'a, K, V> {
 /// Reference to a vacant entry in the map.
 ///
 /// This can be used to insert an entry into the map.
-pub struct VacantEntry<'a, K, V> {
+pub struct VacantEntry<'a, K, V, S = StarlarkHasherBuilder> {
     key: Hashed<K>,
-    map: &'a mut SmallMap<K, V>,
+    map: &'a mut SmallMap<K, V, S>,
 }
 
 /// Occupied or vacant entry.
-pub enum Entry<'a, K, V> {
+pub enum Entry<'a, K, V, S = StarlarkHasherBuilder> {
     /// Occupied entry.
     Occupied(OccupiedEntry<'a, K, V>),
     /// No entry for given key.
-    Vacant(VacantEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V, S>),
 }
 
 impl<'a, K, V> OccupiedEntry<'a, K, V> {
@@ -740,7 +1274,7 @@ impl<'a, K, V> OccupiedEntry<'a, K, V> {
     }
 }
 
-impl<'a, K, V> VacantEntry<'a, K, V>
+impl<'a, K, V, S> VacantEntry<'a, K, V, S>
 where
     K: Eq,
 {
@@ -762,7 +1296,7 @@ where
     }
 }
 
-impl<'a, K, V> Entry<'a, K, V>
+impl<'a, K, V, S> Entry<'a, K, V, S>
 where
     K: Eq,
 {
@@ -805,6 +1339,53 @@ where
     }
 }
 
+/// Reference to a vacant entry in the map found by a hash,
+/// see [`raw_entry_from_hash`](SmallMap::raw_entry_from_hash).
+pub struct RawVacantEntryMut<'a, K, V, S = StarlarkHasherBuilder> {
+    hash: StarlarkHashValue,
+    map: &'a mut SmallMap<K, V, S>,
+}
+
+/// Occupied or vacant entry found by a hash.
+pub enum RawEntryMut<'a, K, V, S = StarlarkHasherBuilder> {
+    /// Occupied entry.
+    Occupied(OccupiedEntry<'a, K, V>),
+    /// No entry for given hash and key.
+    Vacant(RawVacantEntryMut<'a, K, V, S>),
+}
+
+impl<'a, K, V, S> RawVacantEntryMut<'a, K, V, S> {
+    /// The hash the entry was searched by.
+    #[inline]
+    pub fn hash(&self) -> StarlarkHashValue {
+        self.hash
+    }
+
+    /// Insert the key and the value into the entry.
+    ///
+    /// The key must have the hash the entry was searched by,
+    /// and must be equal to the searched key, otherwise the map will misbehave.
+    #[inline]
+    pub fn insert(self, key: K, value: V) -> (&'a K, &'a mut V) {
+        self.map
+            .insert_hashed_unique_unchecked(Hashed::new_unchecked(self.hash, key), value)
+    }
+}
+
+impl<'a, K, V, S> RawEntryMut<'a, K, V, S> {
+    /// Insert the key and the value if vacant, see [`RawVacantEntryMut::insert`].
+    #[inline]
+    pub fn or_insert_with(self, default: impl FnOnce() -> (K, V)) -> (&'a K, &'a mut V) {
+        match self {
+            RawEntryMut::Occupied(e) => e.into_mut_entry(),
+            RawEntryMut::Vacant(e) => {
+                let (key, value) = default();
+                e.insert(key, value)
+            }
+        }
+    }
+}
+
 impl<K, V> FromIterator<(K, V)> for SmallMap<K, V>
 where
     K: Hash + Eq,
@@ -833,7 +1414,7 @@ where
     }
 }
 
-impl<K, V> IntoIterator for SmallMap<K, V> {
+impl<K, V, S> IntoIterator for SmallMap<K, V, S> {
     type Item = (K, V);
     type IntoIter = IntoIter<K, V>;
 
@@ -843,7 +1424,7 @@ impl<K, V> IntoIterator for SmallMap<K, V> {
     }
 }
 
-impl<'a, K, V> IntoIterator for &'a SmallMap<K, V> {
+impl<'a, K, V, S> IntoIterator for &'a SmallMap<K, V, S> {
     type Item = (&'a K, &'a V);
     type IntoIter = Iter<'a, K, V>;
 
@@ -853,7 +1434,7 @@ impl<'a, K, V> IntoIterator for &'a SmallMap<K, V> {
     }
 }
 
-impl<'a, K, V> IntoIterator for &'a mut SmallMap<K, V> {
+impl<'a, K, V, S> IntoIterator for &'a mut SmallMap<K, V, S> {
     type Item = (&'a K, &'a mut V);
     type IntoIter = IterMut<'a, K, V>;
 
@@ -874,9 +1455,10 @@ impl<K: Eq, V: PartialEq> PartialEq for SmallMap<K, V> {
 
 impl<K: Eq, V: Eq> Eq for SmallMap<K, V> {}
 
-impl<K, V> Extend<(K, V)> for SmallMap<K, V>
+impl<K, V, S> Extend<(K, V)> for SmallMap<K, V, S>
 where
     K: Hash + Eq,
+    S: BuildHasher,
 {
     fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
         for (k, v) in iter {
@@ -1119,6 +1701,116 @@ mod tests {
         assert!(map.is_empty());
     }
 
+    #[test]
+    fn test_split_off() {
+        for (len, at) in [
+            (0, 0),
+            (5, 2),
+            (5, 5),
+            (100, 0),
+            (100, 10),
+            (100, 50),
+            (100, 90),
+        ] {
+            let mut map: SmallMap<usize, usize> = (0..len).map(|i| (i, i * 10)).collect();
+            let tail = map.split_off(at);
+            map.assert_invariants();
+            tail.assert_invariants();
+            assert_eq!(
+                (0..at).collect::<Vec<_>>(),
+                map.keys().copied().collect::<Vec<_>>()
+            );
+            assert_eq!(
+                (at..len).collect::<Vec<_>>(),
+                tail.keys().copied().collect::<Vec<_>>()
+            );
+            if at < len {
+                assert_eq!(Some(&(at * 10)), tail.get(&at));
+                assert_eq!(None, map.get(&at));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_split_off_out_of_bounds() {
+        let mut map = SmallMap::new();
+        map.insert(1, 10);
+        map.split_off(2);
+    }
+
+    #[test]
+    fn test_raw_entry_from_hash() {
+        let mut a = SmallMap::new();
+        let mut b = SmallMap::new();
+        a.insert("x".to_owned(), 1);
+        let key = Hashed::new("x");
+        let eq = |k: &String| k == key.key();
+        assert_eq!(
+            Some((0, &"x".to_owned(), &1)),
+            a.get_full_from_hash(key.hash(), eq)
+        );
+        assert_eq!(None, b.get_full_from_hash(key.hash(), eq));
+
+        match a.raw_entry_from_hash(key.hash(), eq) {
+            RawEntryMut::Occupied(mut e) => *e.get_mut() += 1,
+            RawEntryMut::Vacant(_) => panic!(),
+        }
+        assert_eq!(Some(&2), a.get("x"));
+        let (k, v) = b
+            .raw_entry_from_hash(key.hash(), eq)
+            .or_insert_with(|| (key.key().to_string(), 10));
+        assert_eq!(("x", 10), (k.as_str(), *v));
+        assert_eq!(Some(&10), b.get("x"));
+
+        // Enough entries to build the index.
+        for i in 0..100 {
+            let key = Hashed::new(i.to_string());
+            match b.raw_entry_from_hash(key.hash(), |k| k == key.key()) {
+                RawEntryMut::Occupied(_) => panic!(),
+                RawEntryMut::Vacant(e) => {
+                    assert_eq!(key.hash(), e.hash());
+                    e.insert(key.into_key(), i);
+                }
+            }
+        }
+        b.assert_invariants();
+        assert_eq!(Some(&50), b.get("50"));
+    }
+
+    #[test]
+    fn test_swap_remove() {
+        for len in [5, 100] {
+            let mut map: SmallMap<u32, u32> = (0..len).map(|i| (i, i * 10)).collect();
+            assert_eq!(Some(20), map.swap_remove(&2));
+            assert_eq!(None, map.swap_remove(&2));
+            map.assert_invariants();
+            assert_eq!(Some((&(len - 1), &((len - 1) * 10))), map.get_index(2));
+            assert_eq!(Some(((len - 1), (len - 1) * 10)), map.swap_remove_index(2));
+            map.assert_invariants();
+            assert_eq!(
+                Some(((len - 2), (len - 2) * 10)),
+                map.swap_remove_entry(&(len - 2))
+            );
+            map.assert_invariants();
+            assert_eq!(None, map.swap_remove_index(len as usize));
+            assert_eq!(Some(0), map.swap_remove(&0));
+            map.assert_invariants();
+            assert_eq!(len as usize - 4, map.len());
+            for i in 0..len {
+                assert_eq!(![0, 2, len - 2, len - 1].contains(&i), map.contains_key(&i));
+            }
+        }
+    }
+
+    #[test]
+    fn test_shift_remove() {
+        let mut map: SmallMap<u32, u32> = (0..100).map(|i| (i, i * 10)).collect();
+        assert_eq!(Some(20), map.shift_remove(&2));
+        map.assert_invariants();
+        assert_eq!(Some((&3, &30)), map.get_index(2));
+    }
+
     #[test]
     fn test_first() {
         let mut map = SmallMap::new();
@@ -1130,6 +1822,34 @@ mod tests {
         assert_eq!(map.first(), Some((&2, &20)));
     }
 
+    #[test]
+    fn test_get_index_mut() {
+        let mut map = SmallMap::new();
+        map.insert("a", 1);
+        map.insert("b", 2);
+        let (k, v) = map.get_index_mut(1).unwrap();
+        assert_eq!("b", *k);
+        *v = 20;
+        assert_eq!(Some(&20), map.get("b"));
+        assert!(map.get_index_mut(2).is_none());
+    }
+
+    #[test]
+    fn test_get_many_mut() {
+        let mut map = SmallMap::new();
+        map.insert("a", vec![1]);
+        map.insert("b", vec![2]);
+        map.insert("c", vec![3]);
+        let [a, c] = map.get_many_mut(["a", "c"]).unwrap();
+        a.append(c);
+        assert_eq!(Some(&vec![1, 3]), map.get("a"));
+        assert_eq!(Some(&vec![]), map.get("c"));
+
+        assert!(map.get_many_mut(["a", "d"]).is_none());
+        assert!(map.get_many_mut(["b", "b"]).is_none());
+        assert!(map.get_many_mut::<str, 0>([]).is_some());
+    }
+
     #[test]
     fn test_last() {
         let mut map = SmallMap::new();
@@ -1231,4 +1951,224 @@ mod tests {
         assert_eq!(Some(&980), m.get(&98));
         m.assert_invariants();
     }
+
+    #[test]
+    fn test_custom_hasher() {
+        let hasher = std::collections::hash_map::RandomState::new();
+        let mut m = SmallMap::with_hasher(hasher);
+        // Large enough so the index is used.
+        for i in 0..100 {
+            assert_eq!(None, m.insert(i.to_string(), i));
+        }
+        m.assert_invariants();
+        assert_eq!(Some(&30), m.get("30"));
+        assert_eq!(Some(&30), m.get_hashed(m.hash_key("30")));
+        assert_eq!(None, m.get("100"));
+        assert_eq!(Some(1), m.remove("1"));
+        *m.entry("2".to_owned()).or_default() += 1;
+        assert_eq!(Some(&3), m.get("2"));
+        assert_eq!(99, m.len());
+        m.assert_invariants();
+    }
+
+    #[test]
+    fn test_rev() {
+        let m: SmallMap<u32, u32> = (0..100).map(|i| (i, i * 10)).collect();
+        assert!(m.iter().rev().map(|(k, _)| *k).eq((0..100).rev()));
+        assert!(m.keys().rev().copied().eq((0..100).rev()));
+        assert!(m.values().rev().copied().eq((0..100).rev().map(|i| i * 10)));
+        assert_eq!(100, m.iter().rev().len());
+        let mut iter = m.clone().into_iter();
+        assert_eq!(Some((99, 990)), iter.next_back());
+        assert_eq!(Some((0, 0)), iter.next());
+        assert_eq!(98, iter.len());
+        assert!(iter.rev().map(|(k, _)| k).eq((1..99).rev()));
+    }
+
+    #[test]
+    fn test_map_values() {
+        let m: SmallMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        let m = m.map_values(|v| v.to_string());
+        m.assert_invariants();
+        assert_eq!(100, m.len());
+        assert_eq!(Some(&"42".to_owned()), m.get(&42));
+        assert!(m.keys().copied().eq(0..100));
+    }
+
+    #[test]
+    fn test_partition() {
+        let m: SmallMap<u32, u32> = (0..100).map(|i| (i, i * 10)).collect();
+        let (even, odd) = m.partition(|k, _| k % 2 == 0);
+        even.assert_invariants();
+        odd.assert_invariants();
+        assert!(even.keys().copied().eq((0..100).step_by(2)));
+        assert!(odd.keys().copied().eq((1..100).step_by(2)));
+        assert_eq!(Some(&420), even.get(&42));
+        assert_eq!(None, odd.get(&42));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut m: SmallMap<u32, String> = (0..10).map(|i| (i, i.to_string())).collect();
+        let other: SmallMap<u32, String> = (5..20).rev().map(|i| (i, format!("+{}", i))).collect();
+        let mut conflicts = Vec::new();
+        m.merge(other, |k, old, new| {
+            conflicts.push(*k);
+            old.push_str(&new);
+        });
+        m.assert_invariants();
+        assert_eq!(vec![9, 8, 7, 6, 5], conflicts);
+        assert!(m.keys().copied().eq((0..10).chain((10..20).rev())));
+        assert_eq!(Some("7+7"), m.get(&7).map(|s| s.as_str()));
+        assert_eq!(Some("4"), m.get(&4).map(|s| s.as_str()));
+        assert_eq!(Some("+15"), m.get(&15).map(|s| s.as_str()));
+    }
+
+    #[test]
+    fn test_group_by_value() {
+        let m = smallmap! {
+            "a" => 1,
+            "b" => 2,
+            "c" => 1,
+            "d" => 3,
+            "e" => 2,
+        };
+        let groups = m.group_by_value();
+        groups.assert_invariants();
+        assert_eq!(
+            vec![(1, vec!["a", "c"]), (2, vec!["b", "e"]), (3, vec!["d"])],
+            groups.into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_try_reserve_shrink_to_fit() {
+        let mut m = SmallMap::new();
+        m.try_reserve(100).unwrap();
+        assert!(m.capacity() >= 100);
+        assert!(m.index.is_some());
+        for i in 0..50 {
+            m.insert(i, i);
+        }
+        m.shrink_to_fit();
+        assert_eq!(50, m.capacity());
+        m.assert_invariants();
+        for i in 10..50 {
+            m.remove(&i);
+        }
+        m.shrink_to_fit();
+        assert_eq!(10, m.capacity());
+        assert!(m.index.is_none());
+        m.assert_invariants();
+        assert_eq!(Some(&5), m.get(&5));
+        assert!(m.try_reserve(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_reserve_exact() {
+        let mut m = SmallMap::new();
+        for i in 0..20 {
+            m.insert(i, i);
+        }
+        m.shrink_to_fit();
+        m.reserve_exact(7);
+        assert_eq!(27, m.capacity());
+        m.try_reserve_exact(10).unwrap();
+        assert_eq!(30, m.capacity());
+        m.assert_invariants();
+        assert!(m.try_reserve_exact(usize::MAX).is_err());
+        assert!(m.try_reserve_exact(isize::MAX as usize / 4).is_err());
+        assert_eq!(20, m.len());
+        assert_eq!(Some(&7), m.get(&7));
+    }
+
+    #[test]
+    fn test_shrink_to() {
+        let mut m = SmallMap::with_capacity(100);
+        for i in 0..50 {
+            m.insert(i, i);
+        }
+        m.shrink_to(60);
+        assert_eq!(60, m.capacity());
+        m.assert_invariants();
+        m.shrink_to(10);
+        assert_eq!(50, m.capacity());
+        for i in 5..50 {
+            m.remove(&i);
+        }
+        m.shrink_to(8);
+        assert_eq!(8, m.capacity());
+        assert!(m.index.is_none());
+        m.assert_invariants();
+        assert_eq!(Some(&3), m.get(&3));
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut m = SmallMap::new();
+        for i in 0..100 {
+            m.insert(i, i.to_string());
+        }
+        let capacity = m.capacity();
+        let mut drain = m.drain();
+        assert_eq!(Some((0, "0".to_owned())), drain.next());
+        assert_eq!(Some((99, "99".to_owned())), drain.next_back());
+        assert_eq!(98, drain.len());
+        drop(drain);
+        assert!(m.is_empty());
+        assert_eq!(capacity, m.capacity());
+        m.assert_invariants();
+        assert_eq!(None, m.get(&5));
+
+        for i in 0..50 {
+            m.insert(i, i.to_string());
+        }
+        m.assert_invariants();
+        assert_eq!(Some(&"5".to_owned()), m.get(&5));
+        assert_eq!(
+            (0..50).map(|i| (i, i.to_string())).collect::<Vec<_>>(),
+            m.drain().collect::<Vec<_>>()
+        );
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn test_drain_range() {
+        let mut m: SmallMap<u32, String> = (0..100).map(|i| (i, i.to_string())).collect();
+        let mut drained = m.drain_range(10..20);
+        assert_eq!(Some((10, "10".to_owned())), drained.next());
+        drop(drained);
+        m.assert_invariants();
+        assert_eq!(90, m.len());
+        assert!(m.keys().copied().eq((0..10).chain(20..100)));
+        assert_eq!(None, m.get(&15));
+        assert_eq!(Some(&"50".to_owned()), m.get(&50));
+        assert_eq!(Some(40), m.get_index_of(&50));
+
+        assert_eq!(
+            (0..5).map(|i| (i, i.to_string())).collect::<Vec<_>>(),
+            m.drain_range(..5).collect::<Vec<_>>()
+        );
+        assert_eq!(75, m.drain_range(10..).count());
+        m.assert_invariants();
+        assert!(m.index.is_none());
+        assert!(m.keys().copied().eq((5..10).chain(20..25)));
+        assert_eq!(Some(&"22".to_owned()), m.get(&22));
+    }
+
+    #[test]
+    fn test_try_with_capacity() {
+        let m = SmallMap::<u64, u64>::try_with_capacity(100).unwrap();
+        assert!(m.capacity() >= 100);
+        assert!(m.index.is_some());
+        m.assert_invariants();
+        assert!(SmallMap::<u64, u64>::try_with_capacity(usize::MAX).is_err());
+        assert!(SmallMap::<u64, u64>::try_with_capacity(usize::MAX / 8).is_err());
+    }
+
+    #[test]
+    fn test_default_hasher_hash_key() {
+        let m = SmallMap::<String, u32>::new();
+        assert_eq!(Hashed::new("x"), m.hash_key("x"));
+    }
 }
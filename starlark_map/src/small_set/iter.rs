@@ -15,6 +15,8 @@
  * limitations under the License.
  */
 
+use std::iter::FusedIterator;
+
 use crate::iter::def_double_ended_iter;
 use crate::iter::def_iter;
 use crate::small_map;
@@ -45,6 +47,12 @@ pub struct IntoIterHashed<T> {
     pub(crate) iter: small_map::IntoIterHashed<T, ()>,
 }
 
+/// Iterator that moves entries out of a [`SmallSet`](crate::small_set::SmallSet),
+/// leaving the set empty.
+pub struct Drain<'a, T> {
+    pub(crate) iter: small_map::Drain<'a, T, ()>,
+}
+
 impl<'a, T> Clone for Iter<'a, T> {
     fn clone(&self) -> Iter<'a, T> {
         Iter {
@@ -88,6 +96,32 @@ impl<T> IntoIterHashed<T> {
     }
 }
 
+impl<'a, T> Drain<'a, T> {
+    #[inline]
+    fn map((k, ()): (T, ())) -> T {
+        k
+    }
+}
+
+impl<'a, T> Iterator for Drain<'a, T> {
+    type Item = T;
+
+    def_iter!();
+}
+
+impl<'a, T> DoubleEndedIterator for Drain<'a, T> {
+    def_double_ended_iter!();
+}
+
+impl<'a, T> ExactSizeIterator for Drain<'a, T> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, T> FusedIterator for Drain<'a, T> {}
+
 impl<'a, T> Iterator for Iter<'a, T> {
     type Item = &'a T;
 
@@ -105,6 +139,8 @@ impl<'a, T> ExactSizeIterator for Iter<'a, T> {
     }
 }
 
+impl<'a, T> FusedIterator for Iter<'a, T> {}
+
 impl<'a, T> Iterator for IterMutUnchecked<'a, T> {
     type Item = &'a mut T;
 
@@ -122,6 +158,8 @@ impl<'a, T> ExactSizeIterator for IterMutUnchecked<'a, T> {
     }
 }
 
+impl<'a, T> FusedIterator for IterMutUnchecked<'a, T> {}
+
 impl<'a, T> Iterator for IterHashed<'a, T> {
     type Item = Hashed<&'a T>;
 
@@ -139,6 +177,8 @@ impl<'a, T> ExactSizeIterator for IterHashed<'a, T> {
     }
 }
 
+impl<'a, T> FusedIterator for IterHashed<'a, T> {}
+
 impl<T> Iterator for IntoIter<T> {
     type Item = T;
 
@@ -156,6 +196,8 @@ impl<T> ExactSizeIterator for IntoIter<T> {
     }
 }
 
+impl<T> FusedIterator for IntoIter<T> {}
+
 impl<T> Iterator for IntoIterHashed<T> {
     type Item = Hashed<T>;
 
@@ -172,3 +214,5 @@ impl<T> ExactSizeIterator for IntoIterHashed<T> {
         self.iter.len()
     }
 }
+
+impl<T> FusedIterator for IntoIterHashed<T> {}
@@ -21,26 +21,56 @@ mod iter;
 
 use std::fmt;
 use std::fmt::Debug;
+use std::hash::BuildHasher;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::iter::FusedIterator;
+use std::mem;
+use std::ops::BitAnd;
+use std::ops::BitOr;
+use std::ops::BitXor;
+use std::ops::Sub;
 
 use allocative::Allocative;
-use gazebo::prelude::*;
+use allocative::Visitor;
 
 use crate::equivalent::Equivalent;
 use crate::hashed::Hashed;
 use crate::small_map::SmallMap;
+pub use crate::small_set::iter::Drain;
 pub use crate::small_set::iter::IntoIter;
 pub use crate::small_set::iter::IntoIterHashed;
 pub use crate::small_set::iter::Iter;
 pub use crate::small_set::iter::IterHashed;
 pub use crate::small_set::iter::IterMutUnchecked;
+use crate::StarlarkHasherBuilder;
+use crate::TryReserveError;
 
 /// An memory-efficient set with deterministic order, based on [`SmallMap`].
-#[derive(Clone, Default_, Allocative)]
-pub struct SmallSet<T>(SmallMap<T, ()>);
+///
+/// Like [`SmallMap`], uses [`StarlarkHasherBuilder`] unless a different hasher is given
+/// with [`with_hasher`](SmallSet::with_hasher).
+#[derive(Clone)]
+pub struct SmallSet<T, S = StarlarkHasherBuilder>(SmallMap<T, (), S>);
+
+const _: () = assert!(mem::size_of::<SmallSet<u64>>() <= 4 * mem::size_of::<usize>());
+
+impl<T, S: Default> Default for SmallSet<T, S> {
+    #[inline]
+    fn default() -> Self {
+        SmallSet(SmallMap::default())
+    }
+}
 
-impl<T: Debug> Debug for SmallSet<T> {
+impl<T: Allocative, S> Allocative for SmallSet<T, S> {
+    fn visit<'a, 'b: 'a>(&self, visitor: &'a mut Visitor<'b>) {
+        let mut visitor = visitor.enter_self_sized::<Self>();
+        visitor.visit_field(allocative::Key::new("0"), &self.0);
+        visitor.exit();
+    }
+}
+
+impl<T: Debug, S> Debug for SmallSet<T, S> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_set().entries(self.iter()).finish()
     }
@@ -84,6 +114,98 @@ impl<T> SmallSet<T> {
         Self(SmallMap::with_capacity(n))
     }
 
+    /// Empty small set with preallocated capacity,
+    /// return an error if memory cannot be allocated.
+    #[inline]
+    pub fn try_with_capacity(n: usize) -> Result<Self, TryReserveError> {
+        Ok(Self(SmallMap::try_with_capacity(n)?))
+    }
+}
+
+impl<T, S> SmallSet<T, S> {
+    /// Empty set which uses the given hasher to hash elements.
+    #[inline]
+    pub const fn with_hasher(hasher: S) -> Self {
+        SmallSet(SmallMap::with_hasher(hasher))
+    }
+
+    /// Empty set with preallocated capacity which uses the given hasher.
+    #[inline]
+    pub fn with_capacity_and_hasher(n: usize, hasher: S) -> Self {
+        SmallSet(SmallMap::with_capacity_and_hasher(n, hasher))
+    }
+
+    /// Empty set with preallocated capacity which uses the given hasher,
+    /// return an error if memory cannot be allocated.
+    #[inline]
+    pub fn try_with_capacity_and_hasher(n: usize, hasher: S) -> Result<Self, TryReserveError> {
+        Ok(SmallSet(SmallMap::try_with_capacity_and_hasher(n, hasher)?))
+    }
+
+    /// Hasher used to hash elements of this set.
+    #[inline]
+    pub fn hasher(&self) -> &S {
+        self.0.hasher()
+    }
+
+    /// Hash the value with the hasher of this set.
+    ///
+    /// The result can be passed to the functions which take prehashed values.
+    #[inline]
+    pub fn hash_key<Q: Hash>(&self, value: Q) -> Hashed<Q>
+    where
+        S: BuildHasher,
+    {
+        self.0.hash_key(value)
+    }
+
+    /// Reserve capacity for at least `additional` more elements to be inserted.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize)
+    where
+        T: Eq,
+    {
+        self.0.reserve(additional);
+    }
+
+    /// Like [`reserve`](SmallSet::reserve), but return an error
+    /// instead of panicking when memory cannot be allocated.
+    #[inline]
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.0.try_reserve(additional)
+    }
+
+    /// Reserve capacity for exactly `additional` more elements,
+    /// without the amortized growth of [`reserve`](SmallSet::reserve).
+    #[inline]
+    pub fn reserve_exact(&mut self, additional: usize)
+    where
+        T: Eq,
+    {
+        self.0.reserve_exact(additional);
+    }
+
+    /// Like [`reserve_exact`](SmallSet::reserve_exact), but return an error
+    /// instead of panicking when memory cannot be allocated.
+    #[inline]
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.0.try_reserve_exact(additional)
+    }
+
+    /// Shrink the capacity of the set as much as possible.
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.0.shrink_to_fit();
+    }
+
+    /// Shrink the capacity of the set with a lower limit.
+    ///
+    /// The capacity remains at least as large as both the length and `min_capacity`.
+    #[inline]
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.0.shrink_to(min_capacity);
+    }
+
     /// Current capacity of the set.
     #[inline]
     pub fn capacity(&self) -> usize {
@@ -130,6 +252,7 @@ impl<T> SmallSet<T> {
     pub fn insert(&mut self, key: T) -> bool
     where
         T: Hash + Eq,
+        S: BuildHasher,
     {
         self.0.insert(key, ()).is_none()
     }
@@ -160,6 +283,7 @@ impl<T> SmallSet<T> {
     where
         Q: Hash + Equivalent<T> + ?Sized,
         T: Eq,
+        S: BuildHasher,
     {
         self.0.get_full(value).map(|(_, t, _)| t)
     }
@@ -175,6 +299,8 @@ impl<T> SmallSet<T> {
     }
 
     /// Find an entry by an index.
+    ///
+    /// There is no mutable version, because changing an element could change its hash.
     #[inline]
     pub fn get_index(&self, index: usize) -> Option<&T> {
         self.0.get_index(index).map(|(k, _)| k)
@@ -186,6 +312,7 @@ impl<T> SmallSet<T> {
     where
         Q: Hash + Equivalent<T> + ?Sized,
         T: Eq,
+        S: BuildHasher,
     {
         self.0.get_index_of(value)
     }
@@ -222,10 +349,38 @@ impl<T> SmallSet<T> {
     where
         Q: ?Sized + Hash + Equivalent<T>,
         T: Eq,
+        S: BuildHasher,
     {
         self.0.remove(key).is_some()
     }
 
+    /// Remove the element from the set if it is present,
+    /// replacing it with the last element.
+    ///
+    /// This does not preserve the order, but other elements are not shifted.
+    #[inline]
+    pub fn swap_remove<Q>(&mut self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+        S: BuildHasher,
+    {
+        self.0.swap_remove(key).is_some()
+    }
+
+    /// Remove the element from the set if it is present,
+    /// shifting the following elements to preserve the order.
+    ///
+    /// This is the same as [`remove`](SmallSet::remove).
+    #[inline]
+    pub fn shift_remove<Q>(&mut self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<T>,
+        T: Eq,
+        S: BuildHasher,
+    {
+        self.remove(key)
+    }
+
     /// Insert entry if it doesn't exist.
     ///
     /// Return the resulting entry in the map.
@@ -233,8 +388,9 @@ impl<T> SmallSet<T> {
     pub fn get_or_insert(&mut self, value: T) -> &T
     where
         T: Hash + Eq,
+        S: BuildHasher,
     {
-        let value = Hashed::new(value);
+        let value = self.0.hash_key(value);
         match self
             .0
             .get_index_of_hashed_raw(value.hash(), |v| value.key().equivalent(v))
@@ -252,8 +408,9 @@ impl<T> SmallSet<T> {
     where
         Q: Hash + Equivalent<T> + ToOwned<Owned = T> + ?Sized,
         T: Eq,
+        S: BuildHasher,
     {
-        let value = Hashed::new(value);
+        let value = self.0.hash_key(value);
         match self.0.get_index_of_hashed(value) {
             Some(index) => self.0.get_index(index).unwrap().0,
             None => self.0.insert_hashed_unique_unchecked(value.owned(), ()).0,
@@ -268,19 +425,54 @@ impl<T> SmallSet<T> {
     where
         Q: ?Sized + Hash + Equivalent<T>,
         T: Eq,
+        S: BuildHasher,
     {
         self.0.remove_entry(key).map(|(k, _)| k)
     }
 
-    /// Remove the last element from the set.
+    /// Insert the element, replacing an equal element if it is present,
+    /// and return the replaced element.
+    ///
+    /// The replaced element keeps its position in the iteration order.
     #[inline]
-    pub fn pop(&mut self) -> Option<T>
+    pub fn replace(&mut self, value: T) -> Option<T>
     where
-        T: Eq,
+        T: Hash + Eq,
+        S: BuildHasher,
     {
+        let value = self.0.hash_key(value);
+        match self
+            .0
+            .get_index_of_hashed_raw(value.hash(), |v| value.key().equivalent(v))
+        {
+            // SAFETY: the index was just found, and the element is equal to the key there.
+            Some(index) => Some(unsafe { self.0.replace_key_unchecked(index, value) }),
+            None => {
+                self.0.insert_hashed_unique_unchecked(value, ());
+                None
+            }
+        }
+    }
+
+    /// Remove the last element from the set.
+    ///
+    /// This is cheap: the elements are not shifted, and the element is not hashed again.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
         self.0.pop().map(|(k, ())| k)
     }
 
+    /// Split the set in two at the given position. Returns the elements `[at, len)`,
+    /// and leaves `[0, at)` in `self`. Elements are not rehashed.
+    ///
+    /// Panics if `at > len`.
+    pub fn split_off(&mut self, at: usize) -> Self
+    where
+        S: Clone,
+    {
+        SmallSet(self.0.split_off(at))
+    }
+
     /// Is the set empty?
     #[inline]
     pub fn is_empty(&self) -> bool {
@@ -299,6 +491,7 @@ impl<T> SmallSet<T> {
     where
         Q: Hash + Equivalent<T> + ?Sized,
         T: Eq,
+        S: BuildHasher,
     {
         self.0.contains_key(key)
     }
@@ -311,6 +504,16 @@ impl<T> SmallSet<T> {
         self.0.clear()
     }
 
+    /// Remove all elements from the set, returning them in an iterator in insertion order.
+    ///
+    /// Retain the capacity, so the set can be refilled without reallocating.
+    #[inline]
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        Drain {
+            iter: self.0.drain(),
+        }
+    }
+
     /// Returns a reference to the first item.
     #[inline]
     pub fn first(&self) -> Option<&T> {
@@ -324,9 +527,10 @@ impl<T> SmallSet<T> {
     }
 
     /// Iterator over elements of this set which are not in the other set.
-    pub fn difference<'a>(&'a self, other: &'a Self) -> Difference<'a, T>
+    pub fn difference<'a>(&'a self, other: &'a Self) -> Difference<'a, T, S>
     where
         T: Eq + Hash,
+        S: BuildHasher,
     {
         Difference {
             iter: self.iter(),
@@ -334,19 +538,79 @@ impl<T> SmallSet<T> {
         }
     }
 
+    /// Iterator over elements of this set which are also in the other set,
+    /// in the order of this set.
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> Intersection<'a, T, S>
+    where
+        T: Eq + Hash,
+        S: BuildHasher,
+    {
+        Intersection {
+            iter: self.iter(),
+            other,
+        }
+    }
+
     /// Iterator over union of two sets.
     ///
     /// Iteration order is: elements of this set followed by elements in the other set
     /// not present in this set.
-    pub fn union<'a>(&'a self, other: &'a Self) -> Union<'a, T>
+    pub fn union<'a>(&'a self, other: &'a Self) -> Union<'a, T, S>
     where
         T: Eq + Hash,
+        S: BuildHasher,
     {
         Union {
             iter: self.iter().chain(other.difference(self)),
         }
     }
 
+    /// Iterator over elements which are in exactly one of the two sets.
+    ///
+    /// Iteration order is: elements of this set not present in the other set,
+    /// followed by elements of the other set not present in this set.
+    pub fn symmetric_difference<'a>(&'a self, other: &'a Self) -> SymmetricDifference<'a, T, S>
+    where
+        T: Eq + Hash,
+        S: BuildHasher,
+    {
+        SymmetricDifference {
+            iter: self.difference(other).chain(other.difference(self)),
+        }
+    }
+
+    /// Is every element of this set also in the other set.
+    pub fn is_subset(&self, other: &Self) -> bool
+    where
+        T: Eq + Hash,
+        S: BuildHasher,
+    {
+        self.len() <= other.len() && self.iter().all(|x| other.contains(x))
+    }
+
+    /// Is every element of the other set also in this set.
+    pub fn is_superset(&self, other: &Self) -> bool
+    where
+        T: Eq + Hash,
+        S: BuildHasher,
+    {
+        other.is_subset(self)
+    }
+
+    /// Do the sets have no elements in common.
+    pub fn is_disjoint(&self, other: &Self) -> bool
+    where
+        T: Eq + Hash,
+        S: BuildHasher,
+    {
+        let (small, large) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        !small.iter().any(|x| large.contains(x))
+    }
+
     /// Sort entries.
     pub fn sort(&mut self)
     where
@@ -374,7 +638,7 @@ impl<T> SmallSet<T> {
     }
 }
 
-impl<'a, T> IntoIterator for &'a SmallSet<T> {
+impl<'a, T, S> IntoIterator for &'a SmallSet<T, S> {
     type Item = &'a T;
     type IntoIter = Iter<'a, T>;
 
@@ -386,7 +650,7 @@ impl<'a, T> IntoIterator for &'a SmallSet<T> {
     }
 }
 
-impl<T> IntoIterator for SmallSet<T> {
+impl<T, S> IntoIterator for SmallSet<T, S> {
     type Item = T;
     type IntoIter = IntoIter<T>;
 
@@ -398,9 +662,10 @@ impl<T> IntoIterator for SmallSet<T> {
     }
 }
 
-impl<T> Extend<T> for SmallSet<T>
+impl<T, S> Extend<T> for SmallSet<T, S>
 where
     T: Eq + Hash,
+    S: BuildHasher,
 {
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
         self.0.extend(iter.into_iter().map(|v| (v, ())));
@@ -408,14 +673,15 @@ where
 }
 
 /// Iterator over the difference of two sets.
-pub struct Difference<'a, T: 'a> {
+pub struct Difference<'a, T: 'a, S = StarlarkHasherBuilder> {
     iter: Iter<'a, T>,
-    other: &'a SmallSet<T>,
+    other: &'a SmallSet<T, S>,
 }
 
-impl<'a, T: 'a> Iterator for Difference<'a, T>
+impl<'a, T: 'a, S> Iterator for Difference<'a, T, S>
 where
     T: Hash + Eq,
+    S: BuildHasher,
 {
     type Item = &'a T;
 
@@ -437,14 +703,127 @@ where
     }
 }
 
+impl<'a, T: 'a, S> DoubleEndedIterator for Difference<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+    #[allow(clippy::while_let_on_iterator)]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some(item) = self.iter.next_back() {
+            if !self.other.contains(item) {
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+impl<'a, T: 'a, S> FusedIterator for Difference<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+}
+
+/// Iterator over the intersection of two sets.
+pub struct Intersection<'a, T: 'a, S = StarlarkHasherBuilder> {
+    iter: Iter<'a, T>,
+    other: &'a SmallSet<T, S>,
+}
+
+impl<'a, T: 'a, S> Iterator for Intersection<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+    type Item = &'a T;
+
+    #[allow(clippy::while_let_on_iterator)]
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(item) = self.iter.next() {
+            if self.other.contains(item) {
+                return Some(item);
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.iter.len().min(self.other.len())))
+    }
+}
+
+impl<'a, T: 'a, S> DoubleEndedIterator for Intersection<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+    #[allow(clippy::while_let_on_iterator)]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some(item) = self.iter.next_back() {
+            if self.other.contains(item) {
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+impl<'a, T: 'a, S> FusedIterator for Intersection<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+}
+
 /// Iterator over a union of two sets.
-pub struct Union<'a, T: 'a> {
-    iter: std::iter::Chain<Iter<'a, T>, Difference<'a, T>>,
+pub struct Union<'a, T: 'a, S = StarlarkHasherBuilder> {
+    iter: std::iter::Chain<Iter<'a, T>, Difference<'a, T, S>>,
+}
+
+impl<'a, T: 'a, S> Iterator for Union<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
 }
 
-impl<'a, T: 'a> Iterator for Union<'a, T>
+impl<'a, T: 'a, S> DoubleEndedIterator for Union<'a, T, S>
 where
     T: Hash + Eq,
+    S: BuildHasher,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+}
+
+impl<'a, T: 'a, S> FusedIterator for Union<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+}
+
+/// Iterator over the symmetric difference of two sets.
+pub struct SymmetricDifference<'a, T: 'a, S = StarlarkHasherBuilder> {
+    iter: std::iter::Chain<Difference<'a, T, S>, Difference<'a, T, S>>,
+}
+
+impl<'a, T: 'a, S> Iterator for SymmetricDifference<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
 {
     type Item = &'a T;
 
@@ -457,6 +836,94 @@ where
     }
 }
 
+impl<'a, T: 'a, S> DoubleEndedIterator for SymmetricDifference<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+}
+
+impl<'a, T: 'a, S> FusedIterator for SymmetricDifference<'a, T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+}
+
+impl<T, S> SmallSet<T, S>
+where
+    T: Eq + Hash + Clone,
+    S: BuildHasher + Default,
+{
+    /// Set of the cloned elements, which must be unique.
+    fn from_unique<'a>(iter: impl Iterator<Item = &'a T>) -> Self
+    where
+        T: 'a,
+    {
+        let mut res = SmallSet::with_capacity_and_hasher(iter.size_hint().0, S::default());
+        for x in iter {
+            res.insert_hashed_unique_unchecked(res.hash_key(x.clone()));
+        }
+        res
+    }
+}
+
+impl<T, S> BitOr for &SmallSet<T, S>
+where
+    T: Eq + Hash + Clone,
+    S: BuildHasher + Default,
+{
+    type Output = SmallSet<T, S>;
+
+    /// Union of two sets, in the order of [`union`](SmallSet::union).
+    fn bitor(self, other: Self) -> SmallSet<T, S> {
+        SmallSet::from_unique(self.union(other))
+    }
+}
+
+impl<T, S> BitAnd for &SmallSet<T, S>
+where
+    T: Eq + Hash + Clone,
+    S: BuildHasher + Default,
+{
+    type Output = SmallSet<T, S>;
+
+    /// Intersection of two sets, in the order of the left set.
+    fn bitand(self, other: Self) -> SmallSet<T, S> {
+        SmallSet::from_unique(self.intersection(other))
+    }
+}
+
+impl<T, S> Sub for &SmallSet<T, S>
+where
+    T: Eq + Hash + Clone,
+    S: BuildHasher + Default,
+{
+    type Output = SmallSet<T, S>;
+
+    /// Elements of the left set which are not in the right set, in the order of the left set.
+    fn sub(self, other: Self) -> SmallSet<T, S> {
+        SmallSet::from_unique(self.difference(other))
+    }
+}
+
+impl<T, S> BitXor for &SmallSet<T, S>
+where
+    T: Eq + Hash + Clone,
+    S: BuildHasher + Default,
+{
+    type Output = SmallSet<T, S>;
+
+    /// Elements in exactly one of the sets,
+    /// in the order of [`symmetric_difference`](SmallSet::symmetric_difference).
+    fn bitxor(self, other: Self) -> SmallSet<T, S> {
+        SmallSet::from_unique(self.symmetric_difference(other))
+    }
+}
+
 /// Create a [`SmallSet`](SmallSet) from a list of values.
 ///
 /// ## Example
@@ -494,6 +961,7 @@ mod tests {
     use std::rc::Rc;
 
     use dupe::Dupe;
+    use gazebo::prelude::*;
 
     use super::*;
 
@@ -618,6 +1086,79 @@ mod tests {
         assert_eq!(s.last(), Some(&2));
     }
 
+    #[test]
+    fn test_pop() {
+        let mut s = SmallSet::from_iter([3, 1, 2]);
+        assert_eq!(Some(2), s.pop());
+        assert_eq!(Some(1), s.pop());
+        assert!(s.contains(&3));
+        assert!(!s.contains(&1));
+        assert_eq!(Some(3), s.pop());
+        assert_eq!(None, s.pop());
+    }
+
+    #[test]
+    fn test_take_replace() {
+        /// Equal if the first field is equal.
+        #[derive(Debug)]
+        struct Interned(u32, &'static str);
+
+        impl PartialEq for Interned {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+
+        impl Eq for Interned {}
+
+        impl Hash for Interned {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.hash(state)
+            }
+        }
+
+        for len in [3, 50] {
+            let mut s: SmallSet<Interned> = (0..len).map(|i| Interned(i, "old")).collect();
+            assert_eq!(None, s.replace(Interned(len, "new")).map(|x| x.1));
+            assert_eq!(Some("old"), s.replace(Interned(1, "new")).map(|x| x.1));
+            assert_eq!(Some("new"), s.get(&Interned(1, "")).map(|x| x.1));
+            assert_eq!(Some(1), s.get_index_of(&Interned(1, "")));
+            assert_eq!(len as usize + 1, s.len());
+
+            assert_eq!(Some("new"), s.take(&Interned(1, "")).map(|x| x.1));
+            assert_eq!(None, s.take(&Interned(1, "")));
+            assert_eq!(Some(1), s.get_index_of(&Interned(2, "")));
+        }
+    }
+
+    #[test]
+    fn test_swap_remove() {
+        let mut s = SmallSet::from_iter([1, 2, 3, 4]);
+        assert!(s.swap_remove(&2));
+        assert!(!s.swap_remove(&2));
+        assert_eq!(vec![1, 4, 3], s.iter().copied().collect::<Vec<_>>());
+        assert!(s.shift_remove(&1));
+        assert_eq!(vec![4, 3], s.iter().copied().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut s = SmallSet::from_iter(0..50);
+        let t = s.split_off(20);
+        assert_eq!(
+            (0..20).collect::<Vec<_>>(),
+            s.iter().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            (20..50).collect::<Vec<_>>(),
+            t.iter().copied().collect::<Vec<_>>()
+        );
+        assert!(s.contains(&19));
+        assert!(!s.contains(&20));
+        assert!(t.contains(&20));
+        assert!(!t.contains(&19));
+    }
+
     #[test]
     fn test_remove() {
         let mut h: HashSet<u32> = HashSet::from_iter([17]);
@@ -636,6 +1177,46 @@ mod tests {
         assert_eq!(vec![3], d);
     }
 
+    #[test]
+    fn test_intersection() {
+        let a = SmallSet::from_iter([1, 2, 3, 5]);
+        let b = SmallSet::from_iter([5, 2, 4, 1]);
+        let d = Vec::from_iter(a.intersection(&b).copied());
+        assert_eq!(vec![1, 2, 5], d);
+        assert_eq!(
+            vec![&5, &2, &1],
+            a.intersection(&b).rev().collect::<Vec<_>>()
+        );
+        assert_eq!(vec![&5, &2, &1], b.intersection(&a).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_operators() {
+        let a = SmallSet::from_iter(["c", "a", "b"]);
+        let b = SmallSet::from_iter(["d", "b", "c"]);
+        assert!((&a | &b).eq_ordered(&SmallSet::from_iter(["c", "a", "b", "d"])));
+        assert!((&a & &b).eq_ordered(&SmallSet::from_iter(["c", "b"])));
+        assert!((&a - &b).eq_ordered(&SmallSet::from_iter(["a"])));
+        assert!((&b - &a).eq_ordered(&SmallSet::from_iter(["d"])));
+
+        let mut c = SmallSet::with_hasher(std::collections::hash_map::RandomState::new());
+        c.extend(0..20);
+        let mut d = SmallSet::with_hasher(std::collections::hash_map::RandomState::new());
+        d.extend(10..30);
+        let union = &c | &d;
+        assert_eq!(
+            (0..30).collect::<Vec<_>>(),
+            union.iter().copied().collect::<Vec<_>>()
+        );
+        assert!(union.contains(&25));
+        let intersection = &c & &d;
+        assert_eq!(
+            (10..20).collect::<Vec<_>>(),
+            intersection.into_iter().collect::<Vec<_>>()
+        );
+        assert!((&c - &d).contains(&5));
+    }
+
     #[test]
     fn test_union() {
         let a = SmallSet::from_iter([1, 2, 3]);
@@ -644,6 +1225,44 @@ mod tests {
         assert_eq!(vec![1, 2, 3, 4], d);
     }
 
+    #[test]
+    fn test_symmetric_difference() {
+        let a = smallset! {1, 2, 3, 5};
+        let b = smallset! {5, 4, 1, 6};
+        assert_eq!(
+            vec![2, 3, 4, 6],
+            a.symmetric_difference(&b).copied().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![6, 4, 3, 2],
+            a.symmetric_difference(&b)
+                .rev()
+                .copied()
+                .collect::<Vec<_>>()
+        );
+        assert!((&b ^ &a).eq_ordered(&smallset! {4, 6, 2, 3}));
+        assert_eq!(0, a.symmetric_difference(&a).count());
+    }
+
+    #[test]
+    fn test_subset_superset_disjoint() {
+        let a = smallset! {1, 2};
+        let b = smallset! {2, 3, 1};
+        let c = smallset! {4, 5, 6};
+        let empty = SmallSet::new();
+        assert!(a.is_subset(&b));
+        assert!(!b.is_subset(&a));
+        assert!(a.is_subset(&a));
+        assert!(empty.is_subset(&a));
+        assert!(b.is_superset(&a));
+        assert!(!a.is_superset(&b));
+        assert!(a.is_superset(&empty));
+        assert!(a.is_disjoint(&c));
+        assert!(c.is_disjoint(&b));
+        assert!(!a.is_disjoint(&b));
+        assert!(empty.is_disjoint(&empty));
+    }
+
     #[test]
     fn test_sort() {
         let mut a = SmallSet::from_iter([1, 3, 2]);
@@ -663,4 +1282,39 @@ mod tests {
         assert_eq!((0, Some(0)), iter.size_hint());
         assert_eq!(None, iter.next());
     }
+
+    #[test]
+    fn test_difference_union_rev() {
+        let a = smallset! {1, 2, 3, 4};
+        let b = smallset! {2, 5};
+        assert_eq!(vec![&4, &3, &1], a.difference(&b).rev().collect::<Vec<_>>());
+        assert_eq!(
+            vec![&5, &4, &3, &2, &1],
+            a.union(&b).rev().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_custom_hasher() {
+        let mut s = SmallSet::with_hasher(std::collections::hash_map::RandomState::new());
+        for i in 0..100 {
+            assert!(s.insert(i));
+        }
+        assert!(!s.insert(10));
+        assert!(s.contains(&10));
+        assert!(s.remove(&10));
+        assert!(!s.contains(&10));
+        assert_eq!(&20, s.get_or_insert(20));
+        assert_eq!(99, s.len());
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut s = smallset! {3, 1, 2};
+        assert_eq!(vec![3, 1, 2], s.drain().collect::<Vec<_>>());
+        assert!(s.is_empty());
+        assert!(!s.contains(&1));
+        s.insert(1);
+        assert_eq!(vec![1], s.into_iter().collect::<Vec<_>>());
+    }
 }
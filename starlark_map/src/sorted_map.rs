@@ -0,0 +1,231 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Map with entries sorted by key.
+
+use std::borrow::Borrow;
+use std::cmp;
+use std::hash::Hash;
+use std::ops::Bound;
+use std::ops::RangeBounds;
+
+use allocative::Allocative;
+use gazebo::prelude::*;
+
+use crate::equivalent::Equivalent;
+use crate::small_map;
+use crate::small_map::SmallMap;
+
+/// [`SmallMap`] with entries sorted by key.
+///
+/// Lookups by key are hash lookups like in [`SmallMap`],
+/// while the order of the entries allows querying ranges of keys.
+/// The map is constructed from an iterator or a [`SmallMap`],
+/// and is not modified after that.
+#[derive(Debug, Clone, Default_, Allocative)]
+pub struct SortedMap<K, V> {
+    map: SmallMap<K, V>,
+}
+
+impl<K, V> SortedMap<K, V> {
+    /// Empty map.
+    #[inline]
+    pub const fn new() -> Self {
+        SortedMap {
+            map: SmallMap::new(),
+        }
+    }
+
+    /// Number of entries.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Is the map empty?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Iterate over the entries in key order.
+    #[inline]
+    pub fn iter(&self) -> small_map::Iter<'_, K, V> {
+        self.map.iter()
+    }
+
+    /// Iterate over the keys in order.
+    #[inline]
+    pub fn keys(&self) -> small_map::Keys<'_, K, V> {
+        self.map.keys()
+    }
+
+    /// Iterate over the values in key order.
+    #[inline]
+    pub fn values(&self) -> small_map::Values<'_, K, V> {
+        self.map.values()
+    }
+
+    /// Find the value by the key.
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        K: Eq,
+    {
+        self.map.get(key)
+    }
+
+    /// Check if the map contains the key.
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        K: Eq,
+    {
+        self.map.contains_key(key)
+    }
+
+    /// The entry with the smallest key.
+    #[inline]
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.map.first()
+    }
+
+    /// The entry with the largest key.
+    #[inline]
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.map.last()
+    }
+
+    /// Iterate over the entries with keys in the range, in key order.
+    ///
+    /// The boundaries are found with binary search. An empty iterator is returned
+    /// if the start of the range is greater than the end.
+    pub fn range<Q, R>(&self, range: R) -> small_map::Iter<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(s) => self.map.partition_point(|k| k.borrow() < s),
+            Bound::Excluded(s) => self.map.partition_point(|k| k.borrow() <= s),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(e) => self.map.partition_point(|k| k.borrow() <= e),
+            Bound::Excluded(e) => self.map.partition_point(|k| k.borrow() < e),
+            Bound::Unbounded => self.map.len(),
+        };
+        self.map.iter_range(start..cmp::max(start, end))
+    }
+
+    /// Convert into the underlying [`SmallMap`], with entries in key order.
+    #[inline]
+    pub fn into_small_map(self) -> SmallMap<K, V> {
+        self.map
+    }
+}
+
+impl<K: Ord + Hash, V> From<SmallMap<K, V>> for SortedMap<K, V> {
+    #[inline]
+    fn from(mut map: SmallMap<K, V>) -> Self {
+        map.sort_keys();
+        SortedMap { map }
+    }
+}
+
+impl<K: Ord + Hash, V> FromIterator<(K, V)> for SortedMap<K, V> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        SmallMap::from_iter(iter).into()
+    }
+}
+
+impl<K, V> IntoIterator for SortedMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = small_map::IntoIter<K, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a SortedMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = small_map::Iter<'a, K, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Eq, V: PartialEq> PartialEq for SortedMap<K, V> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for SortedMap<K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use crate::sorted_map::SortedMap;
+
+    #[test]
+    fn test_sorted() {
+        let map = SortedMap::from_iter([(3, "c"), (1, "a"), (2, "b")]);
+        assert_eq!(vec![1, 2, 3], map.keys().copied().collect::<Vec<_>>());
+        assert_eq!(Some(&"b"), map.get(&2));
+        assert_eq!(Some((&1, &"a")), map.first_key_value());
+        assert_eq!(Some((&3, &"c")), map.last_key_value());
+        assert_eq!(None, SortedMap::<u32, u32>::new().first_key_value());
+    }
+
+    #[test]
+    fn test_range() {
+        let map: SortedMap<u32, u32> = (0..20).map(|i| (i * 2, i)).collect();
+        fn keys<'a>(iter: impl Iterator<Item = (&'a u32, &'a u32)>) -> Vec<u32> {
+            iter.map(|(k, _)| *k).collect()
+        }
+
+        assert_eq!(vec![4, 6, 8], keys(map.range(3..10)));
+        assert_eq!(vec![4, 6, 8, 10], keys(map.range(4..=10)));
+        assert_eq!(vec![34, 36, 38], keys(map.range(33..)));
+        assert_eq!(vec![0, 2], keys(map.range(..4)));
+        assert_eq!(20, map.range(..).len());
+        assert!(keys(map.range((Bound::Included(10), Bound::Excluded(5)))).is_empty());
+        assert!(keys(map.range(40..)).is_empty());
+        assert_eq!(vec![8, 6, 4], keys(map.range(3..10).rev()));
+
+        let map: SortedMap<String, u32> = ["b", "d", "a", "c"]
+            .iter()
+            .map(|s| (s.to_string(), 0))
+            .collect();
+        let range = map.range::<str, _>((Bound::Included("b"), Bound::Excluded("d")));
+        assert_eq!(
+            vec!["b", "c"],
+            range.map(|(k, _)| k.as_str()).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,160 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Set with elements sorted.
+
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::ops::RangeBounds;
+
+use allocative::Allocative;
+use gazebo::prelude::*;
+
+use crate::equivalent::Equivalent;
+use crate::small_set;
+use crate::small_set::SmallSet;
+use crate::sorted_map::SortedMap;
+
+/// [`SmallSet`] with elements sorted.
+///
+/// Like [`SortedMap`], this set is constructed from an iterator or a [`SmallSet`],
+/// and is not modified after that.
+#[derive(Debug, Clone, Default_, Allocative)]
+pub struct SortedSet<T> {
+    map: SortedMap<T, ()>,
+}
+
+impl<T> SortedSet<T> {
+    /// Empty set.
+    #[inline]
+    pub const fn new() -> Self {
+        SortedSet {
+            map: SortedMap::new(),
+        }
+    }
+
+    /// Number of elements.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Is the set empty?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Iterate over the elements in order.
+    #[inline]
+    pub fn iter(&self) -> small_set::Iter<'_, T> {
+        small_set::Iter {
+            iter: self.map.iter(),
+        }
+    }
+
+    /// Check if the set contains the element.
+    #[inline]
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        Q: Hash + Equivalent<T> + ?Sized,
+        T: Eq,
+    {
+        self.map.contains_key(value)
+    }
+
+    /// The smallest element.
+    #[inline]
+    pub fn first(&self) -> Option<&T> {
+        self.map.first_key_value().map(|(k, _)| k)
+    }
+
+    /// The largest element.
+    #[inline]
+    pub fn last(&self) -> Option<&T> {
+        self.map.last_key_value().map(|(k, _)| k)
+    }
+
+    /// Iterate over the elements in the range, in order.
+    ///
+    /// An empty iterator is returned if the start of the range is greater than the end.
+    #[inline]
+    pub fn range<Q, R>(&self, range: R) -> small_set::Iter<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        small_set::Iter {
+            iter: self.map.range(range),
+        }
+    }
+}
+
+impl<T: Ord + Hash> From<SmallSet<T>> for SortedSet<T> {
+    #[inline]
+    fn from(set: SmallSet<T>) -> Self {
+        set.into_iter().collect()
+    }
+}
+
+impl<T: Ord + Hash> FromIterator<T> for SortedSet<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        SortedSet {
+            map: iter.into_iter().map(|x| (x, ())).collect(),
+        }
+    }
+}
+
+impl<T: Eq> PartialEq for SortedSet<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<T: Eq> Eq for SortedSet<T> {}
+
+impl<'a, T> IntoIterator for &'a SortedSet<T> {
+    type Item = &'a T;
+    type IntoIter = small_set::Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sorted_set::SortedSet;
+
+    #[test]
+    fn test_range() {
+        let set: SortedSet<u32> = [5, 1, 9, 3, 7].into_iter().collect();
+        assert_eq!(vec![1, 3, 5, 7, 9], set.iter().copied().collect::<Vec<_>>());
+        assert_eq!(Some(&1), set.first());
+        assert_eq!(Some(&9), set.last());
+        assert!(set.contains(&7));
+        assert_eq!(vec![3, 5], set.range(2..6).copied().collect::<Vec<_>>());
+        assert_eq!(
+            vec![9, 7],
+            set.range(6..).rev().copied().collect::<Vec<_>>()
+        );
+    }
+}
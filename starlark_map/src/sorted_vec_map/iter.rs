@@ -0,0 +1,184 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::iter::FusedIterator;
+
+use dupe::Clone_;
+
+use crate::iter::def_double_ended_iter;
+use crate::iter::def_iter;
+use crate::vec_map;
+use crate::Hashed;
+
+/// Iterator over the hashed entries of [`SortedVecMap`](crate::sorted_vec_map::SortedVecMap).
+#[derive(Clone_)]
+pub struct IterHashed<'a, K, V> {
+    pub(crate) iter: vec_map::IterHashed<'a, K, V>,
+}
+
+impl<'a, K, V> IterHashed<'a, K, V> {
+    #[inline]
+    fn map((k, v): (Hashed<&'a K>, &'a V)) -> <Self as Iterator>::Item {
+        (k, v)
+    }
+}
+
+impl<'a, K, V> Iterator for IterHashed<'a, K, V> {
+    type Item = (Hashed<&'a K>, &'a V);
+
+    def_iter!();
+}
+
+impl<'a, K, V> ExactSizeIterator for IterHashed<'a, K, V> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, K, V> FusedIterator for IterHashed<'a, K, V> {}
+
+impl<'a, K, V> DoubleEndedIterator for IterHashed<'a, K, V> {
+    def_double_ended_iter!();
+}
+
+/// Iterator over the entries of [`SortedVecMap`](crate::sorted_vec_map::SortedVecMap).
+#[derive(Clone_)]
+pub struct Iter<'a, K, V> {
+    pub(crate) iter: vec_map::Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    #[inline]
+    fn map((k, v): (&'a K, &'a V)) -> <Self as Iterator>::Item {
+        (k, v)
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    def_iter!();
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    def_double_ended_iter!();
+}
+
+/// Iterator over the keys of [`SortedVecMap`](crate::sorted_vec_map::SortedVecMap).
+#[derive(Clone_)]
+pub struct Keys<'a, K, V> {
+    pub(crate) iter: vec_map::Keys<'a, K, V>,
+}
+
+impl<'a, K, V> Keys<'a, K, V> {
+    #[inline]
+    fn map(k: &'a K) -> <Self as Iterator>::Item {
+        k
+    }
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    def_iter!();
+}
+
+impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, K, V> FusedIterator for Keys<'a, K, V> {}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    def_double_ended_iter!();
+}
+
+/// Iterator over the values of [`SortedVecMap`](crate::sorted_vec_map::SortedVecMap).
+#[derive(Clone_)]
+pub struct Values<'a, K, V> {
+    pub(crate) iter: vec_map::Values<'a, K, V>,
+}
+
+impl<'a, K, V> Values<'a, K, V> {
+    #[inline]
+    fn map(v: &'a V) -> <Self as Iterator>::Item {
+        v
+    }
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    def_iter!();
+}
+
+impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, K, V> FusedIterator for Values<'a, K, V> {}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    def_double_ended_iter!();
+}
+
+/// Iterator that moves entries out of a [`SortedVecMap`](crate::sorted_vec_map::SortedVecMap).
+pub struct IntoIter<K, V> {
+    pub(crate) iter: vec_map::IntoIter<K, V>,
+}
+
+impl<K, V> IntoIter<K, V> {
+    #[inline]
+    fn map((k, v): (K, V)) -> <Self as Iterator>::Item {
+        (k, v)
+    }
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    def_iter!();
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    def_double_ended_iter!();
+}
@@ -0,0 +1,478 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Compact map for small read-mostly tables.
+
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use allocative::Allocative;
+use gazebo::prelude::*;
+
+use crate::equivalent::Equivalent;
+use crate::hashed::Hashed;
+use crate::small_map::SmallMap;
+pub use crate::sorted_vec_map::iter::IntoIter;
+pub use crate::sorted_vec_map::iter::Iter;
+pub use crate::sorted_vec_map::iter::IterHashed;
+pub use crate::sorted_vec_map::iter::Keys;
+pub use crate::sorted_vec_map::iter::Values;
+use crate::vec_map::VecMap;
+use crate::StarlarkHashValue;
+
+mod iter;
+
+/// Map stored as a single array of entries sorted by key hash.
+///
+/// This is the same representation as [`SmallMap`] without an index,
+/// but lookup is a binary search by hash rather than a linear scan,
+/// so lookups stay cheap for any size, while the memory overhead is just the hashes.
+///
+/// Insertion and removal are `O(n)`, so this map is meant for small tables
+/// which are built once and read many times. Tables which keep growing
+/// should be converted to [`SmallMap`] with [`into_small_map`](SortedVecMap::into_small_map).
+///
+/// Entries are iterated in the order of their hashes, not in insertion order.
+#[derive(Clone, Default_, Allocative)]
+pub struct SortedVecMap<K, V> {
+    /// Entries sorted by hash, entries with equal hashes are in insertion order.
+    entries: VecMap<K, V>,
+}
+
+impl<K: Debug, V: Debug> Debug for SortedVecMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V> SortedVecMap<K, V> {
+    /// Empty map.
+    #[inline]
+    pub const fn new() -> Self {
+        SortedVecMap {
+            entries: VecMap::new(),
+        }
+    }
+
+    /// Empty map with specified capacity.
+    #[inline]
+    pub fn with_capacity(n: usize) -> Self {
+        SortedVecMap {
+            entries: VecMap::with_capacity(n),
+        }
+    }
+
+    /// Number of entries.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Is the map empty?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Current capacity of the map.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    /// Reserve capacity for at least `additional` more entries.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.entries.reserve(additional);
+    }
+
+    /// Iterate over the entries.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            iter: self.entries.iter(),
+        }
+    }
+
+    /// Iterate over the entries, with keys hashed.
+    #[inline]
+    pub fn iter_hashed(&self) -> IterHashed<'_, K, V> {
+        IterHashed {
+            iter: self.entries.iter_hashed(),
+        }
+    }
+
+    /// Iterate over the keys.
+    #[inline]
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys {
+            iter: self.entries.keys(),
+        }
+    }
+
+    /// Iterate over the values.
+    #[inline]
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values {
+            iter: self.entries.values(),
+        }
+    }
+
+    /// First index of an entry with the hash, or the index where such entry would be inserted.
+    #[inline]
+    fn lower_bound(&self, hash: StarlarkHashValue) -> usize {
+        self.entries
+            .hashes()
+            .partition_point(|h| h.get() < hash.get())
+    }
+
+    /// Index of the entry with the key, or the index where the key should be inserted.
+    #[inline]
+    fn search<Q>(&self, key: Hashed<&Q>) -> Result<usize, usize>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        let hashes = self.entries.hashes();
+        let mut i = self.lower_bound(key.hash());
+        while i < hashes.len() && hashes[i] == key.hash() {
+            // SAFETY: `i` is in bounds.
+            let (k, _) = unsafe { self.entries.get_unchecked(i) };
+            if key.key().equivalent(k.key()) {
+                return Ok(i);
+            }
+            i += 1;
+        }
+        Err(i)
+    }
+
+    /// Find the position of the key, with the key hashed.
+    #[inline]
+    pub fn get_index_of_hashed<Q>(&self, key: Hashed<&Q>) -> Option<usize>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        self.search(key).ok()
+    }
+
+    /// Find the entry by index. Entries are sorted by hash.
+    #[inline]
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.entries.get_index(index)
+    }
+
+    /// Find the entry by the key, with the key hashed.
+    #[inline]
+    pub fn get_full_hashed<Q>(&self, key: Hashed<&Q>) -> Option<(usize, &K, &V)>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        let index = self.get_index_of_hashed(key)?;
+        // SAFETY: `search` returns valid index.
+        let (k, v) = unsafe { self.entries.get_unchecked(index) };
+        Some((index, k.into_key(), v))
+    }
+
+    /// Find the value by the key, with the key hashed.
+    #[inline]
+    pub fn get_hashed<Q>(&self, key: Hashed<&Q>) -> Option<&V>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        self.get_full_hashed(key).map(|(_, _, v)| v)
+    }
+
+    /// Find the value by the key.
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_hashed(Hashed::new(key))
+    }
+
+    /// Find the mutable value by the key, with the key hashed.
+    #[inline]
+    pub fn get_mut_hashed<Q>(&mut self, key: Hashed<&Q>) -> Option<&mut V>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        let index = self.get_index_of_hashed(key)?;
+        // SAFETY: `search` returns valid index.
+        let (_, v) = unsafe { self.entries.get_unchecked_mut(index) };
+        Some(v)
+    }
+
+    /// Find the mutable value by the key.
+    #[inline]
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_mut_hashed(Hashed::new(key))
+    }
+
+    /// Check if the map contains the key, with the key hashed.
+    #[inline]
+    pub fn contains_key_hashed<Q>(&self, key: Hashed<&Q>) -> bool
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        self.get_index_of_hashed(key).is_some()
+    }
+
+    /// Check if the map contains the key.
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.contains_key_hashed(Hashed::new(key))
+    }
+
+    /// Insert an entry into the map with the key hashed.
+    /// Return the previous value if the key was present.
+    pub fn insert_hashed(&mut self, key: Hashed<K>, value: V) -> Option<V>
+    where
+        K: Eq,
+    {
+        match self.search(key.as_ref()) {
+            Ok(index) => {
+                // SAFETY: `search` returns valid index.
+                let (_, v) = unsafe { self.entries.get_unchecked_mut(index) };
+                Some(std::mem::replace(v, value))
+            }
+            Err(index) => {
+                self.entries
+                    .insert_hashed_unique_unchecked_at(index, key, value);
+                None
+            }
+        }
+    }
+
+    /// Insert an entry into the map.
+    /// Return the previous value if the key was present.
+    #[inline]
+    pub fn insert(&mut self, key: K, value: V) -> Option<V>
+    where
+        K: Hash + Eq,
+    {
+        self.insert_hashed(Hashed::new(key), value)
+    }
+
+    /// Remove the entry by the key with the key hashed, returning the removed entry.
+    pub fn remove_hashed_entry<Q>(&mut self, key: Hashed<&Q>) -> Option<(K, V)>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        let index = self.get_index_of_hashed(key)?;
+        let (k, v) = self.entries.remove(index);
+        Some((k.into_key(), v))
+    }
+
+    /// Remove the entry by the key with the key hashed.
+    #[inline]
+    pub fn remove_hashed<Q>(&mut self, key: Hashed<&Q>) -> Option<V>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        self.remove_hashed_entry(key).map(|(_, v)| v)
+    }
+
+    /// Remove the entry by the key.
+    #[inline]
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_hashed(Hashed::new(key))
+    }
+
+    /// Remove all entries.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Convert into [`SmallMap`], preserving the iteration order.
+    ///
+    /// This does not rehash the keys.
+    pub fn into_small_map(self) -> SmallMap<K, V>
+    where
+        K: Eq,
+    {
+        let mut map = SmallMap::with_capacity(self.len());
+        for (k, v) in self.entries.into_iter_hashed() {
+            // Keys are unique in this map.
+            map.insert_hashed_unique_unchecked(k, v);
+        }
+        map
+    }
+}
+
+impl<K: Eq, V> From<SortedVecMap<K, V>> for SmallMap<K, V> {
+    #[inline]
+    fn from(map: SortedVecMap<K, V>) -> Self {
+        map.into_small_map()
+    }
+}
+
+impl<K: Eq, V> From<SmallMap<K, V>> for SortedVecMap<K, V> {
+    fn from(map: SmallMap<K, V>) -> Self {
+        map.into_iter_hashed().collect()
+    }
+}
+
+impl<K: Hash + Eq, V> FromIterator<(K, V)> for SortedVecMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        iter.into_iter().map(|(k, v)| (Hashed::new(k), v)).collect()
+    }
+}
+
+impl<K: Eq, V> FromIterator<(Hashed<K>, V)> for SortedVecMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (Hashed<K>, V)>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut map = SortedVecMap::with_capacity(iter.size_hint().0);
+        for (k, v) in iter {
+            map.insert_hashed(k, v);
+        }
+        map
+    }
+}
+
+impl<K: Hash + Eq, V> Extend<(K, V)> for SortedVecMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K, V> IntoIterator for SortedVecMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            iter: self.entries.into_iter(),
+        }
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a SortedVecMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Eq, V: PartialEq> PartialEq for SortedVecMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        // Entries with equal hashes may be in different order.
+        self.len() == other.len()
+            && self
+                .iter_hashed()
+                .all(|(k, v)| other.get_hashed(k) == Some(v))
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for SortedVecMap<K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl<K: Hash + Eq, V> SortedVecMap<K, V> {
+        fn assert_invariants(&self) {
+            let hashes = self.entries.hashes();
+            assert!(hashes.windows(2).all(|w| w[0].get() <= w[1].get()));
+            for (k, _) in self.iter_hashed() {
+                assert_eq!(k.hash(), StarlarkHashValue::new(k.key()));
+            }
+        }
+    }
+
+    /// Key with a poor hash to exercise equal hashes.
+    #[derive(Debug, PartialEq, Eq)]
+    struct BadHash(u32);
+
+    impl Hash for BadHash {
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            (self.0 % 3).hash(state)
+        }
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut m = SortedVecMap::new();
+        for i in 0..100 {
+            assert_eq!(None, m.insert(i.to_string(), i));
+        }
+        m.assert_invariants();
+        assert_eq!(100, m.len());
+        for i in 0..100 {
+            assert_eq!(Some(&i), m.get(i.to_string().as_str()));
+        }
+        assert_eq!(None, m.get("100"));
+        assert_eq!(Some(5), m.insert("5".to_owned(), 50));
+        *m.get_mut("6").unwrap() = 60;
+        assert_eq!(Some(&50), m.get("5"));
+        assert_eq!(Some(&60), m.get("6"));
+        assert_eq!(Some(7), m.remove("7"));
+        assert_eq!(None, m.remove("7"));
+        assert_eq!(99, m.len());
+        m.assert_invariants();
+    }
+
+    #[test]
+    fn test_equal_hashes() {
+        let mut m = SortedVecMap::new();
+        for i in 0..20 {
+            m.insert(BadHash(i), i);
+        }
+        m.assert_invariants();
+        for i in 0..20 {
+            assert_eq!(Some(&i), m.get(&BadHash(i)));
+        }
+        assert_eq!(None, m.get(&BadHash(20)));
+        assert_eq!(Some(4), m.remove(&BadHash(4)));
+        assert_eq!(None, m.get(&BadHash(4)));
+        assert_eq!(Some(&7), m.get(&BadHash(7)));
+
+        let other: SortedVecMap<_, _> = (0..20)
+            .rev()
+            .filter(|i| *i != 4)
+            .map(|i| (BadHash(i), i))
+            .collect();
+        assert_eq!(m, other);
+    }
+
+    #[test]
+    fn test_small_map_conversion() {
+        let m: SortedVecMap<String, u32> = (0..30).map(|i| (i.to_string(), i)).collect();
+        let small = m.clone().into_small_map();
+        assert_eq!(30, small.len());
+        assert!(m.iter().eq(small.iter()));
+        let back = SortedVecMap::from(small);
+        back.assert_invariants();
+        assert_eq!(m, back);
+    }
+}
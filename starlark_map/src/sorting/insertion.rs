@@ -46,10 +46,13 @@ pub(crate) fn slice_swap_shift<T>(slice: &mut [T], a: usize, b: usize) {
     assert!(a < b);
     assert!(b < slice.len());
 
+    // Derive all the pointers from one, `as_mut_ptr` would invalidate a pointer
+    // obtained with `as_ptr` under stacked borrows.
+    let ptr = slice.as_mut_ptr();
     unsafe {
-        let tmp = ptr::read(slice.as_mut_ptr().add(b));
-        ptr::copy(slice.as_ptr().add(a), slice.as_mut_ptr().add(a + 1), b - a);
-        ptr::write(slice.as_mut_ptr().add(a), tmp);
+        let tmp = ptr::read(ptr.add(b));
+        ptr::copy(ptr.add(a), ptr.add(a + 1), b - a);
+        ptr::write(ptr.add(a), tmp);
     }
 }
 
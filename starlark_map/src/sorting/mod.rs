@@ -16,3 +16,4 @@
  */
 
 pub(crate) mod insertion;
+pub(crate) mod stable;
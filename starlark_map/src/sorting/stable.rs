@@ -0,0 +1,225 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Generic stable in-place merge sort (sort arbitrary collections, not just slices).
+//!
+//! This is the "SymMerge" algorithm by Pok-Son Kim and Arne Kutzner,
+//! the same one used by Go's `sort.Stable`. It performs `O(n log n)` comparisons
+//! and `O(n log^2 n)` swaps, and does not allocate.
+//!
+//! Elements are only moved with `swap`, so if `less` panics,
+//! the collection is left in a valid (but unspecified) order.
+
+/// Size of the blocks sorted with insertion sort before merging.
+const BLOCK_SIZE: usize = 20;
+
+/// Sort the range `a..b` with insertion sort.
+fn insertion_sort_range<A: ?Sized>(
+    array: &mut A,
+    a: usize,
+    b: usize,
+    less: &mut impl FnMut(&mut A, usize, usize) -> bool,
+    swap: &mut impl FnMut(&mut A, usize, usize),
+) {
+    for i in a + 1..b {
+        let mut j = i;
+        while j > a && less(array, j, j - 1) {
+            swap(array, j, j - 1);
+            j -= 1;
+        }
+    }
+}
+
+/// Swap ranges `a..a + n` and `b..b + n`. Ranges must not overlap.
+fn swap_range<A: ?Sized>(
+    array: &mut A,
+    a: usize,
+    b: usize,
+    n: usize,
+    swap: &mut impl FnMut(&mut A, usize, usize),
+) {
+    for i in 0..n {
+        swap(array, a + i, b + i);
+    }
+}
+
+/// Rotate the range `a..b` so that the element at `m` moves to `a`.
+fn rotate<A: ?Sized>(
+    array: &mut A,
+    a: usize,
+    m: usize,
+    b: usize,
+    swap: &mut impl FnMut(&mut A, usize, usize),
+) {
+    let mut i = m - a;
+    let mut j = b - m;
+    while i != j {
+        if i > j {
+            swap_range(array, m - i, m, j, swap);
+            i -= j;
+        } else {
+            swap_range(array, m - i, m + j - i, i, swap);
+            j -= i;
+        }
+    }
+    swap_range(array, m - i, m, i, swap);
+}
+
+/// Merge sorted ranges `a..m` and `m..b`.
+fn sym_merge<A: ?Sized>(
+    array: &mut A,
+    a: usize,
+    m: usize,
+    b: usize,
+    less: &mut impl FnMut(&mut A, usize, usize) -> bool,
+    swap: &mut impl FnMut(&mut A, usize, usize),
+) {
+    if m - a == 1 {
+        // Single element on the left: binary search its position on the right
+        // (after all equal elements), and shift it there.
+        let mut i = m;
+        let mut j = b;
+        while i < j {
+            let h = i + (j - i) / 2;
+            if less(array, h, a) {
+                i = h + 1;
+            } else {
+                j = h;
+            }
+        }
+        for k in a..i - 1 {
+            swap(array, k, k + 1);
+        }
+        return;
+    }
+    if b - m == 1 {
+        // Single element on the right: binary search its position on the left
+        // (after all equal elements), and shift it there.
+        let mut i = a;
+        let mut j = m;
+        while i < j {
+            let h = i + (j - i) / 2;
+            if !less(array, m, h) {
+                i = h + 1;
+            } else {
+                j = h;
+            }
+        }
+        for k in (i + 1..=m).rev() {
+            swap(array, k, k - 1);
+        }
+        return;
+    }
+
+    let mid = a + (b - a) / 2;
+    let n = mid + m;
+    let (mut start, mut r) = if m > mid { (n - b, mid) } else { (a, m) };
+    let p = n - 1;
+    while start < r {
+        let c = start + (r - start) / 2;
+        if !less(array, p - c, c) {
+            start = c + 1;
+        } else {
+            r = c;
+        }
+    }
+    let end = n - start;
+    if start < m && m < end {
+        rotate(array, start, m, end, swap);
+    }
+    if a < start && start < mid {
+        sym_merge(array, a, start, mid, less, swap);
+    }
+    if mid < end && end < b {
+        sym_merge(array, mid, end, b, less, swap);
+    }
+}
+
+/// Stable sort for generic collections (not just slices) which does not allocate.
+pub(crate) fn stable_sort<A: ?Sized>(
+    array: &mut A,
+    len: usize,
+    mut less: impl FnMut(&mut A, usize, usize) -> bool,
+    // Swap elements at two distinct indices.
+    mut swap: impl FnMut(&mut A, usize, usize),
+) {
+    let mut a = 0;
+    while a < len {
+        let b = len.min(a + BLOCK_SIZE);
+        insertion_sort_range(array, a, b, &mut less, &mut swap);
+        a = b;
+    }
+
+    let mut block_size = BLOCK_SIZE;
+    while block_size < len {
+        let mut a = 0;
+        while a + block_size < len {
+            let m = a + block_size;
+            let b = len.min(m + block_size);
+            sym_merge(array, a, m, b, &mut less, &mut swap);
+            a = b;
+        }
+        block_size *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sorting::stable::stable_sort;
+
+    fn stable_sort_ints(slice: &mut [u32]) {
+        stable_sort(
+            slice,
+            slice.len(),
+            |slice, i, j| {
+                // Compare / 100 to test stability.
+                slice[i] / 100 < slice[j] / 100
+            },
+            |slice, i, j| slice.swap(i, j),
+        );
+    }
+
+    #[test]
+    fn test_stable_sort_small() {
+        let mut slice = [600, 200, 400];
+        stable_sort_ints(&mut slice);
+        assert_eq!([200, 400, 600], slice);
+
+        let mut slice = [202, 402, 602, 201, 401, 601];
+        stable_sort_ints(&mut slice);
+        assert_eq!([202, 201, 402, 401, 602, 601], slice);
+    }
+
+    #[test]
+    fn test_stable_sort_matches_std() {
+        let mut seed = 17u32;
+        for len in [0, 1, 2, 19, 20, 21, 39, 40, 41, 100, 257, 1000] {
+            let mut slice: Vec<u32> = (0..len)
+                .map(|i| {
+                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                    // Few distinct keys, so there are many equal elements;
+                    // the low digits record the original position.
+                    (seed >> 16) % 10 * 100 + i % 100
+                })
+                .collect();
+            let mut expected = slice.clone();
+            expected.sort_by_key(|x| x / 100);
+            stable_sort_ints(&mut slice);
+            assert_eq!(expected, slice);
+        }
+    }
+}
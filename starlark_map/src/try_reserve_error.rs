@@ -0,0 +1,67 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt;
+use std::fmt::Display;
+
+/// Error returned by `try_reserve` functions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TryReserveError {
+    kind: TryReserveErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TryReserveErrorKind {
+    CapacityOverflow,
+    AllocError,
+}
+
+impl TryReserveError {
+    pub(crate) fn capacity_overflow() -> TryReserveError {
+        TryReserveError {
+            kind: TryReserveErrorKind::CapacityOverflow,
+        }
+    }
+
+    pub(crate) fn alloc_error() -> TryReserveError {
+        TryReserveError {
+            kind: TryReserveErrorKind::AllocError,
+        }
+    }
+}
+
+impl From<hashbrown::TryReserveError> for TryReserveError {
+    fn from(e: hashbrown::TryReserveError) -> TryReserveError {
+        match e {
+            hashbrown::TryReserveError::CapacityOverflow => TryReserveError::capacity_overflow(),
+            hashbrown::TryReserveError::AllocError { .. } => TryReserveError::alloc_error(),
+        }
+    }
+}
+
+impl Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            TryReserveErrorKind::CapacityOverflow => {
+                write!(f, "capacity overflow when reserving memory")
+            }
+            TryReserveErrorKind::AllocError => write!(f, "memory allocation failed"),
+        }
+    }
+}
+
+impl std::error::Error for TryReserveError {}
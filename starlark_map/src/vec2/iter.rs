@@ -15,17 +15,22 @@
  * limitations under the License.
  */
 
+use std::iter::FusedIterator;
 use std::marker::PhantomData;
+use std::mem;
 use std::ptr;
 use std::ptr::NonNull;
 use std::slice;
 
 use dupe::Clone_;
 
+use crate::vec2::DeallocOnDrop;
 use crate::vec2::Vec2;
+use crate::vec2::drop_in_place_pair;
 
+/// Iterator over the pairs of a [`Vec2`].
 #[derive(Clone_)]
-pub(crate) struct Iter<'a, A, B> {
+pub struct Iter<'a, A, B> {
     pub(crate) aaa: slice::Iter<'a, A>,
     pub(crate) bbb: NonNull<B>,
     pub(crate) _marker: PhantomData<slice::Iter<'a, B>>,
@@ -58,6 +63,8 @@ impl<'s, A, B> ExactSizeIterator for Iter<'s, A, B> {
     }
 }
 
+impl<'s, A, B> FusedIterator for Iter<'s, A, B> {}
+
 impl<'s, A, B> DoubleEndedIterator for Iter<'s, A, B> {
     #[inline]
     fn next_back(&mut self) -> Option<Self::Item> {
@@ -67,13 +74,17 @@ impl<'s, A, B> DoubleEndedIterator for Iter<'s, A, B> {
     }
 }
 
-pub(crate) struct IntoIter<A, B> {
+/// Iterator that moves pairs out of a [`Vec2`].
+pub struct IntoIter<A, B> {
     /// Pointer to the next `A`. Updated as we iterate.
     pub(crate) aaa_begin: NonNull<A>,
     /// Pointer to the next `B`. Updated as we iterate.
     pub(crate) bbb_begin: NonNull<B>,
-    /// Pointer to the end of the `bbb`. Updated as we iterate.
-    pub(crate) bbb_end: NonNull<B>,
+    /// Number of remaining elements. Updated as we iterate.
+    ///
+    /// Stored explicitly rather than as an end pointer,
+    /// because pointers do not advance when `B` is zero-sized.
+    pub(crate) rem: usize,
     /// The layout of `Vec2` is `[padding, aaa, bbb]`.
     /// This field is a pointer to `bbb`. Used for `Drop`.
     pub(crate) bbb_ptr: NonNull<B>,
@@ -89,12 +100,13 @@ impl<A, B> Iterator for IntoIter<A, B> {
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        if self.bbb_begin == self.bbb_end {
+        if self.rem == 0 {
             None
         } else {
+            self.rem -= 1;
             unsafe {
-                let a = ptr::read(self.aaa_begin.as_ref());
-                let b = ptr::read(self.bbb_begin.as_ref());
+                let a = ptr::read(self.aaa_begin.as_ptr());
+                let b = ptr::read(self.bbb_begin.as_ptr());
                 self.aaa_begin = NonNull::new_unchecked(self.aaa_begin.as_ptr().add(1));
                 self.bbb_begin = NonNull::new_unchecked(self.bbb_begin.as_ptr().add(1));
                 Some((a, b))
@@ -111,39 +123,142 @@ impl<A, B> Iterator for IntoIter<A, B> {
 
 impl<A, B> Drop for IntoIter<A, B> {
     fn drop(&mut self) {
-        unsafe {
-            let rem = self.len();
-            ptr::drop_in_place(slice::from_raw_parts_mut(self.aaa_begin.as_ptr(), rem));
-            ptr::drop_in_place(slice::from_raw_parts_mut(self.bbb_begin.as_ptr(), rem));
-            Vec2::<A, B>::dealloc_impl(self.bbb_ptr, self.cap);
-        }
+        // Free the buffer even if a destructor panics.
+        let _dealloc = DeallocOnDrop::<A, B> {
+            bbb_ptr: self.bbb_ptr,
+            cap: self.cap,
+            _marker: PhantomData,
+        };
+        let rem = mem::take(&mut self.rem);
+        unsafe { drop_in_place_pair(self.aaa_begin, self.bbb_begin, rem) }
     }
 }
 
 impl<A, B> ExactSizeIterator for IntoIter<A, B> {
     #[inline]
     fn len(&self) -> usize {
-        unsafe { self.bbb_end.as_ptr().offset_from(self.bbb_begin.as_ptr()) as usize }
+        self.rem
     }
 }
 
+impl<A, B> FusedIterator for IntoIter<A, B> {}
+
 impl<A, B> DoubleEndedIterator for IntoIter<A, B> {
     fn next_back(&mut self) -> Option<Self::Item> {
-        if self.bbb_begin == self.bbb_end {
+        if self.rem == 0 {
+            None
+        } else {
+            self.rem -= 1;
+            unsafe {
+                let a = ptr::read(self.aaa_begin.as_ptr().add(self.rem));
+                let b = ptr::read(self.bbb_begin.as_ptr().add(self.rem));
+                Some((a, b))
+            }
+        }
+    }
+}
+
+/// Iterator that moves a range of elements out of a [`Vec2`].
+///
+/// Elements after the range are moved into its place when the iterator is dropped.
+pub struct Drain<'a, A, B> {
+    /// Pointer to the next `A`. Updated as we iterate.
+    pub(crate) aaa_begin: NonNull<A>,
+    /// Pointer to the next `B`. Updated as we iterate.
+    pub(crate) bbb_begin: NonNull<B>,
+    /// Number of remaining drained elements. Updated as we iterate.
+    pub(crate) rem: usize,
+    /// The drained vector, its length is the start of the range while we iterate.
+    pub(crate) vec2: NonNull<Vec2<A, B>>,
+    /// Index of the first element after the range.
+    pub(crate) tail_start: usize,
+    /// Number of elements after the range.
+    pub(crate) tail_len: usize,
+    pub(crate) _marker: PhantomData<&'a mut Vec2<A, B>>,
+}
+
+unsafe impl<A: Send, B: Send> Send for Drain<'_, A, B> {}
+unsafe impl<A: Sync, B: Sync> Sync for Drain<'_, A, B> {}
+
+impl<A, B> Iterator for Drain<'_, A, B> {
+    type Item = (A, B);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rem == 0 {
             None
         } else {
+            self.rem -= 1;
             unsafe {
-                self.bbb_end = NonNull::new_unchecked(self.bbb_end.as_ptr().sub(1));
-                let new_len = self.len();
+                let a = ptr::read(self.aaa_begin.as_ptr());
+                let b = ptr::read(self.bbb_begin.as_ptr());
+                self.aaa_begin = NonNull::new_unchecked(self.aaa_begin.as_ptr().add(1));
+                self.bbb_begin = NonNull::new_unchecked(self.bbb_begin.as_ptr().add(1));
+                Some((a, b))
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.len();
+        (rem, Some(rem))
+    }
+}
+
+impl<A, B> Drop for Drain<'_, A, B> {
+    fn drop(&mut self) {
+        /// Moves the tail even if a destructor of a drained element panics.
+        struct MoveTail<'r, 'a, A, B>(&'r mut Drain<'a, A, B>);
+
+        impl<A, B> Drop for MoveTail<'_, '_, A, B> {
+            fn drop(&mut self) {
+                let Drain {
+                    vec2,
+                    tail_start,
+                    tail_len,
+                    ..
+                } = *self.0;
+                if tail_len == 0 {
+                    return;
+                }
+                unsafe {
+                    let vec2 = &mut *vec2.as_ptr();
+                    let start = vec2.len;
+                    let (aaa, bbb) = (vec2.aaa_ptr().as_ptr(), vec2.bbb_ptr().as_ptr());
+                    ptr::copy(aaa.add(tail_start), aaa.add(start), tail_len);
+                    ptr::copy(bbb.add(tail_start), bbb.add(start), tail_len);
+                    vec2.len = start + tail_len;
+                }
+            }
+        }
 
-                debug_assert!(ptr::eq(
-                    self.bbb_begin.as_ptr().add(new_len),
-                    self.bbb_end.as_ptr()
-                ));
+        // `Vec2` length is already the start of the range, so if a destructor panics,
+        // the remaining elements are leaked, but not dropped twice.
+        let rem = mem::take(&mut self.rem);
+        let guard = MoveTail(self);
+        unsafe { drop_in_place_pair(guard.0.aaa_begin, guard.0.bbb_begin, rem) }
+    }
+}
 
-                let a = ptr::read(self.aaa_begin.as_ptr().add(new_len));
-                let b = ptr::read(self.bbb_end.as_ptr());
+impl<A, B> ExactSizeIterator for Drain<'_, A, B> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.rem
+    }
+}
+
+impl<A, B> FusedIterator for Drain<'_, A, B> {}
 
+impl<A, B> DoubleEndedIterator for Drain<'_, A, B> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.rem == 0 {
+            None
+        } else {
+            self.rem -= 1;
+            unsafe {
+                let a = ptr::read(self.aaa_begin.as_ptr().add(self.rem));
+                let b = ptr::read(self.bbb_begin.as_ptr().add(self.rem));
                 Some((a, b))
             }
         }
@@ -15,15 +15,21 @@
  * limitations under the License.
  */
 
+//! Vector of pairs with the components stored in separate arrays.
+
 use std::alloc;
 use std::alloc::Layout;
 use std::alloc::LayoutError;
 use std::cmp;
 use std::cmp::Ordering;
 use std::fmt::Debug;
+use std::hash::Hash;
+use std::hash::Hasher;
 use std::marker::PhantomData;
 use std::mem;
 use std::mem::MaybeUninit;
+use std::ops::Bound;
+use std::ops::RangeBounds;
 use std::ptr;
 use std::ptr::NonNull;
 use std::slice;
@@ -33,9 +39,15 @@ use allocative::Visitor;
 
 use crate::sorting::insertion::insertion_sort;
 use crate::sorting::insertion::slice_swap_shift;
+use crate::sorting::stable::stable_sort;
+use crate::TryReserveError;
 
 pub(crate) mod iter;
 
+pub use crate::vec2::iter::Drain;
+pub use crate::vec2::iter::IntoIter;
+pub use crate::vec2::iter::Iter;
+
 #[derive(Eq, PartialEq, Debug)]
 struct Vec2Layout<A, B> {
     layout: Layout,
@@ -71,21 +83,70 @@ impl<A, B> Vec2Layout<A, B> {
         })
     }
 
+    /// Non-null pointer to `bbb` aligned for both `A` and `B`.
+    ///
+    /// Used when nothing is allocated: for zero capacity, or when both `A` and `B`
+    /// are zero-sized. `aaa` pointer computed from it is aligned too
+    /// because `aaa` is empty or zero-sized.
+    const fn dangling() -> NonNull<B> {
+        let align = if mem::align_of::<A>() > mem::align_of::<B>() {
+            mem::align_of::<A>()
+        } else {
+            mem::align_of::<B>()
+        };
+        unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(align)) }
+    }
+
     unsafe fn alloc(&self) -> NonNull<B> {
+        match self.try_alloc() {
+            Some(bbb_ptr) => bbb_ptr,
+            None => alloc::handle_alloc_error(self.layout),
+        }
+    }
+
+    /// Allocate, return `None` if the allocator fails.
+    unsafe fn try_alloc(&self) -> Option<NonNull<B>> {
+        if self.layout.size() == 0 {
+            // Allocating zero bytes is undefined behavior.
+            return Some(Self::dangling());
+        }
         let ptr: *mut u8 = alloc::alloc(self.layout);
+        if ptr.is_null() {
+            return None;
+        }
         let bbb_ptr: *mut B = ptr.add(self.offset_of_bbb).cast();
-        NonNull::new_unchecked(bbb_ptr)
+        Some(NonNull::new_unchecked(bbb_ptr))
     }
 
     unsafe fn dealloc(&self, bbb_ptr: NonNull<B>) {
+        if self.layout.size() == 0 {
+            return;
+        }
         let ptr: *mut u8 = bbb_ptr.as_ptr().cast::<u8>().sub(self.offset_of_bbb);
         alloc::dealloc(ptr, self.layout)
     }
 }
 
 /// Array of pairs `(A, B)`, where `A` and `B` are stored separately.
-/// This reduces memory consumption when `A` and `B` have different alignments.
-pub(crate) struct Vec2<A, B> {
+/// This reduces memory consumption when `A` and `B` have different alignments,
+/// and makes scanning one of the components cache-friendly.
+///
+/// Both components live in a single allocation, `A` elements first.
+/// The API mirrors `Vec<(A, B)>`, with [`aaa`](Vec2::aaa) and [`bbb`](Vec2::bbb)
+/// giving access to each component as a slice.
+pub struct Vec2<A, B> {
+    // All the unsafe code of this module goes through few primitives:
+    // `aaa_ptr`/`bbb_ptr` for addressing (alignment is checked in debug builds),
+    // `Vec2Layout` for allocation (zero-sized allocations are never requested
+    // from the allocator), and `drop_in_place_pair` for destruction
+    // (panicking destructors do not cause double drops or leak the buffer).
+    //
+    // Raw pointers into the buffer are always derived from `bbb_ptr`,
+    // never from references returned by `aaa` or `bbb`,
+    // so the code is sound under stacked borrows.
+    // The `miri` CI job checks it, run `cargo +nightly miri test -p starlark_map vec2`
+    // locally after changing it.
+    //
     // Layout is `[padding, A, A, ..., A, B, B, ..., B]`
     bbb_ptr: NonNull<B>,
     len: usize,
@@ -93,6 +154,8 @@ pub(crate) struct Vec2<A, B> {
     _marker: PhantomData<(A, B)>,
 }
 
+const _: () = assert!(mem::size_of::<Vec2<u64, u32>>() <= 3 * mem::size_of::<usize>());
+
 unsafe impl<A: Send, B: Send> Send for Vec2<A, B> {}
 unsafe impl<A: Sync, B: Sync> Sync for Vec2<A, B> {}
 
@@ -109,6 +172,21 @@ impl<A: Debug, B: Debug> Debug for Vec2<A, B> {
     }
 }
 
+impl<A: PartialEq, B: PartialEq> PartialEq for Vec2<A, B> {
+    fn eq(&self, other: &Self) -> bool {
+        self.aaa() == other.aaa() && self.bbb() == other.bbb()
+    }
+}
+
+impl<A: Eq, B: Eq> Eq for Vec2<A, B> {}
+
+impl<A: Hash, B: Hash> Hash for Vec2<A, B> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.aaa().hash(state);
+        self.bbb().hash(state);
+    }
+}
+
 impl<A: Clone, B: Clone> Clone for Vec2<A, B> {
     fn clone(&self) -> Vec2<A, B> {
         let mut r = Vec2::with_capacity(self.len());
@@ -120,18 +198,20 @@ impl<A: Clone, B: Clone> Clone for Vec2<A, B> {
 }
 
 impl<A, B> Vec2<A, B> {
+    /// Create an empty vector. Does not allocate.
     #[inline]
-    pub(crate) const fn new() -> Vec2<A, B> {
+    pub const fn new() -> Vec2<A, B> {
         Vec2 {
-            bbb_ptr: NonNull::dangling(),
+            bbb_ptr: Vec2Layout::<A, B>::dangling(),
             len: 0,
             cap: 0,
             _marker: PhantomData,
         }
     }
 
+    /// Create an empty vector with space for at least `cap` elements.
     #[inline]
-    pub(crate) fn with_capacity(cap: usize) -> Vec2<A, B> {
+    pub fn with_capacity(cap: usize) -> Vec2<A, B> {
         if cap == 0 {
             Vec2::new()
         } else {
@@ -145,53 +225,99 @@ impl<A, B> Vec2<A, B> {
         }
     }
 
+    /// Like [`with_capacity`](Vec2::with_capacity), but return an error instead of panicking
+    /// on capacity overflow or allocation failure.
+    pub fn try_with_capacity(cap: usize) -> Result<Vec2<A, B>, TryReserveError> {
+        if cap == 0 {
+            Ok(Vec2::new())
+        } else {
+            let layout = Vec2Layout::<A, B>::new_checked(cap)
+                .map_err(|_| TryReserveError::capacity_overflow())?;
+            let bbb_ptr = unsafe { layout.try_alloc() }.ok_or_else(TryReserveError::alloc_error)?;
+            Ok(Vec2 {
+                bbb_ptr,
+                len: 0,
+                cap,
+                _marker: PhantomData,
+            })
+        }
+    }
+
+    /// Number of elements.
     #[inline]
-    pub(crate) fn len(&self) -> usize {
+    pub fn len(&self) -> usize {
         self.len
     }
 
+    /// Number of elements the vector can hold without reallocating.
     #[inline]
-    pub(crate) fn capacity(&self) -> usize {
+    pub fn capacity(&self) -> usize {
         self.cap
     }
 
+    /// Is the vector empty?
     #[inline]
-    pub(crate) fn is_empty(&self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.len == 0
     }
 
     #[inline]
     fn aaa_ptr(&self) -> NonNull<A> {
-        unsafe { NonNull::new_unchecked(self.bbb_ptr.cast::<A>().as_ptr().sub(self.cap)) }
+        let aaa_ptr =
+            unsafe { NonNull::new_unchecked(self.bbb_ptr.cast::<A>().as_ptr().sub(self.cap)) };
+        debug_assert!(aaa_ptr.as_ptr().is_aligned());
+        aaa_ptr
     }
 
     #[inline]
     fn bbb_ptr(&self) -> NonNull<B> {
+        debug_assert!(self.bbb_ptr.as_ptr().is_aligned());
         self.bbb_ptr
     }
 
+    /// The first components of the pairs.
     #[inline]
-    pub(crate) fn aaa(&self) -> &[A] {
+    pub fn aaa(&self) -> &[A] {
         unsafe { slice::from_raw_parts(self.aaa_ptr().as_ptr(), self.len) }
     }
 
+    /// The first components of the pairs, mutable.
     #[inline]
-    pub(crate) fn aaa_mut(&mut self) -> &mut [A] {
+    pub fn aaa_mut(&mut self) -> &mut [A] {
         unsafe { slice::from_raw_parts_mut(self.aaa_ptr().as_ptr(), self.len) }
     }
 
+    /// Both components as slices.
+    #[inline]
+    pub fn as_slices(&self) -> (&[A], &[B]) {
+        (self.aaa(), self.bbb())
+    }
+
+    /// Both components as mutable slices.
+    #[inline]
+    pub fn as_mut_slices(&mut self) -> (&mut [A], &mut [B]) {
+        unsafe {
+            (
+                slice::from_raw_parts_mut(self.aaa_ptr().as_ptr(), self.len),
+                slice::from_raw_parts_mut(self.bbb_ptr().as_ptr(), self.len),
+            )
+        }
+    }
+
     #[inline]
     fn aaa_uninit(&mut self) -> &mut [MaybeUninit<A>] {
         unsafe { slice::from_raw_parts_mut(self.aaa_ptr().as_ptr() as *mut _, self.cap) }
     }
 
+    /// The second components of the pairs.
     #[inline]
-    pub(crate) fn bbb(&self) -> &[B] {
+    pub fn bbb(&self) -> &[B] {
         unsafe { slice::from_raw_parts(self.bbb_ptr().as_ptr(), self.len) }
     }
 
+    /// The second components of the pairs, mutable.
     #[inline]
-    fn bbb_mut(&mut self) -> &mut [B] {
+    pub fn bbb_mut(&mut self) -> &mut [B] {
         unsafe { slice::from_raw_parts_mut(self.bbb_ptr().as_ptr(), self.len) }
     }
 
@@ -209,32 +335,99 @@ impl<A, B> Vec2<A, B> {
         1
     };
 
-    #[allow(clippy::mem_forget)]
-    #[cold]
-    fn reserve_slow(&mut self, additional: usize) {
-        debug_assert!(self.cap - self.len < additional);
-
-        let required_cap = self.len.checked_add(additional).expect("capacity overflow");
+    /// Capacity to grow to when `additional` elements do not fit.
+    fn grow_cap(&self, additional: usize) -> Option<usize> {
+        let required_cap = self.len.checked_add(additional)?;
         let new_cap = cmp::max(required_cap, Self::MIN_NON_ZERO_CAP);
-        let new_cap = cmp::max(new_cap, self.cap * 2);
-        let new = Self::with_capacity(new_cap);
+        Some(cmp::max(new_cap, self.cap.saturating_mul(2)))
+    }
+
+    /// Move the elements to the new empty buffer.
+    #[allow(clippy::mem_forget)]
+    fn move_to(&mut self, new: Vec2<A, B>) {
+        assert!(new.len == 0);
+        assert!(new.cap >= self.len);
         unsafe {
             ptr::copy_nonoverlapping(self.aaa_ptr().as_ptr(), new.aaa_ptr().as_ptr(), self.len);
             ptr::copy_nonoverlapping(self.bbb_ptr().as_ptr(), new.bbb_ptr().as_ptr(), self.len);
             self.dealloc();
-            self.bbb_ptr = new.bbb_ptr;
-            mem::forget(new);
-            self.cap = new_cap;
         }
+        self.bbb_ptr = new.bbb_ptr;
+        self.cap = new.cap;
+        mem::forget(new);
     }
 
+    #[cold]
+    fn reserve_slow(&mut self, additional: usize) {
+        debug_assert!(self.cap - self.len < additional);
+
+        let new_cap = self.grow_cap(additional).expect("capacity overflow");
+        let new = Self::with_capacity(new_cap);
+        self.move_to(new);
+    }
+
+    /// Reserve capacity for at least `additional` more elements.
     #[inline]
-    pub(crate) fn reserve(&mut self, additional: usize) {
+    pub fn reserve(&mut self, additional: usize) {
         if self.cap - self.len < additional {
             self.reserve_slow(additional);
         }
     }
 
+    /// Like [`reserve`](Vec2::reserve), but return an error instead of panicking
+    /// on capacity overflow or allocation failure.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if self.cap - self.len >= additional {
+            return Ok(());
+        }
+        let new_cap = self
+            .grow_cap(additional)
+            .ok_or_else(TryReserveError::capacity_overflow)?;
+        let new = Self::try_with_capacity(new_cap)?;
+        self.move_to(new);
+        Ok(())
+    }
+
+    /// Reserve capacity for exactly `additional` more elements,
+    /// without the amortized growth of [`reserve`](Vec2::reserve).
+    pub fn reserve_exact(&mut self, additional: usize) {
+        if self.cap - self.len < additional {
+            let new_cap = self.len.checked_add(additional).expect("capacity overflow");
+            let new = Self::with_capacity(new_cap);
+            self.move_to(new);
+        }
+    }
+
+    /// Like [`reserve_exact`](Vec2::reserve_exact), but return an error instead of panicking
+    /// on capacity overflow or allocation failure.
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if self.cap - self.len >= additional {
+            return Ok(());
+        }
+        let new_cap = self
+            .len
+            .checked_add(additional)
+            .ok_or_else(TryReserveError::capacity_overflow)?;
+        let new = Self::try_with_capacity(new_cap)?;
+        self.move_to(new);
+        Ok(())
+    }
+
+    /// Shrink the capacity to the larger of the length and `min_capacity`.
+    /// Does nothing if the capacity is already smaller than `min_capacity`.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let new_cap = cmp::max(self.len, min_capacity);
+        if self.cap > new_cap {
+            let new = Self::with_capacity(new_cap);
+            self.move_to(new);
+        }
+    }
+
+    /// Shrink the capacity to the length.
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
     #[inline]
     unsafe fn dealloc_impl(data: NonNull<B>, cap: usize) {
         if cap != 0 {
@@ -248,13 +441,18 @@ impl<A, B> Vec2<A, B> {
         Self::dealloc_impl(self.bbb_ptr, self.cap);
     }
 
-    unsafe fn drop_in_place(&mut self) {
-        ptr::drop_in_place::<[A]>(self.aaa_mut());
-        ptr::drop_in_place::<[B]>(self.bbb_mut());
+    /// Drop the elements and set the length to zero. Capacity is retained.
+    ///
+    /// The length is reset before calling destructors,
+    /// so if a destructor panics, the remaining elements are dropped once.
+    fn drop_elements(&mut self) {
+        let len = mem::take(&mut self.len);
+        unsafe { drop_in_place_pair(self.aaa_ptr(), self.bbb_ptr(), len) }
     }
 
+    /// Append a pair to the end.
     #[inline]
-    pub(crate) fn push(&mut self, a: A, b: B) {
+    pub fn push(&mut self, a: A, b: B) {
         self.reserve(1);
         let len = self.len;
         unsafe {
@@ -264,8 +462,24 @@ impl<A, B> Vec2<A, B> {
         self.len += 1;
     }
 
+    /// Insert an element at position `index`, shifting all elements after it to the right.
+    pub fn insert(&mut self, index: usize, a: A, b: B) {
+        assert!(index <= self.len);
+        self.reserve(1);
+        unsafe {
+            let aaa = self.aaa_ptr().as_ptr().add(index);
+            let bbb = self.bbb_ptr().as_ptr().add(index);
+            ptr::copy(aaa, aaa.add(1), self.len - index);
+            ptr::copy(bbb, bbb.add(1), self.len - index);
+            ptr::write(aaa, a);
+            ptr::write(bbb, b);
+        }
+        self.len += 1;
+    }
+
+    /// Get the pair at `index`, or `None` if out of bounds.
     #[inline]
-    pub(crate) fn get(&self, index: usize) -> Option<(&A, &B)> {
+    pub fn get(&self, index: usize) -> Option<(&A, &B)> {
         if index < self.len {
             unsafe {
                 let a = self.aaa().get_unchecked(index);
@@ -277,8 +491,9 @@ impl<A, B> Vec2<A, B> {
         }
     }
 
+    /// Get the pair at `index` without bounds check.
     #[inline]
-    pub(crate) unsafe fn get_unchecked(&self, index: usize) -> (&A, &B) {
+    pub unsafe fn get_unchecked(&self, index: usize) -> (&A, &B) {
         debug_assert!(index < self.len);
         (
             self.aaa().get_unchecked(index),
@@ -286,8 +501,9 @@ impl<A, B> Vec2<A, B> {
         )
     }
 
+    /// Get the pair at `index` mutably without bounds check.
     #[inline]
-    pub(crate) unsafe fn get_unchecked_mut(&mut self, index: usize) -> (&mut A, &mut B) {
+    pub unsafe fn get_unchecked_mut(&mut self, index: usize) -> (&mut A, &mut B) {
         debug_assert!(index < self.len);
         let k_ptr = self.aaa_ptr().as_ptr();
         let v_ptr = self.bbb_ptr().as_ptr();
@@ -301,7 +517,10 @@ impl<A, B> Vec2<A, B> {
         (ptr::read(a), ptr::read(b))
     }
 
-    pub(crate) fn remove(&mut self, index: usize) -> (A, B) {
+    /// Remove and return the pair at `index`, shifting all elements after it to the left.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> (A, B) {
         assert!(index < self.len);
         unsafe {
             let (a, b) = self.read(index);
@@ -320,24 +539,183 @@ impl<A, B> Vec2<A, B> {
         }
     }
 
-    #[inline]
-    pub(crate) fn clear(&mut self) {
+    /// Remove and return the pair at `index`, replacing it with the last pair.
+    /// This does not preserve ordering, but is O(1).
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> (A, B) {
+        assert!(index < self.len);
         unsafe {
-            self.drop_in_place();
-            self.len = 0;
+            let (a, b) = self.read(index);
+            let last = self.len - 1;
+            if index != last {
+                ptr::copy_nonoverlapping(
+                    self.aaa_ptr().as_ptr().add(last),
+                    self.aaa_ptr().as_ptr().add(index),
+                    1,
+                );
+                ptr::copy_nonoverlapping(
+                    self.bbb_ptr().as_ptr().add(last),
+                    self.bbb_ptr().as_ptr().add(index),
+                    1,
+                );
+            }
+            self.len = last;
+            (a, b)
         }
     }
 
+    /// Shorten the vector to `len` elements, dropping the rest.
+    /// Does nothing if `len` is not less than the current length.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let rem = self.len - len;
+        // Reset the length first, so a panicking destructor does not cause double drop.
+        self.len = len;
+        unsafe {
+            drop_in_place_pair(
+                NonNull::new_unchecked(self.aaa_ptr().as_ptr().add(len)),
+                NonNull::new_unchecked(self.bbb_ptr().as_ptr().add(len)),
+                rem,
+            );
+        }
+    }
+
+    /// Split the vector in two at `at`. Returns the pairs `[at, len)`,
+    /// and leaves `[0, at)` in `self`. The capacity of `self` is retained.
+    ///
+    /// Panics if `at > len`.
+    pub fn split_off(&mut self, at: usize) -> Vec2<A, B> {
+        assert!(at <= self.len);
+        let rem = self.len - at;
+        let mut other = Vec2::with_capacity(rem);
+        unsafe {
+            ptr::copy_nonoverlapping(
+                self.aaa_ptr().as_ptr().add(at),
+                other.aaa_ptr().as_ptr(),
+                rem,
+            );
+            ptr::copy_nonoverlapping(
+                self.bbb_ptr().as_ptr().add(at),
+                other.bbb_ptr().as_ptr(),
+                rem,
+            );
+        }
+        self.len = at;
+        other.len = rem;
+        other
+    }
+
+    /// Retain only the pairs for which the predicate returns `true`,
+    /// preserving the order.
+    pub fn retain(&mut self, mut f: impl FnMut(&A, &B) -> bool) {
+        /// Restores the vector if the predicate or a destructor panics:
+        /// moves the unprocessed tail over the removed pairs and sets the length.
+        struct Guard<'a, A, B> {
+            vec2: &'a mut Vec2<A, B>,
+            len: usize,
+            processed: usize,
+            deleted: usize,
+        }
+
+        impl<A, B> Drop for Guard<'_, A, B> {
+            fn drop(&mut self) {
+                let tail = self.len - self.processed;
+                if self.deleted != 0 && tail != 0 {
+                    unsafe {
+                        let aaa = self.vec2.aaa_ptr().as_ptr();
+                        let bbb = self.vec2.bbb_ptr().as_ptr();
+                        let dst = self.processed - self.deleted;
+                        ptr::copy(aaa.add(self.processed), aaa.add(dst), tail);
+                        ptr::copy(bbb.add(self.processed), bbb.add(dst), tail);
+                    }
+                }
+                self.vec2.len = self.len - self.deleted;
+            }
+        }
+
+        let len = mem::take(&mut self.len);
+        let mut guard = Guard {
+            vec2: self,
+            len,
+            processed: 0,
+            deleted: 0,
+        };
+        while guard.processed < len {
+            unsafe {
+                let a = guard.vec2.aaa_ptr().as_ptr().add(guard.processed);
+                let b = guard.vec2.bbb_ptr().as_ptr().add(guard.processed);
+                let keep = f(&*a, &*b);
+                guard.processed += 1;
+                if !keep {
+                    guard.deleted += 1;
+                    drop_in_place_pair(NonNull::new_unchecked(a), NonNull::new_unchecked(b), 1);
+                } else if guard.deleted != 0 {
+                    ptr::copy_nonoverlapping(a, a.sub(guard.deleted), 1);
+                    ptr::copy_nonoverlapping(b, b.sub(guard.deleted), 1);
+                }
+            }
+        }
+    }
+
+    /// Remove all the elements. The capacity is retained.
     #[inline]
-    pub(crate) fn pop(&mut self) -> Option<(A, B)> {
+    pub fn clear(&mut self) {
+        self.drop_elements();
+    }
+
+    /// Remove and return the last pair.
+    #[inline]
+    pub fn pop(&mut self) -> Option<(A, B)> {
         let new_len = self.len.checked_sub(1)?;
         let (a, b) = unsafe { self.read(new_len) };
         self.len = new_len;
         Some((a, b))
     }
 
+    /// Remove the elements in the range, returning them in an iterator.
+    /// The capacity is retained.
+    ///
+    /// The elements after the range are shifted once, when the iterator is dropped.
+    /// If the iterator is dropped before it is exhausted, the remaining elements
+    /// of the range are dropped. If the iterator is leaked, the elements after the range
+    /// are leaked too.
+    ///
+    /// Panics if the start of the range is greater than the end,
+    /// or the end is greater than the length.
+    #[inline]
+    pub fn drain(&mut self, range: impl RangeBounds<usize>) -> iter::Drain<'_, A, B> {
+        let start = match range.start_bound() {
+            Bound::Included(&s) => s,
+            Bound::Excluded(&s) => s.checked_add(1).expect("range start overflow"),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&e) => e.checked_add(1).expect("range end overflow"),
+            Bound::Excluded(&e) => e,
+            Bound::Unbounded => self.len,
+        };
+        assert!(start <= end, "range start {} > end {}", start, end);
+        assert!(end <= self.len, "range end {} > length {}", end, self.len);
+        let len = mem::replace(&mut self.len, start);
+        unsafe {
+            iter::Drain {
+                aaa_begin: NonNull::new_unchecked(self.aaa_ptr().as_ptr().add(start)),
+                bbb_begin: NonNull::new_unchecked(self.bbb_ptr().as_ptr().add(start)),
+                rem: end - start,
+                vec2: NonNull::from(self),
+                tail_start: end,
+                tail_len: len - end,
+                _marker: PhantomData,
+            }
+        }
+    }
+
+    /// Iterate over the pairs.
     #[inline]
-    pub(crate) fn iter(&self) -> iter::Iter<'_, A, B> {
+    pub fn iter(&self) -> iter::Iter<'_, A, B> {
         iter::Iter {
             aaa: self.aaa().iter(),
             bbb: self.bbb_ptr(),
@@ -345,20 +723,6 @@ impl<A, B> Vec2<A, B> {
         }
     }
 
-    #[allow(clippy::mem_forget)]
-    #[inline]
-    pub(crate) fn into_iter(self) -> iter::IntoIter<A, B> {
-        let iter = iter::IntoIter {
-            aaa_begin: self.aaa_ptr(),
-            bbb_begin: self.bbb_ptr(),
-            bbb_end: unsafe { NonNull::new_unchecked(self.bbb_ptr().as_ptr().add(self.len)) },
-            bbb_ptr: self.bbb_ptr,
-            cap: self.cap,
-        };
-        mem::forget(self);
-        iter
-    }
-
     pub(crate) fn sort_insertion_by<F>(&mut self, mut compare: F)
     where
         F: FnMut((&A, &B), (&A, &B)) -> Ordering,
@@ -376,7 +740,11 @@ impl<A, B> Vec2<A, B> {
         );
     }
 
-    pub(crate) fn sort_by<F>(&mut self, mut compare: F)
+    /// Sort the pairs with a comparator. The sort is stable.
+    ///
+    /// Elements are sorted in place by swapping them in both arrays,
+    /// without allocating a temporary buffer.
+    pub fn sort_by<F>(&mut self, mut compare: F)
     where
         F: FnMut((&A, &B), (&A, &B)) -> Ordering,
     {
@@ -387,26 +755,100 @@ impl<A, B> Vec2<A, B> {
             return;
         }
 
-        // TODO: sort without allocation.
-        // TODO: drain.
-        let mut entries: Vec<(A, B)> = mem::take(self).into_iter().collect();
-        entries.sort_by(|(xa, xb), (ya, yb)| compare((xa, xb), (ya, yb)));
-        for (a, b) in entries {
-            self.push(a, b);
-        }
+        stable_sort(
+            self,
+            self.len,
+            |vec2, i, j| unsafe {
+                compare(vec2.get_unchecked(i), vec2.get_unchecked(j)) == Ordering::Less
+            },
+            |vec2, i, j| {
+                vec2.aaa_mut().swap(i, j);
+                vec2.bbb_mut().swap(i, j);
+            },
+        );
     }
 }
 
 impl<A, B> Drop for Vec2<A, B> {
     #[inline]
     fn drop(&mut self) {
-        unsafe {
-            if self.cap != 0 {
-                self.drop_in_place();
-                self.dealloc();
-            }
+        // Free the buffer even if a destructor panics.
+        let _dealloc = DeallocOnDrop::<A, B> {
+            bbb_ptr: self.bbb_ptr,
+            cap: self.cap,
+            _marker: PhantomData,
+        };
+        self.drop_elements();
+    }
+}
+
+/// Deallocates [`Vec2`] buffer when dropped, without calling element destructors.
+pub(crate) struct DeallocOnDrop<A, B> {
+    pub(crate) bbb_ptr: NonNull<B>,
+    pub(crate) cap: usize,
+    pub(crate) _marker: PhantomData<(A, B)>,
+}
+
+impl<A, B> Drop for DeallocOnDrop<A, B> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { Vec2::<A, B>::dealloc_impl(self.bbb_ptr, self.cap) }
+    }
+}
+
+/// Drop `len` elements starting at `aaa` and `len` elements starting at `bbb`.
+///
+/// If a destructor panics, the remaining elements are still dropped
+/// (including all the `B` elements if an `A` destructor panics).
+pub(crate) unsafe fn drop_in_place_pair<A, B>(aaa: NonNull<A>, bbb: NonNull<B>, len: usize) {
+    struct DropBbb<B>(*mut [B]);
+
+    impl<B> Drop for DropBbb<B> {
+        #[inline]
+        fn drop(&mut self) {
+            unsafe { ptr::drop_in_place(self.0) }
         }
     }
+
+    let _bbb = DropBbb(ptr::slice_from_raw_parts_mut(bbb.as_ptr(), len));
+    ptr::drop_in_place(ptr::slice_from_raw_parts_mut(aaa.as_ptr(), len));
+}
+
+impl<A, B> IntoIterator for Vec2<A, B> {
+    type Item = (A, B);
+    type IntoIter = iter::IntoIter<A, B>;
+
+    #[allow(clippy::mem_forget)]
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        let iter = iter::IntoIter {
+            aaa_begin: self.aaa_ptr(),
+            bbb_begin: self.bbb_ptr(),
+            rem: self.len,
+            bbb_ptr: self.bbb_ptr,
+            cap: self.cap,
+        };
+        mem::forget(self);
+        iter
+    }
+}
+
+impl<A, B> Extend<(A, B)> for Vec2<A, B> {
+    fn extend<T: IntoIterator<Item = (A, B)>>(&mut self, iter: T) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for (a, b) in iter {
+            self.push(a, b);
+        }
+    }
+}
+
+impl<A, B> FromIterator<(A, B)> for Vec2<A, B> {
+    fn from_iter<T: IntoIterator<Item = (A, B)>>(iter: T) -> Self {
+        let mut vec2 = Vec2::new();
+        vec2.extend(iter);
+        vec2
+    }
 }
 
 impl<'s, A, B> IntoIterator for &'s Vec2<A, B> {
@@ -445,7 +887,11 @@ impl<A: Allocative, B: Allocative> Allocative for Vec2<A, B> {
 #[cfg(test)]
 mod tests {
     use std::alloc::Layout;
+    use std::cell::Cell;
     use std::marker::PhantomData;
+    use std::panic;
+    use std::panic::AssertUnwindSafe;
+    use std::rc::Rc;
 
     use crate::vec2::Vec2;
     use crate::vec2::Vec2Layout;
@@ -491,6 +937,71 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_insert() {
+        let mut v = Vec2::new();
+        for i in 0..10 {
+            v.push(i.to_string(), i);
+        }
+        v.insert(0, "a".to_owned(), 100);
+        v.insert(5, "b".to_owned(), 101);
+        v.insert(12, "c".to_owned(), 102);
+        assert_eq!(13, v.len());
+        assert_eq!(Some((&"a".to_owned(), &100)), v.get(0));
+        assert_eq!(Some((&"3".to_owned(), &3)), v.get(4));
+        assert_eq!(Some((&"b".to_owned(), &101)), v.get(5));
+        assert_eq!(Some((&"4".to_owned(), &4)), v.get(6));
+        assert_eq!(Some((&"c".to_owned(), &102)), v.get(12));
+    }
+
+    #[test]
+    fn test_try_reserve_shrink_to_fit() {
+        let mut v = Vec2::new();
+        v.try_reserve(10).unwrap();
+        assert!(v.capacity() >= 10);
+        for i in 0..5 {
+            v.push(i.to_string(), i);
+        }
+        v.shrink_to(0);
+        assert_eq!(5, v.capacity());
+        assert_eq!(Some((&"3".to_owned(), &3)), v.get(3));
+        assert!(v.try_reserve(usize::MAX).is_err());
+        v.clear();
+        v.shrink_to(0);
+        assert_eq!(0, v.capacity());
+    }
+
+    #[test]
+    fn test_reserve_exact() {
+        let mut v = Vec2::new();
+        for i in 0..5 {
+            v.push(i.to_string(), i);
+        }
+        v.reserve_exact(3);
+        assert_eq!(8, v.capacity());
+        v.try_reserve_exact(4).unwrap();
+        assert_eq!(9, v.capacity());
+        v.try_reserve_exact(2).unwrap();
+        assert_eq!(9, v.capacity());
+        assert!(v.try_reserve_exact(usize::MAX).is_err());
+        assert_eq!(Some((&"4".to_owned(), &4)), v.get(4));
+    }
+
+    #[test]
+    fn test_shrink_to() {
+        let mut v = Vec2::with_capacity(20);
+        for i in 0..5 {
+            v.push(i.to_string(), i);
+        }
+        v.shrink_to(8);
+        assert_eq!(8, v.capacity());
+        v.shrink_to(10);
+        assert_eq!(8, v.capacity());
+        v.shrink_to(2);
+        assert_eq!(5, v.capacity());
+        assert_eq!(Some((&"4".to_owned(), &4)), v.get(4));
+    }
+
     #[test]
     fn test_into_iter() {
         let mut v = Vec2::new();
@@ -503,6 +1014,61 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_drain() {
+        let mut v = Vec2::new();
+        for i in 0..10 {
+            v.push(i.to_string(), i * 2);
+        }
+        let cap = v.capacity();
+        let mut drain = v.drain(..);
+        assert_eq!(10, drain.len());
+        assert_eq!(Some(("0".to_owned(), 0)), drain.next());
+        assert_eq!(Some(("9".to_owned(), 18)), drain.next_back());
+        assert_eq!(Some(("1".to_owned(), 2)), drain.next());
+        assert_eq!(7, drain.len());
+        // Drop the remaining elements.
+        drop(drain);
+        assert!(v.is_empty());
+        assert_eq!(cap, v.capacity());
+
+        v.push("a".to_owned(), 1);
+        assert_eq!(vec![("a".to_owned(), 1)], v.drain(..).collect::<Vec<_>>());
+        assert_eq!(0, Vec2::<String, u32>::new().drain(..).count());
+    }
+
+    #[test]
+    fn test_drain_range() {
+        let mut v = Vec2::new();
+        for i in 0..10 {
+            v.push(i.to_string(), i * 2);
+        }
+        assert_eq!(
+            vec![("3".to_owned(), 6), ("4".to_owned(), 8)],
+            v.drain(3..5).collect::<Vec<_>>()
+        );
+        assert_eq!(&[0, 2, 4, 10, 12, 14, 16, 18], v.bbb());
+
+        // Dropped before exhausted, the rest of the range is dropped too.
+        let mut drain = v.drain(1..=3);
+        assert_eq!(3, drain.len());
+        assert_eq!(Some(("1".to_owned(), 2)), drain.next());
+        drop(drain);
+        assert_eq!(&["0", "6", "7", "8", "9"], v.aaa());
+        assert_eq!(&[0, 12, 14, 16, 18], v.bbb());
+
+        assert_eq!(0, v.drain(2..2).count());
+        assert_eq!(2, v.drain(3..).count());
+        assert_eq!(1, v.drain(..1).count());
+        assert_eq!(&["6", "7"], v.aaa());
+        assert_eq!(&[12, 14], v.bbb());
+
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| drop(v.drain(1..3)))).is_err());
+        let start = v.len();
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| drop(v.drain(start..1)))).is_err());
+        assert_eq!(2, v.len());
+    }
+
     #[test]
     fn test_sort_insertion_by() {
         let mut v = Vec2::new();
@@ -516,4 +1082,258 @@ mod tests {
         assert_eq!(Some((&3, &2)), v.get(2));
         assert_eq!(Some((&3, &4)), v.get(3));
     }
+
+    #[test]
+    fn test_sort_by() {
+        let mut v = Vec2::new();
+        let mut expected = Vec::new();
+        for i in 0..1000u32 {
+            let key = i.wrapping_mul(2654435761) % 37;
+            v.push(key, i.to_string());
+            expected.push((key, i.to_string()));
+        }
+        let (cap, ptr) = (v.capacity(), v.aaa().as_ptr());
+        // Compare only `A` to test stability.
+        v.sort_by(|(xa, _), (ya, _)| xa.cmp(ya));
+        expected.sort_by_key(|(a, _)| *a);
+        // Sorted in place.
+        assert_eq!((cap, ptr), (v.capacity(), v.aaa().as_ptr()));
+        assert_eq!(expected, v.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_swap_remove() {
+        let mut v: Vec2<String, u32> = (0..5).map(|i| (i.to_string(), i)).collect();
+        assert_eq!(("1".to_owned(), 1), v.swap_remove(1));
+        assert_eq!(("3".to_owned(), 3), v.swap_remove(3));
+        assert_eq!(
+            vec![
+                ("0".to_owned(), 0),
+                ("4".to_owned(), 4),
+                ("2".to_owned(), 2)
+            ],
+            v.into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_truncate_split_off() {
+        let mut v: Vec2<String, u32> = (0..10).map(|i| (i.to_string(), i)).collect();
+        let w = v.split_off(6);
+        assert_eq!(
+            (
+                &["6", "7", "8", "9"].map(String::from)[..],
+                &[6, 7, 8, 9][..]
+            ),
+            w.as_slices()
+        );
+        v.truncate(8);
+        assert_eq!(6, v.len());
+        v.truncate(2);
+        assert_eq!(
+            (&["0", "1"].map(String::from)[..], &[0, 1][..]),
+            v.as_slices()
+        );
+        assert!(v.split_off(2).is_empty());
+        assert_eq!(2, v.split_off(0).len());
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut v: Vec2<String, u32> = (0..10).map(|i| (i.to_string(), i)).collect();
+        v.retain(|a, b| a != "3" && b % 2 == 1);
+        assert_eq!(&[1, 5, 7, 9], v.bbb());
+        v.retain(|_, _| true);
+        assert_eq!(4, v.len());
+        v.retain(|_, _| false);
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn test_retain_panic() {
+        let mut v: Vec2<String, u32> = (0..6).map(|i| (i.to_string(), i)).collect();
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            v.retain(|_, b| {
+                assert!(*b != 3);
+                *b % 2 == 0
+            })
+        }));
+        assert!(res.is_err());
+        // Processed elements are filtered, the rest are kept.
+        assert_eq!(&[0, 2, 3, 4, 5], v.bbb());
+        assert_eq!("3", v.aaa()[2]);
+    }
+
+    #[test]
+    fn test_extend_eq() {
+        let mut v = Vec2::new();
+        v.extend([(1, 'a'), (2, 'b')]);
+        v.extend(vec![(3, 'c')]);
+        let (aaa, bbb) = v.as_mut_slices();
+        aaa[0] = 10;
+        bbb[2] = 'z';
+        assert_eq!(Vec2::from_iter([(10, 'a'), (2, 'b'), (3, 'z')]), v);
+        assert_ne!(Vec2::from_iter([(10, 'a'), (2, 'b')]), v);
+        v.shrink_to_fit();
+        assert_eq!(3, v.capacity());
+    }
+
+    #[test]
+    fn test_zero_sized() {
+        let mut v = Vec2::<(), ()>::new();
+        for _ in 0..10 {
+            v.push((), ());
+        }
+        assert_eq!(10, v.len());
+        assert_eq!(10, v.iter().count());
+        assert_eq!(Some((&(), &())), v.iter().next_back());
+        assert_eq!(3, v.drain(..).take(3).count());
+        assert!(v.is_empty());
+        v.push((), ());
+        v.push((), ());
+        let mut iter = v.into_iter();
+        assert_eq!(Some(((), ())), iter.next_back());
+        assert_eq!(1, iter.len());
+        assert_eq!(1, iter.count());
+
+        // Nothing is allocated, so any capacity fits.
+        let v = Vec2::<(), ()>::with_capacity(usize::MAX);
+        assert_eq!(usize::MAX, v.capacity());
+    }
+
+    #[test]
+    fn test_zero_sized_a_or_b() {
+        let mut v = Vec2::<(), u64>::new();
+        let mut w = Vec2::<String, ()>::new();
+        for i in 0..10 {
+            v.push((), i);
+            w.push(i.to_string(), ());
+        }
+        v.remove(3);
+        w.remove(3);
+        v.insert(0, (), 100);
+        w.insert(0, "a".to_owned(), ());
+        assert_eq!(Some((&(), &4)), v.get(4));
+        assert_eq!(Some((&"4".to_owned(), &())), w.get(4));
+        assert_eq!(
+            vec![100, 0, 1, 2, 4, 5, 6, 7, 8, 9],
+            v.into_iter().map(|(_, b)| b).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(("9".to_owned(), ())), w.drain(..).next_back());
+        assert!(w.is_empty());
+    }
+
+    #[test]
+    fn test_empty_over_aligned() {
+        #[repr(align(64))]
+        #[derive(Debug, PartialEq)]
+        struct Aligned(u8);
+
+        let mut v = Vec2::<Aligned, u8>::new();
+        assert!(v.aaa().is_empty());
+        assert!(v.bbb().is_empty());
+        assert_eq!(0, v.drain(..).count());
+        v.shrink_to(0);
+        assert_eq!(None, v.into_iter().next());
+        let mut v = Vec2::<u8, Aligned>::new();
+        v.push(1, Aligned(2));
+        assert_eq!(Some((&1, &Aligned(2))), v.get(0));
+    }
+
+    #[test]
+    fn test_huge_capacity() {
+        assert!(Vec2::<u64, u32>::try_with_capacity(usize::MAX).is_err());
+        assert!(Vec2::<u64, u32>::try_with_capacity(usize::MAX / 8).is_err());
+        let mut v = Vec2::<u64, u32>::new();
+        v.push(1, 2);
+        assert!(v.try_reserve(usize::MAX).is_err());
+        assert!(v.try_reserve(isize::MAX as usize / 4).is_err());
+        assert_eq!(Some((&1, &2)), v.get(0));
+    }
+
+    /// Counts drops, optionally panicking in `drop`.
+    struct DropCounter {
+        drops: Rc<Cell<usize>>,
+        panic: bool,
+    }
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+            if self.panic {
+                panic!("test panic");
+            }
+        }
+    }
+
+    fn vec2_with_panicking_drop(drops: &Rc<Cell<usize>>) -> Vec2<DropCounter, DropCounter> {
+        let mut v = Vec2::new();
+        for i in 0..4 {
+            let counter = |panic| DropCounter {
+                drops: drops.clone(),
+                panic,
+            };
+            v.push(counter(i == 1), counter(false));
+        }
+        v
+    }
+
+    #[test]
+    fn test_panic_during_drop() {
+        let drops = Rc::new(Cell::new(0));
+        let v = vec2_with_panicking_drop(&drops);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| drop(v))).is_err());
+        assert_eq!(8, drops.get());
+    }
+
+    #[test]
+    fn test_panic_during_clear() {
+        let drops = Rc::new(Cell::new(0));
+        let mut v = vec2_with_panicking_drop(&drops);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| v.clear())).is_err());
+        assert_eq!(8, drops.get());
+        assert!(v.is_empty());
+        drop(v);
+        assert_eq!(8, drops.get());
+    }
+
+    #[test]
+    fn test_panic_during_into_iter_drop() {
+        let drops = Rc::new(Cell::new(0));
+        let mut iter = vec2_with_panicking_drop(&drops).into_iter();
+        drop(iter.next());
+        assert_eq!(2, drops.get());
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| drop(iter))).is_err());
+        assert_eq!(8, drops.get());
+    }
+
+    #[test]
+    fn test_panic_during_drain_drop() {
+        let drops = Rc::new(Cell::new(0));
+        let mut v = vec2_with_panicking_drop(&drops);
+        let mut drain = v.drain(..);
+        drop(drain.next_back());
+        assert_eq!(2, drops.get());
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| drop(drain))).is_err());
+        assert_eq!(8, drops.get());
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn test_panic_during_drain_range_drop() {
+        let drops = Rc::new(Cell::new(0));
+        let mut v = vec2_with_panicking_drop(&drops);
+        let counter = || DropCounter {
+            drops: drops.clone(),
+            panic: false,
+        };
+        v.push(counter(), counter());
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| drop(v.drain(..4)))).is_err());
+        assert_eq!(8, drops.get());
+        // The tail is moved even if a destructor panics.
+        assert_eq!(1, v.len());
+        drop(v);
+        assert_eq!(10, drops.get());
+    }
 }
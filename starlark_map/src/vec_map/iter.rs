@@ -15,6 +15,7 @@
  * limitations under the License.
  */
 
+use std::iter::FusedIterator;
 use std::slice;
 
 use dupe::Clone_;
@@ -54,6 +55,8 @@ impl<'a, K: 'a, V: 'a> ExactSizeIterator for Keys<'a, K, V> {
     }
 }
 
+impl<'a, K: 'a, V: 'a> FusedIterator for Keys<'a, K, V> {}
+
 #[derive(Clone_)]
 pub(crate) struct Values<'a, K: 'a, V: 'a> {
     pub(crate) iter: Iter<'a, K, V>,
@@ -83,6 +86,8 @@ impl<'a, K: 'a, V: 'a> ExactSizeIterator for Values<'a, K, V> {
     }
 }
 
+impl<'a, K: 'a, V: 'a> FusedIterator for Values<'a, K, V> {}
+
 pub(crate) struct ValuesMut<'a, K: 'a, V: 'a> {
     pub(crate) iter: IterMut<'a, K, V>,
 }
@@ -111,6 +116,8 @@ impl<'a, K: 'a, V: 'a> ExactSizeIterator for ValuesMut<'a, K, V> {
     }
 }
 
+impl<'a, K: 'a, V: 'a> FusedIterator for ValuesMut<'a, K, V> {}
+
 #[derive(Clone_)]
 pub(crate) struct Iter<'a, K: 'a, V: 'a> {
     pub(crate) iter: slice::Iter<'a, (K, V)>,
@@ -128,6 +135,8 @@ impl<'a, K: 'a, V: 'a> DoubleEndedIterator for Iter<'a, K, V> {
 
 impl<'a, K: 'a, V: 'a> ExactSizeIterator for Iter<'a, K, V> {}
 
+impl<'a, K: 'a, V: 'a> FusedIterator for Iter<'a, K, V> {}
+
 impl<'a, K: 'a, V: 'a> Iter<'a, K, V> {
     #[inline]
     fn map((k, v): &'a (K, V)) -> (&'a K, &'a V) {
@@ -164,6 +173,8 @@ impl<'a, K: 'a, V: 'a> ExactSizeIterator for IterHashed<'a, K, V> {
     }
 }
 
+impl<'a, K: 'a, V: 'a> FusedIterator for IterHashed<'a, K, V> {}
+
 pub(crate) struct IterMut<'a, K: 'a, V: 'a> {
     pub(crate) iter: slice::IterMut<'a, (K, V)>,
 }
@@ -203,6 +214,8 @@ impl<'a, K: 'a, V: 'a> ExactSizeIterator for IterMut<'a, K, V> {
     }
 }
 
+impl<'a, K: 'a, V: 'a> FusedIterator for IterMut<'a, K, V> {}
+
 impl<'a, K: 'a, V: 'a> Iterator for IterMutUnchecked<'a, K, V> {
     type Item = (&'a mut K, &'a mut V);
 
@@ -220,6 +233,8 @@ impl<'a, K: 'a, V: 'a> ExactSizeIterator for IterMutUnchecked<'a, K, V> {
     }
 }
 
+impl<'a, K: 'a, V: 'a> FusedIterator for IterMutUnchecked<'a, K, V> {}
+
 pub(crate) struct IntoIterHashed<K, V> {
     pub(crate) iter: vec2::iter::IntoIter<(K, V), StarlarkHashValue>,
 }
@@ -244,6 +259,8 @@ impl<K, V> ExactSizeIterator for IntoIterHashed<K, V> {
     }
 }
 
+impl<K, V> FusedIterator for IntoIterHashed<K, V> {}
+
 impl<K, V> DoubleEndedIterator for IntoIterHashed<K, V> {
     def_double_ended_iter!();
 }
@@ -275,3 +292,35 @@ impl<'a, K: 'a, V: 'a> ExactSizeIterator for IntoIter<K, V> {
         self.iter.len()
     }
 }
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+pub(crate) struct Drain<'a, K, V> {
+    pub(crate) iter: vec2::iter::Drain<'a, (K, V), StarlarkHashValue>,
+}
+
+impl<'a, K, V> Drain<'a, K, V> {
+    #[inline]
+    fn map(((k, v), _hash): ((K, V), StarlarkHashValue)) -> (K, V) {
+        (k, v)
+    }
+}
+
+impl<'a, K, V> Iterator for Drain<'a, K, V> {
+    type Item = (K, V);
+
+    def_iter!();
+}
+
+impl<'a, K, V> DoubleEndedIterator for Drain<'a, K, V> {
+    def_double_ended_iter!();
+}
+
+impl<'a, K, V> ExactSizeIterator for Drain<'a, K, V> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, K, V> FusedIterator for Drain<'a, K, V> {}
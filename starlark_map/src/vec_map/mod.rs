@@ -22,6 +22,8 @@ mod simd;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::mem;
+use std::ops::Range;
+use std::ops::RangeBounds;
 
 use allocative::Allocative;
 use gazebo::prelude::*;
@@ -31,6 +33,7 @@ use crate::hash_value::StarlarkHashValue;
 use crate::hashed::Hashed;
 pub(crate) use crate::vec2::Vec2;
 use crate::vec_map::hint::likely;
+pub(crate) use crate::vec_map::iter::Drain;
 pub(crate) use crate::vec_map::iter::IntoIter;
 pub(crate) use crate::vec_map::iter::IntoIterHashed;
 pub(crate) use crate::vec_map::iter::Iter;
@@ -41,6 +44,7 @@ pub(crate) use crate::vec_map::iter::Keys;
 pub(crate) use crate::vec_map::iter::Values;
 pub(crate) use crate::vec_map::iter::ValuesMut;
 use crate::vec_map::simd::find_hash_in_array;
+use crate::TryReserveError;
 
 /// Bucket in [`VecMap`].
 #[derive(Debug, Clone, Eq, PartialEq, Allocative)]
@@ -80,10 +84,33 @@ impl<K, V> VecMap<K, V> {
         }
     }
 
+    #[inline]
+    pub(crate) fn try_with_capacity(n: usize) -> Result<Self, TryReserveError> {
+        Ok(VecMap {
+            buckets: Vec2::try_with_capacity(n)?,
+        })
+    }
+
     pub(crate) fn reserve(&mut self, additional: usize) {
         self.buckets.reserve(additional);
     }
 
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.buckets.try_reserve(additional)
+    }
+
+    pub(crate) fn reserve_exact(&mut self, additional: usize) {
+        self.buckets.reserve_exact(additional);
+    }
+
+    pub(crate) fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.buckets.try_reserve_exact(additional)
+    }
+
+    pub(crate) fn shrink_to(&mut self, min_capacity: usize) {
+        self.buckets.shrink_to(min_capacity);
+    }
+
     #[inline]
     pub(crate) fn capacity(&self) -> usize {
         self.buckets.capacity()
@@ -139,12 +166,54 @@ impl<K, V> VecMap<K, V> {
         (Hashed::new_unchecked(*hash, key), value)
     }
 
+    /// Mutable references to the values at the given indices.
+    ///
+    /// The indices must be in bounds and distinct.
+    #[inline]
+    pub(crate) unsafe fn get_many_unchecked_mut<const N: usize>(
+        &mut self,
+        indices: [usize; N],
+    ) -> [&mut V; N] {
+        let buckets = self.buckets.aaa_mut().as_mut_ptr();
+        indices.map(|i| {
+            debug_assert!(i < self.buckets.len());
+            &mut (*buckets.add(i)).1
+        })
+    }
+
+    /// Replace the key at the index, returning the old key.
+    ///
+    /// The index must be in bounds, and the new key must have the same hash.
+    #[inline]
+    pub(crate) unsafe fn replace_key_unchecked(&mut self, index: usize, key: K) -> K {
+        debug_assert!(index < self.buckets.len());
+        mem::replace(&mut self.buckets.aaa_mut().get_unchecked_mut(index).0, key)
+    }
+
     #[inline]
     pub(crate) fn insert_hashed_unique_unchecked(&mut self, key: Hashed<K>, value: V) {
         let hash = key.hash();
         self.buckets.push((key.into_key(), value), hash);
     }
 
+    /// Insert an entry at given position. The key must not be present in the map.
+    #[inline]
+    pub(crate) fn insert_hashed_unique_unchecked_at(
+        &mut self,
+        index: usize,
+        key: Hashed<K>,
+        value: V,
+    ) {
+        let hash = key.hash();
+        self.buckets.insert(index, (key.into_key(), value), hash);
+    }
+
+    /// Hashes of the keys, in the iteration order.
+    #[inline]
+    pub(crate) fn hashes(&self) -> &[StarlarkHashValue] {
+        self.buckets.bbb()
+    }
+
     pub(crate) fn remove_hashed_entry<Q>(&mut self, key: Hashed<&Q>) -> Option<(K, V)>
     where
         Q: ?Sized + Equivalent<K>,
@@ -163,6 +232,13 @@ impl<K, V> VecMap<K, V> {
         (Hashed::new_unchecked(hash, key), value)
     }
 
+    /// Remove the entry at the index, replacing it with the last entry.
+    #[inline]
+    pub(crate) fn swap_remove(&mut self, index: usize) -> (Hashed<K>, V) {
+        let ((key, value), hash) = self.buckets.swap_remove(index);
+        (Hashed::new_unchecked(hash, key), value)
+    }
+
     #[inline]
     pub(crate) fn pop(&mut self) -> Option<(Hashed<K>, V)> {
         let ((key, value), hash) = self.buckets.pop()?;
@@ -183,6 +259,28 @@ impl<K, V> VecMap<K, V> {
         self.buckets.clear();
     }
 
+    #[inline]
+    pub(crate) fn split_off(&mut self, at: usize) -> VecMap<K, V> {
+        VecMap {
+            buckets: self.buckets.split_off(at),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn drain(&mut self) -> Drain<'_, K, V> {
+        Drain {
+            iter: self.buckets.drain(..),
+        }
+    }
+
+    /// Remove the entries in the range, shifting the entries after it once.
+    #[inline]
+    pub(crate) fn drain_range(&mut self, range: impl RangeBounds<usize>) -> VecMap<K, V> {
+        VecMap {
+            buckets: self.buckets.drain(range).collect(),
+        }
+    }
+
     #[inline]
     pub(crate) fn values(&self) -> Values<K, V> {
         Values { iter: self.iter() }
@@ -214,6 +312,21 @@ impl<K, V> VecMap<K, V> {
         }
     }
 
+    /// Iterate over the entries with indices in the range.
+    #[inline]
+    pub(crate) fn iter_range(&self, range: Range<usize>) -> Iter<'_, K, V> {
+        Iter {
+            iter: self.buckets.aaa()[range].iter(),
+        }
+    }
+
+    /// Index of the first entry whose key does not satisfy the predicate,
+    /// assuming the entries are partitioned by it.
+    #[inline]
+    pub(crate) fn partition_point(&self, mut pred: impl FnMut(&K) -> bool) -> usize {
+        self.buckets.aaa().partition_point(|(k, _)| pred(k))
+    }
+
     #[inline]
     pub(crate) fn iter_hashed(&self) -> IterHashed<K, V> {
         IterHashed {
@@ -244,6 +357,15 @@ impl<K, V> VecMap<K, V> {
         }
     }
 
+    /// Transform the values, keeping the keys and their order.
+    pub(crate) fn map_values<V2>(self, mut f: impl FnMut(V) -> V2) -> VecMap<K, V2> {
+        let mut res = VecMap::with_capacity(self.len());
+        for (k, v) in self.into_iter_hashed() {
+            res.insert_hashed_unique_unchecked(k, f(v));
+        }
+        res
+    }
+
     pub(crate) fn sort_keys(&mut self)
     where
         K: Ord,
@@ -27,6 +27,7 @@ let client: LanguageClient;
 
 interface AdditionalClientSettings {
     enable_goto_definition: boolean;
+    enable_workspace_analysis: boolean;
 }
 
 /// Get a setting at the path, or throw an error if it's not set.
@@ -41,6 +42,7 @@ function requireSetting<T>(path: string): T {
 function additionalClientSettings(): AdditionalClientSettings {
     return {
         enable_goto_definition: vscode.workspace.getConfiguration().get("starlark.enableGotoDefinition", true),
+        enable_workspace_analysis: vscode.workspace.getConfiguration().get("starlark.enableWorkspaceAnalysis", false),
     };
 }
 
@@ -99,6 +101,8 @@ export function activate(context: ExtensionContext) {
         // Register the server for Starlark documents
         documentSelector: [{ scheme: 'file', language: 'starlark' }],
         initializationOptions: additionalClientSettings(),
+        // Send the `starlark` settings with `workspace/didChangeConfiguration`.
+        synchronize: { configurationSection: 'starlark' },
     };
 
     // Create the language client and start the client.